@@ -7,6 +7,7 @@ use rustic_core::{ErrorKind, RepositoryBackends, RusticError, RusticResult, Writ
 
 use crate::{
     local::LocalBackend,
+    throttle::{parse_limit, ThrottledBackend, TokenBucket},
     util::{location_to_type_and_path, BackendLocation},
 };
 
@@ -17,7 +18,10 @@ use crate::opendal::OpenDALBackend;
 use crate::rclone::RcloneBackend;
 
 #[cfg(feature = "rest")]
-use crate::rest::RestBackend;
+use crate::rest::{header_options, RestBackend};
+
+#[cfg(feature = "sftp")]
+use crate::sftp::SftpBackend;
 
 #[cfg(feature = "clap")]
 use clap::ValueHint;
@@ -60,6 +64,48 @@ pub struct BackendOptions {
     #[cfg_attr(feature = "clap", clap(skip))]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::btreemap::append_or_ignore))]
     pub options_cold: BTreeMap<String, String>,
+
+    /// Custom HTTP headers `NAME:VALUE` which are sent with every request of the REST and rclone backends.
+    ///
+    /// Can be given multiple times. A single header can also be given as option `header`.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long = "header", global = true, value_name = "NAME:VALUE", value_parser = parse_header_arg)
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::append))]
+    pub headers: Vec<(String, String)>,
+
+    /// Limit the upload bandwidth of all repository backends to the given bytes per second (0 means unlimited).
+    ///
+    /// Can also be given as option `limit-upload`, e.g. `5MiB`.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, global = true, env = "RUSTIC_UPLOAD_LIMIT", value_name = "BYTES/s", value_parser = parse_limit_arg)
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub upload_limit: Option<u64>,
+
+    /// Limit the download bandwidth of all repository backends to the given bytes per second (0 means unlimited).
+    ///
+    /// Can also be given as option `limit-download`, e.g. `5MiB`.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, global = true, env = "RUSTIC_DOWNLOAD_LIMIT", value_name = "BYTES/s", value_parser = parse_limit_arg)
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub download_limit: Option<u64>,
+}
+
+#[cfg(feature = "clap")]
+fn parse_header_arg(s: &str) -> Result<(String, String), String> {
+    s.split_once(':')
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .ok_or_else(|| format!("header `{s}` is invalid, please use the form `name:value`"))
+}
+
+#[cfg(feature = "clap")]
+fn parse_limit_arg(s: &str) -> Result<u64, String> {
+    parse_limit(s).map_err(|err| err.to_string())
 }
 
 impl BackendOptions {
@@ -74,28 +120,75 @@ impl BackendOptions {
     /// The backends for the repository.
     pub fn to_backends(&self) -> RusticResult<RepositoryBackends> {
         let mut options = self.options.clone();
-        options.extend(self.options_cold.clone());
+        let mut options_cold = self.options_cold.clone();
+        let mut options_hot = self.options_hot.clone();
+        let mut all_options = [&mut options, &mut options_cold, &mut options_hot];
+        let upload_limit = Self::take_limit(self.upload_limit, &mut all_options, "limit-upload")?;
+        let download_limit =
+            Self::take_limit(self.download_limit, &mut all_options, "limit-download")?;
+        // the buckets are shared by hot and cold backend to limit the aggregated bandwidth
+        let upload = TokenBucket::new(upload_limit);
+        let download = TokenBucket::new(download_limit);
+
+        let mut cold_options = options.clone();
+        cold_options.extend(options_cold);
         let be = self
-            .get_backend(self.repository.as_ref(), options)?
+            .get_backend(self.repository.as_ref(), cold_options, &self.headers)?
             .ok_or_else(|| {
                 RusticError::new(
                     ErrorKind::Backend,
                     "No repository given. Please make sure, that you have set the repository.",
                 )
             })?;
-        let mut options = self.options.clone();
-        options.extend(self.options_hot.clone());
-        let be_hot = self.get_backend(self.repo_hot.as_ref(), options)?;
+        let be = ThrottledBackend::new_throttle(be, upload.clone(), download.clone());
+
+        let mut hot_options = options;
+        hot_options.extend(options_hot);
+        let be_hot = self
+            .get_backend(self.repo_hot.as_ref(), hot_options, &self.headers)?
+            .map(|be| ThrottledBackend::new_throttle(be, upload, download));
 
         Ok(RepositoryBackends::new(be, be_hot))
     }
 
+    /// Get a bandwidth limit, either from the given value or from the given option.
+    ///
+    /// The option is removed from all `options`, so that it isn't passed to any backend.
+    /// As the limit is shared by the hot and the cold backend, the first option found is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The limit given explicitly, which takes precedence.
+    /// * `options` - The options to search for the limit, in order of precedence.
+    /// * `key` - The name of the option.
+    ///
+    /// # Errors
+    ///
+    /// * If an option value is not a valid byte size.
+    ///
+    /// # Returns
+    ///
+    /// The limit in bytes per second, 0 means unlimited.
+    fn take_limit(
+        value: Option<u64>,
+        options: &mut [&mut BTreeMap<String, String>],
+        key: &str,
+    ) -> RusticResult<u64> {
+        let mut limit = value;
+        for options in options.iter_mut() {
+            let option = options.remove(key).map(|s| parse_limit(&s)).transpose()?;
+            limit = limit.or(option);
+        }
+        Ok(limit.unwrap_or_default())
+    }
+
     /// Get the backend for the given repository.
     ///
     /// # Arguments
     ///
     /// * `repo_string` - The repository string to use.
     /// * `options` - Additional options for the backend.
+    /// * `headers` - Custom HTTP headers for the backend.
     ///
     /// # Errors
     ///
@@ -110,12 +203,13 @@ impl BackendOptions {
         &self,
         repo_string: Option<&String>,
         options: BTreeMap<String, String>,
+        headers: &[(String, String)],
     ) -> RusticResult<Option<Arc<dyn WriteBackend>>> {
         repo_string
             .map(|string| {
                 let (be_type, location) = location_to_type_and_path(string)?;
                 be_type
-                    .to_backend(location.clone(), options.into())
+                    .to_backend_with_headers(location.clone(), options, headers)
                     .map_err(|err| {
                         err
                         .prepend_guidance_line("Could not load the backend `{name}` at `{location}`. Please check the given backend and try again.")
@@ -148,7 +242,7 @@ pub trait BackendChoice {
 
 /// The supported backend types.
 ///
-/// Currently supported types are "local", "rclone", "rest", "opendal"
+/// Currently supported types are "local", "rclone", "rest", "opendal", "sftp"
 ///
 /// # Notes
 ///
@@ -174,28 +268,66 @@ pub enum SupportedBackend {
     /// An openDAL backend (general)
     #[strum(serialize = "opendal", to_string = "openDAL Backend")]
     OpenDAL,
+
+    #[cfg(feature = "sftp")]
+    /// A native SFTP backend
+    #[strum(serialize = "sftp", to_string = "SFTP Backend")]
+    Sftp,
 }
 
-impl BackendChoice for SupportedBackend {
-    fn to_backend(
-        &self,
+impl SupportedBackend {
+    /// Init backend from a path, options and custom HTTP headers.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location of the backend.
+    /// * `options` - Additional options for creating the backend.
+    /// * `headers` - Custom HTTP headers as pairs of name and value.
+    ///
+    /// # Errors
+    ///
+    /// * If headers are given for a backend which doesn't support them.
+    /// * If the backend could not be created.
+    pub fn to_backend_with_headers(
+        self,
         location: BackendLocation,
-        options: Option<BTreeMap<String, String>>,
+        options: BTreeMap<String, String>,
+        headers: &[(String, String)],
     ) -> RusticResult<Arc<dyn WriteBackend>> {
-        let options = options.unwrap_or_default();
-
         Ok(match self {
-            Self::Local => Arc::new(LocalBackend::new(location, options)?),
             #[cfg(feature = "rclone")]
-            Self::Rclone => Arc::new(RcloneBackend::new(location, options)?),
+            Self::Rclone => Arc::new(RcloneBackend::new_with_headers(location, options, headers)?),
             #[cfg(feature = "rest")]
-            Self::Rest => Arc::new(RestBackend::new(location, options)?),
+            Self::Rest => Arc::new(RestBackend::new(
+                location,
+                options.into_iter().chain(header_options(headers)),
+            )?),
+            _ if !headers.is_empty() => {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Custom headers are not supported by the `{backend}`. Please only use them with the REST or rclone backend.",
+                )
+                .attach_context("backend", self.to_string()));
+            }
+            Self::Local => Arc::new(LocalBackend::new(location, options)?),
             #[cfg(feature = "opendal")]
             Self::OpenDAL => Arc::new(OpenDALBackend::new(location, options)?),
+            #[cfg(feature = "sftp")]
+            Self::Sftp => Arc::new(SftpBackend::new(location, options)?),
         })
     }
 }
 
+impl BackendChoice for SupportedBackend {
+    fn to_backend(
+        &self,
+        location: BackendLocation,
+        options: Option<BTreeMap<String, String>>,
+    ) -> RusticResult<Arc<dyn WriteBackend>> {
+        self.to_backend_with_headers(location, options.unwrap_or_default(), &[])
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -211,6 +343,8 @@ mod tests {
     #[case("rest", SupportedBackend::Rest)]
     #[cfg(feature = "opendal")]
     #[case("opendal", SupportedBackend::OpenDAL)]
+    #[cfg(feature = "sftp")]
+    #[case("sftp", SupportedBackend::Sftp)]
     fn test_try_from_is_ok(#[case] input: &str, #[case] expected: SupportedBackend) {
         assert_eq!(SupportedBackend::try_from(input).unwrap(), expected);
     }
@@ -219,4 +353,79 @@ mod tests {
     fn test_try_from_unknown_is_err() {
         assert!(SupportedBackend::try_from("unknown").is_err());
     }
+
+    #[test]
+    fn test_take_limit_removes_key_from_all_options() -> RusticResult<()> {
+        let key = "limit-upload";
+        let mut options = BTreeMap::new();
+        let mut options_cold = BTreeMap::from([(key.to_string(), "1KiB".to_string())]);
+        let mut options_hot = BTreeMap::from([(key.to_string(), "2KiB".to_string())]);
+        let mut all_options = [&mut options, &mut options_cold, &mut options_hot];
+
+        let limit = BackendOptions::take_limit(None, &mut all_options, key)?;
+        assert_eq!(limit, 1024);
+        assert!(options_cold.is_empty());
+        assert!(options_hot.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_headers_for_local_backend_is_err() {
+        let (be_type, location) = location_to_type_and_path("/tmp/repo").unwrap();
+        let headers = [("X-Auth-Token".to_string(), "abc".to_string())];
+        assert!(be_type
+            .to_backend_with_headers(location, BTreeMap::new(), &headers)
+            .is_err());
+    }
+
+    #[cfg(feature = "rest")]
+    #[test]
+    fn test_rest_backend_sends_all_headers() -> anyhow::Result<()> {
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+            thread,
+        };
+
+        use rustic_core::{FileType, ReadBackend};
+
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let server = thread::spawn(move || -> anyhow::Result<Vec<String>> {
+            let (mut stream, _) = listener.accept()?;
+            let mut lines = Vec::new();
+            let mut reader = BufReader::new(stream.try_clone()?);
+            loop {
+                let mut line = String::new();
+                _ = reader.read_line(&mut line)?;
+                let line = line.trim_end().to_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                lines.push(line);
+            }
+            stream.write_all(
+                b"HTTP/1.1 200 OK\r\nContent-Type: application/vnd.x.restic.rest.v2\r\nContent-Length: 2\r\nConnection: close\r\n\r\n[]",
+            )?;
+            Ok(lines)
+        });
+
+        let options = BackendOptions::default()
+            .repository(format!("rest:http://{addr}/"))
+            .options(BTreeMap::from([("retry".to_string(), "false".to_string())]))
+            .headers(vec![
+                ("X-Auth-Token".to_string(), "abc".to_string()),
+                ("X-Other".to_string(), "def".to_string()),
+            ]);
+        let list = options
+            .to_backends()?
+            .repository()
+            .list(FileType::Snapshot)?;
+        assert!(list.is_empty());
+
+        let lines = server.join().unwrap()?;
+        assert!(lines.contains(&"x-auth-token: abc".to_string()));
+        assert!(lines.contains(&"x-other: def".to_string()));
+        Ok(())
+    }
 }
@@ -19,6 +19,7 @@ The following backends are currently supported and can be enabled with features:
 - `OpenDALBackend` - Backend for accessing a `OpenDAL` filesystem.
 - `RcloneBackend` - Backend for accessing a Rclone filesystem.
 - `RestBackend` - Backend for accessing a REST API.
+- `SftpBackend` - Backend for accessing a SSH server using SFTP.
 
 ## Usage & Examples
 
@@ -50,11 +51,19 @@ This crate exposes a few features for controlling dependency usage:
 
 - **rest** - Enables support for the `rest` backend. *This feature is enabled by
   default*.
+
+- **parallel-list** - Lists the pack files of the `local` backend in parallel,
+  which enables the `rayon` dependency. *This feature is enabled by default*.
+
+- **sftp** - Enables support for the native `sftp` backend using `libssh2`.
+  *This feature is disabled by default*.
 */
 
 pub mod choose;
 /// Local backend for Rustic.
 pub mod local;
+/// Bandwidth limiting for backends.
+pub mod throttle;
 /// Utility functions for the backend.
 pub mod util;
 
@@ -70,6 +79,10 @@ pub mod rclone;
 #[cfg(feature = "rest")]
 pub mod rest;
 
+/// SFTP backend for Rustic.
+#[cfg(feature = "sftp")]
+pub mod sftp;
+
 #[cfg(feature = "opendal")]
 pub use crate::opendal::OpenDALBackend;
 
@@ -79,10 +92,14 @@ pub use crate::rclone::RcloneBackend;
 #[cfg(feature = "rest")]
 pub use crate::rest::RestBackend;
 
+#[cfg(feature = "sftp")]
+pub use crate::sftp::SftpBackend;
+
 // rustic_backend Public API
 pub use crate::{
     choose::{BackendOptions, SupportedBackend},
     local::LocalBackend,
+    throttle::ThrottledBackend,
 };
 
 // re-export for error handling
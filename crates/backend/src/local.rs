@@ -1,18 +1,29 @@
 use std::{
     fs::{self, File},
-    io::{Read, Seek, SeekFrom, Write},
+    io::{ErrorKind as IoErrorKind, Read, Seek, SeekFrom, Write},
     path::{Path, PathBuf},
     process::Command,
 };
+#[cfg(feature = "parallel-list")]
+use std::{
+    num::NonZeroUsize,
+    sync::{Arc, OnceLock},
+    thread::available_parallelism,
+};
 
 use aho_corasick::AhoCorasick;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use log::{debug, error, trace, warn};
+#[cfg(feature = "parallel-list")]
+use rayon::{
+    prelude::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPool, ThreadPoolBuilder,
+};
 use walkdir::WalkDir;
 
 use rustic_core::{
-    CommandInput, ErrorKind, FileType, Id, ReadBackend, RusticError, RusticResult, WriteBackend,
-    ALL_FILE_TYPES,
+    CommandInput, ErrorKind, FileType, Id, PartialChunks, ReadBackend, RusticError, RusticResult,
+    WriteBackend, ALL_FILE_TYPES, STREAMING_CHUNK_SIZE,
 };
 
 /// A local backend.
@@ -24,6 +35,12 @@ pub struct LocalBackend {
     post_create_command: Option<String>,
     /// The command to call after a file was deleted.
     post_delete_command: Option<String>,
+    /// The number of threads used for listing pack files.
+    #[cfg(feature = "parallel-list")]
+    list_concurrency: usize,
+    /// The thread pool used for listing pack files, shared by all clones of this backend.
+    #[cfg(feature = "parallel-list")]
+    list_pool: Arc<OnceLock<ThreadPool>>,
 }
 
 impl LocalBackend {
@@ -42,6 +59,8 @@ impl LocalBackend {
     ///
     /// * `post-create-command` - The command to call after a file was created.
     /// * `post-delete-command` - The command to call after a file was deleted.
+    /// * `list-concurrency` - The number of threads used for listing pack files. Defaults to the number of CPUs.
+    ///   Only available with the `parallel-list` feature.
     pub fn new(
         path: impl AsRef<str>,
         options: impl IntoIterator<Item = (String, String)>,
@@ -49,6 +68,8 @@ impl LocalBackend {
         let path = path.as_ref().into();
         let mut post_create_command = None;
         let mut post_delete_command = None;
+        #[cfg(feature = "parallel-list")]
+        let mut list_concurrency = available_parallelism().map_or(1, NonZeroUsize::get);
         for (option, value) in options {
             match option.as_str() {
                 "post-create-command" => {
@@ -57,6 +78,21 @@ impl LocalBackend {
                 "post-delete-command" => {
                     post_delete_command = Some(value);
                 }
+                #[cfg(feature = "parallel-list")]
+                "list-concurrency" => {
+                    list_concurrency = value
+                        .parse::<usize>()
+                        .map_err(|err| {
+                            RusticError::with_source(
+                                ErrorKind::InvalidInput,
+                                "Cannot parse value `{value}`, invalid value for option `{option}`.",
+                                err,
+                            )
+                            .attach_context("value", value.as_str())
+                            .attach_context("option", option.as_str())
+                        })?
+                        .max(1);
+                }
                 opt => {
                     warn!("Option {opt} is not supported! Ignoring it.");
                 }
@@ -67,9 +103,76 @@ impl LocalBackend {
             path,
             post_create_command,
             post_delete_command,
+            #[cfg(feature = "parallel-list")]
+            list_concurrency,
+            #[cfg(feature = "parallel-list")]
+            list_pool: Arc::default(),
         })
     }
 
+    /// Returns the thread pool used for listing pack files, creating it on first use.
+    ///
+    /// # Errors
+    ///
+    /// * If the thread pool could not be created.
+    #[cfg(feature = "parallel-list")]
+    fn list_pool(&self) -> RusticResult<&ThreadPool> {
+        if let Some(pool) = self.list_pool.get() {
+            return Ok(pool);
+        }
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(self.list_concurrency)
+            .build()
+            .map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Internal,
+                    "Failed to create the thread pool with `{num_threads}` threads. Please try again.",
+                    err,
+                )
+                .attach_context("num_threads", self.list_concurrency.to_string())
+            })?;
+
+        // If another thread was faster, our pool is simply dropped.
+        Ok(self.list_pool.get_or_init(|| pool))
+    }
+
+    /// Lists all pack files with their size, listing the subdirectories of `data` in parallel.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The `data` directory.
+    ///
+    /// # Errors
+    ///
+    /// * If the `data` directory exists, but could not be read.
+    /// * If the thread pool could not be created.
+    /// * If listing one of the subdirectories failed, see [`list_dir_with_size`].
+    #[cfg(feature = "parallel-list")]
+    fn list_packs_with_size(&self, path: &Path) -> RusticResult<Vec<(Id, u32)>> {
+        let dirs: Vec<_> = match fs::read_dir(path) {
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(Vec::new()),
+            entries => entries
+                .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect())
+                .map_err(|err| {
+                    RusticError::with_source(
+                        ErrorKind::Backend,
+                        "Failed to list the directory `{path}`. Please check the directory and try again.",
+                        err,
+                    )
+                    .attach_context("path", path.to_string_lossy())
+                })?,
+        };
+
+        let lists: Vec<_> = self.list_pool()?.install(|| {
+            dirs.par_iter()
+                .map(|dir| list_dir_with_size(dir))
+                .collect::<RusticResult<_>>()
+        })?;
+
+        Ok(lists.into_iter().flatten().collect())
+    }
+
     /// Base path of the given file type and id.
     ///
     /// # Arguments
@@ -189,6 +292,74 @@ impl LocalBackend {
     }
 }
 
+/// Lists all files with their size within the given directory.
+///
+/// Entries which vanish while listing are skipped; a missing directory results in an empty list.
+///
+/// # Arguments
+///
+/// * `path` - The directory to list.
+///
+/// # Errors
+///
+/// * If the directory or one of its entries could not be read.
+/// * If a file name is not a valid id.
+/// * If the metadata of a file could not be queried.
+/// * If the length of a file could not be converted to u32.
+fn list_dir_with_size(path: &Path) -> RusticResult<Vec<(Id, u32)>> {
+    let is_not_found = |err: &walkdir::Error| {
+        err.io_error().map(std::io::Error::kind) == Some(IoErrorKind::NotFound)
+    };
+
+    WalkDir::new(path)
+        .into_iter()
+        .map(|r| -> RusticResult<_> {
+            let e = match r {
+                Ok(e) => e,
+                Err(err) if is_not_found(&err) => return Ok(None),
+                Err(err) => {
+                    return Err(RusticError::with_source(
+                        ErrorKind::Backend,
+                        "Failed to list the directory `{path}`. Please check the directory and try again.",
+                        err,
+                    )
+                    .attach_context("path", path.to_string_lossy()))
+                }
+            };
+            if !e.file_type().is_file() {
+                return Ok(None);
+            }
+
+            let id = e.file_name().to_string_lossy().parse()?;
+            let metadata = match e.metadata() {
+                Ok(metadata) => metadata,
+                Err(err) if is_not_found(&err) => return Ok(None),
+                Err(err) => {
+                    return Err(RusticError::with_source(
+                        ErrorKind::Backend,
+                        "Failed to query metadata of the file `{path}`. Please check the file and try again.",
+                        err,
+                    )
+                    .attach_context("path", e.path().to_string_lossy()))
+                }
+            };
+
+            let size = metadata.len().try_into().map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Backend,
+                    "Failed to convert file length `{length}` to u32.",
+                    err,
+                )
+                .attach_context("length", metadata.len().to_string())
+                .ask_report()
+            })?;
+
+            Ok(Some((id, size)))
+        })
+        .filter_map(RusticResult::transpose)
+        .collect()
+}
+
 impl ReadBackend for LocalBackend {
     /// Returns the location of the backend.
     ///
@@ -240,9 +411,10 @@ impl ReadBackend for LocalBackend {
     ///
     /// # Errors
     ///
-    /// * If the metadata of the file could not be queried.
-    /// * If the length of the file could not be converted to u32.
-    /// * If the metadata of the file could not be queried.
+    /// * If the directory or one of its entries could not be read.
+    /// * If a file name is not a valid id.
+    /// * If the metadata of a file could not be queried.
+    /// * If the length of a file could not be converted to u32.
     fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
         trace!("listing tpe: {tpe:?}");
         let path = self.path.join(tpe.dirname());
@@ -274,53 +446,49 @@ impl ReadBackend for LocalBackend {
             });
         }
 
-        let walker = WalkDir::new(path)
-            .into_iter()
-            .inspect(|r| {
-                if let Err(err) = r {
-                    error!("Error while listing files: {err:?}");
-                }
-            })
-            .filter_map(walkdir::Result::ok)
-            .filter(|e| e.file_type().is_file())
-            .map(|e| -> RusticResult<_> {
-                Ok((
-                    e.file_name().to_string_lossy().parse()?,
-                    {
-                        let metadata = e.metadata()
-                        .map_err(|err|
-                            RusticError::with_source(
-                                ErrorKind::Backend,
-                                "Failed to query metadata of the file `{path}`. Please check the file and try again.",
-                                err
-                            )
-                            .attach_context("path", e.path().to_string_lossy())
-                        )
-                        ?;
+        #[cfg(feature = "parallel-list")]
+        if tpe == FileType::Pack {
+            return self.list_packs_with_size(&path);
+        }
 
-                        metadata
-                        .len()
-                        .try_into()
-                        .map_err(|err|
-                            RusticError::with_source(
-                                ErrorKind::Backend,
-                                "Failed to convert file length `{length}` to u32.",
-                                err
-                            )
-                            .attach_context("length", metadata.len().to_string())
-                            .ask_report()
-                        )?
-                    },
-                ))
-            })
-            .inspect(|r| {
-                if let Err(err) = r {
-                    error!("Error while listing files: {}", err.display_log());
-                }
-            })
-            .filter_map(RusticResult::ok);
+        list_dir_with_size(&path)
+    }
 
-        Ok(walker.collect())
+    /// Returns the size of the given file or `None` if the file doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    ///
+    /// # Errors
+    ///
+    /// * If the metadata of the file could not be queried.
+    /// * If the length of the file could not be converted to u32.
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        let path = self.path(tpe, id);
+        let metadata = match path.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(RusticError::with_source(
+                    ErrorKind::Backend,
+                    "Failed to query metadata of the file `{path}`. Please check the file and try again.",
+                    err,
+                )
+                .attach_context("path", path.to_string_lossy()));
+            }
+        };
+        let size = metadata.len().try_into().map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Backend,
+                "Failed to convert file length `{length}` to u32.",
+                err,
+            )
+            .attach_context("length", metadata.len().to_string())
+            .ask_report()
+        })?;
+        Ok(Some(size))
     }
 
     /// Reads full data of the given file.
@@ -350,6 +518,8 @@ impl ReadBackend for LocalBackend {
 
     /// Reads partial data of the given file.
     ///
+    /// This collects the chunks returned by [`ReadBackend::read_partial_streaming`].
+    ///
     /// # Arguments
     ///
     /// * `tpe` - The type of the file.
@@ -362,16 +532,50 @@ impl ReadBackend for LocalBackend {
     ///
     /// * If the file could not be opened.
     /// * If the file could not be sought to the given position.
-    /// * If the length of the file could not be converted to u32.
     /// * If the exact length of the file could not be read.
     fn read_partial(
         &self,
         tpe: FileType,
         id: &Id,
-        _cacheable: bool,
+        cacheable: bool,
         offset: u32,
         length: u32,
     ) -> RusticResult<Bytes> {
+        let mut data = BytesMut::with_capacity(length as usize);
+        for chunk in self.read_partial_streaming(tpe, id, cacheable, offset, length)? {
+            data.extend_from_slice(&chunk?);
+        }
+        Ok(data.freeze())
+    }
+
+    /// Reads partial data of the given file as a stream of chunks.
+    ///
+    /// The file is opened once and read sequentially in chunks of at most [`STREAMING_CHUNK_SIZE`] bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file is cacheable.
+    /// * `offset` - The offset to read from.
+    /// * `length` - The length to read.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be opened.
+    /// * If the file could not be sought to the given position.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the chunks which errors if the exact length of a chunk could not be read.
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        _cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
         trace!("reading tpe: {tpe:?}, id: {id}, offset: {offset}, length: {length}");
         let filename = self.path(tpe, id);
         let mut file = File::open(filename.clone()).map_err(|err| {
@@ -388,34 +592,35 @@ impl ReadBackend for LocalBackend {
                 "Failed to seek to the position `{offset}` in the file `{path}`. Please check the file and try again.",
                 err,
             )
-            .attach_context("path", self.path(tpe, id).to_string_lossy())
+            .attach_context("path", filename.to_string_lossy())
             .attach_context("offset", offset.to_string())
         })?;
 
-        let mut vec = vec![
-            0;
-            length.try_into().map_err(|err| {
-                RusticError::with_source(
-                    ErrorKind::Backend,
-                    "Failed to convert length `{length}` to u64.",
-                    err,
-                )
-                .attach_context("length", length.to_string())
-                .ask_report()
-            })?
-        ];
-
-        file.read_exact(&mut vec).map_err(|err| {
-            RusticError::with_source(
-                ErrorKind::Backend,
-                "Failed to read the exact length `{length}` of the file `{path}`. Please check the file and try again.",
-                err,
+        let mut remaining = length;
+        Ok(Box::new(std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            let chunk_length = remaining.min(STREAMING_CHUNK_SIZE);
+            remaining -= chunk_length;
+
+            let mut vec = vec![0; chunk_length as usize];
+            Some(
+                file.read_exact(&mut vec)
+                    .map(|()| Bytes::from(vec))
+                    .map_err(|err| {
+                        // don't try to read further after an error
+                        remaining = 0;
+                        RusticError::with_source(
+                            ErrorKind::Backend,
+                            "Failed to read the exact length `{length}` of the file `{path}`. Please check the file and try again.",
+                            err,
+                        )
+                        .attach_context("path", filename.to_string_lossy())
+                        .attach_context("length", chunk_length.to_string())
+                    }),
             )
-            .attach_context("path", self.path(tpe, id).to_string_lossy())
-            .attach_context("length", length.to_string())
-        })?;
-
-        Ok(vec.into())
+        })))
     }
 }
 
@@ -585,6 +790,10 @@ impl WriteBackend for LocalBackend {
             )
             .attach_context("path", filename.to_string_lossy())
         )?;
+        if tpe == FileType::SelfTest {
+            // the self-test directory is no part of the repository, so don't leave it behind
+            _ = fs::remove_dir(self.base_path(tpe, id));
+        }
         if let Some(command) = &self.post_delete_command {
             if let Err(err) = Self::call_command(tpe, id, &filename, command) {
                 warn!("post-delete: {}", err.display_log());
@@ -593,3 +802,87 @@ impl WriteBackend for LocalBackend {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeSet;
+
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(1)]
+    #[case(8)]
+    #[cfg(feature = "parallel-list")]
+    fn list_with_size_lists_many_packs(#[case] concurrency: usize) -> RusticResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let be = LocalBackend::new(
+            dir.path().to_string_lossy(),
+            [("list-concurrency".to_string(), concurrency.to_string())],
+        )?;
+        be.create()?;
+
+        let ids: BTreeSet<_> = (0..5000).map(|_| Id::random()).collect();
+        for id in &ids {
+            be.write_bytes(FileType::Pack, id, false, Bytes::new())?;
+        }
+
+        let listed: BTreeSet<_> = be
+            .list_with_size(FileType::Pack)?
+            .into_iter()
+            .map(|(id, size)| {
+                assert_eq!(size, 0);
+                id
+            })
+            .collect();
+        assert_eq!(listed, ids);
+        Ok(())
+    }
+
+    #[test]
+    fn list_with_size_of_missing_repo_is_empty() -> RusticResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let be = LocalBackend::new(dir.path().join("missing").to_string_lossy(), [])?;
+        assert!(be.list_with_size(FileType::Pack)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn list_with_size_propagates_errors() -> RusticResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let be = LocalBackend::new(dir.path().to_string_lossy(), [])?;
+        be.create()?;
+
+        let id = Id::random();
+        be.write_bytes(FileType::Pack, &id, false, Bytes::new())?;
+        fs::write(dir.path().join("data").join("00").join("invalid"), "").unwrap();
+        fs::write(dir.path().join("index").join("invalid"), "").unwrap();
+
+        assert!(be.list_with_size(FileType::Pack).is_err());
+        assert!(be.list_with_size(FileType::Index).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn self_test_leaves_no_files_behind() -> RusticResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let be = LocalBackend::new(dir.path().to_string_lossy(), [])?;
+        be.create()?;
+
+        let result = be.self_test()?;
+        assert!(result.remove.is_some());
+        assert!(!dir.path().join(FileType::SelfTest.dirname()).exists());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "parallel-list")]
+    fn invalid_list_concurrency_is_err() {
+        assert!(LocalBackend::new(
+            "/tmp",
+            [("list-concurrency".to_string(), "many".to_string())]
+        )
+        .is_err());
+    }
+}
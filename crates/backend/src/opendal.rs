@@ -1,11 +1,13 @@
 /// `OpenDAL` backend for rustic.
-use std::{collections::BTreeMap, str::FromStr, sync::OnceLock};
+use std::{collections::BTreeMap, str::FromStr, sync::OnceLock, time::Duration};
 
 use bytes::Bytes;
 use bytesize::ByteSize;
 use log::{error, trace};
 use opendal::{
-    layers::{BlockingLayer, ConcurrentLimitLayer, LoggingLayer, RetryLayer, ThrottleLayer},
+    layers::{
+        BlockingLayer, ConcurrentLimitLayer, LoggingLayer, RetryLayer, ThrottleLayer, TimeoutLayer,
+    },
     BlockingOperator, Operator, Scheme,
 };
 use rayon::prelude::{IntoParallelIterator, ParallelIterator};
@@ -87,6 +89,39 @@ impl FromStr for Throttle {
     }
 }
 
+/// Parse a timeout given as `humantime` duration
+///
+/// # Arguments
+///
+/// * `value` - The timeout to parse
+///
+/// # Errors
+///
+/// * If the value is not a valid `humantime` duration.
+/// * If the duration is zero.
+fn parse_timeout(value: &str) -> RusticResult<Duration> {
+    let timeout = *humantime::Duration::from_str(value).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InvalidInput,
+            "Could not parse value `{value}` as `humantime` duration. Invalid value for option `{option}`.",
+            err,
+        )
+        .attach_context("value", value)
+        .attach_context("option", "timeout")
+    })?;
+
+    if timeout.is_zero() {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Invalid value `{value}` for option `{option}`, the timeout must be greater than zero.",
+        )
+        .attach_context("value", value)
+        .attach_context("option", "timeout"));
+    }
+
+    Ok(timeout)
+}
+
 impl OpenDALBackend {
     /// Create a new openDAL backend.
     ///
@@ -95,9 +130,23 @@ impl OpenDALBackend {
     /// * `path` - The path to the `OpenDAL` backend.
     /// * `options` - Additional options for the `OpenDAL` backend.
     ///
+    /// # Options
+    ///
+    /// Besides the options of the chosen `OpenDAL` service, the following options are supported by all services:
+    ///
+    /// * `retry` - The number of retries, `false`/`off` to disable or `default`.
+    /// * `timeout` - The timeout for each operation and each IO step as `humantime` duration, e.g. `30s`.
+    ///   If not given, operations don't time out.
+    /// * `connections` - The maximum number of concurrent connections.
+    /// * `throttle` - Limit bandwidth and burst, e.g. `10kiB,10MB`.
+    ///
+    /// Timed out operations are retried like other temporary errors. Retries happen within each single
+    /// backend call and compose with any retries done by the callers of the backend.
+    ///
     /// # Errors
     ///
     /// * If the path is not a valid `OpenDAL` path.
+    /// * If an option has an invalid value.
     ///
     /// # Returns
     ///
@@ -115,6 +164,10 @@ impl OpenDALBackend {
                 .attach_context("value", value.to_string())
             })?,
         };
+        let timeout = options
+            .get("timeout")
+            .map(|value| parse_timeout(value))
+            .transpose()?;
         let connections = options
             .get("connections")
             .map(|c| {
@@ -151,8 +204,18 @@ impl OpenDALBackend {
                 )
                 .attach_context("path", path.as_ref().to_string())
                 .attach_context("schema", schema.to_string())
-            })?
-            .layer(RetryLayer::new().with_max_times(max_retries).with_jitter());
+            })?;
+
+        // add the timeout before the retry layer such that timed out operations are retried
+        if let Some(timeout) = timeout {
+            operator = operator.layer(
+                TimeoutLayer::new()
+                    .with_timeout(timeout)
+                    .with_io_timeout(timeout),
+            );
+        }
+
+        operator = operator.layer(RetryLayer::new().with_max_times(max_retries).with_jitter());
 
         if let Some(Throttle { bandwidth, burst }) = throttle {
             operator = operator.layer(ThrottleLayer::new(bandwidth, burst));
@@ -513,6 +576,23 @@ mod tests {
         assert!(Throttle::from_str(input).is_err());
     }
 
+    #[rstest]
+    #[case("30s", Duration::from_secs(30))]
+    #[case("1m 30s", Duration::from_secs(90))]
+    #[case("500ms", Duration::from_millis(500))]
+    fn correct_timeout(#[case] input: &str, #[case] expected: Duration) {
+        assert_eq!(parse_timeout(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("30")]
+    #[case("0s")]
+    #[case("thirty seconds")]
+    fn invalid_timeout(#[case] input: &str) {
+        assert!(parse_timeout(input).is_err());
+    }
+
     #[rstest]
     fn new_opendal_backend(
         #[files("tests/fixtures/opendal/*.toml")] test_case: PathBuf,
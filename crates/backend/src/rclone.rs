@@ -14,10 +14,11 @@ use rand::{
 };
 use semver::{BuildMetadata, Prerelease, Version, VersionReq};
 
-use crate::rest::RestBackend;
+use crate::rest::{header_options, RestBackend};
 
 use rustic_core::{
-    CommandInput, ErrorKind, FileType, Id, ReadBackend, RusticError, RusticResult, WriteBackend,
+    CommandInput, ErrorKind, FileType, Id, PartialChunks, ReadBackend, RusticError, RusticResult,
+    SelfTestResult, WriteBackend,
 };
 
 pub(super) mod constants {
@@ -142,9 +143,35 @@ impl RcloneBackend {
     /// # Panics
     ///
     /// * If the rclone command is not found.
+    pub fn new(url: impl AsRef<str>, options: BTreeMap<String, String>) -> RusticResult<Self> {
+        Self::new_with_headers(url, options, &[])
+    }
+
+    /// Create a rclone backend which sends the given custom HTTP headers with every request.
+    ///
+    /// See [`RcloneBackend::new`] for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to use.
+    /// * `options` - Additional options.
+    /// * `headers` - Custom HTTP headers as pairs of name and value.
+    ///
+    /// # Errors
+    ///
+    /// * If a header is invalid or set by rustic itself.
+    /// * See [`RcloneBackend::new`] for further errors.
+    ///
+    /// # Panics
+    ///
+    /// * If the rclone command is not found.
     // TODO: This should be an error, not a panic.
     #[allow(clippy::too_many_lines)]
-    pub fn new(url: impl AsRef<str>, options: BTreeMap<String, String>) -> RusticResult<Self> {
+    pub fn new_with_headers(
+        url: impl AsRef<str>,
+        options: BTreeMap<String, String>,
+        headers: &[(String, String)],
+    ) -> RusticResult<Self> {
         let rclone_command = options.get("rclone-command");
         let use_password = options
             .get("use-password")
@@ -278,7 +305,7 @@ impl RcloneBackend {
         }
 
         debug!("using REST backend with url {}.", url.as_ref());
-        let rest = RestBackend::new(rest_url, options)?;
+        let rest = RestBackend::new(rest_url, options.into_iter().chain(header_options(headers)))?;
 
         let handle = Some(std::thread::spawn(move || loop {
             let mut line = String::new();
@@ -353,6 +380,31 @@ impl ReadBackend for RcloneBackend {
     ) -> RusticResult<Bytes> {
         self.rest.read_partial(tpe, id, cacheable, offset, length)
     }
+
+    /// Reads partial data of the given file as a stream of chunks.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the data should be cached.
+    /// * `offset` - The offset to read from.
+    /// * `length` - The length to read.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the chunks read.
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.rest
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
 }
 
 impl WriteBackend for RcloneBackend {
@@ -383,6 +435,11 @@ impl WriteBackend for RcloneBackend {
     fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()> {
         self.rest.remove(tpe, id, cacheable)
     }
+
+    /// Runs the self-test of the underlying REST backend.
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        self.rest.self_test()
+    }
 }
 
 #[cfg(test)]
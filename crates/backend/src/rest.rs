@@ -6,7 +6,10 @@ use bytes::Bytes;
 use log::{trace, warn};
 use reqwest::{
     blocking::{Client, ClientBuilder},
-    header::{HeaderMap, HeaderValue},
+    header::{
+        HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, HOST, RANGE,
+        USER_AGENT,
+    },
     Url,
 };
 use serde::Deserialize;
@@ -28,6 +31,78 @@ pub(super) mod constants {
     pub(super) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(600);
 }
 
+/// Headers which are set by rustic itself and therefore can't be given as custom header
+const RESERVED_HEADERS: [HeaderName; 6] = [
+    ACCEPT,
+    CONTENT_LENGTH,
+    CONTENT_TYPE,
+    HOST,
+    RANGE,
+    USER_AGENT,
+];
+
+/// Parses a custom header given as `name:value`.
+///
+/// # Arguments
+///
+/// * `header` - The header to parse.
+///
+/// # Errors
+///
+/// * If the header is not of the form `name:value`.
+/// * If the name or the value is not a valid HTTP header name or value.
+/// * If the header is set by rustic itself.
+fn parse_header(header: &str) -> RusticResult<(HeaderName, HeaderValue)> {
+    let (name, value) = header.split_once(':').ok_or_else(|| {
+        RusticError::new(
+            ErrorKind::InvalidInput,
+            "Header `{header}` is invalid, please use the form `name:value`.",
+        )
+        .attach_context("header", header)
+    })?;
+    let (name, value) = (name.trim(), value.trim());
+
+    let name = HeaderName::from_str(name).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InvalidInput,
+            "`{name}` is not a valid HTTP header name.",
+            err,
+        )
+        .attach_context("name", name)
+    })?;
+    if RESERVED_HEADERS.contains(&name) {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Header `{name}` is set by rustic and cannot be given as custom header.",
+        )
+        .attach_context("name", name.as_str()));
+    }
+
+    let value = HeaderValue::from_str(value).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InvalidInput,
+            "Value of header `{name}` is not a valid HTTP header value.",
+            err,
+        )
+        .attach_context("name", name.as_str())
+    })?;
+
+    Ok((name, value))
+}
+
+/// Converts custom headers into `header` options understood by [`RestBackend::new`].
+///
+/// # Arguments
+///
+/// * `headers` - The headers as pairs of name and value.
+pub(crate) fn header_options(
+    headers: &[(String, String)],
+) -> impl Iterator<Item = (String, String)> + '_ {
+    headers
+        .iter()
+        .map(|(name, value)| ("header".to_string(), format!("{name}:{value}")))
+}
+
 fn construct_backoff_error(err: reqwest::Error) -> Box<RusticError> {
     RusticError::with_source(
         ErrorKind::Backend,
@@ -79,10 +154,18 @@ impl RestBackend {
     /// # Arguments
     ///
     /// * `url` - The url to create the [`RestBackend`] from.
+    /// * `options` - Additional options for the backend.
+    ///
+    /// # Options
+    ///
+    /// * `retry` - The number of retries, `false`/`off` to disable or `default`.
+    /// * `timeout` - The timeout for requests as `humantime` duration.
+    /// * `header` - A custom header `name:value` which is sent with every request. Use `BackendOptions::headers` to give multiple headers.
     ///
     /// # Errors
     ///
     /// * If the url could not be parsed.
+    /// * If an option has an invalid value.
     /// * If the client could not be built.
     pub fn new(
         url: impl AsRef<str>,
@@ -104,22 +187,13 @@ impl RestBackend {
                 .attach_context("url", url)
         })?;
 
-        let mut headers = HeaderMap::new();
-        _ = headers.insert("User-Agent", HeaderValue::from_static("rustic"));
-
-        let mut client = ClientBuilder::new()
-            .default_headers(headers)
-            .timeout(constants::DEFAULT_TIMEOUT) // set default timeout to 10 minutes (we can have *large* packfiles)
-            .build()
-            .map_err(|err| {
-                RusticError::with_source(ErrorKind::Backend, "Failed to build HTTP client", err)
-            })?;
-
         // backon doesn't allow us to specify `None` for `max_delay`
         // see <https://github.com/Xuanwo/backon/pull/160>
         let mut backoff = ExponentialBuilder::default()
             .with_max_delay(Duration::MAX) // no maximum elapsed time; we count number of retries
             .with_max_times(constants::DEFAULT_RETRY);
+        let mut timeout = constants::DEFAULT_TIMEOUT; // default timeout is 10 minutes (we can have *large* packfiles)
+        let mut headers = HeaderMap::new();
 
         // FIXME: If we have multiple times the same option, this could lead to unexpected behavior
         for (option, value) in options {
@@ -139,7 +213,7 @@ impl RestBackend {
                 };
                 backoff = backoff.with_max_times(max_retries);
             } else if option == "timeout" {
-                let timeout = humantime::Duration::from_str(&value).map_err(|err| {
+                timeout = *humantime::Duration::from_str(&value).map_err(|err| {
                     RusticError::with_source(
                         ErrorKind::InvalidInput,
                         "Could not parse value `{value}` as `humantime` duration. Invalid value for option `{option}`.",
@@ -148,20 +222,23 @@ impl RestBackend {
                     .attach_context("value", value)
                     .attach_context("option", "timeout")
                 })?;
-
-                client = ClientBuilder::new()
-                    .timeout(*timeout)
-                    .build()
-                    .map_err(|err| {
-                        RusticError::with_source(
-                            ErrorKind::Backend,
-                            "Failed to build HTTP client",
-                            err,
-                        )
-                    })?;
+            } else if option == "header" {
+                let (name, value) = parse_header(&value)?;
+                _ = headers.append(name, value);
             }
         }
 
+        // set after the custom headers, so that they can't replace it
+        _ = headers.insert(USER_AGENT, HeaderValue::from_static("rustic"));
+
+        let client = ClientBuilder::new()
+            .default_headers(headers)
+            .timeout(timeout)
+            .build()
+            .map_err(|err| {
+                RusticError::with_source(ErrorKind::Backend, "Failed to build HTTP client", err)
+            })?;
+
         Ok(Self {
             url,
             client,
@@ -263,7 +340,7 @@ impl ReadBackend for RestBackend {
             let list = self
                 .client
                 .get(url.clone())
-                .header("Accept", "application/vnd.x.restic.rest.v2")
+                .header(ACCEPT, "application/vnd.x.restic.rest.v2")
                 .send()?
                 .error_for_status()?
                 .json::<Option<Vec<ListEntry>>>()? // use Option to be handle null json value
@@ -310,6 +387,8 @@ impl ReadBackend for RestBackend {
 
     /// Returns a part of the content of a file.
     ///
+    /// This is also used by [`ReadBackend::read_partial_streaming`] which issues one range request per chunk.
+    ///
     /// # Arguments
     ///
     /// * `tpe` - The type of the file.
@@ -343,7 +422,7 @@ impl ReadBackend for RestBackend {
         self.retry_notify(|| {
             self.client
                 .get(url.clone())
-                .header("Range", header_value.clone())
+                .header(RANGE, header_value.clone())
                 .send()?
                 .error_for_status()?
                 .bytes()
@@ -452,4 +531,52 @@ impl WriteBackend for RestBackend {
         })
         .map_err(construct_backoff_error)
     }
+
+    /// REST servers usually only accept the file types of the repository, so the test file is written as lock file.
+    ///
+    /// It doesn't contain a valid lock and is hence ignored when reading the locks.
+    fn self_test_file_type(&self) -> FileType {
+        FileType::Lock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("X-Auth-Token:abc", "x-auth-token", "abc")]
+    #[case("X-Auth-Token: abc ", "x-auth-token", "abc")]
+    #[case("Authorization:Bearer a:b", "authorization", "Bearer a:b")]
+    fn parse_header_passes(#[case] header: &str, #[case] name: &str, #[case] value: &str) {
+        let (n, v) = parse_header(header).unwrap();
+        assert_eq!(n.as_str(), name);
+        assert_eq!(v.to_str().unwrap(), value);
+    }
+
+    #[rstest]
+    #[case("X-Auth-Token")]
+    #[case("X Auth:abc")]
+    #[case("X-Auth-Token:a\nb")]
+    #[case("Content-Type:text/plain")]
+    #[case("user-agent:other")]
+    fn parse_header_fails(#[case] header: &str) {
+        assert!(parse_header(header).is_err());
+    }
+
+    #[test]
+    fn new_with_headers_passes() {
+        let options = [
+            ("header".to_string(), "X-Auth-Token:abc".to_string()),
+            ("timeout".to_string(), "10s".to_string()),
+        ];
+        assert!(RestBackend::new("http://localhost:8000", options).is_ok());
+    }
+
+    #[test]
+    fn new_with_invalid_header_fails() {
+        let options = [("header".to_string(), "X-Auth-Token".to_string())];
+        assert!(RestBackend::new("http://localhost:8000", options).is_err());
+    }
 }
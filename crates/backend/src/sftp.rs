@@ -0,0 +1,797 @@
+use std::{
+    fmt,
+    io::{Read, Seek, SeekFrom, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Mutex,
+    thread,
+};
+
+use bytes::Bytes;
+use log::{debug, trace, warn};
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, Session, Sftp};
+
+use rustic_core::{
+    ErrorKind, FileType, Id, ReadBackend, RusticError, RusticResult, WriteBackend, ALL_FILE_TYPES,
+};
+
+pub(super) mod constants {
+    /// Default port of the SSH server
+    pub(super) const DEFAULT_PORT: u16 = 22;
+
+    /// Default number of parallel connections used to list the pack files
+    pub(super) const DEFAULT_CONNECTIONS: usize = 5;
+
+    /// SFTP status code for a file which does not exist
+    pub(super) const SFTP_NO_SUCH_FILE: i32 = 2;
+}
+
+/// A backend which accesses a repository on a SSH server using SFTP.
+///
+/// The repository uses the same layout as restic, i.e. pack files are stored in
+/// `data/<first two hex digits of the id>/<id>`.
+pub struct SftpBackend {
+    /// The user to authenticate as.
+    user: String,
+    /// The host of the SSH server.
+    host: String,
+    /// The port of the SSH server.
+    port: u16,
+    /// The base path of the repository on the server.
+    path: String,
+    /// The private key to use. If not given, the SSH agent is used.
+    key_file: Option<PathBuf>,
+    /// The passphrase of the private key.
+    key_passphrase: Option<String>,
+    /// The known hosts file to verify the host key.
+    known_hosts_file: Option<PathBuf>,
+    /// Whether to refuse hosts which are not contained in the known hosts file.
+    strict_host_key_checking: bool,
+    /// The number of parallel connections used for listing pack files.
+    connections: usize,
+    /// Idle connections which can be reused.
+    pool: Mutex<Vec<Sftp>>,
+}
+
+impl fmt::Debug for SftpBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SftpBackend")
+            .field("user", &self.user)
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("path", &self.path)
+            .field("key_file", &self.key_file)
+            .field("known_hosts_file", &self.known_hosts_file)
+            .field("strict_host_key_checking", &self.strict_host_key_checking)
+            .field("connections", &self.connections)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Creates an error for a failed SFTP operation.
+///
+/// # Arguments
+///
+/// * `err` - The underlying error.
+/// * `operation` - The operation which failed.
+/// * `path` - The remote path the operation was called on.
+fn sftp_error(err: ssh2::Error, operation: &str, path: &Path) -> Box<RusticError> {
+    RusticError::with_source(
+        ErrorKind::Backend,
+        "SFTP operation `{operation}` failed on `{path}`. Please check the path and the permissions on the server.",
+        err,
+    )
+    .attach_context("operation", operation)
+    .attach_context("path", path.to_string_lossy())
+}
+
+/// Returns true if the error means that the file does not exist.
+fn is_not_found(err: &ssh2::Error) -> bool {
+    err.code() == ErrorCode::SFTP(constants::SFTP_NO_SUCH_FILE)
+}
+
+impl SftpBackend {
+    /// Create a new [`SftpBackend`].
+    ///
+    /// No connection is established until the backend is used.
+    ///
+    /// # Arguments
+    ///
+    /// * `location` - The location of the repository as `[user@]host:path`
+    /// * `options` - Additional options for the backend
+    ///
+    /// # Errors
+    ///
+    /// * If the location does not contain a host and a path.
+    /// * If an option value could not be parsed.
+    ///
+    /// # Options
+    ///
+    /// * `user` - The user to authenticate as, if not given in the location. Defaults to `$USER`.
+    /// * `port` - The port of the SSH server. Defaults to 22.
+    /// * `key-file` - The private key to authenticate with. If not given, the SSH agent is used.
+    /// * `key-passphrase` - The passphrase of the private key.
+    /// * `known-hosts-file` - The known hosts file. Defaults to `~/.ssh/known_hosts`.
+    /// * `strict-host-key-checking` - If `false`, unknown hosts are accepted. Defaults to `true`.
+    /// * `connections` - The number of parallel connections used for listing pack files. Defaults to 5.
+    pub fn new(
+        location: impl AsRef<str>,
+        options: impl IntoIterator<Item = (String, String)>,
+    ) -> RusticResult<Self> {
+        let location = location.as_ref();
+        let (host, path) = location
+            .split_once(':')
+            .filter(|(host, path)| !host.is_empty() && !path.is_empty())
+            .ok_or_else(|| {
+                RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "SFTP location `{location}` is invalid. Please use the form `sftp:[user@]host:path`.",
+                )
+                .attach_context("location", location)
+            })?;
+        let (mut user, host) = match host.split_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host.to_string()),
+            None => (None, host.to_string()),
+        };
+
+        let mut port = constants::DEFAULT_PORT;
+        let mut key_file = None;
+        let mut key_passphrase = None;
+        let mut known_hosts_file =
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".ssh/known_hosts"));
+        let mut strict_host_key_checking = true;
+        let mut connections = constants::DEFAULT_CONNECTIONS;
+
+        for (option, value) in options {
+            match option.as_str() {
+                "user" => {
+                    _ = user.get_or_insert(value);
+                }
+                "port" => port = parse_option(&option, &value)?,
+                "key-file" => key_file = Some(PathBuf::from(value)),
+                "key-passphrase" => key_passphrase = Some(value),
+                "known-hosts-file" => known_hosts_file = Some(PathBuf::from(value)),
+                "strict-host-key-checking" => {
+                    strict_host_key_checking = parse_option(&option, &value)?;
+                }
+                "connections" => connections = parse_option::<usize>(&option, &value)?.max(1),
+                opt => {
+                    warn!("Option {opt} is not supported! Ignoring it.");
+                }
+            }
+        }
+
+        let user = user
+            .or_else(|| std::env::var("USER").ok())
+            .ok_or_else(|| {
+                RusticError::new(
+                    ErrorKind::MissingInput,
+                    "No user given for SFTP location `{location}`. Please use `sftp:user@host:path` or set the option `user`.",
+                )
+                .attach_context("location", location)
+            })?;
+
+        Ok(Self {
+            user,
+            host,
+            port,
+            path: path.trim_end_matches('/').to_string(),
+            key_file,
+            key_passphrase,
+            known_hosts_file,
+            strict_host_key_checking,
+            connections,
+            pool: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Path of the directory containing the given file type and id.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    fn base_path(&self, tpe: FileType, id: &Id) -> PathBuf {
+        match tpe {
+            FileType::Config => PathBuf::from(&self.path),
+            FileType::Pack => PathBuf::from(format!("{}/data/{}", self.path, &id.to_hex()[0..2])),
+            _ => PathBuf::from(format!("{}/{}", self.path, tpe.dirname())),
+        }
+    }
+
+    /// Path to the given file type and id.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    fn path(&self, tpe: FileType, id: &Id) -> PathBuf {
+        match tpe {
+            FileType::Config => PathBuf::from(format!("{}/config", self.path)),
+            _ => self.base_path(tpe, id).join(id.to_hex().as_str()),
+        }
+    }
+
+    /// Open a new SFTP connection.
+    ///
+    /// # Errors
+    ///
+    /// * If the server could not be reached.
+    /// * If the host key could not be verified.
+    /// * If the authentication failed.
+    fn connect(&self) -> RusticResult<Sftp> {
+        debug!("connecting to {}@{}:{}", self.user, self.host, self.port);
+        let ssh_error = |err: ssh2::Error, operation: &str| {
+            RusticError::with_source(
+                ErrorKind::Backend,
+                "SSH `{operation}` with `{host}` failed. Please check the server and your credentials.",
+                err,
+            )
+            .attach_context("operation", operation)
+            .attach_context("host", format!("{}@{}:{}", self.user, self.host, self.port))
+        };
+
+        let tcp = TcpStream::connect((self.host.as_str(), self.port)).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Backend,
+                "Failed to connect to `{host}:{port}`. Please check the host and port.",
+                err,
+            )
+            .attach_context("host", self.host.clone())
+            .attach_context("port", self.port.to_string())
+        })?;
+
+        let mut session = Session::new().map_err(|err| ssh_error(err, "session"))?;
+        session.set_tcp_stream(tcp);
+        session
+            .handshake()
+            .map_err(|err| ssh_error(err, "handshake"))?;
+        self.check_host_key(&session)?;
+
+        match &self.key_file {
+            Some(key_file) => session.userauth_pubkey_file(
+                &self.user,
+                None,
+                key_file,
+                self.key_passphrase.as_deref(),
+            ),
+            None => session.userauth_agent(&self.user),
+        }
+        .map_err(|err| ssh_error(err, "authentication"))?;
+
+        session.sftp().map_err(|err| ssh_error(err, "sftp"))
+    }
+
+    /// Verify the host key of the server against the known hosts file.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session after the handshake.
+    ///
+    /// # Errors
+    ///
+    /// * If the host key does not match the known one.
+    /// * If the host is unknown and `strict-host-key-checking` is enabled.
+    fn check_host_key(&self, session: &Session) -> RusticResult<()> {
+        let host_error = |guidance: &'static str| {
+            RusticError::new(ErrorKind::Backend, guidance).attach_context("host", self.host.clone())
+        };
+
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| host_error("The server `{host}` did not send a host key."))?;
+
+        let mut known_hosts = session.known_hosts().map_err(|err| {
+            RusticError::with_source(ErrorKind::Backend, "Failed to initialize known hosts.", err)
+        })?;
+        if let Some(file) = &self.known_hosts_file {
+            if let Err(err) = known_hosts.read_file(file, KnownHostFileKind::OpenSSH) {
+                debug!("could not read known hosts file {file:?}: {err}");
+            }
+        }
+
+        match known_hosts.check_port(&self.host, self.port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::Mismatch => Err(host_error(
+                "The host key of `{host}` does not match the known host key. Aborting.",
+            )),
+            CheckResult::NotFound | CheckResult::Failure if !self.strict_host_key_checking => {
+                warn!("host key of {} could not be verified", self.host);
+                Ok(())
+            }
+            CheckResult::NotFound | CheckResult::Failure => Err(host_error(
+                "The host `{host}` is not contained in the known hosts file. Please connect once using `ssh` or set the option `strict-host-key-checking=false`.",
+            )),
+        }
+    }
+
+    /// Run the given operation using a pooled connection.
+    ///
+    /// The connection is returned to the pool unless the SSH session itself failed.
+    ///
+    /// # Arguments
+    ///
+    /// * `op` - The operation to run.
+    ///
+    /// # Errors
+    ///
+    /// * If no connection could be established.
+    /// * If the operation failed.
+    fn with_sftp<T>(
+        &self,
+        op: impl FnOnce(&Sftp) -> RusticResult<Result<T, ssh2::Error>>,
+    ) -> RusticResult<Result<T, ssh2::Error>> {
+        let pooled = self.pool.lock().unwrap().pop();
+        let sftp = match pooled {
+            Some(sftp) => sftp,
+            None => self.connect()?,
+        };
+        let result = op(&sftp);
+        let broken = matches!(&result, Ok(Err(err)) if matches!(err.code(), ErrorCode::Session(_)));
+        if !broken {
+            self.pool.lock().unwrap().push(sftp);
+        }
+        result
+    }
+
+    /// List all files with their size in the given directory.
+    ///
+    /// A missing directory is treated as empty.
+    ///
+    /// # Arguments
+    ///
+    /// * `sftp` - The connection to use.
+    /// * `dir` - The directory to list.
+    ///
+    /// # Errors
+    ///
+    /// * If the directory could not be read.
+    fn list_dir(sftp: &Sftp, dir: &Path) -> Result<Vec<(PathBuf, u64)>, ssh2::Error> {
+        match sftp.readdir(dir) {
+            Ok(entries) => Ok(entries
+                .into_iter()
+                .filter(|(_, stat)| stat.is_file())
+                .map(|(path, stat)| (path, stat.size.unwrap_or_default()))
+                .collect()),
+            Err(err) if is_not_found(&err) => Ok(Vec::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// List all pack files, listing the subdirectories of `data` with parallel connections.
+    ///
+    /// # Errors
+    ///
+    /// * If a directory could not be read.
+    fn list_packs(&self) -> RusticResult<Vec<(PathBuf, u64)>> {
+        let dirs: Vec<_> = (0u8..=255)
+            .map(|i| PathBuf::from(format!("{}/data/{}", self.path, hex::encode([i]))))
+            .collect();
+        let chunk_size = dirs.len().div_ceil(self.connections);
+
+        thread::scope(|scope| {
+            let handles: Vec<_> = dirs
+                .chunks(chunk_size)
+                .map(|dirs| {
+                    scope.spawn(move || -> RusticResult<Vec<_>> {
+                        let mut files = Vec::new();
+                        for dir in dirs {
+                            files.extend(
+                                self.with_sftp(|sftp| Ok(Self::list_dir(sftp, dir)))?
+                                    .map_err(|err| sftp_error(err, "readdir", dir))?,
+                            );
+                        }
+                        Ok(files)
+                    })
+                })
+                .collect();
+
+            let mut files = Vec::new();
+            for handle in handles {
+                let listed = handle.join().map_err(|_| {
+                    RusticError::new(
+                        ErrorKind::Internal,
+                        "Listing the pack directories of `{path}` panicked. This is a bug, please report it.",
+                    )
+                    .attach_context("path", self.path.as_str())
+                })?;
+                files.extend(listed?);
+            }
+            Ok(files)
+        })
+    }
+
+    /// Write the given data to a temporary file and rename it to its final path.
+    ///
+    /// # Arguments
+    ///
+    /// * `sftp` - The connection to use.
+    /// * `parent` - The directory of the file which is created if it is missing.
+    /// * `tmp_path` - The path of the temporary file.
+    /// * `path` - The final path of the file.
+    /// * `buf` - The data to write.
+    ///
+    /// # Errors
+    ///
+    /// * If the data could not be written to the temporary file.
+    /// * If an SFTP operation failed, this error is returned within `Ok`.
+    fn write_file(
+        sftp: &Sftp,
+        parent: &Path,
+        tmp_path: &Path,
+        path: &Path,
+        buf: &[u8],
+    ) -> RusticResult<Result<(), ssh2::Error>> {
+        let mut file = match sftp.create(tmp_path) {
+            Ok(file) => file,
+            Err(err) if is_not_found(&err) => {
+                // the parent directory may be missing
+                if let Err(err) = Self::create_dir(sftp, parent) {
+                    return Ok(Err(err));
+                }
+                match sftp.create(tmp_path) {
+                    Ok(file) => file,
+                    Err(err) => return Ok(Err(err)),
+                }
+            }
+            Err(err) => return Ok(Err(err)),
+        };
+        file.write_all(buf)
+            .and_then(|()| file.flush())
+            .map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Backend,
+                    "Failed to write to the file `{path}`. Please check the file and try again.",
+                    err,
+                )
+                .attach_context("path", tmp_path.to_string_lossy())
+            })?;
+        drop(file);
+
+        match sftp.rename(tmp_path, path, None) {
+            // SFTPv3 servers refuse to rename onto an existing file, so remove it and try again
+            Err(_) if sftp.stat(path).is_ok() => Ok(sftp
+                .unlink(path)
+                .and_then(|()| sftp.rename(tmp_path, path, None))),
+            result => Ok(result),
+        }
+    }
+
+    /// Create the given directory if it does not exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `sftp` - The connection to use.
+    /// * `dir` - The directory to create.
+    ///
+    /// # Errors
+    ///
+    /// * If the directory does not exist and could not be created.
+    fn create_dir(sftp: &Sftp, dir: &Path) -> Result<(), ssh2::Error> {
+        if sftp.stat(dir).is_ok() {
+            return Ok(());
+        }
+        sftp.mkdir(dir, 0o700)
+    }
+}
+
+/// Parse an option value.
+///
+/// # Arguments
+///
+/// * `option` - The name of the option.
+/// * `value` - The value to parse.
+///
+/// # Errors
+///
+/// * If the value could not be parsed.
+fn parse_option<T: FromStr>(option: &str, value: &str) -> RusticResult<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    value.parse().map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InvalidInput,
+            "Cannot parse value `{value}`, invalid value for option `{option}`.",
+            err,
+        )
+        .attach_context("value", value)
+        .attach_context("option", option)
+    })
+}
+
+impl ReadBackend for SftpBackend {
+    /// Returns the location of the backend.
+    ///
+    /// This is `sftp:user@host:path`.
+    fn location(&self) -> String {
+        format!("sftp:{}@{}:{}", self.user, self.host, self.path)
+    }
+
+    /// Lists all files with their size of the given type.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the files to list.
+    ///
+    /// # Errors
+    ///
+    /// * If a directory could not be read.
+    /// * If the length of a file could not be converted to u32.
+    fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+        trace!("listing tpe: {tpe:?}");
+        let files = match tpe {
+            FileType::Config => {
+                let path = self.path(tpe, &Id::default());
+                let size = self
+                    .with_sftp(|sftp| Ok(sftp.stat(&path)))?
+                    .map(|stat| stat.size.unwrap_or_default());
+                return match size {
+                    Ok(size) => Ok(vec![(Id::default(), size_to_u32(size)?)]),
+                    Err(err) if is_not_found(&err) => Ok(Vec::new()),
+                    Err(err) => Err(sftp_error(err, "stat", &path)),
+                };
+            }
+            FileType::Pack => self.list_packs()?,
+            _ => {
+                let dir = self.base_path(tpe, &Id::default());
+                self.with_sftp(|sftp| Ok(Self::list_dir(sftp, &dir)))?
+                    .map_err(|err| sftp_error(err, "readdir", &dir))?
+            }
+        };
+
+        files
+            .into_iter()
+            .filter_map(|(path, size)| {
+                let id = path.file_name()?.to_string_lossy().parse::<Id>().ok()?;
+                Some(size_to_u32(size).map(|size| (id, size)))
+            })
+            .collect()
+    }
+
+    /// Reads full data of the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be read.
+    fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
+        trace!("reading tpe: {tpe:?}, id: {id}");
+        let path = self.path(tpe, id);
+        self.with_sftp(|sftp| {
+            let mut file = match sftp.open(&path) {
+                Ok(file) => file,
+                Err(err) => return Ok(Err(err)),
+            };
+            let mut data = Vec::new();
+            _ = file.read_to_end(&mut data).map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Backend,
+                    "Failed to read the contents of the file `{path}`. Please check the file and try again.",
+                    err,
+                )
+                .attach_context("path", path.to_string_lossy())
+            })?;
+            Ok(Ok(data.into()))
+        })?
+        .map_err(|err| sftp_error(err, "open", &path))
+    }
+
+    /// Reads partial data of the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file is cacheable.
+    /// * `offset` - The offset to read from.
+    /// * `length` - The length to read.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be opened.
+    /// * If the exact length could not be read at the given offset.
+    fn read_partial(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        _cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<Bytes> {
+        trace!("reading tpe: {tpe:?}, id: {id}, offset: {offset}, length: {length}");
+        let path = self.path(tpe, id);
+        self.with_sftp(|sftp| {
+            let mut file = match sftp.open(&path) {
+                Ok(file) => file,
+                Err(err) => return Ok(Err(err)),
+            };
+            let mut data = vec![0; length as usize];
+            file.seek(SeekFrom::Start(offset.into()))
+                .and_then(|_| file.read_exact(&mut data))
+                .map_err(|err| {
+                    RusticError::with_source(
+                        ErrorKind::Backend,
+                        "Failed to read `{length}` bytes at offset `{offset}` of the file `{path}`. Please check the file and try again.",
+                        err,
+                    )
+                    .attach_context("path", path.to_string_lossy())
+                    .attach_context("offset", offset.to_string())
+                    .attach_context("length", length.to_string())
+                })?;
+            Ok(Ok(data.into()))
+        })?
+        .map_err(|err| sftp_error(err, "open", &path))
+    }
+}
+
+/// Converts a file size to u32.
+///
+/// # Errors
+///
+/// * If the size does not fit into u32.
+fn size_to_u32(size: u64) -> RusticResult<u32> {
+    size.try_into().map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::Backend,
+            "Failed to convert file length `{length}` to u32.",
+            err,
+        )
+        .attach_context("length", size.to_string())
+        .ask_report()
+    })
+}
+
+impl WriteBackend for SftpBackend {
+    /// Create a repository on the backend.
+    ///
+    /// # Errors
+    ///
+    /// * If a directory could not be created.
+    fn create(&self) -> RusticResult<()> {
+        trace!("creating repo at {}", self.location());
+        let mut dirs = vec![PathBuf::from(&self.path)];
+        dirs.extend(
+            ALL_FILE_TYPES
+                .iter()
+                .map(|tpe| PathBuf::from(format!("{}/{}", self.path, tpe.dirname()))),
+        );
+        dirs.extend(
+            (0u8..=255).map(|i| PathBuf::from(format!("{}/data/{}", self.path, hex::encode([i])))),
+        );
+
+        self.with_sftp(|sftp| {
+            for dir in &dirs {
+                if let Err(err) = Self::create_dir(sftp, dir) {
+                    return Ok(Err((err, dir.clone())));
+                }
+            }
+            Ok(Ok(()))
+        })?
+        .map_err(|(err, dir)| sftp_error(err, "mkdir", &dir))
+    }
+
+    /// Write the given bytes to the given file.
+    ///
+    /// The data is written to a temporary file which is renamed afterwards, so that
+    /// no partially written files remain in the repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file is cacheable.
+    /// * `buf` - The bytes to write.
+    ///
+    /// # Errors
+    ///
+    /// * If the parent directory could not be created.
+    /// * If the file could not be written or renamed.
+    fn write_bytes(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        _cacheable: bool,
+        buf: Bytes,
+    ) -> RusticResult<()> {
+        trace!("writing tpe: {:?}, id: {}", &tpe, &id);
+        let path = self.path(tpe, id);
+        let parent = self.base_path(tpe, id);
+        let mut tmp_path = path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        self.with_sftp(|sftp| {
+            let result = Self::write_file(sftp, &parent, &tmp_path, &path, &buf);
+            if !matches!(result, Ok(Ok(()))) {
+                // don't leave a partially written file behind
+                _ = sftp.unlink(&tmp_path);
+            }
+            result
+        })?
+        .map_err(|err| sftp_error(err, "write", &path))
+    }
+
+    /// Remove the given file.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file is cacheable.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be removed.
+    fn remove(&self, tpe: FileType, id: &Id, _cacheable: bool) -> RusticResult<()> {
+        trace!("removing tpe: {:?}, id: {}", &tpe, &id);
+        let path = self.path(tpe, id);
+        self.with_sftp(|sftp| Ok(sftp.unlink(&path)))?
+            .map_err(|err| sftp_error(err, "unlink", &path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("user@host:/backups", "sftp:user@host:/backups")]
+    #[case("user@host:/backups/", "sftp:user@host:/backups")]
+    #[case("user@host:backups", "sftp:user@host:backups")]
+    fn test_location_passes(#[case] location: &str, #[case] expected: &str) {
+        let be = SftpBackend::new(location, []).unwrap();
+        assert_eq!(be.location(), expected);
+    }
+
+    #[test]
+    fn test_options_passes() {
+        let options = [
+            ("user".to_string(), "other".to_string()),
+            ("port".to_string(), "2222".to_string()),
+            ("connections".to_string(), "0".to_string()),
+        ];
+        let be = SftpBackend::new("host:/backups", options).unwrap();
+        assert_eq!(be.location(), "sftp:other@host:/backups");
+        assert_eq!(be.port, 2222);
+        assert_eq!(be.connections, 1);
+
+        let options = [("port".to_string(), "ssh".to_string())];
+        assert!(SftpBackend::new("host:/backups", options).is_err());
+    }
+
+    #[rstest]
+    #[case("host")]
+    #[case(":/backups")]
+    #[case("user@host:")]
+    fn test_invalid_location_fails(#[case] location: &str) {
+        assert!(SftpBackend::new(location, []).is_err());
+    }
+
+    #[test]
+    fn test_sharded_paths_passes() {
+        let be = SftpBackend::new("user@host:/repo", []).unwrap();
+        let id: Id = "1234567890123456789012345678901234567890123456789012345678901234"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            be.path(FileType::Pack, &id),
+            PathBuf::from(format!("/repo/data/12/{}", id.to_hex().as_str()))
+        );
+        assert_eq!(
+            be.path(FileType::Snapshot, &id),
+            PathBuf::from(format!("/repo/snapshots/{}", id.to_hex().as_str()))
+        );
+        assert_eq!(
+            be.path(FileType::Config, &id),
+            PathBuf::from("/repo/config")
+        );
+    }
+}
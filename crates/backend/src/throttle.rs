@@ -0,0 +1,340 @@
+use std::{
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use bytes::Bytes;
+use bytesize::ByteSize;
+
+use rustic_core::{
+    ErrorKind, FileType, Id, PartialChunks, ReadBackend, RusticError, RusticResult, WriteBackend,
+};
+
+/// Parses a bandwidth limit like "5MiB" into bytes per second.
+///
+/// # Arguments
+///
+/// * `s` - The string to parse.
+///
+/// # Errors
+///
+/// * If the string is not a valid byte size.
+pub fn parse_limit(s: &str) -> RusticResult<u64> {
+    ByteSize::from_str(s.trim())
+        .map(|b| b.as_u64())
+        .map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InvalidInput,
+                "Parsing bandwidth limit from string `{string}` failed. Please use a value like `5MiB`.",
+                err,
+            )
+            .attach_context("string", s)
+        })
+}
+
+/// A token bucket which limits the number of bytes per second.
+///
+/// The bucket is meant to be shared by all threads, so the limit applies to the aggregated bandwidth.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// The allowed bytes per second
+    rate: u64,
+    /// The available tokens and the time they were last refilled
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    /// Creates a new [`TokenBucket`].
+    ///
+    /// # Arguments
+    ///
+    /// * `rate` - The allowed bytes per second. 0 means unlimited and returns `None`.
+    #[must_use]
+    pub fn new(rate: u64) -> Option<Arc<Self>> {
+        (rate > 0).then(|| {
+            Arc::new(Self {
+                rate,
+                state: Mutex::new((0.0, Instant::now())),
+            })
+        })
+    }
+
+    /// Takes `bytes` tokens from the bucket and blocks until they are available.
+    ///
+    /// The bucket holds at most one second worth of tokens; if there are not enough tokens,
+    /// they are borrowed from the future and the caller sleeps until the debt is paid.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The number of bytes to transfer.
+    pub fn consume(&self, bytes: usize) {
+        let wait = self.take(bytes, Instant::now());
+        if !wait.is_zero() {
+            sleep(wait);
+        }
+    }
+
+    /// Takes `bytes` tokens from the bucket at the time `now` and returns how long to wait until they are available.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The number of bytes to transfer.
+    /// * `now` - The current time.
+    #[allow(clippy::cast_precision_loss)]
+    fn take(&self, bytes: usize, now: Instant) -> Duration {
+        let rate = self.rate as f64;
+        let mut state = self.state.lock().unwrap();
+        let refill = now.saturating_duration_since(state.1).as_secs_f64() * rate;
+        state.0 = (state.0 + refill).min(rate) - bytes as f64;
+        state.1 = state.1.max(now);
+        Duration::from_secs_f64((-state.0).max(0.0) / rate)
+    }
+}
+
+/// A backend which limits the upload and download bandwidth of another backend.
+#[derive(Clone, Debug)]
+pub struct ThrottledBackend {
+    /// The backend to use.
+    be: Arc<dyn WriteBackend>,
+    /// The bucket to limit uploads.
+    upload: Option<Arc<TokenBucket>>,
+    /// The bucket to limit downloads.
+    download: Option<Arc<TokenBucket>>,
+}
+
+impl ThrottledBackend {
+    /// Wraps the given backend into a [`ThrottledBackend`], if a limit is given.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to use.
+    /// * `upload` - The bucket to limit uploads.
+    /// * `download` - The bucket to limit downloads.
+    pub fn new_throttle(
+        be: Arc<dyn WriteBackend>,
+        upload: Option<Arc<TokenBucket>>,
+        download: Option<Arc<TokenBucket>>,
+    ) -> Arc<dyn WriteBackend> {
+        if upload.is_none() && download.is_none() {
+            be
+        } else {
+            Arc::new(Self {
+                be,
+                upload,
+                download,
+            })
+        }
+    }
+
+    /// Blocks until `bytes` may be downloaded.
+    fn throttle_download(&self, bytes: usize) {
+        if let Some(bucket) = &self.download {
+            bucket.consume(bytes);
+        }
+    }
+}
+
+impl ReadBackend for ThrottledBackend {
+    fn location(&self) -> String {
+        self.be.location()
+    }
+
+    fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+        self.be.list_with_size(tpe)
+    }
+
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
+    fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
+        let data = self.be.read_full(tpe, id)?;
+        self.throttle_download(data.len());
+        Ok(data)
+    }
+
+    fn read_partial(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<Bytes> {
+        let data = self.be.read_partial(tpe, id, cacheable, offset, length)?;
+        self.throttle_download(data.len());
+        Ok(data)
+    }
+
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        let chunks = self
+            .be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)?;
+        Ok(Box::new(chunks.inspect(|chunk| {
+            if let Ok(data) = chunk {
+                self.throttle_download(data.len());
+            }
+        })))
+    }
+
+    fn needs_warm_up(&self) -> bool {
+        self.be.needs_warm_up()
+    }
+
+    fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()> {
+        self.be.warm_up(tpe, id)
+    }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
+}
+
+impl WriteBackend for ThrottledBackend {
+    fn create(&self) -> RusticResult<()> {
+        self.be.create()
+    }
+
+    fn write_bytes(&self, tpe: FileType, id: &Id, cacheable: bool, buf: Bytes) -> RusticResult<()> {
+        if let Some(bucket) = &self.upload {
+            bucket.consume(buf.len());
+        }
+        self.be.write_bytes(tpe, id, cacheable, buf)
+    }
+
+    fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()> {
+        self.be.remove(tpe, id, cacheable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct NullBackend;
+
+    impl ReadBackend for NullBackend {
+        fn location(&self) -> String {
+            "null".to_string()
+        }
+
+        fn list_with_size(&self, _tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+            Ok(Vec::new())
+        }
+
+        fn read_full(&self, _tpe: FileType, _id: &Id) -> RusticResult<Bytes> {
+            Ok(Bytes::new())
+        }
+
+        fn read_partial(
+            &self,
+            _tpe: FileType,
+            _id: &Id,
+            _cacheable: bool,
+            _offset: u32,
+            _length: u32,
+        ) -> RusticResult<Bytes> {
+            Ok(Bytes::new())
+        }
+    }
+
+    impl WriteBackend for NullBackend {
+        fn write_bytes(
+            &self,
+            _tpe: FileType,
+            _id: &Id,
+            _cacheable: bool,
+            _buf: Bytes,
+        ) -> RusticResult<()> {
+            Ok(())
+        }
+
+        fn remove(&self, _tpe: FileType, _id: &Id, _cacheable: bool) -> RusticResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parse_limit_passes() {
+        assert_eq!(parse_limit("5MiB").unwrap(), 5 * 1024 * 1024);
+        assert_eq!(parse_limit("0").unwrap(), 0);
+        assert!(parse_limit("fast").is_err());
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        assert!(TokenBucket::new(0).is_none());
+    }
+
+    #[test]
+    fn test_wait_is_computed_from_taken_tokens() {
+        const MIB: usize = 1024 * 1024;
+        let bucket = TokenBucket::new(5 * MIB as u64).unwrap();
+        let start = bucket.state.lock().unwrap().1;
+
+        // the bucket starts empty, so 10 MiB need 2 seconds
+        assert_eq!(bucket.take(10 * MIB, start), Duration::from_secs(2));
+        // after 2 seconds, the debt has been paid
+        assert_eq!(
+            bucket.take(0, start + Duration::from_secs(2)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            bucket.take(MIB, start + Duration::from_secs(2)),
+            Duration::from_millis(200)
+        );
+        // the bucket holds at most one second worth of tokens
+        assert_eq!(
+            bucket.take(0, start + Duration::from_secs(60)),
+            Duration::ZERO
+        );
+        assert_eq!(
+            bucket.take(10 * MIB, start + Duration::from_secs(60)),
+            Duration::from_secs(1)
+        );
+        // going back in time doesn't refill the bucket
+        assert_eq!(
+            bucket.take(0, start + Duration::from_secs(59)),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_write_takes_upload_tokens() {
+        const MIB: usize = 1024 * 1024;
+        let upload = TokenBucket::new(1024 * MIB as u64).unwrap();
+        let download = TokenBucket::new(1024 * MIB as u64).unwrap();
+        let be = ThrottledBackend::new_throttle(
+            Arc::new(NullBackend),
+            Some(upload.clone()),
+            Some(download.clone()),
+        );
+
+        be.write_bytes(FileType::Pack, &Id::default(), false, vec![0; MIB].into())
+            .unwrap();
+        // the bucket is refilled to at most one second worth of tokens before taking the written bytes
+        #[allow(clippy::cast_precision_loss)]
+        let max_tokens = (1024 * MIB - MIB) as f64;
+        assert!(upload.state.lock().unwrap().0 <= max_tokens);
+        assert!(download.state.lock().unwrap().0.abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn test_no_limit_returns_backend() {
+        let be: Arc<dyn WriteBackend> = Arc::new(NullBackend);
+        assert!(Arc::ptr_eq(
+            &ThrottledBackend::new_throttle(be.clone(), None, None),
+            &be
+        ));
+    }
+}
@@ -11,11 +11,15 @@ use pariter::{scope, IteratorExt};
 
 use crate::{
     archiver::{
-        file_archiver::FileArchiver, parent::Parent, tree::TreeIterator,
+        file_archiver::{FileArchiver, SkipCompression},
+        parent::Parent,
+        tree::TreeIterator,
         tree_archiver::TreeArchiver,
     },
     backend::{decrypt::DecryptFullBackend, ReadSource, ReadSourceEntry},
     blob::BlobType,
+    cancellation::CancellationToken,
+    commands::backup::{BackupEvent, BackupEventCallback},
     error::{ErrorKind, RusticError, RusticResult},
     index::{
         indexer::{Indexer, SharedIndexer},
@@ -29,6 +33,28 @@ use crate::{
 /// Tree stack empty
 pub struct TreeStackEmptyError;
 
+/// Options for creating an [`Archiver`].
+#[allow(missing_debug_implementations)]
+pub(crate) struct ArchiverOptions {
+    /// The token to cancel the backup.
+    pub(crate) cancel: CancellationToken,
+
+    /// The callback to emit backup events to.
+    pub(crate) event_sink: Option<BackupEventCallback>,
+
+    /// If set, use fixed-size chunks of this size instead of content defined chunking.
+    pub(crate) fixed_chunk_size: Option<usize>,
+
+    /// The number of files to read in parallel, `None` means number of CPUs.
+    pub(crate) read_concurrency: Option<usize>,
+
+    /// Decides which files are stored without compression.
+    pub(crate) skip_compression: SkipCompression,
+
+    /// The number of data pack files to write in parallel.
+    pub(crate) write_concurrency: usize,
+}
+
 /// The `Archiver` is responsible for archiving files and trees.
 /// It will read the file, chunk it, and write the chunks to the backend.
 ///
@@ -59,6 +85,15 @@ pub struct Archiver<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> {
 
     /// The `SnapshotFile` to write to.
     snap: SnapshotFile,
+
+    /// The token to cancel the backup.
+    cancel: CancellationToken,
+
+    /// The callback to emit backup events to.
+    event_sink: Option<BackupEventCallback>,
+
+    /// The number of files to read in parallel, `None` means number of CPUs.
+    read_concurrency: Option<usize>,
 }
 
 impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
@@ -71,6 +106,7 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
     /// * `config` - The config file.
     /// * `parent` - The parent snapshot to use.
     /// * `snap` - The `SnapshotFile` to write to.
+    /// * `opts` - The options for the archiver.
     ///
     /// # Errors
     ///
@@ -82,13 +118,37 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
         config: &ConfigFile,
         parent: Parent,
         mut snap: SnapshotFile,
+        opts: ArchiverOptions,
     ) -> RusticResult<Self> {
+        let ArchiverOptions {
+            cancel,
+            event_sink,
+            fixed_chunk_size,
+            read_concurrency,
+            skip_compression,
+            write_concurrency,
+        } = opts;
         let indexer = Indexer::new(be.clone()).into_shared();
         let mut summary = snap.summary.take().unwrap_or_default();
         summary.backup_start = Local::now();
 
-        let file_archiver = FileArchiver::new(be.clone(), index, indexer.clone(), config)?;
-        let tree_archiver = TreeArchiver::new(be.clone(), index, indexer.clone(), config, summary)?;
+        let file_archiver = FileArchiver::new(
+            be.clone(),
+            index,
+            indexer.clone(),
+            config,
+            fixed_chunk_size,
+            skip_compression,
+            write_concurrency,
+        )?;
+        let tree_archiver = TreeArchiver::new(
+            be.clone(),
+            index,
+            indexer.clone(),
+            config,
+            summary,
+            event_sink.clone(),
+        )?;
 
         Ok(Self {
             file_archiver,
@@ -98,6 +158,9 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
             be,
             index,
             snap,
+            cancel,
+            event_sink,
+            read_concurrency,
         })
     }
 
@@ -105,6 +168,9 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
     ///
     /// This will archive all files and trees in the given source.
     ///
+    /// If the backup is cancelled, no further entries are read from the source. The data already
+    /// processed is still saved to the repository, but no snapshot is written.
+    ///
     /// # Type Parameters
     ///
     /// * `R` - The type of the source.
@@ -123,6 +189,7 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
     /// * If sending the message to the raw packer fails.
     /// * If the index file could not be serialized.
     /// * If the time is not in the range of `Local::now()`.
+    /// * If the backup has been cancelled.
     pub fn archive<R>(
         mut self,
         src: &R,
@@ -137,6 +204,12 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
         <R as ReadSource>::Open: Send,
         <R as ReadSource>::Iter: Send,
     {
+        if let Some(sink) = &self.event_sink {
+            sink.call(BackupEvent::ScanStarted {
+                path: backup_path.to_path_buf(),
+            });
+        }
+
         std::thread::scope(|s| -> RusticResult<_> {
             // determine backup size in parallel to running backup
             let src_size_handle = s.spawn(|| {
@@ -149,34 +222,39 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
                 }
             });
 
-            // filter out errors and handle as_path
-            let iter = src.entries().filter_map(|item| match item {
-                Err(err) => {
-                    warn!("ignoring error: {}", err.display_log());
-                    None
-                }
-                Ok(ReadSourceEntry { path, node, open }) => {
-                    let snapshot_path = if let Some(as_path) = as_path {
-                        as_path
-                            .clone()
-                            .join(path.strip_prefix(backup_path).unwrap())
-                    } else {
-                        path
-                    };
-                    Some(if node.is_dir() {
-                        (snapshot_path, node, open)
-                    } else {
-                        (
-                            snapshot_path
-                                .parent()
-                                .expect("file path should have a parent!")
-                                .to_path_buf(),
-                            node,
-                            open,
-                        )
-                    })
-                }
-            });
+            // stop reading the source once cancelled, filter out errors and handle as_path
+            let cancel = &self.cancel;
+            let read_concurrency = self.read_concurrency;
+            let iter = src
+                .entries()
+                .take_while(|_| !cancel.is_cancelled())
+                .filter_map(|item| match item {
+                    Err(err) => {
+                        warn!("ignoring error: {}", err.display_log());
+                        None
+                    }
+                    Ok(ReadSourceEntry { path, node, open }) => {
+                        let snapshot_path = if let Some(as_path) = as_path {
+                            as_path
+                                .clone()
+                                .join(path.strip_prefix(backup_path).unwrap())
+                        } else {
+                            path
+                        };
+                        Some(if node.is_dir() {
+                            (snapshot_path, node, open)
+                        } else {
+                            (
+                                snapshot_path
+                                    .parent()
+                                    .expect("file path should have a parent!")
+                                    .to_path_buf(),
+                                node,
+                                open,
+                            )
+                        })
+                    }
+                });
             // handle beginning and ending of trees
             let iter = TreeIterator::new(iter);
 
@@ -192,7 +270,14 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
                     },
                 )
                 // archive files in parallel
-                .parallel_map_scoped(scope, |item| self.file_archiver.process(item, p))
+                .parallel_map_scoped_custom(
+                    scope,
+                    |options| match read_concurrency {
+                        Some(threads) => options.threads(threads),
+                        None => options,
+                    },
+                    |item| self.file_archiver.process(item, p),
+                )
                 .readahead_scoped(scope)
                 .filter_map(|item| match item {
                     Ok(item) => Some(item),
@@ -219,6 +304,9 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
 
         self.indexer.write().unwrap().finalize()?;
 
+        // all processed data has been saved, but don't save an incomplete snapshot
+        self.cancel.check()?;
+
         summary.finalize(self.snap.time).map_err(|err| {
             RusticError::with_source(
                 ErrorKind::Internal,
@@ -233,7 +321,156 @@ impl<'a, BE: DecryptFullBackend, I: ReadGlobalIndex> Archiver<'a, BE, I> {
             self.snap.id = id.into();
         }
 
+        if let (Some(sink), Some(summary)) = (&self.event_sink, &self.snap.summary) {
+            sink.call(BackupEvent::Finished {
+                summary: Box::new(summary.clone()),
+            });
+        }
+
         p.finish();
         Ok(self.snap)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsStr,
+        io::{Cursor, Read},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        backend::{
+            decrypt::DecryptBackend,
+            node::{Metadata, Node, NodeType},
+            MockBackend, ReadSourceOpen, WriteBackend,
+        },
+        chunker::random_poly,
+        crypto::aespoly1305::Key,
+        index::{
+            binarysorted::{IndexCollector, IndexType},
+            GlobalIndex,
+        },
+        progress::NoProgress,
+        repofile::configfile::RepositoryId,
+    };
+
+    /// Counts the readers which are open at the same time
+    #[derive(Debug, Default)]
+    struct Counter {
+        current: AtomicUsize,
+        max: AtomicUsize,
+    }
+
+    struct CountingOpen(Arc<Counter>);
+
+    struct CountingReader {
+        counter: Arc<Counter>,
+        data: Cursor<Vec<u8>>,
+    }
+
+    impl ReadSourceOpen for CountingOpen {
+        type Reader = CountingReader;
+
+        fn open(self) -> RusticResult<Self::Reader> {
+            let current = self.0.current.fetch_add(1, Ordering::SeqCst) + 1;
+            _ = self.0.max.fetch_max(current, Ordering::SeqCst);
+            Ok(CountingReader {
+                counter: self.0,
+                data: Cursor::new(vec![42; 100]),
+            })
+        }
+    }
+
+    impl Read for CountingReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            // slow reader to let parallel readers overlap
+            thread::sleep(Duration::from_millis(2));
+            self.data.read(buf)
+        }
+    }
+
+    impl Drop for CountingReader {
+        fn drop(&mut self) {
+            _ = self.counter.current.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    struct CountingSource(Arc<Counter>);
+
+    impl ReadSource for CountingSource {
+        type Open = CountingOpen;
+        type Iter = std::vec::IntoIter<RusticResult<ReadSourceEntry<CountingOpen>>>;
+
+        fn size(&self) -> RusticResult<Option<u64>> {
+            Ok(None)
+        }
+
+        fn entries(&self) -> Self::Iter {
+            (0..20)
+                .map(|i| {
+                    let meta = Metadata {
+                        size: 100,
+                        ..Default::default()
+                    };
+                    let name = format!("file{i:02}");
+                    Ok(ReadSourceEntry {
+                        path: PathBuf::from("test").join(&name),
+                        node: Node::new_node(OsStr::new(&name), NodeType::File, meta),
+                        open: Some(CountingOpen(self.0.clone())),
+                    })
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+        }
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(3)]
+    fn archiver_respects_read_concurrency(#[case] read_concurrency: usize) -> RusticResult<()> {
+        let mut mock = MockBackend::new();
+        _ = mock.expect_location().return_const("mock".to_string());
+        _ = mock
+            .expect_write_bytes()
+            .returning(|_, _, _, _: Bytes| Ok(()));
+        let be: Arc<dyn WriteBackend> = Arc::new(mock);
+        let be = DecryptBackend::new(be, Key::new());
+        let index = GlobalIndex::new_from_index(IndexCollector::new(IndexType::Full).into_index());
+        let config = ConfigFile::new(2, RepositoryId::default(), random_poly()?);
+        let parent = Parent::new(&be, &index, None, false, false, false);
+
+        let archiver = Archiver::new(
+            be,
+            &index,
+            &config,
+            parent,
+            SnapshotFile::default(),
+            ArchiverOptions {
+                cancel: CancellationToken::new(),
+                event_sink: None,
+                fixed_chunk_size: None,
+                read_concurrency: Some(read_concurrency),
+                skip_compression: SkipCompression::default(),
+                write_concurrency: 1,
+            },
+        )?;
+        let counter = Arc::new(Counter::default());
+        let src = CountingSource(counter.clone());
+        _ = archiver.archive(&src, Path::new("test"), None, false, true, &NoProgress)?;
+
+        let max = counter.max.load(Ordering::SeqCst);
+        assert!((1..=read_concurrency).contains(&max), "max readers: {max}");
+        Ok(())
+    }
+}
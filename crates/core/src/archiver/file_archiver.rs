@@ -1,5 +1,6 @@
-use std::io::Read;
+use std::{collections::BTreeSet, io::Read, path::Path};
 
+use itertools::Either;
 use rustic_cdc::Rabin64;
 
 use crate::{
@@ -9,7 +10,7 @@ use crate::{
         tree_archiver::TreeItem,
     },
     backend::{
-        decrypt::DecryptWriteBackend,
+        decrypt::{is_compressible, DecryptWriteBackend},
         node::{Node, NodeType},
         ReadSourceOpen,
     },
@@ -17,7 +18,7 @@ use crate::{
         packer::{Packer, PackerStats},
         BlobId, BlobType, DataId,
     },
-    chunker::ChunkIter,
+    chunker::{ChunkIter, ChunkSizes, FixedChunkIter},
     crypto::hasher::hash,
     error::{ErrorKind, RusticError, RusticResult},
     index::{indexer::SharedIndexer, ReadGlobalIndex},
@@ -25,6 +26,46 @@ use crate::{
     repofile::configfile::ConfigFile,
 };
 
+/// Decides which files are stored without compression.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SkipCompression {
+    /// The lower-case extensions of files which are stored without compression.
+    extensions: BTreeSet<String>,
+    /// Whether to store files uncompressed if their first chunk is incompressible.
+    probe: bool,
+}
+
+impl SkipCompression {
+    /// Creates a new `SkipCompression`.
+    ///
+    /// # Arguments
+    ///
+    /// * `extensions` - The extensions of files which are stored without compression.
+    /// * `probe` - Whether to store files uncompressed if their first chunk is incompressible.
+    pub(crate) fn new(extensions: &[String], probe: bool) -> Self {
+        Self {
+            extensions: extensions
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                .collect(),
+            probe,
+        }
+    }
+
+    /// Whether the file at the given path is stored without compression because of its extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file.
+    fn skips_extension(&self, path: &Path) -> bool {
+        !self.extensions.is_empty()
+            && path.extension().is_some_and(|ext| {
+                self.extensions
+                    .contains(&ext.to_string_lossy().to_lowercase())
+            })
+    }
+}
+
 /// The `FileArchiver` is responsible for archiving files.
 /// It will read the file, chunk it, and write the chunks to the backend.
 ///
@@ -37,6 +78,9 @@ pub(crate) struct FileArchiver<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex>
     index: &'a I,
     data_packer: Packer<BE>,
     rabin: Rabin64,
+    chunk_sizes: ChunkSizes,
+    fixed_chunk_size: Option<usize>,
+    skip_compression: SkipCompression,
 }
 
 impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
@@ -53,6 +97,9 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
     /// * `index` - The index to read from.
     /// * `indexer` - The indexer to write to.
     /// * `config` - The config file.
+    /// * `fixed_chunk_size` - If set, use fixed-size chunks of this size instead of content defined chunking.
+    /// * `skip_compression` - Decides which files are stored without compression.
+    /// * `write_concurrency` - The number of pack files to write in parallel.
     ///
     /// # Errors
     ///
@@ -63,15 +110,20 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
         index: &'a I,
         indexer: SharedIndexer<BE>,
         config: &ConfigFile,
+        fixed_chunk_size: Option<usize>,
+        skip_compression: SkipCompression,
+        write_concurrency: usize,
     ) -> RusticResult<Self> {
         let poly = config.poly()?;
+        let chunk_sizes = config.chunk_sizes()?;
 
-        let data_packer = Packer::new(
+        let data_packer = Packer::new_with_write_concurrency(
             be,
             BlobType::Data,
             indexer,
             config,
             index.total_size(BlobType::Data),
+            write_concurrency,
         )?;
 
         let rabin = Rabin64::new_with_polynom(6, &poly);
@@ -80,6 +132,9 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
             index,
             data_packer,
             rabin,
+            chunk_sizes,
+            fixed_chunk_size,
+            skip_compression,
         })
     }
 
@@ -132,10 +187,20 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
                             .attach_context("path", path.display().to_string())
                         })?;
 
-                    self.backup_reader(r, node, p)?
+                    self.backup_reader(r, node, &path, p)?
                 } else {
                     (node, 0)
                 };
+                let parent = match parent {
+                    ParentResult::ToCompare(content) => {
+                        if node.content.as_deref().unwrap_or_default() == content.as_slice() {
+                            ParentResult::Matched(())
+                        } else {
+                            ParentResult::NotMatched
+                        }
+                    }
+                    parent => parent,
+                };
                 TreeType::Other((path, node, (parent, filesize)))
             }
         })
@@ -146,32 +211,52 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> FileArchiver<'a, BE, I> {
         &self,
         r: impl Read + Send + 'static,
         node: Node,
+        path: &Path,
         p: &impl Progress,
     ) -> RusticResult<(Node, u64)> {
-        let chunks: Vec<_> = ChunkIter::new(
-            r,
-            usize::try_from(node.meta.size).map_err(|err| {
-                RusticError::with_source(
-                    ErrorKind::Internal,
-                    "Failed to convert node size `{size}` to usize",
-                    err,
-                )
-                .attach_context("size", node.meta.size.to_string())
-            })?,
-            self.rabin.clone(),
-        )
-        .map(|chunk| {
-            let chunk = chunk?;
-            let id = hash(&chunk);
-            let size = chunk.len() as u64;
-
-            if !self.index.has_data(&DataId::from(id)) {
-                self.data_packer.add(chunk.into(), BlobId::from(id))?;
-            }
-            p.inc(size);
-            Ok((DataId::from(id), size))
-        })
-        .collect::<RusticResult<_>>()?;
+        let size_hint = usize::try_from(node.meta.size).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Failed to convert node size `{size}` to usize",
+                err,
+            )
+            .attach_context("size", node.meta.size.to_string())
+        })?;
+        let chunks = match self.fixed_chunk_size {
+            Some(chunk_size) => Either::Left(FixedChunkIter::new(r, size_hint, chunk_size)),
+            None => Either::Right(ChunkIter::new(
+                r,
+                size_hint,
+                self.rabin.clone(),
+                self.chunk_sizes,
+            )),
+        };
+        let mut compress = !self.skip_compression.skips_extension(path);
+        let mut probe = compress && self.skip_compression.probe;
+        let chunks: Vec<_> = chunks
+            .map(|chunk| {
+                let chunk = chunk?;
+                let id = hash(&chunk);
+                let size = chunk.len() as u64;
+
+                if probe {
+                    // decide by the first chunk whether to compress the whole file
+                    compress = is_compressible(&chunk);
+                    probe = false;
+                }
+
+                if !self.index.has_data(&DataId::from(id)) {
+                    if compress {
+                        self.data_packer.add(chunk.into(), BlobId::from(id))?;
+                    } else {
+                        self.data_packer
+                            .add_uncompressed(chunk.into(), BlobId::from(id))?;
+                    }
+                }
+                p.inc(size);
+                Ok((DataId::from(id), size))
+            })
+            .collect::<RusticResult<_>>()?;
 
         let filesize = chunks.iter().map(|x| x.1).sum();
         let content = chunks.into_iter().map(|x| x.0).collect();
@@ -8,7 +8,10 @@ use log::warn;
 use crate::{
     archiver::{tree::TreeType, TreeStackEmptyError},
     backend::{decrypt::DecryptReadBackend, node::Node},
-    blob::tree::{Tree, TreeId},
+    blob::{
+        tree::{Tree, TreeId},
+        DataId,
+    },
     index::ReadGlobalIndex,
 };
 
@@ -34,6 +37,8 @@ pub struct Parent {
     ignore_ctime: bool,
     /// Ignore inode number when comparing nodes.
     ignore_inode: bool,
+    /// Compare the content of files instead of trusting their metadata.
+    force_hash: bool,
 }
 
 /// The result of a parent search.
@@ -49,6 +54,9 @@ pub(crate) enum ParentResult<T> {
     NotFound,
     /// The parent was found but doesn't match.
     NotMatched,
+    /// The parent file was found, but whether it matches is decided by comparing the content
+    /// with the given parent content after reading the file.
+    ToCompare(Vec<DataId>),
 }
 
 impl<T> ParentResult<T> {
@@ -70,6 +78,7 @@ impl<T> ParentResult<T> {
             Self::Matched(t) => ParentResult::Matched(f(t)),
             Self::NotFound => ParentResult::NotFound,
             Self::NotMatched => ParentResult::NotMatched,
+            Self::ToCompare(content) => ParentResult::ToCompare(content),
         }
     }
 }
@@ -87,12 +96,14 @@ impl Parent {
     /// * `tree_id` - The tree id of the parent tree.
     /// * `ignore_ctime` - Ignore ctime when comparing nodes.
     /// * `ignore_inode` - Ignore inode number when comparing nodes.
+    /// * `force_hash` - Compare the content of files instead of trusting their metadata.
     pub(crate) fn new(
         be: &impl DecryptReadBackend,
         index: &impl ReadGlobalIndex,
         tree_id: Option<TreeId>,
         ignore_ctime: bool,
         ignore_inode: bool,
+        force_hash: bool,
     ) -> Self {
         // if tree_id is given, try to load tree from backend.
         let tree = tree_id.and_then(|tree_id| match Tree::from_backend(be, index, tree_id) {
@@ -112,6 +123,7 @@ impl Parent {
             stack: Vec::new(),
             ignore_ctime,
             ignore_inode,
+            force_hash,
         }
     }
 
@@ -271,6 +283,18 @@ impl Parent {
                 self.finish_dir()?;
                 TreeType::EndTree
             }
+            TreeType::Other((path, node, open)) if self.force_hash && node.is_file() => {
+                let parent = self
+                    .p_node(&node.name())
+                    .map_or(ParentResult::NotFound, |p_node| {
+                        if p_node.is_file() {
+                            ParentResult::ToCompare(p_node.content.clone().unwrap_or_default())
+                        } else {
+                            ParentResult::NotMatched
+                        }
+                    });
+                TreeType::Other((path, node, (open, parent)))
+            }
             TreeType::Other((path, mut node, open)) => {
                 let parent = self.is_parent(&node, &node.name());
                 let parent = match parent {
@@ -11,6 +11,7 @@ use crate::{
         tree::{Tree, TreeId},
         BlobType,
     },
+    commands::backup::{BackupEvent, BackupEventCallback, FileStatus},
     error::{ErrorKind, RusticError, RusticResult},
     index::{indexer::SharedIndexer, ReadGlobalIndex},
     repofile::{configfile::ConfigFile, snapshotfile::SnapshotSummary},
@@ -37,6 +38,8 @@ pub(crate) struct TreeArchiver<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex>
     tree_packer: Packer<BE>,
     /// The summary of the snapshot.
     summary: SnapshotSummary,
+    /// The callback to emit backup events to.
+    event_sink: Option<BackupEventCallback>,
 }
 
 impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> TreeArchiver<'a, BE, I> {
@@ -54,6 +57,7 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> TreeArchiver<'a, BE, I> {
     /// * `indexer` - The indexer to write to.
     /// * `config` - The config file.
     /// * `summary` - The summary of the snapshot.
+    /// * `event_sink` - The callback to emit backup events to.
     ///
     /// # Errors
     ///
@@ -65,6 +69,7 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> TreeArchiver<'a, BE, I> {
         indexer: SharedIndexer<BE>,
         config: &ConfigFile,
         summary: SnapshotSummary,
+        event_sink: Option<BackupEventCallback>,
     ) -> RusticResult<Self> {
         let tree_packer = Packer::new(
             be,
@@ -80,6 +85,7 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> TreeArchiver<'a, BE, I> {
             index,
             tree_packer,
             summary,
+            event_sink,
         })
     }
 
@@ -131,19 +137,29 @@ impl<'a, BE: DecryptWriteBackend, I: ReadGlobalIndex> TreeArchiver<'a, BE, I> {
     /// * `parent` - The parent result of the file.
     fn add_file(&mut self, path: &Path, node: Node, parent: &ParentResult<()>, size: u64) {
         let filename = path.join(node.name());
-        match parent {
+        let status = match parent {
             ParentResult::Matched(()) => {
                 debug!("unchanged file: {:?}", filename);
                 self.summary.files_unmodified += 1;
+                FileStatus::Unchanged
             }
-            ParentResult::NotMatched => {
+            ParentResult::NotMatched | ParentResult::ToCompare(_) => {
                 debug!("changed   file: {:?}", filename);
                 self.summary.files_changed += 1;
+                FileStatus::Changed
             }
             ParentResult::NotFound => {
                 debug!("new       file: {:?}", filename);
                 self.summary.files_new += 1;
+                FileStatus::New
             }
+        };
+        if let Some(sink) = &self.event_sink {
+            sink.call(BackupEvent::FileProcessed {
+                path: filename,
+                size,
+                status,
+            });
         }
         self.summary.total_files_processed += 1;
         self.summary.total_bytes_processed += size;
@@ -6,13 +6,24 @@ pub(crate) mod dry_run;
 pub(crate) mod hotcold;
 pub(crate) mod ignore;
 pub(crate) mod local_destination;
+pub(crate) mod mirror;
 pub(crate) mod node;
+pub(crate) mod read_only;
+pub(crate) mod reader;
 pub(crate) mod stdin;
 pub(crate) mod warm_up;
 
-use std::{io::Read, ops::Deref, path::PathBuf, sync::Arc};
+use std::{
+    io::{Read, Seek},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use bytes::Bytes;
+use chrono::{DateTime, Local};
+use derive_setters::Setters;
 use enum_map::Enum;
 use log::trace;
 
@@ -22,7 +33,10 @@ use mockall::mock;
 use serde_derive::{Deserialize, Serialize};
 
 use crate::{
-    backend::node::{Metadata, Node, NodeType},
+    backend::{
+        mirror::MirrorFailureMode,
+        node::{Metadata, Node, NodeType},
+    },
     error::{ErrorKind, RusticError, RusticResult},
     id::Id,
 };
@@ -37,12 +51,19 @@ pub enum BackendErrorKind {
 
 pub(crate) type BackendResult<T> = Result<T, BackendErrorKind>;
 
-/// All [`FileType`]s which are located in separated directories
-pub const ALL_FILE_TYPES: [FileType; 4] = [
+/// The default size of the chunks returned by [`ReadBackend::read_partial_streaming`]
+pub const STREAMING_CHUNK_SIZE: u32 = 4 * 1024 * 1024; // 4 MiB
+
+/// An iterator over the chunks of a partially read file
+pub type PartialChunks<'a> = Box<dyn Iterator<Item = RusticResult<Bytes>> + Send + 'a>;
+
+/// All [`FileType`]s of repository files which are located in separated directories
+pub const ALL_FILE_TYPES: [FileType; 5] = [
     FileType::Key,
     FileType::Snapshot,
     FileType::Index,
     FileType::Pack,
+    FileType::Lock,
 ];
 
 /// Type for describing the kind of a file that can occur.
@@ -63,6 +84,15 @@ pub enum FileType {
     /// Data
     #[serde(rename = "pack")]
     Pack,
+    /// Locks
+    #[serde(rename = "lock")]
+    Lock,
+    /// Temporary files written by [`WriteBackend::self_test`]
+    ///
+    /// These are no repository files and are stored in their own directory,
+    /// hence they are not contained in [`ALL_FILE_TYPES`].
+    #[serde(rename = "selftest")]
+    SelfTest,
 }
 
 impl FileType {
@@ -75,13 +105,15 @@ impl FileType {
             Self::Index => "index",
             Self::Key => "keys",
             Self::Pack => "data",
+            Self::Lock => "locks",
+            Self::SelfTest => "selftest",
         }
     }
 
     /// Returns if the file type is cacheable.
     const fn is_cacheable(self) -> bool {
         match self {
-            Self::Config | Self::Key | Self::Pack => false,
+            Self::Config | Self::Key | Self::Pack | Self::Lock | Self::SelfTest => false,
             Self::Snapshot | Self::Index => true,
         }
     }
@@ -122,6 +154,27 @@ pub trait ReadBackend: Send + Sync + 'static {
             .collect())
     }
 
+    /// Returns the size of the given file or `None` if the file doesn't exist.
+    ///
+    /// The default implementation lists all files of the given type. Backends should override it
+    /// if they can query the size of a single file.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    ///
+    /// # Errors
+    ///
+    /// * If the files could not be listed.
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        Ok(self
+            .list_with_size(tpe)?
+            .into_iter()
+            .find(|(i, _)| i == id)
+            .map(|(_, size)| size))
+    }
+
     /// Reads full data of the given file.
     ///
     /// # Arguments
@@ -156,6 +209,49 @@ pub trait ReadBackend: Send + Sync + 'static {
         length: u32,
     ) -> RusticResult<Bytes>;
 
+    /// Reads partial data of the given file as a stream of chunks.
+    ///
+    /// The chunks are at most [`STREAMING_CHUNK_SIZE`] bytes long and are returned in order,
+    /// so that the whole requested range doesn't need to be held in memory at once.
+    ///
+    /// The default implementation issues a [`ReadBackend::read_partial`] for each chunk,
+    /// i.e. a range request per chunk for remote backends.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file should be cached.
+    /// * `offset` - The offset to read from.
+    /// * `length` - The length to read.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be opened for reading.
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the chunks. Errors while reading a chunk are returned by the iterator.
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        let id = *id;
+        let end = offset.saturating_add(length);
+        Ok(Box::new(
+            (offset..end)
+                .step_by(STREAMING_CHUNK_SIZE as usize)
+                .map(move |start| {
+                    let length = STREAMING_CHUNK_SIZE.min(end - start);
+                    self.read_partial(tpe, &id, cacheable, start, length)
+                }),
+        ))
+    }
+
     /// Specify if the backend needs a warming-up of files before accessing them.
     fn needs_warm_up(&self) -> bool {
         false
@@ -174,6 +270,23 @@ pub trait ReadBackend: Send + Sync + 'static {
     fn warm_up(&self, _tpe: FileType, _id: &Id) -> RusticResult<()> {
         Ok(())
     }
+
+    /// Check if the given file is already warm, i.e. can be accessed without warming it up.
+    ///
+    /// Warm-up is skipped for files which are reported to be warm. The default implementation
+    /// cannot tell and always returns `false`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    ///
+    /// # Errors
+    ///
+    /// * If the state of the file could not be determined.
+    fn is_warm(&self, _tpe: FileType, _id: &Id) -> RusticResult<bool> {
+        Ok(false)
+    }
 }
 
 /// Trait for Searching in a backend.
@@ -287,6 +400,20 @@ pub trait FindInBackend: ReadBackend {
 
 impl<T: ReadBackend> FindInBackend for T {}
 
+/// The result of a backend self-test, see [`WriteBackend::self_test`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelfTestResult {
+    /// Time needed to list the lock files
+    pub list: Duration,
+    /// Time needed to write the test file or `None` if writing was not tested
+    pub write: Option<Duration>,
+    /// Time needed to read the test file or `None` if reading was not tested
+    pub read: Option<Duration>,
+    /// Time needed to remove the test file or `None` if removing was not tested
+    pub remove: Option<Duration>,
+}
+
 /// Trait for backends that can write.
 /// This trait is implemented by all backends that can write data.
 pub trait WriteBackend: ReadBackend {
@@ -337,6 +464,67 @@ pub trait WriteBackend: ReadBackend {
     ///
     /// The result of the removal.
     fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()>;
+
+    /// The file type used for the test file written by [`WriteBackend::self_test`].
+    ///
+    /// Defaults to the dedicated [`FileType::SelfTest`]. Backends which only accept the file types of
+    /// the repository should override this.
+    fn self_test_file_type(&self) -> FileType {
+        FileType::SelfTest
+    }
+
+    /// Checks that the backend is reachable and writable.
+    ///
+    /// The default implementation lists the lock files, writes a small test file with a random id,
+    /// reads it back, compares the content and removes it again. The test file uses the file type
+    /// given by [`WriteBackend::self_test_file_type`].
+    ///
+    /// # Errors
+    ///
+    /// * If listing, writing, reading or removing the test file failed.
+    /// * If the read content differs from the written content.
+    ///
+    /// # Returns
+    ///
+    /// The time needed for the single operations.
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        let start = Instant::now();
+        _ = self.list(FileType::Lock)?;
+        let list = start.elapsed();
+
+        let tpe = self.self_test_file_type();
+        let id = Id::random();
+        let data = Bytes::from(format!("rustic backend self-test {id}"));
+
+        let start = Instant::now();
+        self.write_bytes(tpe, &id, false, data.clone())?;
+        let write = start.elapsed();
+
+        let start = Instant::now();
+        let read_data = self.read_full(tpe, &id);
+        let read = start.elapsed();
+
+        // always try to remove the test file, even if reading failed
+        let start = Instant::now();
+        let removed = self.remove(tpe, &id, false);
+        let remove = start.elapsed();
+
+        if read_data? != data {
+            return Err(RusticError::new(
+                ErrorKind::Backend,
+                "Self-test of backend `{location}` failed: The read data differs from the written data.",
+            )
+            .attach_context("location", self.location()));
+        }
+        removed?;
+
+        Ok(SelfTestResult {
+            list,
+            write: Some(write),
+            read: Some(read),
+            remove: Some(remove),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -355,6 +543,9 @@ mock! {
             offset: u32,
             length: u32,
         ) -> RusticResult<Bytes>;
+        fn needs_warm_up(&self) -> bool;
+        fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()>;
+        fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool>;
     }
 
     impl WriteBackend for Backend {
@@ -374,6 +565,12 @@ impl WriteBackend for Arc<dyn WriteBackend> {
     fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()> {
         self.deref().remove(tpe, id, cacheable)
     }
+    fn self_test_file_type(&self) -> FileType {
+        self.deref().self_test_file_type()
+    }
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        self.deref().self_test()
+    }
 }
 
 impl ReadBackend for Arc<dyn WriteBackend> {
@@ -383,6 +580,9 @@ impl ReadBackend for Arc<dyn WriteBackend> {
     fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
         self.deref().list_with_size(tpe)
     }
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.deref().file_size(tpe, id)
+    }
     fn list(&self, tpe: FileType) -> RusticResult<Vec<Id>> {
         self.deref().list(tpe)
     }
@@ -400,6 +600,17 @@ impl ReadBackend for Arc<dyn WriteBackend> {
         self.deref()
             .read_partial(tpe, id, cacheable, offset, length)
     }
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.deref()
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
 }
 
 impl std::fmt::Debug for dyn WriteBackend {
@@ -529,6 +740,203 @@ pub trait WriteSource: Clone {
     fn write_at<P: Into<PathBuf>>(&self, path: P, offset: u64, data: Bytes);
 }
 
+/// An entry which already exists in a [`RestoreDestination`].
+#[derive(Debug, Clone)]
+pub struct DestinationEntry {
+    /// The path of the entry within the destination, see [`RestoreDestination::path`].
+    pub path: PathBuf,
+
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+
+    /// Whether the entry is a regular file.
+    pub is_file: bool,
+}
+
+/// An existing file in a [`RestoreDestination`] which has the size of the file to restore.
+///
+/// # Type Parameters
+///
+/// * `F` - The type of the opened file.
+#[derive(Debug)]
+pub struct ExistingFile<F> {
+    /// The modification time of the existing file, if known.
+    pub mtime: Option<DateTime<Local>>,
+
+    /// The opened file, used to verify its contents.
+    pub file: F,
+}
+
+/// Options for setting the metadata of restored items, see [`RestoreDestination::set_metadata`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Setters)]
+#[setters(into)]
+#[non_exhaustive]
+pub struct MetadataOptions {
+    /// Use numeric ids instead of user/group when restoring uid/gid
+    pub numeric_id: bool,
+
+    /// Don't restore ownership (user/group)
+    pub no_ownership: bool,
+}
+
+/// Trait for destinations a snapshot can be restored to.
+///
+/// All methods taking an `item` get the path of the restored node relative to the restore root.
+/// Methods are called from multiple threads, but never concurrently for the same file.
+///
+/// [`LocalDestination`] implements this trait for restoring into the local filesystem.
+///
+/// [`LocalDestination`]: crate::LocalDestination
+pub trait RestoreDestination: Sync {
+    /// The type used to read existing files
+    type File: Read + Seek;
+
+    /// Returns the path of `item` within the destination.
+    ///
+    /// This is used to compare the restored nodes with [`existing_entries`](Self::existing_entries).
+    /// The default implementation returns `item`.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The item to get the path for
+    fn path(&self, item: &Path) -> PathBuf {
+        item.to_path_buf()
+    }
+
+    /// Returns the entries already existing in the destination, sorted by their path.
+    ///
+    /// The entry with path `self.path("")` is the restore root and is never removed.
+    /// The default implementation returns no entries, i.e. treats the destination as empty.
+    fn existing_entries(&self) -> Box<dyn Iterator<Item = DestinationEntry> + '_> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Returns whether the destination distinguishes names which only differ in case.
+    ///
+    /// This is probed once before restoring. The default implementation returns `true`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - If true, the destination must not be modified to determine the case sensitivity
+    ///
+    /// # Errors
+    ///
+    /// * If the case sensitivity could not be determined.
+    fn is_case_sensitive(&self, _dry_run: bool) -> RusticResult<bool> {
+        Ok(true)
+    }
+
+    /// Opens the existing file `item` if it has the given `size`.
+    ///
+    /// The default implementation never finds a matching file.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The item to open
+    /// * `size` - The size the file must have
+    ///
+    /// # Errors
+    ///
+    /// * If the matching file could not be inspected.
+    fn get_matching_file(
+        &self,
+        _item: &Path,
+        _size: u64,
+    ) -> RusticResult<Option<ExistingFile<Self::File>>> {
+        Ok(None)
+    }
+
+    /// Removes the existing directory at `path` recursively.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of an entry returned by [`existing_entries`](Self::existing_entries)
+    ///
+    /// # Errors
+    ///
+    /// * If the directory could not be removed.
+    fn remove_dir(&self, path: &Path) -> RusticResult<()>;
+
+    /// Removes the existing file at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of an entry returned by [`existing_entries`](Self::existing_entries)
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be removed.
+    fn remove_file(&self, path: &Path) -> RusticResult<()>;
+
+    /// Creates the directory `item`.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The directory to create
+    ///
+    /// # Errors
+    ///
+    /// * If the directory could not be created.
+    fn create_dir(&self, item: &Path) -> RusticResult<()>;
+
+    /// Sets the length of the file `item`, creating it if it doesn't exist.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The file to set the length for
+    /// * `size` - The length to set
+    ///
+    /// # Errors
+    ///
+    /// * If the length of the file could not be set.
+    fn set_length(&self, item: &Path, size: u64) -> RusticResult<()>;
+
+    /// Writes `data` to the file `item` at `offset`.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The file to write to
+    /// * `offset` - The offset to write at
+    /// * `data` - The data to write
+    ///
+    /// # Errors
+    ///
+    /// * If the data could not be written.
+    fn write_at(&self, item: &Path, offset: u64, data: &[u8]) -> RusticResult<()>;
+
+    /// Reads `length` bytes from the existing file `item` at `offset`.
+    ///
+    /// This is only called for files returned by [`get_matching_file`](Self::get_matching_file).
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The file to read from
+    /// * `offset` - The offset to read from
+    /// * `length` - The length to read
+    ///
+    /// # Errors
+    ///
+    /// * If the data could not be read.
+    fn read_at(&self, item: &Path, offset: u64, length: u64) -> RusticResult<Bytes>;
+
+    /// Sets the metadata of `item` as given by `node`.
+    ///
+    /// This is called after all contents have been restored; for directories after their contents.
+    /// For symlinks restored as copies of their target (see [`crate::RestoreOptions::dereference_symlinks`]),
+    /// `node` is the node of the target file.
+    ///
+    /// # Arguments
+    ///
+    /// * `item` - The item to set the metadata for
+    /// * `node` - The node containing the metadata
+    /// * `opts` - The options for setting the metadata, e.g. whether to restore ownership
+    ///
+    /// # Errors
+    ///
+    /// * If the metadata could not be set.
+    fn set_metadata(&self, item: &Path, node: &Node, opts: MetadataOptions) -> RusticResult<()>;
+}
+
 /// The backends a repository can be initialized and operated on
 ///
 /// # Note
@@ -543,6 +951,12 @@ pub struct RepositoryBackends {
 
     /// The hot repository of this [`RepositoryBackends`].
     repo_hot: Option<Arc<dyn WriteBackend>>,
+
+    /// The mirror backends of this [`RepositoryBackends`].
+    mirrors: Vec<Arc<dyn WriteBackend>>,
+
+    /// How failed modifications of the mirror backends are handled.
+    mirror_failure: MirrorFailureMode,
 }
 
 impl RepositoryBackends {
@@ -556,9 +970,37 @@ impl RepositoryBackends {
         Self {
             repository,
             repo_hot,
+            mirrors: Vec::new(),
+            mirror_failure: MirrorFailureMode::default(),
         }
     }
 
+    /// Adds a mirror backend to this [`RepositoryBackends`].
+    ///
+    /// All modifications of the repository are also applied to the mirror backends, so the data
+    /// only needs to be processed once to write it to multiple backends. The mirror backends must be
+    /// empty when initializing the repository or already contain a copy of the repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `mirror` - The mirror backend to add.
+    #[must_use]
+    pub fn with_mirror(mut self, mirror: Arc<dyn WriteBackend>) -> Self {
+        self.mirrors.push(mirror);
+        self
+    }
+
+    /// Sets how failed modifications of the mirror backends are handled.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The [`MirrorFailureMode`] to use.
+    #[must_use]
+    pub fn on_mirror_failure(mut self, mode: MirrorFailureMode) -> Self {
+        self.mirror_failure = mode;
+        self
+    }
+
     /// Returns the repository of this [`RepositoryBackends`].
     #[must_use]
     pub fn repository(&self) -> Arc<dyn WriteBackend> {
@@ -570,4 +1012,16 @@ impl RepositoryBackends {
     pub fn repo_hot(&self) -> Option<Arc<dyn WriteBackend>> {
         self.repo_hot.clone()
     }
+
+    /// Returns the mirror backends of this [`RepositoryBackends`].
+    #[must_use]
+    pub fn mirrors(&self) -> &[Arc<dyn WriteBackend>] {
+        &self.mirrors
+    }
+
+    /// Returns how failed modifications of the mirror backends are handled.
+    #[must_use]
+    pub fn mirror_failure(&self) -> MirrorFailureMode {
+        self.mirror_failure
+    }
 }
@@ -12,7 +12,7 @@ use log::{trace, warn};
 use walkdir::WalkDir;
 
 use crate::{
-    backend::{FileType, ReadBackend, WriteBackend},
+    backend::{FileType, PartialChunks, ReadBackend, SelfTestResult, WriteBackend},
     error::{ErrorKind, RusticError, RusticResult},
     id::Id,
     repofile::configfile::RepositoryId,
@@ -79,6 +79,10 @@ impl ReadBackend for CachedBackend {
         Ok(list)
     }
 
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
     /// Reads full data of the given file.
     ///
     /// # Arguments
@@ -170,6 +174,39 @@ impl ReadBackend for CachedBackend {
             self.be.read_partial(tpe, id, cacheable, offset, length)
         }
     }
+
+    /// Reads partial data of the given file as a stream of chunks.
+    ///
+    /// Cacheable files are read completely through the cache and returned as a single chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `cacheable` - Whether the file is cacheable.
+    /// * `offset` - The offset to read from.
+    /// * `length` - The length to read.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be read.
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        if cacheable || tpe.is_cacheable() {
+            let data = self.read_partial(tpe, id, cacheable, offset, length)?;
+            Ok(Box::new(std::iter::once(Ok(data))))
+        } else {
+            self.be
+                .read_partial_streaming(tpe, id, cacheable, offset, length)
+        }
+    }
+
     fn needs_warm_up(&self) -> bool {
         self.be.needs_warm_up()
     }
@@ -177,6 +214,10 @@ impl ReadBackend for CachedBackend {
     fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()> {
         self.be.warm_up(tpe, id)
     }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
 }
 
 impl WriteBackend for CachedBackend {
@@ -226,6 +267,10 @@ impl WriteBackend for CachedBackend {
         }
         self.be.remove(tpe, id, cacheable)
     }
+
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        self.be.self_test()
+    }
 }
 
 /// Backend that caches data in a directory.
@@ -1,14 +1,24 @@
-use std::{num::NonZeroU32, sync::Arc};
+use std::{
+    fmt::{self, Debug},
+    io::{Read, Write},
+    num::NonZeroU32,
+    sync::Arc,
+};
 
 use bytes::Bytes;
 use crossbeam_channel::{unbounded, Receiver};
+use log::{debug, warn};
 use rayon::prelude::*;
-use zstd::stream::{copy_encode, decode_all, encode_all};
+use zstd::{
+    dict::{DecoderDictionary, EncoderDictionary},
+    stream::{copy_encode, decode_all, Decoder, Encoder},
+    zstd_safe::{get_dict_id_from_dict, get_dict_id_from_frame},
+};
 
 pub use zstd::compression_level_range;
 
 use crate::{
-    backend::{FileType, ReadBackend, WriteBackend},
+    backend::{FileType, PartialChunks, ReadBackend, WriteBackend},
     crypto::{hasher::hash, CryptoKey},
     error::{ErrorKind, RusticError, RusticResult},
     id::Id,
@@ -22,6 +32,217 @@ pub fn max_compression_level() -> i32 {
     *compression_level_range().end()
 }
 
+/// A zstd dictionary, prepared once for compression and decompression
+///
+/// Frames compressed with a dictionary record the id of the dictionary. This is used to only apply
+/// the dictionary to data which was compressed with it.
+pub struct ZstdDictionary {
+    /// The raw dictionary
+    data: Bytes,
+    /// The id of the dictionary
+    id: Option<NonZeroU32>,
+    /// The compression level and the dictionary prepared for compressing with it
+    encoder: Option<(i32, EncoderDictionary<'static>)>,
+    /// The dictionary prepared for decompression
+    decoder: DecoderDictionary<'static>,
+}
+
+impl ZstdDictionary {
+    /// Prepare the given dictionary
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The raw dictionary
+    /// * `level` - The compression level to prepare the dictionary for, if data is compressed
+    pub(crate) fn new(data: Bytes, level: Option<i32>) -> Self {
+        Self {
+            id: get_dict_id_from_dict(&data),
+            encoder: level.map(|level| (level, EncoderDictionary::copy(&data, level))),
+            decoder: DecoderDictionary::copy(&data),
+            data,
+        }
+    }
+
+    /// Compress data using the dictionary and append it to `out`
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to compress
+    /// * `out` - The buffer to append the compressed data to
+    /// * `level` - The compression level
+    ///
+    /// # Errors
+    ///
+    /// * If the data could not be compressed
+    fn compress_into(&self, data: &[u8], out: &mut Vec<u8>, level: i32) -> std::io::Result<()> {
+        let mut encoder = match &self.encoder {
+            Some((prepared_level, dictionary)) if *prepared_level == level => {
+                Encoder::with_prepared_dictionary(out, dictionary)?
+            }
+            _ => Encoder::with_dictionary(out, level, &self.data)?,
+        };
+        encoder.write_all(data)?;
+        _ = encoder.finish()?;
+        Ok(())
+    }
+}
+
+impl Debug for ZstdDictionary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstdDictionary")
+            .field("id", &self.id)
+            .field("size", &self.data.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Decompress zstd compressed data
+///
+/// The dictionary is only used if the data was compressed with it. Data compressed without a dictionary,
+/// e.g. before the dictionary was added to the repository, is decompressed without it.
+///
+/// # Arguments
+///
+/// * `data` - The compressed data
+/// * `dictionary` - The dictionary of the repository, if any
+///
+/// # Errors
+///
+/// * If the data was compressed with another dictionary
+/// * If the data could not be decompressed
+pub(crate) fn decompress(
+    data: &[u8],
+    dictionary: Option<&ZstdDictionary>,
+) -> std::io::Result<Vec<u8>> {
+    let Some(id) = get_dict_id_from_frame(data) else {
+        return decode_all(data);
+    };
+    match dictionary {
+        Some(dictionary) if dictionary.id == Some(id) => {
+            let mut out = Vec::new();
+            _ = Decoder::with_prepared_dictionary(data, &dictionary.decoder)?
+                .read_to_end(&mut out)?;
+            Ok(out)
+        }
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("data is compressed with the unknown dictionary {id}"),
+        )),
+    }
+}
+
+/// Compress data using zstd and append it to `out`
+///
+/// # Arguments
+///
+/// * `data` - The data to compress
+/// * `out` - The buffer to append the compressed data to
+/// * `level` - The compression level
+/// * `dictionary` - The dictionary to use for compression, if any
+///
+/// # Errors
+///
+/// * If the data could not be compressed
+pub(crate) fn compress_into(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    level: i32,
+    dictionary: Option<&[u8]>,
+) -> std::io::Result<()> {
+    match dictionary {
+        None => copy_encode(data, out, level),
+        Some(dictionary) => {
+            let mut encoder = Encoder::with_dictionary(out, level, dictionary)?;
+            encoder.write_all(data)?;
+            _ = encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+/// The maximum size of the sample used by [`is_compressible`]
+const COMPRESSIBILITY_SAMPLE_SIZE: usize = 64 * 1024;
+
+/// The maximum ratio of compressed to uncompressed size for data to be considered compressible
+const COMPRESSIBILITY_MAX_RATIO: f64 = 0.95;
+
+/// Quickly probe whether data is worth compressing
+///
+/// This compresses a sample of the data with a fast compression level and checks if the compressed
+/// size is at most 95% of the sample size.
+///
+/// # Arguments
+///
+/// * `data` - The data to probe
+pub(crate) fn is_compressible(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(COMPRESSIBILITY_SAMPLE_SIZE)];
+    if sample.is_empty() {
+        return true;
+    }
+    let mut out = Vec::new();
+    if copy_encode(sample, &mut out, 1).is_err() {
+        return true;
+    }
+    #[allow(clippy::cast_precision_loss)]
+    let ratio = out.len() as f64 / sample.len() as f64;
+    ratio <= COMPRESSIBILITY_MAX_RATIO
+}
+
+/// The number of retries when verifying a written file fails, see [`write_verified`]
+const WRITE_VERIFICATION_RETRIES: usize = 3;
+
+/// Writes a file and verifies the write by reading the file back.
+///
+/// If the file read back differs from the written data, it is written again.
+///
+/// # Arguments
+///
+/// * `be` - The backend to write to.
+/// * `verify_be` - The backend to read the file back from. This should not use a cache.
+/// * `tpe` - The type of the file.
+/// * `id` - The id of the file.
+/// * `data` - The data to write.
+///
+/// # Errors
+///
+/// * If the file could not be written.
+/// * If the file read back still differs from the written data after all retries.
+pub(crate) fn write_verified(
+    be: &impl WriteBackend,
+    verify_be: &impl ReadBackend,
+    tpe: FileType,
+    id: &Id,
+    data: Bytes,
+) -> RusticResult<()> {
+    for retry in 0..=WRITE_VERIFICATION_RETRIES {
+        if retry > 0 {
+            warn!("verification of written file {tpe:?}/{id} failed, writing it again (retry {retry}/{WRITE_VERIFICATION_RETRIES})");
+        }
+        be.write_bytes(tpe, id, false, data.clone())?;
+        match verify_be.read_full(tpe, id) {
+            Ok(read) if read == data => return Ok(()),
+            Ok(read) => debug!(
+                "file {tpe:?}/{id} read back has {} bytes, expected {} bytes or different content",
+                read.len(),
+                data.len()
+            ),
+            Err(err) => debug!(
+                "reading back file {tpe:?}/{id} failed: {}",
+                err.display_log()
+            ),
+        }
+    }
+
+    Err(RusticError::new(
+        ErrorKind::Backend,
+        "Verification of written file `{tpe}/{id}` failed: The file read back differs from the written data, even after `{retries}` retries.",
+    )
+    .attach_context("tpe", tpe.to_string())
+    .attach_context("id", id.to_string())
+    .attach_context("retries", WRITE_VERIFICATION_RETRIES.to_string())
+    .append_guidance_line("Please check the backend for errors. The file may be missing or corrupted in the repository."))
+}
+
 /// A backend that can decrypt data.
 /// This is a trait that is implemented by all backends that can decrypt data.
 /// It is implemented for all backends that implement `DecryptWriteBackend` and `DecryptReadBackend`.
@@ -56,6 +277,11 @@ pub trait DecryptReadBackend: ReadBackend + Clone + 'static {
     /// * If the file could not be read.
     fn read_encrypted_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes>;
 
+    /// Returns the zstd dictionary used to compress data, if any.
+    fn zstd_dictionary(&self) -> Option<&ZstdDictionary> {
+        None
+    }
+
     /// Reads the given file from partial data.
     ///
     /// # Arguments
@@ -74,7 +300,7 @@ pub trait DecryptReadBackend: ReadBackend + Clone + 'static {
     ) -> RusticResult<Bytes> {
         let mut data = self.decrypt(data)?;
         if let Some(length) = uncompressed_length {
-            data = decode_all(&*data).map_err(|err| {
+            data = decompress(&data, self.zstd_dictionary()).map_err(|err| {
                 RusticError::with_source(
                     ErrorKind::Internal,
                     "Failed to decode zstd compressed data. The data may be corrupted.",
@@ -152,13 +378,14 @@ pub trait DecryptReadBackend: ReadBackend + Clone + 'static {
     ///
     /// # Arguments
     ///
-    /// * `p` - The progress bar.
+    /// * `p` - The progress bar. Its length is set to the number of files.
     ///
     /// # Errors
     ///
     /// If the files could not be read.
     fn stream_all<F: RepoFile>(&self, p: &impl Progress) -> StreamResult<F::Id, F> {
         let list = self.list(F::TYPE)?;
+        p.set_length(list.len() as u64);
         self.stream_list(&list, p)
     }
 
@@ -169,13 +396,12 @@ pub trait DecryptReadBackend: ReadBackend + Clone + 'static {
     /// # Arguments
     ///
     /// * `list` - The list of files to stream.
-    /// * `p` - The progress bar.
+    /// * `p` - The progress bar. It is increased for each file, its length must be set by the caller.
     ///
     /// # Errors
     ///
     /// If the files could not be read.
     fn stream_list<F: RepoFile>(&self, list: &[Id], p: &impl Progress) -> StreamResult<F::Id, F> {
-        p.set_length(list.len() as u64);
         let (tx, rx) = unbounded();
 
         list.into_par_iter()
@@ -219,6 +445,17 @@ pub trait DecryptWriteBackend: WriteBackend + Clone + 'static {
     /// The processed data, the original data length and when compression is used, the uncomressed length
     fn process_data(&self, data: &[u8]) -> RusticResult<(Vec<u8>, u32, Option<NonZeroU32>)>;
 
+    /// Process some blob data without compressing it.
+    /// This is used for data which is known to be incompressible.
+    ///
+    /// # Returns
+    ///
+    /// The processed data, the original data length and `None` as uncompressed length
+    fn process_data_uncompressed(
+        &self,
+        data: &[u8],
+    ) -> RusticResult<(Vec<u8>, u32, Option<NonZeroU32>)>;
+
     /// Writes the given data to the backend without compression and returns the id of the data.
     ///
     /// # Arguments
@@ -347,6 +584,13 @@ pub trait DecryptWriteBackend: WriteBackend + Clone + 'static {
     ///
     /// * `zstd` - The compression level to use for zstd. TODO: What happens if this is None? What are defaults?
     fn set_zstd(&mut self, zstd: Option<i32>);
+
+    /// Sets the dictionary to use for zstd compression and decompression.
+    ///
+    /// # Arguments
+    ///
+    /// * `dictionary` - The dictionary to use. If `None`, no dictionary is used.
+    fn set_zstd_dictionary(&mut self, dictionary: Option<Bytes>);
     fn set_extra_verify(&mut self, extra_check: bool);
 }
 
@@ -363,8 +607,12 @@ pub struct DecryptBackend<C: CryptoKey> {
     key: C,
     /// The compression level to use for zstd.
     zstd: Option<i32>,
+    /// The dictionary to use for zstd compression and decompression.
+    zstd_dictionary: Option<Arc<ZstdDictionary>>,
     /// Whether to do an extra verification by decompressing and decrypting the data
     extra_verify: bool,
+    /// The backend to read written repository files back from to verify the write, if any
+    verify_be: Option<Arc<dyn WriteBackend>>,
 }
 
 impl<C: CryptoKey> DecryptBackend<C> {
@@ -388,16 +636,65 @@ impl<C: CryptoKey> DecryptBackend<C> {
             key,
             // zstd and extra_verify are directly set, where needed.
             zstd: None,
+            zstd_dictionary: None,
             extra_verify: false,
+            verify_be: None,
+        }
+    }
+
+    /// Sets the backend to read written repository files back from to verify the write.
+    ///
+    /// This should be a backend without caching, such that the data is really read from the storage.
+    ///
+    /// # Arguments
+    ///
+    /// * `verify_be` - The backend to read from. If `None`, written files are not verified.
+    pub fn set_write_verification(&mut self, verify_be: Option<Arc<dyn WriteBackend>>) {
+        self.verify_be = verify_be;
+    }
+
+    /// Writes a repository file and verifies the write, if requested.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the file.
+    /// * `id` - The id of the file.
+    /// * `data` - The data to write.
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be written or verified.
+    fn write_file(&self, tpe: FileType, id: &Id, data: Bytes) -> RusticResult<()> {
+        match &self.verify_be {
+            Some(verify_be) => write_verified(self, verify_be, tpe, id, data),
+            None => self.write_bytes(tpe, id, false, data),
+        }
+    }
+
+    /// Compress data using zstd and the dictionary, if any, and append it to `out`
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The data to compress
+    /// * `out` - The buffer to append the compressed data to
+    /// * `level` - The compression level
+    ///
+    /// # Errors
+    ///
+    /// * If the data could not be compressed
+    fn compress_into(&self, data: &[u8], out: &mut Vec<u8>, level: i32) -> std::io::Result<()> {
+        match &self.zstd_dictionary {
+            Some(dictionary) => dictionary.compress_into(data, out, level),
+            None => copy_encode(data, out, level),
         }
     }
 
     /// Decrypt and potentially decompress an already read repository file
-    fn decrypt_file(&self, data: &[u8]) -> RusticResult<Vec<u8>> {
+    pub(crate) fn decrypt_file(&self, data: &[u8]) -> RusticResult<Vec<u8>> {
         let decrypted = self.decrypt(data)?;
         Ok(match decrypted.first() {
             Some(b'{' | b'[') => decrypted, // not compressed
-            Some(2) => decode_all(&decrypted[1..]).map_err(|err| {
+            Some(2) => decompress(&decrypted[1..], self.zstd_dictionary()).map_err(|err| {
                 RusticError::with_source(
                     ErrorKind::Internal,
                     "Failed to decode zstd compressed data. The data may be corrupted.",
@@ -414,11 +711,11 @@ impl<C: CryptoKey> DecryptBackend<C> {
     }
 
     /// encrypt and potentially compress a repository file
-    fn encrypt_file(&self, data: &[u8]) -> RusticResult<Vec<u8>> {
+    pub(crate) fn encrypt_file(&self, data: &[u8]) -> RusticResult<Vec<u8>> {
         let data_encrypted = match self.zstd {
             Some(level) => {
                 let mut out = vec![2_u8];
-                copy_encode(data, &mut out, level).map_err(|err| {
+                self.compress_into(data, &mut out, level).map_err(|err| {
                     RusticError::with_source(
                         ErrorKind::Internal,
                         "Compressing and appending data failed. The data may be corrupted.",
@@ -451,6 +748,15 @@ impl<C: CryptoKey> DecryptBackend<C> {
 
     /// encrypt and potentially compress some data
     fn encrypt_data(&self, data: &[u8]) -> RusticResult<(Vec<u8>, u32, Option<NonZeroU32>)> {
+        self.encrypt_data_with_zstd(data, self.zstd)
+    }
+
+    /// encrypt some data and compress it if a compression level is given
+    fn encrypt_data_with_zstd(
+        &self,
+        data: &[u8],
+        zstd: Option<i32>,
+    ) -> RusticResult<(Vec<u8>, u32, Option<NonZeroU32>)> {
         let data_len: u32 = data.len().try_into().map_err(|err| {
             RusticError::with_source(
                 ErrorKind::Internal,
@@ -461,21 +767,21 @@ impl<C: CryptoKey> DecryptBackend<C> {
             .ask_report()
         })?;
 
-        let (data_encrypted, uncompressed_length) = match self.zstd {
+        let (data_encrypted, uncompressed_length) = match zstd {
             None => (self.key.encrypt_data(data)?, None),
             // compress if requested
-            Some(level) => (
-                self.key
-                    .encrypt_data(&encode_all(data, level).map_err(|err| {
-                        RusticError::with_source(
-                            ErrorKind::Internal,
-                            "Failed to encode zstd compressed data. The data may be corrupted.",
-                            err,
-                        )
-                        .attach_context("compression_level", level.to_string())
-                    })?)?,
-                NonZeroU32::new(data_len),
-            ),
+            Some(level) => {
+                let mut out = Vec::new();
+                self.compress_into(data, &mut out, level).map_err(|err| {
+                    RusticError::with_source(
+                        ErrorKind::Internal,
+                        "Failed to encode zstd compressed data. The data may be corrupted.",
+                        err,
+                    )
+                    .attach_context("compression_level", level.to_string())
+                })?;
+                (self.key.encrypt_data(&out)?, NonZeroU32::new(data_len))
+            }
         };
         Ok((data_encrypted, data_len, uncompressed_length))
     }
@@ -534,7 +840,14 @@ impl<C: CryptoKey> DecryptWriteBackend for DecryptBackend<C> {
 
         let id = hash(&data_encrypted);
 
-        self.write_bytes(tpe, &id, false, data_encrypted.into())?;
+        self.write_file(tpe, &id, data_encrypted.into())?;
+        Ok(id)
+    }
+
+    fn hash_write_full_uncompressed(&self, tpe: FileType, data: &[u8]) -> RusticResult<Id> {
+        let data = self.key().encrypt_data(data)?;
+        let id = hash(&data);
+        self.write_file(tpe, &id, data.into())?;
         Ok(id)
     }
 
@@ -546,6 +859,18 @@ impl<C: CryptoKey> DecryptWriteBackend for DecryptBackend<C> {
         Ok((data_encrypted, data_len, uncompressed_length))
     }
 
+    fn process_data_uncompressed(
+        &self,
+        data: &[u8],
+    ) -> RusticResult<(Vec<u8>, u32, Option<NonZeroU32>)> {
+        let (data_encrypted, data_len, uncompressed_length) =
+            self.encrypt_data_with_zstd(data, None)?;
+
+        self.very_data(&data_encrypted, uncompressed_length, data)?;
+
+        Ok((data_encrypted, data_len, uncompressed_length))
+    }
+
     /// Sets the compression level to use for zstd.
     ///
     /// # Arguments
@@ -553,6 +878,24 @@ impl<C: CryptoKey> DecryptWriteBackend for DecryptBackend<C> {
     /// * `zstd` - The compression level to use for zstd.
     fn set_zstd(&mut self, zstd: Option<i32>) {
         self.zstd = zstd;
+        // prepare the dictionary for the new compression level
+        let dictionary = self
+            .zstd_dictionary
+            .as_ref()
+            .map(|dictionary| Arc::new(ZstdDictionary::new(dictionary.data.clone(), zstd)));
+        self.zstd_dictionary = dictionary;
+    }
+
+    /// Sets the dictionary to use for zstd compression and decompression.
+    ///
+    /// The dictionary is prepared for the compression level set by [`DecryptWriteBackend::set_zstd`].
+    ///
+    /// # Arguments
+    ///
+    /// * `dictionary` - The dictionary to use. If `None`, no dictionary is used.
+    fn set_zstd_dictionary(&mut self, dictionary: Option<Bytes>) {
+        self.zstd_dictionary =
+            dictionary.map(|dictionary| Arc::new(ZstdDictionary::new(dictionary, self.zstd)));
     }
 
     /// Sets `extra_check`, i.e. whether to do an extra check after compressing/encrypting
@@ -579,6 +922,11 @@ impl<C: CryptoKey> DecryptReadBackend for DecryptBackend<C> {
         self.key.decrypt_data(data)
     }
 
+    /// Returns the zstd dictionary used to compress data, if any.
+    fn zstd_dictionary(&self) -> Option<&ZstdDictionary> {
+        self.zstd_dictionary.as_deref()
+    }
+
     /// Reads encrypted data from the backend.
     ///
     /// # Arguments
@@ -608,6 +956,10 @@ impl<C: CryptoKey> ReadBackend for DecryptBackend<C> {
         self.be.list_with_size(tpe)
     }
 
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
     fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
         self.be.read_full(tpe, id)
     }
@@ -622,6 +974,18 @@ impl<C: CryptoKey> ReadBackend for DecryptBackend<C> {
     ) -> RusticResult<Bytes> {
         self.be.read_partial(tpe, id, cacheable, offset, length)
     }
+
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
 }
 
 impl<C: CryptoKey> WriteBackend for DecryptBackend<C> {
@@ -654,6 +1018,117 @@ mod tests {
         (be, data)
     }
 
+    #[test]
+    fn zstd_dictionary_roundtrip() -> Result<()> {
+        let samples: Vec<_> = (0..1000)
+            .map(|i| format!(r#"{{"name":"file{i}","type":"file","mode":420,"size":{i}}}"#))
+            .collect();
+        let dictionary = zstd::dict::from_samples(&samples, 4096)?;
+        let (mut be, _) = init();
+        be.set_extra_verify(true);
+        let data = br#"{"name":"file1234","type":"file","mode":420,"size":1234}"#;
+
+        // data compressed before the dictionary was added
+        let old_file = be.encrypt_file(data)?;
+        let (old_data, _, old_ul) = be.encrypt_data(data)?;
+
+        be.set_zstd_dictionary(Some(dictionary.into()));
+        let new_file = be.encrypt_file(data)?;
+        let (new_data, _, new_ul) = be.encrypt_data(data)?;
+        // data compressed with another level than the prepared one
+        let (other_level_data, _, other_level_ul) = be.encrypt_data_with_zstd(data, Some(19))?;
+
+        // old and new data can both be read
+        assert_eq!(be.decrypt_file(&old_file)?, data);
+        assert_eq!(be.decrypt_file(&new_file)?, data);
+        assert_eq!(&*be.read_encrypted_from_partial(&old_data, old_ul)?, data);
+        assert_eq!(&*be.read_encrypted_from_partial(&new_data, new_ul)?, data);
+        assert_eq!(
+            &*be.read_encrypted_from_partial(&other_level_data, other_level_ul)?,
+            data
+        );
+
+        // changing the compression level keeps the dictionary
+        be.set_zstd(Some(3));
+        assert!(be.zstd_dictionary().is_some());
+        assert_eq!(&*be.read_encrypted_from_partial(&new_data, new_ul)?, data);
+
+        // the dictionary is needed to read the new data, but not the old data
+        be.set_zstd_dictionary(None);
+        assert!(be.read_encrypted_from_partial(&new_data, new_ul).is_err());
+        assert!(be.decrypt_file(&new_file).is_err());
+        assert_eq!(&*be.read_encrypted_from_partial(&old_data, old_ul)?, data);
+        Ok(())
+    }
+
+    /// A backend which returns a truncated file on the first `truncated_reads` reads
+    fn flaky_backend(truncated_reads: usize, writes: usize) -> MockBackend {
+        let written = Arc::new(std::sync::Mutex::new(Bytes::new()));
+        let reads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut be = MockBackend::new();
+        let w = written.clone();
+        _ = be
+            .expect_write_bytes()
+            .times(writes)
+            .returning(move |_, _, _, buf| {
+                *w.lock().unwrap() = buf;
+                Ok(())
+            });
+        _ = be.expect_read_full().returning(move |_, _| {
+            let data = written.lock().unwrap().clone();
+            if reads.fetch_add(1, std::sync::atomic::Ordering::SeqCst) < truncated_reads {
+                Ok(data.slice(..data.len() / 2))
+            } else {
+                Ok(data)
+            }
+        });
+        be
+    }
+
+    #[test]
+    fn write_verified_retries_on_mismatch() -> Result<()> {
+        let be = flaky_backend(2, 3);
+        write_verified(
+            &be,
+            &be,
+            FileType::Snapshot,
+            &Id::default(),
+            "{test}".into(),
+        )?;
+        Ok(())
+    }
+
+    #[test]
+    fn write_verified_fails_after_retries() {
+        let be = flaky_backend(usize::MAX, WRITE_VERIFICATION_RETRIES + 1);
+        assert!(write_verified(
+            &be,
+            &be,
+            FileType::Snapshot,
+            &Id::default(),
+            "{test}".into()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn process_data_uncompressed_stores_uncompressed() -> Result<()> {
+        let (mut be, _) = init();
+        be.set_extra_verify(true);
+        let data: Vec<u8> = (0..100_000).map(|_| rand::random()).collect();
+        assert!(!is_compressible(&data));
+
+        let (data_encrypted, data_len, ul) = be.process_data_uncompressed(&data)?;
+        assert_eq!(data_len as usize, data.len());
+        assert_eq!(ul, None);
+        assert_eq!(&*be.read_encrypted_from_partial(&data_encrypted, ul)?, data);
+
+        assert!(is_compressible(&[0; 100_000]));
+        let (_, _, ul) = be.process_data(&[0; 100_000])?;
+        assert!(ul.is_some());
+        Ok(())
+    }
+
     #[test]
     fn verify_encrypt_file_ok() -> Result<()> {
         let (mut be, data) = init();
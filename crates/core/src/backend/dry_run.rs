@@ -1,10 +1,11 @@
 use bytes::Bytes;
-use zstd::decode_all;
 
 use crate::{
     backend::{
-        decrypt::{DecryptFullBackend, DecryptReadBackend, DecryptWriteBackend},
-        FileType, ReadBackend, WriteBackend,
+        decrypt::{
+            decompress, DecryptFullBackend, DecryptReadBackend, DecryptWriteBackend, ZstdDictionary,
+        },
+        FileType, PartialChunks, ReadBackend, WriteBackend,
     },
     error::{ErrorKind, RusticError, RusticResult},
     id::Id,
@@ -40,6 +41,10 @@ impl<BE: DecryptFullBackend> DecryptReadBackend for DryRunBackend<BE> {
         self.be.decrypt(data)
     }
 
+    fn zstd_dictionary(&self) -> Option<&ZstdDictionary> {
+        self.be.zstd_dictionary()
+    }
+
     /// Reads encrypted data of the given file.
     ///
     /// # Arguments
@@ -59,7 +64,7 @@ impl<BE: DecryptFullBackend> DecryptReadBackend for DryRunBackend<BE> {
         let decrypted = self.decrypt(&self.read_full(tpe, id)?)?;
         Ok(match decrypted.first() {
             Some(b'{' | b'[') => decrypted, // not compressed
-            Some(2) => decode_all(&decrypted[1..])
+            Some(2) => decompress(&decrypted[1..], self.zstd_dictionary())
                 .map_err(|err|
                     RusticError::with_source(
                         ErrorKind::Internal,
@@ -89,6 +94,10 @@ impl<BE: DecryptFullBackend> ReadBackend for DryRunBackend<BE> {
         self.be.list_with_size(tpe)
     }
 
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
     fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
         self.be.read_full(tpe, id)
     }
@@ -103,6 +112,18 @@ impl<BE: DecryptFullBackend> ReadBackend for DryRunBackend<BE> {
     ) -> RusticResult<Bytes> {
         self.be.read_partial(tpe, id, cacheable, offset, length)
     }
+
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
 }
 
 impl<BE: DecryptFullBackend> DecryptWriteBackend for DryRunBackend<BE> {
@@ -127,12 +148,25 @@ impl<BE: DecryptFullBackend> DecryptWriteBackend for DryRunBackend<BE> {
         self.be.process_data(data)
     }
 
+    fn process_data_uncompressed(
+        &self,
+        data: &[u8],
+    ) -> RusticResult<(Vec<u8>, u32, Option<std::num::NonZeroU32>)> {
+        self.be.process_data_uncompressed(data)
+    }
+
     fn set_zstd(&mut self, zstd: Option<i32>) {
         if !self.dry_run {
             self.be.set_zstd(zstd);
         }
     }
 
+    fn set_zstd_dictionary(&mut self, dictionary: Option<Bytes>) {
+        if !self.dry_run {
+            self.be.set_zstd_dictionary(dictionary);
+        }
+    }
+
     fn set_extra_verify(&mut self, extra_check: bool) {
         if !self.dry_run {
             self.be.set_extra_verify(extra_check);
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use bytes::Bytes;
 
 use crate::{
-    backend::{FileType, ReadBackend, WriteBackend},
+    backend::{FileType, PartialChunks, ReadBackend, SelfTestResult, WriteBackend},
     error::RusticResult,
     id::Id,
 };
@@ -49,6 +49,10 @@ impl ReadBackend for HotColdBackend {
         self.be.list_with_size(tpe)
     }
 
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
     fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
         self.be_hot.read_full(tpe, id)
     }
@@ -68,6 +72,23 @@ impl ReadBackend for HotColdBackend {
         }
     }
 
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        if cacheable || tpe != FileType::Pack {
+            self.be_hot
+                .read_partial_streaming(tpe, id, cacheable, offset, length)
+        } else {
+            self.be
+                .read_partial_streaming(tpe, id, cacheable, offset, length)
+        }
+    }
+
     fn needs_warm_up(&self) -> bool {
         self.be.needs_warm_up()
     }
@@ -75,6 +96,10 @@ impl ReadBackend for HotColdBackend {
     fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()> {
         self.be.warm_up(tpe, id)
     }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
 }
 
 impl WriteBackend for HotColdBackend {
@@ -98,4 +123,10 @@ impl WriteBackend for HotColdBackend {
         }
         Ok(())
     }
+
+    /// Tests the hot and the cold backend and returns the timings of the cold backend.
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        _ = self.be_hot.self_test()?;
+        self.be.self_test()
+    }
 }
@@ -4,8 +4,11 @@ use std::num::TryFromIntError;
 use std::os::unix::fs::{FileTypeExt, MetadataExt};
 
 use std::{
+    collections::HashSet,
     fs::{read_link, File},
+    io::Read,
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
 use bytesize::ByteSize;
@@ -143,6 +146,12 @@ pub struct LocalSourceFilterOptions {
     #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
     pub exclude_if_present: Vec<String>,
 
+    /// Exclude contents of directories containing a CACHEDIR.TAG file with a valid signature.
+    /// The directory and the CACHEDIR.TAG file itself are kept.
+    #[cfg_attr(feature = "clap", clap(long))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
+    pub exclude_cachedirs: bool,
+
     /// Exclude other file systems, don't cross filesystem boundaries and subvolumes
     #[cfg_attr(feature = "clap", clap(long, short = 'x'))]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
@@ -293,17 +302,43 @@ impl LocalSource {
             })?);
 
         let exclude_if_present = filter_opts.exclude_if_present.clone();
-        if !filter_opts.exclude_if_present.is_empty() {
-            _ = walk_builder.filter_entry(move |entry| match entry.file_type() {
-                Some(tpe) if tpe.is_dir() => {
-                    for file in &exclude_if_present {
-                        if entry.path().join(file).exists() {
-                            return false;
-                        }
+        let exclude_cachedirs = filter_opts.exclude_cachedirs;
+        if !exclude_if_present.is_empty() || exclude_cachedirs {
+            // the cache directories found so far; the backup paths are not passed to the filter
+            let cachedirs: HashSet<_> = backup_paths
+                .iter()
+                .map(AsRef::<Path>::as_ref)
+                .filter(|path| exclude_cachedirs && is_cachedir(path))
+                .map(Path::to_path_buf)
+                .collect();
+            let cachedirs = Arc::new(Mutex::new(cachedirs));
+            _ = walk_builder.filter_entry(move |entry| {
+                let is_dir = entry.file_type().is_some_and(|tpe| tpe.is_dir());
+                if is_dir
+                    && exclude_if_present
+                        .iter()
+                        .any(|file| entry.path().join(file).exists())
+                {
+                    return false;
+                }
+                if exclude_cachedirs {
+                    let mut cachedirs = cachedirs.lock().unwrap();
+                    // exclude everything within a cache directory except the tag file itself;
+                    // subdirs are excluded as a whole, so they are not walked at all
+                    if entry.file_name() != CACHEDIR_TAG
+                        && entry
+                            .path()
+                            .parent()
+                            .is_some_and(|parent| cachedirs.contains(parent))
+                    {
+                        return false;
+                    }
+                    // only directories are checked, so each tag file is read once
+                    if is_dir && is_cachedir(entry.path()) {
+                        _ = cachedirs.insert(entry.path().to_path_buf());
                     }
-                    true
                 }
-                _ => true,
+                true
             });
         }
 
@@ -313,6 +348,24 @@ impl LocalSource {
     }
 }
 
+/// Name of the file which tags a cache directory, see <https://bford.info/cachedir/>
+const CACHEDIR_TAG: &str = "CACHEDIR.TAG";
+
+/// Signature a valid CACHEDIR.TAG file must start with
+const CACHEDIR_TAG_SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+/// Checks if the given directory is a cache directory, i.e. contains a CACHEDIR.TAG file with a valid signature
+///
+/// # Arguments
+///
+/// * `dir` - The directory to check
+fn is_cachedir(dir: &Path) -> bool {
+    let mut signature = [0; CACHEDIR_TAG_SIGNATURE.len()];
+    File::open(dir.join(CACHEDIR_TAG))
+        .and_then(|mut file| file.read_exact(&mut signature))
+        .is_ok_and(|()| signature == CACHEDIR_TAG_SIGNATURE)
+}
+
 #[derive(Debug)]
 /// Describes an open file from the local backend.
 pub struct OpenFile(PathBuf);
@@ -564,6 +617,10 @@ fn list_extended_attributes(path: &Path) -> IgnoreResult<Vec<ExtendedAttribute>>
 
 /// List [`ExtendedAttribute`] for a [`Node`] located at `path`
 ///
+/// On Linux, this includes POSIX ACLs which are stored in the `system.posix_acl_access` and
+/// `system.posix_acl_default` attributes. If the filesystem doesn't support extended attributes,
+/// no attributes are returned.
+///
 /// # Argument
 ///
 /// * `path` to the [`Node`] for which to list attributes
@@ -573,11 +630,17 @@ fn list_extended_attributes(path: &Path) -> IgnoreResult<Vec<ExtendedAttribute>>
 /// * If Xattr couldn't be listed or couldn't be read
 #[cfg(all(not(windows), not(target_os = "openbsd")))]
 fn list_extended_attributes(path: &Path) -> IgnoreResult<Vec<ExtendedAttribute>> {
-    xattr::list(path)
-        .map_err(|err| IgnoreErrorKind::ErrorXattr {
-            path: path.to_path_buf(),
-            source: err,
-        })?
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(err) if err.kind() == std::io::ErrorKind::Unsupported => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(IgnoreErrorKind::ErrorXattr {
+                path: path.to_path_buf(),
+                source: err,
+            })
+        }
+    };
+    names
         .map(|name| {
             Ok(ExtendedAttribute {
                 name: name.to_string_lossy().to_string(),
@@ -805,3 +868,142 @@ pub mod mapper {
         mode
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    use tempfile::TempDir;
+
+    /// Create a temp tree with
+    /// - `small` (10 bytes), `large` (10 KiB)
+    /// - `excluded/.nobackup`, `excluded/file`
+    /// - `sub/file`
+    fn temp_tree() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::write(root.join("small"), [0; 10]).unwrap();
+        fs::write(root.join("large"), [0; 10 * 1024]).unwrap();
+        fs::create_dir(root.join("excluded")).unwrap();
+        fs::write(root.join("excluded/.nobackup"), "").unwrap();
+        fs::write(root.join("excluded/file"), "file").unwrap();
+        fs::create_dir(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file"), "file").unwrap();
+        dir
+    }
+
+    fn entries(root: &Path, filter_opts: &LocalSourceFilterOptions) -> Vec<PathBuf> {
+        let source =
+            LocalSource::new(LocalSourceSaveOptions::default(), filter_opts, &[root]).unwrap();
+        source
+            .entries()
+            .map(|entry| {
+                entry
+                    .unwrap()
+                    .path
+                    .strip_prefix(root)
+                    .unwrap()
+                    .to_path_buf()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn all_entries_are_found_without_filter() {
+        let dir = temp_tree();
+        let entries = entries(dir.path(), &LocalSourceFilterOptions::default());
+        assert_eq!(
+            entries,
+            [
+                "excluded",
+                "excluded/.nobackup",
+                "excluded/file",
+                "large",
+                "small",
+                "sub",
+                "sub/file"
+            ]
+            .map(PathBuf::from)
+        );
+    }
+
+    #[test]
+    fn exclude_larger_than_skips_large_files() {
+        let dir = temp_tree();
+        let opts = LocalSourceFilterOptions::default().exclude_larger_than(ByteSize::kib(1));
+        let entries = entries(dir.path(), &opts);
+        assert!(!entries.contains(&PathBuf::from("large")));
+        assert!(entries.contains(&PathBuf::from("small")));
+        // directories are not affected
+        assert!(entries.contains(&PathBuf::from("sub")));
+    }
+
+    #[test]
+    fn exclude_if_present_skips_directory() {
+        let dir = temp_tree();
+        let opts =
+            LocalSourceFilterOptions::default().exclude_if_present(vec![".nobackup".to_string()]);
+        let entries = entries(dir.path(), &opts);
+        assert!(entries.iter().all(|path| !path.starts_with("excluded")));
+        assert!(entries.contains(&PathBuf::from("sub/file")));
+    }
+
+    #[test]
+    fn exclude_cachedirs_skips_cache_contents() {
+        let dir = temp_tree();
+        let root = dir.path();
+        fs::create_dir_all(root.join("cache/sub")).unwrap();
+        fs::write(
+            root.join("cache/CACHEDIR.TAG"),
+            "Signature: 8a477f597d28d172789f06886806bc55\n# a cache directory\n",
+        )
+        .unwrap();
+        fs::write(root.join("cache/file"), "file").unwrap();
+        fs::write(root.join("cache/sub/file"), "file").unwrap();
+        // a tag file with invalid signature is ignored
+        fs::write(root.join("sub/CACHEDIR.TAG"), "no signature").unwrap();
+
+        let opts = LocalSourceFilterOptions::default().exclude_cachedirs(true);
+        let found = entries(root, &opts);
+        assert!(found.contains(&PathBuf::from("cache")));
+        assert!(found.contains(&PathBuf::from("cache/CACHEDIR.TAG")));
+        assert!(!found.contains(&PathBuf::from("cache/file")));
+        assert!(found.iter().all(|path| !path.starts_with("cache/sub")));
+        assert!(found.contains(&PathBuf::from("sub/file")));
+
+        // a backup path which is a cache directory is handled in the same way
+        let found = entries(&root.join("cache"), &opts);
+        assert_eq!(found, [PathBuf::from("CACHEDIR.TAG")]);
+
+        // without the option, cache directories are backed up
+        let found = entries(root, &LocalSourceFilterOptions::default());
+        assert!(found.contains(&PathBuf::from("cache/sub/file")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn one_file_system_does_not_cross_mount_boundaries() {
+        use std::os::unix::fs::MetadataExt;
+
+        // `/dev/pts` usually is a separate `devpts` mount containing `ptmx`
+        let (root, mount) = (Path::new("/dev"), Path::new("/dev/pts"));
+        let device = |path: &Path| fs::metadata(path).map(|meta| meta.dev()).ok();
+        if device(root).is_none() || device(root) == device(mount) {
+            return;
+        }
+
+        let found = |filter_opts: &LocalSourceFilterOptions| -> Vec<PathBuf> {
+            LocalSource::new(LocalSourceSaveOptions::default(), filter_opts, &[root])
+                .unwrap()
+                .entries()
+                .filter_map(|entry| Some(entry.ok()?.path))
+                .collect()
+        };
+        let ptmx = mount.join("ptmx");
+        assert!(found(&LocalSourceFilterOptions::default()).contains(&ptmx));
+        let opts = LocalSourceFilterOptions::default().one_file_system(true);
+        assert!(!found(&opts).contains(&ptmx));
+    }
+}
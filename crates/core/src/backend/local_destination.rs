@@ -2,6 +2,8 @@
 use std::os::unix::fs::{symlink, PermissionsExt};
 
 use std::{
+    collections::HashSet,
+    ffi::OsStr,
     fs::{self, File, OpenOptions},
     io::{Read, Seek, SeekFrom, Write},
     num::TryFromIntError,
@@ -11,9 +13,10 @@ use std::{
 use bytes::Bytes;
 #[allow(unused_imports)]
 use cached::proc_macro::cached;
+use chrono::{DateTime, Local, Utc};
 use filetime::{set_symlink_file_times, FileTime};
-#[cfg(not(windows))]
-use log::warn;
+use ignore::WalkBuilder;
+use log::{debug, error, warn};
 #[cfg(not(windows))]
 use nix::errno::Errno;
 #[cfg(not(windows))]
@@ -29,8 +32,12 @@ use crate::backend::ignore::mapper::map_mode_from_go;
 #[cfg(not(windows))]
 use crate::backend::node::NodeType;
 use crate::{
-    backend::node::{ExtendedAttribute, Metadata, Node},
+    backend::{
+        node::{ExtendedAttribute, Metadata, Node},
+        DestinationEntry, ExistingFile, MetadataOptions, RestoreDestination,
+    },
     error::{ErrorKind, RusticError, RusticResult},
+    id::Id,
 };
 
 /// [`LocalDestinationErrorKind`] describes the errors that can be returned by an action on the filesystem in Backends
@@ -465,6 +472,10 @@ impl LocalDestination {
     #[cfg(not(any(windows, target_os = "openbsd")))]
     /// Set extended attributes for `item` (relative to the base path)
     ///
+    /// Extended attributes which are not contained in `extended_attributes` are removed.
+    /// If the destination doesn't support extended attributes or an attribute can't be set,
+    /// a warning is logged and the remaining attributes are still processed.
+    ///
     /// # Arguments
     ///
     /// * `item` - The item to set the extended attributes for
@@ -474,15 +485,10 @@ impl LocalDestination {
     ///
     /// * If listing the extended attributes failed.
     /// * If getting an extended attribute failed.
-    /// * If setting an extended attribute failed.
     ///
     /// # Returns
     ///
     /// Ok if the extended attributes were set.
-    ///
-    /// # Panics
-    ///
-    /// * If the extended attributes could not be set.
     pub(crate) fn set_extended_attributes(
         &self,
         item: impl AsRef<Path>,
@@ -491,12 +497,36 @@ impl LocalDestination {
         let filename = self.path(item);
         let mut done = vec![false; extended_attributes.len()];
 
-        for curr_name in xattr::list(&filename).map_err(|err| {
-            LocalDestinationErrorKind::ListingXattrsFailed {
-                source: err,
-                path: filename.clone(),
+        let curr_names = match xattr::list(&filename) {
+            Ok(names) => names,
+            Err(err) if err.kind() == std::io::ErrorKind::Unsupported => {
+                if !extended_attributes.is_empty() {
+                    warn!("{filename:?}: destination doesn't support extended attributes, not setting them.");
+                }
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(LocalDestinationErrorKind::ListingXattrsFailed {
+                    source: err,
+                    path: filename,
+                })
+            }
+        };
+
+        let set = |name: &str, value: &[u8]| {
+            if let Err(err) = xattr::set(&filename, name, value) {
+                warn!(
+                    "{}",
+                    LocalDestinationErrorKind::SettingXattrFailed {
+                        name: name.to_string(),
+                        filename: filename.clone(),
+                        source: err,
+                    }
+                );
             }
-        })? {
+        };
+
+        for curr_name in curr_names {
             match extended_attributes.iter().enumerate().find(
                 |(_, ExtendedAttribute { name, .. })| name == curr_name.to_string_lossy().as_ref(),
             ) {
@@ -509,12 +539,7 @@ impl LocalDestination {
                         }
                     })?;
                     if value != &curr_value {
-                        xattr::set(&filename, name, value.as_ref().unwrap_or(&Vec::new()))
-                            .map_err(|err| LocalDestinationErrorKind::SettingXattrFailed {
-                                name: name.clone(),
-                                filename: filename.clone(),
-                                source: err,
-                            })?;
+                        set(name, value.as_deref().unwrap_or_default());
                     }
                     done[index] = true;
                 }
@@ -528,13 +553,7 @@ impl LocalDestination {
 
         for (index, ExtendedAttribute { name, value }) in extended_attributes.iter().enumerate() {
             if !done[index] {
-                xattr::set(&filename, name, value.as_ref().unwrap_or(&Vec::new())).map_err(
-                    |err| LocalDestinationErrorKind::SettingXattrFailed {
-                        name: name.clone(),
-                        filename: filename.clone(),
-                        source: err,
-                    },
-                )?;
+                set(name, value.as_deref().unwrap_or_default());
             }
         }
 
@@ -806,3 +825,209 @@ impl LocalDestination {
         Ok(())
     }
 }
+
+impl RestoreDestination for LocalDestination {
+    type File = File;
+
+    fn path(&self, item: &Path) -> PathBuf {
+        Self::path(self, item)
+    }
+
+    fn existing_entries(&self) -> Box<dyn Iterator<Item = DestinationEntry> + '_> {
+        let entries = WalkBuilder::new(Self::path(self, ""))
+            .follow_links(false)
+            .hidden(false)
+            .ignore(false)
+            .sort_by_file_path(Path::cmp)
+            .build()
+            .inspect(|r| {
+                if let Err(err) = r {
+                    error!("Error during collection of files: {err:?}");
+                }
+            })
+            .filter_map(Result::ok)
+            .map(|entry| DestinationEntry {
+                is_dir: entry.file_type().is_some_and(|t| t.is_dir()),
+                is_file: entry.file_type().is_some_and(|t| t.is_file()),
+                path: entry.into_path(),
+            });
+        Box::new(entries)
+    }
+
+    fn is_case_sensitive(&self, dry_run: bool) -> RusticResult<bool> {
+        let dir = if self.is_file {
+            self.path.parent().unwrap_or_else(|| Path::new(""))
+        } else {
+            &self.path
+        };
+        // the destination may not exist yet, so use the nearest existing dir which usually is on the same filesystem
+        let dir = dir
+            .ancestors()
+            .find(|dir| dir.is_dir())
+            .unwrap_or_else(|| Path::new("."));
+        let io_error = |err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to probe the case sensitivity of the directory `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", dir.display().to_string())
+        };
+
+        // first try to use an existing entry to not modify the destination
+        let names: HashSet<_> = fs::read_dir(dir)
+            .map_err(io_error)?
+            .filter_map(|entry| Some(entry.ok()?.file_name()))
+            .collect();
+        // only use ASCII names, as case folding of other characters differs between filesystems
+        for name in &names {
+            let Some(name) = name.to_str().filter(|name| name.is_ascii()) else {
+                continue;
+            };
+            let upper = name.to_ascii_uppercase();
+            let swapped = if upper == name {
+                name.to_ascii_lowercase()
+            } else {
+                upper
+            };
+            if swapped == name {
+                continue;
+            }
+            // if the name with swapped case exists but is not listed, it refers to the same entry
+            return Ok(names.contains(OsStr::new(&swapped))
+                || fs::symlink_metadata(dir.join(&swapped)).is_err());
+        }
+
+        if dry_run {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "The case sensitivity of the directory `{path}` can't be determined without writing to it.",
+            )
+            .attach_context("path", dir.display().to_string()));
+        }
+
+        // create a probe file with a lower case name and check if the upper case name exists
+        let name = format!(".rustic-case-probe-{}", Id::random());
+        let probe = dir.join(&name);
+        _ = File::create(&probe).map_err(io_error)?;
+        let case_sensitive = fs::symlink_metadata(dir.join(name.to_ascii_uppercase())).is_err();
+        fs::remove_file(&probe).map_err(io_error)?;
+        Ok(case_sensitive)
+    }
+
+    fn get_matching_file(
+        &self,
+        item: &Path,
+        size: u64,
+    ) -> RusticResult<Option<ExistingFile<File>>> {
+        let Some(file) = Self::get_matching_file(self, item, size) else {
+            return Ok(None);
+        };
+        let meta = file.metadata().map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to get the metadata of the file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", item.display().to_string())
+        })?;
+        let mtime = meta
+            .modified()
+            .ok()
+            .map(|t| DateTime::<Utc>::from(t).with_timezone(&Local));
+        Ok(Some(ExistingFile { mtime, file }))
+    }
+
+    fn remove_dir(&self, path: &Path) -> RusticResult<()> {
+        Self::remove_dir(self, path).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to remove the directory `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", path.display().to_string())
+        })
+    }
+
+    fn remove_file(&self, path: &Path) -> RusticResult<()> {
+        Self::remove_file(self, path).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to remove the file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", path.display().to_string())
+        })
+    }
+
+    fn create_dir(&self, item: &Path) -> RusticResult<()> {
+        Self::create_dir(self, item).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to create the directory `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", item.display().to_string())
+        })
+    }
+
+    fn set_length(&self, item: &Path, size: u64) -> RusticResult<()> {
+        Self::set_length(self, item, size).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to set the length of the file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", item.display().to_string())
+        })
+    }
+
+    fn write_at(&self, item: &Path, offset: u64, data: &[u8]) -> RusticResult<()> {
+        Self::write_at(self, item, offset, data).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to write to the file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", item.display().to_string())
+        })
+    }
+
+    fn read_at(&self, item: &Path, offset: u64, length: u64) -> RusticResult<Bytes> {
+        Self::read_at(self, item, offset, length).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to read from the existing file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", item.display().to_string())
+        })
+    }
+
+    fn set_metadata(&self, item: &Path, node: &Node, opts: MetadataOptions) -> RusticResult<()> {
+        debug!("setting metadata for {:?}", item);
+        self.create_special(item, node)
+            .unwrap_or_else(|_| warn!("restore {:?}: creating special file failed.", item));
+        match (opts.no_ownership, opts.numeric_id) {
+            (true, _) => {}
+            (false, true) => self
+                .set_uid_gid(item, &node.meta)
+                .unwrap_or_else(|_| warn!("restore {:?}: setting UID/GID failed.", item)),
+            (false, false) => self
+                .set_user_group(item, &node.meta)
+                .unwrap_or_else(|_| warn!("restore {:?}: setting User/Group failed.", item)),
+        }
+        self.set_permission(item, node)
+            .unwrap_or_else(|_| warn!("restore {:?}: chmod failed.", item));
+        self.set_extended_attributes(item, &node.meta.extended_attributes)
+            .unwrap_or_else(|err| {
+                warn!(
+                    "restore {:?}: setting extended attributes failed: {err}",
+                    item
+                );
+            });
+        self.set_times(item, &node.meta)
+            .unwrap_or_else(|_| warn!("restore {:?}: setting file times failed.", item));
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use log::warn;
+
+use crate::{
+    backend::{FileType, PartialChunks, ReadBackend, SelfTestResult, WriteBackend},
+    error::RusticResult,
+    id::Id,
+};
+
+/// How to handle a failed modification of a mirror backend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MirrorFailureMode {
+    /// Abort the operation with an error
+    #[default]
+    Fatal,
+    /// Log a warning and continue
+    Warn,
+}
+
+/// A backend which mirrors all modifications to other backends.
+///
+/// All writes and removals are done on the main backend and then on all mirror backends.
+/// All reads are done from the main backend only.
+///
+/// As the data is written as-is, the mirror backends contain exact copies of the repository files.
+/// Mirrors must therefore either be empty when the repository is initialized or already contain a copy
+/// of the repository.
+#[derive(Clone, Debug)]
+pub struct MirrorBackend {
+    /// The backend to use.
+    be: Arc<dyn WriteBackend>,
+    /// The backends to mirror all modifications to.
+    mirrors: Vec<Arc<dyn WriteBackend>>,
+    /// How to handle failed modifications of mirror backends.
+    on_failure: MirrorFailureMode,
+}
+
+impl MirrorBackend {
+    /// Creates a new `MirrorBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to use.
+    /// * `mirrors` - The backends to mirror all modifications to.
+    /// * `on_failure` - How to handle failed modifications of mirror backends.
+    pub fn new(
+        be: Arc<dyn WriteBackend>,
+        mirrors: Vec<Arc<dyn WriteBackend>>,
+        on_failure: MirrorFailureMode,
+    ) -> Self {
+        Self {
+            be,
+            mirrors,
+            on_failure,
+        }
+    }
+
+    /// Runs the given modification on all mirror backends.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The name of the operation, used for error messages.
+    /// * `f` - The modification to run.
+    ///
+    /// # Errors
+    ///
+    /// * If the modification failed on a mirror backend and failures are fatal.
+    fn on_mirrors(
+        &self,
+        operation: &str,
+        f: impl Fn(&Arc<dyn WriteBackend>) -> RusticResult<()>,
+    ) -> RusticResult<()> {
+        for mirror in &self.mirrors {
+            if let Err(err) = f(mirror) {
+                match self.on_failure {
+                    MirrorFailureMode::Fatal => {
+                        return Err(err
+                            .prepend_guidance_line(
+                                "Failed to {operation} on mirror backend `{mirror}`.",
+                            )
+                            .attach_context("operation", operation)
+                            .attach_context("mirror", mirror.location()));
+                    }
+                    MirrorFailureMode::Warn => warn!(
+                        "failed to {operation} on mirror backend {}: {}",
+                        mirror.location(),
+                        err.display_log()
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ReadBackend for MirrorBackend {
+    fn location(&self) -> String {
+        self.be.location()
+    }
+
+    fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+        self.be.list_with_size(tpe)
+    }
+
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
+    fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
+        self.be.read_full(tpe, id)
+    }
+
+    fn read_partial(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<Bytes> {
+        self.be.read_partial(tpe, id, cacheable, offset, length)
+    }
+
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
+
+    fn needs_warm_up(&self) -> bool {
+        self.be.needs_warm_up()
+    }
+
+    fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()> {
+        self.be.warm_up(tpe, id)
+    }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
+}
+
+impl WriteBackend for MirrorBackend {
+    fn create(&self) -> RusticResult<()> {
+        self.be.create()?;
+        self.on_mirrors("create repository", |mirror| mirror.create())
+    }
+
+    fn write_bytes(&self, tpe: FileType, id: &Id, cacheable: bool, buf: Bytes) -> RusticResult<()> {
+        self.be.write_bytes(tpe, id, cacheable, buf.clone())?;
+        self.on_mirrors("write file", |mirror| {
+            mirror.write_bytes(tpe, id, cacheable, buf.clone())
+        })
+    }
+
+    fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()> {
+        self.be.remove(tpe, id, cacheable)?;
+        self.on_mirrors("remove file", |mirror| mirror.remove(tpe, id, cacheable))
+    }
+
+    /// Tests the mirror backends and the main backend and returns the timings of the main backend.
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        self.on_mirrors("run self-test", |mirror| mirror.self_test().map(|_| ()))?;
+        self.be.self_test()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        backend::MockBackend,
+        error::{ErrorKind, RusticError},
+    };
+
+    fn mirror_backend(mirror_ok: bool, on_failure: MirrorFailureMode) -> MirrorBackend {
+        let mut be = MockBackend::new();
+        _ = be
+            .expect_write_bytes()
+            .times(1)
+            .returning(|_, _, _, _| Ok(()));
+        let mut mirror = MockBackend::new();
+        _ = mirror.expect_location().return_const("mirror".to_string());
+        _ = mirror
+            .expect_write_bytes()
+            .times(1)
+            .returning(move |_, _, _, _| {
+                if mirror_ok {
+                    Ok(())
+                } else {
+                    Err(RusticError::new(
+                        ErrorKind::Backend,
+                        "Mirror not reachable.",
+                    ))
+                }
+            });
+        MirrorBackend::new(Arc::new(be), vec![Arc::new(mirror)], on_failure)
+    }
+
+    #[test]
+    fn mirror_backend_writes_to_all_backends() {
+        let be = mirror_backend(true, MirrorFailureMode::Fatal);
+        assert!(be
+            .write_bytes(FileType::Snapshot, &Id::default(), true, Bytes::new())
+            .is_ok());
+    }
+
+    #[test]
+    fn mirror_backend_handles_failures() {
+        let be = mirror_backend(false, MirrorFailureMode::Fatal);
+        assert!(be
+            .write_bytes(FileType::Snapshot, &Id::default(), true, Bytes::new())
+            .is_err());
+
+        let be = mirror_backend(false, MirrorFailureMode::Warn);
+        assert!(be
+            .write_bytes(FileType::Snapshot, &Id::default(), true, Bytes::new())
+            .is_ok());
+    }
+}
@@ -2,7 +2,7 @@ use std::{
     cmp::Ordering,
     ffi::{OsStr, OsString},
     fmt::Debug,
-    path::Path,
+    path::{Component, Path, PathBuf},
     str::FromStr,
 };
 
@@ -23,7 +23,12 @@ use serde_with::{
     serde_as, skip_serializing_none, DefaultOnNull,
 };
 
-use crate::blob::{tree::TreeId, DataId};
+#[cfg(not(windows))]
+use crate::backend::ignore::mapper::{map_mode_from_go, map_mode_to_go};
+use crate::{
+    blob::{tree::TreeId, DataId},
+    error::{ErrorKind, RusticError, RusticResult},
+};
 
 #[cfg(not(windows))]
 /// [`NodeErrorKind`] describes the errors that can be returned by an action utilizing a node in Backends
@@ -362,6 +367,282 @@ impl Node {
     pub fn name(&self) -> OsString {
         unescape_filename(&self.name).unwrap_or_else(|_| OsString::from_str(&self.name).unwrap())
     }
+
+    #[must_use]
+    /// Get a summary of the node metadata similar to [`std::fs::Metadata`], e.g. for display
+    pub fn as_fs_metadata_summary(&self) -> FsMetadataSummary {
+        let file_type = match self.node_type {
+            NodeType::File => "file",
+            NodeType::Dir => "dir",
+            NodeType::Symlink { .. } => "symlink",
+            NodeType::Dev { .. } => "dev",
+            NodeType::Chardev { .. } => "chardev",
+            NodeType::Fifo => "fifo",
+            NodeType::Socket => "socket",
+        };
+        #[cfg(not(windows))]
+        let permissions = self.meta.mode.map(|mode| map_mode_from_go(mode) & 0o7777);
+        #[cfg(windows)]
+        let permissions = self.meta.mode.map(|mode| mode & 0o7777);
+
+        FsMetadataSummary {
+            file_type: file_type.to_string(),
+            len: self.meta.size,
+            permissions,
+            modified: self.meta.mtime,
+            accessed: self.meta.atime,
+            changed: self.meta.ctime,
+            uid: self.meta.uid,
+            gid: self.meta.gid,
+            user: self.meta.user.clone(),
+            group: self.meta.group.clone(),
+            link_target: self
+                .is_symlink()
+                .then(|| self.node_type.to_link().to_path_buf()),
+        }
+    }
+}
+
+/// A summary of the metadata of a [`Node`] similar to [`std::fs::Metadata`]
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FsMetadataSummary {
+    /// Type of the node, e.g. `file`, `dir` or `symlink`
+    pub file_type: String,
+    /// Size of the node
+    pub len: u64,
+    /// Unix permission bits including setuid, setgid and sticky bit
+    pub permissions: Option<u32>,
+    /// Last modification time
+    pub modified: Option<DateTime<Local>>,
+    /// Last access time
+    pub accessed: Option<DateTime<Local>>,
+    /// Last status change time
+    pub changed: Option<DateTime<Local>>,
+    /// Unix uid (user id)
+    pub uid: Option<u32>,
+    /// Unix gid (group id)
+    pub gid: Option<u32>,
+    /// Unix user name
+    pub user: Option<String>,
+    /// Unix group name
+    pub group: Option<String>,
+    /// Target of a symlink
+    pub link_target: Option<PathBuf>,
+}
+
+/// A builder for synthetic [`Node`]s, e.g. to inject generated files into a merge.
+///
+/// The invariants of the node type are checked when calling [`NodeBuilder::build`].
+#[derive(Clone, Debug)]
+pub struct NodeBuilder {
+    /// Name of the node
+    name: OsString,
+    /// Type of the node
+    node_type: NodeType,
+    /// Metadata of the node
+    meta: Metadata,
+    /// Contents of the node
+    content: Option<Vec<DataId>>,
+    /// Subtree of the node
+    subtree: Option<TreeId>,
+    /// Unix permission bits
+    mode: Option<u32>,
+}
+
+impl NodeBuilder {
+    /// Create a new builder
+    fn new(name: &OsStr, node_type: NodeType) -> Self {
+        Self {
+            name: name.to_os_string(),
+            node_type,
+            meta: Metadata::default(),
+            content: None,
+            subtree: None,
+            mode: None,
+        }
+    }
+
+    /// Create a builder for a regular file
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the file
+    #[must_use]
+    pub fn file(name: impl AsRef<OsStr>) -> Self {
+        Self::new(name.as_ref(), NodeType::File)
+    }
+
+    /// Create a builder for a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the directory
+    /// * `subtree` - The tree containing the directory contents
+    #[must_use]
+    pub fn dir(name: impl AsRef<OsStr>, subtree: TreeId) -> Self {
+        let mut builder = Self::new(name.as_ref(), NodeType::Dir);
+        builder.subtree = Some(subtree);
+        builder
+    }
+
+    /// Create a builder for a symlink
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the symlink
+    /// * `target` - Target of the symlink
+    #[must_use]
+    pub fn symlink(name: impl AsRef<OsStr>, target: impl AsRef<Path>) -> Self {
+        Self::new(name.as_ref(), NodeType::from_link(target.as_ref()))
+    }
+
+    /// Set the size of the node
+    #[must_use]
+    pub const fn size(mut self, size: u64) -> Self {
+        self.meta.size = size;
+        self
+    }
+
+    /// Set the modification time of the node
+    #[must_use]
+    pub const fn mtime(mut self, mtime: DateTime<Local>) -> Self {
+        self.meta.mtime = Some(mtime);
+        self
+    }
+
+    /// Set the unix permission bits (including setuid, setgid and sticky bit) of the node
+    #[must_use]
+    pub const fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Set the unix user id of the node
+    #[must_use]
+    pub const fn uid(mut self, uid: u32) -> Self {
+        self.meta.uid = Some(uid);
+        self
+    }
+
+    /// Set the unix group id of the node
+    #[must_use]
+    pub const fn gid(mut self, gid: u32) -> Self {
+        self.meta.gid = Some(gid);
+        self
+    }
+
+    /// Add an extended attribute to the node
+    #[must_use]
+    pub fn extended_attribute(mut self, name: impl Into<String>, value: Option<Vec<u8>>) -> Self {
+        self.meta.extended_attributes.push(ExtendedAttribute {
+            name: name.into(),
+            value,
+        });
+        self
+    }
+
+    /// Set the content of a file, i.e. the ids of the data blobs which must already exist in the repository
+    #[must_use]
+    pub fn content(mut self, content: Vec<DataId>) -> Self {
+        self.content = Some(content);
+        self
+    }
+
+    /// Build the [`Node`]
+    ///
+    /// # Errors
+    ///
+    /// * If the name is empty or not a single path component.
+    /// * If the mode contains bits other than permission bits.
+    /// * If an extended attribute name is empty or given multiple times.
+    /// * If content is given for a node which is not a file.
+    /// * If a file has a non-zero size, but no content.
+    pub fn build(self) -> RusticResult<Node> {
+        let mut components = Path::new(&self.name).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(name)), None) if name == self.name
+        ) {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Node name `{name}` is invalid. It must be a single path component.",
+            )
+            .attach_context("name", self.name.to_string_lossy()));
+        }
+
+        let mut meta = self.meta;
+        if let Some(mode) = self.mode {
+            if mode & !0o7777 != 0 {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Mode `{mode}` is invalid. Only permission bits are allowed.",
+                )
+                .attach_context("mode", format!("{mode:o}")));
+            }
+            let file_type = match self.node_type {
+                NodeType::Dir => 0o040_000,
+                NodeType::Symlink { .. } => 0o120_000,
+                _ => 0o100_000,
+            };
+            #[cfg(not(windows))]
+            let mode = map_mode_to_go(mode | file_type);
+            #[cfg(windows)]
+            let mode = mode | file_type;
+            meta.mode = Some(mode);
+        }
+
+        let mut names: Vec<_> = meta
+            .extended_attributes
+            .iter()
+            .map(|attr| attr.name.as_str())
+            .collect();
+        names.sort_unstable();
+        if let Some(name) = names.iter().find(|name| name.is_empty()) {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Extended attribute name `{name}` is invalid. It must not be empty.",
+            )
+            .attach_context("name", *name));
+        }
+        if let Some(name) = names.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]) {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Extended attribute `{name}` is given multiple times.",
+            )
+            .attach_context("name", name));
+        }
+
+        let content = match self.node_type {
+            NodeType::File => {
+                let content = self.content.unwrap_or_default();
+                if content.is_empty() && meta.size > 0 {
+                    return Err(RusticError::new(
+                        ErrorKind::InvalidInput,
+                        "File `{name}` has size `{size}`, but no content.",
+                    )
+                    .attach_context("name", self.name.to_string_lossy())
+                    .attach_context("size", meta.size.to_string()));
+                }
+                Some(content)
+            }
+            _ if self.content.is_some() => {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Node `{name}` of type `{node_type}` cannot have content.",
+                )
+                .attach_context("name", self.name.to_string_lossy())
+                .attach_context("node_type", self.node_type.to_string()));
+            }
+            _ => None,
+        };
+
+        let mut node = Node::new_node(&self.name, self.node_type, meta);
+        node.content = content;
+        node.subtree = self.subtree;
+        Ok(node)
+    }
 }
 
 /// An ordering function returning the latest node by mtime
@@ -383,7 +664,7 @@ pub fn last_modified_node(n1: &Node, n2: &Node) -> Ordering {
 // TODO(Windows): This is not able to handle non-unicode filenames and
 // doesn't treat filenames which need and escape (like `\`, `"`, ...) correctly
 #[cfg(windows)]
-fn escape_filename(name: &OsStr) -> String {
+pub(crate) fn escape_filename(name: &OsStr) -> String {
     name.to_string_lossy().to_string()
 }
 
@@ -407,7 +688,7 @@ fn unescape_filename(s: &str) -> Result<OsString, core::convert::Infallible> {
 // stconv.Quote, see https://pkg.go.dev/strconv#Quote
 // However, so far there was no specification what Quote really does, so this
 // is some kind of try-and-error and maybe does not cover every case.
-fn escape_filename(name: &OsStr) -> String {
+pub(crate) fn escape_filename(name: &OsStr) -> String {
     let mut input = name.as_bytes();
     let mut s = String::with_capacity(name.len());
 
@@ -644,4 +925,49 @@ mod tests {
         let path = Path::new(OsStr::from_bytes(&bytes));
         path == NodeType::from_link(path).to_link()
     }
+
+    #[test]
+    fn node_builder_passes() {
+        let file = NodeBuilder::file("a")
+            .mode(0o640)
+            .uid(1000)
+            .extended_attribute("user.a", Some(b"b".to_vec()))
+            .build()
+            .unwrap();
+        assert!(file.is_file());
+        assert_eq!(file.content, Some(Vec::new()));
+        let summary = file.as_fs_metadata_summary();
+        assert_eq!(summary.file_type, "file");
+        assert_eq!(summary.permissions, Some(0o640));
+        assert_eq!(summary.uid, Some(1000));
+
+        let dir = NodeBuilder::dir("d", TreeId::default())
+            .mode(0o755)
+            .build()
+            .unwrap();
+        assert!(dir.is_dir());
+        assert_eq!(dir.subtree, Some(TreeId::default()));
+        assert_eq!(dir.as_fs_metadata_summary().permissions, Some(0o755));
+
+        let link = NodeBuilder::symlink("l", "target").build().unwrap();
+        assert_eq!(
+            link.as_fs_metadata_summary().link_target,
+            Some(PathBuf::from("target"))
+        );
+    }
+
+    #[rstest]
+    #[case(NodeBuilder::file(""))]
+    #[case(NodeBuilder::file("."))]
+    #[case(NodeBuilder::file(".."))]
+    #[case(NodeBuilder::file("a/b"))]
+    #[case(NodeBuilder::file("a/"))]
+    #[case(NodeBuilder::file("a").mode(0o170_644))]
+    #[case(NodeBuilder::file("a").size(1))]
+    #[case(NodeBuilder::file("a").extended_attribute("", None))]
+    #[case(NodeBuilder::file("a").extended_attribute("x", None).extended_attribute("x", None))]
+    #[case(NodeBuilder::symlink("l", "target").content(Vec::new()))]
+    fn node_builder_fails(#[case] builder: NodeBuilder) {
+        assert!(builder.build().is_err());
+    }
 }
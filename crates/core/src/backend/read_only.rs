@@ -0,0 +1,163 @@
+use std::{sync::Arc, time::Instant};
+
+use bytes::Bytes;
+
+use crate::{
+    backend::{FileType, PartialChunks, ReadBackend, SelfTestResult, WriteBackend},
+    error::{ErrorKind, RusticError, RusticResult},
+    id::Id,
+};
+
+/// A backend which refuses all modifications of the underlying backend.
+#[derive(Clone, Debug)]
+pub struct ReadOnlyBackend {
+    /// The backend to use.
+    be: Arc<dyn WriteBackend>,
+}
+
+impl ReadOnlyBackend {
+    /// Creates a new `ReadOnlyBackend`.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to use.
+    pub fn new_read_only(be: Arc<dyn WriteBackend>) -> Arc<dyn WriteBackend> {
+        Arc::new(Self { be })
+    }
+
+    /// Returns the error for a refused modification.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The refused operation.
+    fn error(&self, operation: &str) -> Box<RusticError> {
+        RusticError::new(
+            ErrorKind::ReadOnly,
+            "Repository `{location}` is opened in read-only mode and `{operation}` is not allowed. Aborting.",
+        )
+        .attach_context("location", self.be.location())
+        .attach_context("operation", operation)
+    }
+}
+
+impl ReadBackend for ReadOnlyBackend {
+    fn location(&self) -> String {
+        self.be.location()
+    }
+
+    fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+        self.be.list_with_size(tpe)
+    }
+
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
+    fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
+        self.be.read_full(tpe, id)
+    }
+
+    fn read_partial(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<Bytes> {
+        self.be.read_partial(tpe, id, cacheable, offset, length)
+    }
+
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
+
+    fn needs_warm_up(&self) -> bool {
+        self.be.needs_warm_up()
+    }
+
+    fn warm_up(&self, tpe: FileType, id: &Id) -> RusticResult<()> {
+        self.be.warm_up(tpe, id)
+    }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
+}
+
+impl WriteBackend for ReadOnlyBackend {
+    fn create(&self) -> RusticResult<()> {
+        Err(self.error("create"))
+    }
+
+    fn write_bytes(
+        &self,
+        _tpe: FileType,
+        _id: &Id,
+        _cacheable: bool,
+        _buf: Bytes,
+    ) -> RusticResult<()> {
+        Err(self.error("write"))
+    }
+
+    fn remove(&self, _tpe: FileType, _id: &Id, _cacheable: bool) -> RusticResult<()> {
+        Err(self.error("remove"))
+    }
+
+    /// Only checks that listing works, as writing is not allowed.
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        let start = Instant::now();
+        _ = self.be.list(FileType::Lock)?;
+        Ok(SelfTestResult {
+            list: start.elapsed(),
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::backend::MockBackend;
+
+    #[test]
+    fn read_only_backend_refuses_modifications() {
+        let mut mock = MockBackend::new();
+        _ = mock.expect_location().return_const("mock".to_string());
+        _ = mock
+            .expect_read_full()
+            .returning(|_, _| Ok(Bytes::from_static(b"data")));
+        let be = ReadOnlyBackend::new_read_only(Arc::new(mock));
+
+        assert_eq!(
+            be.read_full(FileType::Snapshot, &Id::default()).unwrap(),
+            b"data"[..]
+        );
+        assert!(be.create().is_err());
+        assert!(be
+            .write_bytes(FileType::Snapshot, &Id::default(), true, Bytes::new())
+            .is_err());
+        assert!(be.remove(FileType::Snapshot, &Id::default(), true).is_err());
+    }
+
+    #[test]
+    fn read_only_backend_self_test_only_lists() {
+        let mut mock = MockBackend::new();
+        _ = mock.expect_list().times(1).returning(|_| Ok(Vec::new()));
+        let be = ReadOnlyBackend::new_read_only(Arc::new(mock));
+
+        let result = be.self_test().unwrap();
+        assert!(result.write.is_none());
+        assert!(result.read.is_none());
+        assert!(result.remove.is_none());
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    io::Read,
+    iter::{once, Once},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::{
+    backend::{ReadSource, ReadSourceEntry},
+    error::{ErrorKind, RusticError, RusticResult},
+};
+
+/// The `ReaderSource` is a `ReadSource` for a single entry read from an arbitrary reader.
+#[derive(Debug)]
+pub struct ReaderSource<R> {
+    /// The path of the entry.
+    path: PathBuf,
+    /// The reader
+    ///
+    /// # Note
+    ///
+    /// This is in a Mutex as we want to take out the reader
+    /// in the `entries` method - but this method only gets a
+    /// reference of self.
+    reader: Mutex<Option<R>>,
+}
+
+impl<R> ReaderSource<R> {
+    /// Creates a new `ReaderSource`.
+    pub const fn new(path: PathBuf, reader: R) -> Self {
+        Self {
+            path,
+            reader: Mutex::new(Some(reader)),
+        }
+    }
+}
+
+impl<R: Read + Send + 'static> ReadSource for ReaderSource<R> {
+    /// The open type.
+    type Open = R;
+    /// The iterator type.
+    type Iter = Once<RusticResult<ReadSourceEntry<R>>>;
+
+    /// Returns the size of the source, which is unknown for a reader.
+    fn size(&self) -> RusticResult<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Returns an iterator over the source.
+    fn entries(&self) -> Self::Iter {
+        let open = self.reader.lock().unwrap().take();
+        once(
+            ReadSourceEntry::from_path(self.path.clone(), open).map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Backend,
+                    "Failed to create ReadSourceEntry from reader",
+                    err,
+                )
+            }),
+        )
+    }
+}
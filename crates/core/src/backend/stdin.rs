@@ -1,25 +1,23 @@
 use std::{
     io::{stdin, Stdin},
-    iter::{once, Once},
     path::PathBuf,
 };
 
 use crate::{
-    backend::{ReadSource, ReadSourceEntry},
-    error::{ErrorKind, RusticError, RusticResult},
+    backend::{reader::ReaderSource, ReadSource},
+    error::RusticResult,
 };
 
 /// The `StdinSource` is a `ReadSource` for stdin.
-#[derive(Debug, Clone)]
-pub struct StdinSource {
-    /// The path of the stdin entry.
-    path: PathBuf,
-}
+///
+/// This is a [`ReaderSource`] reading from [`Stdin`].
+#[derive(Debug)]
+pub struct StdinSource(ReaderSource<Stdin>);
 
 impl StdinSource {
     /// Creates a new `StdinSource`.
-    pub const fn new(path: PathBuf) -> Self {
-        Self { path }
+    pub fn new(path: PathBuf) -> Self {
+        Self(ReaderSource::new(path, stdin()))
     }
 }
 
@@ -27,24 +25,15 @@ impl ReadSource for StdinSource {
     /// The open type.
     type Open = Stdin;
     /// The iterator type.
-    type Iter = Once<RusticResult<ReadSourceEntry<Stdin>>>;
+    type Iter = <ReaderSource<Stdin> as ReadSource>::Iter;
 
     /// Returns the size of the source.
     fn size(&self) -> RusticResult<Option<u64>> {
-        Ok(None)
+        self.0.size()
     }
 
     /// Returns an iterator over the source.
     fn entries(&self) -> Self::Iter {
-        let open = Some(stdin());
-        once(
-            ReadSourceEntry::from_path(self.path.clone(), open).map_err(|err| {
-                RusticError::with_source(
-                    ErrorKind::Backend,
-                    "Failed to create ReadSourceEntry from Stdin",
-                    err,
-                )
-            }),
-        )
+        self.0.entries()
     }
 }
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use bytes::Bytes;
 
 use crate::{
-    backend::{FileType, ReadBackend, WriteBackend},
+    backend::{FileType, PartialChunks, ReadBackend, SelfTestResult, WriteBackend},
     error::RusticResult,
     id::Id,
 };
@@ -35,6 +35,10 @@ impl ReadBackend for WarmUpAccessBackend {
         self.be.list_with_size(tpe)
     }
 
+    fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+        self.be.file_size(tpe, id)
+    }
+
     fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
         self.be.read_full(tpe, id)
     }
@@ -50,6 +54,18 @@ impl ReadBackend for WarmUpAccessBackend {
         self.be.read_partial(tpe, id, cacheable, offset, length)
     }
 
+    fn read_partial_streaming(
+        &self,
+        tpe: FileType,
+        id: &Id,
+        cacheable: bool,
+        offset: u32,
+        length: u32,
+    ) -> RusticResult<PartialChunks<'_>> {
+        self.be
+            .read_partial_streaming(tpe, id, cacheable, offset, length)
+    }
+
     fn needs_warm_up(&self) -> bool {
         true
     }
@@ -59,6 +75,10 @@ impl ReadBackend for WarmUpAccessBackend {
         _ = self.be.read_partial(tpe, id, false, 0, 1);
         Ok(())
     }
+
+    fn is_warm(&self, tpe: FileType, id: &Id) -> RusticResult<bool> {
+        self.be.is_warm(tpe, id)
+    }
 }
 
 impl WriteBackend for WarmUpAccessBackend {
@@ -74,4 +94,8 @@ impl WriteBackend for WarmUpAccessBackend {
         // First remove cold file
         self.be.remove(tpe, id, cacheable)
     }
+
+    fn self_test(&self) -> RusticResult<SelfTestResult> {
+        self.be.self_test()
+    }
 }
@@ -43,7 +43,7 @@ pub enum PackerErrorKind {
         size_limit: Option<u32>,
         id: BlobId,
         data: Bytes,
-        source: crossbeam_channel::SendError<(Bytes, BlobId, Option<u32>)>,
+        source: crossbeam_channel::SendError<(Bytes, BlobId, Option<u32>, bool)>,
     },
     /// Sending crossbeam data message failed: `data`: `{data:?}`, `index_pack`: `{index_pack:?}` : `{source}`
     SendingCrossbeamDataMessage {
@@ -192,7 +192,7 @@ pub struct Packer<BE: DecryptWriteBackend> {
     /// The shared indexer containing the backend.
     indexer: SharedIndexer<BE>,
     /// The sender to send blobs to the raw packer.
-    sender: Sender<(Bytes, BlobId, Option<u32>)>,
+    sender: Sender<(Bytes, BlobId, Option<u32>, bool)>,
     /// The receiver to receive the status from the raw packer.
     finish: Receiver<RusticResult<PackerStats>>,
 }
@@ -216,13 +216,46 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
     ///
     /// * If sending the message to the raw packer fails.
     /// * If converting the data length to u64 fails
-    #[allow(clippy::unnecessary_wraps)]
     pub fn new(
         be: BE,
         blob_type: BlobType,
         indexer: SharedIndexer<BE>,
         config: &ConfigFile,
         total_size: u64,
+    ) -> RusticResult<Self> {
+        Self::new_with_write_concurrency(be, blob_type, indexer, config, total_size, 1)
+    }
+
+    /// Creates a new `Packer` which writes up to `write_concurrency` pack files in parallel.
+    ///
+    /// Each pack file which is being written is held in memory, so the memory usage grows linearly with
+    /// `write_concurrency`; apart from these only a fixed small number of finished pack files is queued.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `BE` - The backend type.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to write to.
+    /// * `blob_type` - The blob type.
+    /// * `indexer` - The indexer to write to.
+    /// * `config` - The config file.
+    /// * `total_size` - The total size of the pack file.
+    /// * `write_concurrency` - The number of pack files to write in parallel.
+    ///
+    /// # Errors
+    ///
+    /// * If sending the message to the raw packer fails.
+    /// * If converting the data length to u64 fails
+    #[allow(clippy::unnecessary_wraps)]
+    pub fn new_with_write_concurrency(
+        be: BE,
+        blob_type: BlobType,
+        indexer: SharedIndexer<BE>,
+        config: &ConfigFile,
+        total_size: u64,
+        write_concurrency: usize,
     ) -> RusticResult<Self> {
         let raw_packer = Arc::new(RwLock::new(RawPacker::new(
             be.clone(),
@@ -230,6 +263,7 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
             indexer.clone(),
             config,
             total_size,
+            write_concurrency,
         )));
 
         let (tx, rx) = bounded(0);
@@ -247,13 +281,17 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
                     .into_iter()
                     .readahead_scoped(scope)
                     // early check if id is already contained
-                    .filter(|(_, id, _)| !indexer.read().unwrap().has(id))
-                    .filter(|(_, id, _)| !raw_packer.read().unwrap().has(id))
+                    .filter(|(_, id, _, _)| !indexer.read().unwrap().has(id))
+                    .filter(|(_, id, _, _)| !raw_packer.read().unwrap().has(id))
                     .readahead_scoped(scope)
                     .parallel_map_scoped(
                         scope,
-                        |(data, id, size_limit): (Bytes, BlobId, Option<u32>)| {
-                            let (data, data_len, uncompressed_length) = be.process_data(&data)?;
+                        |(data, id, size_limit, compress): (Bytes, BlobId, Option<u32>, bool)| {
+                            let (data, data_len, uncompressed_length) = if compress {
+                                be.process_data(&data)?
+                            } else {
+                                be.process_data_uncompressed(&data)?
+                            };
                             Ok((
                                 data,
                                 id,
@@ -299,16 +337,48 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
     ///
     /// * If sending the message to the raw packer fails.
     pub fn add(&self, data: Bytes, id: BlobId) -> RusticResult<()> {
+        self.add_with_compression(data, id, true)
+    }
+
+    /// Adds the blob to the packfile without compressing it
+    ///
+    /// This should be used for data which is known to be incompressible.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The blob data
+    /// * `id` - The blob id
+    ///
+    /// # Errors
+    ///
+    /// * If sending the message to the raw packer fails.
+    pub fn add_uncompressed(&self, data: Bytes, id: BlobId) -> RusticResult<()> {
+        self.add_with_compression(data, id, false)
+    }
+
+    /// Adds the blob to the packfile, compressing it if requested and supported by the repository
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The blob data
+    /// * `id` - The blob id
+    /// * `compress` - Whether to compress the blob
+    ///
+    /// # Errors
+    ///
+    /// * If sending the message to the raw packer fails.
+    fn add_with_compression(&self, data: Bytes, id: BlobId, compress: bool) -> RusticResult<()> {
         // compute size limit based on total size and size bounds
-        self.add_with_sizelimit(data, id, None).map_err(|err| {
-            RusticError::with_source(
-                ErrorKind::Internal,
-                "Failed to add blob `{id}` to packfile.",
-                err,
-            )
-            .attach_context("id", id.to_string())
-            .ask_report()
-        })
+        self.add_with_sizelimit(data, id, None, compress)
+            .map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Internal,
+                    "Failed to add blob `{id}` to packfile.",
+                    err,
+                )
+                .attach_context("id", id.to_string())
+                .ask_report()
+            })
     }
 
     /// Adds the blob to the packfile, allows specifying a size limit for the pack file
@@ -318,6 +388,7 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
     /// * `data` - The blob data
     /// * `id` - The blob id
     /// * `size_limit` - The size limit for the pack file
+    /// * `compress` - Whether to compress the blob
     ///
     /// # Errors
     ///
@@ -327,9 +398,10 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
         data: Bytes,
         id: BlobId,
         size_limit: Option<u32>,
+        compress: bool,
     ) -> PackerResult<()> {
         self.sender
-            .send((data.clone(), id, size_limit))
+            .send((data.clone(), id, size_limit, compress))
             .map_err(|err| PackerErrorKind::SendingCrossbeamMessage {
                 size_limit,
                 id,
@@ -353,7 +425,7 @@ impl<BE: DecryptWriteBackend> Packer<BE> {
     ///
     /// * If the blob is already present in the index
     /// * If sending the message to the raw packer fails.
-    fn add_raw(
+    pub(crate) fn add_raw(
         &self,
         data: &[u8],
         id: &BlobId,
@@ -473,12 +545,14 @@ impl<BE: DecryptWriteBackend> RawPacker<BE> {
     /// * `indexer` - The indexer to write to.
     /// * `config` - The config file.
     /// * `total_size` - The total size of the pack file.
+    /// * `write_concurrency` - The number of pack files to write in parallel.
     fn new(
         be: BE,
         blob_type: BlobType,
         indexer: SharedIndexer<BE>,
         config: &ConfigFile,
         total_size: u64,
+        write_concurrency: usize,
     ) -> Self {
         let file_writer = Some(Actor::new(
             FileWriterHandle {
@@ -487,7 +561,7 @@ impl<BE: DecryptWriteBackend> RawPacker<BE> {
                 cacheable: blob_type.is_cacheable(),
             },
             1,
-            1,
+            write_concurrency,
         ));
 
         let pack_sizer = PackSizer::from_config(config, blob_type, total_size);
@@ -780,11 +854,15 @@ impl Actor {
     ///
     /// * `fwh` - The file writer handle.
     /// * `queue_len` - The length of the queue.
-    /// * `par` - The number of parallel threads.
+    /// * `par` - The number of parallel threads writing files.
+    ///
+    /// # Notes
+    ///
+    /// The files are written in parallel, but they are added to the index in the order they were sent.
     fn new<BE: DecryptWriteBackend>(
         fwh: FileWriterHandle<BE>,
         queue_len: usize,
-        _par: usize,
+        par: usize,
     ) -> Self {
         let (tx, rx) = bounded(queue_len);
         let (finish_tx, finish_rx) = bounded::<RusticResult<()>>(0);
@@ -799,7 +877,12 @@ impl Actor {
                         (file, PackId::from(id), index)
                     })
                     .readahead_scoped(scope)
-                    .map(|load| fwh.process(load))
+                    // limit the buffer to bound the number of files held in memory
+                    .parallel_map_scoped_custom(
+                        scope,
+                        |options| options.threads(par).buffer_size(par),
+                        |load| fwh.process(load),
+                    )
                     .readahead_scoped(scope)
                     .try_for_each(|index| fwh.index(index?));
                 _ = finish_tx.send(status);
@@ -959,7 +1042,7 @@ impl<BE: DecryptFullBackend> Repacker<BE> {
         )?;
 
         self.packer
-            .add_with_sizelimit(data, blob.id, Some(self.size_limit))
+            .add_with_sizelimit(data, blob.id, Some(self.size_limit), true)
             .map_err(|err| {
                 RusticError::with_source(
                     ErrorKind::Internal,
@@ -976,3 +1059,67 @@ impl<BE: DecryptFullBackend> Repacker<BE> {
         self.packer.finalize()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+    };
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::{
+        backend::{decrypt::DecryptBackend, MockBackend, WriteBackend},
+        crypto::aespoly1305::Key,
+        index::indexer::Indexer,
+    };
+
+    #[rstest]
+    #[case(1)]
+    #[case(4)]
+    fn file_writer_respects_write_concurrency(#[case] par: usize) {
+        // counts the writes which happen at the same time
+        let current = Arc::new(AtomicUsize::new(0));
+        let max = Arc::new(AtomicUsize::new(0));
+
+        let mut mock = MockBackend::new();
+        _ = mock.expect_location().return_const("mock".to_string());
+        let (c, m) = (current.clone(), max.clone());
+        _ = mock
+            .expect_write_bytes()
+            .returning(move |_, _, _, _: Bytes| {
+                let now = c.fetch_add(1, Ordering::SeqCst) + 1;
+                _ = m.fetch_max(now, Ordering::SeqCst);
+                // slow writes to let parallel writes overlap
+                thread::sleep(Duration::from_millis(20));
+                _ = c.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            });
+        let be: Arc<dyn WriteBackend> = Arc::new(mock);
+        let be = DecryptBackend::new(be, Key::new());
+        let indexer = Indexer::new_unindexed(be.clone()).into_shared();
+
+        let fwh = FileWriterHandle {
+            be,
+            indexer,
+            cacheable: false,
+        };
+        let actor = Actor::new(fwh, 1, par);
+        for i in 0..16 {
+            actor
+                .send((Bytes::from(vec![i; 10]), IndexPack::default()))
+                .unwrap();
+        }
+        actor.finalize().unwrap();
+
+        let max = max.load(Ordering::SeqCst);
+        assert_eq!(current.load(Ordering::SeqCst), 0);
+        if par == 1 {
+            assert_eq!(max, 1);
+        } else {
+            assert!((2..=par).contains(&max), "max writers: {max}");
+        }
+    }
+}
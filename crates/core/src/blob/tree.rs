@@ -2,15 +2,18 @@ use std::{
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet, BinaryHeap},
     ffi::{OsStr, OsString},
-    mem,
+    mem::{self, discriminant},
     path::{Component, Path, PathBuf, Prefix},
-    str::{self, Utf8Error},
+    str::{self, FromStr, Utf8Error},
 };
 
+use bytesize::ByteSize;
 use crossbeam_channel::{bounded, unbounded, Receiver, Sender};
 use derive_setters::Setters;
+use globset::{GlobBuilder, GlobMatcher};
 use ignore::overrides::{Override, OverrideBuilder};
 use ignore::Match;
+use log::trace;
 use serde::{Deserialize, Deserializer};
 use serde_derive::Serialize;
 
@@ -395,6 +398,65 @@ impl Tree {
             matches,
         })
     }
+
+    /// Get all [`Node`]s/[`Path`]s from given root trees which match one of the given glob patterns
+    ///
+    /// The patterns use the gitignore syntax, i.e. a pattern without `/` matches the file name at any
+    /// depth and patterns starting with `!` exclude matching paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to read from
+    /// * `index` - The index to use
+    /// * `ids` - The tree ids to search in
+    /// * `patterns` - The glob patterns to match
+    /// * `case_insensitive` - Whether the patterns are matched case-insensitively
+    ///
+    /// # Errors
+    ///
+    /// * If a glob pattern is invalid
+    /// * If loading trees from the backend fails
+    pub(crate) fn find_nodes_matching_glob(
+        be: &impl DecryptReadBackend,
+        index: &impl ReadGlobalIndex,
+        ids: impl IntoIterator<Item = TreeId>,
+        patterns: &[String],
+        case_insensitive: bool,
+    ) -> RusticResult<FindMatches> {
+        let mut override_builder = OverrideBuilder::new("");
+        _ = override_builder
+            .case_insensitive(case_insensitive)
+            .map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Internal,
+                    "Failed to set case insensitivity in override builder.",
+                    err,
+                )
+                .ask_report()
+            })?;
+        for pattern in patterns {
+            _ = override_builder.add(pattern).map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::InvalidInput,
+                    "Invalid glob pattern `{glob}`.",
+                    err,
+                )
+                .attach_context("glob", pattern.to_string())
+            })?;
+        }
+        let overrides = override_builder.build().map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Failed to build matcher for a set of glob overrides.",
+                err,
+            )
+            .ask_report()
+        })?;
+
+        Self::find_matching_nodes(be, index, ids, &|path, node| {
+            !overrides.matched(path, node.is_dir()).is_ignore()
+        })
+    }
 }
 
 /// Results from `find_node_from_path`
@@ -453,6 +515,119 @@ impl IntoIterator for Tree {
     }
 }
 
+/// [`WalkAction`] tells a tree walk how to continue after visiting a node, see [`TreeVisitor`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WalkAction {
+    /// Continue the walk and descend into the subtree of a directory
+    #[default]
+    Continue,
+    /// Continue the walk, but don't descend into the subtree of this directory
+    SkipSubtree,
+    /// Stop the walk immediately
+    Stop,
+}
+
+/// A [`TreeVisitor`] is called for each node when walking a tree.
+///
+/// Nodes are visited in-order, i.e. a directory is visited before the contents of its subtree.
+/// All methods default to continue the walk without doing anything.
+pub trait TreeVisitor {
+    /// Visit a directory
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the directory
+    /// * `node` - The node of the directory
+    ///
+    /// # Errors
+    ///
+    /// * Any error aborts the walk and is returned
+    fn visit_dir(&mut self, _path: &Path, _node: &Node) -> RusticResult<WalkAction> {
+        Ok(WalkAction::Continue)
+    }
+
+    /// Visit a regular file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file
+    /// * `node` - The node of the file
+    ///
+    /// # Errors
+    ///
+    /// * Any error aborts the walk and is returned
+    fn visit_file(&mut self, _path: &Path, _node: &Node) -> RusticResult<WalkAction> {
+        Ok(WalkAction::Continue)
+    }
+
+    /// Visit a special file, e.g. a symlink or a device
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the special file
+    /// * `node` - The node of the special file
+    ///
+    /// # Errors
+    ///
+    /// * Any error aborts the walk and is returned
+    fn visit_special(&mut self, _path: &Path, _node: &Node) -> RusticResult<WalkAction> {
+        Ok(WalkAction::Continue)
+    }
+}
+
+impl Tree {
+    /// Walk the given tree recursively and call the visitor for each node.
+    ///
+    /// Subtrees are only loaded if the visitor continues into them.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to read from.
+    /// * `index` - The index to use.
+    /// * `id` - The ID of the tree to walk.
+    /// * `path` - The path of the tree; nodes are visited with their name appended.
+    /// * `visitor` - The visitor to call.
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be loaded.
+    /// * If the visitor returned an error.
+    ///
+    /// # Returns
+    ///
+    /// [`WalkAction::Stop`] if the walk was stopped by the visitor, else [`WalkAction::Continue`].
+    pub(crate) fn walk(
+        be: &impl DecryptReadBackend,
+        index: &impl ReadGlobalIndex,
+        id: TreeId,
+        path: &mut PathBuf,
+        visitor: &mut impl TreeVisitor,
+    ) -> RusticResult<WalkAction> {
+        let tree = Self::from_backend(be, index, id)?;
+        for node in tree {
+            path.push(node.name());
+            let action = if node.is_dir() {
+                visitor.visit_dir(path, &node)?
+            } else if node.is_file() {
+                visitor.visit_file(path, &node)?
+            } else {
+                visitor.visit_special(path, &node)?
+            };
+
+            let action = match (action, node.subtree) {
+                (WalkAction::Continue, Some(id)) => Self::walk(be, index, id, path, visitor)?,
+                (action, _) => action,
+            };
+            _ = path.pop();
+
+            if action == WalkAction::Stop {
+                return Ok(WalkAction::Stop);
+            }
+        }
+        Ok(WalkAction::Continue)
+    }
+}
+
 #[cfg_attr(feature = "clap", derive(clap::Parser))]
 #[derive(Clone, Debug, Setters)]
 #[setters(into)]
@@ -487,6 +662,67 @@ pub struct TreeStreamerOptions {
     /// recursively list the dir
     #[cfg_attr(feature = "clap", clap(long))]
     pub recursive: bool,
+
+    /// Only list nodes with at least this size
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, value_name = "SIZE", help_heading = "Exclude options")
+    )]
+    pub min_size: Option<ByteSize>,
+
+    /// Only list nodes with at most this size
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, value_name = "SIZE", help_heading = "Exclude options")
+    )]
+    pub max_size: Option<ByteSize>,
+
+    /// Only list nodes of the given types (all types if empty).
+    ///
+    /// Only the kind of the node type is compared, e.g. [`NodeType::Symlink`] matches all symlinks
+    /// regardless of their target.
+    #[cfg_attr(feature = "clap", clap(skip))]
+    pub node_types: Vec<NodeType>,
+
+    /// Ordered include (`+PATTERN`) and exclude (`-PATTERN`) rules; the last matching rule wins
+    /// (can be specified multiple times).
+    ///
+    /// # Note
+    ///
+    /// * The rules are applied to the entries which are not already excluded by the glob options,
+    ///   i.e. an entry is only listed if it passes both the glob options and the rules.
+    /// * See [`FilterRule`] for how patterns are matched.
+    #[cfg_attr(
+        feature = "clap",
+        clap(
+            long = "filter",
+            value_name = "RULE",
+            allow_hyphen_values = true,
+            help_heading = "Exclude options"
+        )
+    )]
+    pub filter_rules: Vec<FilterRule>,
+}
+
+impl TreeStreamerOptions {
+    /// Check if the given node matches the size and node type filters.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to check
+    #[must_use]
+    pub fn matches(&self, node: &Node) -> bool {
+        self.min_size
+            .map_or(true, |min| node.meta.size >= min.as_u64())
+            && self
+                .max_size
+                .map_or(true, |max| node.meta.size <= max.as_u64())
+            && (self.node_types.is_empty()
+                || self
+                    .node_types
+                    .iter()
+                    .any(|tpe| discriminant(tpe) == discriminant(&node.node_type)))
+    }
 }
 
 impl Default for TreeStreamerOptions {
@@ -497,10 +733,146 @@ impl Default for TreeStreamerOptions {
             glob_file: Vec::default(),
             iglob_file: Vec::default(),
             recursive: true,
+            min_size: None,
+            max_size: None,
+            node_types: Vec::default(),
+            filter_rules: Vec::default(),
         }
     }
 }
 
+/// The action of a [`FilterRule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Include matching entries
+    Include,
+    /// Exclude matching entries
+    Exclude,
+}
+
+/// An include or exclude rule for paths.
+///
+/// A list of rules is evaluated top-to-bottom and the last matching rule decides whether an entry is
+/// included. Entries not matching any rule are included. For example, the rules
+/// `-*`, `+home/user`, `+home/user/**` exclude everything but `home/user` and its contents.
+///
+/// Patterns are glob patterns where `*` doesn't match `/`. Patterns containing a `/` are matched
+/// against the full path (a leading `/` is ignored), other patterns are matched against the file name.
+///
+/// [`FilterRule`] implements [`FromStr`] to read it from `+PATTERN` or `-PATTERN`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterRule {
+    /// The glob pattern to match
+    pub pattern: String,
+    /// Whether matching entries are included or excluded
+    pub action: FilterAction,
+}
+
+impl FilterRule {
+    /// Create a rule including entries matching `pattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob pattern to match
+    pub fn include(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            action: FilterAction::Include,
+        }
+    }
+
+    /// Create a rule excluding entries matching `pattern`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The glob pattern to match
+    pub fn exclude(pattern: impl Into<String>) -> Self {
+        Self {
+            pattern: pattern.into(),
+            action: FilterAction::Exclude,
+        }
+    }
+}
+
+impl FromStr for FilterRule {
+    type Err = Box<RusticError>;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rule = if let Some(pattern) = s.strip_prefix('+') {
+            Self::include(pattern.trim_start())
+        } else if let Some(pattern) = s.strip_prefix('-') {
+            Self::exclude(pattern.trim_start())
+        } else {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Invalid filter rule `{rule}`. Rules must start with `+` (include) or `-` (exclude), e.g. `-*.tmp`.",
+            )
+            .attach_context("rule", s));
+        };
+        // check the pattern
+        _ = FilterRules::new(std::slice::from_ref(&rule))?;
+        Ok(rule)
+    }
+}
+
+/// Compiled [`FilterRule`]s
+#[derive(Debug, Clone)]
+pub(crate) struct FilterRules(Vec<(GlobMatcher, bool, FilterAction)>);
+
+impl FilterRules {
+    /// Compile the given rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `rules` - The rules to compile
+    ///
+    /// # Errors
+    ///
+    /// * If a pattern is not a valid glob pattern.
+    ///
+    /// # Returns
+    ///
+    /// The compiled rules or `None` if no rules are given.
+    pub(crate) fn new(rules: &[FilterRule]) -> RusticResult<Option<Self>> {
+        if rules.is_empty() {
+            return Ok(None);
+        }
+        let rules = rules
+            .iter()
+            .map(|rule| {
+                let pattern = rule.pattern.trim_start_matches('/');
+                let matcher = GlobBuilder::new(pattern)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| {
+                        RusticError::with_source(
+                            ErrorKind::InvalidInput,
+                            "Failed to parse glob pattern `{pattern}` of filter rule. Please check the pattern.",
+                            err,
+                        )
+                        .attach_context("pattern", rule.pattern.clone())
+                    })?
+                    .compile_matcher();
+                Ok((matcher, rule.pattern.contains('/'), rule.action))
+            })
+            .collect::<RusticResult<_>>()?;
+        Ok(Some(Self(rules)))
+    }
+
+    /// Check whether the given path is included by the rules.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to check
+    pub(crate) fn is_included(&self, path: &Path) -> bool {
+        let name = path.file_name().map(Path::new).unwrap_or(path);
+        self.0
+            .iter()
+            .rev()
+            .find(|(matcher, full_path, _)| matcher.is_match(if *full_path { path } else { name }))
+            .map_or(true, |(_, _, action)| *action == FilterAction::Include)
+    }
+}
+
 /// [`NodeStreamer`] recursively streams all nodes of a given tree including all subtrees in-order
 #[derive(Debug, Clone)]
 pub struct NodeStreamer<'a, BE, I>
@@ -522,6 +894,10 @@ where
     overrides: Option<Override>,
     /// Whether to stream recursively
     recursive: bool,
+    /// The options to filter nodes by size and node type
+    filter: Option<TreeStreamerOptions>,
+    /// The ordered include/exclude rules
+    rules: Option<FilterRules>,
 }
 
 impl<'a, BE, I> NodeStreamer<'a, BE, I>
@@ -541,7 +917,7 @@ where
     /// * If the tree ID is not found in the backend.
     /// * If deserialization fails.
     pub fn new(be: BE, index: &'a I, node: &Node) -> RusticResult<Self> {
-        Self::new_streamer(be, index, node, None, true)
+        Self::new_streamer(be, index, node, None, true, None, None)
     }
 
     /// Creates a new `NodeStreamer`.
@@ -552,6 +928,8 @@ where
     /// * `node` - The node to start from.
     /// * `overrides` - The glob overrides.
     /// * `recursive` - Whether to stream recursively.
+    /// * `filter` - The options to filter nodes by size and node type.
+    /// * `rules` - The ordered include/exclude rules.
     ///
     /// # Errors
     ///
@@ -563,6 +941,8 @@ where
         node: &Node,
         overrides: Option<Override>,
         recursive: bool,
+        filter: Option<TreeStreamerOptions>,
+        rules: Option<FilterRules>,
     ) -> RusticResult<Self> {
         let inner = if node.is_dir() {
             Tree::from_backend(&be, index, node.subtree.unwrap())?
@@ -579,11 +959,16 @@ where
             index,
             overrides,
             recursive,
+            filter,
+            rules,
         })
     }
 
     /// Creates a new `NodeStreamer` with glob patterns.
     ///
+    /// Nodes not matching the size and node type filters are not returned, but directories
+    /// are still descended into when streaming recursively.
+    ///
     /// # Arguments
     ///
     /// * `be` - The backend to read from.
@@ -595,6 +980,7 @@ where
     ///
     /// * If building the streamer fails.
     /// * If reading a glob file fails.
+    /// * If a pattern of the filter rules is invalid.
     pub fn new_with_glob(
         be: BE,
         index: &'a I,
@@ -695,7 +1081,20 @@ where
             .ask_report()
         })?;
 
-        Self::new_streamer(be, index, node, Some(overrides), opts.recursive)
+        let filter =
+            (opts.min_size.is_some() || opts.max_size.is_some() || !opts.node_types.is_empty())
+                .then(|| opts.clone());
+        let rules = FilterRules::new(&opts.filter_rules)?;
+
+        Self::new_streamer(
+            be,
+            index,
+            node,
+            Some(overrides),
+            opts.recursive,
+            filter,
+            rules,
+        )
     }
 }
 
@@ -731,6 +1130,18 @@ where
                         }
                     }
 
+                    if let Some(rules) = &self.rules {
+                        if !rules.is_included(&path) {
+                            continue;
+                        }
+                    }
+
+                    if let Some(filter) = &self.filter {
+                        if !filter.matches(&node) {
+                            continue;
+                        }
+                    }
+
                     return Some(Ok((path, node)));
                 }
                 None => match self.open_iterators.pop() {
@@ -932,6 +1343,18 @@ impl<P: Progress> Iterator for TreeStreamerOnce<P> {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// A conflict which occurred when merging trees: Nodes with identical path which differ
+pub struct MergeConflict {
+    /// The path of the conflicting nodes
+    pub path: PathBuf,
+    /// The index of the tree (or snapshot) whose node has been kept
+    pub winner: usize,
+    /// The indices of the trees (or snapshots) whose nodes have been dropped
+    pub losers: Vec<usize>,
+}
+
 /// Merge trees from a list of trees
 ///
 /// # Arguments
@@ -941,10 +1364,12 @@ impl<P: Progress> Iterator for TreeStreamerOnce<P> {
 /// * `cmp` - The comparison function for the nodes.
 /// * `save` - The function to save the tree.
 /// * `summary` - The summary of the snapshot.
+/// * `conflicts` - The list to add merge conflicts to. The indices refer to `trees`.
 ///
 /// # Errors
 ///
-// TODO!: add errors
+/// * If a tree could not be read from the backend.
+/// * If a merged tree could not be saved.
 pub(crate) fn merge_trees(
     be: &impl DecryptReadBackend,
     index: &impl ReadGlobalIndex,
@@ -952,130 +1377,195 @@ pub(crate) fn merge_trees(
     cmp: &impl Fn(&Node, &Node) -> Ordering,
     save: &impl Fn(Tree) -> RusticResult<(TreeId, u64)>,
     summary: &mut SnapshotSummary,
+    conflicts: &mut Vec<MergeConflict>,
 ) -> RusticResult<TreeId> {
-    // We store nodes with the index of the tree in an Binary Heap where we sort only by node name
-    struct SortedNode(Node, usize);
-    impl PartialEq for SortedNode {
-        fn eq(&self, other: &Self) -> bool {
-            self.0.name == other.0.name
-        }
-    }
-    impl PartialOrd for SortedNode {
-        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-            Some(self.cmp(other))
-        }
-    }
-    impl Eq for SortedNode {}
-    impl Ord for SortedNode {
-        fn cmp(&self, other: &Self) -> Ordering {
-            self.0.name.cmp(&other.0.name).reverse()
-        }
+    let trees: Vec<_> = trees.iter().copied().zip(0..).collect();
+    TreeMerger {
+        be,
+        index,
+        cmp,
+        save,
+        summary,
+        conflicts,
     }
+    .merge_trees_at(&trees, Path::new(""))
+}
 
-    let mut tree_iters: Vec<_> = trees
-        .iter()
-        .map(|id| Tree::from_backend(be, index, *id).map(IntoIterator::into_iter))
-        .collect::<RusticResult<_>>()?;
+/// The state used when merging trees, see [`merge_trees`].
+struct TreeMerger<'a, BE, I, C, S> {
+    /// The backend to read from.
+    be: &'a BE,
+    /// The index to read from.
+    index: &'a I,
+    /// The comparison function for the nodes.
+    cmp: &'a C,
+    /// The function to save the tree.
+    save: &'a S,
+    /// The summary of the snapshot.
+    summary: &'a mut SnapshotSummary,
+    /// The list to add merge conflicts to.
+    conflicts: &'a mut Vec<MergeConflict>,
+}
 
-    // fill Heap with first elements from all trees
-    let mut elems = BinaryHeap::new();
-    for (num, iter) in tree_iters.iter_mut().enumerate() {
-        if let Some(node) = iter.next() {
-            elems.push(SortedNode(node, num));
+impl<BE, I, C, S> TreeMerger<'_, BE, I, C, S>
+where
+    BE: DecryptReadBackend,
+    I: ReadGlobalIndex,
+    C: Fn(&Node, &Node) -> Ordering,
+    S: Fn(Tree) -> RusticResult<(TreeId, u64)>,
+{
+    /// Merge trees from a list of trees located at the given path
+    ///
+    /// # Arguments
+    ///
+    /// * `trees` - The IDs of the trees to merge together with the index of the originating tree.
+    /// * `path` - The path of the trees.
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read from the backend.
+    /// * If a merged tree could not be saved.
+    fn merge_trees_at(&mut self, trees: &[(TreeId, usize)], path: &Path) -> RusticResult<TreeId> {
+        // We store nodes with the index of the tree in an Binary Heap where we sort only by node name
+        struct SortedNode(Node, usize);
+        impl PartialEq for SortedNode {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.name == other.0.name
+            }
         }
-    }
-
-    let mut tree = Tree::new();
-    let (mut node, mut num) = match elems.pop() {
-        None => {
-            let (id, size) = save(tree)?;
-            summary.dirs_unmodified += 1;
-            summary.total_dirs_processed += 1;
-            summary.total_dirsize_processed += size;
-            return Ok(id);
+        impl PartialOrd for SortedNode {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Eq for SortedNode {}
+        impl Ord for SortedNode {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.0.name.cmp(&other.0.name).reverse()
+            }
         }
-        Some(SortedNode(node, num)) => (node, num),
-    };
 
-    let mut nodes = Vec::new();
-    loop {
-        // push next element from tree_iters[0] (if any is left) into BinaryHeap
-        if let Some(next_node) = tree_iters[num].next() {
-            elems.push(SortedNode(next_node, num));
+        let mut tree_iters: Vec<_> = trees
+            .iter()
+            .map(|(id, _)| {
+                Tree::from_backend(self.be, self.index, *id).map(IntoIterator::into_iter)
+            })
+            .collect::<RusticResult<_>>()?;
+
+        // fill Heap with first elements from all trees
+        let mut elems = BinaryHeap::new();
+        for (num, iter) in tree_iters.iter_mut().enumerate() {
+            if let Some(node) = iter.next() {
+                elems.push(SortedNode(node, num));
+            }
         }
 
-        match elems.pop() {
+        let mut tree = Tree::new();
+        let (mut node, mut num) = match elems.pop() {
             None => {
-                // Add node to nodes list
-                nodes.push(node);
-                // no node left to proceed, merge nodes and quit
-                tree.add(merge_nodes(be, index, nodes, cmp, save, summary)?);
-                break;
-            }
-            Some(SortedNode(new_node, new_num)) if node.name != new_node.name => {
-                // Add node to nodes list
-                nodes.push(node);
-                // next node has other name; merge present nodes
-                tree.add(merge_nodes(be, index, nodes, cmp, save, summary)?);
-                nodes = Vec::new();
-                // use this node as new node
-                (node, num) = (new_node, new_num);
-            }
-            Some(SortedNode(new_node, new_num)) => {
-                // Add node to nodes list
-                nodes.push(node);
-                // use this node as new node
-                (node, num) = (new_node, new_num);
+                let (id, size) = (self.save)(tree)?;
+                self.summary.dirs_unmodified += 1;
+                self.summary.total_dirs_processed += 1;
+                self.summary.total_dirsize_processed += size;
+                return Ok(id);
             }
+            Some(SortedNode(node, num)) => (node, num),
         };
+
+        let mut nodes = Vec::new();
+        loop {
+            // push next element from tree_iters[0] (if any is left) into BinaryHeap
+            if let Some(next_node) = tree_iters[num].next() {
+                elems.push(SortedNode(next_node, num));
+            }
+
+            match elems.pop() {
+                None => {
+                    // Add node to nodes list
+                    nodes.push((node, trees[num].1));
+                    // no node left to proceed, merge nodes and quit
+                    tree.add(self.merge_nodes(nodes, path)?);
+                    break;
+                }
+                Some(SortedNode(new_node, new_num)) if node.name != new_node.name => {
+                    // Add node to nodes list
+                    nodes.push((node, trees[num].1));
+                    // next node has other name; merge present nodes
+                    tree.add(self.merge_nodes(nodes, path)?);
+                    nodes = Vec::new();
+                    // use this node as new node
+                    (node, num) = (new_node, new_num);
+                }
+                Some(SortedNode(new_node, new_num)) => {
+                    // Add node to nodes list
+                    nodes.push((node, trees[num].1));
+                    // use this node as new node
+                    (node, num) = (new_node, new_num);
+                }
+            };
+        }
+        let (id, size) = (self.save)(tree)?;
+        if trees.iter().any(|(tree_id, _)| *tree_id == id) {
+            self.summary.dirs_unmodified += 1;
+        } else {
+            self.summary.dirs_changed += 1;
+        }
+        self.summary.total_dirs_processed += 1;
+        self.summary.total_dirsize_processed += size;
+        Ok(id)
     }
-    let (id, size) = save(tree)?;
-    if trees.contains(&id) {
-        summary.dirs_unmodified += 1;
-    } else {
-        summary.dirs_changed += 1;
-    }
-    summary.total_dirs_processed += 1;
-    summary.total_dirsize_processed += size;
-    Ok(id)
-}
 
-/// Merge nodes from a list of nodes
-///
-/// # Arguments
-///
-/// * `be` - The backend to read from.
-/// * `nodes` - The nodes to merge.
-/// * `cmp` - The comparison function for the nodes.
-/// * `save` - The function to save the tree.
-/// * `summary` - The summary of the snapshot.
-///
-/// # Errors
-///
-// TODO: add errors
-pub(crate) fn merge_nodes(
-    be: &impl DecryptReadBackend,
-    index: &impl ReadGlobalIndex,
-    nodes: Vec<Node>,
-    cmp: &impl Fn(&Node, &Node) -> Ordering,
-    save: &impl Fn(Tree) -> RusticResult<(TreeId, u64)>,
-    summary: &mut SnapshotSummary,
-) -> RusticResult<Node> {
-    let trees: Vec<_> = nodes
-        .iter()
-        .filter(|node| node.is_dir())
-        .map(|node| node.subtree.unwrap())
-        .collect();
-
-    let mut node = nodes.into_iter().max_by(|n1, n2| cmp(n1, n2)).unwrap();
-
-    // if this is a dir, merge with all other dirs
-    if node.is_dir() {
-        node.subtree = Some(merge_trees(be, index, &trees, cmp, save, summary)?);
-    } else {
-        summary.files_unmodified += 1;
-        summary.total_files_processed += 1;
-        summary.total_bytes_processed += node.meta.size;
+    /// Merge nodes from a list of nodes
+    ///
+    /// If the chosen node is a dir, it is merged with all other dirs. All other nodes which
+    /// differ from the chosen node are reported as conflict.
+    ///
+    /// # Arguments
+    ///
+    /// * `nodes` - The nodes to merge together with the index of the originating tree.
+    /// * `path` - The path of the parent tree.
+    ///
+    /// # Errors
+    ///
+    /// * If a subtree could not be read from the backend.
+    /// * If a merged subtree could not be saved.
+    fn merge_nodes(&mut self, nodes: Vec<(Node, usize)>, path: &Path) -> RusticResult<Node> {
+        let trees: Vec<_> = nodes
+            .iter()
+            .filter(|(node, _)| node.is_dir())
+            .map(|(node, num)| (node.subtree.unwrap(), *num))
+            .collect();
+
+        let (winner, winner_num) = nodes
+            .iter()
+            .max_by(|(n1, _), (n2, _)| (self.cmp)(n1, n2))
+            .map(|(node, num)| (node.clone(), *num))
+            .unwrap();
+        let path = path.join(winner.name());
+
+        let losers: Vec<_> = nodes
+            .into_iter()
+            .filter(|(node, _)| !(winner.is_dir() && node.is_dir()) && *node != winner)
+            .map(|(_, num)| num)
+            .collect();
+        if !losers.is_empty() {
+            trace!("merge conflict at {path:?}: {winner_num} wins over {losers:?}");
+            self.conflicts.push(MergeConflict {
+                path: path.clone(),
+                winner: winner_num,
+                losers,
+            });
+        }
+
+        let mut node = winner;
+        // if this is a dir, merge with all other dirs
+        if node.is_dir() {
+            node.subtree = Some(self.merge_trees_at(&trees, &path)?);
+        } else {
+            self.summary.files_unmodified += 1;
+            self.summary.total_files_processed += 1;
+            self.summary.total_bytes_processed += node.meta.size;
+        }
+        Ok(node)
     }
-    Ok(node)
 }
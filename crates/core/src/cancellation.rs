@@ -0,0 +1,66 @@
+//! Cancellation of long-running operations
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::error::{ErrorKind, RusticError, RusticResult};
+
+/// A token which allows to cancel long-running operations from another thread.
+///
+/// Cloned tokens share their state, so cancelling one of them cancels all clones.
+/// Operations poll the token at loop boundaries and return an error of kind
+/// [`ErrorKind::Cancelled`] once it has been cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new [`CancellationToken`] which is not cancelled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel all operations using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+
+    /// Returns whether this token has been cancelled.
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    /// Return an error if this token has been cancelled.
+    ///
+    /// # Errors
+    ///
+    /// * If the token has been cancelled.
+    pub(crate) fn check(&self) -> RusticResult<()> {
+        if self.is_cancelled() {
+            return Err(RusticError::new(
+                ErrorKind::Cancelled,
+                "The operation has been cancelled.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancellation_token_is_shared() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(token.check().is_err());
+    }
+}
@@ -1,31 +1,116 @@
 use std::io::{self, Read};
 
+use bytes::Bytes;
 use rand::{thread_rng, Rng};
 use rustic_cdc::{Polynom, Polynom64, Rabin64, RollingHash64};
 
-use crate::error::{ErrorKind, RusticError, RusticResult};
+use crate::{
+    error::{ErrorKind, RusticError, RusticResult},
+    repofile::ConfigFile,
+};
 
 pub(super) mod constants {
-    /// The Splitmask is used to determine if a chunk is a chunk boundary.
-    pub(super) const SPLITMASK: u64 = (1u64 << 20) - 1;
     /// The size of a kilobyte.
     pub(super) const KB: usize = 1024;
     /// The size of a megabyte.
     pub(super) const MB: usize = 1024 * KB;
-    /// The minimum size of a chunk.
+    /// The default minimum size of a chunk.
     pub(super) const MIN_SIZE: usize = 512 * KB;
-    /// The maximum size of a chunk.
+    /// The default average size of a chunk.
+    pub(super) const AVG_SIZE: usize = MB;
+    /// The default maximum size of a chunk.
     pub(super) const MAX_SIZE: usize = 8 * MB;
+    /// The smallest allowed chunk size.
+    pub(super) const LOWER_BOUND: usize = 4 * KB;
+    /// The largest allowed chunk size.
+    pub(super) const UPPER_BOUND: usize = 64 * MB;
     /// Buffer size used for reading - TODO: Find out optimal size for best performance!
     pub(super) const BUF_SIZE: usize = 4 * KB;
     /// Random polynomial maximum tries.
     pub(super) const RAND_POLY_MAX_TRIES: i32 = 1_000_000;
 }
 
-/// Default predicate for chunking.
-#[inline]
-const fn default_predicate(x: u64) -> bool {
-    (x & constants::SPLITMASK) == 0
+/// The sizes used by the content defined chunker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ChunkSizes {
+    /// The minimum size of a chunk.
+    pub(crate) min: usize,
+    /// The average size of a chunk; must be a power of two.
+    pub(crate) avg: usize,
+    /// The maximum size of a chunk.
+    pub(crate) max: usize,
+}
+
+impl Default for ChunkSizes {
+    fn default() -> Self {
+        Self {
+            min: constants::MIN_SIZE,
+            avg: constants::AVG_SIZE,
+            max: constants::MAX_SIZE,
+        }
+    }
+}
+
+impl ChunkSizes {
+    /// Creates new [`ChunkSizes`], using the defaults for values not given.
+    ///
+    /// # Arguments
+    ///
+    /// * `min` - The minimum size of a chunk.
+    /// * `avg` - The average size of a chunk.
+    /// * `max` - The maximum size of a chunk.
+    ///
+    /// # Errors
+    ///
+    /// * If a size is outside of the allowed bounds.
+    /// * If the average size is not a power of two.
+    /// * If the sizes are not ordered as `min < avg < max`.
+    pub(crate) fn new(min: Option<u32>, avg: Option<u32>, max: Option<u32>) -> RusticResult<Self> {
+        let default = Self::default();
+        let sizes = Self {
+            min: min.map_or(default.min, |size| size as usize),
+            avg: avg.map_or(default.avg, |size| size as usize),
+            max: max.map_or(default.max, |size| size as usize),
+        };
+
+        let bounds = constants::LOWER_BOUND..=constants::UPPER_BOUND;
+        for size in [sizes.min, sizes.avg, sizes.max] {
+            if !bounds.contains(&size) {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Chunk size `{size}` is not allowed. Chunk sizes must be between `{lower}` and `{upper}` bytes.",
+                )
+                .attach_context("size", size.to_string())
+                .attach_context("lower", constants::LOWER_BOUND.to_string())
+                .attach_context("upper", constants::UPPER_BOUND.to_string()));
+            }
+        }
+
+        if !sizes.avg.is_power_of_two() {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Average chunk size `{avg}` must be a power of two.",
+            )
+            .attach_context("avg", sizes.avg.to_string()));
+        }
+
+        if sizes.min >= sizes.avg || sizes.avg >= sizes.max {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Chunk sizes must satisfy min < avg < max. You provided min `{min}`, avg `{avg}` and max `{max}`.",
+            )
+            .attach_context("min", sizes.min.to_string())
+            .attach_context("avg", sizes.avg.to_string())
+            .attach_context("max", sizes.max.to_string()));
+        }
+
+        Ok(sizes)
+    }
+
+    /// The mask used to determine if the rolling hash marks a chunk boundary.
+    const fn split_mask(&self) -> u64 {
+        self.avg as u64 - 1
+    }
 }
 
 /// `ChunkIter` is an iterator that chunks data.
@@ -39,8 +124,8 @@ pub(crate) struct ChunkIter<R: Read + Send> {
     /// The reader.
     reader: R,
 
-    /// The predicate used to determine if a chunk is a chunk boundary.
-    predicate: fn(u64) -> bool,
+    /// The mask used to determine if a chunk is a chunk boundary.
+    split_mask: u64,
 
     /// The rolling hash.
     rabin: Rabin64,
@@ -66,16 +151,17 @@ impl<R: Read + Send> ChunkIter<R> {
     /// * `reader` - The reader to read from.
     /// * `size_hint` - The size hint is used to optimize memory allocation; this should be an upper bound on the size.
     /// * `rabin` - The rolling hash.
-    pub(crate) fn new(reader: R, size_hint: usize, rabin: Rabin64) -> Self {
+    /// * `sizes` - The sizes of the chunks.
+    pub(crate) fn new(reader: R, size_hint: usize, rabin: Rabin64, sizes: ChunkSizes) -> Self {
         Self {
             buf: vec![0; constants::BUF_SIZE],
             pos: constants::BUF_SIZE,
             reader,
-            predicate: default_predicate,
+            split_mask: sizes.split_mask(),
             rabin,
             size_hint, // size hint is used to optimize memory allocation; this should be an upper bound on the size
-            min_size: constants::MIN_SIZE,
-            max_size: constants::MAX_SIZE,
+            min_size: sizes.min,
+            max_size: sizes.max,
             finished: false,
         }
     }
@@ -132,7 +218,7 @@ impl<R: Read + Send> Iterator for ChunkIter<R> {
                 break;
             }
 
-            if (self.predicate)(self.rabin.hash) {
+            if (self.rabin.hash & self.split_mask) == 0 {
                 break;
             }
 
@@ -168,6 +254,206 @@ impl<R: Read + Send> Iterator for ChunkIter<R> {
     }
 }
 
+/// A content defined chunker using the chunker parameters of a repository.
+///
+/// The chunk boundaries depend on the chunker polynomial and the chunk sizes saved in the repository config.
+/// Only data chunked with the parameters of a repository is deduplicated against the data saved in this
+/// repository. Use [`Repository::chunker`] to get a chunker which chunks identically to backups.
+///
+/// Note that backups using a fixed chunk size don't use content defined chunking.
+///
+/// [`Repository::chunker`]: crate::Repository::chunker
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunker {
+    /// The polynomial used for the rolling hash.
+    poly: u64,
+    /// The sizes of the chunks.
+    sizes: ChunkSizes,
+}
+
+impl Chunker {
+    /// Creates a new [`Chunker`] from the chunker parameters of the given config.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The repository config
+    ///
+    /// # Errors
+    ///
+    /// * If the chunker polynomial or the chunk sizes of the config are invalid.
+    pub(crate) fn from_config(config: &ConfigFile) -> RusticResult<Self> {
+        Ok(Self {
+            poly: config.poly()?,
+            sizes: config.chunk_sizes()?,
+        })
+    }
+
+    /// The polynomial used for the rolling hash.
+    #[must_use]
+    pub const fn poly(&self) -> u64 {
+        self.poly
+    }
+
+    /// The minimum size of a chunk.
+    #[must_use]
+    pub const fn min_size(&self) -> usize {
+        self.sizes.min
+    }
+
+    /// The average size of a chunk.
+    #[must_use]
+    pub const fn avg_size(&self) -> usize {
+        self.sizes.avg
+    }
+
+    /// The maximum size of a chunk.
+    #[must_use]
+    pub const fn max_size(&self) -> usize {
+        self.sizes.max
+    }
+
+    /// Chunk the data read from `reader`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to read the data from
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the chunks together with their offset in the read data.
+    pub fn chunks<R: Read + Send>(&self, reader: R) -> Chunks<R> {
+        let rabin = Rabin64::new_with_polynom(6, &self.poly);
+        Chunks {
+            iter: ChunkIter::new(reader, usize::MAX, rabin, self.sizes),
+            offset: 0,
+        }
+    }
+}
+
+/// An iterator over the chunks of some data, see [`Chunker::chunks`].
+///
+/// The iterator yields the offset of each chunk within the data together with the chunk.
+pub struct Chunks<R: Read + Send> {
+    /// The underlying chunk iterator.
+    iter: ChunkIter<R>,
+    /// The offset of the next chunk.
+    offset: u64,
+}
+
+impl<R: Read + Send> std::fmt::Debug for Chunks<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Chunks")
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R: Read + Send> Iterator for Chunks<R> {
+    type Item = RusticResult<(u64, Bytes)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let chunk = match self.iter.next()? {
+            Ok(chunk) => chunk,
+            Err(err) => return Some(Err(err)),
+        };
+        let offset = self.offset;
+        self.offset += chunk.len() as u64;
+        Some(Ok((offset, Bytes::from(chunk))))
+    }
+}
+
+/// Checks the given fixed chunk size and converts it to `usize`.
+///
+/// # Arguments
+///
+/// * `size` - The size of the chunks.
+///
+/// # Errors
+///
+/// * If the size is outside of the allowed bounds.
+pub(crate) fn fixed_chunk_size(size: u64) -> RusticResult<usize> {
+    let bounds = constants::LOWER_BOUND..=constants::UPPER_BOUND;
+    match usize::try_from(size) {
+        Ok(size) if bounds.contains(&size) => Ok(size),
+        _ => Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Fixed chunk size `{size}` is not allowed. Chunk sizes must be between `{lower}` and `{upper}` bytes.",
+        )
+        .attach_context("size", size.to_string())
+        .attach_context("lower", constants::LOWER_BOUND.to_string())
+        .attach_context("upper", constants::UPPER_BOUND.to_string())),
+    }
+}
+
+/// `FixedChunkIter` is an iterator that chunks data into chunks of a fixed size.
+///
+/// All chunks except the last one have exactly the given size.
+pub(crate) struct FixedChunkIter<R: Read + Send> {
+    /// The reader.
+    reader: R,
+
+    /// The size of the chunks.
+    chunk_size: usize,
+
+    /// The size hint is used to optimize memory allocation; this should be an upper bound on the size.
+    size_hint: usize,
+
+    /// If the iterator is finished.
+    finished: bool,
+}
+
+impl<R: Read + Send> FixedChunkIter<R> {
+    /// Creates a new `FixedChunkIter`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to read from.
+    /// * `size_hint` - The size hint is used to optimize memory allocation; this should be an upper bound on the size.
+    /// * `chunk_size` - The size of the chunks.
+    pub(crate) const fn new(reader: R, size_hint: usize, chunk_size: usize) -> Self {
+        Self {
+            reader,
+            chunk_size,
+            size_hint,
+            finished: false,
+        }
+    }
+}
+
+impl<R: Read + Send> Iterator for FixedChunkIter<R> {
+    type Item = RusticResult<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let mut vec = Vec::with_capacity(self.size_hint.min(self.chunk_size));
+        let size = match (&mut self.reader)
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut vec)
+        {
+            Ok(size) => size,
+            Err(err) => {
+                return Some(Err(RusticError::with_source(
+                    ErrorKind::InputOutput,
+                    "Failed to read from reader in iterator",
+                    err,
+                )));
+            }
+        };
+
+        if size < self.chunk_size {
+            self.finished = true;
+            if vec.is_empty() {
+                return None;
+            }
+        }
+        self.size_hint = self.size_hint.saturating_sub(size);
+        Some(Ok(vec))
+    }
+}
+
 /// [`random_poly`] returns an random irreducible polynomial of degree 53
 /// (largest prime number below 64-8)
 /// There are (2^53-2/53) irreducible polynomials of degree 53 in
@@ -295,6 +581,8 @@ fn qp(p: i32, g: Polynom64) -> Polynom64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use rstest::rstest;
     use std::io::{repeat, Cursor};
 
     #[test]
@@ -304,7 +592,7 @@ mod tests {
 
         let poly = random_poly().unwrap();
         let rabin = Rabin64::new_with_polynom(6, &poly);
-        let chunker = ChunkIter::new(&mut reader, 0, rabin);
+        let chunker = ChunkIter::new(&mut reader, 0, rabin, ChunkSizes::default());
 
         assert_eq!(0, chunker.into_iter().count());
     }
@@ -316,7 +604,7 @@ mod tests {
 
         let poly = random_poly().unwrap();
         let rabin = Rabin64::new_with_polynom(6, &poly);
-        let chunker = ChunkIter::new(&mut reader, 100, rabin);
+        let chunker = ChunkIter::new(&mut reader, 100, rabin, ChunkSizes::default());
 
         assert_eq!(0, chunker.into_iter().count());
     }
@@ -327,9 +615,78 @@ mod tests {
 
         let poly = random_poly().unwrap();
         let rabin = Rabin64::new_with_polynom(6, &poly);
-        let mut chunker = ChunkIter::new(&mut reader, usize::MAX, rabin);
+        let mut chunker = ChunkIter::new(&mut reader, usize::MAX, rabin, ChunkSizes::default());
 
         let chunk = chunker.next().unwrap().unwrap();
         assert_eq!(constants::MIN_SIZE, chunk.len());
     }
+
+    #[test]
+    fn chunk_sizes_change_chunk_count() {
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut data = vec![0u8; 16 * constants::MB];
+        rng.fill_bytes(&mut data);
+
+        let poly = random_poly().unwrap();
+        let count = |sizes: ChunkSizes| {
+            let rabin = Rabin64::new_with_polynom(6, &poly);
+            ChunkIter::new(Cursor::new(&data), data.len(), rabin, sizes)
+                .map(|chunk| chunk.unwrap().len())
+                .collect::<Vec<_>>()
+        };
+
+        let default_chunks = count(ChunkSizes::default());
+        let small = ChunkSizes::new(Some(16 * 1024), Some(64 * 1024), Some(256 * 1024)).unwrap();
+        let small_chunks = count(small);
+
+        assert_eq!(default_chunks.iter().sum::<usize>(), data.len());
+        assert_eq!(small_chunks.iter().sum::<usize>(), data.len());
+        assert!(small_chunks.len() > default_chunks.len());
+        assert!(small_chunks.iter().all(|len| *len <= small.max));
+        // only the last chunk may be smaller than the minimum size
+        assert!(small_chunks[..small_chunks.len() - 1]
+            .iter()
+            .all(|len| *len >= small.min));
+    }
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(4096, 1)]
+    #[case(4097, 2)]
+    #[case(10 * 4096, 10)]
+    fn test_fixed_chunks(#[case] len: usize, #[case] count: usize) {
+        let data = vec![1u8; len];
+        let chunks: Vec<_> = FixedChunkIter::new(Cursor::new(&data), len, 4096)
+            .map(|chunk| chunk.unwrap())
+            .collect();
+
+        assert_eq!(chunks.len(), count);
+        assert_eq!(chunks.concat(), data);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 4096));
+    }
+
+    #[rstest]
+    #[case(4 * 1024, true)]
+    #[case(64 * 1024 * 1024, true)]
+    #[case(1024, false)]
+    #[case(128 * 1024 * 1024, false)]
+    fn test_fixed_chunk_size_validation(#[case] size: u64, #[case] ok: bool) {
+        assert_eq!(fixed_chunk_size(size).is_ok(), ok);
+    }
+
+    #[rstest]
+    #[case(None, None, None, true)]
+    #[case(Some(16 * 1024), Some(64 * 1024), Some(256 * 1024), true)]
+    #[case(Some(1024), None, None, false)]
+    #[case(None, Some(1000 * 1024), None, false)]
+    #[case(Some(2 * 1024 * 1024), None, None, false)]
+    #[case(None, None, Some(128 * 1024 * 1024), false)]
+    fn test_chunk_sizes_validation(
+        #[case] min: Option<u32>,
+        #[case] avg: Option<u32>,
+        #[case] max: Option<u32>,
+        #[case] ok: bool,
+    ) {
+        assert_eq!(ChunkSizes::new(min, avg, max).is_ok(), ok);
+    }
 }
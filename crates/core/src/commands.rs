@@ -7,11 +7,15 @@ pub mod check;
 pub mod config;
 /// The `copy` command.
 pub mod copy;
+pub mod dedup;
+/// The `diff` command.
+pub mod diff;
 /// The `dump` command.
 pub mod dump;
 pub mod forget;
 pub mod init;
 pub mod key;
+pub mod manifest;
 pub mod merge;
 pub mod prune;
 /// The `repair` command.
@@ -19,4 +23,6 @@ pub mod repair;
 /// The `repoinfo` command.
 pub mod repoinfo;
 pub mod restore;
+pub mod rewrite;
 pub mod snapshots;
+pub mod stats;
@@ -2,24 +2,27 @@
 use derive_setters::Setters;
 use log::info;
 
-use std::path::PathBuf;
+use std::{fmt, io::Read, path::PathBuf, sync::Arc};
 
 use path_dedot::ParseDot;
 use serde_derive::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
-    archiver::{parent::Parent, Archiver},
+    archiver::{file_archiver::SkipCompression, parent::Parent, Archiver, ArchiverOptions},
     backend::{
         childstdout::ChildStdoutSource,
         dry_run::DryRunBackend,
         ignore::{LocalSource, LocalSourceFilterOptions, LocalSourceSaveOptions},
+        reader::ReaderSource,
         stdin::StdinSource,
+        ReadSource,
     },
+    chunker::fixed_chunk_size,
     error::{ErrorKind, RusticError, RusticResult},
     progress::ProgressBars,
     repofile::{
-        snapshotfile::{SnapshotGroup, SnapshotGroupCriterion, SnapshotId},
+        snapshotfile::{SnapshotGroup, SnapshotGroupCriterion, SnapshotId, SnapshotSummary},
         PathList, SnapshotFile,
     },
     repository::{IndexedIds, IndexedTree, Repository},
@@ -29,6 +32,100 @@ use crate::{
 #[cfg(feature = "clap")]
 use clap::ValueHint;
 
+/// A predicate which selects the snapshots which may be used as parent.
+///
+/// The latest snapshot matching the predicate is used as parent.
+#[derive(Clone)]
+pub struct ParentFilter(Arc<dyn Fn(&SnapshotFile) -> bool + Send + Sync>);
+
+impl ParentFilter {
+    /// Create a new [`ParentFilter`] from the given predicate.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The predicate which returns `true` for snapshots which may be used as parent
+    pub fn new(filter: impl Fn(&SnapshotFile) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(filter))
+    }
+
+    /// Check if the given snapshot may be used as parent.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to check
+    #[must_use]
+    pub fn matches(&self, snap: &SnapshotFile) -> bool {
+        (self.0)(snap)
+    }
+}
+
+impl fmt::Debug for ParentFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ParentFilter(..)")
+    }
+}
+
+/// How a file was processed w.r.t. the parent snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileStatus {
+    /// The file is unchanged compared to the parent snapshot
+    Unchanged,
+    /// The file has been changed compared to the parent snapshot
+    Changed,
+    /// The file is not contained in the parent snapshot
+    New,
+}
+
+/// An event emitted during a backup, see [`BackupOptions::on_event`]
+///
+/// Events can be serialized, e.g. to emit them as JSON lines.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum BackupEvent {
+    /// Reading the backup source has been started
+    ScanStarted {
+        /// The path which is backed up
+        path: PathBuf,
+    },
+    /// A file has been processed
+    FileProcessed {
+        /// The path of the file within the snapshot
+        path: PathBuf,
+        /// The size of the file
+        size: u64,
+        /// How the file was processed w.r.t. the parent snapshot
+        status: FileStatus,
+    },
+    /// The backup has been finished
+    Finished {
+        /// The summary of the backup
+        summary: Box<SnapshotSummary>,
+    },
+}
+
+/// Callback which is called for each [`BackupEvent`], see [`BackupOptions::on_event`]
+#[derive(Clone)]
+pub struct BackupEventCallback(Arc<dyn Fn(BackupEvent) + Send + Sync>);
+
+impl BackupEventCallback {
+    /// Call the callback.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to emit
+    pub fn call(&self, event: BackupEvent) {
+        (self.0)(event);
+    }
+}
+
+impl fmt::Debug for BackupEventCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BackupEventCallback(..)")
+    }
+}
+
 /// `backup` subcommand
 #[serde_as]
 #[cfg_attr(feature = "clap", derive(clap::Parser))]
@@ -46,6 +143,17 @@ pub struct ParentOptions {
     #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
     pub group_by: Option<SnapshotGroupCriterion>,
 
+    /// Custom filter to select the parent snapshot.
+    ///
+    /// If set, the latest snapshot matching this filter is used as parent instead of the
+    /// latest snapshot matching `group_by`. An explicitly given `parent` still takes precedence,
+    /// and `force` still disables using a parent at all.
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[cfg_attr(feature = "merge", merge(skip))]
+    #[serde(skip)]
+    #[setters(skip)]
+    pub parent_filter: Option<ParentFilter>,
+
     /// Snapshot to use as parent
     #[cfg_attr(
         feature = "clap",
@@ -73,9 +181,34 @@ pub struct ParentOptions {
     #[cfg_attr(feature = "clap", clap(long, conflicts_with = "force",))]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
     pub ignore_inode: bool,
+
+    /// Read all files and compare their content with the parent instead of trusting unchanged metadata.
+    ///
+    /// Use this if file modifications may not change size or mtime, e.g. on targets of tools resetting mtimes.
+    /// Note that this is much slower, as all files have to be read and chunked like without a parent.
+    #[cfg_attr(feature = "clap", clap(long, conflicts_with = "force",))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
+    pub force_hash: bool,
 }
 
 impl ParentOptions {
+    /// Use the latest snapshot matching the given predicate as parent.
+    ///
+    /// This overrides the default parent detection using `group_by`. An explicitly given `parent` still
+    /// takes precedence and `force` still disables using a parent.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - The predicate which returns `true` for snapshots which may be used as parent
+    #[must_use]
+    pub fn parent_filter(
+        mut self,
+        filter: impl Fn(&SnapshotFile) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.parent_filter = Some(ParentFilter::new(filter));
+        self
+    }
+
     /// Get parent snapshot.
     ///
     /// # Type Parameters
@@ -101,14 +234,24 @@ impl ParentOptions {
         let parent = match (backup_stdin, self.force, &self.parent) {
             (true, _, _) | (false, true, _) => None,
             (false, false, None) => {
-                // get suitable snapshot group from snapshot and opts.group_by. This is used to filter snapshots for the parent detection
-                let group = SnapshotGroup::from_snapshot(snap, self.group_by.unwrap_or_default());
-                SnapshotFile::latest(
-                    repo.dbe(),
-                    |snap| snap.has_group(&group),
-                    &repo.pb.progress_counter(""),
-                )
-                .ok()
+                if let Some(filter) = &self.parent_filter {
+                    SnapshotFile::latest(
+                        repo.dbe(),
+                        |snap| filter.matches(snap),
+                        &repo.pb.progress_counter("getting latest snapshot..."),
+                    )
+                    .ok()
+                } else {
+                    // get suitable snapshot group from snapshot and opts.group_by. This is used to filter snapshots for the parent detection
+                    let group =
+                        SnapshotGroup::from_snapshot(snap, self.group_by.unwrap_or_default());
+                    SnapshotFile::latest(
+                        repo.dbe(),
+                        |snap| snap.has_group(&group),
+                        &repo.pb.progress_counter("getting latest snapshot..."),
+                    )
+                    .ok()
+                }
             }
             (false, false, Some(parent)) => SnapshotFile::from_id(repo.dbe(), parent).ok(),
         };
@@ -123,6 +266,7 @@ impl ParentOptions {
                 parent_tree,
                 self.ignore_ctime,
                 self.ignore_inode,
+                self.force_hash,
             ),
         )
     }
@@ -164,6 +308,62 @@ pub struct BackupOptions {
     #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
     pub dry_run: bool,
 
+    /// Use fixed-size chunks of the given size (in bytes) instead of content defined chunking.
+    ///
+    /// This is useful for sources which consist of fixed-size objects, e.g. a content-addressed store.
+    ///
+    /// # Note
+    ///
+    /// * Deduplication still works blob-by-blob, but data chunked with a fixed size doesn't deduplicate
+    ///   with data chunked content defined. Mixing both across snapshots therefore reduces deduplication.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "SIZE"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub fixed_chunk_size: Option<u64>,
+
+    /// Number of files to read and chunk in parallel (default: number of CPUs).
+    ///
+    /// More readers can speed up backups of many small files from fast storage, while fewer readers
+    /// avoid seeking on spinning disks.
+    ///
+    /// # Note
+    ///
+    /// * The chunked data is compressed, encrypted and written to the backend by separate threads.
+    ///   If the backend is the bottleneck, more readers don't speed up the backup.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "NUM"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub read_concurrency: Option<usize>,
+
+    /// Number of data pack files to write to the backend in parallel (default: 1).
+    ///
+    /// More parallel writes can speed up backups to backends with high latency like S3, while
+    /// a low number avoids many concurrent syncs on local backends.
+    ///
+    /// # Note
+    ///
+    /// * Each pack file being written is held in memory, so the memory usage grows with this number.
+    /// * Retries of failed writes happen within each parallel write and don't block other writes.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "NUM"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub write_concurrency: Option<usize>,
+
+    /// Store files with these extensions without compression (case-insensitive, e.g. `jpg,mp4,zip`)
+    ///
+    /// This saves CPU time for already compressed file formats. It has no effect on repositories
+    /// without compression.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, value_name = "EXT[,EXT,..]", value_delimiter = ',')
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
+    pub no_compression_extensions: Vec<String>,
+
+    /// Store files without compression if a sample of their first chunk doesn't compress by at least 5%
+    ///
+    /// It has no effect on repositories without compression.
+    #[cfg_attr(feature = "clap", clap(long))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
+    pub compression_probe: bool,
+
     #[cfg_attr(feature = "clap", clap(flatten))]
     #[serde(flatten)]
     /// Options how to use a parent snapshot
@@ -178,6 +378,71 @@ pub struct BackupOptions {
     #[serde(flatten)]
     /// Options how to filter from a local source
     pub ignore_filter_opts: LocalSourceFilterOptions,
+
+    /// Callback which is called for each event during the backup, e.g. for each processed file.
+    ///
+    /// The callback may be called from a different thread than the one calling the backup.
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[cfg_attr(feature = "merge", merge(skip))]
+    #[serde(skip)]
+    #[setters(skip)]
+    pub event_sink: Option<BackupEventCallback>,
+}
+
+impl BackupOptions {
+    /// Set the callback which is called for each event during the backup.
+    ///
+    /// Logging is not affected by setting a callback.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The callback getting the [`BackupEvent`]
+    #[must_use]
+    pub fn on_event(mut self, callback: impl Fn(BackupEvent) + Send + Sync + 'static) -> Self {
+        self.event_sink = Some(BackupEventCallback(Arc::new(callback)));
+        self
+    }
+}
+
+/// The outcome of a backup run
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum BackupOutcome {
+    /// A new snapshot has been saved
+    Created(SnapshotFile),
+    /// The backup was identical to the parent snapshot, so no new snapshot has been saved.
+    ///
+    /// The contained snapshot has not been saved and has no id.
+    Unchanged {
+        /// The id of the parent snapshot
+        parent: SnapshotId,
+        /// The snapshot describing the backup run
+        snapshot: SnapshotFile,
+    },
+}
+
+impl BackupOutcome {
+    /// Returns whether a new snapshot has been saved
+    #[must_use]
+    pub const fn is_created(&self) -> bool {
+        matches!(self, Self::Created(_))
+    }
+
+    /// Returns the snapshot describing the backup run
+    #[must_use]
+    pub const fn snapshot(&self) -> &SnapshotFile {
+        match self {
+            Self::Created(snapshot) | Self::Unchanged { snapshot, .. } => snapshot,
+        }
+    }
+
+    /// Turn this [`BackupOutcome`] into the snapshot describing the backup run
+    #[must_use]
+    pub fn into_snapshot(self) -> SnapshotFile {
+        match self {
+            Self::Created(snapshot) | Self::Unchanged { snapshot, .. } => snapshot,
+        }
+    }
 }
 
 /// Backup data, create a snapshot.
@@ -201,25 +466,131 @@ pub struct BackupOptions {
 /// * If sending the message to the raw packer fails.
 /// * If the index file could not be serialized.
 /// * If the time is not in the range of `Local::now()`
+/// * If the fixed chunk size is not allowed
 ///
 /// # Returns
 ///
-/// The snapshot pointing to the backup'ed data.
-#[allow(clippy::too_many_lines)]
+/// The [`BackupOutcome`] containing the snapshot pointing to the backup'ed data.
 pub(crate) fn backup<P: ProgressBars, S: IndexedIds>(
     repo: &Repository<P, S>,
     opts: &BackupOptions,
     source: &PathList,
+    snap: SnapshotFile,
+) -> RusticResult<BackupOutcome> {
+    if *source == PathList::from_string("-")? {
+        let path = PathBuf::from(&opts.stdin_filename);
+        if let Some(command) = &opts.stdin_command {
+            let src = ChildStdoutSource::new(command, path.clone())?;
+            let outcome = backup_source(repo, opts, &src, &[path], true, snap)?;
+            src.finish()?;
+            Ok(outcome)
+        } else {
+            let src = StdinSource::new(path.clone());
+            backup_source(repo, opts, &src, &[path], true, snap)
+        }
+    } else {
+        let backup_path = source.paths();
+        let src = LocalSource::new(
+            opts.ignore_save_opts,
+            &opts.ignore_filter_opts,
+            &backup_path,
+        )?;
+        backup_source(repo, opts, &src, &backup_path, false, snap)
+    }
+}
+
+/// Backup the data read from a reader as a single file, create a snapshot.
+///
+/// # Type Parameters
+///
+/// * `P` - The type of the progress bars.
+/// * `S` - The type of the indexed tree.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `reader` - The reader to backup
+/// * `filename` - The name of the file in the snapshot
+/// * `opts` - The backup options
+/// * `snap` - The snapshot to backup
+///
+/// # Errors
+///
+/// * If the filename (or `as_path`, if given) doesn't name a file.
+/// * If reading from the reader fails.
+/// * If the data could not be saved, see [`backup`].
+///
+/// # Returns
+///
+/// The [`BackupOutcome`] containing the snapshot pointing to the backup'ed data.
+pub(crate) fn backup_reader<P: ProgressBars, S: IndexedIds>(
+    repo: &Repository<P, S>,
+    reader: impl Read + Send + 'static,
+    filename: &str,
+    opts: &BackupOptions,
+    snap: SnapshotFile,
+) -> RusticResult<BackupOutcome> {
+    let path = PathBuf::from(filename);
+    let snapshot_path = opts.as_path.as_ref().unwrap_or(&path);
+    if snapshot_path.file_name().is_none() {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "The path `{path}` doesn't name a file, so it can't be used for the data of a reader. Please use a filename like `data`.",
+        )
+        .attach_context("path", snapshot_path.display().to_string()));
+    }
+    let src = ReaderSource::new(path.clone(), reader);
+    backup_source(repo, opts, &src, &[path], true, snap)
+}
+
+/// Backup the given source, create a snapshot.
+///
+/// # Type Parameters
+///
+/// * `P` - The type of the progress bars.
+/// * `S` - The type of the indexed tree.
+/// * `R` - The type of the source.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `opts` - The backup options
+/// * `src` - The source to backup
+/// * `backup_path` - The paths of the source
+/// * `single_file` - Whether the source is a single stream saved as file, e.g. stdin
+/// * `snap` - The snapshot to backup
+///
+/// # Errors
+///
+/// See [`backup`].
+#[allow(clippy::too_many_lines)]
+fn backup_source<P: ProgressBars, S: IndexedIds, R>(
+    repo: &Repository<P, S>,
+    opts: &BackupOptions,
+    src: &R,
+    backup_path: &[PathBuf],
+    single_file: bool,
     mut snap: SnapshotFile,
-) -> RusticResult<SnapshotFile> {
+) -> RusticResult<BackupOutcome>
+where
+    R: ReadSource + 'static,
+    <R as ReadSource>::Open: Send,
+    <R as ReadSource>::Iter: Send,
+{
     let index = repo.index();
-
-    let backup_stdin = *source == PathList::from_string("-")?;
-    let backup_path = if backup_stdin {
-        vec![PathBuf::from(&opts.stdin_filename)]
-    } else {
-        source.paths()
-    };
+    let fixed_chunk_size = opts.fixed_chunk_size.map(fixed_chunk_size).transpose()?;
+    if opts.read_concurrency == Some(0) {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "The read concurrency must be at least 1.",
+        ));
+    }
+    if opts.write_concurrency == Some(0) {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "The write concurrency must be at least 1.",
+        ));
+    }
 
     let as_path = opts
         .as_path
@@ -247,7 +618,7 @@ pub(crate) fn backup<P: ProgressBars, S: IndexedIds>(
             )
             .attach_context("paths", p.display().to_string())
         })?,
-        None => snap.paths.set_paths(&backup_path).map_err(|err| {
+        None => snap.paths.set_paths(backup_path).map_err(|err| {
             RusticError::with_source(
                 ErrorKind::Internal,
                 "Failed to set paths `{paths}` in snapshot.",
@@ -264,7 +635,7 @@ pub(crate) fn backup<P: ProgressBars, S: IndexedIds>(
         })?,
     };
 
-    let (parent_id, parent) = opts.parent_opts.get_parent(repo, &snap, backup_stdin);
+    let (parent_id, parent) = opts.parent_opts.get_parent(repo, &snap, single_file);
     match parent_id {
         Some(id) => {
             info!("using parent {id}");
@@ -275,51 +646,49 @@ pub(crate) fn backup<P: ProgressBars, S: IndexedIds>(
         }
     };
 
+    let parent_tree = parent.tree_id();
     let be = DryRunBackend::new(repo.dbe().clone(), opts.dry_run);
-    info!("starting to backup {source} ...");
-    let archiver = Archiver::new(be, index, repo.config(), parent, snap)?;
+    info!(
+        "starting to backup {} ...",
+        PathList::from_iter(backup_path)
+    );
+    let archiver = Archiver::new(
+        be,
+        index,
+        repo.config(),
+        parent,
+        snap,
+        ArchiverOptions {
+            cancel: repo.cancel.clone(),
+            event_sink: opts.event_sink.clone(),
+            fixed_chunk_size,
+            read_concurrency: opts.read_concurrency,
+            skip_compression: SkipCompression::new(
+                &opts.no_compression_extensions,
+                opts.compression_probe,
+            ),
+            write_concurrency: opts.write_concurrency.unwrap_or(1),
+        },
+    )?;
     let p = repo.pb.progress_bytes("backing up...");
 
-    let snap = if backup_stdin {
-        let path = &backup_path[0];
-        if let Some(command) = &opts.stdin_command {
-            let src = ChildStdoutSource::new(command, path.clone())?;
-            let res = archiver.archive(
-                &src,
-                path,
-                as_path.as_ref(),
-                opts.parent_opts.skip_if_unchanged,
-                opts.no_scan,
-                &p,
-            )?;
-            src.finish()?;
-            res
-        } else {
-            let src = StdinSource::new(path.clone());
-            archiver.archive(
-                &src,
-                path,
-                as_path.as_ref(),
-                opts.parent_opts.skip_if_unchanged,
-                opts.no_scan,
-                &p,
-            )?
-        }
-    } else {
-        let src = LocalSource::new(
-            opts.ignore_save_opts,
-            &opts.ignore_filter_opts,
-            &backup_path,
-        )?;
-        archiver.archive(
-            &src,
-            &backup_path[0],
-            as_path.as_ref(),
-            opts.parent_opts.skip_if_unchanged,
-            opts.no_scan,
-            &p,
-        )?
-    };
+    let snap = archiver.archive(
+        src,
+        &backup_path[0],
+        as_path.as_ref(),
+        opts.parent_opts.skip_if_unchanged,
+        opts.no_scan,
+        &p,
+    )?;
 
-    Ok(snap)
+    match parent_id {
+        Some(parent) if opts.parent_opts.skip_if_unchanged && Some(snap.tree) == parent_tree => {
+            info!("snapshot is unchanged w.r.t. parent {parent}, not saving it");
+            Ok(BackupOutcome::Unchanged {
+                parent,
+                snapshot: snap,
+            })
+        }
+        _ => Ok(BackupOutcome::Created(snap)),
+    }
 }
@@ -3,12 +3,12 @@ use std::path::Path;
 use bytes::Bytes;
 
 use crate::{
-    backend::{decrypt::DecryptReadBackend, FileType, FindInBackend},
+    backend::{decrypt::DecryptReadBackend, FileType, FindInBackend, ReadBackend},
     blob::{tree::Tree, BlobId, BlobType},
     error::{ErrorKind, RusticError, RusticResult},
     index::ReadIndex,
     progress::ProgressBars,
-    repofile::SnapshotFile,
+    repofile::{packfile::PackId, PackHeader, PackHeaderLength, SnapshotFile},
     repository::{IndexedFull, IndexedTree, Open, Repository},
 };
 
@@ -44,6 +44,22 @@ pub(crate) fn cat_file<P, S: Open>(
     Ok(data)
 }
 
+/// Returns the given range of the data.
+///
+/// If offset is behind the end of the data, an empty `Bytes` is returned.
+/// If length is too large, the data up to the end is returned.
+///
+/// # Arguments
+///
+/// * `data` - The data to slice
+/// * `offset` - The offset to start
+/// * `length` - The length of the range
+pub(crate) fn slice_range(data: &Bytes, offset: usize, length: usize) -> Bytes {
+    let start = offset.min(data.len());
+    let end = offset.saturating_add(length).min(data.len());
+    data.slice(start..end)
+}
+
 // TODO: Add documentation!
 ///
 /// # Type Parameters
@@ -116,3 +132,112 @@ pub(crate) fn cat_tree<P: ProgressBars, S: IndexedTree>(
         .blob_from_backend(repo.dbe(), BlobType::Tree, &BlobId::from(*id))?;
     Ok(data)
 }
+
+/// Reads all blobs contained in a pack and passes them to the given callback.
+///
+/// The pack is read once as a whole; the header is parsed from the end of the pack
+/// and each blob is then decrypted (and decompressed) from the data already read.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to read from.
+/// * `pack` - The id of the pack to read.
+/// * `callback` - Called for each blob in the order the blobs are stored in the pack.
+///
+/// # Errors
+///
+/// * If the pack could not be read from the backend.
+/// * If the pack header could not be read or decrypted.
+/// * If a blob lies outside of the pack data.
+/// * If a blob could not be decrypted or decompressed.
+/// * If the callback returns an error.
+pub(crate) fn cat_pack_blobs_with<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    pack: PackId,
+    mut callback: impl FnMut(BlobId, BlobType, Bytes) -> RusticResult<()>,
+) -> RusticResult<()> {
+    repo.warm_up_wait(std::iter::once(pack))?;
+    let data = repo.dbe().read_full(FileType::Pack, &pack)?;
+
+    let pack_err = |msg: &'static str| {
+        RusticError::new(ErrorKind::Internal, msg).attach_context("pack_id", pack.to_string())
+    };
+
+    let len_start = data
+        .len()
+        .checked_sub(4)
+        .ok_or_else(|| pack_err("Pack `{pack_id}` is too small to contain a header."))?;
+    let header_len = PackHeaderLength::from_binary(&data[len_start..])
+        .map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Error reading pack header length of pack `{pack_id}`.",
+                err,
+            )
+            .attach_context("pack_id", pack.to_string())
+        })?
+        .to_u32() as usize;
+    let header_start = len_start
+        .checked_sub(header_len)
+        .ok_or_else(|| pack_err("Header length of pack `{pack_id}` exceeds the pack size."))?;
+
+    let header = repo.dbe().decrypt(&data[header_start..len_start])?;
+    let blobs = PackHeader::from_binary(&header)
+        .map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Error reading pack header of pack `{pack_id}`.",
+                err,
+            )
+            .attach_context("pack_id", pack.to_string())
+        })?
+        .into_blobs();
+
+    for blob in blobs {
+        let start = blob.offset as usize;
+        let end = start + blob.length as usize;
+        if end > header_start {
+            return Err(RusticError::new(
+                ErrorKind::Internal,
+                "Blob `{blob_id}` lies outside of the data of pack `{pack_id}`.",
+            )
+            .attach_context("blob_id", blob.id.to_string())
+            .attach_context("pack_id", pack.to_string()));
+        }
+        let blob_data = repo
+            .dbe()
+            .read_encrypted_from_partial(&data[start..end], blob.uncompressed_length)?;
+        callback(blob.id, blob.tpe, blob_data)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+    use rstest::rstest;
+
+    use super::slice_range;
+
+    #[rstest]
+    #[case(0, 4, b"0123")]
+    #[case(2, 3, b"234")]
+    #[case(8, 10, b"89")]
+    #[case(10, 1, b"")]
+    #[case(20, 1, b"")]
+    #[case(3, usize::MAX, b"3456789")]
+    fn test_slice_range_passes(
+        #[case] offset: usize,
+        #[case] length: usize,
+        #[case] expected: &[u8],
+    ) {
+        let data = Bytes::from_static(b"0123456789");
+        assert_eq!(slice_range(&data, offset, length), expected);
+    }
+}
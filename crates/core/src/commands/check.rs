@@ -1,25 +1,33 @@
 //! `check` subcommand
 use std::{
-    collections::{BTreeSet, HashMap},
-    fmt::Debug,
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt::{Debug, Display},
     num::ParseIntError,
+    path::PathBuf,
     str::FromStr,
+    sync::Mutex,
+    time::Duration,
 };
 
 use bytes::Bytes;
 use bytesize::ByteSize;
-use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, Timelike};
 use derive_setters::Setters;
 use log::{debug, error, warn};
-use rand::{prelude::SliceRandom, thread_rng, Rng};
-use rayon::prelude::{IntoParallelIterator, ParallelIterator};
-use zstd::stream::decode_all;
+use rand::{prelude::SliceRandom, rngs::StdRng, thread_rng, Rng, SeedableRng};
+use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use crate::{
-    backend::{cache::Cache, decrypt::DecryptReadBackend, node::NodeType, FileType, ReadBackend},
-    blob::{tree::TreeStreamerOnce, BlobId, BlobType},
+    backend::{
+        cache::Cache,
+        decrypt::{decompress, DecryptReadBackend},
+        node::NodeType,
+        FileType, ReadBackend,
+    },
+    blob::{tree::TreeStreamerOnce, BlobId, BlobType, DataId},
+    cancellation::CancellationToken,
     crypto::hasher::hash,
-    error::{RusticError, RusticResult},
+    error::{RusticError, RusticResult, Severity},
     id::Id,
     index::{
         binarysorted::{IndexCollector, IndexType},
@@ -27,7 +35,8 @@ use crate::{
     },
     progress::{Progress, ProgressBars},
     repofile::{
-        packfile::PackId, IndexFile, IndexPack, PackHeader, PackHeaderLength, PackHeaderRef,
+        indexfile::IndexId, packfile::PackId, snapshotfile::SnapshotId, IndexFile, IndexPack,
+        PackHeader, PackHeaderLength, PackHeaderRef, SnapshotFile,
     },
     repository::{Open, Repository},
     ErrorKind, TreeId,
@@ -46,22 +55,46 @@ pub enum ReadSubsetOption {
     Size(u64),
     /// Read a subset of packfiles based on Ids: Using (1,n) .. (n,n) in separate runs will cover all pack files
     IdSubSet((u32, u32)),
+    /// Read all pack files which have been written within the given duration
+    Newest(Duration),
+    /// Read all pack files which have been written before the given duration.
+    ///
+    /// Pack files without time information are always read, as they predate time tracking.
+    Oldest(Duration),
 }
 
 impl ReadSubsetOption {
-    fn apply(self, packs: impl IntoIterator<Item = IndexPack>) -> Vec<IndexPack> {
-        self.apply_with_rng(packs, &mut thread_rng())
+    /// Apply the subset option to the given packs.
+    ///
+    /// If a seed is given, the same random subset is chosen for the same packs, independent of their order.
+    fn apply(
+        self,
+        packs: impl IntoIterator<Item = IndexPack>,
+        seed: Option<u64>,
+    ) -> Vec<IndexPack> {
+        match seed {
+            Some(seed) => {
+                let mut packs: Vec<_> = packs.into_iter().collect();
+                packs.sort_unstable_by_key(|p| p.id);
+                self.apply_with_rng(packs, Local::now(), &mut StdRng::seed_from_u64(seed))
+            }
+            None => self.apply_with_rng(packs, Local::now(), &mut thread_rng()),
+        }
     }
 
     fn apply_with_rng(
         self,
         packs: impl IntoIterator<Item = IndexPack>,
+        now: DateTime<Local>,
         rng: &mut impl Rng,
     ) -> Vec<IndexPack> {
         fn id_matches_n_m(id: &Id, n: u32, m: u32) -> bool {
             id.as_u32() % m == n % m
         }
 
+        // age of the pack; packs from the future are treated as new
+        let age = |p: &IndexPack| p.time.map(|time| (now - time).to_std().unwrap_or_default());
+
         let mut total_size: u64 = 0;
         let mut packs: Vec<_> = packs
             .into_iter()
@@ -81,6 +114,14 @@ impl ReadSubsetOption {
                 packs.retain(|p| id_matches_n_m(&p.id, n, m));
                 None
             }
+            Self::Newest(duration) => {
+                packs.retain(|p| age(p).is_some_and(|age| age <= duration));
+                None
+            }
+            Self::Oldest(duration) => {
+                packs.retain(|p| age(p).map_or(true, |age| age > duration));
+                None
+            }
         } {
             // random subset of given size is required
             packs.shuffle(rng);
@@ -156,13 +197,33 @@ impl FromStr for ReadSubsetOption {
             })?;
 
             Self::Percentage(percentage)
+        } else if let Some((newest_or_oldest, duration)) = s.split_once(':') {
+            let duration = humantime::parse_duration(duration).map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::InvalidInput,
+                    "Error parsing duration from value `{value}` for ReadSubset option. Please use a value like 'newest:7d'.",
+                    err,
+                )
+                .attach_context("value", s)
+            })?;
+            match newest_or_oldest {
+                "newest" => Self::Newest(duration),
+                "oldest" => Self::Oldest(duration),
+                _ => {
+                    return Err(RusticError::new(
+                        ErrorKind::InvalidInput,
+                        "Invalid value `{value}` for ReadSubset option. Allowed values: 'newest:<duration>' or 'oldest:<duration>'.",
+                    )
+                    .attach_context("value", s));
+                }
+            }
         } else if let Some((n, m)) = s.split_once('/') {
             let now = Local::now().naive_local();
             let subset = parse_n_m(now, n, m).map_err(
                 |err|
                     RusticError::with_source(
                         ErrorKind::InvalidInput,
-                        "Error parsing 'n/m' from value `{value}` for ReadSubset option. Allowed values: 'all', 'x%', 'n/m' or a size.",
+                        "Error parsing 'n/m' from value `{value}` for ReadSubset option. Allowed values: 'all', 'x%', 'n/m', 'newest:<duration>', 'oldest:<duration>' or a size.",
                         err
                     )
                     .attach_context("value", s)
@@ -176,7 +237,7 @@ impl FromStr for ReadSubsetOption {
                     .map_err(|err| {
                         RusticError::with_source(
                             ErrorKind::InvalidInput,
-                            "Error parsing size from value `{value}` for ReadSubset option. Allowed values: 'all', 'x%', 'n/m' or a size.",
+                            "Error parsing size from value `{value}` for ReadSubset option. Allowed values: 'all', 'x%', 'n/m', 'newest:<duration>', 'oldest:<duration>' or a size.",
                             err
                         )
                         .attach_context("value", s)
@@ -204,12 +265,329 @@ pub struct CheckOptions {
     #[cfg_attr(feature = "clap", clap(long))]
     pub read_data: bool,
 
-    /// Read only a subset of the data. Allowed values: "all", "n/m" for specific part, "x%" or a size for a random subset.
+    /// Read only a subset of the data. Allowed values: "all", "n/m" for specific part, "x%" or a size for a random subset,
+    /// "newest:<duration>" or "oldest:<duration>" for packs written within or before the given duration.
     #[cfg_attr(
         feature = "clap",
         clap(long, default_value = "all", requires = "read_data")
     )]
     pub read_data_subset: ReadSubsetOption,
+
+    /// Seed for choosing a random subset of the data. Using the same seed reads the same packs,
+    /// as long as the repository doesn't change. If not set, a different subset is chosen for each run.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, value_name = "SEED", requires = "read_data")
+    )]
+    pub read_data_seed: Option<u64>,
+
+    /// Only check snapshots newer than the given snapshot and the packs referenced by them.
+    /// Packs written before the given snapshot are not read, even if `read_data` is set.
+    ///
+    /// # Note
+    ///
+    /// This is meant for fast checks after a backup and is no full integrity check of the repository:
+    /// Trees and packs only referenced by older snapshots are not checked.
+    ///
+    /// Whether a pack is new is decided by comparing the time the pack was written (as saved in the index)
+    /// with the `time` of the given snapshot. As the snapshot time can be set by the user, e.g. with
+    /// `--time` during backup, a wrong snapshot time may skip packs which should be read.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "SNAPSHOT_ID"))]
+    pub since: Option<SnapshotId>,
+
+    /// Don't log the issues found, only return them in the [`CheckResults`]
+    #[cfg_attr(feature = "clap", clap(skip))]
+    pub quiet: bool,
+}
+
+/// The kind of a [`CheckIssue`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CheckIssueKind {
+    /// A file in the hot repository doesn't exist in the repository
+    HotFileNotInRepo {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+    },
+    /// The size of a file in the hot repository doesn't match its size in the repository
+    HotFileSizeMismatch {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+        /// The size in the hot repository
+        size_hot: u32,
+        /// The size in the repository
+        size: u32,
+    },
+    /// A file of the repository is missing in the hot repository
+    HotFileMissing {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+    },
+    /// A file could not be read from the cache
+    CacheReadFailed {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+    },
+    /// A file could not be read from the backend
+    ReadFailed {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+    },
+    /// A cached file is not identical to the file in the backend
+    CacheMismatch {
+        /// The type of the file
+        file_type: FileType,
+        /// The id of the file
+        id: Id,
+    },
+    /// Pack files could not be removed from the cache
+    CacheCleanupFailed,
+    /// A pack marked for deletion has no time set
+    PackTimeMissing {
+        /// The id of the pack
+        id: PackId,
+    },
+    /// The type of a blob in the index doesn't match the type of its pack
+    BlobTypeMismatch {
+        /// The id of the pack
+        pack: PackId,
+        /// The id of the blob
+        blob: BlobId,
+        /// The type of the blob
+        blob_type: BlobType,
+        /// The type of the pack
+        expected: BlobType,
+    },
+    /// The offset of a blob in the index doesn't match the offset computed from the previous blobs
+    BlobOffsetMismatch {
+        /// The id of the pack
+        pack: PackId,
+        /// The id of the blob
+        blob: BlobId,
+        /// The offset in the index
+        offset: u32,
+        /// The expected offset
+        expected: u32,
+    },
+    /// A pack file is not referenced by the index
+    PackNotInIndex {
+        /// The id of the pack
+        id: PackId,
+        /// Whether the pack is in the hot repository
+        hot: bool,
+    },
+    /// A pack in the hot repository is a data pack
+    HotDataPack {
+        /// The id of the pack
+        id: PackId,
+    },
+    /// The size of a pack file doesn't match the size computed by the index
+    PackSizeMismatch {
+        /// The id of the pack
+        id: PackId,
+        /// Whether the pack is in the hot repository
+        hot: bool,
+        /// The size computed by the index
+        index_size: u32,
+        /// The actual size
+        size: u32,
+    },
+    /// A pack referenced by the index is missing
+    PackMissing {
+        /// The id of the pack
+        id: PackId,
+        /// Whether the pack is missing in the hot repository
+        hot: bool,
+    },
+    /// A file in a tree doesn't have a content
+    FileWithoutContent {
+        /// The path of the file
+        path: PathBuf,
+    },
+    /// A blob of a file has a null id
+    FileBlobNullId {
+        /// The path of the file
+        path: PathBuf,
+        /// The position of the blob within the file content
+        index: usize,
+    },
+    /// A blob of a file is missing in the index
+    FileBlobMissing {
+        /// The path of the file
+        path: PathBuf,
+        /// The id of the blob
+        blob: DataId,
+    },
+    /// A dir in a tree doesn't have a subtree
+    DirWithoutSubtree {
+        /// The path of the dir
+        path: PathBuf,
+    },
+    /// The subtree of a dir has a null id
+    DirSubtreeNullId {
+        /// The path of the dir
+        path: PathBuf,
+    },
+    /// The subtree of a dir is missing in the index
+    DirSubtreeMissing {
+        /// The path of the dir
+        path: PathBuf,
+        /// The id of the subtree
+        tree: TreeId,
+    },
+    /// The header of a pack file could not be read
+    PackHeaderReadFailed {
+        /// The id of the pack
+        id: PackId,
+    },
+    /// A pack file could not be read
+    PackReadFailed {
+        /// The id of the pack
+        id: PackId,
+    },
+    /// The data of a pack file could not be decrypted or parsed
+    PackCheckFailed {
+        /// The id of the pack
+        id: PackId,
+    },
+    /// The data of a pack file is invalid
+    InvalidPack {
+        /// The id of the pack
+        id: PackId,
+        /// The problem found
+        problem: PackProblem,
+    },
+}
+
+/// An issue found by `check`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CheckIssue {
+    /// The severity of the issue, either [`Severity::Warning`] or [`Severity::Error`]
+    pub severity: Severity,
+    /// The machine-readable kind of the issue
+    pub kind: CheckIssueKind,
+    /// The human-readable description of the issue
+    pub message: String,
+}
+
+impl Display for CheckIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// The issues found by `check`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+#[must_use]
+pub struct CheckResults {
+    /// The issues found
+    pub issues: Vec<CheckIssue>,
+}
+
+impl CheckResults {
+    /// Returns whether no issue with [`Severity::Error`] has been found
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// Returns the issues with [`Severity::Error`]
+    pub fn errors(&self) -> impl Iterator<Item = &CheckIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+    }
+
+    /// Returns the issues with [`Severity::Warning`]
+    pub fn warnings(&self) -> impl Iterator<Item = &CheckIssue> {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+    }
+
+    /// Turn the results into an error if any issue with [`Severity::Error`] has been found
+    ///
+    /// # Errors
+    ///
+    /// * If an issue with [`Severity::Error`] has been found.
+    pub fn into_result(self) -> RusticResult<Self> {
+        let errors = self.errors().count();
+        if errors > 0 {
+            let first = self.errors().next().map(ToString::to_string);
+            return Err(RusticError::new(
+                ErrorKind::Verification,
+                "Check found `{count}` errors, the first one is: `{first}`",
+            )
+            .attach_context("count", errors.to_string())
+            .attach_context("first", first.unwrap_or_default())
+            .attach_severity(Severity::Error));
+        }
+        Ok(self)
+    }
+}
+
+/// Collects the issues found by `check` and logs them, if requested
+#[derive(Debug)]
+struct CheckResultsCollector {
+    /// Whether to log the issues
+    log: bool,
+    /// The issues collected so far
+    issues: Mutex<Vec<CheckIssue>>,
+}
+
+impl CheckResultsCollector {
+    /// Create a new collector
+    fn new(opts: CheckOptions) -> Self {
+        Self {
+            log: !opts.quiet,
+            issues: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add an issue
+    fn add(&self, severity: Severity, kind: CheckIssueKind, message: String) {
+        if self.log {
+            if severity == Severity::Warning {
+                warn!("{message}");
+            } else {
+                error!("{message}");
+            }
+        }
+        self.issues.lock().unwrap().push(CheckIssue {
+            severity,
+            kind,
+            message,
+        });
+    }
+
+    /// Add an issue with [`Severity::Error`]
+    fn error(&self, kind: CheckIssueKind, message: String) {
+        self.add(Severity::Error, kind, message);
+    }
+
+    /// Add an issue with [`Severity::Warning`]
+    fn warn(&self, kind: CheckIssueKind, message: String) {
+        self.add(Severity::Warning, kind, message);
+    }
+
+    /// Return the collected issues
+    fn into_results(self) -> CheckResults {
+        CheckResults {
+            issues: self.issues.into_inner().unwrap(),
+        }
+    }
 }
 
 /// Runs the `check` command
@@ -227,17 +605,24 @@ pub struct CheckOptions {
 ///
 /// # Errors
 ///
-/// * If the repository is corrupted
+/// * If files needed for the check could not be listed or read.
+/// * If the check has been cancelled.
 ///
-/// # Panics
+/// # Returns
 ///
-// TODO: Add panics
+/// The issues found
 pub(crate) fn check_repository<P: ProgressBars, S: Open>(
     repo: &Repository<P, S>,
     opts: CheckOptions,
     trees: Vec<TreeId>,
-) -> RusticResult<()> {
+) -> RusticResult<CheckResults> {
+    let results = CheckResultsCollector::new(opts);
     let be = repo.dbe();
+    let since = opts
+        .since
+        .map(|id| be.get_file::<SnapshotFile>(&id))
+        .transpose()?
+        .map(|snap| snap.time);
     let cache = repo.cache();
     let hot_be = &repo.be_hot;
     let raw_be = repo.dbe();
@@ -253,18 +638,20 @@ pub(crate) fn check_repository<P: ProgressBars, S: Open>(
 
                 let p = pb.progress_bytes(format!("checking {file_type:?} in cache..."));
                 // TODO: Make concurrency (20) customizable
-                check_cache_files(20, cache, raw_be, file_type, &p)?;
+                check_cache_files(20, cache, raw_be, file_type, &p, &results)?;
             }
         }
     }
 
     if let Some(hot_be) = hot_be {
         for file_type in [FileType::Snapshot, FileType::Index] {
-            check_hot_files(raw_be, hot_be, file_type, pb)?;
+            check_hot_files(raw_be, hot_be, file_type, pb, &results)?;
         }
     }
 
-    let index_collector = check_packs(be, hot_be.as_ref(), pb)?;
+    repo.cancel.check()?;
+    let index_collector = check_packs(be, hot_be.as_ref(), pb, &results)?;
+    repo.cancel.check()?;
 
     if let Some(cache) = &cache {
         let p = pb.progress_spinner("cleaning up packs from cache...");
@@ -274,9 +661,12 @@ pub(crate) fn check_repository<P: ProgressBars, S: Open>(
             .map(|(id, size)| (**id, *size))
             .collect();
         if let Err(err) = cache.remove_not_in_list(FileType::Pack, &ids) {
-            warn!(
-                "Error in cache backend removing pack files: {}",
-                err.display_log()
+            results.warn(
+                CheckIssueKind::CacheCleanupFailed,
+                format!(
+                    "Error in cache backend removing pack files: {}",
+                    err.display_log()
+                ),
             );
         }
         p.finish();
@@ -284,47 +674,196 @@ pub(crate) fn check_repository<P: ProgressBars, S: Open>(
         if !opts.trust_cache {
             let p = pb.progress_bytes("checking packs in cache...");
             // TODO: Make concurrency (5) customizable
-            check_cache_files(5, cache, raw_be, FileType::Pack, &p)?;
+            check_cache_files(5, cache, raw_be, FileType::Pack, &p, &results)?;
         }
     }
 
     let index_be = GlobalIndex::new_from_index(index_collector.into_index());
 
-    let packs = check_trees(be, &index_be, trees, pb)?;
+    let packs = check_trees(be, &index_be, trees, pb, &repo.cancel, &results)?;
 
     if opts.read_data {
         let packs = index_be
             .into_index()
             .into_iter()
-            .filter(|p| packs.contains(&p.id));
+            .filter(|p| packs.contains(&p.id))
+            .filter(|p| written_since(p, since));
+
+        read_packs(repo, opts, packs, &results)?;
+    }
+
+    Ok(results.into_results())
+}
+
+/// Returns the snapshots which are newer than the snapshot `since`
+///
+/// If `since` is `None`, all snapshots are returned.
+///
+/// # Arguments
+///
+/// * `snaps` - The snapshots to filter
+/// * `since` - The id of the marker snapshot
+///
+/// # Errors
+///
+/// * If the marker snapshot is not contained in `snaps`.
+pub(crate) fn snapshots_since(
+    snaps: Vec<SnapshotFile>,
+    since: Option<SnapshotId>,
+) -> RusticResult<Vec<SnapshotFile>> {
+    let Some(since) = since else {
+        return Ok(snaps);
+    };
+    let time = snaps
+        .iter()
+        .find(|snap| snap.id == since)
+        .ok_or_else(|| {
+            RusticError::new(
+                ErrorKind::InvalidInput,
+                "Snapshot `{id}` given as check marker does not exist.",
+            )
+            .attach_context("id", since.to_string())
+        })?
+        .time;
+    Ok(snaps.into_iter().filter(|snap| snap.time > time).collect())
+}
+
+/// Checks if the pack has been written at or after `since`
+///
+/// `since` is the `time` of the marker snapshot which is compared with the write time of the pack
+/// saved in the index. Note that the snapshot time can be set by the user and need not be the time
+/// the snapshot was actually created.
+///
+/// Packs without a time are always considered as new.
+fn written_since(pack: &IndexPack, since: Option<DateTime<Local>>) -> bool {
+    since.map_or(true, |since| pack.time.map_or(true, |time| time >= since))
+}
+
+/// Runs the `check` command only for the given snapshot
+///
+/// Only the snapshot tree and the packs referenced by it are checked. The headers of all
+/// referenced packs are read and compared with the index, even if `read_data` is not set.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to check
+/// * `opts` - The check options to use
+/// * `snap` - The snapshot to check
+///
+/// # Errors
+///
+/// * If the index could not be read.
+/// * If a tree could not be loaded.
+///
+/// # Returns
+///
+/// The issues found
+pub(crate) fn check_snapshot<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    opts: CheckOptions,
+    snap: &SnapshotFile,
+) -> RusticResult<CheckResults> {
+    let results = CheckResultsCollector::new(opts);
+    let be = repo.dbe();
+    let pb = &repo.pb;
 
-        debug!("using read-data-subset {:?}", opts.read_data_subset);
-        let packs = opts.read_data_subset.apply(packs);
+    let mut index_collector = IndexCollector::new(IndexType::Full);
+    let p = pb.progress_counter("reading index...");
+    for index in be.stream_all::<IndexFile>(&p)? {
+        index_collector.extend(index?.1.packs);
+    }
+    p.finish();
+    let index_be = GlobalIndex::new_from_index(index_collector.into_index());
 
-        repo.warm_up_wait(packs.iter().map(|pack| pack.id))?;
+    let packs = check_trees(be, &index_be, vec![snap.tree], pb, &repo.cancel, &results)?;
+    let packs: Vec<_> = index_be
+        .into_index()
+        .into_iter()
+        .filter(|p| packs.contains(&p.id))
+        .collect();
+
+    let p = pb.progress_counter("checking pack headers...");
+    p.set_length(packs.len() as u64);
+    let cancel = &repo.cancel;
+    let res = packs.par_iter().try_for_each(|pack| -> RusticResult<_> {
+        cancel.check()?;
+        check_index_pack(pack.clone(), &results);
+        check_pack_header(be, pack, &results);
+        p.inc(1);
+        Ok(())
+    });
+    p.finish();
+    res?;
+
+    if opts.read_data {
+        read_packs(repo, opts, packs, &results)?;
+    }
 
-        let total_pack_size = packs.iter().map(|pack| u64::from(pack.pack_size())).sum();
-        let p = pb.progress_bytes("reading pack data...");
-        p.set_length(total_pack_size);
+    Ok(results.into_results())
+}
+
+/// Reads the given subset of packs and checks their contents
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `opts` - The check options containing the subset and seed to use
+/// * `packs` - The packs to choose the subset from
+/// * `results` - The collector for the issues found
+///
+/// # Errors
+///
+/// * If warming up the packs failed.
+/// * If the check has been cancelled.
+fn read_packs<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    opts: CheckOptions,
+    packs: impl IntoIterator<Item = IndexPack>,
+    results: &CheckResultsCollector,
+) -> RusticResult<()> {
+    let be = repo.dbe();
+    let subset = opts.read_data_subset;
+    debug!("using read-data-subset {subset:?}");
+    let packs = subset.apply(packs, opts.read_data_seed);
 
-        packs.into_par_iter().for_each(|pack| {
+    repo.warm_up_wait(packs.iter().map(|pack| pack.id))?;
+
+    let total_pack_size = packs.iter().map(|pack| u64::from(pack.pack_size())).sum();
+    let p = repo.pb.progress_bytes("reading pack data...");
+    p.set_length(total_pack_size);
+
+    let cancel = &repo.cancel;
+    let res = packs
+        .into_par_iter()
+        .try_for_each(|pack| -> RusticResult<_> {
+            cancel.check()?;
             let id = pack.id;
             let data = match be.read_full(FileType::Pack, &id) {
                 Ok(data) => data,
                 Err(err) => {
-                    error!("Error reading data for pack {id} : {}", err.display_log());
-                    return;
+                    results.error(
+                        CheckIssueKind::PackReadFailed { id },
+                        format!("Error reading data for pack {id} : {}", err.display_log()),
+                    );
+                    return Ok(());
                 }
             };
-            match check_pack(be, pack, data, &p) {
+            match check_pack(be, pack, data, &p, results) {
                 Ok(()) => {}
-                Err(err) => error!("Pack {id} is not valid: {}", err.display_log()),
+                Err(err) => results.error(
+                    CheckIssueKind::PackCheckFailed { id },
+                    format!("Pack {id} is not valid: {}", err.display_log()),
+                ),
             }
+            Ok(())
         });
-        p.finish();
-    }
-
-    Ok(())
+    p.finish();
+    res
 }
 
 /// Checks if all files in the backend are also in the hot backend
@@ -335,15 +874,17 @@ pub(crate) fn check_repository<P: ProgressBars, S: Open>(
 /// * `be_hot` - The hot backend to check
 /// * `file_type` - The type of the files to check
 /// * `pb` - The progress bar to use
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a file is missing or has a different size
+/// * If the files could not be listed
 fn check_hot_files(
     be: &impl ReadBackend,
     be_hot: &impl ReadBackend,
     file_type: FileType,
     pb: &impl ProgressBars,
+    results: &CheckResultsCollector,
 ) -> RusticResult<()> {
     let p = pb.progress_spinner(format!("checking {file_type:?} in hot repo..."));
     let mut files = be
@@ -355,16 +896,28 @@ fn check_hot_files(
 
     for (id, size_hot) in files_hot {
         match files.remove(&id) {
-            None => error!("hot file Type: {file_type:?}, Id: {id} does not exist in repo"),
-            Some(size) if size != size_hot => {
-                error!("Type: {file_type:?}, Id: {id}: hot size: {size_hot}, actual size: {size}");
-            }
+            None => results.error(
+                CheckIssueKind::HotFileNotInRepo { file_type, id },
+                format!("hot file Type: {file_type:?}, Id: {id} does not exist in repo"),
+            ),
+            Some(size) if size != size_hot => results.error(
+                CheckIssueKind::HotFileSizeMismatch {
+                    file_type,
+                    id,
+                    size_hot,
+                    size,
+                },
+                format!("Type: {file_type:?}, Id: {id}: hot size: {size_hot}, actual size: {size}"),
+            ),
             _ => {} //everything ok
         }
     }
 
     for (id, _) in files {
-        error!("hot file Type: {file_type:?}, Id: {id} is missing!",);
+        results.error(
+            CheckIssueKind::HotFileMissing { file_type, id },
+            format!("hot file Type: {file_type:?}, Id: {id} is missing!"),
+        );
     }
     p.finish();
 
@@ -380,16 +933,18 @@ fn check_hot_files(
 /// * `be` - The backend to check
 /// * `file_type` - The type of the files to check
 /// * `p` - The progress bar to use
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a file is missing or has a different size
+/// * If the files in the cache could not be listed
 fn check_cache_files(
     _concurrency: usize,
     cache: &Cache,
     be: &impl ReadBackend,
     file_type: FileType,
     p: &impl Progress,
+    results: &CheckResultsCollector,
 ) -> RusticResult<()> {
     let files = cache.list_with_size(file_type)?;
 
@@ -408,23 +963,26 @@ fn check_cache_files(
                 cache.read_full(file_type, &id),
                 be.read_full(file_type, &id),
             ) {
-                (Err(err), _) => {
-                    error!(
+                (Err(err), _) => results.error(
+                    CheckIssueKind::CacheReadFailed { file_type, id },
+                    format!(
                         "Error reading cached file Type: {file_type:?}, Id: {id} : {}",
                         err.display_log()
-                    );
-                }
-                (_, Err(err)) => {
-                    error!(
+                    ),
+                ),
+                (_, Err(err)) => results.error(
+                    CheckIssueKind::ReadFailed { file_type, id },
+                    format!(
                         "Error reading file Type: {file_type:?}, Id: {id} : {}",
                         err.display_log()
-                    );
-                }
-                (Ok(Some(data_cached)), Ok(data)) if data_cached != data => {
-                    error!(
+                    ),
+                ),
+                (Ok(Some(data_cached)), Ok(data)) if data_cached != data => results.error(
+                    CheckIssueKind::CacheMismatch { file_type, id },
+                    format!(
                         "Cached file Type: {file_type:?}, Id: {id} is not identical to backend!"
-                    );
-                }
+                    ),
+                ),
                 (Ok(_), Ok(_)) => {} // everything ok
             }
 
@@ -441,12 +999,12 @@ fn check_cache_files(
 ///
 /// * `be` - The backend to check
 /// * `hot_be` - The hot backend to check
-/// * `read_data` - Whether to read the data of the packs
 /// * `pb` - The progress bar to use
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a pack is missing or has a different size
+/// * If the index could not be read or the packs could not be listed
 ///
 /// # Returns
 ///
@@ -455,6 +1013,7 @@ fn check_packs(
     be: &impl DecryptReadBackend,
     hot_be: Option<&impl ReadBackend>,
     pb: &impl ProgressBars,
+    results: &CheckResultsCollector,
 ) -> RusticResult<IndexCollector> {
     let mut packs = HashMap::new();
     let mut tree_packs = HashMap::new();
@@ -475,29 +1034,13 @@ fn check_packs(
 
             // Check if time is set _
             if check_time && p.time.is_none() {
-                error!("pack {}: No time is set! Run prune to correct this!", p.id);
+                results.error(
+                    CheckIssueKind::PackTimeMissing { id: p.id },
+                    format!("pack {}: No time is set! Run prune to correct this!", p.id),
+                );
             }
 
-            // check offsests in index
-            let mut expected_offset: u32 = 0;
-            let mut blobs = p.blobs;
-            blobs.sort_unstable();
-            for blob in blobs {
-                if blob.tpe != blob_type {
-                    error!(
-                        "pack {}: blob {} blob type does not match: type: {:?}, expected: {:?}",
-                        p.id, blob.id, blob.tpe, blob_type
-                    );
-                }
-
-                if blob.offset != expected_offset {
-                    error!(
-                        "pack {}: blob {} offset in index: {}, expected: {}",
-                        p.id, blob.id, blob.offset, expected_offset
-                    );
-                }
-                expected_offset += blob.length;
-            }
+            check_index_pack(p, results);
         }
     }
 
@@ -505,17 +1048,62 @@ fn check_packs(
 
     if let Some(hot_be) = hot_be {
         let p = pb.progress_spinner("listing packs in hot repo...");
-        check_packs_list_hot(hot_be, tree_packs, &packs)?;
+        check_packs_list_hot(hot_be, tree_packs, &packs, results)?;
         p.finish();
     }
 
     let p = pb.progress_spinner("listing packs...");
-    check_packs_list(be, packs)?;
+    check_packs_list(be, packs, results)?;
     p.finish();
 
     Ok(index_collector)
 }
 
+/// Checks the blob types and offsets of a pack in the index
+///
+/// # Arguments
+///
+/// * `p` - The pack to check
+/// * `results` - The collector for the issues found
+fn check_index_pack(p: IndexPack, results: &CheckResultsCollector) {
+    let blob_type = p.blob_type();
+    let mut expected_offset: u32 = 0;
+    let mut blobs = p.blobs;
+    blobs.sort_unstable();
+    for blob in blobs {
+        if blob.tpe != blob_type {
+            results.error(
+                CheckIssueKind::BlobTypeMismatch {
+                    pack: p.id,
+                    blob: blob.id,
+                    blob_type: blob.tpe,
+                    expected: blob_type,
+                },
+                format!(
+                    "pack {}: blob {} blob type does not match: type: {:?}, expected: {:?}",
+                    p.id, blob.id, blob.tpe, blob_type
+                ),
+            );
+        }
+
+        if blob.offset != expected_offset {
+            results.error(
+                CheckIssueKind::BlobOffsetMismatch {
+                    pack: p.id,
+                    blob: blob.id,
+                    offset: blob.offset,
+                    expected: expected_offset,
+                },
+                format!(
+                    "pack {}: blob {} offset in index: {}, expected: {}",
+                    p.id, blob.id, blob.offset, expected_offset
+                ),
+            );
+        }
+        expected_offset += blob.length;
+    }
+}
+
 // TODO: Add documentation
 /// Checks if all packs in the backend are also in the index
 ///
@@ -523,23 +1111,41 @@ fn check_packs(
 ///
 /// * `be` - The backend to check
 /// * `packs` - The packs to check
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a pack is missing or has a different size
-fn check_packs_list(be: &impl ReadBackend, mut packs: HashMap<PackId, u32>) -> RusticResult<()> {
+/// * If the packs could not be listed
+fn check_packs_list(
+    be: &impl ReadBackend,
+    mut packs: HashMap<PackId, u32>,
+    results: &CheckResultsCollector,
+) -> RusticResult<()> {
     for (id, size) in be.list_with_size(FileType::Pack)? {
-        match packs.remove(&PackId::from(id)) {
-            None => warn!("pack {id} not referenced in index. Can be a parallel backup job. To repair: 'rustic repair index'."),
-            Some(index_size) if index_size != size => {
-                error!("pack {id}: size computed by index: {index_size}, actual size: {size}. To repair: 'rustic repair index'.");
-            }
+        let id = PackId::from(id);
+        match packs.remove(&id) {
+            None => results.warn(
+                CheckIssueKind::PackNotInIndex { id, hot: false },
+                format!("pack {id} not referenced in index. Can be a parallel backup job. To repair: 'rustic repair index'."),
+            ),
+            Some(index_size) if index_size != size => results.error(
+                CheckIssueKind::PackSizeMismatch {
+                    id,
+                    hot: false,
+                    index_size,
+                    size,
+                },
+                format!("pack {id}: size computed by index: {index_size}, actual size: {size}. To repair: 'rustic repair index'."),
+            ),
             _ => {} //everything ok
         }
     }
 
     for (id, _) in packs {
-        error!("pack {id} is referenced by the index but not present! To repair: 'rustic repair index'.",);
+        results.error(
+            CheckIssueKind::PackMissing { id, hot: false },
+            format!("pack {id} is referenced by the index but not present! To repair: 'rustic repair index'."),
+        );
     }
     Ok(())
 }
@@ -548,35 +1154,54 @@ fn check_packs_list(be: &impl ReadBackend, mut packs: HashMap<PackId, u32>) -> R
 ///
 /// # Arguments
 ///
-/// * `be` - The backend to check
-/// * `packs` - The packs to check
+/// * `be` - The hot backend to check
+/// * `treepacks` - The tree packs to check
+/// * `packs` - All packs of the index
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a pack is missing or has a different size
+/// * If the packs could not be listed
 fn check_packs_list_hot(
     be: &impl ReadBackend,
     mut treepacks: HashMap<PackId, u32>,
     packs: &HashMap<PackId, u32>,
+    results: &CheckResultsCollector,
 ) -> RusticResult<()> {
     for (id, size) in be.list_with_size(FileType::Pack)? {
-        match treepacks.remove(&PackId::from(id)) {
+        let id = PackId::from(id);
+        match treepacks.remove(&id) {
             None => {
-                if packs.contains_key(&PackId::from(id)) {
-                    warn!("hot pack {id} is a data pack. This should not happen.");
+                if packs.contains_key(&id) {
+                    results.warn(
+                        CheckIssueKind::HotDataPack { id },
+                        format!("hot pack {id} is a data pack. This should not happen."),
+                    );
                 } else {
-                    warn!("hot pack {id} not referenced in index. Can be a parallel backup job. To repair: 'rustic repair index'.");
+                    results.warn(
+                        CheckIssueKind::PackNotInIndex { id, hot: true },
+                        format!("hot pack {id} not referenced in index. Can be a parallel backup job. To repair: 'rustic repair index'."),
+                    );
                 }
             }
-            Some(index_size) if index_size != size => {
-                error!("hot pack {id}: size computed by index: {index_size}, actual size: {size}. To repair: 'rustic repair index'.");
-            }
+            Some(index_size) if index_size != size => results.error(
+                CheckIssueKind::PackSizeMismatch {
+                    id,
+                    hot: true,
+                    index_size,
+                    size,
+                },
+                format!("hot pack {id}: size computed by index: {index_size}, actual size: {size}. To repair: 'rustic repair index'."),
+            ),
             _ => {} //everything ok
         }
     }
 
     for (id, _) in treepacks {
-        error!("tree pack {id} is referenced by the index but not present in hot repo! To repair: 'rustic repair index'.",);
+        results.error(
+            CheckIssueKind::PackMissing { id, hot: true },
+            format!("tree pack {id} is referenced by the index but not present in hot repo! To repair: 'rustic repair index'."),
+        );
     }
     Ok(())
 }
@@ -587,41 +1212,57 @@ fn check_packs_list_hot(
 ///
 /// * `index` - The index to check
 /// * `pb` - The progress bar to use
+/// * `cancel` - The token to cancel the check
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If a snapshot or tree is missing or has a different size
+/// * If a tree could not be loaded
+/// * If the check has been cancelled
 fn check_trees(
     be: &impl DecryptReadBackend,
     index: &impl ReadGlobalIndex,
     snap_trees: Vec<TreeId>,
     pb: &impl ProgressBars,
+    cancel: &CancellationToken,
+    results: &CheckResultsCollector,
 ) -> RusticResult<BTreeSet<PackId>> {
     let mut packs = BTreeSet::new();
     let p = pb.progress_counter("checking trees...");
     let mut tree_streamer = TreeStreamerOnce::new(be, index, snap_trees, p)?;
     while let Some(item) = tree_streamer.next().transpose()? {
+        cancel.check()?;
         let (path, tree) = item;
         for node in tree.nodes {
+            let path = path.join(node.name());
             match node.node_type {
                 NodeType::File => node.content.as_ref().map_or_else(
                     || {
-                        error!("file {:?} doesn't have a content", path.join(node.name()));
+                        results.error(
+                            CheckIssueKind::FileWithoutContent { path: path.clone() },
+                            format!("file {path:?} doesn't have a content"),
+                        );
                     },
                     |content| {
                         for (i, id) in content.iter().enumerate() {
                             if id.is_null() {
-                                error!("file {:?} blob {} has null ID", path.join(node.name()), i);
+                                results.error(
+                                    CheckIssueKind::FileBlobNullId {
+                                        path: path.clone(),
+                                        index: i,
+                                    },
+                                    format!("file {path:?} blob {i} has null ID"),
+                                );
                             }
 
                             match index.get_data(id) {
-                                None => {
-                                    error!(
-                                        "file {:?} blob {} is missing in index",
-                                        path.join(node.name()),
-                                        id
-                                    );
-                                }
+                                None => results.error(
+                                    CheckIssueKind::FileBlobMissing {
+                                        path: path.clone(),
+                                        blob: *id,
+                                    },
+                                    format!("file {path:?} blob {id} is missing in index"),
+                                ),
                                 Some(entry) => {
                                     _ = packs.insert(entry.pack);
                                 }
@@ -632,20 +1273,22 @@ fn check_trees(
 
                 NodeType::Dir => {
                     match node.subtree {
-                        None => {
-                            error!("dir {:?} subtree does not exist", path.join(node.name()));
-                        }
-                        Some(tree) if tree.is_null() => {
-                            error!("dir {:?} subtree has null ID", path.join(node.name()));
-                        }
+                        None => results.error(
+                            CheckIssueKind::DirWithoutSubtree { path: path.clone() },
+                            format!("dir {path:?} subtree does not exist"),
+                        ),
+                        Some(tree) if tree.is_null() => results.error(
+                            CheckIssueKind::DirSubtreeNullId { path: path.clone() },
+                            format!("dir {path:?} subtree has null ID"),
+                        ),
                         Some(id) => match index.get_tree(&id) {
-                            None => {
-                                error!(
-                                    "dir {:?} subtree blob {} is missing in index",
-                                    path.join(node.name()),
-                                    id
-                                );
-                            }
+                            None => results.error(
+                                CheckIssueKind::DirSubtreeMissing {
+                                    path: path.clone(),
+                                    tree: id,
+                                },
+                                format!("dir {path:?} subtree blob {id} is missing in index"),
+                            ),
                             Some(entry) => {
                                 _ = packs.insert(entry.pack);
                             }
@@ -661,7 +1304,101 @@ fn check_trees(
     Ok(packs)
 }
 
-/// Check if a pack is valid
+/// Check if the header of a pack file matches the index
+///
+/// Only the header is read from the pack file, not the blob data.
+///
+/// # Arguments
+///
+/// * `be` - The backend to use
+/// * `index_pack` - The pack to check
+/// * `results` - The collector for the issues found
+fn check_pack_header(
+    be: &impl DecryptReadBackend,
+    index_pack: &IndexPack,
+    results: &CheckResultsCollector,
+) {
+    let id = index_pack.id;
+    let header_len = PackHeaderRef::from_index_pack(index_pack).size();
+    match PackHeader::from_file(be, id, Some(header_len), index_pack.pack_size()) {
+        Err(err) => results.error(
+            CheckIssueKind::PackHeaderReadFailed { id },
+            format!("pack {id}: reading header failed: {}", err.display_log()),
+        ),
+        Ok(header) => {
+            let mut blobs = index_pack.blobs.clone();
+            blobs.sort_unstable_by_key(|b| b.offset);
+            if header.into_blobs() != blobs {
+                results.error(
+                    CheckIssueKind::InvalidPack {
+                        id,
+                        problem: PackProblem::HeaderMismatch,
+                    },
+                    format!("pack {id}: Header from pack file does not match the index"),
+                );
+            }
+        }
+    }
+}
+
+/// A problem found when verifying a pack file, see [`PackVerification`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackProblem {
+    /// The size of the pack file doesn't match the size computed from the index
+    SizeMismatch {
+        /// The size computed from the index
+        expected: u32,
+        /// The actual size of the pack file
+        actual: usize,
+    },
+    /// The hash of the pack file doesn't match its id
+    HashMismatch {
+        /// The computed hash
+        computed: PackId,
+    },
+    /// The header length saved in the pack file doesn't match the length computed from the index
+    HeaderLengthMismatch {
+        /// The header length saved in the pack file
+        in_pack: u32,
+        /// The header length computed from the index
+        computed: u32,
+    },
+    /// The header of the pack file doesn't match the index
+    HeaderMismatch,
+    /// The uncompressed length of a blob doesn't match the saved uncompressed length
+    UncompressedLengthMismatch {
+        /// The id of the blob
+        blob: BlobId,
+    },
+    /// The hash of a blob doesn't match its id
+    BlobHashMismatch {
+        /// The id of the blob
+        blob: BlobId,
+        /// The computed hash
+        computed: BlobId,
+    },
+}
+
+/// The result of verifying a single pack file, see [`Repository::verify_pack`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PackVerification {
+    /// The id of the verified pack
+    pub id: PackId,
+    /// The problems found. If the pack itself is broken, the contained blobs are not checked.
+    pub problems: Vec<PackProblem>,
+}
+
+impl PackVerification {
+    /// Returns whether the pack is valid, i.e. no problems have been found
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check if a pack is valid and collect all problems found
 ///
 /// # Arguments
 ///
@@ -669,34 +1406,92 @@ fn check_trees(
 /// * `index_pack` - The pack to check
 /// * `data` - The data of the pack
 /// * `p` - The progress bar to use
+/// * `results` - The collector for the issues found
 ///
 /// # Errors
 ///
-/// * If the pack is invalid
+/// * If the pack could not be decrypted or parsed
+fn check_pack(
+    be: &impl DecryptReadBackend,
+    index_pack: IndexPack,
+    data: Bytes,
+    p: &impl Progress,
+    results: &CheckResultsCollector,
+) -> RusticResult<()> {
+    let size = index_pack.pack_size();
+    let verification = verify_pack_data(be, index_pack, data, p)?;
+    let id = verification.id;
+    for problem in verification.problems {
+        let message = match &problem {
+            PackProblem::SizeMismatch { actual, .. } => format!(
+                "pack {id}: data size does not match expected size. Read: {actual} bytes, expected: {size} bytes"
+            ),
+            PackProblem::HashMismatch { computed } => {
+                format!("pack {id}: Hash mismatch. Computed hash: {computed}")
+            }
+            PackProblem::HeaderLengthMismatch { in_pack, computed } => format!(
+                "pack {id}: Header length in pack file doesn't match index. In pack: {in_pack}, calculated: {computed}"
+            ),
+            PackProblem::HeaderMismatch => {
+                format!("pack {id}: Header from pack file does not match the index")
+            }
+            PackProblem::UncompressedLengthMismatch { blob } => format!(
+                "pack {id}, blob {blob}: Actual uncompressed length does not fit saved uncompressed length"
+            ),
+            PackProblem::BlobHashMismatch { blob, computed } => {
+                format!("pack {id}, blob {blob}: Hash mismatch. Computed hash: {computed}")
+            }
+        };
+        results.error(CheckIssueKind::InvalidPack { id, problem }, message);
+    }
+    Ok(())
+}
+
+/// Verify the data of a pack
 ///
-/// # Panics
+/// Checks the size, the hash, the header length, the header and the hashes of all contained blobs.
 ///
-/// * If zstd decompression fails.
-fn check_pack(
+/// # Arguments
+///
+/// * `be` - The backend to use
+/// * `index_pack` - The pack to check
+/// * `data` - The data of the pack
+/// * `p` - The progress bar to use
+///
+/// # Errors
+///
+/// * If the pack header or a blob could not be decrypted
+/// * If the pack header could not be parsed
+///
+/// # Returns
+///
+/// The problems found within the pack
+pub(crate) fn verify_pack_data(
     be: &impl DecryptReadBackend,
     index_pack: IndexPack,
     mut data: Bytes,
     p: &impl Progress,
-) -> RusticResult<()> {
+) -> RusticResult<PackVerification> {
     let id = index_pack.id;
+    let mut verification = PackVerification {
+        id,
+        problems: Vec::new(),
+    };
     let size = index_pack.pack_size();
     if data.len() != size as usize {
-        error!(
-            "pack {id}: data size does not match expected size. Read: {} bytes, expected: {size} bytes",
-            data.len()
-        );
-        return Ok(());
+        verification.problems.push(PackProblem::SizeMismatch {
+            expected: size,
+            actual: data.len(),
+        });
+        return Ok(verification);
     }
 
     let comp_id = PackId::from(hash(&data));
     if id != comp_id {
-        error!("pack {id}: Hash mismatch. Computed hash: {comp_id}");
-        return Ok(());
+        verification
+            .problems
+            .push(PackProblem::HashMismatch { computed: comp_id });
+        return Ok(verification);
     }
 
     // check header length
@@ -714,8 +1509,13 @@ fn check_pack(
         })?
         .to_u32();
     if pack_header_len != header_len {
-        error!("pack {id}: Header length in pack file doesn't match index. In pack: {pack_header_len}, calculated: {header_len}");
-        return Ok(());
+        verification
+            .problems
+            .push(PackProblem::HeaderLengthMismatch {
+                in_pack: pack_header_len,
+                computed: header_len,
+            });
+        return Ok(verification);
     }
 
     // check header
@@ -735,10 +1535,10 @@ fn check_pack(
     let mut blobs = index_pack.blobs;
     blobs.sort_unstable_by_key(|b| b.offset);
     if pack_blobs != blobs {
-        error!("pack {id}: Header from pack file does not match the index");
         debug!("pack file header: {pack_blobs:?}");
         debug!("index: {:?}", blobs);
-        return Ok(());
+        verification.problems.push(PackProblem::HeaderMismatch);
+        return Ok(verification);
     }
     p.inc(u64::from(header_len) + 4);
 
@@ -746,25 +1546,139 @@ fn check_pack(
     for blob in blobs {
         let blob_id = blob.id;
         let mut blob_data = be.decrypt(&data.split_to(blob.length as usize))?;
+        p.inc(blob.length.into());
 
         // TODO: this is identical to backend/decrypt.rs; unify these two parts!
         if let Some(length) = blob.uncompressed_length {
-            blob_data = decode_all(&*blob_data).unwrap();
+            blob_data = decompress(&blob_data, be.zstd_dictionary()).map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Internal,
+                    "Failed to decode zstd compressed data of blob `{blob_id}` in pack `{pack_id}`. The data may be corrupted.",
+                    err,
+                )
+                .attach_context("pack_id", id.to_string())
+                .attach_context("blob_id", blob_id.to_string())
+            })?;
             if blob_data.len() != length.get() as usize {
-                error!("pack {id}, blob {blob_id}: Actual uncompressed length does not fit saved uncompressed length");
-                return Ok(());
+                verification
+                    .problems
+                    .push(PackProblem::UncompressedLengthMismatch { blob: blob_id });
+                continue;
             }
         }
 
         let comp_id = BlobId::from(hash(&blob_data));
-        if blob.id != comp_id {
-            error!("pack {id}, blob {blob_id}: Hash mismatch. Computed hash: {comp_id}");
-            return Ok(());
+        if blob_id != comp_id {
+            verification.problems.push(PackProblem::BlobHashMismatch {
+                blob: blob_id,
+                computed: comp_id,
+            });
         }
-        p.inc(blob.length.into());
     }
 
-    Ok(())
+    Ok(verification)
+}
+
+/// Verify a single pack of the repository
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository containing the pack
+/// * `id` - The id of the pack to verify
+///
+/// # Errors
+///
+/// * If the index could not be read.
+/// * If the pack is not referenced by the index.
+/// * If the pack could not be read from the backend.
+/// * If the pack header or a blob could not be decrypted.
+///
+/// # Returns
+///
+/// The problems found within the pack
+pub(crate) fn verify_pack<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    id: PackId,
+) -> RusticResult<PackVerification> {
+    let be = repo.dbe();
+    let p = repo.pb.progress_counter("reading index...");
+    let mut index_pack = None;
+    for index in be.stream_all::<IndexFile>(&p)? {
+        if let Some(pack) = index?.1.packs.into_iter().find(|pack| pack.id == id) {
+            index_pack = Some(pack);
+            break;
+        }
+    }
+    p.finish();
+
+    let Some(index_pack) = index_pack else {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Pack `{pack_id}` is not referenced by the index.",
+        )
+        .attach_context("pack_id", id.to_string()));
+    };
+
+    repo.warm_up_wait(std::iter::once(id))?;
+    let data = be.read_full(FileType::Pack, &id)?;
+    verify_pack_data(be, index_pack, data, &repo.pb.progress_hidden())
+}
+
+/// Find index files which reference packs that don't exist in the backend
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to search
+///
+/// # Errors
+///
+/// * If the packs could not be listed.
+/// * If an index file could not be read.
+///
+/// # Returns
+///
+/// The ids of the stale index files together with the missing packs they reference, sorted by index id
+pub(crate) fn find_stale_index_entries<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+) -> RusticResult<Vec<(IndexId, Vec<PackId>)>> {
+    let be = repo.dbe();
+
+    let p = repo.pb.progress_spinner("listing packs...");
+    let existing: HashSet<_> = be
+        .list(FileType::Pack)?
+        .into_iter()
+        .map(PackId::from)
+        .collect();
+    p.finish();
+
+    let p = repo.pb.progress_counter("reading index...");
+    let mut stale = Vec::new();
+    for index in be.stream_all::<IndexFile>(&p)? {
+        let (id, index) = index?;
+        let mut missing: Vec<_> = index
+            .all_packs()
+            .map(|(pack, _)| pack.id)
+            .filter(|id| !existing.contains(id))
+            .collect();
+        if !missing.is_empty() {
+            missing.sort_unstable();
+            stale.push((id, missing));
+        }
+    }
+    p.finish();
+
+    stale.sort_unstable_by_key(|(id, _)| *id);
+    Ok(stale)
 }
 
 #[cfg(test)]
@@ -804,7 +1718,7 @@ mod tests {
         let total_size = size(&test_packs);
 
         let subset: ReadSubsetOption = s.parse().unwrap();
-        let packs = subset.apply_with_rng(test_packs, &mut rng);
+        let packs = subset.apply_with_rng(test_packs, Local::now(), &mut rng);
         let test_size = size(&packs);
 
         match subset {
@@ -816,13 +1730,65 @@ mod tests {
             ReadSubsetOption::Size(size) => {
                 assert!(test_size <= size && size <= test_size + u64::from(PACK_SIZE));
             }
-            ReadSubsetOption::IdSubSet(_) => {}
+            ReadSubsetOption::IdSubSet(_)
+            | ReadSubsetOption::Newest(_)
+            | ReadSubsetOption::Oldest(_) => {}
         };
 
         let ids: Vec<_> = packs.iter().map(|pack| (pack.id, pack.size)).collect();
         assert_ron_snapshot!(s, ids);
     }
 
+    #[rstest]
+    #[case("5%")]
+    #[case("250MiB")]
+    fn test_read_subset_with_seed(mut rng: StdRng, #[case] s: &str) {
+        let ids = |packs: Vec<IndexPack>| -> Vec<_> { packs.iter().map(|pack| pack.id).collect() };
+
+        let test_packs = test_packs(&mut rng);
+        let subset: ReadSubsetOption = s.parse().unwrap();
+        let packs = ids(subset.apply(test_packs.clone(), Some(42)));
+        assert!(!packs.is_empty());
+
+        // the same seed chooses the same packs, independent of the order
+        let mut reversed = test_packs.clone();
+        reversed.reverse();
+        assert_eq!(ids(subset.apply(reversed, Some(42))), packs);
+
+        // another seed chooses other packs
+        assert_ne!(ids(subset.apply(test_packs, Some(43))), packs);
+    }
+
+    #[rstest]
+    #[case("newest:7d", &[0, 1])]
+    #[case("newest:1h", &[0])]
+    #[case("oldest:7d", &[2, 3])]
+    #[case("oldest:1h", &[1, 2, 3])]
+    fn test_read_subset_by_time(#[case] s: &str, #[case] expected: &[usize]) {
+        let now = Local::now();
+        let times = [
+            Some(now - chrono::Duration::minutes(10)),
+            Some(now - chrono::Duration::days(2)),
+            Some(now - chrono::Duration::days(30)),
+            None,
+        ];
+        let test_packs: Vec<_> = times
+            .iter()
+            .map(|time| IndexPack {
+                id: PackId::from(Id::random()),
+                blobs: Vec::new(),
+                time: *time,
+                size: Some(PACK_SIZE),
+            })
+            .collect();
+
+        let subset: ReadSubsetOption = s.parse().unwrap();
+        let packs = subset.apply_with_rng(test_packs.clone(), now, &mut thread_rng());
+        let ids: Vec<_> = packs.iter().map(|pack| pack.id).collect();
+        let expected: Vec<_> = expected.iter().map(|i| test_packs[*i].id).collect();
+        assert_eq!(ids, expected);
+    }
+
     #[rstest]
     #[case("5", "12")]
     #[case("29", "28")]
@@ -870,7 +1836,7 @@ mod tests {
 
         let mut run_with = |s: &str| {
             let subset: ReadSubsetOption = s.parse().unwrap();
-            let packs = subset.apply(test_packs.clone());
+            let packs = subset.apply(test_packs.clone(), None);
             for pack in packs {
                 assert!(all_packs.remove(&pack.id));
             }
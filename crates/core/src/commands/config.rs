@@ -1,15 +1,164 @@
 //! `config` subcommand
+use std::time::{Duration, Instant};
+
 use bytesize::ByteSize;
 use derive_setters::Setters;
+use log::{info, warn};
+use rand::{thread_rng, Rng};
+use serde_derive::Serialize;
 
 use crate::{
-    backend::decrypt::{DecryptBackend, DecryptWriteBackend},
+    backend::{
+        decrypt::{
+            compress_into, compression_level_range, DecryptBackend, DecryptReadBackend,
+            DecryptWriteBackend,
+        },
+        FileType,
+    },
     crypto::CryptoKey,
     error::{ErrorKind, RusticError, RusticResult},
-    repofile::ConfigFile,
+    progress::NoProgress,
+    repofile::{ConfigFile, IndexBlob, IndexFile, PackId},
     repository::{Open, Repository},
 };
 
+pub(super) mod constants {
+    /// The latest repository version supported.
+    pub(super) const LATEST_VERSION: u32 = 3;
+    /// The latest repository version which can be migrated to without further configuration.
+    ///
+    /// Version 3 is only useful with a compression dictionary, so it must be chosen explicitly.
+    pub(super) const LATEST_SAFE_VERSION: u32 = 2;
+    /// The maximum size of a trained compression dictionary, this is also the default of the zstd cli.
+    pub(super) const MAX_DICTIONARY_SIZE: usize = 112_640;
+    /// The maximum size of a blob used as sample for training a dictionary; larger blobs are not used.
+    pub(super) const MAX_SAMPLE_SIZE: u32 = 128 * 1024;
+    /// The maximum number of blobs used as samples for training a dictionary.
+    pub(super) const MAX_SAMPLES: usize = 10_000;
+    /// The maximum total size of all samples used for training a dictionary.
+    pub(super) const MAX_TOTAL_SAMPLE_SIZE: usize = 100 * 1024 * 1024;
+    /// The compression levels tested when suggesting a compression level; unsupported levels are skipped.
+    pub(super) const SUGGESTION_LEVELS: [i32; 8] = [1, 3, 6, 9, 12, 15, 19, 22];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+/// A change of a single field of the [`ConfigFile`]
+pub struct ConfigChange {
+    /// The name of the changed field
+    pub field: String,
+    /// The old value, `Null` if it was not set
+    pub old: serde_json::Value,
+    /// The new value, `Null` if it is not set
+    pub new: serde_json::Value,
+}
+
+/// Compute the new [`ConfigFile`] by applying the [`ConfigOptions`] to the current config of the repository
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to apply the config to
+/// * `opts` - The options to apply
+///
+/// # Errors
+///
+/// * If the repository is in append-only mode.
+/// * If the options could not be applied, see [`ConfigOptions::apply`].
+/// * If the compression dictionary could not be trained.
+fn new_config<P, S: Open>(
+    repo: &Repository<P, S>,
+    opts: &ConfigOptions,
+) -> RusticResult<ConfigFile> {
+    if repo.config().append_only == Some(true) {
+        return Err(RusticError::new(
+            ErrorKind::AppendOnly,
+            "Changing config is not allowed in append-only repositories. Please disable append-only mode first, if you know what you are doing. Aborting.",
+        ));
+    }
+
+    let mut new_config = repo.config().clone();
+    opts.apply(&mut new_config)?;
+    if opts.train_compression_dictionary {
+        new_config.compression_dictionary = Some(train_dictionary(repo)?);
+    }
+    Ok(new_config)
+}
+
+/// Compute the field-level changes between two [`ConfigFile`]s
+///
+/// # Arguments
+///
+/// * `old` - The old config
+/// * `new` - The new config
+///
+/// # Errors
+///
+/// * If a config could not be serialized to json.
+fn config_changes(old: &ConfigFile, new: &ConfigFile) -> RusticResult<Vec<ConfigChange>> {
+    let to_map = |config: &ConfigFile| match serde_json::to_value(config) {
+        Ok(serde_json::Value::Object(map)) => Ok(map),
+        Ok(_) => Err(RusticError::new(
+            ErrorKind::Internal,
+            "Config is not serialized as json object.",
+        )),
+        Err(err) => Err(RusticError::with_source(
+            ErrorKind::Internal,
+            "Failed to serialize config.",
+            err,
+        )),
+    };
+    let (old, mut new) = (to_map(old)?, to_map(new)?);
+
+    let mut changes = Vec::new();
+    for (field, old) in old {
+        let new = new.remove(&field).unwrap_or_default();
+        if old != new {
+            changes.push(ConfigChange { field, old, new });
+        }
+    }
+    changes.extend(new.into_iter().map(|(field, new)| ConfigChange {
+        field,
+        old: serde_json::Value::Null,
+        new,
+    }));
+    changes.sort_by(|c1, c2| c1.field.cmp(&c2.field));
+    Ok(changes)
+}
+
+/// Compute the changes applying the [`ConfigOptions`] would make, without saving the config
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to apply the config to
+/// * `opts` - The options to apply
+///
+/// # Errors
+///
+/// * If the repository is in append-only mode.
+/// * If the options could not be applied, see [`ConfigOptions::apply`].
+/// * If the config could not be serialized to json.
+///
+/// # Returns
+///
+/// The changed fields of the config
+pub(crate) fn apply_config_dry_run<P, S: Open>(
+    repo: &Repository<P, S>,
+    opts: &ConfigOptions,
+) -> RusticResult<Vec<ConfigChange>> {
+    let new_config = new_config(repo, opts)?;
+    config_changes(repo.config(), &new_config)
+}
+
 /// Apply the [`ConfigOptions`] to a given [`ConfigFile`]
 ///
 /// # Type Parameters
@@ -24,13 +173,18 @@ use crate::{
 ///
 /// # Errors
 ///
+/// * If the repository is in append-only mode.
 /// * If the version is not supported.
 /// * If the version is lower than the current version.
 /// * If compression is set for a v1 repo.
 /// * If the compression level is not supported.
+/// * If a compression dictionary should be trained for a repo with version < 3 or which already has one.
+/// * If the compression dictionary could not be trained.
+/// * If the version is raised to 3 without training a compression dictionary.
 /// * If the size is too large.
 /// * If the min pack size tolerance percent is wrong.
 /// * If the max pack size tolerance percent is wrong.
+/// * If the chunk sizes are out of bounds or not ordered.
 /// * If the file could not be serialized to json.
 ///
 /// # Returns
@@ -40,16 +194,8 @@ pub(crate) fn apply_config<P, S: Open>(
     repo: &Repository<P, S>,
     opts: &ConfigOptions,
 ) -> RusticResult<bool> {
-    if repo.config().append_only == Some(true) {
-        return Err(RusticError::new(
-            ErrorKind::AppendOnly,
-            "Changing config is not allowed in append-only repositories. Please disable append-only mode first, if you know what you are doing. Aborting.",
-        ));
-    }
-
-    let mut new_config = repo.config().clone();
-    opts.apply(&mut new_config)?;
-    if &new_config == repo.config() {
+    let new_config = new_config(repo, opts)?;
+    if config_changes(repo.config(), &new_config)?.is_empty() {
         Ok(false)
     } else {
         save_config(repo, new_config, *repo.dbe().key())?;
@@ -57,6 +203,68 @@ pub(crate) fn apply_config<P, S: Open>(
     }
 }
 
+/// Check whether the repository can be migrated to a newer repository version
+///
+/// Only migrations which don't need any further configuration are considered, see
+/// [`Repository::migrate_to_latest`].
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to check
+///
+/// # Returns
+///
+/// The version to migrate to, if the repository uses an older version
+pub(crate) fn needs_migration<P, S: Open>(repo: &Repository<P, S>) -> Option<u32> {
+    (repo.config().version < constants::LATEST_SAFE_VERSION)
+        .then_some(constants::LATEST_SAFE_VERSION)
+}
+
+/// Migrate the repository config to the latest repository version which needs no further configuration
+///
+/// Only the config is changed, existing pack files are not touched. See
+/// [`Repository::migrate_to_latest`] for details.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to migrate
+/// * `dry_run` - Only compute the changes, don't save the config
+///
+/// # Errors
+///
+/// * If the repository is in append-only mode.
+/// * If the repository uses an unsupported or newer version.
+/// * If the config could not be serialized to json.
+///
+/// # Returns
+///
+/// The changed fields of the config
+pub(crate) fn migrate_to_latest<P, S: Open>(
+    repo: &Repository<P, S>,
+    dry_run: bool,
+) -> RusticResult<Vec<ConfigChange>> {
+    let Some(version) = needs_migration(repo) else {
+        return Ok(Vec::new());
+    };
+    let opts = ConfigOptions::default().set_version(version);
+    let new_config = new_config(repo, &opts)?;
+    let changes = config_changes(repo.config(), &new_config)?;
+    if !dry_run && !changes.is_empty() {
+        save_config(repo, new_config, *repo.dbe().key())?;
+    }
+    Ok(changes)
+}
+
 /// Save a [`ConfigFile`] to the repository
 ///
 /// # Type Parameters
@@ -79,13 +287,16 @@ pub(crate) fn save_config<P, S>(
     key: impl CryptoKey,
 ) -> RusticResult<()> {
     new_config.is_hot = None;
-    let dbe = DecryptBackend::new(repo.be.clone(), key);
+    let verify = !repo.opts.no_verify_writes;
+    let mut dbe = DecryptBackend::new(repo.be.clone(), key);
+    dbe.set_write_verification(verify.then(|| repo.be.clone()));
     // for hot/cold backend, this only saves the config to the cold repo.
     _ = dbe.save_file_uncompressed(&new_config)?;
 
     if let Some(hot_be) = repo.be_hot.clone() {
         // save config to hot repo
-        let dbe = DecryptBackend::new(hot_be.clone(), key);
+        let mut dbe = DecryptBackend::new(hot_be.clone(), key);
+        dbe.set_write_verification(verify.then_some(hot_be));
         new_config.is_hot = Some(true);
         _ = dbe.save_file_uncompressed(&new_config)?;
     }
@@ -104,10 +315,19 @@ pub struct ConfigOptions {
     #[cfg_attr(feature = "clap", clap(long, value_name = "LEVEL"))]
     pub set_compression: Option<i32>,
 
-    /// Set repository version. Allowed versions: 1,2
+    /// Set repository version. Allowed versions: 1,2,3
+    ///
+    /// Version 3 additionally requires a compression dictionary, see `train_compression_dictionary`.
     #[cfg_attr(feature = "clap", clap(long, value_name = "VERSION"))]
     pub set_version: Option<u32>,
 
+    /// Train a zstd compression dictionary from a random sample of the blobs in the repository and use it for
+    /// compression. This improves the compression of small blobs, e.g. many small similar files or trees.
+    /// Requires repository version 3 and a repository which already contains data.
+    /// Note that the dictionary can't be changed once set.
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub train_compression_dictionary: bool,
+
     /// Set append-only mode.
     /// Note that only append-only commands work once this is set. `forget`, `prune` or `config` won't work any longer.
     #[cfg_attr(feature = "clap", clap(long))]
@@ -166,11 +386,30 @@ pub struct ConfigOptions {
     /// Default: true
     #[cfg_attr(feature = "clap", clap(long))]
     pub set_extra_verify: Option<bool>,
+
+    /// Set minimum size of chunks. Smaller chunks can improve deduplication for many small or similar files.
+    /// Note that changing the chunk sizes reduces deduplication with data saved using other chunk sizes.
+    /// Defaults to `512 KiB` if not set.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "SIZE"))]
+    pub set_chunker_min_size: Option<ByteSize>,
+
+    /// Set average size of chunks; must be a power of two.
+    /// Defaults to `1 MiB` if not set.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "SIZE"))]
+    pub set_chunker_avg_size: Option<ByteSize>,
+
+    /// Set maximum size of chunks.
+    /// Defaults to `8 MiB` if not set.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "SIZE"))]
+    pub set_chunker_max_size: Option<ByteSize>,
 }
 
 impl ConfigOptions {
     /// Apply the [`ConfigOptions`] to a given [`ConfigFile`]
     ///
+    /// The compression dictionary is not trained here, as this needs the data of the repository.
+    /// This is done by [`Repository::apply_config`], `apply` only validates that a dictionary can be added.
+    ///
     /// # Arguments
     ///
     /// * `config` - The config to apply the options to
@@ -181,13 +420,17 @@ impl ConfigOptions {
     /// * If the version is lower than the current version
     /// * If compression is set for a v1 repo
     /// * If the compression level is not supported
+    /// * If a compression dictionary should be trained for a repo with version < 3 or which already has one
+    /// * If the version is raised to 3 without training a compression dictionary
     /// * If the size is too large
     /// * If the min packsize tolerate percent is wrong
     /// * If the max packsize tolerate percent is wrong
+    /// * If the chunk sizes are out of bounds or not ordered
     pub fn apply(&self, config: &mut ConfigFile) -> RusticResult<()> {
+        let old_version = config.version;
         if let Some(version) = self.set_version {
-            // only allow versions 1 and 2
-            let range = 1..=2;
+            // only allow versions 1 to the latest version
+            let range = 1..=constants::LATEST_VERSION;
 
             if !range.contains(&version) {
                 return Err(RusticError::new(
@@ -229,6 +472,33 @@ impl ConfigOptions {
             config.compression = Some(compression);
         }
 
+        if self.train_compression_dictionary {
+            if config.version < 3 {
+                return Err(RusticError::new(
+                    ErrorKind::Unsupported,
+                    "Compression dictionaries are unsupported for config version `{version}`. Please set version 3.",
+                )
+                .attach_context("version", config.version.to_string()));
+            }
+            if config.compression_dictionary.is_some() {
+                return Err(RusticError::new(
+                    ErrorKind::Unsupported,
+                    "The repository already has a compression dictionary. Changing it would make compressed data unreadable.",
+                ));
+            }
+        }
+
+        if old_version < 3
+            && config.version == 3
+            && config.compression_dictionary.is_none()
+            && !self.train_compression_dictionary
+        {
+            return Err(RusticError::new(
+                ErrorKind::Unsupported,
+                "Config version 3 requires a compression dictionary. Please also train a compression dictionary or use version 2.",
+            ));
+        }
+
         if let Some(append_only) = self.set_append_only {
             config.append_only = Some(append_only);
         }
@@ -294,10 +564,138 @@ impl ConfigOptions {
 
         config.extra_verify = self.set_extra_verify;
 
+        if let Some(size) = self.set_chunker_min_size {
+            config.chunker_min_size = Some(
+                size.as_u64()
+                    .try_into()
+                    .map_err(|err| construct_size_too_large_error(err, size))?,
+            );
+        }
+        if let Some(size) = self.set_chunker_avg_size {
+            config.chunker_avg_size = Some(
+                size.as_u64()
+                    .try_into()
+                    .map_err(|err| construct_size_too_large_error(err, size))?,
+            );
+        }
+        if let Some(size) = self.set_chunker_max_size {
+            config.chunker_max_size = Some(
+                size.as_u64()
+                    .try_into()
+                    .map_err(|err| construct_size_too_large_error(err, size))?,
+            );
+        }
+        // refuse chunk sizes which can't be used by the chunker
+        _ = config.chunk_sizes()?;
+
         Ok(())
     }
 }
 
+/// Train a zstd compression dictionary from a random sample of the blobs in the repository
+///
+/// Only blobs up to [`constants::MAX_SAMPLE_SIZE`] are used, as the dictionary improves the compression of
+/// small blobs. Blobs which can't be read are skipped.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to read the samples from
+///
+/// # Errors
+///
+/// * If the index could not be read.
+/// * If the repository contains no blobs to use as samples.
+/// * If training the dictionary failed, e.g. because there are too few samples.
+///
+/// # Returns
+///
+/// The trained dictionary
+fn train_dictionary<P, S: Open>(repo: &Repository<P, S>) -> RusticResult<Vec<u8>> {
+    let be = repo.dbe();
+
+    // choose random blobs using reservoir sampling
+    let mut rng = thread_rng();
+    let mut candidates: Vec<(PackId, IndexBlob)> = Vec::new();
+    let mut seen = 0_usize;
+    for index in be.stream_all::<IndexFile>(&NoProgress)? {
+        let (_, index) = index?;
+        for pack in index.packs {
+            for blob in pack
+                .blobs
+                .into_iter()
+                .filter(|blob| blob.length <= constants::MAX_SAMPLE_SIZE)
+            {
+                if candidates.len() < constants::MAX_SAMPLES {
+                    candidates.push((pack.id, blob));
+                } else {
+                    let i = rng.gen_range(0..=seen);
+                    if i < constants::MAX_SAMPLES {
+                        candidates[i] = (pack.id, blob);
+                    }
+                }
+                seen += 1;
+            }
+        }
+    }
+    // read the blobs in pack order
+    candidates.sort_unstable_by_key(|(pack, blob)| (*pack, blob.offset));
+
+    let mut samples = Vec::new();
+    let mut total_size = 0;
+    for (pack, blob) in candidates {
+        let sample = match be.read_encrypted_partial(
+            FileType::Pack,
+            &pack,
+            false,
+            blob.offset,
+            blob.length,
+            blob.uncompressed_length,
+        ) {
+            Ok(sample) => sample,
+            Err(err) => {
+                warn!(
+                    "ignoring sample blob {} in pack {pack}: {}",
+                    blob.id,
+                    err.display_log()
+                );
+                continue;
+            }
+        };
+        total_size += sample.len();
+        samples.push(sample);
+        if total_size >= constants::MAX_TOTAL_SAMPLE_SIZE {
+            break;
+        }
+    }
+
+    if samples.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::MissingInput,
+            "The repository contains no blobs to train the compression dictionary from. Please add some data to the repository first.",
+        ));
+    }
+
+    let dictionary = zstd::dict::from_samples(&samples, constants::MAX_DICTIONARY_SIZE).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InvalidInput,
+            "Training the compression dictionary from `{count}` sample blobs failed. Please add more data to the repository first.",
+            err,
+        )
+        .attach_context("count", samples.len().to_string())
+    })?;
+    info!(
+        "trained compression dictionary of {} bytes from {} sample blobs",
+        dictionary.len(),
+        samples.len()
+    );
+    Ok(dictionary)
+}
+
 fn construct_size_too_large_error(
     err: std::num::TryFromIntError,
     size: ByteSize,
@@ -309,3 +707,271 @@ fn construct_size_too_large_error(
     )
     .attach_context("size", size.to_string())
 }
+
+/// What to optimize for when recommending a compression level, see [`CompressionSuggestion::recommended`]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompressionTarget {
+    /// Prefer fast compression; accept compressed data up to 10% larger than the best result
+    Speed,
+    /// Balance speed and size; accept compressed data up to 2% larger than the best result
+    Balanced,
+    /// Prefer small compressed data, regardless of the speed
+    Size,
+}
+
+impl CompressionTarget {
+    /// The tolerated size increase compared to the best result in percent
+    const fn tolerance_percent(self) -> u64 {
+        match self {
+            Self::Speed => 10,
+            Self::Balanced => 2,
+            Self::Size => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[non_exhaustive]
+/// The result of compressing a sample with a single compression level
+pub struct CompressionLevelResult {
+    /// The compression level used
+    pub level: i32,
+    /// The size of the compressed sample
+    pub compressed_size: u64,
+    /// The compression ratio, i.e. uncompressed size divided by compressed size
+    pub ratio: f64,
+    /// The time needed to compress the sample
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+/// The results of compressing a sample with several compression levels, see [`Repository::suggest_compression_level`]
+pub struct CompressionSuggestion {
+    /// The size of the uncompressed sample
+    pub sample_size: u64,
+    /// The results for all tested compression levels, sorted by level
+    pub results: Vec<CompressionLevelResult>,
+}
+
+impl CompressionSuggestion {
+    /// Recommend a compression level for the given target.
+    ///
+    /// As higher levels are slower, the lowest tested level is chosen whose compressed size is
+    /// within the tolerance of the target compared to the smallest compressed size.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - What to optimize for
+    #[must_use]
+    pub fn recommended(&self, target: CompressionTarget) -> i32 {
+        let best = self
+            .results
+            .iter()
+            .map(|result| result.compressed_size)
+            .min()
+            .unwrap_or_default();
+        let limit = best + best * target.tolerance_percent() / 100;
+        self.results
+            .iter()
+            .find(|result| result.compressed_size <= limit)
+            .map_or(0, |result| result.level)
+    }
+}
+
+/// Compress a sample with several compression levels and collect the results
+///
+/// # Arguments
+///
+/// * `sample` - The data to compress
+/// * `dictionary` - The compression dictionary to use, if any
+///
+/// # Errors
+///
+/// * If the sample is empty.
+/// * If the sample could not be compressed.
+pub(crate) fn compression_suggestion(
+    sample: &[u8],
+    dictionary: Option<&[u8]>,
+) -> RusticResult<CompressionSuggestion> {
+    if sample.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Cannot suggest a compression level for an empty sample. Please provide some data.",
+        ));
+    }
+    let sample_size = sample.len() as u64;
+
+    let range = compression_level_range();
+    let mut results = Vec::new();
+    let mut out = Vec::with_capacity(sample.len());
+    for level in constants::SUGGESTION_LEVELS
+        .into_iter()
+        .filter(|level| range.contains(level))
+    {
+        out.clear();
+        let start = Instant::now();
+        compress_into(sample, &mut out, level, dictionary).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Compressing sample with level `{level}` failed.",
+                err,
+            )
+            .attach_context("level", level.to_string())
+        })?;
+        let duration = start.elapsed();
+        let compressed_size = out.len() as u64;
+
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = sample_size as f64 / compressed_size as f64;
+        results.push(CompressionLevelResult {
+            level,
+            compressed_size,
+            ratio,
+            duration,
+        });
+    }
+
+    Ok(CompressionSuggestion {
+        sample_size,
+        results,
+    })
+}
+
+/// Compress a sample with several compression levels using the compression settings of the repository
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use the compression dictionary from
+/// * `sample` - The data to compress
+///
+/// # Errors
+///
+/// * If the sample is empty.
+/// * If the compression dictionary of the repository is invalid.
+/// * If the sample could not be compressed.
+pub(crate) fn suggest_compression_level<P, S: Open>(
+    repo: &Repository<P, S>,
+    sample: &[u8],
+) -> RusticResult<CompressionSuggestion> {
+    compression_suggestion(sample, repo.config().zstd_dictionary()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::repofile::configfile::RepositoryId;
+
+    #[test]
+    fn config_changes_lists_changed_fields() -> RusticResult<()> {
+        let old = ConfigFile::new(2, RepositoryId::default(), 0);
+        let mut new = old.clone();
+        ConfigOptions::default()
+            .set_compression(5)
+            .set_treepack_size(ByteSize::mib(8))
+            .apply(&mut new)?;
+
+        let changes = config_changes(&old, &new)?;
+        assert_eq!(
+            changes,
+            vec![
+                ConfigChange {
+                    field: "compression".to_string(),
+                    old: serde_json::Value::Null,
+                    new: 5.into(),
+                },
+                ConfigChange {
+                    field: "treepack_size".to_string(),
+                    old: serde_json::Value::Null,
+                    new: (8 * 1024 * 1024).into(),
+                },
+            ]
+        );
+
+        assert!(config_changes(&old, &old)?.is_empty());
+
+        // validation errors are returned
+        assert!(ConfigOptions::default()
+            .set_version(1)
+            .apply(&mut new)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compression_dictionary_needs_version_3() -> RusticResult<()> {
+        let samples: Vec<_> = (0..1000)
+            .map(|i| format!(r#"{{"id":{i},"name":"sample {i}","tags":["a","b"]}}"#))
+            .collect();
+        let dictionary = zstd::dict::from_samples(&samples, 4096).unwrap();
+        let opts = ConfigOptions::default().train_compression_dictionary(true);
+
+        let mut config = ConfigFile::new(2, RepositoryId::default(), 0);
+        assert!(opts.apply(&mut config).is_err());
+        // version 3 needs a dictionary
+        assert!(ConfigOptions::default()
+            .set_version(3)
+            .apply(&mut config.clone())
+            .is_err());
+
+        // the dictionary itself is trained from the repository data
+        opts.set_version(3).apply(&mut config)?;
+        assert_eq!(config.version, 3);
+        assert!(config.compression_dictionary.is_none());
+
+        // the dictionary can't be changed
+        config.compression_dictionary = Some(dictionary);
+        assert!(config.zstd_dictionary()?.is_some());
+        assert!(opts.apply(&mut config).is_err());
+
+        // older versions refuse to use the dictionary
+        config.version = 2;
+        assert!(config.zstd_dictionary().is_err());
+
+        // dictionaries without id can't be used, as data compressed with them can't be recognized
+        config.version = 3;
+        config.compression_dictionary = Some(b"raw content dictionary".to_vec());
+        assert!(config.zstd_dictionary().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compression_suggestion_tests_supported_levels() -> RusticResult<()> {
+        let sample: Vec<u8> = (0..100_000_u32)
+            .flat_map(|i| format!("line {} with value {}\n", i % 1000, i % 7).into_bytes())
+            .collect();
+        let suggestion = compression_suggestion(&sample, None)?;
+        assert_eq!(suggestion.sample_size, sample.len() as u64);
+        assert!(!suggestion.results.is_empty());
+        assert!(suggestion
+            .results
+            .iter()
+            .all(|result| compression_level_range().contains(&result.level) && result.ratio > 1.0));
+        assert!(suggestion
+            .results
+            .windows(2)
+            .all(|results| results[0].level < results[1].level));
+
+        let best = suggestion
+            .results
+            .iter()
+            .min_by_key(|result| result.compressed_size)
+            .unwrap();
+        let size = suggestion.recommended(CompressionTarget::Size);
+        let balanced = suggestion.recommended(CompressionTarget::Balanced);
+        let speed = suggestion.recommended(CompressionTarget::Speed);
+        assert!(size <= best.level);
+        assert!(speed <= balanced && balanced <= size);
+
+        assert!(compression_suggestion(&[], None).is_err());
+        Ok(())
+    }
+}
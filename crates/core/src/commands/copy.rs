@@ -1,13 +1,18 @@
 use std::collections::BTreeSet;
 
-use log::trace;
+use log::{info, trace};
 use rayon::prelude::{IntoParallelRefIterator, ParallelBridge, ParallelIterator};
 
 use crate::{
-    backend::{decrypt::DecryptWriteBackend, node::NodeType},
+    backend::{
+        decrypt::{DecryptBackend, DecryptWriteBackend},
+        node::NodeType,
+        FileType, ReadBackend,
+    },
     blob::{packer::Packer, tree::TreeStreamerOnce, BlobId, BlobType},
+    crypto::{aespoly1305::Key, CryptoKey},
     error::RusticResult,
-    index::{indexer::Indexer, ReadIndex},
+    index::{indexer::Indexer, IndexEntry, ReadIndex},
     progress::{Progress, ProgressBars},
     repofile::SnapshotFile,
     repository::{IndexedFull, IndexedIds, IndexedTree, Open, Repository},
@@ -23,8 +28,30 @@ pub struct CopySnapshot {
     pub relevant: bool,
 }
 
+/// Check whether blobs can be copied without decrypting and re-encrypting them.
+///
+/// This is the case if both repositories use the same master key and the destination
+/// can read the (maybe compressed) blobs of the source as they are.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to copy from
+/// * `repo_dest` - The repository to copy to
+fn can_copy_raw<Q, R: Open, P, S: Open>(
+    repo: &Repository<Q, R>,
+    repo_dest: &Repository<P, S>,
+) -> bool {
+    let (config, config_dest) = (repo.config(), repo_dest.config());
+    repo.dbe().key() == repo_dest.dbe().key()
+        && (config.version == 1 || config_dest.version > 1)
+        && config.compression_dictionary == config_dest.compression_dictionary
+}
+
 /// Copy the given snapshots to the destination repository.
 ///
+/// If both repositories share the same master key, blobs are copied as they are after
+/// verifying their MAC, see [`can_copy_raw`]. Else they are decrypted and encrypted again with the key of the destination.
+///
 /// # Type Parameters
 ///
 /// * `Q` - The progress bar type.
@@ -75,16 +102,45 @@ pub(crate) fn copy<'a, Q, R: IndexedFull, P: ProgressBars, S: IndexedIds>(
         index_dest.total_size(BlobType::Tree),
     )?;
 
+    let raw = can_copy_raw(repo, repo_dest);
+    if raw {
+        info!("source and destination use the same key, copying blobs without re-encryption");
+    }
+
     let p = pb.progress_bytes("copying blobs...");
 
+    let copy_blob =
+        |packer: &Packer<DecryptBackend<Key>>, id: BlobId, entry: IndexEntry| -> RusticResult<_> {
+            if raw {
+                let data = be.read_partial(
+                    FileType::Pack,
+                    &entry.pack,
+                    entry.blob_type.is_cacheable(),
+                    entry.offset,
+                    entry.length,
+                )?;
+                // verify the MAC to not copy corrupted data into the destination
+                _ = be.key().decrypt_data(&data)?;
+                let data_len = u64::from(entry.data_length());
+                p.inc(data_len);
+                packer.add_raw(&data, &id, data_len, entry.uncompressed_length, None)
+            } else {
+                let data = entry.read_data(be)?;
+                p.inc(data.len() as u64);
+                packer.add(data, id)
+            }
+        };
+
     snap_trees
         .par_iter()
         .try_for_each(|id| -> RusticResult<_> {
             trace!("copy tree blob {id}");
             if !index_dest.has_tree(id) {
-                let data = index.get_tree(id).unwrap().read_data(be)?;
-                p.inc(data.len() as u64);
-                tree_packer.add(data, BlobId::from(**id))?;
+                copy_blob(
+                    &tree_packer,
+                    BlobId::from(**id),
+                    index.get_tree(id).unwrap(),
+                )?;
             }
             Ok(())
         })?;
@@ -101,9 +157,11 @@ pub(crate) fn copy<'a, Q, R: IndexedFull, P: ProgressBars, S: IndexedIds>(
                             |id| -> RusticResult<_> {
                                 trace!("copy data blob {id}");
                                 if !index_dest.has_data(id) {
-                                    let data = index.get_data(id).unwrap().read_data(be)?;
-                                    p.inc(data.len() as u64);
-                                    data_packer.add(data, BlobId::from(**id))?;
+                                    copy_blob(
+                                        &data_packer,
+                                        BlobId::from(**id),
+                                        index.get_data(id).unwrap(),
+                                    )?;
                                 }
                                 Ok(())
                             },
@@ -114,9 +172,11 @@ pub(crate) fn copy<'a, Q, R: IndexedFull, P: ProgressBars, S: IndexedIds>(
                         let id = node.subtree.unwrap();
                         trace!("copy tree blob {id}");
                         if !index_dest.has_tree(&id) {
-                            let data = index.get_tree(&id).unwrap().read_data(be)?;
-                            p.inc(data.len() as u64);
-                            tree_packer.add(data, BlobId::from(*id))?;
+                            copy_blob(
+                                &tree_packer,
+                                BlobId::from(*id),
+                                index.get_tree(&id).unwrap(),
+                            )?;
                         }
                     }
 
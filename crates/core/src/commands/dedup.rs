@@ -0,0 +1,95 @@
+//! Estimate the deduplication of local files
+
+use std::{collections::BTreeSet, fs::File, path::Path};
+
+use rustic_cdc::Rabin64;
+use serde_derive::Serialize;
+
+use crate::{
+    blob::DataId,
+    chunker::ChunkIter,
+    crypto::hasher::hash,
+    error::{ErrorKind, RusticError, RusticResult},
+    index::ReadIndex,
+    repository::{IndexedIds, Repository},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+/// Estimate how much of a file is already contained in the repository
+pub struct DedupEstimate {
+    /// Number of chunks of the file
+    pub chunks: u64,
+    /// Number of chunks which already exist in the repository
+    pub existing_chunks: u64,
+    /// Number of chunks which would be newly added to the repository
+    pub new_chunks: u64,
+    /// Total size of the file
+    pub size: u64,
+    /// Size of the chunks which already exist in the repository
+    pub existing_size: u64,
+    /// Size of the chunks which would be newly added to the repository
+    pub new_size: u64,
+}
+
+/// Chunk the given file and check which chunks already exist in the repository.
+///
+/// Chunks which occur multiple times within the file are only counted as new on their first occurrence.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to check against.
+/// * `path` - The path of the file to check.
+///
+/// # Errors
+///
+/// * If the file could not be opened or read.
+/// * If the chunker parameters of the repository config are invalid.
+pub(crate) fn would_deduplicate<P, S: IndexedIds>(
+    repo: &Repository<P, S>,
+    path: &Path,
+) -> RusticResult<DedupEstimate> {
+    let file = File::open(path).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InputOutput,
+            "Failed to open the file `{path}`. Please check the path and try again.",
+            err,
+        )
+        .attach_context("path", path.display().to_string())
+    })?;
+    let size_hint = file
+        .metadata()
+        .map_or(0, |meta| usize::try_from(meta.len()).unwrap_or(usize::MAX));
+
+    let config = repo.config();
+    let rabin = Rabin64::new_with_polynom(6, &config.poly()?);
+    let index = repo.index();
+
+    let mut estimate = DedupEstimate::default();
+    let mut seen = BTreeSet::new();
+    for chunk in ChunkIter::new(file, size_hint, rabin, config.chunk_sizes()?) {
+        let chunk = chunk.map_err(|err| {
+            err.prepend_guidance_line("Failed to read the file `{path}`.")
+                .attach_context("path", path.display().to_string())
+        })?;
+        let id = DataId::from(hash(&chunk));
+        let size = chunk.len() as u64;
+
+        estimate.chunks += 1;
+        estimate.size += size;
+        if index.has_data(&id) || !seen.insert(id) {
+            estimate.existing_chunks += 1;
+            estimate.existing_size += size;
+        } else {
+            estimate.new_chunks += 1;
+            estimate.new_size += size;
+        }
+    }
+
+    Ok(estimate)
+}
@@ -0,0 +1,458 @@
+//! `diff` subcommand
+
+use std::{cmp::Ordering, ffi::OsStr, mem::discriminant, path::PathBuf};
+
+use derive_setters::Setters;
+use log::trace;
+
+use crate::{
+    backend::node::{Metadata, Node, NodeType},
+    blob::tree::NodeStreamer,
+    error::RusticResult,
+    progress::{Progress, ProgressBars},
+    repofile::SnapshotFile,
+    repository::{IndexedTree, Repository},
+};
+
+#[cfg_attr(feature = "clap", derive(clap::Parser))]
+#[derive(Debug, Copy, Clone, Default, Setters)]
+#[setters(into)]
+#[non_exhaustive]
+/// Options for the `diff` command
+pub struct DiffOptions {
+    /// Also compare the contents (i.e. the blob ids) of files with identical size and mtime
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub content: bool,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+/// Statistics about the differences between two snapshots
+pub struct DiffStats {
+    /// Number of entries only present in the second snapshot
+    pub added: u64,
+    /// Number of entries only present in the first snapshot
+    pub removed: u64,
+    /// Number of entries present in both snapshots which differ
+    pub modified: u64,
+    /// Number of entries which changed their type, e.g. from file to dir.
+    ///
+    /// These are also counted as removed and added.
+    pub type_changed: u64,
+    /// Number of entries present in both snapshots which are identical
+    pub unchanged: u64,
+}
+
+#[derive(Default, Debug, Clone)]
+#[non_exhaustive]
+/// The differences between two snapshots
+pub struct SnapshotDiff {
+    /// Entries only present in the second snapshot
+    pub added: Vec<(PathBuf, Node)>,
+    /// Entries only present in the first snapshot
+    pub removed: Vec<(PathBuf, Node)>,
+    /// Entries present in both snapshots which differ (as contained in the second snapshot)
+    pub modified: Vec<(PathBuf, Node)>,
+    /// Statistics about the differences
+    pub stats: DiffStats,
+}
+
+impl SnapshotDiff {
+    /// Record a difference.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The difference to record
+    fn add(&mut self, entry: DiffEntry) {
+        let DiffEntry {
+            path,
+            kind,
+            old,
+            new,
+        } = entry;
+        match (kind, old, new) {
+            (DiffKind::Added, _, Some(node)) => {
+                self.stats.added += 1;
+                self.added.push((path, node));
+            }
+            (DiffKind::Removed, Some(node), _) => {
+                self.stats.removed += 1;
+                self.removed.push((path, node));
+            }
+            (DiffKind::Modified, _, Some(node)) => {
+                self.stats.modified += 1;
+                self.modified.push((path, node));
+            }
+            (DiffKind::TypeChanged, Some(old), Some(new)) => {
+                self.stats.type_changed += 1;
+                self.stats.removed += 1;
+                self.stats.added += 1;
+                self.removed.push((path.clone(), old));
+                self.added.push((path, new));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+/// The kind of a difference between two snapshots
+pub enum DiffKind {
+    /// The entry is only present in the second snapshot
+    Added,
+    /// The entry is only present in the first snapshot
+    Removed,
+    /// The entry is present in both snapshots, but differs
+    Modified,
+    /// The entry is present in both snapshots, but changed its type, e.g. from file to dir
+    TypeChanged,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+/// A single difference between two snapshots
+pub struct DiffEntry {
+    /// The path of the entry
+    pub path: PathBuf,
+    /// The kind of the difference
+    pub kind: DiffKind,
+    /// The node in the first snapshot, if present
+    pub old: Option<Node>,
+    /// The node in the second snapshot, if present
+    pub new: Option<Node>,
+}
+
+impl DiffEntry {
+    /// Create a new [`DiffEntry`] and log it.
+    fn new(path: PathBuf, kind: DiffKind, old: Option<Node>, new: Option<Node>) -> Self {
+        trace!("{kind:?}: {path:?}");
+        Self {
+            path,
+            kind,
+            old,
+            new,
+        }
+    }
+}
+
+/// Iterator over the differences between two streams of nodes.
+///
+/// Both streams must be sorted by path, as done by [`NodeStreamer`]. They are walked in lockstep,
+/// so only the current nodes are held in memory. Unchanged entries are only counted.
+pub(crate) struct DiffStreamer<I1, I2> {
+    /// The nodes of the first snapshot
+    iter1: I1,
+    /// The nodes of the second snapshot
+    iter2: I2,
+    /// The current node of the first snapshot
+    next1: Option<(PathBuf, Node)>,
+    /// The current node of the second snapshot
+    next2: Option<(PathBuf, Node)>,
+    /// The diff options
+    opts: DiffOptions,
+    /// Number of entries which are identical
+    unchanged: u64,
+    /// Whether the iterator is finished
+    finished: bool,
+}
+
+impl<I1, I2> DiffStreamer<I1, I2>
+where
+    I1: Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    I2: Iterator<Item = RusticResult<(PathBuf, Node)>>,
+{
+    /// Create a new [`DiffStreamer`].
+    ///
+    /// # Arguments
+    ///
+    /// * `iter1` - The nodes of the first (old) snapshot
+    /// * `iter2` - The nodes of the second (new) snapshot
+    /// * `opts` - The diff options
+    ///
+    /// # Errors
+    ///
+    /// * If the first node of a stream could not be read.
+    pub(crate) fn new(mut iter1: I1, mut iter2: I2, opts: DiffOptions) -> RusticResult<Self> {
+        let next1 = iter1.next().transpose()?;
+        let next2 = iter2.next().transpose()?;
+        Ok(Self {
+            iter1,
+            iter2,
+            next1,
+            next2,
+            opts,
+            unchanged: 0,
+            finished: false,
+        })
+    }
+
+    /// Get the next difference.
+    ///
+    /// # Errors
+    ///
+    /// * If a node could not be read.
+    fn next_entry(&mut self) -> RusticResult<Option<DiffEntry>> {
+        loop {
+            match (self.next1.take(), self.next2.take()) {
+                (None, None) => return Ok(None),
+                (Some((path1, node1)), None) => {
+                    self.next1 = self.iter1.next().transpose()?;
+                    return Ok(Some(DiffEntry::new(
+                        path1,
+                        DiffKind::Removed,
+                        Some(node1),
+                        None,
+                    )));
+                }
+                (None, Some((path2, node2))) => {
+                    self.next2 = self.iter2.next().transpose()?;
+                    return Ok(Some(DiffEntry::new(
+                        path2,
+                        DiffKind::Added,
+                        None,
+                        Some(node2),
+                    )));
+                }
+                (Some((path1, node1)), Some((path2, node2))) => match path1.cmp(&path2) {
+                    Ordering::Less => {
+                        self.next1 = self.iter1.next().transpose()?;
+                        self.next2 = Some((path2, node2));
+                        return Ok(Some(DiffEntry::new(
+                            path1,
+                            DiffKind::Removed,
+                            Some(node1),
+                            None,
+                        )));
+                    }
+                    Ordering::Equal => {
+                        self.next1 = self.iter1.next().transpose()?;
+                        self.next2 = self.iter2.next().transpose()?;
+                        let kind =
+                            if discriminant(&node1.node_type) != discriminant(&node2.node_type) {
+                                DiffKind::TypeChanged
+                            } else if is_modified(&node1, &node2, self.opts) {
+                                DiffKind::Modified
+                            } else {
+                                self.unchanged += 1;
+                                continue;
+                            };
+                        return Ok(Some(DiffEntry::new(path2, kind, Some(node1), Some(node2))));
+                    }
+                    Ordering::Greater => {
+                        self.next1 = Some((path1, node1));
+                        self.next2 = self.iter2.next().transpose()?;
+                        return Ok(Some(DiffEntry::new(
+                            path2,
+                            DiffKind::Added,
+                            None,
+                            Some(node2),
+                        )));
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<I1, I2> Iterator for DiffStreamer<I1, I2>
+where
+    I1: Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    I2: Iterator<Item = RusticResult<(PathBuf, Node)>>,
+{
+    type Item = RusticResult<DiffEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let entry = self.next_entry().transpose();
+        if !matches!(entry, Some(Ok(_))) {
+            self.finished = true;
+        }
+        entry
+    }
+}
+
+/// Check if two nodes of the same type differ.
+///
+/// Directories are never reported as modified, as their changes are reflected by their contents.
+///
+/// # Arguments
+///
+/// * `node1` - The node from the first snapshot
+/// * `node2` - The node from the second snapshot
+/// * `opts` - The diff options
+fn is_modified(node1: &Node, node2: &Node, opts: DiffOptions) -> bool {
+    if node1.is_dir() {
+        return false;
+    }
+    node1.node_type != node2.node_type
+        || node1.meta.size != node2.meta.size
+        || node1.meta.mtime != node2.meta.mtime
+        || (opts.content && node1.content != node2.content)
+}
+
+/// Create the root node of a snapshot.
+///
+/// # Arguments
+///
+/// * `snap` - The snapshot
+fn root_node(snap: &SnapshotFile) -> Node {
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snap.tree);
+    node
+}
+
+/// Stream the differences between two snapshots.
+///
+/// Both snapshot trees are streamed in lockstep, so only the current nodes need to be held in memory.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type
+/// * `S` - The state the repository is in
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap1` - The first (old) snapshot
+/// * `snap2` - The second (new) snapshot
+/// * `opts` - The diff options
+///
+/// # Errors
+///
+/// * If the root tree of a snapshot could not be loaded from the backend.
+pub(crate) fn diff_snapshots_streaming<'a, P, S: IndexedTree>(
+    repo: &'a Repository<P, S>,
+    snap1: &SnapshotFile,
+    snap2: &SnapshotFile,
+    opts: DiffOptions,
+) -> RusticResult<impl Iterator<Item = RusticResult<DiffEntry>> + 'a> {
+    let streamer1 = NodeStreamer::new(repo.dbe().clone(), repo.index(), &root_node(snap1))?;
+    let streamer2 = NodeStreamer::new(repo.dbe().clone(), repo.index(), &root_node(snap2))?;
+    DiffStreamer::new(streamer1, streamer2, opts)
+}
+
+/// Compute the differences between two snapshots.
+///
+/// Both snapshot trees are streamed in lockstep, so only the current nodes need to be held in memory
+/// besides the resulting differences.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type
+/// * `S` - The state the repository is in
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap1` - The first (old) snapshot
+/// * `snap2` - The second (new) snapshot
+/// * `opts` - The diff options
+///
+/// # Errors
+///
+/// * If a tree could not be loaded from the backend.
+pub(crate) fn diff_snapshots<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+    snap1: &SnapshotFile,
+    snap2: &SnapshotFile,
+    opts: DiffOptions,
+) -> RusticResult<SnapshotDiff> {
+    let p = repo.pb.progress_spinner("comparing snapshots...");
+    let mut diff = SnapshotDiff::default();
+
+    let streamer1 = NodeStreamer::new(repo.dbe().clone(), repo.index(), &root_node(snap1))?;
+    let streamer2 = NodeStreamer::new(repo.dbe().clone(), repo.index(), &root_node(snap2))?;
+    let mut entries = DiffStreamer::new(streamer1, streamer2, opts)?;
+    for entry in entries.by_ref() {
+        diff.add(entry?);
+    }
+    diff.stats.unchanged = entries.unchanged;
+
+    p.finish();
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    use crate::{blob::DataId, id::Id};
+
+    fn file(size: u64, content: u8) -> Node {
+        let mut node = Node::new_node(OsStr::new("file"), NodeType::File, Metadata::default());
+        node.meta.size = size;
+        node.content = Some(vec![DataId::from(Id::new([content; 32]))]);
+        node
+    }
+
+    #[rstest]
+    #[case(file(1, 1), file(1, 1), false, false)]
+    #[case(file(1, 1), file(2, 1), false, true)]
+    #[case(file(1, 1), file(1, 2), false, false)]
+    #[case(file(1, 1), file(1, 2), true, true)]
+    fn test_is_modified(
+        #[case] node1: Node,
+        #[case] node2: Node,
+        #[case] content: bool,
+        #[case] expected: bool,
+    ) {
+        let opts = DiffOptions::default().content(content);
+        assert_eq!(is_modified(&node1, &node2, opts), expected);
+    }
+
+    #[test]
+    fn test_type_change_is_removed_and_added() {
+        let mut diff = SnapshotDiff::default();
+        let dir = Node::new_node(OsStr::new("file"), NodeType::Dir, Metadata::default());
+        let entries = DiffStreamer::new(
+            std::iter::once(Ok((PathBuf::from("file"), file(1, 1)))),
+            std::iter::once(Ok((PathBuf::from("file"), dir))),
+            DiffOptions::default(),
+        )
+        .unwrap();
+        for entry in entries {
+            diff.add(entry.unwrap());
+        }
+        assert_eq!(
+            diff.stats,
+            DiffStats {
+                added: 1,
+                removed: 1,
+                type_changed: 1,
+                ..Default::default()
+            }
+        );
+    }
+    #[test]
+    fn test_diff_streamer() {
+        let node = |name: &str, size| (PathBuf::from(name), file(size, 1));
+        let entries: Vec<_> = DiffStreamer::new(
+            vec![node("a", 1), node("b", 1), node("c", 1)]
+                .into_iter()
+                .map(Ok),
+            vec![node("b", 1), node("c", 2), node("d", 1)]
+                .into_iter()
+                .map(Ok),
+            DiffOptions::default(),
+        )
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            (entry.path, entry.kind)
+        })
+        .collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("a"), DiffKind::Removed),
+                (PathBuf::from("c"), DiffKind::Modified),
+                (PathBuf::from("d"), DiffKind::Added),
+            ]
+        );
+    }
+}
@@ -1,12 +1,41 @@
-use std::io::Write;
+use std::{
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use bytes::{Buf, Bytes};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+use tar::{EntryType, Header};
+use zip::{write::SimpleFileOptions, ZipWriter};
 
+#[cfg(not(windows))]
+use crate::backend::ignore::mapper::map_mode_from_go;
 use crate::{
     backend::node::{Node, NodeType},
-    blob::{BlobId, BlobType},
+    blob::{tree::NodeStreamer, BlobId, BlobType, DataId},
     error::{ErrorKind, RusticError, RusticResult},
     repository::{IndexedFull, Repository},
 };
 
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Display, EnumString, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+/// The archive format used when dumping a tree
+pub enum DumpFormat {
+    /// A tar archive
+    #[default]
+    Tar,
+    /// A zip archive
+    Zip,
+}
+
 /// Dumps the contents of a file.
 ///
 /// # Type Parameters
@@ -48,3 +77,280 @@ pub(crate) fn dump<P, S: IndexedFull>(
     }
     Ok(())
 }
+
+/// Dumps a node and everything below it as an archive.
+///
+/// Directories, regular files and symlinks are added to the archive, other special files are skipped.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The type of the indexed tree.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to read from.
+/// * `node` - The node to dump.
+/// * `format` - The archive format to use.
+/// * `w` - The writer to write the archive to.
+///
+/// # Errors
+///
+/// * If a tree or blob could not be read.
+/// * If the archive could not be written.
+pub(crate) fn dump_tree<P, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    node: &Node,
+    format: DumpFormat,
+    w: &mut impl Write,
+) -> RusticResult<()> {
+    let nodes = NodeStreamer::new(repo.dbe().clone(), repo.index(), node)?;
+    match format {
+        DumpFormat::Tar => dump_tar(repo, nodes, w),
+        DumpFormat::Zip => dump_zip(repo, nodes, w),
+    }
+}
+
+/// Writes the given nodes as tar archive.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to read from.
+/// * `nodes` - The nodes to add to the archive.
+/// * `w` - The writer to write the archive to.
+///
+/// # Errors
+///
+/// * If a tree or blob could not be read.
+/// * If the archive could not be written.
+fn dump_tar<P, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    nodes: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    w: &mut impl Write,
+) -> RusticResult<()> {
+    let mut ar = tar::Builder::new(w);
+    for item in nodes {
+        let (path, node) = item?;
+        let mut header = Header::new_gnu();
+        header.set_mode(unix_mode(&node));
+        header.set_mtime(
+            node.meta
+                .mtime
+                .map_or(0, |mtime| u64::try_from(mtime.timestamp()).unwrap_or(0)),
+        );
+        if let Some(uid) = node.meta.uid {
+            header.set_uid(uid.into());
+        }
+        if let Some(gid) = node.meta.gid {
+            header.set_gid(gid.into());
+        }
+        if let Some(user) = &node.meta.user {
+            header
+                .set_username(user)
+                .map_err(|err| archive_error(&path, err))?;
+        }
+        if let Some(group) = &node.meta.group {
+            header
+                .set_groupname(group)
+                .map_err(|err| archive_error(&path, err))?;
+        }
+
+        let res = match &node.node_type {
+            NodeType::Dir => {
+                header.set_entry_type(EntryType::Directory);
+                header.set_size(0);
+                ar.append_data(&mut header, &path, io::empty())
+            }
+            NodeType::File => {
+                header.set_entry_type(EntryType::Regular);
+                header.set_size(node.meta.size);
+                ar.append_data(&mut header, &path, ContentReader::new(repo, &node))
+            }
+            NodeType::Symlink { .. } => {
+                header.set_entry_type(EntryType::Symlink);
+                header.set_size(0);
+                ar.append_link(&mut header, &path, node.node_type.to_link())
+            }
+            _ => {
+                warn!("dump: skipping special file {path:?}");
+                continue;
+            }
+        };
+        res.map_err(|err| archive_error(&path, err))?;
+    }
+
+    ar.finish().map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InputOutput,
+            "Failed to finish the tar archive.",
+            err,
+        )
+    })
+}
+
+/// Writes the given nodes as zip archive.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to read from.
+/// * `nodes` - The nodes to add to the archive.
+/// * `w` - The writer to write the archive to.
+///
+/// # Errors
+///
+/// * If a tree or blob could not be read.
+/// * If the archive could not be written.
+fn dump_zip<P, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    nodes: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    w: &mut impl Write,
+) -> RusticResult<()> {
+    let mut zip = ZipWriter::new_stream(w);
+    for item in nodes {
+        let (path, node) = item?;
+        let name = zip_name(&path);
+        let mut options = SimpleFileOptions::default()
+            .unix_permissions(unix_mode(&node))
+            .large_file(node.meta.size >= u64::from(u32::MAX));
+        if let Some(mtime) = node.meta.mtime.as_ref().and_then(zip_datetime) {
+            options = options.last_modified_time(mtime);
+        }
+
+        let res = match &node.node_type {
+            NodeType::Dir => zip.add_directory(name, options),
+            NodeType::File => zip.start_file(name, options).and_then(|()| {
+                _ = io::copy(&mut ContentReader::new(repo, &node), &mut zip)?;
+                Ok(())
+            }),
+            NodeType::Symlink { .. } => {
+                zip.add_symlink(name, node.node_type.to_link().to_string_lossy(), options)
+            }
+            _ => {
+                warn!("dump: skipping special file {path:?}");
+                continue;
+            }
+        };
+        res.map_err(|err| archive_error(&path, err))?;
+    }
+
+    _ = zip.finish().map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::InputOutput,
+            "Failed to finish the zip archive.",
+            err,
+        )
+    })?;
+    Ok(())
+}
+
+/// Creates the error for a failure to add `path` to an archive.
+fn archive_error(
+    path: &Path,
+    err: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+) -> Box<RusticError> {
+    RusticError::with_source(
+        ErrorKind::InputOutput,
+        "Failed to add `{path}` to the archive.",
+        err,
+    )
+    .attach_context("path", path.display().to_string())
+}
+
+/// Returns the unix permission bits of the node, using defaults if no mode is saved.
+fn unix_mode(node: &Node) -> u32 {
+    let default = if node.is_dir() { 0o755 } else { 0o644 };
+    #[cfg(not(windows))]
+    let mode = node.meta.mode.map(map_mode_from_go);
+    #[cfg(windows)]
+    let mode = node.meta.mode;
+    mode.map_or(default, |mode| mode & 0o7777)
+}
+
+/// Returns the name of the path within a zip archive, i.e. its components joined by `/`.
+fn zip_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(name) => Some(name.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Converts the time to the zip time format, if it is within its range.
+fn zip_datetime(time: &DateTime<Local>) -> Option<zip::DateTime> {
+    zip::DateTime::from_date_and_time(
+        u16::try_from(time.year()).ok()?,
+        u8::try_from(time.month()).ok()?,
+        u8::try_from(time.day()).ok()?,
+        u8::try_from(time.hour()).ok()?,
+        u8::try_from(time.minute()).ok()?,
+        u8::try_from(time.second()).ok()?,
+    )
+    .ok()
+}
+
+/// A reader streaming the contents of a file node blob by blob
+struct ContentReader<'a, P, S> {
+    /// The repository to read from
+    repo: &'a Repository<P, S>,
+    /// The blobs which are not yet read
+    ids: std::slice::Iter<'a, DataId>,
+    /// The remaining data of the current blob
+    data: Bytes,
+}
+
+impl<'a, P, S: IndexedFull> ContentReader<'a, P, S> {
+    /// Creates a new reader for the contents of `node`.
+    fn new(repo: &'a Repository<P, S>, node: &'a Node) -> Self {
+        Self {
+            repo,
+            ids: node.content.as_deref().unwrap_or_default().iter(),
+            data: Bytes::new(),
+        }
+    }
+}
+
+impl<P, S: IndexedFull> Read for ContentReader<'_, P, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.data.is_empty() {
+            let Some(id) = self.ids.next() else {
+                return Ok(0);
+            };
+            self.data = self
+                .repo
+                .get_blob_cached(&BlobId::from(**id), BlobType::Data)
+                .map_err(io::Error::other)?;
+        }
+        let len = buf.len().min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data.advance(len);
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("a/b/c", "a/b/c")]
+    #[case("a", "a")]
+    #[case("./a/b", "a/b")]
+    fn zip_name_uses_slashes(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(zip_name(Path::new(path)), expected);
+    }
+
+    #[test]
+    fn zip_datetime_rejects_out_of_range() {
+        let time = DateTime::parse_from_rfc3339("1970-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Local);
+        assert!(zip_datetime(&time).is_none());
+        let time = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Local);
+        assert!(zip_datetime(&time).is_some());
+    }
+}
@@ -1,18 +1,22 @@
 //! `forget` subcommand
 
+use std::collections::{BTreeMap, HashMap};
+
 use chrono::{DateTime, Datelike, Duration, Local, Timelike};
 use derive_setters::Setters;
 use serde_derive::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr};
 
 use crate::{
+    backend::decrypt::DecryptWriteBackend,
+    commands::prune::{prune_repository, PruneOptions, PrunePlan, PruneStats},
     error::{ErrorKind, RusticError, RusticResult},
     progress::ProgressBars,
     repofile::{
         snapshotfile::{SnapshotGroup, SnapshotGroupCriterion, SnapshotId},
-        SnapshotFile, StringList,
+        SnapshotFile, StringList, TagMatchMode,
     },
-    repository::{Open, Repository},
+    repository::{Open, Repository, Writable},
 };
 
 type CheckFunction = fn(&SnapshotFile, &SnapshotFile) -> bool;
@@ -54,6 +58,48 @@ impl ForgetGroups {
             })
             .collect()
     }
+
+    /// Get all groups in which no snapshot would be kept.
+    ///
+    /// This can be used as a safety check before actually forgetting snapshots, as such groups
+    /// often indicate wrong keep options.
+    #[must_use]
+    pub fn groups_keeping_nothing(&self) -> Vec<&ForgetGroup> {
+        self.0
+            .iter()
+            .filter(|fg| !fg.snapshots.is_empty() && fg.snapshots.iter().all(|fsn| !fsn.keep))
+            .collect()
+    }
+}
+
+/// Check that each group keeps at least one snapshot, unless `keep_none` is set.
+///
+/// # Arguments
+///
+/// * `keep` - The keep options which were used
+/// * `groups` - The groups to check
+///
+/// # Errors
+///
+/// * If `keep_none` is not set and there are groups in which all snapshots would be forgotten
+fn check_keeping_nothing(keep: &KeepOptions, groups: &ForgetGroups) -> RusticResult<()> {
+    if keep.keep_none {
+        return Ok(());
+    }
+
+    let groups: Vec<_> = groups
+        .groups_keeping_nothing()
+        .into_iter()
+        .map(|fg| fg.group.to_string())
+        .collect();
+    if !groups.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "The keep options would forget all snapshots of the groups `{groups}`. Please check the keep options or use `keep-none` to allow this. Aborting.",
+        )
+        .attach_context("groups", groups.join(", ")));
+    }
+    Ok(())
 }
 
 /// Get the list of snapshots to forget.
@@ -99,6 +145,196 @@ pub(crate) fn get_forget_snapshots<P: ProgressBars, S: Open>(
     Ok(ForgetGroups(groups))
 }
 
+/// Forget snapshots depending on the given [`KeepOptions`].
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `keep` - The keep options to use
+/// * `group_by` - The criterion to group snapshots by
+/// * `filter` - The filter to apply to the snapshots
+///
+/// # Errors
+///
+/// * If keep options are not valid
+/// * If a group would keep no snapshot and `keep_none` is not set
+/// * If the repository is in append-only mode and there are snapshots to forget
+/// * If the snapshots could not be removed
+///
+/// # Returns
+///
+/// The IDs of the removed snapshots
+pub(crate) fn forget<P: ProgressBars, S: Writable>(
+    repo: &Repository<P, S>,
+    keep: &KeepOptions,
+    group_by: SnapshotGroupCriterion,
+    filter: impl FnMut(&SnapshotFile) -> bool,
+) -> RusticResult<Vec<SnapshotId>> {
+    let groups = get_forget_snapshots(repo, keep, group_by, filter)?;
+    check_keeping_nothing(keep, &groups)?;
+    let forget_ids = groups.into_forget_ids();
+    if forget_ids.is_empty() {
+        return Ok(forget_ids);
+    }
+
+    if repo.config().append_only == Some(true) {
+        let ids: Vec<_> = forget_ids.iter().map(ToString::to_string).collect();
+        return Err(RusticError::new(
+            ErrorKind::AppendOnly,
+            "Repository is in append-only mode and snapshots cannot be deleted from it. The following snapshots would have been forgotten: `{ids}`. Aborting.",
+        )
+        .attach_context("ids", ids.join(", ")));
+    }
+
+    repo.delete_snapshots_unlocked(&forget_ids)?;
+    Ok(forget_ids)
+}
+
+/// The result of [`Repository::forget_and_prune`]
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ForgetPruneResult {
+    /// The groups of snapshots with the information which snapshots are forgotten
+    pub forget_groups: ForgetGroups,
+    /// The ids of the forgotten snapshots
+    pub forget_ids: Vec<SnapshotId>,
+    /// The statistics of the prune plan
+    pub prune_stats: PruneStats,
+}
+
+/// Compute the prune plan for the repository after forgetting the given snapshots.
+///
+/// The prune plan ignores the snapshots to forget, so it matches the repository state after forgetting.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `forget_groups` - The groups of snapshots with the information which snapshots are forgotten
+/// * `prune_opts` - The options for the pruning
+///
+/// # Errors
+///
+/// * If the prune plan could not be computed
+///
+/// # Returns
+///
+/// The result without modifications, the prune options ignoring the forgotten snapshots and the prune plan
+fn forget_prune_plan<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    forget_groups: ForgetGroups,
+    prune_opts: &PruneOptions,
+) -> RusticResult<(ForgetPruneResult, PruneOptions, PrunePlan)> {
+    let forget_ids: Vec<_> = forget_groups
+        .0
+        .iter()
+        .flat_map(|group| &group.snapshots)
+        .filter_map(|snap| (!snap.keep).then_some(snap.snapshot.id))
+        .collect();
+
+    let mut prune_opts = prune_opts.clone();
+    prune_opts.ignore_snaps.extend(forget_ids.iter().copied());
+    let prune_plan = PrunePlan::from_prune_options(repo, &prune_opts)?;
+
+    let result = ForgetPruneResult {
+        forget_groups,
+        forget_ids,
+        prune_stats: prune_plan.stats.clone(),
+    };
+    Ok((result, prune_opts, prune_plan))
+}
+
+/// Compute what [`forget_and_prune`] would do without modifying the repository.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `keep` - The keep options to use
+/// * `group_by` - The criterion to group snapshots by
+/// * `filter` - The filter to apply to the snapshots
+/// * `prune_opts` - The options for the pruning
+///
+/// # Errors
+///
+/// * If keep options are not valid
+/// * If the prune plan could not be computed
+///
+/// # Returns
+///
+/// The forget groups, the ids of the snapshots to forget and the statistics of the prune plan
+pub(crate) fn forget_and_prune_dry_run<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    keep: &KeepOptions,
+    group_by: SnapshotGroupCriterion,
+    filter: impl FnMut(&SnapshotFile) -> bool,
+    prune_opts: &PruneOptions,
+) -> RusticResult<ForgetPruneResult> {
+    let forget_groups = get_forget_snapshots(repo, keep, group_by, filter)?;
+    let (result, _, _) = forget_prune_plan(repo, forget_groups, prune_opts)?;
+    Ok(result)
+}
+
+/// Forget snapshots depending on the given [`KeepOptions`] and prune the repository afterwards.
+///
+/// Both the snapshots to forget and the prune plan are computed before anything is modified
+/// and the repository is locked exclusively during the whole operation.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `keep` - The keep options to use
+/// * `group_by` - The criterion to group snapshots by
+/// * `filter` - The filter to apply to the snapshots
+/// * `prune_opts` - The options for the pruning
+///
+/// # Errors
+///
+/// * If the repository is in append-only mode
+/// * If keep options are not valid
+/// * If a group would keep no snapshot and `keep_none` is not set
+/// * If the prune plan could not be computed
+/// * If the snapshots could not be removed
+/// * If pruning failed
+///
+/// # Returns
+///
+/// The forget groups, the ids of the forgotten snapshots and the statistics of the prune plan
+pub(crate) fn forget_and_prune<P: ProgressBars, S: Writable>(
+    repo: &Repository<P, S>,
+    keep: &KeepOptions,
+    group_by: SnapshotGroupCriterion,
+    filter: impl FnMut(&SnapshotFile) -> bool,
+    prune_opts: &PruneOptions,
+) -> RusticResult<ForgetPruneResult> {
+    if repo.config().append_only == Some(true) {
+        return Err(RusticError::new(
+            ErrorKind::AppendOnly,
+            "Repository is in append-only mode and snapshots cannot be forgotten and pruned. Aborting.",
+        ));
+    }
+
+    let _lock = repo.lock_exclusive()?;
+    let forget_groups = get_forget_snapshots(repo, keep, group_by, filter)?;
+    // check this before computing the prune plan, which is expensive
+    check_keeping_nothing(keep, &forget_groups)?;
+    let (result, prune_opts, prune_plan) = forget_prune_plan(repo, forget_groups, prune_opts)?;
+
+    let p = repo.pb.progress_counter("removing snapshots...");
+    repo.dbe().delete_list(true, result.forget_ids.iter(), p)?;
+    prune_repository(repo, &prune_opts, prune_plan)?;
+
+    Ok(result)
+}
+
 #[cfg_attr(feature = "clap", derive(clap::Parser))]
 #[cfg_attr(feature = "merge", derive(conflate::Merge))]
 #[skip_serializing_none]
@@ -116,6 +352,11 @@ pub struct KeepOptions {
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub keep_tags: Vec<StringList>,
 
+    /// How to compare tags in `keep_tags` and `per_tag` with the tags of snapshots (default: exact)
+    #[cfg_attr(feature = "clap", clap(long, value_name = "MODE"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub tag_match: Option<TagMatchMode>,
+
     /// Keep snapshots ids that start with ID (can be specified multiple times)
     #[cfg_attr(feature = "clap", clap(long = "keep-id", value_name = "ID"))]
     #[cfg_attr(feature = "merge", merge(strategy=conflate::vec::overwrite_empty))]
@@ -259,6 +500,25 @@ pub struct KeepOptions {
     #[cfg_attr(feature = "merge", merge(strategy=conflate::bool::overwrite_false))]
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub delete_unchanged: bool,
+
+    /// Keep options for snapshots with the given taglists. These replace the other options for
+    /// all snapshots matching the taglist; the other options apply to all remaining snapshots.
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[cfg_attr(feature = "merge", merge(strategy = merge_per_tag))]
+    #[serde_as(as = "BTreeMap<DisplayFromStr, _>")]
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub per_tag: BTreeMap<StringList, KeepOptions>,
+}
+
+/// Merge per-tag keep options; entries already present in `left` take precedence.
+#[cfg(feature = "merge")]
+fn merge_per_tag(
+    left: &mut BTreeMap<StringList, KeepOptions>,
+    right: BTreeMap<StringList, KeepOptions>,
+) {
+    for (tags, keep) in right {
+        _ = left.entry(tags).or_insert(keep);
+    }
 }
 
 /// Always return false
@@ -459,7 +719,11 @@ impl KeepOptions {
             reason.push("id");
         }
 
-        if !self.keep_tags.is_empty() && sn.tags.matches(&self.keep_tags) {
+        if !self.keep_tags.is_empty()
+            && sn
+                .tags
+                .matches_with(&self.keep_tags, self.tag_match.unwrap_or_default())
+        {
             reason.push("tags");
         }
 
@@ -552,6 +816,11 @@ impl KeepOptions {
     /// Apply the `[KeepOptions]` to the given list of [`SnapshotFile`]s returning the corresponding
     /// list of [`ForgetSnapshot`]s
     ///
+    /// If `per_tag` options are given, the snapshots are partitioned by the taglists: All snapshots
+    /// matching a taglist are evaluated by the corresponding options, all other snapshots by the
+    /// remaining options. A snapshot matching multiple taglists is kept if any of the matching
+    /// options keeps it.
+    ///
     /// # Arguments
     ///
     /// * `snapshots` - The list of snapshots to apply the options to
@@ -566,6 +835,75 @@ impl KeepOptions {
     /// The list of snapshots with the attribute `keep` set to `true` if the snapshot should be kept and
     /// `reasons` set to the list of reasons why the snapshot should be kept
     pub fn apply(
+        &self,
+        snapshots: Vec<SnapshotFile>,
+        now: DateTime<Local>,
+    ) -> RusticResult<Vec<ForgetSnapshot>> {
+        if self.per_tag.is_empty() {
+            return self.apply_without_per_tag(snapshots, now);
+        }
+
+        let mode = self.tag_match.unwrap_or_default();
+        let (tagged, untagged): (Vec<_>, Vec<_>) = snapshots.into_iter().partition(|sn| {
+            self.per_tag
+                .keys()
+                .any(|tags| sn.tags.matches_with(std::slice::from_ref(tags), mode))
+        });
+
+        let mut snaps = self.apply_without_per_tag(untagged, now)?;
+
+        let positions: HashMap<_, _> = tagged
+            .iter()
+            .enumerate()
+            .map(|(i, sn)| (sn.id, i))
+            .collect();
+        let mut tagged: Vec<_> = tagged
+            .into_iter()
+            .map(|snapshot| ForgetSnapshot {
+                snapshot,
+                keep: false,
+                reasons: Vec::new(),
+            })
+            .collect();
+
+        for (tags, keep) in &self.per_tag {
+            let matching = tagged
+                .iter()
+                .filter(|fsn| {
+                    fsn.snapshot
+                        .tags
+                        .matches_with(std::slice::from_ref(tags), mode)
+                })
+                .map(|fsn| fsn.snapshot.clone())
+                .collect();
+            for fsn in keep.apply(matching, now)? {
+                let tagged_sn = &mut tagged[positions[&fsn.snapshot.id]];
+                if fsn.keep && !tagged_sn.keep {
+                    tagged_sn.keep = true;
+                    tagged_sn.reasons.clear();
+                }
+                if fsn.keep == tagged_sn.keep {
+                    tagged_sn.reasons.extend(fsn.reasons);
+                }
+            }
+        }
+
+        snaps.extend(tagged);
+        snaps.sort_unstable_by(|fsn1, fsn2| fsn1.snapshot.cmp(&fsn2.snapshot).reverse());
+        Ok(snaps)
+    }
+
+    /// Apply the `[KeepOptions]` ignoring the `per_tag` options, see [`KeepOptions::apply`]
+    ///
+    /// # Arguments
+    ///
+    /// * `snapshots` - The list of snapshots to apply the options to
+    /// * `now` - The current time
+    ///
+    /// # Errors
+    ///
+    /// * If keep options are not valid
+    fn apply_without_per_tag(
         &self,
         mut snapshots: Vec<SnapshotFile>,
         now: DateTime<Local>,
@@ -832,6 +1170,42 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[rstest]
+    fn test_apply_per_tag(test_snapshots: Vec<SnapshotFile>) -> Result<()> {
+        let now = parse_time("2016-01-18 12:02:03")?;
+        let per_tag = BTreeMap::from([
+            (
+                StringList::from_str("foo")?,
+                KeepOptions::default().keep_none(true),
+            ),
+            (
+                StringList::from_str("bar")?,
+                KeepOptions::default().keep_last(-1),
+            ),
+        ]);
+        let options = KeepOptions::default().keep_last(1).per_tag(per_tag);
+        let count = test_snapshots.len();
+        let result = options.apply(test_snapshots, now)?;
+        assert_eq!(result.len(), count);
+        assert!(result
+            .windows(2)
+            .all(|fsn| fsn[0].snapshot >= fsn[1].snapshot));
+
+        let bar = StringList::from_str("bar")?;
+        let (tagged, untagged): (Vec<_>, Vec<_>) = result
+            .iter()
+            .filter(|fsn| fsn.keep)
+            .partition(|fsn| fsn.snapshot.tags != StringList::default());
+        // all snapshots tagged with "bar" are kept, even if they are also tagged with "foo"
+        assert_eq!(tagged.len(), 3);
+        assert!(tagged
+            .iter()
+            .all(|fsn| fsn.snapshot.tags.contains_all(&bar) && fsn.reasons == ["last"]));
+        // the latest untagged snapshot and the snapshot which must be kept
+        assert_eq!(untagged.len(), 2);
+        Ok(())
+    }
+
     #[rstest]
     #[case(KeepOptions::default().keep_last(10))]
     #[case(KeepOptions::default().keep_last(15))]
@@ -10,9 +10,9 @@ use crate::{
         key::{init_key, KeyOptions},
     },
     crypto::aespoly1305::Key,
-    error::RusticResult,
+    error::{ErrorKind, RusticError, RusticResult},
     id::Id,
-    repofile::{configfile::RepositoryId, ConfigFile},
+    repofile::{configfile::RepositoryId, ConfigFile, KeyId},
     repository::Repository,
 };
 
@@ -32,17 +32,25 @@ use crate::{
 ///
 /// # Errors
 ///
+/// * If a compression dictionary should be trained, as the new repository contains no data to train it from.
 /// * If no polynomial could be found in one million tries.
 ///
 /// # Returns
 ///
-/// A tuple of the key and the config file.
+/// A tuple of the key, the id of the key file and the config file.
 pub(crate) fn init<P, S>(
     repo: &Repository<P, S>,
     pass: &str,
     key_opts: &KeyOptions,
     config_opts: &ConfigOptions,
-) -> RusticResult<(Key, ConfigFile)> {
+) -> RusticResult<(Key, KeyId, ConfigFile)> {
+    if config_opts.train_compression_dictionary {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "A compression dictionary can't be trained when initializing a repository, as it is trained from the repository data. Please add some data and train the dictionary afterwards.",
+        ));
+    }
+
     // Create config first to allow catching errors from here without writing anything
     let repo_id = RepositoryId::from(Id::random());
     let chunker_poly = random_poly()?;
@@ -54,10 +62,10 @@ pub(crate) fn init<P, S>(
     }
     config_opts.apply(&mut config)?;
 
-    let key = init_with_config(repo, pass, key_opts, &config)?;
+    let (key, key_id) = init_with_config(repo, pass, key_opts, &config)?;
     info!("repository {} successfully created.", repo_id);
 
-    Ok((key, config))
+    Ok((key, key_id, config))
 }
 
 /// Initialize a new repository with a given config.
@@ -76,17 +84,17 @@ pub(crate) fn init<P, S>(
 ///
 /// # Returns
 ///
-/// The key used to encrypt the config.
+/// The key used to encrypt the config and the id of the key file.
 pub(crate) fn init_with_config<P, S>(
     repo: &Repository<P, S>,
     pass: &str,
     key_opts: &KeyOptions,
     config: &ConfigFile,
-) -> RusticResult<Key> {
+) -> RusticResult<(Key, KeyId)> {
     repo.be.create()?;
     let (key, id) = init_key(repo, key_opts, pass)?;
     info!("key {id} successfully added.");
     save_config(repo, config.clone(), key)?;
 
-    Ok(key)
+    Ok((key, id))
 }
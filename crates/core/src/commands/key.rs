@@ -1,12 +1,17 @@
 //! `key` subcommand
+use chrono::{DateTime, Duration, Local};
 use derive_setters::Setters;
+use serde_derive::Serialize;
 
 use crate::{
-    backend::{decrypt::DecryptWriteBackend, FileType, WriteBackend},
+    backend::{
+        decrypt::{write_verified, DecryptWriteBackend},
+        FileType, ReadBackend, WriteBackend,
+    },
     crypto::{aespoly1305::Key, hasher::hash},
     error::{ErrorKind, RusticError, RusticResult},
-    repofile::{KeyFile, KeyId},
-    repository::{Open, Repository},
+    repofile::{keyfile::find_key_in_backend, KeyFile, KeyId},
+    repository::{Open, Repository, Writable},
 };
 
 #[cfg_attr(feature = "clap", derive(clap::Parser))]
@@ -118,8 +123,187 @@ pub(crate) fn add_key_to_repo<P, S>(
 
     let id = KeyId::from(hash(&data));
 
-    repo.be
-        .write_bytes(FileType::Key, &id, false, data.into())?;
+    if repo.opts.no_verify_writes {
+        repo.be
+            .write_bytes(FileType::Key, &id, false, data.into())?;
+    } else {
+        write_verified(&repo.be, &repo.be, FileType::Key, &id, data.into())?;
+    }
 
     Ok(id)
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+/// Public information about a key of the repository
+pub struct KeyInfo {
+    /// The id of the key file
+    pub id: KeyId,
+    /// Hostname where the key was created
+    pub hostname: Option<String>,
+    /// User which created the key
+    pub username: Option<String>,
+    /// Creation time of the key
+    pub created: Option<DateTime<Local>>,
+}
+
+/// List the keys of the repository.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to list the keys of
+///
+/// # Errors
+///
+/// * If the key files could not be listed or read.
+///
+/// # Returns
+///
+/// The information about the keys, sorted by id.
+pub(crate) fn list_keys<P, S>(repo: &Repository<P, S>) -> RusticResult<Vec<KeyInfo>> {
+    let mut ids: Vec<KeyId> = repo
+        .be
+        .list(FileType::Key)?
+        .into_iter()
+        .map(KeyId::from)
+        .collect();
+    ids.sort_unstable();
+
+    ids.into_iter()
+        .map(|id| {
+            let keyfile = KeyFile::from_backend(&repo.be, &id)?;
+            Ok(KeyInfo {
+                id,
+                hostname: keyfile.hostname,
+                username: keyfile.username,
+                created: keyfile.created,
+            })
+        })
+        .collect()
+}
+
+/// List the keys of the repository which have been created before the given age.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to list the keys of
+/// * `age` - The minimum age of the keys to return
+///
+/// # Errors
+///
+/// * If the key files could not be listed or read.
+///
+/// # Returns
+///
+/// The information about the keys older than `age`, sorted by id. Keys without creation time are not returned.
+pub(crate) fn keys_older_than<P, S>(
+    repo: &Repository<P, S>,
+    age: Duration,
+) -> RusticResult<Vec<KeyInfo>> {
+    let limit = Local::now() - age;
+    Ok(list_keys(repo)?
+        .into_iter()
+        .filter(|key| key.created.is_some_and(|created| created < limit))
+        .collect())
+}
+
+/// Check which keys would be removed by [`delete_keys`].
+///
+/// # Arguments
+///
+/// * `repo` - The repository to remove the keys from
+/// * `ids` - The ids of the keys to remove
+///
+/// # Errors
+///
+/// * If a key is not found in the repository
+/// * If the key used to open the repository is contained in `ids`
+/// * If all keys of the repository would be removed
+///
+/// # Returns
+///
+/// The information about the keys to remove, sorted by id.
+pub(crate) fn keys_to_delete<P, S: Open>(
+    repo: &Repository<P, S>,
+    ids: &[KeyId],
+) -> RusticResult<Vec<KeyInfo>> {
+    if ids.contains(repo.key_id()) {
+        return Err(RusticError::new(
+            ErrorKind::Key,
+            "Key `{id}` is used to open the repository and cannot be removed.",
+        )
+        .attach_context("id", repo.key_id().to_string()));
+    }
+
+    let keys = list_keys(repo)?;
+    if let Some(id) = ids.iter().find(|id| !keys.iter().any(|key| key.id == **id)) {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Key `{id}` not found in repository.",
+        )
+        .attach_context("id", id.to_string()));
+    }
+
+    let (delete, keep): (Vec<_>, Vec<_>) = keys.into_iter().partition(|key| ids.contains(&key.id));
+    if keep.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::Key,
+            "Removing the keys would remove all keys of the repository. Refusing to remove the last key.",
+        ));
+    }
+
+    Ok(delete)
+}
+
+/// Remove the given keys from the repository.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to remove the keys from
+/// * `ids` - The ids of the keys to remove
+///
+/// # Errors
+///
+/// * If a key is not found in the repository
+/// * If the key used to open the repository is contained in `ids`
+/// * If all keys of the repository would be removed
+/// * If removing a key file failed
+///
+/// # Returns
+///
+/// The information about the removed keys, sorted by id.
+pub(crate) fn delete_keys<P, S: Writable>(
+    repo: &Repository<P, S>,
+    ids: &[KeyId],
+) -> RusticResult<Vec<KeyInfo>> {
+    let delete = keys_to_delete(repo, ids)?;
+    for key in &delete {
+        repo.be.remove(FileType::Key, &key.id, false)?;
+    }
+    Ok(delete)
+}
+
+/// Check which key of the repository can be opened with the given password.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to check
+/// * `pass` - The password to check
+///
+/// # Errors
+///
+/// * If the key files could not be listed or read.
+///
+/// # Returns
+///
+/// The id of the key which can be opened with the password or `None` if the password doesn't fit to any key.
+pub(crate) fn verify_password<P, S>(
+    repo: &Repository<P, S>,
+    pass: &str,
+) -> RusticResult<Option<KeyId>> {
+    match find_key_in_backend(&repo.be, &pass, None) {
+        Ok((id, _)) => Ok(Some(id)),
+        Err(err) if err.is_incorrect_password() => Ok(None),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,156 @@
+//! Compute manifest hashes of snapshots
+
+use std::collections::BTreeSet;
+
+use log::info;
+
+use crate::{
+    backend::decrypt::DecryptWriteBackend,
+    blob::tree::TreeStreamerOnce,
+    crypto::hasher::hash,
+    error::{ErrorKind, RusticError, RusticResult},
+    id::Id,
+    progress::ProgressBars,
+    repofile::SnapshotFile,
+    repository::{IndexedTree, Repository, Writable},
+};
+
+/// The key in [`SnapshotFile::extra`] under which the manifest hash is saved
+pub const MANIFEST_HASH_KEY: &str = "manifest_hash";
+
+/// Compute the manifest hash of a snapshot.
+///
+/// The manifest lists all tree and data blob ids referenced by the snapshot, each set sorted by id.
+/// It consists of the lines `tree <id>` for all trees, followed by the lines `data <id>` for all data blobs,
+/// where `<id>` is the hex representation of the id and each line is terminated by `\n`.
+/// The manifest hash is the SHA256 hash of this manifest.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to compute the manifest hash for
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+pub(crate) fn manifest_hash<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+) -> RusticResult<Id> {
+    let mut tree_ids = BTreeSet::from([snap.tree]);
+    let mut data_ids = BTreeSet::new();
+
+    let p = repo.pb.progress_counter("computing manifest...");
+    let mut tree_streamer = TreeStreamerOnce::new(repo.dbe(), repo.index(), vec![snap.tree], p)?;
+    while let Some((_, tree)) = tree_streamer.next().transpose()? {
+        for node in tree.nodes {
+            if let Some(subtree) = node.subtree {
+                _ = tree_ids.insert(subtree);
+            }
+            data_ids.extend(node.content.into_iter().flatten());
+        }
+    }
+
+    let mut manifest = String::with_capacity(70 * (tree_ids.len() + data_ids.len()));
+    for id in tree_ids {
+        manifest.push_str("tree ");
+        manifest.push_str(&id.to_hex());
+        manifest.push('\n');
+    }
+    for id in data_ids {
+        manifest.push_str("data ");
+        manifest.push_str(&id.to_hex());
+        manifest.push('\n');
+    }
+
+    Ok(hash(manifest.as_bytes()))
+}
+
+/// Compute the manifest hash of a snapshot and save the snapshot with the manifest hash in `extra`.
+///
+/// The snapshot is replaced, i.e. the old snapshot file is removed and the original id is kept in
+/// `original`. Snapshots which already contain a manifest hash are returned unchanged.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to save the manifest hash for
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+/// * If the snapshot could not be saved or the old snapshot could not be removed.
+///
+/// # Returns
+///
+/// The snapshot with manifest hash
+pub(crate) fn save_manifest_hash<P: ProgressBars, S: IndexedTree + Writable>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+) -> RusticResult<SnapshotFile> {
+    if snap.extra.contains_key(MANIFEST_HASH_KEY) {
+        return Ok(snap.clone());
+    }
+
+    let mut new_snap = snap.clone();
+    let id = manifest_hash(repo, snap)?;
+    _ = new_snap
+        .extra
+        .insert(MANIFEST_HASH_KEY.to_string(), id.to_hex().as_str().into());
+    new_snap.original = Some(snap.original.unwrap_or(snap.id));
+    new_snap.id = repo.dbe().save_file(&new_snap)?.into();
+    repo.delete_snapshots_unlocked(&[snap.id])?;
+    info!(
+        "saved snapshot {} with manifest hash of snapshot {}",
+        new_snap.id, snap.id
+    );
+
+    Ok(new_snap)
+}
+
+/// Verify the manifest hash which is saved in the `extra` field of a snapshot.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to verify
+///
+/// # Errors
+///
+/// * If the saved manifest hash is no valid id.
+/// * If a tree could not be read.
+///
+/// # Returns
+///
+/// `None` if the snapshot contains no manifest hash, else whether the saved manifest hash matches
+pub(crate) fn verify_manifest_hash<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+) -> RusticResult<Option<bool>> {
+    let Some(saved) = snap.extra.get(MANIFEST_HASH_KEY) else {
+        return Ok(None);
+    };
+    let saved = saved
+        .as_str()
+        .ok_or_else(|| {
+            RusticError::new(
+                ErrorKind::InvalidInput,
+                "The manifest hash `{hash}` of snapshot `{snapshot}` is no string.",
+            )
+            .attach_context("hash", saved.to_string())
+            .attach_context("snapshot", snap.id.to_string())
+        })
+        .and_then(Id::from_hex)?;
+
+    Ok(Some(manifest_hash(repo, snap)? == saved))
+}
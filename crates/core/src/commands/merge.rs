@@ -8,7 +8,7 @@ use crate::{
     backend::{decrypt::DecryptWriteBackend, node::Node},
     blob::{
         packer::Packer,
-        tree::{self, Tree, TreeId},
+        tree::{self, MergeConflict, Tree, TreeId},
         BlobId, BlobType,
     },
     error::{ErrorKind, RusticError, RusticResult},
@@ -26,6 +26,7 @@ use crate::{
 /// * `snapshots` - The snapshots to merge
 /// * `cmp` - The comparison function for the trees
 /// * `snap` - The snapshot to merge into
+/// * `conflicts` - If given, the merge conflicts are added to this list. The indices refer to `snapshots`.
 ///
 /// # Returns
 ///
@@ -35,6 +36,7 @@ pub(crate) fn merge_snapshots<P: ProgressBars, S: IndexedTree>(
     snapshots: &[SnapshotFile],
     cmp: &impl Fn(&Node, &Node) -> Ordering,
     mut snap: SnapshotFile,
+    conflicts: Option<&mut Vec<MergeConflict>>,
 ) -> RusticResult<SnapshotFile> {
     let now = Local::now();
 
@@ -63,7 +65,7 @@ pub(crate) fn merge_snapshots<P: ProgressBars, S: IndexedTree>(
     summary.backup_start = Local::now();
 
     let trees: Vec<TreeId> = snapshots.iter().map(|sn| sn.tree).collect();
-    snap.tree = merge_trees(repo, &trees, cmp, &mut summary)?;
+    snap.tree = merge_trees(repo, &trees, cmp, &mut summary, conflicts)?;
 
     summary.finalize(now).map_err(|err| {
         RusticError::with_source(ErrorKind::Internal, "Failed to finalize summary.", err)
@@ -87,6 +89,7 @@ pub(crate) fn merge_snapshots<P: ProgressBars, S: IndexedTree>(
 /// * `trees` - The trees to merge
 /// * `cmp` - The comparison function for the trees
 /// * `summary` - The summary to update
+/// * `conflicts` - If given, the merge conflicts are added to this list. The indices refer to `trees`.
 ///
 /// # Errors
 ///
@@ -100,6 +103,7 @@ pub(crate) fn merge_trees<P: ProgressBars, S: IndexedTree>(
     trees: &[TreeId],
     cmp: &impl Fn(&Node, &Node) -> Ordering,
     summary: &mut SnapshotSummary,
+    conflicts: Option<&mut Vec<MergeConflict>>,
 ) -> RusticResult<TreeId> {
     let be = repo.dbe();
     let index = repo.index();
@@ -133,8 +137,11 @@ pub(crate) fn merge_trees<P: ProgressBars, S: IndexedTree>(
         Ok((new_id, size))
     };
 
+    let mut no_conflicts = Vec::new();
+    let conflicts = conflicts.unwrap_or(&mut no_conflicts);
+
     let p = repo.pb.progress_spinner("merging snapshots...");
-    let tree_merged = tree::merge_trees(be, index, trees, cmp, &save, summary)?;
+    let tree_merged = tree::merge_trees(be, index, trees, cmp, &save, summary, conflicts)?;
     let stats = packer.finalize()?;
     indexer.write().unwrap().finalize()?;
     p.finish();
@@ -41,7 +41,7 @@ use crate::{
         indexfile::IndexId, packfile::PackId, HeaderEntry, IndexBlob, IndexFile, IndexPack,
         SnapshotFile, SnapshotId,
     },
-    repository::{Open, Repository},
+    repository::{Open, Repository, Writable},
 };
 
 pub(super) mod constants {
@@ -123,6 +123,14 @@ pub struct PruneOptions {
     #[cfg_attr(feature = "clap", clap(long))]
     pub no_resize: bool,
 
+    /// Only repack packs which are too small. Larger packs are kept even if they are partly unused;
+    /// completely unused packs are still removed. This allows fast prune runs with low IO.
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, conflicts_with_all = &["repack_all", "repack_uncompressed", "no_resize"])
+    )]
+    pub repack_small_only: bool,
+
     #[cfg_attr(feature = "clap", clap(skip))]
     /// Ignore these snapshots when looking for data-still-in-use.
     ///
@@ -148,6 +156,7 @@ impl Default for PruneOptions {
             repack_all: false,
             repack_cacheable_only: None,
             no_resize: false,
+            repack_small_only: false,
             ignore_snaps: Vec::new(),
         }
     }
@@ -255,7 +264,7 @@ pub struct DebugStatsKey {
     pub status: EnumSet<PackStatus>,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct DebugStats(pub BTreeMap<DebugStatsKey, DebugDetailedStats>);
 
 impl DebugStats {
@@ -348,7 +357,7 @@ impl SizeStats {
 }
 
 /// Statistics about a [`PrunePlan`]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct PruneStats {
     /// Statistics about pack count
     pub packs_to_delete: DeleteStats,
@@ -435,6 +444,70 @@ impl Default for PackToDo {
     }
 }
 
+/// The reason for the decision what to do with a pack
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub enum PackDecisionReason {
+    /// The pack is too young to be repacked or removed
+    TooYoung,
+    /// The pack is not cacheable, but only cacheable packs should be repacked
+    NotCacheable,
+    /// The pack is not too small, but only too small packs should be repacked
+    NotTooSmall,
+    /// All blobs of the pack are used
+    Used,
+    /// No blob of the pack is used
+    Unused,
+    /// Some blobs of the pack are unused
+    PartlyUsed,
+    /// The pack is not compressed or all packs should be repacked
+    ToCompress,
+    /// The pack size doesn't fit the target pack size
+    SizeMismatch,
+    /// Repacking the pack would exceed the maximum size to repack
+    MaxRepackReached,
+    /// The unused size in the repository is within the allowed limit
+    UnusedWithinLimit,
+    /// Resizing packs is disabled
+    NoResize,
+    /// Resizing the pack alone is not worth a repack
+    ResizeNotNeeded,
+    /// The pack is marked for deletion long enough
+    MarkedExpired,
+    /// The pack is marked for deletion, but not long enough
+    MarkedNotExpired,
+    /// The pack is marked for deletion, but has no time set
+    MarkedTimeNotSet,
+    /// The pack is marked for deletion, but contains used blobs
+    MarkedButUsed,
+}
+
+/// The decision what to do with a single pack within a [`PrunePlan`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct PackDecision {
+    /// The id of the pack
+    pub id: PackId,
+    /// The type of the blobs in the pack
+    pub blob_type: BlobType,
+    /// The size of the pack
+    pub size: u32,
+    /// What to do with the pack
+    pub todo: PackToDo,
+    /// Why this is done with the pack
+    pub reason: PackDecisionReason,
+    /// The properties of the pack which were considered
+    pub status: EnumSet<PackStatus>,
+    /// The number of used blobs in the pack
+    pub used_blobs: u16,
+    /// The number of unused blobs in the pack
+    pub unused_blobs: u16,
+    /// The size of the used blobs in the pack
+    pub used_size: u32,
+    /// The size of the unused blobs in the pack
+    pub unused_size: u32,
+}
+
 /// A pack which is to be pruned
 #[derive(Debug)]
 struct PrunePack {
@@ -448,6 +521,8 @@ struct PrunePack {
     delete_mark: bool,
     /// The task to be executed on the pack
     to_do: PackToDo,
+    /// The decision about the task, once decided
+    decision: Option<PackDecision>,
     /// The time the pack was created
     time: Option<DateTime<Local>>,
     /// The blobs in the pack
@@ -468,6 +543,7 @@ impl PrunePack {
             size: p.pack_size(),
             delete_mark,
             to_do: PackToDo::Undecided,
+            decision: None,
             time: p.time,
             blobs: p.blobs,
         }
@@ -520,12 +596,15 @@ impl PrunePack {
     /// # Arguments
     ///
     /// * `todo` - The task to be executed on the pack
+    /// * `reason` - The reason for the task
     /// * `pi` - The `PackInfo` of the pack
+    /// * `status` - The status of the pack
     /// * `stats` - The `PruneStats` of the `PrunePlan`
     #[allow(clippy::similar_names)]
     fn set_todo(
         &mut self,
         todo: PackToDo,
+        reason: PackDecisionReason,
         pi: &PackInfo,
         status: EnumSet<PackStatus>,
         stats: &mut PruneStats,
@@ -563,6 +642,18 @@ impl PrunePack {
             }
         }
         self.to_do = todo;
+        self.decision = Some(PackDecision {
+            id: self.id,
+            blob_type: tpe,
+            size: self.size,
+            todo,
+            reason,
+            status,
+            used_blobs: pi.used_blobs,
+            unused_blobs: pi.unused_blobs,
+            used_size: pi.used_size,
+            unused_size: pi.unused_size,
+        });
     }
 
     /// Returns whether the pack is compressed
@@ -584,6 +675,16 @@ enum RepackReason {
     SizeMismatch,
 }
 
+impl From<&RepackReason> for PackDecisionReason {
+    fn from(reason: &RepackReason) -> Self {
+        match reason {
+            RepackReason::PartlyUsed => Self::PartlyUsed,
+            RepackReason::ToCompress => Self::ToCompress,
+            RepackReason::SizeMismatch => Self::SizeMismatch,
+        }
+    }
+}
+
 /// A plan what should be repacked or removed by a `prune` run
 #[derive(Debug)]
 pub struct PrunePlan {
@@ -597,6 +698,8 @@ pub struct PrunePlan {
     repack_candidates: Vec<(PackInfo, EnumSet<PackStatus>, RepackReason, usize, usize)>,
     /// The index files
     index_files: Vec<PruneIndex>,
+    /// The decisions for all packs in the index
+    pack_decisions: Vec<PackDecision>,
     /// `prune` statistics
     pub stats: PruneStats,
 }
@@ -672,6 +775,7 @@ impl PrunePlan {
             existing_packs,
             repack_candidates: Vec::new(),
             index_files,
+            pack_decisions: Vec::new(),
             stats: PruneStats::default(),
         }
     }
@@ -770,6 +874,7 @@ impl PrunePlan {
             repack_cacheable_only,
             opts.repack_uncompressed,
             opts.repack_all,
+            opts.repack_small_only,
             &pack_sizer,
         )?;
 
@@ -778,10 +883,17 @@ impl PrunePlan {
             &opts.max_unused,
             opts.repack_uncompressed || opts.repack_all,
             opts.no_resize,
+            opts.repack_small_only,
             &pack_sizer,
         );
 
         pruner.check_existing_packs()?;
+        pruner.pack_decisions = pruner
+            .index_files
+            .iter()
+            .flat_map(|index| &index.packs)
+            .filter_map(|pack| pack.decision)
+            .collect();
         pruner.filter_index_files(opts.instant_delete);
 
         Ok(pruner)
@@ -833,6 +945,7 @@ impl PrunePlan {
     /// * `repack_cacheable_only` - Whether to only repack cacheable packs
     /// * `repack_uncompressed` - Whether to repack packs containing uncompressed blobs
     /// * `repack_all` - Whether to repack all packs
+    /// * `repack_small_only` - Whether to only repack packs which are too small
     /// * `pack_sizer` - The `PackSizer` for the packs
     ///
     /// # Errors
@@ -840,6 +953,7 @@ impl PrunePlan {
     // TODO: add errors!
     #[allow(clippy::too_many_lines)]
     #[allow(clippy::unnecessary_wraps)]
+    #[allow(clippy::too_many_arguments)]
     fn decide_packs(
         &mut self,
         keep_pack: Duration,
@@ -847,6 +961,7 @@ impl PrunePlan {
         repack_cacheable_only: bool,
         repack_uncompressed: bool,
         repack_all: bool,
+        repack_small_only: bool,
         pack_sizer: &BlobTypeMap<PackSizer>,
     ) -> RusticResult<()> {
         // first process all marked packs then the unmarked ones:
@@ -881,12 +996,22 @@ impl PrunePlan {
                         _ = status.insert(PackStatus::NotCompressed);
                     }
                     let size_mismatch = !pack_sizer[pack.blob_type].size_ok(pack.size);
-                    if pack_sizer[pack.blob_type].is_too_small(pack.size) {
+                    let too_small = pack_sizer[pack.blob_type].is_too_small(pack.size);
+                    if too_small {
                         _ = status.insert(PackStatus::TooSmall);
                     }
                     if pack_sizer[pack.blob_type].is_too_large(pack.size) {
                         _ = status.insert(PackStatus::TooLarge);
                     }
+                    let keep_reason = if too_young {
+                        Some(PackDecisionReason::TooYoung)
+                    } else if keep_uncacheable {
+                        Some(PackDecisionReason::NotCacheable)
+                    } else if repack_small_only && !too_small {
+                        Some(PackDecisionReason::NotTooSmall)
+                    } else {
+                        None
+                    };
                     match (pack.delete_mark, pi.used_blobs, pi.unused_blobs) {
                         (false, 0, _) => {
                             // unused pack
@@ -894,17 +1019,37 @@ impl PrunePlan {
                             _ = status.insert(PackStatus::HasUnusedBlobs);
                             if too_young {
                                 // keep packs which are too young
-                                pack.set_todo(PackToDo::Keep, &pi, status, &mut self.stats);
+                                pack.set_todo(
+                                    PackToDo::Keep,
+                                    PackDecisionReason::TooYoung,
+                                    &pi,
+                                    status,
+                                    &mut self.stats,
+                                );
                             } else {
-                                pack.set_todo(PackToDo::MarkDelete, &pi, status, &mut self.stats);
+                                pack.set_todo(
+                                    PackToDo::MarkDelete,
+                                    PackDecisionReason::Unused,
+                                    &pi,
+                                    status,
+                                    &mut self.stats,
+                                );
                             }
                         }
                         (false, 1.., 0) => {
                             // used pack
                             self.stats.packs.used += 1;
                             _ = status.insert(PackStatus::HasUsedBlobs);
-                            if too_young || keep_uncacheable {
-                                pack.set_todo(PackToDo::Keep, &pi, status, &mut self.stats);
+                            if let Some(reason) = keep_reason {
+                                pack.set_todo(PackToDo::Keep, reason, &pi, status, &mut self.stats);
+                            } else if repack_small_only {
+                                self.repack_candidates.push((
+                                    pi,
+                                    status,
+                                    RepackReason::SizeMismatch,
+                                    index_num,
+                                    pack_num,
+                                ));
                             } else if to_compress || repack_all {
                                 self.repack_candidates.push((
                                     pi,
@@ -922,7 +1067,13 @@ impl PrunePlan {
                                     pack_num,
                                 ));
                             } else {
-                                pack.set_todo(PackToDo::Keep, &pi, status, &mut self.stats);
+                                pack.set_todo(
+                                    PackToDo::Keep,
+                                    PackDecisionReason::Used,
+                                    &pi,
+                                    status,
+                                    &mut self.stats,
+                                );
                             }
                         }
 
@@ -932,9 +1083,19 @@ impl PrunePlan {
                             status
                                 .insert_all(PackStatus::HasUsedBlobs | PackStatus::HasUnusedBlobs);
 
-                            if too_young || keep_uncacheable {
-                                // keep packs which are too young and non-cacheable packs if requested
-                                pack.set_todo(PackToDo::Keep, &pi, status, &mut self.stats);
+                            if let Some(reason) = keep_reason {
+                                // keep packs which are too young, non-cacheable packs and packs which are
+                                // not too small if requested
+                                pack.set_todo(PackToDo::Keep, reason, &pi, status, &mut self.stats);
+                            } else if repack_small_only {
+                                // small partly used pack => candidate for resizing
+                                self.repack_candidates.push((
+                                    pi,
+                                    status,
+                                    RepackReason::SizeMismatch,
+                                    index_num,
+                                    pack_num,
+                                ));
                             } else {
                                 // other partly used pack => candidate for repacking
                                 self.repack_candidates.push((
@@ -954,13 +1115,20 @@ impl PrunePlan {
                                     if self.time - local_date_time >= keep_delete =>
                                 {
                                     _ = status.insert(PackStatus::TooYoung);
-                                    pack.set_todo(PackToDo::Delete, &pi, status, &mut self.stats);
+                                    pack.set_todo(
+                                        PackToDo::Delete,
+                                        PackDecisionReason::MarkedExpired,
+                                        &pi,
+                                        status,
+                                        &mut self.stats,
+                                    );
                                 }
                                 None => {
                                     warn!("pack to delete {}: no time set, this should not happen! Keeping this pack.", pack.id);
                                     _ = status.insert(PackStatus::TimeNotSet);
                                     pack.set_todo(
                                         PackToDo::KeepMarkedAndCorrect,
+                                        PackDecisionReason::MarkedTimeNotSet,
                                         &pi,
                                         status,
                                         &mut self.stats,
@@ -968,6 +1136,7 @@ impl PrunePlan {
                                 }
                                 Some(_) => pack.set_todo(
                                     PackToDo::KeepMarked,
+                                    PackDecisionReason::MarkedNotExpired,
                                     &pi,
                                     status,
                                     &mut self.stats,
@@ -977,7 +1146,13 @@ impl PrunePlan {
                         (true, 1.., _) => {
                             status.insert_all(PackStatus::Marked | PackStatus::HasUsedBlobs);
                             // needed blobs; mark this pack for recovery
-                            pack.set_todo(PackToDo::Recover, &pi, status, &mut self.stats);
+                            pack.set_todo(
+                                PackToDo::Recover,
+                                PackDecisionReason::MarkedButUsed,
+                                &pi,
+                                status,
+                                &mut self.stats,
+                            );
                         }
                     }
                 }
@@ -994,6 +1169,7 @@ impl PrunePlan {
     /// * `max_unused` - The maximum size of unused blobs
     /// * `repack_uncompressed` - Whether to repack packs containing uncompressed blobs
     /// * `no_resize` - Whether to resize packs
+    /// * `repack_small_only` - Whether to only repack packs which are too small
     /// * `pack_sizer` - The `PackSizer` for the packs
     ///
     /// # Errors
@@ -1005,6 +1181,7 @@ impl PrunePlan {
         max_unused: &LimitOption,
         repack_uncompressed: bool,
         no_resize: bool,
+        repack_small_only: bool,
         pack_sizer: &BlobTypeMap<PackSizer>,
     ) {
         let max_unused = match (repack_uncompressed, max_unused) {
@@ -1035,35 +1212,51 @@ impl PrunePlan {
             let blob_type = pi.blob_type;
 
             let total_repack_size: u64 = repack_size.into_values().sum();
-            if total_repack_size + u64::from(pi.used_size) >= max_repack
-                || (self.stats.size_sum().unused_after_prune() < max_unused
-                    && repack_reason == RepackReason::PartlyUsed
-                    && blob_type == BlobType::Data)
-                || (repack_reason == RepackReason::SizeMismatch && no_resize)
+            let keep_reason = if total_repack_size + u64::from(pi.used_size) >= max_repack {
+                Some(PackDecisionReason::MaxRepackReached)
+            } else if self.stats.size_sum().unused_after_prune() < max_unused
+                && repack_reason == RepackReason::PartlyUsed
+                && blob_type == BlobType::Data
             {
-                pack.set_todo(PackToDo::Keep, &pi, status, &mut self.stats);
+                Some(PackDecisionReason::UnusedWithinLimit)
+            } else if repack_reason == RepackReason::SizeMismatch && no_resize {
+                Some(PackDecisionReason::NoResize)
+            } else {
+                None
+            };
+
+            if let Some(reason) = keep_reason {
+                pack.set_todo(PackToDo::Keep, reason, &pi, status, &mut self.stats);
             } else if repack_reason == RepackReason::SizeMismatch {
                 resize_packs[blob_type].push((pi, status, index_num, pack_num));
                 repack_size[blob_type] += u64::from(pi.used_size);
             } else {
-                pack.set_todo(PackToDo::Repack, &pi, status, &mut self.stats);
+                pack.set_todo(
+                    PackToDo::Repack,
+                    (&repack_reason).into(),
+                    &pi,
+                    status,
+                    &mut self.stats,
+                );
                 repack_size[blob_type] += u64::from(pi.used_size);
                 do_repack[blob_type] = true;
             }
         }
         for (blob_type, resize_packs) in resize_packs {
-            // packs in resize_packs are only repacked if we anyway repack this blob type or
-            // if the target pack size is reached for the blob type.
-            let todo = if do_repack[blob_type]
+            // packs in resize_packs are only repacked if we anyway repack this blob type,
+            // if the target pack size is reached for the blob type or if we only repack small
+            // packs and there are at least two of them to consolidate.
+            let (todo, reason) = if do_repack[blob_type]
                 || repack_size[blob_type] > u64::from(pack_sizer[blob_type].pack_size())
+                || (repack_small_only && resize_packs.len() > 1)
             {
-                PackToDo::Repack
+                (PackToDo::Repack, PackDecisionReason::SizeMismatch)
             } else {
-                PackToDo::Keep
+                (PackToDo::Keep, PackDecisionReason::ResizeNotNeeded)
             };
             for (pi, status, index_num, pack_num) in resize_packs {
                 let pack = &mut self.index_files[index_num].packs[pack_num];
-                pack.set_todo(todo, &pi, status, &mut self.stats);
+                pack.set_todo(todo, reason, &pi, status, &mut self.stats);
             }
         }
     }
@@ -1165,6 +1358,15 @@ impl PrunePlan {
         // repacks come at end
     }
 
+    /// Get the decisions what to do with each pack referenced in the index.
+    ///
+    /// This contains all packs, including the ones which are kept, together with the
+    /// reason for the decision. It can be serialized to review the plan before running it.
+    #[must_use]
+    pub fn pack_decisions(&self) -> &[PackDecision] {
+        &self.pack_decisions
+    }
+
     /// Get the list of packs-to-repack from the [`PrunePlan`].
     #[must_use]
     pub fn repack_packs(&self) -> Vec<PackId> {
@@ -1198,7 +1400,7 @@ impl PrunePlan {
     #[allow(clippy::significant_drop_tightening)]
     #[allow(clippy::too_many_lines)]
     #[deprecated(since = "0.5.2", note = "Use `Repository::prune()` instead.")]
-    pub fn do_prune<P: ProgressBars, S: Open>(
+    pub fn do_prune<P: ProgressBars, S: Writable>(
         self,
         repo: &Repository<P, S>,
         opts: &PruneOptions,
@@ -1219,6 +1421,7 @@ impl PrunePlan {
 ///
 /// * If the repository is in append-only mode
 /// * If a pack has no decision
+/// * If the pruning has been cancelled. Packs which have not been repacked yet are kept in this case.
 ///
 /// # Returns
 ///
@@ -1240,9 +1443,11 @@ pub(crate) fn prune_repository<P: ProgressBars, S: Open>(
             "Pruning is not allowed in append-only repositories. Please disable append-only mode first, if you know what you are doing. Aborting.",
         ));
     }
+    repo.cancel.check()?;
     repo.warm_up_wait(prune_plan.repack_packs().into_iter())?;
     let be = repo.dbe();
     let pb = &repo.pb;
+    let cancel = &repo.cancel;
 
     let indexer = Indexer::new_unindexed(be.clone()).into_shared();
 
@@ -1364,6 +1569,11 @@ pub(crate) fn prune_repository<P: ProgressBars, S: Open>(
                     let pack = pack.into_index_pack();
                     indexer.write().unwrap().add(pack)?;
                 }
+                PackToDo::Repack if cancel.is_cancelled() => {
+                    // cancelled: keep pack instead of repacking it
+                    let pack = pack.into_index_pack();
+                    indexer.write().unwrap().add(pack)?;
+                }
                 PackToDo::Repack => {
                     // TODO: repack in parallel
                     for blob in &pack.blobs {
@@ -1431,6 +1641,9 @@ pub(crate) fn prune_repository<P: ProgressBars, S: Open>(
         be.delete_list(true, indexes_remove.iter(), p)?;
     }
 
+    // the repository is consistent now; when cancelled, leave the removal of packs to the next prune run
+    cancel.check()?;
+
     // get variable out of Arc<Mutex<_>>
     let data_packs_remove = data_packs_remove.lock().unwrap();
     if !data_packs_remove.is_empty() {
@@ -1589,6 +1802,7 @@ fn find_used_blobs(
         .into_iter()
         .filter(|id| !ignore_snaps.contains(&SnapshotId::from(*id)))
         .collect();
+    p.set_length(list.len() as u64);
     let snap_trees: Vec<_> = be
         .stream_list::<SnapshotFile>(&list, &p)?
         .into_iter()
@@ -1,2 +1,3 @@
 pub mod index;
 pub mod snapshots;
+pub mod trees;
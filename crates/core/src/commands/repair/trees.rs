@@ -0,0 +1,139 @@
+//! recovering trees which are not referenced by any snapshot, e.g. after an aborted backup
+use std::collections::BTreeSet;
+
+use log::{info, warn};
+
+use crate::{
+    backend::decrypt::{DecryptReadBackend, DecryptWriteBackend},
+    blob::{
+        tree::{Tree, TreeId, TreeStreamerOnce},
+        BlobType,
+    },
+    error::{ErrorKind, RusticError, RusticResult},
+    index::ReadIndex,
+    progress::{Progress, ProgressBars},
+    repofile::{snapshotfile::SnapshotOptions, IndexFile, SnapshotFile},
+    repository::{IndexedTree, Repository, Writable},
+};
+
+/// Find the root trees of all trees in the index which are not referenced by any snapshot.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to search
+///
+/// # Errors
+///
+/// * If the index or snapshot files could not be read.
+/// * If a tree could not be read.
+///
+/// # Returns
+///
+/// The ids of all orphaned trees which are not a subtree of another orphaned tree, sorted by id.
+pub(crate) fn recover_orphaned_trees<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+) -> RusticResult<Vec<TreeId>> {
+    let be = repo.dbe();
+
+    let p = repo.pb.progress_counter("reading index...");
+    let mut orphans = BTreeSet::new();
+    for index in be.stream_all::<IndexFile>(&p)? {
+        for pack in index?.1.packs {
+            orphans.extend(
+                pack.blobs
+                    .iter()
+                    .filter(|blob| blob.tpe == BlobType::Tree)
+                    .map(|blob| TreeId::from(*blob.id)),
+            );
+        }
+    }
+    p.finish();
+
+    let p = repo.pb.progress_counter("reading snapshots...");
+    let mut snap_trees = Vec::new();
+    for snap in be.stream_all::<SnapshotFile>(&p)? {
+        snap_trees.push(snap?.1.tree);
+    }
+    p.finish();
+
+    // remove all trees which are reachable from a snapshot
+    for id in &snap_trees {
+        _ = orphans.remove(id);
+    }
+    let p = repo.pb.progress_counter("finding used trees...");
+    let mut tree_streamer = TreeStreamerOnce::new(be, repo.index(), snap_trees, p)?;
+    while let Some((_, tree)) = tree_streamer.next().transpose()? {
+        repo.cancel.check()?;
+        for id in tree.nodes.iter().filter_map(|node| node.subtree) {
+            _ = orphans.remove(&id);
+        }
+    }
+
+    // only keep the roots of the orphaned trees
+    let p = repo.pb.progress_counter("finding orphaned root trees...");
+    p.set_length(orphans.len() as u64);
+    let mut subtrees = BTreeSet::new();
+    for id in &orphans {
+        repo.cancel.check()?;
+        match Tree::from_backend(be, repo.index(), *id) {
+            Ok(tree) => subtrees.extend(tree.nodes.iter().filter_map(|node| node.subtree)),
+            Err(err) => warn!("ignoring unreadable tree {id}: {}", err.display_log()),
+        }
+        p.inc(1);
+    }
+    p.finish();
+
+    let roots: Vec<_> = orphans.difference(&subtrees).copied().collect();
+    info!("found {} orphaned root trees", roots.len());
+    Ok(roots)
+}
+
+/// Create a new snapshot with the given tree.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to save the snapshot to
+/// * `tree_id` - The root tree of the snapshot
+/// * `opts` - The options to create the snapshot from
+/// * `dry_run` - If true, the snapshot is only returned, but not saved
+///
+/// # Errors
+///
+/// * If the tree is not contained in the index.
+/// * If the snapshot could not be created from the options.
+/// * If the snapshot could not be saved.
+///
+/// # Returns
+///
+/// The created snapshot
+pub(crate) fn create_snapshot_from_tree<P, S: IndexedTree + Writable>(
+    repo: &Repository<P, S>,
+    tree_id: TreeId,
+    opts: &SnapshotOptions,
+    dry_run: bool,
+) -> RusticResult<SnapshotFile> {
+    if !repo.index().has_tree(&tree_id) {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Tree `{tree_id}` is not contained in the index. Please use an existing tree.",
+        )
+        .attach_context("tree_id", tree_id.to_string()));
+    }
+
+    let mut snap = SnapshotFile::from_options(opts)?;
+    snap.tree = tree_id;
+    if !dry_run {
+        snap.id = repo.dbe().save_file(&snap)?.into();
+    }
+    Ok(snap)
+}
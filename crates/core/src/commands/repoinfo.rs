@@ -1,3 +1,5 @@
+use bytesize::ByteSize;
+use derive_setters::Setters;
 use serde_derive::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
 
@@ -156,6 +158,149 @@ pub(crate) fn collect_index_infos<P: ProgressBars, S: Open>(
     Ok(info)
 }
 
+#[cfg_attr(feature = "clap", derive(clap::Parser))]
+#[derive(Clone, Debug, Setters)]
+#[setters(into)]
+#[non_exhaustive]
+/// Options for [`Repository::pack_size_histogram`]
+pub struct PackSizeHistogramOptions {
+    /// Boundaries of the pack size buckets
+    #[cfg_attr(
+        feature = "clap",
+        clap(long, value_name = "SIZE", value_delimiter = ',', default_values_t = default_boundaries())
+    )]
+    pub boundaries: Vec<ByteSize>,
+}
+
+impl Default for PackSizeHistogramOptions {
+    fn default() -> Self {
+        Self {
+            boundaries: default_boundaries(),
+        }
+    }
+}
+
+/// The default boundaries of the pack size buckets
+fn default_boundaries() -> Vec<ByteSize> {
+    [1, 2, 4, 8, 16, 32, 64, 128]
+        .into_iter()
+        .map(ByteSize::mib)
+        .collect()
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+/// A bucket of a [`PackSizeHistogram`] containing all packs with size in `min_size..max_size`
+pub struct PackSizeBucket {
+    /// Minimal pack size of the bucket (inclusive)
+    pub min_size: u64,
+    /// Maximal pack size of the bucket (exclusive), None for the last bucket
+    pub max_size: Option<u64>,
+    /// Number of packs within the bucket
+    pub count: u64,
+    /// Total size of all packs within the bucket
+    pub size: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+/// Pack size buckets for a given blob type
+pub struct PackSizes {
+    /// Packs of the given blob type
+    pub blob_type: BlobType,
+    /// The buckets, sorted by size
+    pub buckets: Vec<PackSizeBucket>,
+}
+
+impl PackSizes {
+    /// Create empty buckets using the given boundaries.
+    ///
+    /// # Arguments
+    ///
+    /// * `blob_type` - The blob type of the packs
+    /// * `boundaries` - The sorted and deduplicated boundaries of the buckets
+    fn new(blob_type: BlobType, boundaries: &[u64]) -> Self {
+        let buckets = std::iter::once(0)
+            .chain(boundaries.iter().copied())
+            .zip(boundaries.iter().copied().map(Some).chain([None]))
+            .map(|(min_size, max_size)| PackSizeBucket {
+                min_size,
+                max_size,
+                count: 0,
+                size: 0,
+            })
+            .collect();
+        Self { blob_type, buckets }
+    }
+
+    /// Add a pack to the matching bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size of the pack
+    fn add(&mut self, size: u64) {
+        if let Some(bucket) = self
+            .buckets
+            .iter_mut()
+            .find(|bucket| bucket.max_size.map_or(true, |max_size| size < max_size))
+        {
+            bucket.count += 1;
+            bucket.size += size;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+/// Distribution of pack sizes, see [`Repository::pack_size_histogram`]
+pub struct PackSizeHistogram {
+    /// Pack size buckets for each blob type
+    pub packs: Vec<PackSizes>,
+}
+
+/// Collects the distribution of pack sizes from the index of the given repository.
+///
+/// Packs marked for deletion are not included.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to collect the pack sizes from.
+/// * `opts` - The options to use.
+///
+/// # Errors
+///
+/// * If the index could not be read.
+pub(crate) fn collect_pack_size_histogram<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    opts: &PackSizeHistogramOptions,
+) -> RusticResult<PackSizeHistogram> {
+    let mut boundaries: Vec<_> = opts.boundaries.iter().map(ByteSize::as_u64).collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    boundaries.retain(|size| *size > 0);
+
+    let mut sizes =
+        BlobTypeMap::<()>::default().map(|blob_type, ()| PackSizes::new(blob_type, &boundaries));
+
+    let p = repo.pb.progress_counter("scanning index...");
+    for index in repo.dbe().stream_all::<IndexFile>(&p)? {
+        for pack in &index?.1.packs {
+            sizes[pack.blob_type()].add(u64::from(pack.pack_size()));
+        }
+    }
+    p.finish();
+
+    Ok(PackSizeHistogram {
+        packs: sizes.into_values().collect(),
+    })
+}
+
 #[skip_serializing_none]
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -226,3 +371,39 @@ pub(crate) fn collect_file_infos<P: ProgressBars, S>(
         repo_hot: files_hot,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_sizes_buckets() {
+        let mut sizes = PackSizes::new(BlobType::Data, &[10, 100]);
+        for size in [0, 9, 10, 99, 100, 1000] {
+            sizes.add(size);
+        }
+        assert_eq!(
+            sizes.buckets,
+            vec![
+                PackSizeBucket {
+                    min_size: 0,
+                    max_size: Some(10),
+                    count: 2,
+                    size: 9
+                },
+                PackSizeBucket {
+                    min_size: 10,
+                    max_size: Some(100),
+                    count: 2,
+                    size: 109
+                },
+                PackSizeBucket {
+                    min_size: 100,
+                    max_size: None,
+                    count: 2,
+                    size: 1100
+                },
+            ]
+        );
+    }
+}
@@ -1,36 +1,51 @@
 //! `restore` subcommand
 
+mod resume;
+
 use derive_setters::Setters;
 use log::{debug, error, info, trace, warn};
 
 use std::{
     cmp::Ordering,
-    collections::BTreeMap,
-    num::NonZeroU32,
-    path::{Path, PathBuf},
-    sync::Mutex,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    env, fmt,
+    io::{Seek, SeekFrom},
+    num::{NonZeroU32, NonZeroUsize},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicBool, AtomicUsize},
+        Arc, Mutex,
+    },
+    thread,
 };
 
-use chrono::{DateTime, Local, Utc};
-use ignore::{DirEntry, WalkBuilder};
-use itertools::Itertools;
-use rayon::ThreadPoolBuilder;
+use bytes::{Buf, Bytes, BytesMut};
+use itertools::{Either, Itertools};
+use rayon::{
+    prelude::{IntoParallelIterator, ParallelIterator},
+    ThreadPoolBuilder,
+};
+use serde_derive::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
 
 use crate::{
     backend::{
         decrypt::DecryptReadBackend,
-        local_destination::LocalDestination,
         node::{Node, NodeType},
-        FileType, ReadBackend,
+        DestinationEntry, FileType, MetadataOptions, PartialChunks, ReadBackend,
+        RestoreDestination,
     },
+    blob::tree::{FilterRule, FilterRules, NodeStreamer, Tree},
     error::{ErrorKind, RusticError, RusticResult},
     progress::{Progress, ProgressBars},
-    repofile::packfile::PackId,
+    repofile::{packfile::PackId, SnapshotFile},
     repository::{IndexedFull, IndexedTree, Open, Repository},
 };
 
+use resume::{DoneBlobs, ResumeState, StateFile, TreeDigest};
+
 pub(crate) mod constants {
-    /// The maximum number of reader threads to use for restoring.
+    /// The maximum number of reader threads to use for restoring, if not given explicitly.
     pub(crate) const MAX_READER_THREADS_NUM: usize = 20;
     /// The maximum size of pack-part which is read at once from the backend.
     /// (needed to limit the memory size used for large backends)
@@ -42,10 +57,12 @@ type Filenames = Vec<PathBuf>;
 
 #[allow(clippy::struct_excessive_bools)]
 #[cfg_attr(feature = "clap", derive(clap::Parser))]
-#[derive(Debug, Copy, Clone, Default, Setters)]
+#[derive(Debug, Clone, Copy, Default, Setters)]
 #[setters(into)]
 #[non_exhaustive]
 /// Options for the `restore` command
+///
+/// Options which can't be `Copy` like paths or callbacks are given by [`RestoreExtraOptions`].
 pub struct RestoreOptions {
     /// Remove all files/dirs in destination which are not contained in snapshot.
     ///
@@ -66,6 +83,251 @@ pub struct RestoreOptions {
     /// Always read and verify existing files (don't trust correct modification time and file size)
     #[cfg_attr(feature = "clap", clap(long))]
     pub verify_existing: bool,
+
+    /// Don't warm up the needed packs before restoring, e.g. because this has already been done
+    /// using [`Repository::warm_up_for_restore`]
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub no_warm_up: bool,
+
+    /// Number of threads used to read the file contents from the repository
+    /// [default: `RAYON_NUM_THREADS` if set, else the number of CPUs, but at most 20]
+    #[cfg_attr(feature = "clap", clap(long, value_name = "NUM"))]
+    pub read_threads: Option<usize>,
+
+    /// Restore symlinks pointing to a file within the restored tree as a copy of that file
+    ///
+    /// # Note
+    ///
+    /// * Symlinks pointing outside the restored tree or to a non-existing file, a directory
+    ///   or another symlink are still restored as symlinks (see `skip_external_symlinks`).
+    /// * The paths of all restored files are kept in memory to find the symlink targets.
+    #[cfg_attr(feature = "clap", clap(long))]
+    pub dereference_symlinks: bool,
+
+    /// Don't restore symlinks which can't be dereferenced (only used with `dereference_symlinks`)
+    #[cfg_attr(feature = "clap", clap(long, requires = "dereference_symlinks"))]
+    pub skip_external_symlinks: bool,
+
+    /// What to do with entries which only differ in case from an already restored entry if the
+    /// destination is case-insensitive (default: restore them anyway, overwriting the other entry)
+    #[cfg_attr(feature = "clap", clap(long, value_name = "ACTION"))]
+    pub on_case_conflict: Option<CaseConflictAction>,
+
+    /// Only restore the metadata (ownership, permissions, extended attributes and times) of entries
+    /// already existing in the destination; don't restore any contents.
+    ///
+    /// # Note
+    ///
+    /// * Missing entries are not created, but only reported by a warning.
+    /// * Additional entries are never removed, i.e. `delete` is ignored.
+    #[cfg_attr(feature = "clap", clap(long, conflicts_with = "delete"))]
+    pub metadata_only: bool,
+}
+
+#[cfg_attr(feature = "clap", derive(clap::Parser))]
+#[derive(Debug, Clone, Default, Setters)]
+#[setters(into)]
+#[non_exhaustive]
+/// Additional options for the `restore` command, see [`Repository::prepare_restore_with_extra`]
+///
+/// In contrast to [`RestoreOptions`], these options are not `Copy`. They are given when preparing
+/// the restore and are kept in the [`RestorePlan`] for the actual restore.
+pub struct RestoreExtraOptions {
+    /// Record the restore progress in this file and resume an interrupted restore recorded in it.
+    ///
+    /// # Note
+    ///
+    /// * The file is bound to the restored snapshot; resuming the restore of a different snapshot is refused.
+    /// * The file is removed once the restore has been finished successfully.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "FILE"))]
+    pub resume_state: Option<PathBuf>,
+
+    /// Remove this prefix from the paths of the restored entries, e.g. strip `/home/user` to restore
+    /// `/home/user/project` as `project`
+    ///
+    /// # Note
+    ///
+    /// * Entries not within the prefix are skipped with a warning.
+    /// * The prefix is stripped before `add_prefix` is added.
+    #[cfg_attr(feature = "clap", clap(long, value_name = "PATH"))]
+    pub strip_prefix: Option<PathBuf>,
+
+    /// Add this prefix to the paths of the restored entries
+    #[cfg_attr(feature = "clap", clap(long, value_name = "PATH"))]
+    pub add_prefix: Option<PathBuf>,
+
+    /// Ordered include/exclude rules selecting the entries to restore; the last matching rule wins.
+    ///
+    /// # Note
+    ///
+    /// * The rules are applied to the entries given by the node streamer, i.e. after the glob options
+    ///   used to create the streamer. An entry is only restored if it passes both.
+    /// * Parent directories of included entries are created even if they are excluded, but their
+    ///   metadata is not restored.
+    /// * See [`FilterRule`] for how patterns are matched. On the command line, use the `--filter`
+    ///   option of the streamer options (`LsOptions`) instead.
+    #[cfg_attr(feature = "clap", clap(skip))]
+    pub filter_rules: Vec<FilterRule>,
+
+    /// Callback which decides what to do if restoring a file failed (default: abort the restore)
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[setters(skip)]
+    pub on_file_error: Option<FileErrorCallback>,
+
+    /// Callback which is called when the contents of a file have been completely restored
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[setters(skip)]
+    pub on_file_done: Option<FileDoneCallback>,
+
+    /// Mapper changing the paths the entries are restored to, e.g. to not reveal the real filenames
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[setters(skip)]
+    pub path_mapper: Option<PathMapper>,
+}
+
+impl RestoreExtraOptions {
+    /// Set the callback which decides what to do if restoring a file failed.
+    ///
+    /// Without callback, the restore is aborted on the first error.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The callback getting the path of the file and the error
+    #[must_use]
+    pub fn on_file_error(
+        mut self,
+        callback: impl Fn(&Path, &RusticError) -> ErrorAction + Send + Sync + 'static,
+    ) -> Self {
+        self.on_file_error = Some(FileErrorCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Set the callback which is called when the contents of a file have been completely restored.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - The callback getting the path and the size of the file
+    #[must_use]
+    pub fn on_file_done(mut self, callback: impl Fn(&Path, u64) + Send + Sync + 'static) -> Self {
+        self.on_file_done = Some(FileDoneCallback(Arc::new(callback)));
+        self
+    }
+
+    /// Set the mapper which changes the paths the entries are restored to, while keeping their contents and metadata.
+    ///
+    /// The mapper gets the path after `strip_prefix` and `add_prefix` have been applied.
+    ///
+    /// # Arguments
+    ///
+    /// * `mapper` - The mapper getting the path of an entry and returning the path to restore it to
+    ///
+    /// # Note
+    ///
+    /// * The mapper must return relative paths without `..` and must not map different paths to the same path,
+    ///   otherwise the restore fails. It should keep entries within their parent dir, e.g. by mapping each
+    ///   path component separately.
+    /// * All entries are kept in memory to sort them by the mapped paths.
+    /// * Existing entries in the destination are compared with the mapped paths, i.e. `delete` removes
+    ///   all entries which are not the mapped path of an entry.
+    /// * Symlinks are restored with their original targets.
+    #[must_use]
+    pub fn path_mapper(
+        mut self,
+        mapper: impl Fn(&Path) -> PathBuf + Send + Sync + 'static,
+    ) -> Self {
+        self.path_mapper = Some(PathMapper(Arc::new(mapper)));
+        self
+    }
+}
+
+/// What to do with entries only differing in case on case-insensitive destinations,
+/// see [`RestoreOptions::on_case_conflict`]
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CaseConflictAction {
+    /// Don't restore the conflicting entry (including its contents for directories)
+    Skip,
+    /// Restore the conflicting entry under a name with a suffix like `~1`
+    Rename,
+    /// Abort the restore with an error
+    Error,
+}
+
+/// What to do if restoring a file failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorAction {
+    /// Skip the file and continue restoring the other files
+    Skip,
+    /// Abort the restore and return the error
+    Abort,
+}
+
+/// Callback which decides what to do if restoring a file failed, see [`RestoreExtraOptions::on_file_error`]
+#[derive(Clone)]
+pub struct FileErrorCallback(Arc<dyn Fn(&Path, &RusticError) -> ErrorAction + Send + Sync>);
+
+impl FileErrorCallback {
+    /// Call the callback.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file which failed
+    /// * `err` - The error
+    #[must_use]
+    pub fn call(&self, path: &Path, err: &RusticError) -> ErrorAction {
+        (self.0)(path, err)
+    }
+}
+
+impl fmt::Debug for FileErrorCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FileErrorCallback(..)")
+    }
+}
+
+/// Callback which is called when a file has been restored, see [`RestoreExtraOptions::on_file_done`]
+#[derive(Clone)]
+pub struct FileDoneCallback(Arc<dyn Fn(&Path, u64) + Send + Sync>);
+
+impl FileDoneCallback {
+    /// Call the callback.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the restored file
+    /// * `size` - The size of the restored file
+    pub fn call(&self, path: &Path, size: u64) {
+        (self.0)(path, size);
+    }
+}
+
+impl fmt::Debug for FileDoneCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FileDoneCallback(..)")
+    }
+}
+
+/// Mapper changing the paths of restored entries, see [`RestoreExtraOptions::path_mapper`]
+#[derive(Clone)]
+pub struct PathMapper(Arc<dyn Fn(&Path) -> PathBuf + Send + Sync>);
+
+impl PathMapper {
+    /// Call the mapper.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to map
+    #[must_use]
+    pub fn call(&self, path: &Path) -> PathBuf {
+        (self.0)(path)
+    }
+}
+
+impl fmt::Debug for PathMapper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PathMapper(..)")
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -92,6 +354,143 @@ pub struct RestoreStats {
     pub files: FileDirStats,
     /// directory statistics
     pub dirs: FileDirStats,
+    /// Number of entries which only differ in case from another entry on a case-insensitive destination
+    pub case_conflicts: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+#[non_exhaustive]
+/// Preview of a restore, see [`Repository::restore_preview`]
+pub struct RestorePreview {
+    /// Statistics about the restore
+    pub stats: RestoreStats,
+    /// The total size of the content to restore
+    pub restore_size: u64,
+    /// The total size of matched content, i.e. content which needs no restore
+    pub matched_size: u64,
+    /// The pack files needed to restore, e.g. to warm them up
+    pub packs: Vec<PackId>,
+}
+
+/// Compute what a restore would do without touching the destination.
+///
+/// This is [`collect_and_prepare`] in dry-run mode, so no dirs are created and no additional entries are
+/// removed, regardless of [`RestoreOptions::delete`].
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The type of the indexed tree.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to restore.
+/// * `opts` - The restore options.
+/// * `extra` - The additional restore options.
+/// * `node_streamer` - The node streamer to use.
+/// * `dest` - The destination to restore to.
+///
+/// # Errors
+///
+/// * If the restore information could not be collected.
+/// * If the resume state could not be read or belongs to a different snapshot.
+/// * If entries only differ in case on a case-insensitive destination and `on_case_conflict` is `Error`.
+pub(crate) fn restore_preview<P: ProgressBars, S: IndexedFull, D: RestoreDestination>(
+    repo: &Repository<P, S>,
+    opts: &RestoreOptions,
+    extra: &RestoreExtraOptions,
+    node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    dest: &D,
+) -> RusticResult<RestorePreview> {
+    let plan = collect_and_prepare(repo, opts, extra, node_streamer, dest, true)?;
+    Ok(RestorePreview {
+        stats: plan.stats,
+        restore_size: plan.restore_size,
+        matched_size: plan.matched_size,
+        packs: plan.to_packs(),
+    })
+}
+
+/// Verify the contents of restored files against a snapshot.
+///
+/// All files of the snapshot are read from the destination and compared blob-by-blob with the
+/// blob ids saved in the snapshot. The files are verified in parallel.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type
+/// * `S` - The type of the indexed tree
+/// * `D` - The type of the destination
+///
+/// # Arguments
+///
+/// * `repo` - The repository
+/// * `snap` - The snapshot which was restored
+/// * `dest` - The destination the snapshot was restored to
+///
+/// # Errors
+///
+/// * If the snapshot tree could not be read.
+/// * If a blob of a file is not contained in the index.
+///
+/// # Returns
+///
+/// The paths (relative to the destination) of all files which are missing or differ, sorted by path.
+pub(crate) fn verify_restored<P: ProgressBars, S: IndexedFull, D: RestoreDestination>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+    dest: &D,
+) -> RusticResult<Vec<PathBuf>> {
+    let root = Tree::node_from_path(repo.dbe(), repo.index(), snap.tree, Path::new(""))?;
+
+    // collect all files together with the lengths of their blobs
+    let mut files = Vec::new();
+    for item in NodeStreamer::new(repo.dbe().clone(), repo.index(), &root)? {
+        let (path, node) = item?;
+        if !node.is_file() {
+            continue;
+        }
+        let blobs = node
+            .content
+            .iter()
+            .flatten()
+            .map(|id| {
+                let length = repo.get_index_entry(id)?.data_length();
+                Ok((*id, length as usize))
+            })
+            .collect::<RusticResult<Vec<_>>>()?;
+        files.push((path, node.meta.size, blobs));
+    }
+
+    let p = repo.pb.progress_bytes("verifying restored files...");
+    p.set_length(files.iter().map(|(_, size, _)| size).sum());
+
+    let cancel = &repo.cancel;
+    let differing: RusticResult<Vec<_>> = files
+        .into_par_iter()
+        .filter_map(|(path, size, blobs)| {
+            if let Err(err) = cancel.check() {
+                return Some(Err(err));
+            }
+            let matches = match dest.get_matching_file(&path, size) {
+                Ok(Some(mut existing)) => blobs
+                    .iter()
+                    .all(|(id, length)| id.blob_matches_reader(*length, &mut existing.file)),
+                Ok(None) => false,
+                Err(err) => {
+                    warn!("error opening {path:?}: {}", err.display_log());
+                    false
+                }
+            };
+            p.inc(size);
+            (!matches).then_some(Ok(path))
+        })
+        .collect();
+    p.finish();
+
+    let mut differing = differing?;
+    differing.sort_unstable();
+    Ok(differing)
 }
 
 /// Restore the repository to the given destination.
@@ -112,23 +511,70 @@ pub struct RestoreStats {
 /// # Errors
 ///
 /// * If the restore failed.
-pub(crate) fn restore_repository<P: ProgressBars, S: IndexedTree>(
-    file_infos: RestorePlan,
+pub(crate) fn restore_repository<P: ProgressBars, S: IndexedTree, D: RestoreDestination>(
+    mut file_infos: RestorePlan,
     repo: &Repository<P, S>,
-    opts: RestoreOptions,
+    opts: &RestoreOptions,
     node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
-    dest: &LocalDestination,
+    dest: &D,
 ) -> RusticResult<()> {
-    repo.warm_up_wait(file_infos.to_packs().into_iter())?;
-    restore_contents(repo, dest, file_infos)?;
+    let resume = file_infos.resume.take();
+    let materialized = std::mem::take(&mut file_infos.materialized);
+    let case_mapping = std::mem::take(&mut file_infos.case_mapping);
+    let missing = std::mem::take(&mut file_infos.missing);
+    let extra = std::mem::take(&mut file_infos.extra);
+    if !opts.metadata_only {
+        if !opts.no_warm_up {
+            repo.warm_up_wait(file_infos.to_packs().into_iter())?;
+        }
+        restore_contents(repo, dest, file_infos, opts, &extra, resume.as_ref())?;
+    }
 
     let p = repo.pb.progress_spinner("setting metadata...");
-    restore_metadata(node_streamer, opts, dest)?;
+    let node_streamer = filter_by_rules(node_streamer, &extra.filter_rules)?;
+    let node_streamer = node_streamer.filter_map(|item| match item {
+        Ok((path, node)) => prefixed_path(&path, &extra).map(|path| Ok((path, node))),
+        Err(err) => Some(Err(err)),
+    });
+    let node_streamer = map_paths(node_streamer, &extra)?;
+    let node_streamer = node_streamer.filter_map(|item| match item {
+        Ok((path, node)) => case_mapping
+            .map(&path)
+            .filter(|path| !path.ancestors().any(|path| missing.contains(path)))
+            .map(|path| Ok((path, node))),
+        Err(err) => Some(Err(err)),
+    });
+    restore_metadata(node_streamer, opts, dest, &materialized)?;
     p.finish();
 
+    if let Some(resume) = resume {
+        resume.remove()?;
+    }
+
     Ok(())
 }
 
+/// Only keep the entries of the node streamer which are included by the given rules
+///
+/// # Arguments
+///
+/// * `node_streamer` - The node streamer to filter
+/// * `rules` - The ordered include/exclude rules
+///
+/// # Errors
+///
+/// * If a pattern of the rules is invalid.
+fn filter_by_rules(
+    node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    rules: &[FilterRule],
+) -> RusticResult<impl Iterator<Item = RusticResult<(PathBuf, Node)>>> {
+    let rules = FilterRules::new(rules)?;
+    Ok(node_streamer.filter(move |item| match (item, &rules) {
+        (Ok((path, _)), Some(rules)) => rules.is_included(path),
+        _ => true,
+    }))
+}
+
 /// Collect restore information, scan existing files, create needed dirs and remove superfluous files
 ///
 /// # Type Parameters
@@ -139,6 +585,8 @@ pub(crate) fn restore_repository<P: ProgressBars, S: IndexedTree>(
 /// # Arguments
 ///
 /// * `repo` - The repository to restore.
+/// * `opts` - The restore options.
+/// * `extra` - The additional restore options which are kept in the returned plan.
 /// * `node_streamer` - The node streamer to use.
 /// * `dest` - The destination to restore to.
 /// * `dry_run` - If true, don't actually restore anything, but only print out what would be done.
@@ -147,48 +595,162 @@ pub(crate) fn restore_repository<P: ProgressBars, S: IndexedTree>(
 ///
 /// * If a directory could not be created.
 /// * If the restore information could not be collected.
+/// * If the resume state could not be read or belongs to a different snapshot.
+/// * If entries only differ in case on a case-insensitive destination and `on_case_conflict` is `Error`.
+/// * If `strip_prefix` or `add_prefix` contain a `..` component.
 #[allow(clippy::too_many_lines)]
-pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
+pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull, D: RestoreDestination>(
     repo: &Repository<P, S>,
-    opts: RestoreOptions,
-    mut node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
-    dest: &LocalDestination,
+    opts: &RestoreOptions,
+    extra: &RestoreExtraOptions,
+    node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    dest: &D,
     dry_run: bool,
 ) -> RusticResult<RestorePlan> {
+    check_prefix("strip_prefix", extra.strip_prefix.as_deref())?;
+    check_prefix("add_prefix", extra.add_prefix.as_deref())?;
+
     let p = repo.pb.progress_spinner("collecting file information...");
-    let dest_path = dest.path("");
+    let dest_path = dest.path(Path::new(""));
 
     let mut stats = RestoreStats::default();
     let mut restore_infos = RestorePlan::default();
     let mut additional_existing = false;
     let mut removed_dir = None;
+    // files and symlinks within the restored tree, only collected if symlinks are dereferenced
+    let mut files = BTreeMap::new();
+    let mut symlinks = Vec::new();
 
-    let mut process_existing = |entry: &DirEntry| -> RusticResult<_> {
-        if entry.depth() == 0 {
+    // blobs already restored by an interrupted restore are only trusted if the tree digest matches in the end
+    let previous_state = extra
+        .resume_state
+        .as_deref()
+        .map(StateFile::read)
+        .transpose()?
+        .flatten();
+    let done = previous_state.as_ref().map(|state| state.done.as_ref());
+    let mut digest = extra.resume_state.as_ref().map(|_| TreeDigest::default());
+    let node_streamer = filter_by_rules(node_streamer, &extra.filter_rules)?;
+    let mut node_streamer = node_streamer.map(|item| -> RusticResult<_> {
+        let (path, node) = item?;
+        if let Some(digest) = &mut digest {
+            digest.update(&path, &node)?;
+        }
+        Ok((path, node))
+    });
+
+    // apply `strip_prefix` and `add_prefix`
+    let strip_prefix = extra.strip_prefix.as_deref().map(relative_path);
+    let mut skipped_dir: Option<PathBuf> = None;
+    let node_streamer = node_streamer.filter_map(|item| {
+        let (path, node) = match item {
+            Ok(item) => item,
+            Err(err) => return Some(Err(err)),
+        };
+        let prefixed = prefixed_path(&path, extra);
+        // only warn about the topmost skipped entry; parent dirs of the stripped prefix are silently skipped
+        if prefixed.is_none()
+            && !skipped_dir
+                .as_ref()
+                .is_some_and(|dir| path.starts_with(dir))
+            && !strip_prefix
+                .as_ref()
+                .is_some_and(|strip| strip.starts_with(&path))
+        {
+            warn!("skipping {path:?} which is not within the prefix to strip");
+            skipped_dir = Some(path.clone());
+        }
+        prefixed.map(|path| Ok((path, node)))
+    });
+    let node_streamer = map_paths(node_streamer, extra)?;
+
+    // handle entries which only differ in case if the destination is case-insensitive
+    let case_sensitive = dest.is_case_sensitive(dry_run).unwrap_or_else(|err| {
+        warn!(
+            "could not determine the case sensitivity of the destination, assuming it is case-sensitive: {}",
+            err.display_log()
+        );
+        true
+    });
+    let mut case_mapping = CaseMapping::default();
+    let mut case_names = HashSet::new();
+    let mut case_conflicts = 0;
+    let mut node_streamer = node_streamer.filter_map(|item| {
+        let (path, node) = match item {
+            Ok(item) => item,
+            Err(err) => return Some(Err(err)),
+        };
+        if case_sensitive {
+            return Some(Ok((path, node)));
+        }
+        // entries within skipped dirs are skipped, entries within renamed dirs are renamed
+        let mapped = case_mapping.map(&path)?;
+        if case_names.insert(case_folded(&mapped)) {
+            return Some(Ok((mapped, node)));
+        }
+
+        case_conflicts += 1;
+        match opts.on_case_conflict {
+            None => {
+                warn!("{path:?} only differs in case from another entry and overwrites it on the case-insensitive destination");
+                Some(Ok((mapped, node)))
+            }
+            Some(CaseConflictAction::Skip) => {
+                warn!("skipping {path:?} which only differs in case from another entry");
+                case_mapping.skipped.push(path);
+                None
+            }
+            Some(CaseConflictAction::Rename) => {
+                // at most `case_names.len()` of these names are already used
+                let Some(renamed) = (1..=case_names.len() + 1)
+                    .map(|n| case_conflict_name(&mapped, n))
+                    .find(|renamed| !case_names.contains(&case_folded(renamed)))
+                else {
+                    return Some(Err(RusticError::new(
+                        ErrorKind::Internal,
+                        "No free name found to restore `{path}` which only differs in case from another entry.",
+                    )
+                    .attach_context("path", path.display().to_string())));
+                };
+                _ = case_names.insert(case_folded(&renamed));
+                warn!("restoring {path:?} as {renamed:?} as it only differs in case from another entry");
+                case_mapping.renamed.push((path, renamed.clone()));
+                Some(Ok((renamed, node)))
+            }
+            Some(CaseConflictAction::Error) => Some(Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "The entry `{path}` only differs in case from another entry and can't be restored to the case-insensitive destination.",
+            )
+            .attach_context("path", path.display().to_string()))),
+        }
+    });
+
+    let mut process_existing = |entry: &DestinationEntry| -> RusticResult<_> {
+        if entry.path == dest_path {
             // don't process the root dir which should be existing
             return Ok(());
         }
 
-        debug!("additional {:?}", entry.path());
-        if entry.file_type().unwrap().is_dir() {
+        debug!("additional {:?}", entry.path);
+        if entry.is_dir {
             stats.dirs.additional += 1;
         } else {
             stats.files.additional += 1;
         }
-        match (opts.delete, dry_run, entry.file_type().unwrap().is_dir()) {
+        match (opts.delete && !opts.metadata_only, dry_run, entry.is_dir) {
             (true, true, true) => {
-                info!("would have removed the additional dir: {:?}", entry.path());
+                info!("would have removed the additional dir: {:?}", entry.path);
             }
             (true, true, false) => {
-                info!("would have removed the additional file: {:?}", entry.path());
+                info!("would have removed the additional file: {:?}", entry.path);
             }
             (true, false, true) => {
-                let path = entry.path();
+                let path = &entry.path;
                 match &removed_dir {
                     Some(dir) if path.starts_with(dir) => {}
                     _ => match dest.remove_dir(path) {
                         Ok(()) => {
-                            removed_dir = Some(path.to_path_buf());
+                            removed_dir = Some(path.clone());
                         }
                         Err(err) => {
                             error!("error removing {path:?}: {err}");
@@ -197,8 +759,8 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
                 }
             }
             (true, false, false) => {
-                if let Err(err) = dest.remove_file(entry.path()) {
-                    error!("error removing {:?}: {err}", entry.path());
+                if let Err(err) = dest.remove_file(&entry.path) {
+                    error!("error removing {:?}: {err}", entry.path);
                 }
             }
             (false, _, _) => {
@@ -210,6 +772,25 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
     };
 
     let mut process_node = |path: &PathBuf, node: &Node, exists: bool| -> RusticResult<_> {
+        if opts.metadata_only {
+            if exists {
+                if node.is_dir() {
+                    stats.dirs.modify += 1;
+                } else {
+                    stats.files.modify += 1;
+                }
+                trace!("to set metadata: {path:?}");
+            } else if !path
+                .ancestors()
+                .any(|path| restore_infos.missing.contains(path))
+            {
+                // only warn about the topmost missing entry
+                warn!("restore {path:?}: entry is missing, not restoring its metadata");
+                _ = restore_infos.missing.insert(path.clone());
+            }
+            return Ok(());
+        }
+
         match node.node_type {
             NodeType::Dir => {
                 if exists {
@@ -219,23 +800,30 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
                     stats.dirs.restore += 1;
                     debug!("to restore: {path:?}");
                     if !dry_run {
-                        dest.create_dir(path)
-                            .map_err(|err| {
-                                RusticError::with_source(
-                                    ErrorKind::InputOutput,
-                                    "Failed to create the directory `{path}`. Please check the path and try again.",
-                                    err
-                                )
-                                .attach_context("path", path.display().to_string())
-                            })?;
+                        dest.create_dir(path)?;
                     }
                 }
             }
+            NodeType::Symlink { .. } if opts.dereference_symlinks => {
+                if let Some(target) = symlink_target(path, node.node_type.to_link()) {
+                    symlinks.push((path.clone(), target));
+                }
+            }
             NodeType::File => {
+                if opts.dereference_symlinks {
+                    _ = files.insert(path.clone(), node.clone());
+                }
                 // collect blobs needed for restoring
                 match (
                     exists,
-                    restore_infos.add_file(dest, node, path.clone(), repo, opts.verify_existing)?,
+                    restore_infos.add_file(
+                        dest,
+                        node,
+                        path.clone(),
+                        repo,
+                        opts.verify_existing,
+                        done,
+                    )?,
                 ) {
                     // Note that exists = false and Existing or Verified can happen if the file is changed between scanning the dir
                     // and calling add_file. So we don't care about exists but trust add_file here.
@@ -264,18 +852,7 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
         Ok(())
     };
 
-    let mut dst_iter = WalkBuilder::new(dest_path)
-        .follow_links(false)
-        .hidden(false)
-        .ignore(false)
-        .sort_by_file_path(Path::cmp)
-        .build()
-        .inspect(|r| {
-            if let Err(err) = r {
-                error!("Error during collection of files: {err:?}");
-            }
-        })
-        .filter_map(Result::ok);
+    let mut dst_iter = dest.existing_entries();
 
     let mut next_dst = dst_iter.next();
 
@@ -290,16 +867,17 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
                 next_dst = dst_iter.next();
             }
             (Some(destination), Some((path, node))) => {
-                match destination.path().cmp(&dest.path(path)) {
+                match destination.path.cmp(&dest.path(path)) {
                     Ordering::Less => {
                         process_existing(destination)?;
                         next_dst = dst_iter.next();
                     }
                     Ordering::Equal => {
                         // process existing node
-                        if (node.is_dir() && !destination.file_type().unwrap().is_dir())
-                            || (node.is_file() && !destination.metadata().unwrap().is_file())
-                            || node.is_special()
+                        if !opts.metadata_only
+                            && ((node.is_dir() && !destination.is_dir)
+                                || (node.is_file() && !destination.is_file)
+                                || node.is_special())
                         {
                             // if types do not match, first remove the existing file
                             process_existing(destination)?;
@@ -325,12 +903,243 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
         warn!("Note: additional entries exist in destination");
     }
 
+    // restore symlinks pointing to files within the restored tree as copies of these files
+    for (path, target) in symlinks {
+        let Some(file) = files.get(&target) else {
+            continue;
+        };
+        match restore_infos.add_file(dest, file, path.clone(), repo, opts.verify_existing, done)? {
+            AddFileResult::Existing => stats.files.unchanged += 1,
+            AddFileResult::Verified => stats.files.verified += 1,
+            AddFileResult::Modify => {
+                stats.files.restore += 1;
+                debug!("to restore: {path:?} (dereferenced symlink)");
+            }
+        }
+        _ = restore_infos.materialized.insert(path, file.clone());
+    }
+
+    drop(node_streamer);
+    stats.case_conflicts = case_conflicts;
+    restore_infos.case_mapping = case_mapping;
+    if let (Some(path), Some(digest)) = (&extra.resume_state, digest) {
+        restore_infos.resume = Some(ResumeState::new(
+            path.clone(),
+            digest.finalize(),
+            previous_state,
+        )?);
+    }
+
     restore_infos.stats = stats;
+    restore_infos.extra = extra.clone();
     p.finish();
 
     Ok(restore_infos)
 }
 
+/// The changed paths of entries only differing in case on case-insensitive destinations
+#[derive(Debug, Default)]
+struct CaseMapping {
+    /// The original and the new paths of renamed entries
+    renamed: Vec<(PathBuf, PathBuf)>,
+    /// The original paths of skipped entries
+    skipped: Vec<PathBuf>,
+}
+
+impl CaseMapping {
+    /// Map the original path of an entry to the path it is restored to.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The original path of the entry
+    ///
+    /// # Returns
+    ///
+    /// The path to restore to or `None` if the entry is skipped.
+    fn map(&self, path: &Path) -> Option<PathBuf> {
+        if self.skipped.iter().any(|skipped| path.starts_with(skipped)) {
+            return None;
+        }
+        // use the innermost renamed dir
+        let renamed = self
+            .renamed
+            .iter()
+            .filter(|(original, _)| path.starts_with(original))
+            .max_by_key(|(original, _)| original.components().count());
+        Some(match renamed {
+            Some((original, renamed)) => renamed.join(path.strip_prefix(original).ok()?),
+            None => path.to_path_buf(),
+        })
+    }
+}
+
+/// Map the path of an entry to the path it is restored to using `strip_prefix` and `add_prefix`
+///
+/// # Arguments
+///
+/// * `path` - The path of the entry
+/// * `extra` - The additional restore options containing the prefixes
+///
+/// # Returns
+///
+/// The path to restore to or `None` if the entry is not within `strip_prefix` or is the stripped prefix itself.
+fn prefixed_path(path: &Path, extra: &RestoreExtraOptions) -> Option<PathBuf> {
+    let path = match &extra.strip_prefix {
+        Some(strip) => {
+            let path = path.strip_prefix(relative_path(strip)).ok()?;
+            if path.as_os_str().is_empty() {
+                return None;
+            }
+            path
+        }
+        None => path,
+    };
+    Some(match &extra.add_prefix {
+        Some(add) => relative_path(add).join(path),
+        None => path.to_path_buf(),
+    })
+}
+
+/// Apply the path mapper of the options to the entries of the node streamer
+///
+/// As the mapped paths may be in a different order, all entries are collected and sorted by their mapped path.
+/// Without a path mapper, the node streamer is returned unchanged.
+///
+/// # Arguments
+///
+/// * `node_streamer` - The node streamer to map
+/// * `extra` - The additional restore options containing the path mapper
+///
+/// # Errors
+///
+/// * If the node streamer returned an error.
+/// * If a mapped path is not a relative path consisting of normal components only, e.g. contains `..`.
+/// * If two entries are mapped to the same path.
+fn map_paths(
+    node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+    extra: &RestoreExtraOptions,
+) -> RusticResult<impl Iterator<Item = RusticResult<(PathBuf, Node)>>> {
+    let Some(mapper) = &extra.path_mapper else {
+        return Ok(Either::Left(node_streamer));
+    };
+    let mut entries: Vec<_> = node_streamer
+        .map(|item| {
+            let (path, node) = item?;
+            let mapped = mapper.call(&path);
+            if mapped.as_os_str().is_empty()
+                || !mapped
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "The path mapper mapped `{path}` to `{mapped}` which is not a relative path within the destination.",
+                )
+                .attach_context("path", path.display().to_string())
+                .attach_context("mapped", mapped.display().to_string()));
+            }
+            Ok((mapped, node))
+        })
+        .collect::<RusticResult<_>>()?;
+    entries.sort_unstable_by(|(path1, _), (path2, _)| path1.cmp(path2));
+    if let Some(((path, _), _)) = entries
+        .iter()
+        .tuple_windows()
+        .find(|((path1, _), (path2, _))| path1 == path2)
+    {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "The path mapper mapped several entries to `{path}`.",
+        )
+        .attach_context("path", path.display().to_string()));
+    }
+    Ok(Either::Right(entries.into_iter().map(Ok)))
+}
+
+/// Check that a prefix given in the options doesn't point outside the destination
+///
+/// # Arguments
+///
+/// * `option` - The name of the option
+/// * `prefix` - The prefix to check
+///
+/// # Errors
+///
+/// * If the prefix contains a `..` component.
+fn check_prefix(option: &str, prefix: Option<&Path>) -> RusticResult<()> {
+    if let Some(prefix) = prefix {
+        if prefix
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "The `{option}` `{prefix}` must not contain `..`.",
+            )
+            .attach_context("option", option)
+            .attach_context("prefix", prefix.display().to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the path without root dir and prefix, i.e. relative to the snapshot root
+fn relative_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::RootDir | Component::Prefix(_)))
+        .collect()
+}
+
+/// Returns the path in a form which doesn't differ between names only differing in case or in the unicode
+/// normalization form, e.g. NFC and NFD names which are identical on macOS
+fn case_folded(path: &Path) -> String {
+    path.to_string_lossy().to_lowercase().nfc().collect()
+}
+
+/// Returns the path with a disambiguating suffix `~n` added to the file name
+///
+/// # Arguments
+///
+/// * `path` - The path to add the suffix to
+/// * `n` - The number to use in the suffix
+fn case_conflict_name(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push(format!("~{n}"));
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name)
+}
+
+/// Resolve the target of a symlink within the restored tree.
+///
+/// # Arguments
+///
+/// * `path` - The path of the symlink relative to the restore root
+/// * `link` - The target the symlink points to
+///
+/// # Returns
+///
+/// The path of the target relative to the restore root or `None` if the target is absolute or
+/// points outside the restored tree.
+fn symlink_target(path: &Path, link: &Path) -> Option<PathBuf> {
+    let mut target = path.parent()?.to_path_buf();
+    for component in link.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !target.pop() {
+                    return None;
+                }
+            }
+            Component::Normal(name) => target.push(name),
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(target)
+}
+
 /// Restore the metadata of the files and directories.
 ///
 /// # Arguments
@@ -338,14 +1147,16 @@ pub(crate) fn collect_and_prepare<P: ProgressBars, S: IndexedFull>(
 /// * `node_streamer` - The node streamer to use
 /// * `opts` - The restore options to use
 /// * `dest` - The destination to restore to
+/// * `materialized` - The symlinks which have been restored as copies of the given files
 ///
 /// # Errors
 ///
 /// * If the restore failed.
 fn restore_metadata(
     mut node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
-    opts: RestoreOptions,
-    dest: &LocalDestination,
+    opts: &RestoreOptions,
+    dest: &impl RestoreDestination,
+    materialized: &BTreeMap<PathBuf, Node>,
 ) -> RusticResult<()> {
     let mut dir_stack = Vec::new();
     while let Some((path, node)) = node_streamer.next().transpose()? {
@@ -362,6 +1173,15 @@ fn restore_metadata(
                 // push current path to the stack
                 dir_stack.push((path, node));
             }
+            NodeType::Symlink { .. } if opts.dereference_symlinks => {
+                if let Some(file) = materialized.get(&path) {
+                    set_metadata(dest, opts, &path, file);
+                } else if opts.skip_external_symlinks {
+                    debug!("skipping symlink {path:?} which can't be dereferenced");
+                } else {
+                    set_metadata(dest, opts, &path, &node);
+                }
+            }
             _ => set_metadata(dest, opts, &path, &node),
         }
     }
@@ -374,7 +1194,7 @@ fn restore_metadata(
     Ok(())
 }
 
-/// Set the metadata of the given file or directory.
+/// Set the metadata of the given file or directory, logging a warning if this fails.
 ///
 /// # Arguments
 ///
@@ -382,39 +1202,43 @@ fn restore_metadata(
 /// * `opts` - The restore options to use
 /// * `path` - The path of the file or directory
 /// * `node` - The node information of the file or directory
+fn set_metadata(dest: &impl RestoreDestination, opts: &RestoreOptions, path: &Path, node: &Node) {
+    let opts = MetadataOptions {
+        numeric_id: opts.numeric_id,
+        no_ownership: opts.no_ownership,
+    };
+    if let Err(err) = dest.set_metadata(path, node, opts) {
+        warn!("restore {path:?}: setting metadata failed: {err}");
+    }
+}
+
+/// Returns the number of threads to use for reading the file contents
 ///
-/// # Errors
+/// Uses `read_threads` from the options, else `RAYON_NUM_THREADS`, else the number of available
+/// CPUs limited to [`constants::MAX_READER_THREADS_NUM`]. A value of `0` is treated as not set.
+///
+/// # Arguments
 ///
-/// If the metadata could not be set.
-// TODO: Return a result here, introduce errors and get rid of logging.
-pub(crate) fn set_metadata(
-    dest: &LocalDestination,
-    opts: RestoreOptions,
-    path: &PathBuf,
-    node: &Node,
-) {
-    debug!("setting metadata for {:?}", path);
-    dest.create_special(path, node)
-        .unwrap_or_else(|_| warn!("restore {:?}: creating special file failed.", path));
-    match (opts.no_ownership, opts.numeric_id) {
-        (true, _) => {}
-        (false, true) => dest
-            .set_uid_gid(path, &node.meta)
-            .unwrap_or_else(|_| warn!("restore {:?}: setting UID/GID failed.", path)),
-        (false, false) => dest
-            .set_user_group(path, &node.meta)
-            .unwrap_or_else(|_| warn!("restore {:?}: setting User/Group failed.", path)),
-    }
-    dest.set_permission(path, node)
-        .unwrap_or_else(|_| warn!("restore {:?}: chmod failed.", path));
-    dest.set_extended_attributes(path, &node.meta.extended_attributes)
-        .unwrap_or_else(|_| warn!("restore {:?}: setting extended attributes failed.", path));
-    dest.set_times(path, &node.meta)
-        .unwrap_or_else(|_| warn!("restore {:?}: setting file times failed.", path));
+/// * `opts` - The restore options.
+fn reader_threads(opts: &RestoreOptions) -> usize {
+    opts.read_threads
+        .filter(|threads| *threads > 0)
+        .or_else(|| {
+            env::var("RAYON_NUM_THREADS")
+                .ok()?
+                .parse::<usize>()
+                .ok()
+                .filter(|threads| *threads > 0)
+        })
+        .unwrap_or_else(|| {
+            thread::available_parallelism()
+                .map_or(1, NonZeroUsize::get)
+                .min(constants::MAX_READER_THREADS_NUM)
+        })
 }
 
 /// [`restore_contents`] restores all files contents as described by `file_infos`
-/// using the [`DecryptReadBackend`] `be` and writing them into the [`LocalDestination`] `dest`.
+/// using the [`DecryptReadBackend`] `be` and writing them into the [`RestoreDestination`] `dest`.
 ///
 /// # Type Parameters
 ///
@@ -426,16 +1250,23 @@ pub(crate) fn set_metadata(
 /// * `repo` - The repository to restore.
 /// * `dest` - The destination to restore to.
 /// * `file_infos` - The restore information.
+/// * `opts` - The restore options.
+/// * `extra` - The additional restore options containing the callbacks.
+/// * `resume` - The state to record the restored blobs in, if any.
 ///
 /// # Errors
 ///
-/// * If the length of a file could not be set.
-/// * If the restore failed.
+/// * If restoring a file failed and `on_file_error` didn't decide to skip it.
+/// * If the resume state could not be written.
+/// * If the restore has been cancelled.
 #[allow(clippy::too_many_lines)]
-fn restore_contents<P: ProgressBars, S: Open>(
+fn restore_contents<P: ProgressBars, S: Open, D: RestoreDestination>(
     repo: &Repository<P, S>,
-    dest: &LocalDestination,
+    dest: &D,
     file_infos: RestorePlan,
+    opts: &RestoreOptions,
+    extra: &RestoreExtraOptions,
+    resume: Option<&ResumeState>,
 ) -> RusticResult<()> {
     let RestorePlan {
         names: filenames,
@@ -446,21 +1277,22 @@ fn restore_contents<P: ProgressBars, S: Open>(
     } = file_infos;
     let filenames = &filenames;
     let be = repo.dbe();
+    let cancel = &repo.cancel;
+    let progress = &FileProgress::new(extra, filenames, &file_lengths, &restore_info);
 
     // first create needed empty files, as they are not created later.
     for (i, size) in file_lengths.iter().enumerate() {
         if *size == 0 {
             let path = &filenames[i];
-            dest.set_length(path, *size).map_err(|err| {
-                RusticError::with_source(
-                    ErrorKind::InputOutput,
-                    "Failed to set the length of the file `{path}`. Please check the path and try again.",
-                    err,
-                )
-                .attach_context("path", path.display().to_string())
-            })?;
+            if let Err(err) = dest.set_length(path, *size) {
+                progress.handle_error([i], err);
+            }
         }
     }
+    if progress.is_aborted() {
+        return progress.finish();
+    }
+    progress.report_finished();
 
     let sizes = &Mutex::new(file_lengths);
 
@@ -499,7 +1331,8 @@ fn restore_contents<P: ProgressBars, S: Open>(
         })
         .collect();
 
-    let threads = constants::MAX_READER_THREADS_NUM;
+    let threads = reader_threads(opts);
+    debug!("using {threads} reader threads");
 
     let pool = ThreadPoolBuilder::new()
         .num_threads(threads)
@@ -518,49 +1351,72 @@ fn restore_contents<P: ProgressBars, S: Open>(
             let p = &p;
 
             if !name_dests.is_empty() {
-                // TODO: error handling!
                 s.spawn(move |s1| {
+                    if progress.is_aborted() || cancel.is_cancelled() {
+                        return;
+                    }
                     let read_data = match &from_file {
                         Some((file_idx, offset_file, length_file)) => {
                             // read from existing file
-                            dest.read_at(&filenames[*file_idx], *offset_file, *length_file)
-                                .unwrap()
+                            let path = &filenames[*file_idx];
+                            dest.read_at(path, *offset_file, *length_file)
+                                .map(BlobData::File)
                         }
                         None => {
-                            // read needed part of the pack
-                            be.read_partial(FileType::Pack, &pack, false, offset, length)
-                                .unwrap()
+                            // stream needed part of the pack
+                            be.read_partial_streaming(FileType::Pack, &pack, false, offset, length)
+                                .map(|chunks| BlobData::Pack(PackChunkReader::new(chunks, offset)))
+                        }
+                    };
+                    let mut read_data = match read_data {
+                        Ok(read_data) => read_data,
+                        Err(err) => {
+                            progress.handle_error(name_dests.iter().map(|item| item.1), err);
+                            return;
                         }
                     };
 
                     // save into needed files in parallel
                     for (bl, group) in &name_dests.into_iter().chunk_by(|item| item.0.clone()) {
-                        let size = bl.data_length();
-                        let data = if from_file.is_some() {
-                            read_data.clone()
-                        } else {
-                            let start = usize::try_from(bl.offset - offset).unwrap();
-                            let end = usize::try_from(bl.offset + bl.length - offset).unwrap();
-                            be.read_encrypted_from_partial(
-                                &read_data[start..end],
-                                bl.uncompressed_length,
-                            )
-                            .unwrap()
+                        let group: Vec<_> = group
+                            .filter(|(_, file_idx, _)| !progress.is_failed(*file_idx))
+                            .collect();
+                        let data = match &mut read_data {
+                            BlobData::File(data) => Ok(data.clone()),
+                            BlobData::Pack(reader) => {
+                                reader.read(bl.offset, bl.length).and_then(|data| {
+                                    be.read_encrypted_from_partial(&data, bl.uncompressed_length)
+                                })
+                            }
+                        };
+                        let data = match data {
+                            Ok(data) => data,
+                            Err(err) => {
+                                progress.handle_error(group.iter().map(|item| item.1), err);
+                                continue;
+                            }
                         };
+                        let pending = resume
+                            .map(|state| Arc::new(state.pending(pack, bl.clone(), group.len())));
+                        let size = bl.data_length();
                         for (_, file_idx, start) in group {
                             let data = data.clone();
+                            let pending = pending.clone();
                             s1.spawn(move |_| {
+                                if progress.is_aborted() || progress.is_failed(file_idx) {
+                                    return;
+                                }
                                 let path = &filenames[file_idx];
-                                // Allocate file if it is not yet allocated
-                                let mut sizes_guard = sizes.lock().unwrap();
-                                let filesize = sizes_guard[file_idx];
-                                if filesize > 0 {
-                                    dest.set_length(path, filesize).unwrap();
-                                    sizes_guard[file_idx] = 0;
+                                match write_blob(dest, sizes, path, file_idx, start, &data) {
+                                    Ok(()) => {
+                                        p.inc(size);
+                                        if let Some(pending) = pending {
+                                            pending.written();
+                                        }
+                                        progress.written(file_idx);
+                                    }
+                                    Err(err) => progress.handle_error([file_idx], err),
                                 }
-                                drop(sizes_guard);
-                                dest.write_at(path, start, &data).unwrap();
-                                p.inc(size);
                             });
                         }
                     }
@@ -569,9 +1425,250 @@ fn restore_contents<P: ProgressBars, S: Open>(
         }
     });
 
+    if let Some(resume) = resume {
+        resume.save()?;
+    }
+
     p.finish();
 
-    Ok(())
+    cancel.check()?;
+    progress.finish()
+}
+
+/// Write the data of a blob into a file, allocating the file if it is not yet allocated.
+///
+/// # Arguments
+///
+/// * `dest` - The destination to restore to
+/// * `sizes` - The sizes of the files which are not yet allocated, 0 if already allocated
+/// * `path` - The path of the file
+/// * `file_idx` - The index of the file
+/// * `start` - The position of the blob within the file
+/// * `data` - The data to write
+///
+/// # Errors
+///
+/// * If the length of the file could not be set.
+/// * If the data could not be written.
+fn write_blob(
+    dest: &impl RestoreDestination,
+    sizes: &Mutex<Vec<u64>>,
+    path: &Path,
+    file_idx: usize,
+    start: u64,
+    data: &[u8],
+) -> RusticResult<()> {
+    // Allocate file if it is not yet allocated
+    let mut sizes_guard = sizes.lock().unwrap();
+    let filesize = sizes_guard[file_idx];
+    if filesize > 0 {
+        dest.set_length(path, filesize)?;
+        sizes_guard[file_idx] = 0;
+    }
+    drop(sizes_guard);
+
+    dest.write_at(path, start, data)
+}
+
+/// [`FileProgress`] tracks the restore of the single files and handles errors restoring them.
+#[derive(Debug)]
+struct FileProgress<'a> {
+    /// The additional restore options containing the callbacks
+    extra: &'a RestoreExtraOptions,
+    /// The names of the files to restore
+    filenames: &'a Filenames,
+    /// The length of the files to restore
+    lengths: Vec<u64>,
+    /// The number of outstanding writes for each file
+    remaining: Vec<AtomicUsize>,
+    /// Whether restoring the file failed
+    failed: Vec<AtomicBool>,
+    /// Whether the restore has been aborted
+    aborted: AtomicBool,
+    /// The error which aborted the restore
+    error: Mutex<Option<Box<RusticError>>>,
+}
+
+impl<'a> FileProgress<'a> {
+    /// Creates a new [`FileProgress`].
+    ///
+    /// # Arguments
+    ///
+    /// * `extra` - The additional restore options containing the callbacks
+    /// * `filenames` - The names of the files to restore
+    /// * `lengths` - The length of the files to restore
+    /// * `restore_info` - The blobs to restore
+    fn new(
+        extra: &'a RestoreExtraOptions,
+        filenames: &'a Filenames,
+        lengths: &[u64],
+        restore_info: &RestoreInfo,
+    ) -> Self {
+        let mut remaining = vec![0; filenames.len()];
+        for fl in restore_info.values().flatten().filter(|fl| !fl.matches) {
+            remaining[fl.file_idx] += 1;
+        }
+        Self {
+            extra,
+            filenames,
+            lengths: lengths.to_vec(),
+            remaining: remaining.into_iter().map(AtomicUsize::new).collect(),
+            failed: filenames.iter().map(|_| AtomicBool::new(false)).collect(),
+            aborted: AtomicBool::new(false),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// Returns whether the restore has been aborted.
+    fn is_aborted(&self) -> bool {
+        self.aborted.load(atomic::Ordering::Acquire)
+    }
+
+    /// Returns whether restoring the given file failed.
+    fn is_failed(&self, file_idx: usize) -> bool {
+        self.failed[file_idx].load(atomic::Ordering::Acquire)
+    }
+
+    /// Report all files which don't need any write as finished.
+    fn report_finished(&self) {
+        for (file_idx, remaining) in self.remaining.iter().enumerate() {
+            if remaining.load(atomic::Ordering::Acquire) == 0 && !self.is_failed(file_idx) {
+                self.done(file_idx);
+            }
+        }
+    }
+
+    /// Record a finished write to the given file; the file is reported as finished after the last write.
+    fn written(&self, file_idx: usize) {
+        if self.remaining[file_idx].fetch_sub(1, atomic::Ordering::AcqRel) == 1
+            && !self.is_failed(file_idx)
+        {
+            self.done(file_idx);
+        }
+    }
+
+    /// Call the `on_file_done` callback for the given file.
+    fn done(&self, file_idx: usize) {
+        if let Some(on_file_done) = &self.extra.on_file_done {
+            on_file_done.call(&self.filenames[file_idx], self.lengths[file_idx]);
+        }
+    }
+
+    /// Handle an error restoring the given files.
+    ///
+    /// The `on_file_error` callback is consulted for each file which didn't fail before.
+    /// Without callback, the restore is aborted.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The indices of the affected files
+    /// * `err` - The error
+    fn handle_error(&self, files: impl IntoIterator<Item = usize>, err: Box<RusticError>) {
+        let mut abort = false;
+        for file_idx in files {
+            if self.failed[file_idx].swap(true, atomic::Ordering::AcqRel) {
+                // error has already been handled for this file
+                continue;
+            }
+            let path = &self.filenames[file_idx];
+            let action = self
+                .extra
+                .on_file_error
+                .as_ref()
+                .map_or(ErrorAction::Abort, |on_file_error| {
+                    on_file_error.call(path, &err)
+                });
+            match action {
+                ErrorAction::Skip => warn!("restore {path:?}: skipping file after error: {err}"),
+                ErrorAction::Abort => abort = true,
+            }
+        }
+
+        if abort {
+            self.aborted.store(true, atomic::Ordering::Release);
+            let mut error = self.error.lock().unwrap();
+            if error.is_none() {
+                *error = Some(err);
+            }
+        }
+    }
+
+    /// Returns the error which aborted the restore, if any.
+    fn finish(&self) -> RusticResult<()> {
+        self.error.lock().unwrap().take().map_or(Ok(()), Err)
+    }
+}
+
+/// The source of the blob data to restore
+enum BlobData<'a> {
+    /// Data which is already present in an existing file
+    File(Bytes),
+    /// Data which is streamed from a pack
+    Pack(PackChunkReader<'a>),
+}
+
+/// [`PackChunkReader`] reads consecutive blobs from a stream of pack chunks.
+///
+/// Only the currently needed blob and the remainder of the last chunk are held in memory.
+struct PackChunkReader<'a> {
+    /// The chunks of the pack part
+    chunks: PartialChunks<'a>,
+    /// Data read from `chunks`, but not yet consumed
+    buf: BytesMut,
+    /// The position in the pack where `buf` starts
+    pos: u32,
+}
+
+impl<'a> PackChunkReader<'a> {
+    /// Creates a new [`PackChunkReader`].
+    ///
+    /// # Arguments
+    ///
+    /// * `chunks` - The chunks of the pack part
+    /// * `offset` - The position in the pack where the chunks start
+    fn new(chunks: PartialChunks<'a>, offset: u32) -> Self {
+        Self {
+            chunks,
+            buf: BytesMut::new(),
+            pos: offset,
+        }
+    }
+
+    /// Reads the data at the given position in the pack.
+    ///
+    /// Data before `offset` which has not been read yet is skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - The position in the pack to read from. Must not be before an already read position.
+    /// * `length` - The length to read
+    ///
+    /// # Errors
+    ///
+    /// * If reading a chunk failed.
+    /// * If the chunks ended before the requested data could be read.
+    fn read(&mut self, offset: u32, length: u32) -> RusticResult<Bytes> {
+        let mut skip = (offset - self.pos) as usize;
+        let len = length as usize;
+        loop {
+            let n = skip.min(self.buf.len());
+            self.buf.advance(n);
+            skip -= n;
+            if skip == 0 && self.buf.len() >= len {
+                self.pos = offset + length;
+                return Ok(self.buf.split_to(len).freeze());
+            }
+            let chunk = self.chunks.next().ok_or_else(|| {
+                RusticError::new(
+                    ErrorKind::Backend,
+                    "Pack data ended before reading `{length}` bytes at offset `{offset}`. Please check the backend and try again.",
+                )
+                .attach_context("offset", offset.to_string())
+                .attach_context("length", length.to_string())
+            })??;
+            self.buf.extend_from_slice(&chunk);
+        }
+    }
 }
 
 /// Information about what will be restored.
@@ -595,10 +1692,20 @@ pub struct RestorePlan {
     pub matched_size: u64,
     /// Statistics about the restore.
     pub stats: RestoreStats,
+    /// The state to record the restore progress in
+    resume: Option<ResumeState>,
+    /// The symlinks which are restored as copies of the contained files
+    materialized: BTreeMap<PathBuf, Node>,
+    /// The changed paths of entries only differing in case
+    case_mapping: CaseMapping,
+    /// The entries missing in the destination when only restoring metadata
+    missing: BTreeSet<PathBuf>,
+    /// The additional restore options used for the restore
+    extra: RestoreExtraOptions,
 }
 
 /// `BlobLocation` contains information about a blob within a pack
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 struct BlobLocation {
     /// The offset of the blob within the pack
     offset: u32,
@@ -656,62 +1763,30 @@ impl RestorePlan {
     /// * `name` - The name of the file.
     /// * `repo` - The repository to restore.
     /// * `ignore_mtime` - If true, ignore the modification time of the file.
+    /// * `done` - The blobs which have already been restored by an interrupted restore, if any.
     ///
     /// # Errors
     ///
     /// * If the file could not be added.
     fn add_file<P, S: IndexedFull>(
         &mut self,
-        dest: &LocalDestination,
+        dest: &impl RestoreDestination,
         file: &Node,
         name: PathBuf,
         repo: &Repository<P, S>,
         ignore_mtime: bool,
+        done: Option<&DoneBlobs>,
     ) -> RusticResult<AddFileResult> {
-        let mut open_file = dest.get_matching_file(&name, file.meta.size);
+        let mut open_file = dest.get_matching_file(&name, file.meta.size)?;
 
         // Empty files which exists with correct size should always return Ok(Existing)!
-        if file.meta.size == 0 {
-            if let Some(meta) = open_file
-                .as_ref()
-                .map(std::fs::File::metadata)
-                .transpose()
-                .map_err(|err|
-                    RusticError::with_source(
-                        ErrorKind::InputOutput,
-                        "Failed to get the metadata of the file `{path}`. Please check the path and try again.",
-                        err
-                    )
-                    .attach_context("path", name.display().to_string())
-                )?
-            {
-                if meta.len() == 0 {
-                    // Empty file exists
-                    return Ok(AddFileResult::Existing);
-                }
-            }
+        if file.meta.size == 0 && open_file.is_some() {
+            return Ok(AddFileResult::Existing);
         }
 
         if !ignore_mtime {
-            if let Some(meta) = open_file
-                .as_ref()
-                .map(std::fs::File::metadata)
-                .transpose()
-                .map_err(|err|
-                    RusticError::with_source(
-                        ErrorKind::InputOutput,
-                        "Failed to get the metadata of the file `{path}`. Please check the path and try again.",
-                        err
-                    )
-                    .attach_context("path", name.display().to_string())
-                )?
-            {
-                // TODO: This is the same logic as in backend/ignore.rs => consolidate!
-                let mtime = meta
-                    .modified()
-                    .ok()
-                    .map(|t| DateTime::<Utc>::from(t).with_timezone(&Local));
-                if meta.len() == file.meta.size && mtime == file.meta.mtime {
+            if let Some(existing) = &open_file {
+                if existing.mtime == file.meta.mtime {
                     // File exists with fitting mtime => we suspect this file is ok!
                     debug!("file {name:?} exists with suitable size and mtime, accepting it!");
                     self.matched_size += file.meta.size;
@@ -742,9 +1817,16 @@ impl RestorePlan {
                 .attach_context("length", length.to_string())
             })?;
 
-            let matches = open_file
-                .as_mut()
-                .map_or(false, |file| id.blob_matches_reader(usize_length, file));
+            let restored = done.is_some_and(|done| done.contains(&(ie.pack, bl.clone())));
+            let matches = open_file.as_mut().map_or(false, |existing| {
+                if restored {
+                    // already restored by an interrupted restore, so skip the content
+                    i64::try_from(length)
+                        .is_ok_and(|length| existing.file.seek(SeekFrom::Current(length)).is_ok())
+                } else {
+                    id.blob_matches_reader(usize_length, &mut existing.file)
+                }
+            });
 
             let blob_location = self.r.entry((ie.pack, bl)).or_default();
             blob_location.push(FileLocation {
@@ -786,3 +1868,253 @@ impl RestorePlan {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn chunks(data: &'static [u8], chunk_size: usize) -> PartialChunks<'static> {
+        Box::new(data.chunks(chunk_size).map(|c| Ok(Bytes::from_static(c))))
+    }
+
+    #[test]
+    fn reader_threads_uses_given_number() {
+        assert_eq!(
+            reader_threads(&RestoreOptions::default().read_threads(Some(3))),
+            3
+        );
+        let default = reader_threads(&RestoreOptions::default());
+        assert!(default > 0);
+        assert_eq!(
+            reader_threads(&RestoreOptions::default().read_threads(Some(0))),
+            default
+        );
+    }
+
+    #[rstest]
+    #[case(None, None, "home/user/project/file", Some("home/user/project/file"))]
+    #[case(
+        Some("/home/user"),
+        None,
+        "home/user/project/file",
+        Some("project/file")
+    )]
+    #[case(Some("home/user"), None, "home/user/project", Some("project"))]
+    #[case(Some("/home/user"), None, "home/user", None)]
+    #[case(Some("/home/user"), None, "home", None)]
+    #[case(Some("/home/user"), None, "home/other/file", None)]
+    #[case(
+        None,
+        Some("/tmp/restore"),
+        "project/file",
+        Some("tmp/restore/project/file")
+    )]
+    #[case(
+        Some("/home/user"),
+        Some("restored"),
+        "home/user/project",
+        Some("restored/project")
+    )]
+    #[case(Some("/home/user"), Some("restored"), "srv/file", None)]
+    fn prefixed_path_strips_and_adds_prefixes(
+        #[case] strip: Option<&str>,
+        #[case] add: Option<&str>,
+        #[case] path: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let extra = RestoreExtraOptions::default()
+            .strip_prefix(strip.map(PathBuf::from))
+            .add_prefix(add.map(PathBuf::from));
+        assert_eq!(
+            prefixed_path(Path::new(path), &extra),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[rstest]
+    #[case("../x")]
+    #[case("/home/../..")]
+    #[case("restored/..")]
+    fn check_prefix_rejects_parent_dirs(#[case] prefix: &str) {
+        assert!(check_prefix("add_prefix", Some(Path::new(prefix))).is_err());
+    }
+
+    #[rstest]
+    #[case(None)]
+    #[case(Some("/home/user"))]
+    #[case(Some("./restored"))]
+    fn check_prefix_accepts_normal_paths(#[case] prefix: Option<&str>) {
+        assert!(check_prefix("add_prefix", prefix.map(Path::new)).is_ok());
+    }
+
+    fn map_test_paths(
+        paths: &[&str],
+        mapper: impl Fn(&Path) -> PathBuf + Send + Sync + 'static,
+    ) -> RusticResult<Vec<PathBuf>> {
+        let node_streamer = paths.iter().map(|path| {
+            let node = Node::new_node(
+                std::ffi::OsStr::new(""),
+                NodeType::File,
+                crate::backend::node::Metadata::default(),
+            );
+            Ok((PathBuf::from(path), node))
+        });
+        let extra = RestoreExtraOptions::default().path_mapper(mapper);
+        map_paths(node_streamer, &extra)?
+            .map_ok(|(path, _)| path)
+            .collect()
+    }
+
+    #[test]
+    fn map_paths_sorts_mapped_paths() -> RusticResult<()> {
+        let mapped = map_test_paths(&["a", "b"], |path| {
+            Path::new("x").join(path).with_extension("y")
+        })?;
+        assert_eq!(mapped, vec![PathBuf::from("x/a.y"), PathBuf::from("x/b.y")]);
+        let mapped = map_test_paths(&["a", "b"], |path| {
+            PathBuf::from(if path == Path::new("a") { "z" } else { "y" })
+        })?;
+        assert_eq!(mapped, vec![PathBuf::from("y"), PathBuf::from("z")]);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("/etc/passwd")]
+    #[case("../outside")]
+    #[case("dir/../../outside")]
+    #[case("./file")]
+    #[case("")]
+    fn map_paths_rejects_paths_outside_destination(#[case] target: &'static str) {
+        assert!(map_test_paths(&["a"], move |_| PathBuf::from(target)).is_err());
+    }
+
+    #[test]
+    fn map_paths_rejects_duplicate_paths() {
+        assert!(map_test_paths(&["a", "b", "c"], |_| PathBuf::from("same")).is_err());
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(3)]
+    #[case(100)]
+    fn pack_chunk_reader_reads_across_chunks(#[case] chunk_size: usize) {
+        let mut reader = PackChunkReader::new(chunks(b"0123456789", chunk_size), 10);
+        assert_eq!(reader.read(10, 4).unwrap(), b"0123"[..]);
+        // skip "45"
+        assert_eq!(reader.read(16, 3).unwrap(), b"678"[..]);
+        assert!(reader.read(19, 2).is_err());
+    }
+
+    fn file_progress_info() -> RestoreInfo {
+        let bl = BlobLocation {
+            offset: 0,
+            length: 42,
+            uncompressed_length: None,
+        };
+        let fl = |file_idx| FileLocation {
+            file_idx,
+            file_start: 0,
+            matches: false,
+        };
+        BTreeMap::from([((PackId::default(), bl), vec![fl(0), fl(1)])])
+    }
+
+    #[test]
+    fn file_progress_aborts_without_callback() {
+        let extra = RestoreExtraOptions::default();
+        let filenames = vec![PathBuf::from("a"), PathBuf::from("b")];
+        let progress = FileProgress::new(&extra, &filenames, &[10, 10], &file_progress_info());
+
+        progress.handle_error([0], RusticError::new(ErrorKind::InputOutput, "error"));
+        assert!(progress.is_aborted());
+        assert!(progress.finish().is_err());
+    }
+
+    #[test]
+    fn file_progress_skips_and_reports_done_files() {
+        let done = Arc::new(Mutex::new(Vec::new()));
+        let done_clone = done.clone();
+        let extra = RestoreExtraOptions::default()
+            .on_file_error(|_, _| ErrorAction::Skip)
+            .on_file_done(move |path, size| {
+                done_clone.lock().unwrap().push((path.to_path_buf(), size))
+            });
+        let filenames = vec![PathBuf::from("a"), PathBuf::from("b")];
+        let progress = FileProgress::new(&extra, &filenames, &[10, 20], &file_progress_info());
+
+        progress.handle_error([0], RusticError::new(ErrorKind::InputOutput, "error"));
+        assert!(!progress.is_aborted());
+        assert!(progress.is_failed(0));
+
+        progress.written(1);
+        assert!(progress.finish().is_ok());
+        assert_eq!(*done.lock().unwrap(), vec![(PathBuf::from("b"), 20)]);
+    }
+
+    #[rstest]
+    #[case("dir/link", "file", Some("dir/file"))]
+    #[case("dir/link", "./sub/../file", Some("dir/file"))]
+    #[case("dir/link", "../file", Some("file"))]
+    #[case("dir/link", "../../file", None)]
+    #[case("dir/link", "/dir/file", None)]
+    fn symlink_target_is_resolved_within_tree(
+        #[case] path: &str,
+        #[case] link: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            symlink_target(Path::new(path), Path::new(link)),
+            expected.map(PathBuf::from)
+        );
+    }
+
+    #[rstest]
+    #[case("dir/Foo", "dir/Foo~1")]
+    #[case("dir/Foo.txt", "dir/Foo~1.txt")]
+    #[case(".bashrc", ".bashrc~1")]
+    fn case_conflict_name_adds_suffix(#[case] path: &str, #[case] expected: &str) {
+        assert_eq!(
+            case_conflict_name(Path::new(path), 1),
+            PathBuf::from(expected)
+        );
+    }
+
+    #[test]
+    fn case_folded_ignores_case_and_normalization() {
+        assert_eq!(
+            case_folded(Path::new("Dir/FILE")),
+            case_folded(Path::new("dir/file"))
+        );
+        // "é" in NFC and in NFD
+        assert_eq!(
+            case_folded(Path::new("caf\u{e9}")),
+            case_folded(Path::new("CAFE\u{301}"))
+        );
+        assert_ne!(
+            case_folded(Path::new("cafe")),
+            case_folded(Path::new("caf\u{e9}"))
+        );
+    }
+
+    #[test]
+    fn case_mapping_maps_contents_of_renamed_and_skipped_dirs() {
+        let mapping = CaseMapping {
+            renamed: vec![
+                (PathBuf::from("Foo"), PathBuf::from("Foo~1")),
+                (PathBuf::from("Foo/Bar"), PathBuf::from("Foo~1/Bar~1")),
+            ],
+            skipped: vec![PathBuf::from("Baz")],
+        };
+        assert_eq!(mapping.map(Path::new("foo")), Some(PathBuf::from("foo")));
+        assert_eq!(
+            mapping.map(Path::new("Foo/file")),
+            Some(PathBuf::from("Foo~1/file"))
+        );
+        assert_eq!(
+            mapping.map(Path::new("Foo/Bar/file")),
+            Some(PathBuf::from("Foo~1/Bar~1/file"))
+        );
+        assert_eq!(mapping.map(Path::new("Baz/file")), None);
+    }
+}
@@ -0,0 +1,337 @@
+//! State of a restore which allows to resume an interrupted restore
+
+use std::{
+    borrow::Cow,
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{BufReader, ErrorKind as IoErrorKind},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, warn};
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    backend::node::Node,
+    commands::restore::BlobLocation,
+    error::{ErrorKind, RusticError, RusticResult},
+    id::Id,
+    repofile::packfile::PackId,
+};
+
+/// The minimum time between two writes of the state file
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The blobs which have already been restored
+pub(super) type DoneBlobs = BTreeSet<(PackId, BlobLocation)>;
+
+/// The content of the state file
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct StateFile<'a> {
+    /// The digest of the restored tree, see [`TreeDigest`]
+    pub(super) tree: Id,
+    /// The blobs which have already been restored
+    pub(super) done: Cow<'a, DoneBlobs>,
+}
+
+impl StateFile<'static> {
+    /// Read the state file, if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the state file
+    ///
+    /// # Errors
+    ///
+    /// * If the state file exists, but could not be read or parsed.
+    pub(super) fn read(path: &Path) -> RusticResult<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(RusticError::with_source(
+                    ErrorKind::InputOutput,
+                    "Failed to open the restore state file `{path}`. Please check the path and try again.",
+                    err,
+                )
+                .attach_context("path", path.display().to_string()));
+            }
+        };
+
+        let state: Self = serde_json::from_reader(BufReader::new(file)).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InvalidInput,
+                "Failed to parse the restore state file `{path}`. Please remove the file to start a new restore.",
+                err,
+            )
+            .attach_context("path", path.display().to_string())
+        })?;
+        debug!(
+            "resuming restore, {} blobs have already been restored",
+            state.done.len()
+        );
+        Ok(Some(state))
+    }
+}
+
+/// [`TreeDigest`] computes an id identifying the tree to restore from all nodes to restore.
+///
+/// This is used to refuse resuming a restore of a different snapshot.
+#[derive(Debug, Default)]
+pub(super) struct TreeDigest(Sha256);
+
+impl TreeDigest {
+    /// Add a node to the digest.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the node
+    /// * `node` - The node
+    ///
+    /// # Errors
+    ///
+    /// * If the node could not be serialized.
+    pub(super) fn update(&mut self, path: &Path, node: &Node) -> RusticResult<()> {
+        let node = serde_json::to_vec(node).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Failed to serialize the node `{path}`.",
+                err,
+            )
+            .attach_context("path", path.display().to_string())
+        })?;
+        self.0.update(path.to_string_lossy().as_bytes());
+        self.0.update(node);
+        Ok(())
+    }
+
+    /// Get the resulting id.
+    pub(super) fn finalize(self) -> Id {
+        Id::new(self.0.finalize().into())
+    }
+}
+
+/// [`ResumeState`] records the blobs which have been restored to a state file.
+#[derive(Debug)]
+pub(super) struct ResumeState {
+    /// The path of the state file
+    path: PathBuf,
+    /// The digest of the restored tree
+    tree: Id,
+    /// The blobs which have already been restored
+    done: Mutex<DoneBlobs>,
+    /// The time the state file has been written last
+    last_save: Mutex<Instant>,
+}
+
+impl ResumeState {
+    /// Create a new [`ResumeState`].
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the state file
+    /// * `tree` - The digest of the restored tree
+    /// * `previous` - The state read from the state file, if present
+    ///
+    /// # Errors
+    ///
+    /// * If the previous state belongs to a different tree.
+    pub(super) fn new(
+        path: PathBuf,
+        tree: Id,
+        previous: Option<StateFile<'static>>,
+    ) -> RusticResult<Self> {
+        let done = match previous {
+            Some(state) if state.tree != tree => {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "The restore state file `{path}` belongs to a restore of a different snapshot. Please remove the file or use another state file.",
+                )
+                .attach_context("path", path.display().to_string()));
+            }
+            Some(state) => state.done.into_owned(),
+            None => DoneBlobs::new(),
+        };
+
+        Ok(Self {
+            path,
+            tree,
+            done: Mutex::new(done),
+            last_save: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// Create a [`PendingBlob`] which marks the given blob as restored once all its writes are finished.
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - The pack containing the blob
+    /// * `blob` - The location of the blob within the pack
+    /// * `writes` - The number of writes needed to restore the blob
+    pub(super) fn pending(
+        &self,
+        pack: PackId,
+        blob: BlobLocation,
+        writes: usize,
+    ) -> PendingBlob<'_> {
+        PendingBlob {
+            state: self,
+            key: (pack, blob),
+            remaining: AtomicUsize::new(writes),
+        }
+    }
+
+    /// Mark the given blob as restored.
+    ///
+    /// The state file is only written if the last write is long enough ago.
+    /// Errors are only logged, as they don't affect the restore itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The pack and location of the blob
+    fn add(&self, key: (PackId, BlobLocation)) {
+        let mut done = self.done.lock().unwrap();
+        _ = done.insert(key);
+
+        let mut last_save = self.last_save.lock().unwrap();
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            if let Err(err) = self.write(&done) {
+                warn!("error writing restore state: {err}");
+            }
+            *last_save = Instant::now();
+        }
+    }
+
+    /// Write the state file.
+    ///
+    /// # Errors
+    ///
+    /// * If the state file could not be written.
+    pub(super) fn save(&self) -> RusticResult<()> {
+        self.write(&self.done.lock().unwrap())
+    }
+
+    /// Remove the state file after the restore has been finished.
+    ///
+    /// # Errors
+    ///
+    /// * If the state file exists, but could not be removed.
+    pub(super) fn remove(&self) -> RusticResult<()> {
+        match fs::remove_file(&self.path) {
+            Err(err) if err.kind() != IoErrorKind::NotFound => Err(RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to remove the restore state file `{path}`.",
+                err,
+            )
+            .attach_context("path", self.path.display().to_string())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Atomically write the state file by writing a temporary file and renaming it.
+    ///
+    /// # Arguments
+    ///
+    /// * `done` - The blobs which have already been restored
+    ///
+    /// # Errors
+    ///
+    /// * If the temporary file could not be written or renamed.
+    fn write(&self, done: &DoneBlobs) -> RusticResult<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let io_err = |err: std::io::Error| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to write the restore state file `{path}`. Please check the path and try again.",
+                err,
+            )
+            .attach_context("path", self.path.display().to_string())
+        };
+
+        let state = StateFile {
+            tree: self.tree,
+            done: Cow::Borrowed(done),
+        };
+        let mut file = File::create(&tmp_path).map_err(io_err)?;
+        serde_json::to_writer(&mut file, &state).map_err(|err| io_err(err.into()))?;
+        file.sync_all().map_err(io_err)?;
+        fs::rename(&tmp_path, &self.path).map_err(io_err)?;
+        Ok(())
+    }
+}
+
+/// [`PendingBlob`] tracks the outstanding writes of a blob to restore.
+#[derive(Debug)]
+pub(super) struct PendingBlob<'a> {
+    /// The state to record the blob in
+    state: &'a ResumeState,
+    /// The pack and location of the blob
+    key: (PackId, BlobLocation),
+    /// The number of outstanding writes
+    remaining: AtomicUsize,
+}
+
+impl PendingBlob<'_> {
+    /// Record a finished write; the blob is marked as restored after the last write.
+    pub(super) fn written(&self) {
+        if self.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.state.add(self.key.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(offset: u32) -> (PackId, BlobLocation) {
+        (
+            PackId::default(),
+            BlobLocation {
+                offset,
+                length: 42,
+                uncompressed_length: None,
+            },
+        )
+    }
+
+    #[test]
+    fn resume_state_roundtrip_passes() -> RusticResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state");
+        let tree = Id::random();
+
+        assert!(StateFile::read(&path)?.is_none());
+
+        let state = ResumeState::new(path.clone(), tree, None)?;
+        let (pack, bl) = blob(0);
+        let pending = state.pending(pack, bl, 2);
+        pending.written();
+        state.save()?;
+        // not all writes are finished
+        assert!(StateFile::read(&path)?.unwrap().done.is_empty());
+
+        pending.written();
+        state.save()?;
+        let previous = StateFile::read(&path)?.unwrap();
+        assert_eq!(previous.done.iter().collect::<Vec<_>>(), vec![&blob(0)]);
+
+        // resuming a different tree is refused
+        let other = StateFile::read(&path)?;
+        assert!(ResumeState::new(path.clone(), Id::random(), other).is_err());
+
+        let state = ResumeState::new(path.clone(), tree, Some(previous))?;
+        state.remove()?;
+        assert!(StateFile::read(&path)?.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+//! `rewrite` subcommand: relocate paths within a snapshot
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use chrono::Local;
+use log::info;
+
+use crate::{
+    backend::{
+        decrypt::{DecryptReadBackend, DecryptWriteBackend},
+        node::{escape_filename, Metadata, Node, NodeType},
+    },
+    blob::{
+        packer::Packer,
+        tree::{comp_to_osstr, Tree, TreeId},
+        BlobId, BlobType,
+    },
+    error::{ErrorKind, RusticError, RusticResult},
+    index::{indexer::Indexer, ReadGlobalIndex, ReadIndex},
+    progress::{Progress, ProgressBars},
+    repofile::{snapshotfile::SnapshotOptions, SnapshotFile, SnapshotSummary},
+    repository::{IndexedTree, Repository, Writable},
+};
+
+/// A path within a snapshot given by its components
+type Components = Vec<OsString>;
+
+/// Relocate paths within a snapshot and save the result as new snapshot.
+///
+/// Only the trees along the given source and destination paths are newly written;
+/// all other tree blobs and all data blobs are reused.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to rewrite
+/// * `mappings` - The paths to relocate as `(source, destination)`
+/// * `opts` - The options to create the new snapshot from
+///
+/// # Errors
+///
+/// * If no mappings are given or a path is the root or contains `.` or `..`.
+/// * If sources or destinations overlap with each other.
+/// * If a source doesn't exist in the snapshot.
+/// * If a destination already exists or its parent is not a directory.
+/// * If the snapshot could not be created from the options or could not be saved.
+///
+/// # Returns
+///
+/// The newly saved snapshot
+pub(crate) fn rewrite_snapshot_paths<P: ProgressBars, S: IndexedTree + Writable>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+    mappings: &[(PathBuf, PathBuf)],
+    opts: &SnapshotOptions,
+) -> RusticResult<SnapshotFile> {
+    if mappings.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::MissingInput,
+            "No path mappings given. Please specify at least one path to relocate.",
+        ));
+    }
+
+    let mappings = mappings
+        .iter()
+        .map(|(src, dst)| Ok((components(src)?, components(dst)?)))
+        .collect::<RusticResult<Vec<_>>>()?;
+    check_collisions(&mappings)?;
+
+    let be = repo.dbe();
+    let index = repo.index();
+    let mut removals = Vec::new();
+    let mut insertions = Vec::new();
+    for (src, dst) in &mappings {
+        let node = find_node(be, index, snap.tree, src)?.ok_or_else(|| {
+            RusticError::new(
+                ErrorKind::InvalidInput,
+                "Source path `{path}` does not exist in snapshot `{snapshot}`.",
+            )
+            .attach_context("path", display(src))
+            .attach_context("snapshot", snap.id.to_string())
+        })?;
+        removals.push(src.as_slice());
+        insertions.push((dst.as_slice(), node));
+    }
+
+    let now = Local::now();
+    let mut summary = SnapshotSummary {
+        backup_start: now,
+        ..Default::default()
+    };
+
+    let indexer = Indexer::new(be.clone()).into_shared();
+    let packer = Packer::new(
+        be.clone(),
+        BlobType::Tree,
+        indexer.clone(),
+        repo.config(),
+        index.total_size(BlobType::Tree),
+    )?;
+
+    let p = repo.pb.progress_spinner("rewriting trees...");
+    let tree = rewrite_tree(
+        be,
+        index,
+        &packer,
+        Some(snap.tree),
+        Path::new(""),
+        &removals,
+        &insertions,
+    )?;
+    let stats = packer.finalize()?;
+    indexer.write().unwrap().finalize()?;
+    p.finish();
+    stats.apply(&mut summary, BlobType::Tree);
+
+    summary.finalize(now).map_err(|err| {
+        RusticError::with_source(ErrorKind::Internal, "Failed to finalize summary.", err)
+    })?;
+
+    let mut new_snap = SnapshotFile::from_options(opts)?;
+    new_snap.tree = tree;
+    new_snap.summary = Some(summary);
+    new_snap.original = Some(snap.original.unwrap_or(snap.id));
+    let paths: Vec<_> = snap
+        .paths
+        .iter()
+        .map(|path| relocate_path(Path::new(path), &mappings))
+        .collect();
+    new_snap.paths.set_paths(&paths).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::Internal,
+            "Failed to set paths `{paths}` in snapshot.",
+            err,
+        )
+        .attach_context("paths", snap.paths.to_string())
+    })?;
+
+    new_snap.id = be.save_file(&new_snap)?.into();
+    info!(
+        "saved snapshot {} with relocated paths of snapshot {}",
+        new_snap.id, snap.id
+    );
+    Ok(new_snap)
+}
+
+/// Split a path within a snapshot into its components
+///
+/// # Errors
+///
+/// * If the path contains `.` or `..` or is the root.
+fn components(path: &Path) -> RusticResult<Components> {
+    let comps = path
+        .components()
+        .filter_map(|comp| comp_to_osstr(comp).transpose())
+        .collect::<Result<Components, _>>()
+        .map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InvalidInput,
+                "Path `{path}` is not a valid path within a snapshot.",
+                err,
+            )
+            .attach_context("path", path.display().to_string())
+        })?;
+
+    if comps.is_empty() {
+        return Err(RusticError::new(
+            ErrorKind::InvalidInput,
+            "Path `{path}` is the root of the snapshot which can't be relocated.",
+        )
+        .attach_context("path", path.display().to_string()));
+    }
+    Ok(comps)
+}
+
+/// Display path components as path
+fn display(comps: &[OsString]) -> String {
+    comps.iter().collect::<PathBuf>().display().to_string()
+}
+
+/// Check that no two of the given paths overlap, i.e. no path is equal to or contained in another one.
+///
+/// Sources must not overlap with each other, destinations must not overlap with each other and
+/// no destination may be located within a source.
+///
+/// # Errors
+///
+/// * If two paths overlap.
+fn check_collisions(mappings: &[(Components, Components)]) -> RusticResult<()> {
+    let collision = |first: &[OsString], second: &[OsString]| {
+        RusticError::new(
+            ErrorKind::InvalidInput,
+            "The paths `{first}` and `{second}` collide. Please use non-overlapping paths.",
+        )
+        .attach_context("first", display(first))
+        .attach_context("second", display(second))
+    };
+
+    for (i, (src1, dst1)) in mappings.iter().enumerate() {
+        for (src2, dst2) in &mappings[i + 1..] {
+            if src1.starts_with(src2) || src2.starts_with(src1) {
+                return Err(collision(src1, src2));
+            }
+            if dst1.starts_with(dst2) || dst2.starts_with(dst1) {
+                return Err(collision(dst1, dst2));
+            }
+        }
+        for (src, _) in mappings {
+            if dst1.starts_with(src) {
+                return Err(collision(src, dst1));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the node at the given path
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+///
+/// # Returns
+///
+/// The node or `None` if the path doesn't exist.
+fn find_node(
+    be: &impl DecryptReadBackend,
+    index: &impl ReadGlobalIndex,
+    tree: TreeId,
+    path: &[OsString],
+) -> RusticResult<Option<Node>> {
+    let mut tree = Some(tree);
+    let mut node = None;
+    for name in path {
+        let Some(id) = tree else {
+            return Ok(None);
+        };
+        node = Tree::from_backend(be, index, id)?
+            .nodes
+            .into_iter()
+            .find(|node| node.name() == *name);
+        let Some(found) = &node else {
+            return Ok(None);
+        };
+        tree = found.subtree;
+    }
+    Ok(node)
+}
+
+/// Rewrite the given tree by removing and inserting nodes and save all modified trees.
+///
+/// # Arguments
+///
+/// * `be` - The backend to use
+/// * `index` - The index to use
+/// * `packer` - The packer to save modified trees
+/// * `id` - The tree to rewrite or `None` to create a new tree
+/// * `path` - The path of the tree, used for error messages
+/// * `removals` - The paths to remove, relative to this tree
+/// * `insertions` - The nodes to insert at the given paths, relative to this tree
+///
+/// # Errors
+///
+/// * If a destination already exists or its parent is not a directory.
+/// * If a tree could not be read or saved.
+///
+/// # Returns
+///
+/// The id of the rewritten tree
+fn rewrite_tree<BE: DecryptWriteBackend>(
+    be: &impl DecryptReadBackend,
+    index: &impl ReadGlobalIndex,
+    packer: &Packer<BE>,
+    id: Option<TreeId>,
+    path: &Path,
+    removals: &[&[OsString]],
+    insertions: &[(&[OsString], Node)],
+) -> RusticResult<TreeId> {
+    let old_tree = match id {
+        Some(id) => Tree::from_backend(be, index, id)?,
+        None => Tree::new(),
+    };
+
+    let mut tree = Tree::new();
+    let mut handled = Vec::new();
+    for mut node in old_tree {
+        let name = node.name();
+        let node_path = path.join(&name);
+        let removed = removals.iter().any(|p| p.len() == 1 && p[0] == name);
+        if !removed && insertions.iter().any(|(p, _)| p.len() == 1 && p[0] == name) {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Destination path `{path}` already exists in the snapshot.",
+            )
+            .attach_context("path", node_path.display().to_string()));
+        }
+
+        let sub_removals: Vec<_> = removals.iter().filter_map(|p| below(p, &name)).collect();
+        let sub_insertions: Vec<_> = insertions
+            .iter()
+            .filter_map(|(p, node)| Some((below(p, &name)?, node.clone())))
+            .collect();
+        handled.push(name);
+
+        if removed {
+            continue;
+        }
+        if !sub_removals.is_empty() || !sub_insertions.is_empty() {
+            if !node.is_dir() {
+                return Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Path `{path}` is not a directory.",
+                )
+                .attach_context("path", node_path.display().to_string()));
+            }
+            node.subtree = Some(rewrite_tree(
+                be,
+                index,
+                packer,
+                node.subtree,
+                &node_path,
+                &sub_removals,
+                &sub_insertions,
+            )?);
+        }
+        tree.add(node);
+    }
+
+    let mut new_dirs = Vec::new();
+    for (p, node) in insertions {
+        if handled.contains(&p[0]) && !removals.iter().any(|r| r.len() == 1 && r[0] == p[0]) {
+            continue;
+        }
+        if p.len() == 1 {
+            let mut node = node.clone();
+            node.name = escape_filename(&p[0]);
+            tree.add(node);
+        } else if !new_dirs.contains(&p[0]) {
+            new_dirs.push(p[0].clone());
+        }
+    }
+    for name in new_dirs {
+        let sub_insertions: Vec<_> = insertions
+            .iter()
+            .filter_map(|(p, node)| Some((below(p, &name)?, node.clone())))
+            .collect();
+        let mut node = Node::new_node(&name, NodeType::Dir, Metadata::default());
+        node.subtree = Some(rewrite_tree(
+            be,
+            index,
+            packer,
+            None,
+            &path.join(&name),
+            &[],
+            &sub_insertions,
+        )?);
+        tree.add(node);
+    }
+    tree.nodes.sort_unstable_by_key(Node::name);
+
+    let (chunk, new_id) = tree.serialize().map_err(|err| {
+        RusticError::with_source(ErrorKind::Internal, "Failed to serialize tree.", err).ask_report()
+    })?;
+    if !index.has_tree(&new_id) {
+        packer.add(chunk.into(), BlobId::from(*new_id))?;
+    }
+    Ok(new_id)
+}
+
+/// Returns the remaining path if `path` is located below `name`
+fn below<'a>(path: &'a [OsString], name: &OsString) -> Option<&'a [OsString]> {
+    match path.split_first() {
+        Some((first, rest)) if first == name && !rest.is_empty() => Some(rest),
+        _ => None,
+    }
+}
+
+/// Relocate a snapshot path according to the given mappings
+fn relocate_path(path: &Path, mappings: &[(Components, Components)]) -> PathBuf {
+    let Ok(comps) = components(path) else {
+        return path.to_path_buf();
+    };
+    for (src, dst) in mappings {
+        if comps.starts_with(src) {
+            let mut new_path = if path.has_root() {
+                PathBuf::from("/")
+            } else {
+                PathBuf::new()
+            };
+            new_path.extend(dst);
+            new_path.extend(&comps[src.len()..]);
+            return new_path;
+        }
+    }
+    path.to_path_buf()
+}
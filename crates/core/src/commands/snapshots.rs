@@ -1,13 +1,26 @@
 //! `smapshot` subcommand
 
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    ffi::OsStr,
+};
+
+use log::info;
+
 use crate::{
-    error::RusticResult,
+    backend::{
+        decrypt::{DecryptReadBackend, DecryptWriteBackend},
+        node::{Metadata, Node, NodeType},
+    },
+    blob::tree::{NodeStreamer, TreeId},
+    error::{ErrorKind, RusticError, RusticResult},
+    index::ReadIndex,
     progress::ProgressBars,
     repofile::{
-        snapshotfile::{SnapshotGroup, SnapshotGroupCriterion},
+        snapshotfile::{GroupSummary, SnapshotGroup, SnapshotGroupCriterion, SnapshotSummary},
         SnapshotFile,
     },
-    repository::{Open, Repository},
+    repository::{IndexedTree, Open, Repository, Writable},
     Progress,
 };
 
@@ -61,3 +74,153 @@ pub(crate) fn get_snapshot_group<P: ProgressBars, S: Open>(
 
     Ok(groups)
 }
+
+/// Get a summary of the snapshot groups from the repository.
+///
+/// The snapshots are streamed from the backend and only the aggregated information is kept.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to get the snapshots from.
+/// * `group_by` - The criterion to group the snapshots by.
+/// * `filter` - The filter to apply to the snapshots.
+///
+/// # Returns
+///
+/// The summaries of the snapshot groups, sorted by group.
+pub(crate) fn snapshot_group_summary<P: ProgressBars, S: Open>(
+    repo: &Repository<P, S>,
+    group_by: SnapshotGroupCriterion,
+    mut filter: impl FnMut(&SnapshotFile) -> bool,
+) -> RusticResult<Vec<(SnapshotGroup, GroupSummary)>> {
+    let p = repo.pb.progress_counter("getting snapshots...");
+    let mut groups = BTreeMap::new();
+    for snap in repo.dbe().stream_all::<SnapshotFile>(&p)? {
+        let snap = SnapshotFile::set_id(snap?);
+        if !filter(&snap) {
+            continue;
+        }
+        match groups.entry(SnapshotGroup::from_snapshot(&snap, group_by)) {
+            Entry::Vacant(entry) => {
+                _ = entry.insert(GroupSummary::from_snapshot(&snap));
+            }
+            Entry::Occupied(mut entry) => entry.get_mut().add(&snap),
+        }
+    }
+    p.finish();
+
+    Ok(groups.into_iter().collect())
+}
+
+/// Compute the summary of a snapshot from its tree.
+///
+/// Only the totals of processed files and dirs can be computed from the tree. The counts of
+/// new, changed and unmodified entries as well as the added data are unknown and left at zero.
+/// Start and end of the backup are set to the snapshot time.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to compute the summary for
+///
+/// # Errors
+///
+/// * If a tree could not be read or is missing in the index.
+pub(crate) fn compute_snapshot_summary<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+) -> RusticResult<SnapshotSummary> {
+    let index = repo.index();
+    let tree_size = |id: TreeId| -> RusticResult<u64> {
+        let entry = index.get_tree(&id).ok_or_else(|| {
+            RusticError::new(ErrorKind::Internal, "Tree `{tree_id}` not found in index.")
+                .attach_context("tree_id", id.to_string())
+        })?;
+        Ok(u64::from(entry.data_length()))
+    };
+
+    let p = repo.pb.progress_spinner("computing snapshot summary...");
+    let mut summary = SnapshotSummary {
+        backup_start: snap.time,
+        backup_end: snap.time,
+        total_dirs_processed: 1,
+        total_dirsize_processed: tree_size(snap.tree)?,
+        ..Default::default()
+    };
+
+    let mut root = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    root.subtree = Some(snap.tree);
+    for item in NodeStreamer::new(repo.dbe().clone(), index, &root)? {
+        repo.cancel.check()?;
+        let (_, node) = item?;
+        match node.subtree {
+            Some(id) => {
+                summary.total_dirs_processed += 1;
+                summary.total_dirsize_processed += tree_size(id)?;
+            }
+            None => {
+                summary.total_files_processed += 1;
+                if node.is_file() {
+                    summary.total_bytes_processed += node.meta.size;
+                }
+            }
+        }
+    }
+    p.finish();
+
+    Ok(summary)
+}
+
+/// Compute the summary of a snapshot without summary and save the snapshot with the summary.
+///
+/// The snapshot is replaced, i.e. the old snapshot file is removed and the original id is kept in
+/// `original`. Snapshots which already have a summary are returned unchanged.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type.
+/// * `S` - The state the repository is in.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `snap` - The snapshot to backfill the summary for
+///
+/// # Errors
+///
+/// * If the summary could not be computed.
+/// * If the snapshot could not be saved or the old snapshot could not be removed.
+///
+/// # Returns
+///
+/// The snapshot with summary
+pub(crate) fn backfill_summary<P: ProgressBars, S: IndexedTree + Writable>(
+    repo: &Repository<P, S>,
+    snap: &SnapshotFile,
+) -> RusticResult<SnapshotFile> {
+    if snap.summary.is_some() {
+        return Ok(snap.clone());
+    }
+
+    let mut new_snap = snap.clone();
+    new_snap.summary = Some(compute_snapshot_summary(repo, snap)?);
+    new_snap.original = Some(snap.original.unwrap_or(snap.id));
+    new_snap.id = repo.dbe().save_file(&new_snap)?.into();
+    repo.delete_snapshots_unlocked(&[snap.id])?;
+    info!(
+        "saved snapshot {} with summary of snapshot {}",
+        new_snap.id, snap.id
+    );
+
+    Ok(new_snap)
+}
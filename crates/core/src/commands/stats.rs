@@ -0,0 +1,405 @@
+//! `stats` subcommand
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_derive::{Deserialize, Serialize};
+use serde_with::skip_serializing_none;
+use strum::{Display, EnumString};
+
+use crate::{
+    backend::decrypt::DecryptReadBackend,
+    blob::{
+        tree::{Tree, TreeId, TreeStreamerOnce},
+        BlobType, DataId,
+    },
+    commands::repoinfo::{IndexInfos, RepoFileInfos},
+    error::{ErrorKind, RusticError, RusticResult},
+    index::{IndexEntry, ReadGlobalIndex, ReadIndex},
+    progress::{Progress, ProgressBars},
+    repofile::{snapshotfile::SnapshotGroupCriterion, SnapshotFile},
+    repository::{IndexedFull, IndexedTree, Repository},
+};
+
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, Display, EnumString, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+/// The way the total size and file count of [`RepoStats`] are computed
+pub enum StatsMode {
+    /// The size of all files when restoring all snapshots
+    #[default]
+    RestoreSize,
+    /// The size of all files with unique contents
+    FilesByContents,
+    /// The size of all unique blobs referenced by the snapshots as stored in the repository
+    RawData,
+}
+
+#[skip_serializing_none]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+/// Aggregated statistics about a repository
+pub struct RepoStats {
+    /// The mode used to compute `total_size` and `total_file_count`
+    pub mode: StatsMode,
+    /// Number of snapshots
+    pub snapshots_count: u64,
+    /// Total size, depending on the mode
+    pub total_size: u64,
+    /// Total number of files, depending on the mode
+    pub total_file_count: u64,
+    /// Total number of unique blobs, only for [`StatsMode::RawData`]
+    pub total_blob_count: Option<u64>,
+    /// Total size of the unique blobs before compression, only for [`StatsMode::RawData`]
+    pub total_uncompressed_size: Option<u64>,
+    /// The size of all files when restoring all snapshots
+    pub restore_size: u64,
+    /// The size of all files when restoring the latest snapshot of each snapshot group
+    pub latest_restore_size: u64,
+    /// Total size of all blobs as stored in the repository, i.e. after compression and encryption
+    pub stored_size: u64,
+    /// The ratio between `restore_size` and `stored_size`; `None` if nothing is stored
+    pub dedup_ratio: Option<f64>,
+    /// Information from the index
+    pub index: IndexInfos,
+    /// Information about the repository files
+    pub files: RepoFileInfos,
+}
+
+/// The size of a tree when restoring it, see [`Repository::tree_restore_size`]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct TreeRestoreSize {
+    /// Total size of all files
+    pub total: u64,
+    /// Size of the unique contents, i.e. the uncompressed size of all distinct data blobs.
+    /// Only computed if requested.
+    pub unique: Option<u64>,
+    /// Number of files
+    pub files: u64,
+}
+
+/// Number of files and their total size within a tree
+#[derive(Default, Clone, Copy, Debug)]
+struct TreeSize {
+    /// Number of files
+    files: u64,
+    /// Total size of all files
+    size: u64,
+}
+
+impl TreeSize {
+    /// Add another [`TreeSize`].
+    fn add(&mut self, other: Self) {
+        self.files += other.files;
+        self.size += other.size;
+    }
+}
+
+/// Compute the restore size of a tree including all subtrees.
+///
+/// The results are cached for each tree, so identical trees are only read once.
+///
+/// # Arguments
+///
+/// * `be` - The backend to read from
+/// * `index` - The index to use
+/// * `id` - The id of the tree
+/// * `cache` - The already computed tree sizes
+///
+/// # Errors
+///
+/// * If a tree could not be loaded from the backend.
+fn tree_size(
+    be: &impl DecryptReadBackend,
+    index: &impl ReadGlobalIndex,
+    id: TreeId,
+    cache: &mut BTreeMap<TreeId, TreeSize>,
+) -> RusticResult<TreeSize> {
+    if let Some(size) = cache.get(&id) {
+        return Ok(*size);
+    }
+
+    let mut size = TreeSize::default();
+    for node in Tree::from_backend(be, index, id)?.nodes {
+        if let Some(subtree) = node.subtree {
+            size.add(tree_size(be, index, subtree, cache)?);
+        } else if node.is_file() {
+            size.add(TreeSize {
+                files: 1,
+                size: node.meta.size,
+            });
+        }
+    }
+    _ = cache.insert(id, size);
+    Ok(size)
+}
+
+/// Compute the restore sizes of the given trees.
+///
+/// The sizes of identical (sub)trees are only computed once, so computing the sizes of many
+/// snapshots at once is much faster than computing them one by one.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type
+/// * `S` - The state the repository is in
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `trees` - The trees to compute the sizes for
+/// * `unique` - Whether to compute the size of the unique contents
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+/// * If a blob referenced by a tree is missing in the index.
+pub(crate) fn trees_restore_size<P: ProgressBars, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    trees: &[TreeId],
+    unique: bool,
+) -> RusticResult<Vec<TreeRestoreSize>> {
+    let p = repo.pb.progress_counter("computing restore size...");
+    p.set_length(trees.len() as u64);
+    let mut cache = BTreeMap::new();
+    let sizes = trees
+        .iter()
+        .map(|tree| -> RusticResult<_> {
+            let size = tree_size(repo.dbe(), repo.index(), *tree, &mut cache)?;
+            let unique = unique.then(|| unique_size(repo, *tree)).transpose()?;
+            p.inc(1);
+            Ok(TreeRestoreSize {
+                total: size.size,
+                unique,
+                files: size.files,
+            })
+        })
+        .collect();
+    p.finish();
+    sizes
+}
+
+/// Compute the uncompressed size of all distinct data blobs within a tree.
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+/// * If a blob referenced by the tree is missing in the index.
+fn unique_size<P: ProgressBars, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    tree: TreeId,
+) -> RusticResult<u64> {
+    let mut data_ids = BTreeSet::new();
+    let mut tree_streamer = TreeStreamerOnce::new(
+        repo.dbe(),
+        repo.index(),
+        vec![tree],
+        repo.pb.progress_hidden(),
+    )?;
+    while let Some((_, tree)) = tree_streamer.next().transpose()? {
+        for node in tree.nodes {
+            if node.is_file() {
+                data_ids.extend(node.content.into_iter().flatten());
+            }
+        }
+    }
+
+    data_ids.iter().try_fold(0, |size, id| {
+        let ie = index_entry(repo.index().get_data(id), BlobType::Data, id.to_string())?;
+        Ok(size + u64::from(ie.data_length()))
+    })
+}
+
+/// Get the index entry of a blob referenced by a snapshot.
+///
+/// # Errors
+///
+/// * If the blob is not contained in the index.
+fn index_entry(entry: Option<IndexEntry>, tpe: BlobType, id: String) -> RusticResult<IndexEntry> {
+    entry.ok_or_else(|| {
+        RusticError::new(
+            ErrorKind::Repository,
+            "The {tpe} blob `{id}` is referenced by a snapshot, but missing in the index. Please run `check`.",
+        )
+        .attach_context("tpe", tpe.to_string())
+        .attach_context("id", id)
+    })
+}
+
+/// Collect aggregated statistics about the repository.
+///
+/// # Type Parameters
+///
+/// * `P` - The progress bar type
+/// * `S` - The state the repository is in
+///
+/// # Arguments
+///
+/// * `repo` - The repository to use
+/// * `mode` - The mode to compute the total size and file count
+///
+/// # Errors
+///
+/// * If the index or a tree could not be read.
+/// * If a blob referenced by a snapshot is missing in the index.
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn collect_stats<P: ProgressBars, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    mode: StatsMode,
+) -> RusticResult<RepoStats> {
+    let index = repo.infos_index()?;
+    let files = repo.infos_files()?;
+    let groups = repo.get_snapshot_group(&[], SnapshotGroupCriterion::default(), |_| true)?;
+    let latest_trees: Vec<_> = groups
+        .iter()
+        .filter_map(|(_, snaps)| snaps.iter().max().map(|snap| snap.tree))
+        .collect();
+    let snapshots: Vec<SnapshotFile> = groups.into_iter().flat_map(|(_, snaps)| snaps).collect();
+
+    let p = repo.pb.progress_counter("computing restore size...");
+    p.set_length(snapshots.len() as u64);
+    let mut cache = BTreeMap::new();
+    let mut restore = TreeSize::default();
+    for snap in &snapshots {
+        restore.add(tree_size(repo.dbe(), repo.index(), snap.tree, &mut cache)?);
+        p.inc(1);
+    }
+    p.finish();
+
+    let latest_restore_size = latest_trees.iter().map(|tree| cache[tree].size).sum();
+
+    let mut stats = RepoStats {
+        mode,
+        snapshots_count: snapshots.len() as u64,
+        total_size: restore.size,
+        total_file_count: restore.files,
+        total_blob_count: None,
+        total_uncompressed_size: None,
+        restore_size: restore.size,
+        latest_restore_size,
+        stored_size: index.blobs.iter().map(|blob| blob.size).sum(),
+        dedup_ratio: None,
+        index,
+        files,
+    };
+    stats.dedup_ratio =
+        (stats.stored_size > 0).then(|| stats.restore_size as f64 / stats.stored_size as f64);
+
+    match mode {
+        StatsMode::RestoreSize => {}
+        StatsMode::FilesByContents => {
+            let (files, size) = files_by_contents(repo, &snapshots)?;
+            stats.total_file_count = files;
+            stats.total_size = size;
+        }
+        StatsMode::RawData => raw_data(repo, &snapshots, &mut stats)?,
+    }
+
+    Ok(stats)
+}
+
+/// Count the files with unique contents and their total size.
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+fn files_by_contents<P: ProgressBars, S: IndexedTree>(
+    repo: &Repository<P, S>,
+    snapshots: &[SnapshotFile],
+) -> RusticResult<(u64, u64)> {
+    let trees = snapshots.iter().map(|snap| snap.tree).collect();
+    let p = repo.pb.progress_counter("collecting file contents...");
+    let mut contents = BTreeSet::<Vec<DataId>>::new();
+    let (mut files, mut size) = (0, 0);
+
+    let mut tree_streamer = TreeStreamerOnce::new(repo.dbe(), repo.index(), trees, p)?;
+    while let Some((_, tree)) = tree_streamer.next().transpose()? {
+        for node in tree.nodes {
+            if node.is_file() && contents.insert(node.content.unwrap_or_default()) {
+                files += 1;
+                size += node.meta.size;
+            }
+        }
+    }
+    Ok((files, size))
+}
+
+/// Compute the statistics about the unique blobs referenced by the snapshots.
+///
+/// # Errors
+///
+/// * If a tree could not be read.
+/// * If a referenced blob is missing in the index.
+fn raw_data<P: ProgressBars, S: IndexedFull>(
+    repo: &Repository<P, S>,
+    snapshots: &[SnapshotFile],
+    stats: &mut RepoStats,
+) -> RusticResult<()> {
+    let trees: Vec<_> = snapshots.iter().map(|snap| snap.tree).collect();
+    let mut tree_ids: BTreeSet<_> = trees.iter().copied().collect();
+    let mut data_ids = BTreeSet::new();
+    let mut files = 0;
+
+    let p = repo.pb.progress_counter("collecting blobs...");
+    let mut tree_streamer = TreeStreamerOnce::new(repo.dbe(), repo.index(), trees, p)?;
+    while let Some((_, tree)) = tree_streamer.next().transpose()? {
+        for node in tree.nodes {
+            if let Some(subtree) = node.subtree {
+                _ = tree_ids.insert(subtree);
+            } else if node.is_file() {
+                files += 1;
+                data_ids.extend(node.content.into_iter().flatten());
+            }
+        }
+    }
+
+    let (mut size, mut uncompressed_size) = (0, 0);
+    let mut add = |ie: IndexEntry| {
+        size += u64::from(ie.length);
+        uncompressed_size += u64::from(ie.data_length());
+    };
+    for id in &tree_ids {
+        add(index_entry(
+            repo.index().get_tree(id),
+            BlobType::Tree,
+            id.to_string(),
+        )?);
+    }
+    for id in &data_ids {
+        add(index_entry(
+            repo.index().get_data(id),
+            BlobType::Data,
+            id.to_string(),
+        )?);
+    }
+
+    stats.total_file_count = files;
+    stats.total_size = size;
+    stats.total_uncompressed_size = Some(uncompressed_size);
+    stats.total_blob_count = Some((tree_ids.len() + data_ids.len()) as u64);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_stats_mode_from_str_passes() {
+        assert_eq!(
+            StatsMode::from_str("restore-size").unwrap(),
+            StatsMode::RestoreSize
+        );
+        assert_eq!(
+            StatsMode::from_str("files-by-contents").unwrap(),
+            StatsMode::FilesByContents
+        );
+        assert_eq!(StatsMode::from_str("raw-data").unwrap(), StatsMode::RawData);
+        assert!(StatsMode::from_str("blobs-per-file").is_err());
+    }
+}
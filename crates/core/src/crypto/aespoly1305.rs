@@ -3,6 +3,7 @@ use aes256ctr_poly1305aes::{
     Aes256CtrPoly1305Aes,
 };
 use rand::{thread_rng, RngCore};
+use subtle::ConstantTimeEq;
 
 use crate::{
     crypto::CryptoKey,
@@ -22,9 +23,24 @@ pub(crate) type AeadKey = aes256ctr_poly1305aes::Key;
 ///
 /// The last 16 bytes are used for the number `r` of `Poly1305AES`.
 ///
-#[derive(Clone, Default, Debug, Copy)]
+#[derive(Clone, Default, Copy)]
 pub struct Key(AeadKey);
 
+impl std::fmt::Debug for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Key([REDACTED])")
+    }
+}
+
+// Compare in constant time to not leak key material via timing
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_slice().ct_eq(other.0.as_slice()).into()
+    }
+}
+
+impl Eq for Key {}
+
 impl Key {
     /// Create a new random [`Key`] using a suitable entropy source.
     #[must_use]
@@ -163,4 +179,17 @@ mod tests {
         let res = key.decrypt_data(&data);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn debug_is_redacted() {
+        let key = Key::from_slice(&[0xab; 64]);
+        assert_eq!(format!("{key:?}"), "Key([REDACTED])");
+    }
+
+    #[test]
+    fn keys_compare_by_content() {
+        let key = Key::new();
+        assert_eq!(key, Key::from_slice(&key.0));
+        assert_ne!(key, Key::new());
+    }
 }
@@ -110,12 +110,16 @@ pub enum ErrorKind {
     AppendOnly,
     /// the backend
     Backend,
+    /// cancelled operations
+    Cancelled,
     /// the configuration
     Configuration,
     /// cryptographic operations
     Cryptography,
     /// running an external command
     ExternalCommand,
+    /// an incorrect password
+    IncorrectPassword,
     /// internal operations
     // Blob, Pack, Index, Tree Errors
     // Compression, Parsing, Multithreading etc.
@@ -134,6 +138,8 @@ pub enum ErrorKind {
     Other,
     /// password handling
     Password,
+    /// read-only mode
+    ReadOnly,
     /// the repository
     Repository,
     /// unsupported operations
@@ -358,9 +364,17 @@ impl RusticError {
             .map_or(false, |c| c.as_str() == code)
     }
 
+    /// Returns the kind of the error.
+    pub const fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
     /// Checks if the error is due to an incorrect password
+    ///
+    /// This is only the case if the password didn't fit to any key; errors reading the key files
+    /// have a different kind.
     pub fn is_incorrect_password(&self) -> bool {
-        self.is_code("C002")
+        self.kind == ErrorKind::IncorrectPassword
     }
 
     /// Creates a new error from a given error.
@@ -1,7 +1,8 @@
-use std::{num::NonZeroU32, sync::Arc, thread::sleep, time::Duration};
+use std::{collections::BTreeSet, num::NonZeroU32, sync::Arc, thread::sleep, time::Duration};
 
 use bytes::Bytes;
 use derive_more::Constructor;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::{
     backend::{decrypt::DecryptReadBackend, FileType},
@@ -10,7 +11,8 @@ use crate::{
     index::binarysorted::{Index, IndexCollector, IndexType},
     progress::Progress,
     repofile::{
-        indexfile::{IndexBlob, IndexFile},
+        configfile::RepositoryId,
+        indexfile::{IndexBlob, IndexFile, IndexId, IndexPack},
         packfile::PackId,
     },
 };
@@ -73,6 +75,12 @@ impl IndexEntry {
         Ok(data)
     }
 
+    /// Get the type of the blob described by the [`IndexEntry`]
+    #[must_use]
+    pub const fn blob_type(&self) -> BlobType {
+        self.blob_type
+    }
+
     /// Get the length of the data described by the [`IndexEntry`]
     #[must_use]
     pub const fn data_length(&self) -> u32 {
@@ -114,6 +122,17 @@ pub trait ReadIndex {
     /// * `id` - The id of the blob
     fn has(&self, tpe: BlobType, id: &BlobId) -> bool;
 
+    /// Get all blobs of the given pack
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - The id of the pack
+    ///
+    /// # Returns
+    ///
+    /// The ids and [`IndexEntry`]s of all blobs in the pack which are contained in the index, sorted by offset
+    fn pack_entries(&self, pack: &PackId) -> Vec<(BlobId, IndexEntry)>;
+
     /// Get a tree from the index
     ///
     /// # Arguments
@@ -204,6 +223,8 @@ pub trait ReadGlobalIndex: ReadIndex + Clone + Sync + Send + 'static {}
 pub struct GlobalIndex {
     /// The atomic reference counted, sharable index.
     index: Arc<Index>,
+    /// The ids of the index files contained in the index; `None` if unknown
+    index_ids: Option<BTreeSet<IndexId>>,
 }
 
 impl ReadIndex for GlobalIndex {
@@ -243,6 +264,19 @@ impl ReadIndex for GlobalIndex {
     fn has(&self, tpe: BlobType, id: &BlobId) -> bool {
         self.index.has(tpe, id)
     }
+
+    /// Get all blobs of the given pack
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - The id of the pack
+    ///
+    /// # Returns
+    ///
+    /// The ids and [`IndexEntry`]s of all blobs in the pack, sorted by offset
+    fn pack_entries(&self, pack: &PackId) -> Vec<(BlobId, IndexEntry)> {
+        self.index.pack_entries(pack)
+    }
 }
 
 impl GlobalIndex {
@@ -259,6 +293,7 @@ impl GlobalIndex {
     pub fn new_from_index(index: Index) -> Self {
         Self {
             index: Arc::new(index),
+            index_ids: None,
         }
     }
 
@@ -278,14 +313,19 @@ impl GlobalIndex {
         p: &impl Progress,
         mut collector: IndexCollector,
     ) -> RusticResult<Self> {
-        p.set_title("reading index...");
+        let mut index_ids = BTreeSet::new();
         for index in be.stream_all::<IndexFile>(p)? {
-            collector.extend(index?.1.packs);
+            let (id, index) = index?;
+            _ = index_ids.insert(id);
+            collector.extend(index.packs);
         }
 
         p.finish();
 
-        Ok(Self::new_from_index(collector.into_index()))
+        Ok(Self {
+            index: Arc::new(collector.into_index()),
+            index_ids: Some(index_ids),
+        })
     }
 
     /// Create a new [`GlobalIndex`]
@@ -325,11 +365,168 @@ impl GlobalIndex {
         }
     }
 
+    /// Update the index by reading the index files which are not yet contained
+    ///
+    /// If index files contained in the index have been removed (e.g. by `prune`) or the contained
+    /// index files are unknown, all index files are read again.
+    ///
+    /// # Note
+    ///
+    /// The index is shared by all clones of this `GlobalIndex`. If it is still used elsewhere
+    /// (e.g. by a clone of the repository) when new packs are added, the whole index is copied
+    /// first, which temporarily doubles its memory usage. Drop other users before updating to
+    /// modify the index in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `be` - The backend to read from
+    /// * `p` - The progress tracker
+    ///
+    /// # Errors
+    ///
+    /// * If the index files could not be listed
+    /// * If an index file could not be read
+    pub fn update(&mut self, be: &impl DecryptReadBackend, p: &impl Progress) -> RusticResult<()> {
+        let ids: BTreeSet<_> = be
+            .list(FileType::Index)?
+            .into_iter()
+            .map(IndexId::from)
+            .collect();
+
+        let Some(index_ids) = self
+            .index_ids
+            .as_mut()
+            .filter(|index_ids| index_ids.is_subset(&ids))
+        else {
+            *self = Self::new_from_collector(be, p, IndexCollector::new(self.index.index_type()))?;
+            return Ok(());
+        };
+
+        let new_ids: Vec<_> = ids.difference(index_ids).map(|id| **id).collect();
+        p.set_length(new_ids.len() as u64);
+        let mut packs = Vec::new();
+        for index in be.stream_list::<IndexFile>(&new_ids, p)? {
+            let (id, index) = index?;
+            _ = index_ids.insert(id);
+            packs.extend(index.packs);
+        }
+        p.finish();
+
+        if !packs.is_empty() {
+            // this copies the index if it is shared, see the note above
+            Arc::make_mut(&mut self.index).add_packs(packs);
+        }
+        Ok(())
+    }
+
     pub(crate) fn drop_data(self) -> Self {
+        let index_ids = self.index_ids.clone();
         Self {
             index: Arc::new(self.into_index().drop_data()),
+            index_ids,
         }
     }
 }
 
 impl ReadGlobalIndex for GlobalIndex {}
+
+/// A [`GlobalIndex`] in a serializable form which can be exported and imported again
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ExportedIndex {
+    /// The id of the repository the index belongs to
+    repo_id: RepositoryId,
+    /// The ids of the index files contained in the index; `None` if unknown
+    index_ids: Option<BTreeSet<IndexId>>,
+    /// The packs contained in the index
+    packs: Vec<IndexPack>,
+}
+
+impl GlobalIndex {
+    /// Export the index into a serializable form
+    ///
+    /// # Arguments
+    ///
+    /// * `repo_id` - The id of the repository the index belongs to
+    ///
+    /// # Errors
+    ///
+    /// * If the index doesn't contain full information about data blobs
+    pub(crate) fn export(&self, repo_id: RepositoryId) -> RusticResult<ExportedIndex> {
+        if !matches!(self.index.index_type(), IndexType::Full) {
+            return Err(RusticError::new(
+                ErrorKind::Unsupported,
+                "Only a full index can be exported.",
+            ));
+        }
+
+        Ok(ExportedIndex {
+            repo_id,
+            index_ids: self.index_ids.clone(),
+            packs: self.index.index_packs(),
+        })
+    }
+
+    /// Create a [`GlobalIndex`] from an exported index
+    ///
+    /// # Arguments
+    ///
+    /// * `exported` - The exported index
+    /// * `repo_id` - The id of the repository the index is used for
+    ///
+    /// # Errors
+    ///
+    /// * If the exported index belongs to another repository
+    pub(crate) fn import(exported: ExportedIndex, repo_id: RepositoryId) -> RusticResult<Self> {
+        if exported.repo_id != repo_id {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "The exported index belongs to repository `{exported_id}`, but the repository has id `{repo_id}`.",
+            )
+            .attach_context("exported_id", exported.repo_id.to_string())
+            .attach_context("repo_id", repo_id.to_string()));
+        }
+
+        let mut collector = IndexCollector::new(IndexType::Full);
+        collector.extend(exported.packs);
+
+        Ok(Self {
+            index: Arc::new(collector.into_index()),
+            index_ids: exported.index_ids,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::id::Id;
+
+    fn exported(repo_id: RepositoryId, blob_id: BlobId) -> RusticResult<Vec<u8>> {
+        let mut pack = IndexPack {
+            id: PackId::from(Id::random()),
+            ..Default::default()
+        };
+        pack.add(blob_id, BlobType::Data, 0, 100, None);
+        let mut collector = IndexCollector::new(IndexType::Full);
+        collector.extend(Some(pack));
+        let index = GlobalIndex::new_from_index(collector.into_index());
+
+        Ok(serde_json::to_vec(&index.export(repo_id)?).unwrap())
+    }
+
+    #[test]
+    fn exported_index_is_bound_to_repository() -> RusticResult<()> {
+        let repo_id = RepositoryId::from(Id::random());
+        let blob_id = BlobId::from(Id::random());
+        let data = exported(repo_id, blob_id)?;
+
+        let index = GlobalIndex::import(serde_json::from_slice(&data).unwrap(), repo_id)?;
+        assert!(index.has(BlobType::Data, &blob_id));
+
+        let other_id = RepositoryId::from(Id::random());
+        let err =
+            GlobalIndex::import(serde_json::from_slice(&data).unwrap(), other_id).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        Ok(())
+    }
+}
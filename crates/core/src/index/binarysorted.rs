@@ -11,7 +11,7 @@ use crate::{
 };
 
 /// A sorted entry in the index.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct SortedEntry {
     /// The ID of the entry.
     id: BlobId,
@@ -37,7 +37,7 @@ pub enum IndexType {
 }
 
 // TODO: add documentation!
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) enum EntriesVariants {
     None,
     Ids(Vec<BlobId>),
@@ -67,14 +67,14 @@ pub struct PackIndexes {
     idx: BlobTypeMap<(usize, usize)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub(crate) struct TypeIndex {
     packs: Vec<PackId>,
     entries: EntriesVariants,
     total_size: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Index(BlobTypeMap<TypeIndex>);
 
 impl Index {
@@ -92,6 +92,84 @@ impl Index {
             }
         }))
     }
+
+    /// The [`IndexType`] describing which information is stored in this index
+    pub(crate) fn index_type(&self) -> IndexType {
+        match self.0[BlobType::Data].entries {
+            EntriesVariants::None => IndexType::OnlyTrees,
+            EntriesVariants::Ids(_) => IndexType::DataIds,
+            EntriesVariants::FullEntries(_) => IndexType::Full,
+        }
+    }
+
+    /// The packs contained in this index
+    ///
+    /// In contrast to [`IntoIterator::into_iter`], this doesn't consume the index.
+    pub(crate) fn index_packs(&self) -> Vec<IndexPack> {
+        let mut index_packs = Vec::new();
+        for tpe in [BlobType::Tree, BlobType::Data] {
+            let ti = &self.0[tpe];
+            let mut packs: Vec<_> = ti
+                .packs
+                .iter()
+                .map(|id| IndexPack {
+                    id: *id,
+                    ..Default::default()
+                })
+                .collect();
+
+            if let EntriesVariants::FullEntries(entries) = &ti.entries {
+                for entry in entries {
+                    packs[entry.pack_idx].blobs.push(IndexBlob {
+                        id: entry.id,
+                        tpe,
+                        offset: entry.offset,
+                        length: entry.length,
+                        uncompressed_length: entry.uncompressed_length,
+                    });
+                }
+            }
+            index_packs.extend(packs);
+        }
+        index_packs
+    }
+
+    /// Add packs to this index, keeping the information given by the [`IndexType`] of this index
+    ///
+    /// # Arguments
+    ///
+    /// * `packs` - The packs to add
+    pub(crate) fn add_packs(&mut self, packs: impl IntoIterator<Item = IndexPack>) {
+        for p in packs {
+            let blob_type = p.blob_type();
+            let ti = &mut self.0[blob_type];
+            let idx = ti.packs.len();
+            ti.packs.push(p.id);
+            ti.total_size += u64::from(p.pack_size());
+
+            match &mut ti.entries {
+                EntriesVariants::None => {}
+                EntriesVariants::Ids(ids) => ids.extend(p.blobs.iter().map(|blob| blob.id)),
+                EntriesVariants::FullEntries(entries) => {
+                    entries.extend(p.blobs.iter().map(|blob| SortedEntry {
+                        id: blob.id,
+                        pack_idx: idx,
+                        offset: blob.offset,
+                        length: blob.length,
+                        uncompressed_length: blob.uncompressed_length,
+                    }));
+                }
+            }
+        }
+
+        for ti in self.0.values_mut() {
+            match &mut ti.entries {
+                EntriesVariants::None => {}
+                EntriesVariants::Ids(ids) => ids.par_sort_unstable(),
+                EntriesVariants::FullEntries(entries) => entries.par_sort_unstable_by_key(|e| e.id),
+            }
+        }
+    }
 }
 
 impl IndexCollector {
@@ -276,6 +354,36 @@ impl ReadIndex for Index {
             EntriesVariants::None => false,
         }
     }
+
+    fn pack_entries(&self, pack: &PackId) -> Vec<(BlobId, IndexEntry)> {
+        let mut entries: Vec<_> = self
+            .0
+            .iter()
+            .flat_map(|(blob_type, ti)| {
+                // pack_entries() only gives results if index contains full entries
+                let (EntriesVariants::FullEntries(vec), Some(pack_idx)) =
+                    (&ti.entries, ti.packs.iter().position(|id| id == pack))
+                else {
+                    return Vec::new();
+                };
+                vec.iter()
+                    .filter(|e| e.pack_idx == pack_idx)
+                    .map(|e| {
+                        let entry = IndexEntry::new(
+                            blob_type,
+                            *pack,
+                            e.offset,
+                            e.length,
+                            e.uncompressed_length,
+                        );
+                        (e.id, entry)
+                    })
+                    .collect()
+            })
+            .collect();
+        entries.sort_unstable_by_key(|(_, entry)| entry.offset);
+        entries
+    }
 }
 
 #[cfg(test)]
@@ -451,4 +559,42 @@ mod tests {
         assert!(index.get_id(BlobType::Tree, &id).is_none());
         Ok(())
     }
+
+    #[test]
+    fn pack_entries() -> RusticResult<()> {
+        let pack = "3b25ec6d16401c31099c259311562160b1b5efbcf70bd69d0463104d3b8148fc".parse()?;
+        let unknown = "0000000000000000000000000000000000000000000000000000000000000000".parse()?;
+
+        let full = index(IndexType::Full);
+        let entries = full.pack_entries(&pack);
+        assert_eq!(entries.len(), 4);
+        assert!(entries.windows(2).all(|w| w[0].1.offset < w[1].1.offset));
+        for (id, entry) in entries {
+            assert_eq!(entry.pack, pack);
+            assert_eq!(full.get_id(BlobType::Data, &id), Some(entry));
+        }
+        assert!(full.pack_entries(&unknown).is_empty());
+
+        // only full entries give results
+        assert!(index(IndexType::DataIds).pack_entries(&pack).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn index_packs_matches_into_iter() {
+        let index = index(IndexType::Full);
+        let packs = |packs: Vec<IndexPack>| {
+            packs
+                .into_iter()
+                .map(|mut p| {
+                    p.blobs.sort_unstable_by_key(|b| b.offset);
+                    (p.id, p.blobs)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let expected = packs(index.clone().into_iter().collect());
+        assert_eq!(packs(index.index_packs()), expected);
+        assert_eq!(expected.len(), 3);
+    }
 }
@@ -97,6 +97,9 @@ This crate exposes a few features for controlling dependency usage.
 - **webdav** - Enables a dependency on the `dav-server` and `futures` crate.
   This enables us to run a `WebDAV` server asynchronously on the commandline.
   *This feature is disabled by default*.
+
+- **rest-server** - Enables the `rest_server` module which serves a repository
+  using the restic REST protocol. *This feature is disabled by default*.
 */
 
 // Workspace lints don't seem to work for this?
@@ -105,6 +108,7 @@ This crate exposes a few features for controlling dependency usage.
 pub(crate) mod archiver;
 pub(crate) mod backend;
 pub(crate) mod blob;
+pub(crate) mod cancellation;
 pub(crate) mod chunker;
 pub(crate) mod commands;
 pub(crate) mod crypto;
@@ -115,6 +119,8 @@ pub(crate) mod progress;
 /// Structs which are saved in JSON or binary format in the repository
 pub mod repofile;
 pub(crate) mod repository;
+#[cfg(feature = "rest-server")]
+pub mod rest_server;
 /// Virtual File System support - allows to act on the repository like on a file system
 pub mod vfs;
 
@@ -124,35 +130,70 @@ pub use crate::{
         decrypt::{compression_level_range, max_compression_level},
         ignore::{LocalSource, LocalSourceFilterOptions, LocalSourceSaveOptions},
         local_destination::LocalDestination,
+        mirror::{MirrorBackend, MirrorFailureMode},
         node::last_modified_node,
-        FileType, ReadBackend, ReadSource, ReadSourceEntry, ReadSourceOpen, RepositoryBackends,
-        WriteBackend, ALL_FILE_TYPES,
+        DestinationEntry, ExistingFile, FileType, MetadataOptions, PartialChunks, ReadBackend,
+        ReadSource, ReadSourceEntry, ReadSourceOpen, RepositoryBackends, RestoreDestination,
+        SelfTestResult, WriteBackend, ALL_FILE_TYPES, STREAMING_CHUNK_SIZE,
     },
     blob::{
-        tree::{FindMatches, FindNode, TreeId, TreeStreamerOptions as LsOptions},
+        tree::{
+            FilterAction, FilterRule, FindMatches, FindNode, MergeConflict, TreeId,
+            TreeStreamerOptions as LsOptions, TreeVisitor, WalkAction,
+        },
         BlobId, DataId, PackedId,
     },
+    cancellation::CancellationToken,
+    chunker::{Chunker, Chunks},
     commands::{
-        backup::{BackupOptions, ParentOptions},
-        check::{CheckOptions, ReadSubsetOption},
-        config::ConfigOptions,
+        backup::{
+            BackupEvent, BackupEventCallback, BackupOptions, BackupOutcome, FileStatus,
+            ParentFilter, ParentOptions,
+        },
+        check::{
+            CheckIssue, CheckIssueKind, CheckOptions, CheckResults, PackProblem, PackVerification,
+            ReadSubsetOption,
+        },
+        config::{
+            CompressionLevelResult, CompressionSuggestion, CompressionTarget, ConfigChange,
+            ConfigOptions,
+        },
         copy::CopySnapshot,
-        forget::{ForgetGroup, ForgetGroups, ForgetSnapshot, KeepOptions},
-        key::KeyOptions,
-        prune::{LimitOption, PruneOptions, PrunePlan, PruneStats},
+        dedup::DedupEstimate,
+        diff::{DiffEntry, DiffKind, DiffOptions, DiffStats, SnapshotDiff},
+        dump::DumpFormat,
+        forget::{ForgetGroup, ForgetGroups, ForgetPruneResult, ForgetSnapshot, KeepOptions},
+        key::{KeyInfo, KeyOptions},
+        manifest::MANIFEST_HASH_KEY,
+        prune::{
+            LimitOption, PackDecision, PackDecisionReason, PackStatus, PackToDo, PruneOptions,
+            PrunePlan, PruneStats,
+        },
         repair::{index::RepairIndexOptions, snapshots::RepairSnapshotsOptions},
-        repoinfo::{BlobInfo, IndexInfos, PackInfo, RepoFileInfo, RepoFileInfos},
-        restore::{FileDirStats, RestoreOptions, RestorePlan, RestoreStats},
+        repoinfo::{
+            BlobInfo, IndexInfos, PackInfo, PackSizeBucket, PackSizeHistogram,
+            PackSizeHistogramOptions, PackSizes, RepoFileInfo, RepoFileInfos,
+        },
+        restore::{
+            CaseConflictAction, ErrorAction, FileDirStats, FileDoneCallback, FileErrorCallback,
+            PathMapper, RestoreExtraOptions, RestoreOptions, RestorePlan, RestorePreview,
+            RestoreStats,
+        },
+        stats::{RepoStats, StatsMode, TreeRestoreSize},
     },
     error::{ErrorKind, RusticError, RusticResult, Severity, Status},
     id::{HexId, Id},
+    index::IndexEntry,
     progress::{NoProgress, NoProgressBars, Progress, ProgressBars},
     repofile::snapshotfile::{
-        PathList, SnapshotGroup, SnapshotGroupCriterion, SnapshotOptions, StringList,
+        GroupSummary, PathList, SnapshotFilter, SnapshotGroup, SnapshotGroupCriterion,
+        SnapshotOptions, SnapshotSort, StringList, TagMatchMode,
     },
     repository::{
         command_input::{CommandInput, CommandInputErrorKind},
-        FullIndex, IndexedFull, IndexedIds, IndexedStatus, IndexedTree, Open, OpenStatus,
-        Repository, RepositoryOptions,
+        lock::RepositoryLock,
+        read_password_from_keyring, store_password_in_keyring, FullIndex, IndexedFull, IndexedIds,
+        IndexedStatus, IndexedTree, Open, OpenStatus, ReadOnlyStatus, Repository,
+        RepositoryOptions, Writable, KEYRING_SERVICE,
     },
 };
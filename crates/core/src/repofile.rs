@@ -5,6 +5,7 @@ use serde::{de::DeserializeOwned, Serialize};
 pub(crate) mod configfile;
 pub(crate) mod indexfile;
 pub(crate) mod keyfile;
+pub(crate) mod lockfile;
 pub(crate) mod packfile;
 pub(crate) mod snapshotfile;
 
@@ -51,7 +52,7 @@ use crate::Id;
 pub use {
     crate::{
         backend::{
-            node::{Metadata, Node, NodeType},
+            node::{FsMetadataSummary, Metadata, Node, NodeBuilder, NodeType},
             FileType, ALL_FILE_TYPES,
         },
         blob::{tree::Tree, BlobType, ALL_BLOB_TYPES},
@@ -59,6 +60,7 @@ pub use {
     configfile::ConfigFile,
     indexfile::{IndexBlob, IndexFile, IndexId, IndexPack},
     keyfile::{KeyFile, KeyId},
+    lockfile::{LockFile, LockId},
     packfile::{HeaderEntry, PackHeader, PackHeaderLength, PackHeaderRef, PackId},
     snapshotfile::{DeleteOption, PathList, SnapshotFile, SnapshotId, SnapshotSummary, StringList},
 };
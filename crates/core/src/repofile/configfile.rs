@@ -1,9 +1,11 @@
 use serde_derive::{Deserialize, Serialize};
-use serde_with::skip_serializing_none;
+use serde_with::{base64::Base64, serde_as, skip_serializing_none};
+use zstd::zstd_safe::get_dict_id_from_dict;
 
 use crate::{
     backend::FileType,
     blob::BlobType,
+    chunker::ChunkSizes,
     define_new_id_struct,
     error::{ErrorKind, RusticError, RusticResult},
     impl_repofile,
@@ -35,13 +37,14 @@ pub(super) mod constants {
 define_new_id_struct!(RepositoryId, "repository");
 impl_repofile!(ConfigId, FileType::Config, ConfigFile);
 
+#[serde_as]
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
 /// The config file describes all repository-wide information.
 ///
 /// It is usually saved in the repository as `config`
 pub struct ConfigFile {
-    /// Repository version. Currently 1 and 2 are supported
+    /// Repository version. Currently 1, 2 and 3 are supported
     pub version: u32,
 
     /// The [`Id`] identifying the repsitory
@@ -70,9 +73,18 @@ pub struct ConfigFile {
     ///
     /// `Some(0)` means no compression. If not set, use the default compression:
     /// * for repository version 1, use no compression (as not supported)
-    /// * for repository version 2, use the zstd default compression
+    /// * for repository version 2 and 3, use the zstd default compression
     pub compression: Option<i32>,
 
+    /// Dictionary used for zstd compression and decompression, stored base64-encoded
+    ///
+    /// # Note
+    ///
+    /// This is only supported for repository version 3. Once set, it must not be changed
+    /// as data compressed with it can't be decompressed without it.
+    #[serde_as(as = "Option<Base64>")]
+    pub compression_dictionary: Option<Vec<u8>>,
+
     /// Size of tree packs. This will be enhanced by the `treepack_growfactor` depending on the repository size
     ///
     /// If not set, defaults to 4 MiB
@@ -111,6 +123,21 @@ pub struct ConfigFile {
 
     /// Do an extra verification by decompressing/decrypting all data before uploading to the repository
     pub extra_verify: Option<bool>,
+
+    /// Minimum size of chunks produced by the chunker
+    ///
+    /// If not set, defaults to `512 KiB`
+    pub chunker_min_size: Option<u32>,
+
+    /// Average size of chunks produced by the chunker; must be a power of two
+    ///
+    /// If not set, defaults to `1 MiB`
+    pub chunker_avg_size: Option<u32>,
+
+    /// Maximum size of chunks produced by the chunker
+    ///
+    /// If not set, defaults to `8 MiB`
+    pub chunker_max_size: Option<u32>,
 }
 
 impl ConfigFile {
@@ -155,9 +182,9 @@ impl ConfigFile {
     /// * If the version is not supported
     pub fn zstd(&self) -> RusticResult<Option<i32>> {
         match (self.version, self.compression) {
-            (1, _) | (2, Some(0)) => Ok(None),
-            (2, None) => Ok(Some(0)), // use default (=0) zstd compression
-            (2, Some(c)) => Ok(Some(c)),
+            (1, _) | (2 | 3, Some(0)) => Ok(None),
+            (2 | 3, None) => Ok(Some(0)), // use default (=0) zstd compression
+            (2 | 3, Some(c)) => Ok(Some(c)),
             _ => Err(RusticError::new(
                 ErrorKind::Unsupported,
                 "Config version `{version}` not supported. Please make sure, that you use the correct version.",
@@ -166,6 +193,43 @@ impl ConfigFile {
         }
     }
 
+    /// Get the dictionary used for zstd compression
+    ///
+    /// # Errors
+    ///
+    /// * If a dictionary is set, but the version does not support dictionaries
+    /// * If the dictionary has no id, which is needed to recognize data compressed with it
+    pub fn zstd_dictionary(&self) -> RusticResult<Option<&[u8]>> {
+        match (self.version, &self.compression_dictionary) {
+            (_, None) => Ok(None),
+            (3, Some(dictionary)) if get_dict_id_from_dict(dictionary).is_none() => {
+                Err(RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "The compression dictionary has no dictionary id. Please use a trained dictionary.",
+                ))
+            }
+            (3, Some(dictionary)) => Ok(Some(dictionary)),
+            _ => Err(RusticError::new(
+                ErrorKind::Unsupported,
+                "Compression dictionaries are not supported for config version `{version}`. Please use version 3.",
+            )
+            .attach_context("version", self.version.to_string())),
+        }
+    }
+
+    /// Get the chunk sizes used by the chunker
+    ///
+    /// # Errors
+    ///
+    /// * If the chunk sizes are not valid
+    pub(crate) fn chunk_sizes(&self) -> RusticResult<ChunkSizes> {
+        ChunkSizes::new(
+            self.chunker_min_size,
+            self.chunker_avg_size,
+            self.chunker_max_size,
+        )
+    }
+
     /// Get whether an extra verification (decompressing/decrypting data before writing to the repository) should be performed.
     #[must_use]
     pub fn extra_verify(&self) -> bool {
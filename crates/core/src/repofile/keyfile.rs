@@ -1,4 +1,5 @@
 use chrono::{DateTime, Local};
+use log::warn;
 use rand::{thread_rng, RngCore};
 use scrypt::Params;
 use serde_derive::{Deserialize, Serialize};
@@ -45,13 +46,13 @@ impl_repoid!(KeyId, FileType::Key);
 #[derive(Serialize, Deserialize, Debug)]
 pub struct KeyFile {
     /// Hostname where the key was created
-    hostname: Option<String>,
+    pub(crate) hostname: Option<String>,
 
     /// User which created the key
-    username: Option<String>,
+    pub(crate) username: Option<String>,
 
     /// Creation time of the key
-    created: Option<DateTime<Local>>,
+    pub(crate) created: Option<DateTime<Local>>,
 
     /// The used key derivation function (currently only `scrypt`)
     kdf: String,
@@ -250,7 +251,7 @@ impl KeyFile {
     /// # Returns
     ///
     /// The [`KeyFile`] read from the backend
-    fn from_backend<B: ReadBackend>(be: &B, id: &KeyId) -> RusticResult<Self> {
+    pub(crate) fn from_backend<B: ReadBackend>(be: &B, id: &KeyId) -> RusticResult<Self> {
         let data = be.read_full(FileType::Key, id)?;
 
         serde_json::from_slice(&data).map_err(|err| {
@@ -369,6 +370,9 @@ pub(crate) fn key_from_backend<B: ReadBackend>(
 /// If a key hint is given, only this key is tested.
 /// This is recommended for a large number of keys.
 ///
+/// Key files which can't be read or parsed don't stop the search; if no key fits, the first
+/// such error is returned, as the password might fit to this key.
+///
 /// # Arguments
 ///
 /// * `be` - The backend to use
@@ -377,30 +381,48 @@ pub(crate) fn key_from_backend<B: ReadBackend>(
 ///
 /// # Errors
 ///
-/// * If no suitable key was found
+/// * If no suitable key was found and all keys could be read, an error of kind [`ErrorKind::IncorrectPassword`]
+/// * If no suitable key was found and some keys couldn't be read or parsed
 ///
 /// # Returns
 ///
-/// The found key
+/// The id of the found key file and the key
 pub(crate) fn find_key_in_backend<B: ReadBackend>(
     be: &B,
     passwd: &impl AsRef<[u8]>,
     hint: Option<&KeyId>,
-) -> RusticResult<Key> {
+) -> RusticResult<(KeyId, Key)> {
+    let incorrect_password = || {
+        RusticError::new(
+            ErrorKind::IncorrectPassword,
+            "The password that has been entered, seems to be incorrect. No suitable key found for the given password. Please check your password and try again.",
+        ).attach_error_code("C002")
+    };
+
     if let Some(id) = hint {
-        key_from_backend(be, id, passwd)
-    } else {
-        for id in be.list(FileType::Key)? {
-            match key_from_backend(be, &id.into(), passwd) {
-                Ok(key) => return Ok(key),
-                Err(err) if err.is_code("C001") => continue,
-                err => return err,
+        return match key_from_backend(be, id, passwd) {
+            Ok(key) => Ok((*id, key)),
+            Err(err) if err.is_code("C001") => Err(incorrect_password()),
+            Err(err) => Err(err),
+        };
+    }
+
+    let mut read_error = None;
+    for id in be.list(FileType::Key)? {
+        let id = KeyId::from(id);
+        match key_from_backend(be, &id, passwd) {
+            Ok(key) => return Ok((id, key)),
+            Err(err) if err.is_code("C001") => continue,
+            Err(err) => {
+                warn!("key {id}: reading key failed: {}", err.display_log());
+                _ = read_error.get_or_insert(err);
             }
         }
-
-        Err(RusticError::new(
-            ErrorKind::Password,
-            "The password that has been entered, seems to be incorrect. No suitable key found for the given password. Please check your password and try again.",
-        ).attach_error_code("C002"))
     }
+
+    Err(read_error.map_or_else(incorrect_password, |err| {
+        err.prepend_guidance_line(
+            "No key fits to the given password, but not all keys could be read. The password might belong to an unreadable key.",
+        )
+    }))
 }
@@ -0,0 +1,198 @@
+use chrono::{DateTime, Local};
+use gethostname::gethostname;
+use serde_derive::{Deserialize, Serialize};
+
+use crate::{
+    backend::{node::is_default, FileType},
+    impl_repofile,
+    repofile::RepoFile,
+};
+
+pub(super) mod constants {
+    use chrono::Duration;
+
+    /// Locks which have not been refreshed for this time are considered stale.
+    pub(super) const STALE_TIMEOUT: Duration = Duration::minutes(30);
+}
+
+impl_repofile!(LockId, FileType::Lock, LockFile);
+
+/// Lock files prevent concurrent operations which conflict with each other.
+///
+/// They are usually stored in the repository under `/locks/<ID>`.
+/// The format is compatible with restic, so locks are respected by both rustic and restic.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LockFile {
+    /// Time when the lock was created or last refreshed
+    pub time: DateTime<Local>,
+
+    /// Whether the lock is exclusive
+    pub exclusive: bool,
+
+    /// Hostname of the process holding the lock
+    pub hostname: String,
+
+    /// User of the process holding the lock
+    pub username: String,
+
+    /// Process id of the process holding the lock
+    pub pid: u32,
+
+    /// Unix uid of the user holding the lock
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub uid: u32,
+
+    /// Unix gid of the user holding the lock
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub gid: u32,
+}
+
+impl LockFile {
+    /// Create a new [`LockFile`] for the current process.
+    ///
+    /// # Arguments
+    ///
+    /// * `exclusive` - Whether the lock is exclusive
+    pub(crate) fn new(exclusive: bool) -> Self {
+        #[cfg(not(windows))]
+        let (username, uid, gid) = {
+            let uid = nix::unistd::Uid::current();
+            let username = nix::unistd::User::from_uid(uid)
+                .ok()
+                .flatten()
+                .map(|user| user.name)
+                .unwrap_or_default();
+            (username, uid.as_raw(), nix::unistd::Gid::current().as_raw())
+        };
+        #[cfg(windows)]
+        let (username, uid, gid) = (std::env::var("USERNAME").unwrap_or_default(), 0, 0);
+
+        Self {
+            time: Local::now(),
+            exclusive,
+            hostname: gethostname().to_string_lossy().to_string(),
+            username,
+            pid: std::process::id(),
+            uid,
+            gid,
+        }
+    }
+
+    /// Returns whether the lock is held by the current process.
+    #[must_use]
+    pub fn is_own(&self) -> bool {
+        self.pid == std::process::id() && self.hostname == gethostname().to_string_lossy()
+    }
+
+    /// Returns whether the lock is stale, i.e. whether the process holding it is no longer running.
+    ///
+    /// A lock is stale if it was not refreshed within the last 30 minutes. Additionally, a lock
+    /// from the current host is stale if no process with its pid is running.
+    #[must_use]
+    pub fn is_stale(&self) -> bool {
+        self.is_stale_at(Local::now(), &gethostname().to_string_lossy())
+    }
+
+    /// Returns whether the lock is stale at the given time when checked from the given host.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The time to check against
+    /// * `hostname` - The host on which the check is done
+    pub(crate) fn is_stale_at(&self, now: DateTime<Local>, hostname: &str) -> bool {
+        if now - self.time > constants::STALE_TIMEOUT {
+            return true;
+        }
+        self.hostname == hostname && !process_exists(self.pid)
+    }
+
+    /// Returns whether this lock conflicts with a lock of the given type.
+    ///
+    /// Exclusive locks conflict with all other locks, shared locks only with exclusive locks.
+    ///
+    /// # Arguments
+    ///
+    /// * `exclusive` - Whether the other lock is exclusive
+    #[must_use]
+    pub const fn conflicts_with(&self, exclusive: bool) -> bool {
+        self.exclusive || exclusive
+    }
+}
+
+/// Returns whether a process with the given pid is running on the current host.
+#[cfg(not(windows))]
+fn process_exists(pid: u32) -> bool {
+    use nix::{errno::Errno, sys::signal::kill, unistd::Pid};
+
+    // such pids can't be checked, so assume the process is running
+    let Ok(pid) = i32::try_from(pid) else {
+        return true;
+    };
+    // signal 0 only checks whether the process exists; EPERM means it exists but belongs to another user
+    !matches!(kill(Pid::from_raw(pid), None), Err(Errno::ESRCH))
+}
+
+/// Returns whether a process with the given pid is running on the current host.
+///
+/// This can't be checked on Windows, so processes are assumed to be running.
+#[cfg(windows)]
+const fn process_exists(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+
+    fn lock(hostname: &str, pid: u32, age: Duration) -> LockFile {
+        LockFile {
+            time: Local::now() - age,
+            hostname: hostname.to_string(),
+            pid,
+            ..LockFile::new(true)
+        }
+    }
+
+    #[test]
+    fn stale_locks_are_detected() {
+        let now = Local::now();
+        let own = LockFile::new(false);
+        assert!(own.is_own());
+        assert!(!own.is_stale_at(now, &own.hostname));
+
+        // too old
+        assert!(lock("other", 1, Duration::minutes(31)).is_stale_at(now, "host"));
+        // other host, can't check pid
+        assert!(!lock("other", u32::MAX, Duration::minutes(1)).is_stale_at(now, "host"));
+        // same host, but no such process
+        #[cfg(not(windows))]
+        assert!(
+            lock("host", i32::MAX.unsigned_abs(), Duration::minutes(1)).is_stale_at(now, "host")
+        );
+        // same host, but the pid can't be checked
+        assert!(!lock("host", u32::MAX, Duration::minutes(1)).is_stale_at(now, "host"));
+    }
+
+    #[test]
+    fn conflicts_are_detected() {
+        let exclusive = LockFile::new(true);
+        let shared = LockFile::new(false);
+        assert!(exclusive.conflicts_with(false));
+        assert!(exclusive.conflicts_with(true));
+        assert!(shared.conflicts_with(true));
+        assert!(!shared.conflicts_with(false));
+    }
+
+    #[test]
+    fn restic_lock_can_be_parsed() {
+        let json = r#"{"time":"2024-01-01T12:00:00.123456789+01:00","exclusive":true,"hostname":"host","username":"user","pid":42,"uid":1000,"gid":100}"#;
+        let lock: LockFile = serde_json::from_str(json).unwrap();
+        assert!(lock.exclusive);
+        assert_eq!(lock.hostname, "host");
+        assert_eq!(lock.pid, 42);
+        assert_eq!(lock.uid, 1000);
+    }
+}
@@ -1,7 +1,9 @@
 use std::{
+    borrow::Cow,
     cmp::Ordering,
     collections::{BTreeMap, BTreeSet},
     fmt::{self, Display},
+    io::BufRead,
     path::{Path, PathBuf},
     str::FromStr,
 };
@@ -12,11 +14,15 @@ use clap::ValueHint;
 use derive_setters::Setters;
 use dunce::canonicalize;
 use gethostname::gethostname;
+use globset::GlobBuilder;
 use itertools::Itertools;
-use log::info;
+use log::{info, warn};
 use path_dedot::ParseDot;
 use serde_derive::{Deserialize, Serialize};
 use serde_with::{serde_as, skip_serializing_none, DisplayFromStr};
+use strum::{Display as StrumDisplay, EnumString};
+use unicode_normalization::UnicodeNormalization;
+use walkdir::WalkDir;
 
 use crate::{
     backend::{decrypt::DecryptReadBackend, FileType, FindInBackend},
@@ -115,6 +121,22 @@ pub struct SnapshotOptions {
     #[cfg_attr(feature = "clap", clap(long))]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
     pub command: Option<String>,
+
+    /// Additional custom metadata to add to the snapshot
+    #[cfg_attr(feature = "clap", clap(skip))]
+    #[cfg_attr(feature = "merge", merge(strategy = merge_extra))]
+    pub extra: BTreeMap<String, serde_json::Value>,
+}
+
+/// Merge custom metadata; entries already present in `left` take precedence.
+#[cfg(feature = "merge")]
+fn merge_extra(
+    left: &mut BTreeMap<String, serde_json::Value>,
+    right: BTreeMap<String, serde_json::Value>,
+) {
+    for (key, value) in right {
+        _ = left.entry(key).or_insert(value);
+    }
 }
 
 impl SnapshotOptions {
@@ -143,6 +165,26 @@ impl SnapshotOptions {
         Ok(self)
     }
 
+    /// Add a single tag to this [`SnapshotOptions`] without splitting it at commas
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag to add
+    ///
+    /// # Errors
+    ///
+    /// * If the tag is not valid, see [`StringList::try_push`]
+    ///
+    /// # Returns
+    ///
+    /// The modified [`SnapshotOptions`]
+    pub fn add_tag_checked(mut self, tag: &str) -> RusticResult<Self> {
+        let mut tags = StringList::default();
+        tags.try_push(tag)?;
+        self.tags.push(tags);
+        Ok(self)
+    }
+
     /// Create a new [`SnapshotFile`] using this `SnapshotOption`s
     ///
     /// # Errors
@@ -379,6 +421,10 @@ pub struct SnapshotFile {
     /// A description of what is contained in this snapshot
     pub description: Option<String>,
 
+    /// Additional custom metadata, e.g. information about the build or pipeline which created this snapshot
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, serde_json::Value>,
+
     /// The snapshot Id (not stored within the JSON)
     #[serde(default, skip_serializing_if = "Id::is_null")]
     pub id: SnapshotId,
@@ -406,6 +452,7 @@ impl Default for SnapshotFile {
             delete: DeleteOption::default(),
             summary: Option::default(),
             description: Option::default(),
+            extra: BTreeMap::default(),
             id: SnapshotId::default(),
         }
     }
@@ -481,6 +528,7 @@ impl SnapshotFile {
                 ..Default::default()
             }),
             description: opts.description.clone(),
+            extra: opts.extra.clone(),
             ..Default::default()
         };
 
@@ -506,13 +554,66 @@ impl SnapshotFile {
     /// # Arguments
     ///
     /// * `tuple` - A tuple of the [`Id`] and the [`RepoFile`] to use
-    fn set_id(tuple: (SnapshotId, Self)) -> Self {
+    pub(crate) fn set_id(tuple: (SnapshotId, Self)) -> Self {
         let (id, mut snap) = tuple;
         snap.id = id;
         _ = snap.original.get_or_insert(id);
         snap
     }
 
+    /// Serialize the [`SnapshotFile`] to (pretty-printed) JSON, e.g. to edit it externally.
+    ///
+    /// The JSON is the content of the snapshot file as saved in the repository, i.e. it doesn't contain
+    /// the `id` of the snapshot. Use [`SnapshotFile::from_json_with_id`] to read the JSON back.
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshot could not be serialized.
+    pub fn to_json_string(&self) -> RusticResult<String> {
+        let mut snap = self.clone();
+        snap.id = SnapshotId::default();
+        serde_json::to_string_pretty(&snap).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Internal,
+                "Serializing snapshot `{id}` to JSON failed.",
+                err,
+            )
+            .attach_context("id", self.id.to_string())
+        })
+    }
+
+    /// Deserialize a [`SnapshotFile`] from JSON, e.g. created by [`SnapshotFile::to_json_string`].
+    ///
+    /// The `id` is set to the given id, regardless of an `id` contained in the JSON. Like for
+    /// snapshots read from the repository, `original` is kept if it is set, else it is set to `id`.
+    ///
+    /// # Arguments
+    ///
+    /// * `json` - The JSON to deserialize
+    /// * `id` - The id of the snapshot the JSON belongs to
+    ///
+    /// # Errors
+    ///
+    /// * If the JSON is no valid snapshot.
+    ///
+    /// # Notes
+    ///
+    /// Saving the snapshot using [`Repository::save_snapshots`] creates a new snapshot file with a new id,
+    /// as the id is the hash of the contents. The snapshot with the old `id` is not removed and still exists.
+    ///
+    /// [`Repository::save_snapshots`]: crate::Repository::save_snapshots
+    pub fn from_json_with_id(json: &str, id: SnapshotId) -> RusticResult<Self> {
+        let snap = serde_json::from_str(json).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InvalidInput,
+                "Deserializing snapshot `{id}` from JSON failed. Please check the JSON.",
+                err,
+            )
+            .attach_context("id", id.to_string())
+        })?;
+        Ok(Self::set_id((id, snap)))
+    }
+
     /// Get a [`SnapshotFile`] from the backend
     ///
     /// # Arguments
@@ -674,6 +775,9 @@ impl SnapshotFile {
             .filter(|id| !snaps.contains_key(&SnapshotId::from(**id)))
             .copied()
             .collect();
+        // report progress for all snapshots, already present ones count as done
+        p.set_length(ids.len() as u64);
+        p.inc((ids.len() - missing_ids.len()) as u64);
         for res in be.stream_list::<Self>(&missing_ids, p)? {
             let (id, snap) = res?;
             if filter(&snap) {
@@ -966,6 +1070,146 @@ impl Ord for SnapshotFile {
     }
 }
 
+/// [`SnapshotSort`] determines the order in which snapshots are sorted.
+///
+/// Snapshots with equal host or label are sorted by time; snapshots with equal time by id.
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, StrumDisplay, EnumString, Serialize, Deserialize,
+)]
+#[strum(serialize_all = "kebab-case", ascii_case_insensitive)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum SnapshotSort {
+    /// Oldest snapshots first
+    #[default]
+    TimeAsc,
+    /// Newest snapshots first
+    TimeDesc,
+    /// Sort by hostname in ascending order
+    HostAsc,
+    /// Sort by hostname in descending order
+    HostDesc,
+    /// Sort by label in ascending order
+    LabelAsc,
+    /// Sort by label in descending order
+    LabelDesc,
+}
+
+impl SnapshotSort {
+    /// Compare two snapshots with respect to this sort order.
+    ///
+    /// # Arguments
+    ///
+    /// * `sn1` - The first snapshot
+    /// * `sn2` - The second snapshot
+    #[must_use]
+    pub fn compare(self, sn1: &SnapshotFile, sn2: &SnapshotFile) -> Ordering {
+        let by_time = || sn1.time.cmp(&sn2.time).then_with(|| sn1.id.cmp(&sn2.id));
+        match self {
+            Self::TimeAsc => by_time(),
+            Self::TimeDesc => by_time().reverse(),
+            Self::HostAsc => sn1.hostname.cmp(&sn2.hostname).then_with(by_time),
+            Self::HostDesc => sn2.hostname.cmp(&sn1.hostname).then_with(by_time),
+            Self::LabelAsc => sn1.label.cmp(&sn2.label).then_with(by_time),
+            Self::LabelDesc => sn2.label.cmp(&sn1.label).then_with(by_time),
+        }
+    }
+}
+
+/// [`SnapshotFilter`] selects snapshots by their host, label, paths, tags and time.
+///
+/// Criteria which are not set match all snapshots. Use [`SnapshotFilter::as_predicate`] to pass
+/// the filter to e.g. [`Repository::get_matching_snapshots`].
+///
+/// [`Repository::get_matching_snapshots`]: crate::Repository::get_matching_snapshots
+#[serde_as]
+#[cfg_attr(feature = "merge", derive(conflate::Merge))]
+#[cfg_attr(feature = "clap", derive(clap::Parser))]
+#[skip_serializing_none]
+#[derive(Deserialize, Serialize, Clone, Default, Debug, PartialEq, Eq, Setters)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+#[setters(into)]
+#[non_exhaustive]
+pub struct SnapshotFilter {
+    /// Hostname to filter (can be specified multiple times)
+    #[cfg_attr(feature = "clap", clap(long = "filter-host", value_name = "HOSTNAME"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub host: Vec<String>,
+
+    /// Label to filter (can be specified multiple times)
+    #[cfg_attr(feature = "clap", clap(long = "filter-label", value_name = "LABEL"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub label: Vec<String>,
+
+    /// Path list to filter (can be specified multiple times)
+    #[cfg_attr(
+        feature = "clap",
+        clap(long = "filter-paths", value_name = "PATH[,PATH,..]")
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<StringList>,
+
+    /// Tag list to filter (can be specified multiple times)
+    #[cfg_attr(
+        feature = "clap",
+        clap(long = "filter-tags", value_name = "TAG[,TAG,..]")
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::vec::overwrite_empty))]
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<StringList>,
+
+    /// How to compare tags when filtering by tags (default: exact)
+    #[cfg_attr(
+        feature = "clap",
+        clap(long = "filter-tags-match", value_name = "MODE")
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub tags_match: Option<TagMatchMode>,
+
+    /// Only use snapshots taken at or after the given time
+    #[cfg_attr(feature = "clap", clap(long = "after", value_name = "TIME"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub time_after: Option<DateTime<Local>>,
+
+    /// Only use snapshots taken before the given time
+    #[cfg_attr(feature = "clap", clap(long = "before", value_name = "TIME"))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub time_before: Option<DateTime<Local>>,
+}
+
+impl SnapshotFilter {
+    /// Returns whether the given snapshot matches this filter.
+    ///
+    /// Paths are matched like [`StringList::matches`], tags like [`StringList::matches_with`]
+    /// using [`SnapshotFilter::tags_match`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sn` - The snapshot to check
+    #[must_use]
+    pub fn matches(&self, sn: &SnapshotFile) -> bool {
+        (self.host.is_empty() || self.host.contains(&sn.hostname))
+            && (self.label.is_empty() || self.label.contains(&sn.label))
+            && sn.paths.matches(&self.paths)
+            && sn
+                .tags
+                .matches_with(&self.tags, self.tags_match.unwrap_or_default())
+            && self.time_after.map_or(true, |after| sn.time >= after)
+            && self.time_before.map_or(true, |before| sn.time < before)
+    }
+
+    /// Returns this filter as predicate which can be passed to the snapshot getters of a repository.
+    pub fn as_predicate(&self) -> impl FnMut(&SnapshotFile) -> bool + '_ {
+        |sn| self.matches(sn)
+    }
+}
+
 /// [`SnapshotGroupCriterion`] determines how to group snapshots.
 ///
 /// `Default` grouping is by hostname, label and paths.
@@ -1050,7 +1294,7 @@ impl Display for SnapshotGroupCriterion {
 }
 
 #[skip_serializing_none]
-#[derive(Serialize, Default, Debug, PartialEq, Eq)]
+#[derive(Serialize, Default, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[non_exhaustive]
 /// [`SnapshotGroup`] specifies the group after a grouping using [`SnapshotGroupCriterion`].
 pub struct SnapshotGroup {
@@ -1113,7 +1357,99 @@ impl SnapshotGroup {
     }
 }
 
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+/// [`GroupSummary`] contains aggregated information about the snapshots within a [`SnapshotGroup`].
+pub struct GroupSummary {
+    /// Number of snapshots in the group
+    pub count: usize,
+
+    /// Time of the oldest snapshot in the group
+    pub oldest: DateTime<Local>,
+
+    /// Time of the newest snapshot in the group
+    pub newest: DateTime<Local>,
+
+    /// Sum of the total processed bytes of all snapshots in the group which contain a summary
+    pub total_size: u64,
+}
+
+impl GroupSummary {
+    /// Creates a [`GroupSummary`] containing a single [`SnapshotFile`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sn` - The [`SnapshotFile`] to start with
+    #[must_use]
+    pub fn from_snapshot(sn: &SnapshotFile) -> Self {
+        Self {
+            count: 1,
+            oldest: sn.time,
+            newest: sn.time,
+            total_size: Self::size(sn),
+        }
+    }
+
+    /// Adds a [`SnapshotFile`] to this [`GroupSummary`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sn` - The [`SnapshotFile`] to add
+    pub fn add(&mut self, sn: &SnapshotFile) {
+        self.count += 1;
+        self.oldest = self.oldest.min(sn.time);
+        self.newest = self.newest.max(sn.time);
+        self.total_size += Self::size(sn);
+    }
+
+    /// The size of a [`SnapshotFile`] if it is known from its summary.
+    fn size(sn: &SnapshotFile) -> u64 {
+        sn.summary
+            .as_ref()
+            .map_or(0, |summary| summary.total_bytes_processed)
+    }
+}
+
+/// [`TagMatchMode`] determines how the strings of [`StringList`]s are compared when matching them.
+///
+/// The stored strings are never changed, only the comparison is affected.
+#[cfg_attr(feature = "clap", derive(clap::ValueEnum))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum TagMatchMode {
+    /// Strings must be identical
+    #[default]
+    Exact,
+    /// Strings are compared ignoring case
+    IgnoreCase,
+    /// Strings are compared after Unicode NFC normalization, e.g. composed and decomposed umlauts are equal
+    Normalize,
+    /// Strings are compared after Unicode NFC normalization and ignoring case
+    NormalizeIgnoreCase,
+}
+
+impl TagMatchMode {
+    /// Get the form of a string which is compared in this mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `s` - The string to compare
+    fn key(self, s: &str) -> Cow<'_, str> {
+        match self {
+            Self::Exact => Cow::Borrowed(s),
+            Self::IgnoreCase => Cow::Owned(s.to_lowercase()),
+            Self::Normalize => Cow::Owned(s.nfc().collect()),
+            Self::NormalizeIgnoreCase => Cow::Owned(s.nfc().collect::<String>().to_lowercase()),
+        }
+    }
+}
+
 /// `StringList` is a rustic-internal list of Strings. It is used within [`SnapshotFile`]
+///
+/// Parsing a `StringList` from a string splits it at commas, so single entries
+/// containing a comma cannot be created this way. Use [`StringList::try_push`]
+/// to add validated single entries.
 #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct StringList(pub(crate) BTreeSet<String>);
 
@@ -1152,6 +1488,22 @@ impl StringList {
         sl.0.is_subset(&self.0)
     }
 
+    /// Returns whether a [`StringList`] contains all Strings of another [`StringList`],
+    /// comparing the Strings using the given [`TagMatchMode`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sl` - The [`StringList`] to check
+    /// * `mode` - How to compare the Strings
+    #[must_use]
+    pub fn contains_all_with(&self, sl: &Self, mode: TagMatchMode) -> bool {
+        if mode == TagMatchMode::Exact {
+            return self.contains_all(sl);
+        }
+        let keys: BTreeSet<_> = self.0.iter().map(|s| mode.key(s)).collect();
+        sl.0.iter().all(|s| keys.contains(&mode.key(s)))
+    }
+
     /// Returns whether a [`StringList`] matches a list of [`StringList`]s,
     /// i.e. whether it contains all Strings of one the given [`StringList`]s.
     ///
@@ -1163,6 +1515,18 @@ impl StringList {
         sls.is_empty() || sls.iter().any(|sl| self.contains_all(sl))
     }
 
+    /// Returns whether a [`StringList`] matches a list of [`StringList`]s like [`StringList::matches`],
+    /// comparing the Strings using the given [`TagMatchMode`].
+    ///
+    /// # Arguments
+    ///
+    /// * `sls` - The list of [`StringList`]s to check
+    /// * `mode` - How to compare the Strings
+    #[must_use]
+    pub fn matches_with(&self, sls: &[Self], mode: TagMatchMode) -> bool {
+        sls.is_empty() || sls.iter().any(|sl| self.contains_all_with(sl, mode))
+    }
+
     /// Add a String to a [`StringList`].
     ///
     /// # Arguments
@@ -1172,6 +1536,41 @@ impl StringList {
         _ = self.0.insert(s);
     }
 
+    /// Add a single tag to a [`StringList`] after validating it.
+    ///
+    /// # Arguments
+    ///
+    /// * `tag` - The tag to add
+    ///
+    /// # Errors
+    ///
+    /// * If the tag is empty or only consists of whitespace
+    /// * If the tag has leading or trailing whitespace
+    /// * If the tag contains a comma
+    pub fn try_push(&mut self, tag: &str) -> RusticResult<()> {
+        let reason = if tag.trim().is_empty() {
+            Some("must not be empty")
+        } else if tag.trim() != tag {
+            Some("must not have leading or trailing whitespace")
+        } else if tag.contains(',') {
+            Some("must not contain a comma")
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            return Err(RusticError::new(
+                ErrorKind::InvalidInput,
+                "Invalid tag `{tag}`: The tag {reason}.",
+            )
+            .attach_context("tag", tag)
+            .attach_context("reason", reason));
+        }
+
+        _ = self.0.insert(tag.to_string());
+        Ok(())
+    }
+
     /// Add all Strings from another [`StringList`] to this [`StringList`].
     ///
     /// # Arguments
@@ -1251,6 +1650,11 @@ impl<'str> IntoIterator for &'str StringList {
     }
 }
 
+/// Returns whether the given string contains a glob pattern, i.e. `*`, `?` or a `[...]` class.
+fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?']) || s.find('[').is_some_and(|start| s[start..].contains(']'))
+}
+
 /// `PathList` is a rustic-internal list of `PathBuf`s. It is used in the [`crate::Repository::backup`] command.
 #[derive(Default, Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub struct PathList(Vec<PathBuf>);
@@ -1287,6 +1691,128 @@ impl PathList {
         Ok(Self(vec![source.into()]))
     }
 
+    /// Create a `PathList` from a reader containing one path per line.
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The reader to read the paths from
+    ///
+    /// # Errors
+    ///
+    /// * If reading from the reader failed
+    pub fn from_reader(reader: impl BufRead) -> RusticResult<Self> {
+        let mut paths = Vec::new();
+        for line in reader.lines() {
+            let line = line.map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::InputOutput,
+                    "Failed to read path list. Please check the input.",
+                    err,
+                )
+            })?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            paths.push(line.into());
+        }
+        Ok(Self(paths))
+    }
+
+    /// Expand glob patterns like `*`, `?`, `[...]` or `**` against the filesystem.
+    ///
+    /// Paths without glob patterns and paths which exist literally (e.g. a directory named `[1]`)
+    /// are kept as they are. Patterns are replaced by the sorted list of matching paths; patterns
+    /// matching no paths are reported as a warning and dropped.
+    ///
+    /// # Note
+    ///
+    /// Globs should be expanded before calling [`PathList::sanitize`] which then removes dots and merges the
+    /// resulting paths.
+    ///
+    /// # Errors
+    ///
+    /// * If a glob pattern is not valid
+    /// * If a directory could not be read while matching a glob pattern
+    pub fn expand_globs(self) -> RusticResult<Self> {
+        let mut paths = Vec::new();
+        for path in self.0 {
+            let pattern = path.to_string_lossy().to_string();
+            if !is_glob(&pattern) || path.symlink_metadata().is_ok() {
+                paths.push(path);
+                continue;
+            }
+
+            let glob = GlobBuilder::new(&pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|err| {
+                    RusticError::with_source(
+                        ErrorKind::InvalidInput,
+                        "Failed to parse glob pattern `{pattern}`. Please check the pattern.",
+                        err,
+                    )
+                    .attach_context("pattern", pattern.clone())
+                })?
+                .compile_matcher();
+
+            // walk the path from the last component without glob pattern
+            let base: PathBuf = path
+                .components()
+                .take_while(|c| !is_glob(&c.as_os_str().to_string_lossy()))
+                .collect();
+            let (root, strip) = if base.as_os_str().is_empty() {
+                (Path::new("."), true)
+            } else {
+                (base.as_path(), false)
+            };
+            let mut walker = WalkDir::new(root).min_depth(1).sort_by_file_name();
+            if !pattern.contains("**") {
+                walker = walker.max_depth(path.components().count() - base.components().count());
+            }
+
+            let mut matched = false;
+            for entry in walker {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    // the base directory doesn't exist, so nothing matches
+                    Err(err)
+                        if err.depth() == 0
+                            && err.io_error().map(std::io::Error::kind)
+                                == Some(std::io::ErrorKind::NotFound) =>
+                    {
+                        break;
+                    }
+                    Err(err) => {
+                        return Err(RusticError::with_source(
+                            ErrorKind::InputOutput,
+                            "Failed to read the filesystem while expanding glob pattern `{pattern}`.",
+                            err,
+                        )
+                        .attach_context("pattern", pattern));
+                    }
+                };
+                let path = entry.path();
+                let path = if strip {
+                    path.strip_prefix(".").unwrap_or(path)
+                } else {
+                    path
+                };
+                if glob.is_match(path) {
+                    matched = true;
+                    paths.push(path.to_path_buf());
+                }
+            }
+
+            if !matched {
+                warn!("glob pattern {pattern} matches no paths, ignoring it.");
+            }
+        }
+        Ok(Self(paths))
+    }
+
     /// Number of paths in the `PathList`.
     #[must_use]
     pub fn len(&self) -> usize {
@@ -1411,6 +1937,90 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    #[case("")]
+    #[case("  ")]
+    #[case(" abc")]
+    #[case("abc ")]
+    #[case("abc,def")]
+    fn try_push_rejects_invalid_tags(#[case] tag: &str) {
+        let mut tags = StringList::default();
+        assert!(tags.try_push(tag).is_err());
+        assert_eq!(tags, StringList::default());
+    }
+
+    #[test]
+    fn add_tag_checked_does_not_split() -> Result<()> {
+        let opts = SnapshotOptions::default().add_tag_checked("a b")?;
+        assert!(opts.clone().add_tag_checked("a,b").is_err());
+        let snap = SnapshotFile::from_options(&opts)?;
+        assert_eq!(snap.tags.formatln(), "a b");
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_filter_matches() -> Result<()> {
+        let time = |s: &str| -> Result<DateTime<Local>> { Ok(s.parse()?) };
+        let sn = SnapshotFile {
+            hostname: "host".to_string(),
+            label: "label".to_string(),
+            tags: StringList::from_str("a,b")?,
+            time: time("2024-06-01T12:00:00Z")?,
+            ..Default::default()
+        };
+
+        assert!(SnapshotFilter::default().matches(&sn));
+        assert!(SnapshotFilter::default()
+            .host(vec!["other".to_string(), "host".to_string()])
+            .matches(&sn));
+        assert!(!SnapshotFilter::default()
+            .label(vec!["other".to_string()])
+            .matches(&sn));
+        assert!(SnapshotFilter::default()
+            .tags(vec![StringList::from_str("c")?, StringList::from_str("b")?])
+            .matches(&sn));
+        assert!(!SnapshotFilter::default()
+            .tags(vec![StringList::from_str("a,c")?])
+            .matches(&sn));
+
+        let filter = SnapshotFilter::default()
+            .time_after(time("2024-06-01T12:00:00Z")?)
+            .time_before(time("2024-06-02T00:00:00Z")?);
+        assert!(filter.matches(&sn));
+        let filter = SnapshotFilter::default().time_before(time("2024-06-01T12:00:00Z")?);
+        assert!(!filter.matches(&sn));
+        Ok(())
+    }
+
+    #[rstest]
+    #[case("time-asc", SnapshotSort::TimeAsc)]
+    #[case("time-desc", SnapshotSort::TimeDesc)]
+    #[case("Host-Asc", SnapshotSort::HostAsc)]
+    #[case("label-desc", SnapshotSort::LabelDesc)]
+    fn snapshot_sort_fromstr(#[case] input: &str, #[case] expected: SnapshotSort) -> Result<()> {
+        assert_eq!(SnapshotSort::from_str(input)?, expected);
+        assert_eq!(expected.to_string(), input.to_lowercase());
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_sort_compare() {
+        let snap = |hostname: &str, secs| SnapshotFile {
+            hostname: hostname.to_string(),
+            time: DateTime::from_timestamp(secs, 0).unwrap().into(),
+            ..Default::default()
+        };
+        let mut snaps = vec![snap("b", 1), snap("a", 3), snap("b", 2)];
+
+        snaps.sort_by(|sn1, sn2| SnapshotSort::TimeDesc.compare(sn1, sn2));
+        let hosts: Vec<_> = snaps.iter().map(|sn| sn.hostname.as_str()).collect();
+        assert_eq!(hosts, ["a", "b", "b"]);
+
+        snaps.sort_by(|sn1, sn2| SnapshotSort::HostDesc.compare(sn1, sn2));
+        let times: Vec<_> = snaps.iter().map(|sn| sn.time.timestamp()).collect();
+        assert_eq!(times, [1, 2, 3]);
+    }
+
     #[rstest]
     #[case("host,label,paths", true, true, true, false)]
     #[case("host", true, false, false, false)]
@@ -1439,4 +2049,160 @@ mod tests {
         let result = path_list.to_string();
         assert_eq!(expected, &result);
     }
+
+    #[test]
+    fn test_extra_roundtrip() -> Result<()> {
+        let extra: BTreeMap<_, _> = [
+            ("commit".to_string(), serde_json::json!("abc123")),
+            ("pipeline".to_string(), serde_json::json!({ "id": 42 })),
+        ]
+        .into();
+        let snap = SnapshotFile::from_options(&SnapshotOptions::default().extra(extra.clone()))?;
+        assert_eq!(snap.extra, extra);
+
+        let json = serde_json::to_string(&snap)?;
+        let snap: SnapshotFile = serde_json::from_str(&json)?;
+        assert_eq!(snap.extra, extra);
+
+        // empty extra is not serialized
+        let json = serde_json::to_string(&SnapshotFile::default())?;
+        assert!(!json.contains("extra"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_roundtrip_with_id() -> Result<()> {
+        let id = SnapshotId::from(Id::random());
+        let mut snap =
+            SnapshotFile::from_options(&SnapshotOptions::default().label("test".to_string()))?;
+        snap.id = id;
+
+        let json = snap.to_json_string()?;
+        assert!(!json.contains(&id.to_hex().to_string()));
+
+        let edited = json.replace("\"test\"", "\"edited\"");
+        let new_snap = SnapshotFile::from_json_with_id(&edited, id)?;
+        assert_eq!(new_snap.id, id);
+        assert_eq!(new_snap.original, Some(id));
+        assert_eq!(new_snap.label, "edited");
+        assert_eq!(new_snap.time, snap.time);
+
+        // an existing original is preserved
+        let original = SnapshotId::from(Id::random());
+        snap.original = Some(original);
+        let new_snap = SnapshotFile::from_json_with_id(&snap.to_json_string()?, id)?;
+        assert_eq!(new_snap.id, id);
+        assert_eq!(new_snap.original, Some(original));
+
+        assert!(SnapshotFile::from_json_with_id("{}", id).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_deserialize_without_extra() -> Result<()> {
+        // snapshot as created by restic
+        let json = r#"{"time":"2024-01-01T12:00:00.000000000+01:00","tree":"0000000000000000000000000000000000000000000000000000000000000000","paths":["/home"],"hostname":"host","username":"user"}"#;
+        let snap: SnapshotFile = serde_json::from_str(json)?;
+        assert!(snap.extra.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_list_from_reader() -> Result<()> {
+        let input = "# backup sources\n/home\n\n  # indented comment\nsrc/lib.rs\n";
+        let path_list = PathList::from_reader(input.as_bytes())?;
+        assert_eq!(path_list, PathList::from_iter(["/home", "src/lib.rs"]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_list_expand_globs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for file in ["a.txt", "b.txt", "c.log", "sub/d.txt"] {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, "")?;
+        }
+        let dir = dir.path();
+
+        let path_list = PathList::from_iter([
+            dir.join("*.txt"),
+            dir.join("**/d.txt"),
+            dir.join("*.none"),
+            dir.join("c.log"),
+        ])
+        .expand_globs()?;
+        assert_eq!(
+            path_list,
+            PathList::from_iter([
+                dir.join("a.txt"),
+                dir.join("b.txt"),
+                dir.join("sub/d.txt"),
+                dir.join("c.log"),
+            ])
+        );
+
+        // a glob matching no file is dropped
+        assert!(PathList::from_iter([dir.join("none/*")])
+            .expand_globs()?
+            .is_empty());
+
+        // existing paths and brackets without a class are taken literally
+        for file in ["[1]", "x[y"] {
+            std::fs::write(dir.join(file), "")?;
+        }
+        let literal = PathList::from_iter([dir.join("[1]"), dir.join("x[y"), dir.join("z[")]);
+        assert_eq!(literal.clone().expand_globs()?, literal);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(TagMatchMode::Exact, "Prod", false)]
+    #[case(TagMatchMode::IgnoreCase, "Prod", true)]
+    #[case(TagMatchMode::Normalize, "Prod", false)]
+    #[case(TagMatchMode::NormalizeIgnoreCase, "PROD", true)]
+    #[case(TagMatchMode::Exact, "prod", true)]
+    fn test_tags_match_casing(
+        #[case] mode: TagMatchMode,
+        #[case] filter: &str,
+        #[case] expected: bool,
+    ) -> Result<()> {
+        let tags = StringList::from_str("prod,daily")?;
+        let filter = [StringList::from_str(filter)?];
+        assert_eq!(tags.matches_with(&filter, mode), expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case(TagMatchMode::Exact, false)]
+    #[case(TagMatchMode::IgnoreCase, false)]
+    #[case(TagMatchMode::Normalize, true)]
+    #[case(TagMatchMode::NormalizeIgnoreCase, true)]
+    fn test_tags_match_unicode_normalization(
+        #[case] mode: TagMatchMode,
+        #[case] expected: bool,
+    ) -> Result<()> {
+        // composed "é" vs. "e" followed by a combining acute accent
+        let tags = StringList::from_str("caf\u{e9}")?;
+        let filter = [StringList::from_str("cafe\u{301}")?];
+        assert_eq!(tags.matches_with(&filter, mode), expected);
+        // stored tags are not modified
+        assert_eq!(tags.to_string(), "caf\u{e9}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_filter_tags_match() -> Result<()> {
+        let snap = SnapshotFile::from_options(
+            &SnapshotOptions::default().tags(vec![StringList::from_str("Prod")?]),
+        )?;
+        let mut filter = SnapshotFilter {
+            tags: vec![StringList::from_str("prod")?],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&snap));
+        filter.tags_match = Some(TagMatchMode::IgnoreCase);
+        assert!(filter.matches(&snap));
+        Ok(())
+    }
 }
@@ -1,18 +1,25 @@
 pub(crate) mod command_input;
+pub(crate) mod lock;
 pub(crate) mod warm_up;
 
 use std::{
     cmp::Ordering,
     fs::File,
-    io::{BufRead, BufReader, Write},
+    io::{BufRead, BufReader, Read, Write},
+    ops::Range,
     path::{Path, PathBuf},
     process::{Command, Stdio},
     sync::Arc,
 };
 
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+
 use bytes::Bytes;
+use chrono::Duration;
 use derive_setters::Setters;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
+use secrecy::{zeroize::Zeroize, ExposeSecret, SecretString};
 use serde_with::{serde_as, DisplayFromStr};
 
 use crate::{
@@ -20,33 +27,51 @@ use crate::{
         cache::{Cache, CachedBackend},
         decrypt::{DecryptBackend, DecryptReadBackend, DecryptWriteBackend},
         hotcold::HotColdBackend,
-        local_destination::LocalDestination,
+        mirror::MirrorBackend,
         node::Node,
+        read_only::ReadOnlyBackend,
         warm_up::WarmUpAccessBackend,
-        FileType, ReadBackend, WriteBackend,
+        FileType, ReadBackend, RestoreDestination, SelfTestResult, WriteBackend,
     },
     blob::{
-        tree::{FindMatches, FindNode, NodeStreamer, TreeId, TreeStreamerOptions as LsOptions},
+        tree::{
+            FindMatches, FindNode, MergeConflict, NodeStreamer, TreeId,
+            TreeStreamerOptions as LsOptions, TreeVisitor,
+        },
         BlobId, BlobType, PackedId,
     },
+    cancellation::CancellationToken,
+    chunker::Chunker,
     commands::{
         self,
-        backup::BackupOptions,
-        check::{check_repository, CheckOptions},
-        config::ConfigOptions,
+        backup::{BackupOptions, BackupOutcome},
+        check::{
+            check_repository, check_snapshot, find_stale_index_entries, snapshots_since,
+            verify_pack, CheckOptions, CheckResults, PackVerification,
+        },
+        config::{CompressionSuggestion, ConfigChange, ConfigOptions},
         copy::CopySnapshot,
-        forget::{ForgetGroups, KeepOptions},
-        key::{add_current_key_to_repo, KeyOptions},
+        dedup::DedupEstimate,
+        diff::{diff_snapshots, diff_snapshots_streaming, DiffEntry, DiffOptions, SnapshotDiff},
+        dump::DumpFormat,
+        forget::{ForgetGroups, ForgetPruneResult, KeepOptions},
+        key::{add_current_key_to_repo, KeyInfo, KeyOptions},
         prune::{prune_repository, PruneOptions, PrunePlan},
         repair::{
             index::{index_checked_from_collector, repair_index, RepairIndexOptions},
             snapshots::{repair_snapshots, RepairSnapshotsOptions},
+            trees::{create_snapshot_from_tree, recover_orphaned_trees},
+        },
+        repoinfo::{IndexInfos, PackSizeHistogram, PackSizeHistogramOptions, RepoFileInfos},
+        restore::{
+            collect_and_prepare, restore_preview, restore_repository, verify_restored,
+            RestoreExtraOptions, RestoreOptions, RestorePlan, RestorePreview,
         },
-        repoinfo::{IndexInfos, RepoFileInfos},
-        restore::{collect_and_prepare, restore_repository, RestoreOptions, RestorePlan},
+        stats::{collect_stats, trees_restore_size, RepoStats, StatsMode, TreeRestoreSize},
     },
     crypto::aespoly1305::Key,
     error::{ErrorKind, RusticResult},
+    id::Id,
     index::{
         binarysorted::{IndexCollector, IndexType},
         GlobalIndex, IndexEntry, ReadGlobalIndex, ReadIndex,
@@ -54,13 +79,20 @@ use crate::{
     progress::{NoProgressBars, Progress, ProgressBars},
     repofile::{
         configfile::ConfigId,
+        indexfile::IndexId,
         keyfile::find_key_in_backend,
+        lockfile::{LockFile, LockId},
         packfile::PackId,
-        snapshotfile::{SnapshotGroup, SnapshotGroupCriterion, SnapshotId},
-        ConfigFile, KeyId, PathList, RepoFile, RepoId, SnapshotFile, SnapshotSummary, Tree,
+        snapshotfile::{
+            GroupSummary, SnapshotGroup, SnapshotGroupCriterion, SnapshotId, SnapshotOptions,
+            SnapshotSort,
+        },
+        ConfigFile, IndexBlob, KeyId, PackHeader, PathList, RepoFile, RepoId, SnapshotFile,
+        SnapshotSummary, Tree,
     },
     repository::{
         command_input::CommandInput,
+        lock::{break_locks, list_locks, lock_repository, RepositoryLock},
         warm_up::{warm_up, warm_up_wait},
     },
     vfs::OpenFile,
@@ -74,8 +106,11 @@ mod constants {
     /// Estimated item capacity used for cache in [`FullIndex`](super::FullIndex)
     pub(super) const ESTIMATED_ITEM_CAPACITY: usize = 32;
 
-    /// Estimated weight capacity used for cache in [`FullIndex`](super::FullIndex) (in bytes)
+    /// Default weight capacity used for cache in [`FullIndex`](super::FullIndex) (in bytes)
     pub(super) const WEIGHT_CAPACITY: u64 = 32_000_000;
+
+    /// Maximum number of password prompts in [`Repository::open_with_prompt`](super::Repository::open_with_prompt)
+    pub(super) const MAX_PASSWORD_PROMPTS: usize = 3;
 }
 
 /// Options for using and opening a [`Repository`]
@@ -96,9 +131,9 @@ pub struct RepositoryOptions {
         feature = "clap",
         clap(long, global = true, env = "RUSTIC_PASSWORD", hide_env_values = true)
     )]
-    // TODO: Security related: use `secrecy` library (#663)
+    #[serde(skip_serializing)]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
-    pub password: Option<String>,
+    pub password: Option<SecretString>,
 
     /// File to read the password from
     #[cfg_attr(
@@ -125,11 +160,58 @@ pub struct RepositoryOptions {
     #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
     pub password_command: Option<CommandInput>,
 
+    /// Account in the system keyring to read the password from (service: "rustic")
+    ///
+    /// # Note
+    ///
+    /// * This requires the `keyring` feature.
+    #[cfg_attr(feature = "clap", clap(
+        long,
+        global = true,
+        env = "RUSTIC_PASSWORD_KEYRING",
+        value_name = "ACCOUNT",
+        conflicts_with_all = &["password", "password_file", "password_command"],
+    ))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub password_keyring: Option<String>,
+
+    /// File descriptor to read the password from, e.g. passed by a secret manager
+    #[cfg(unix)]
+    #[cfg_attr(feature = "clap", clap(
+        long,
+        global = true,
+        env = "RUSTIC_PASSWORD_FD",
+        value_name = "FD",
+        conflicts_with_all = &["password", "password_file", "password_command", "password_keyring"],
+    ))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub password_fd: Option<RawFd>,
+
+    /// Named pipe to read the password from, e.g. passed by a secret manager
+    #[cfg(windows)]
+    #[cfg_attr(feature = "clap", clap(
+        long,
+        global = true,
+        env = "RUSTIC_PASSWORD_PIPE",
+        value_name = "PIPE",
+        conflicts_with_all = &["password", "password_file", "password_command", "password_keyring"],
+    ))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub password_pipe: Option<PathBuf>,
+
     /// Don't use a cache.
     #[cfg_attr(feature = "clap", clap(long, global = true, env = "RUSTIC_NO_CACHE"))]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
     pub no_cache: bool,
 
+    /// Don't verify written repository files (snapshots, index, keys, config) by reading them back.
+    ///
+    /// By default, these files are read back after writing and written again if they differ.
+    /// Pack files are never read back.
+    #[cfg_attr(feature = "clap", clap(long, global = true))]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::bool::overwrite_false))]
+    pub no_verify_writes: bool,
+
     /// Use this dir as cache dir instead of the standard cache dir
     #[cfg_attr(
         feature = "clap",
@@ -170,6 +252,20 @@ pub struct RepositoryOptions {
     #[serde_as(as = "Option<DisplayFromStr>")]
     #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
     pub warm_up_wait: Option<humantime::Duration>,
+
+    /// Size of the in-memory blob cache used for repositories with full index (in bytes, default: 32MB).
+    /// Use 0 to disable the blob cache.
+    #[cfg_attr(
+        feature = "clap",
+        clap(
+            long,
+            global = true,
+            env = "RUSTIC_BLOB_CACHE_SIZE",
+            value_name = "BYTES"
+        )
+    )]
+    #[cfg_attr(feature = "merge", merge(strategy = conflate::option::overwrite_none))]
+    pub blob_cache_size: Option<u64>,
 }
 
 impl RepositoryOptions {
@@ -182,14 +278,22 @@ impl RepositoryOptions {
     /// * If splitting the password command failed
     /// * If executing the password command failed
     /// * If reading the password from the command failed
+    /// * If reading the password from the keyring failed
+    /// * If the password file descriptor is not readable (Unix)
+    /// * If opening the password pipe failed (Windows)
     ///
     /// # Returns
     ///
     /// The password or `None` if no password is given
-    pub fn evaluate_password(&self) -> RusticResult<Option<String>> {
-        match (&self.password, &self.password_file, &self.password_command) {
-            (Some(pwd), _, _) => Ok(Some(pwd.clone())),
-            (_, Some(file), _) => {
+    pub fn evaluate_password(&self) -> RusticResult<Option<SecretString>> {
+        match (
+            &self.password,
+            &self.password_file,
+            &self.password_command,
+            &self.password_keyring,
+        ) {
+            (Some(pwd), _, _, _) => Ok(Some(pwd.clone())),
+            (_, Some(file), _, _) => {
                 let mut file = BufReader::new(File::open(file).map_err(|err| {
                     RusticError::with_source(
                         ErrorKind::Password,
@@ -200,7 +304,7 @@ impl RepositoryOptions {
                 })?);
                 Ok(Some(read_password_from_reader(&mut file)?))
             }
-            (_, _, Some(command)) if command.is_set() => {
+            (_, _, Some(command), _) if command.is_set() => {
                 debug!("commands: {command:?}");
                 let run_command = Command::new(command.command())
                     .args(command.args())
@@ -251,9 +355,94 @@ impl RepositoryOptions {
                 let mut pwd = BufReader::new(&*output.stdout);
                 Ok(Some(read_password_from_reader(&mut pwd)?))
             }
-            (None, None, _) => Ok(None),
+            (_, _, _, Some(account)) => Ok(Some(read_password_from_keyring(account)?)),
+            (None, None, _, None) => self.evaluate_password_fd(),
         }
     }
+
+    /// Evaluates the password given by the password file descriptor
+    ///
+    /// # Errors
+    ///
+    /// * If the file descriptor is not open or not readable
+    /// * If reading the password failed
+    #[cfg(unix)]
+    fn evaluate_password_fd(&self) -> RusticResult<Option<SecretString>> {
+        self.password_fd.map(read_password_from_fd).transpose()
+    }
+
+    /// Evaluates the password given by the password pipe
+    ///
+    /// # Errors
+    ///
+    /// * If opening the pipe failed
+    /// * If reading the password failed
+    #[cfg(windows)]
+    fn evaluate_password_fd(&self) -> RusticResult<Option<SecretString>> {
+        let Some(pipe) = &self.password_pipe else {
+            return Ok(None);
+        };
+        let mut pipe = BufReader::new(File::open(pipe).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::Password,
+                "Opening password pipe failed. Is the pipe `{path}` correct?",
+                err,
+            )
+            .attach_context("path", pipe.display().to_string())
+        })?);
+        Ok(Some(read_password_from_reader(&mut pipe)?))
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    #[allow(clippy::unnecessary_wraps)]
+    fn evaluate_password_fd(&self) -> RusticResult<Option<SecretString>> {
+        Ok(None)
+    }
+}
+
+/// A reader for a file descriptor which is not owned, i.e. not closed when dropped
+#[cfg(unix)]
+#[derive(Debug)]
+struct FdReader(RawFd);
+
+#[cfg(unix)]
+impl Read for FdReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Ok(nix::unistd::read(self.0, buf)?)
+    }
+}
+
+/// Read a password from a file descriptor
+///
+/// # Arguments
+///
+/// * `fd` - The file descriptor to read the password from. It is not closed.
+///
+/// # Errors
+///
+/// * If the file descriptor is not open or not readable
+/// * If reading the password failed
+#[cfg(unix)]
+fn read_password_from_fd(fd: RawFd) -> RusticResult<SecretString> {
+    use nix::fcntl::{fcntl, FcntlArg, OFlag};
+
+    let flags = fcntl(fd, FcntlArg::F_GETFL).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::Password,
+            "The file descriptor `{fd}` to read the password from is not open. Please make sure it is passed to the process.",
+            err,
+        )
+        .attach_context("fd", fd.to_string())
+    })?;
+    if OFlag::from_bits_truncate(flags) & OFlag::O_ACCMODE == OFlag::O_WRONLY {
+        return Err(RusticError::new(
+            ErrorKind::Password,
+            "The file descriptor `{fd}` to read the password from is not readable. Please pass a file descriptor opened for reading.",
+        )
+        .attach_context("fd", fd.to_string()));
+    }
+
+    read_password_from_reader(&mut BufReader::new(FdReader(fd)))
 }
 
 /// Read a password from a reader
@@ -265,16 +454,16 @@ impl RepositoryOptions {
 /// # Errors
 ///
 /// * If reading the password failed
-pub fn read_password_from_reader(file: &mut impl BufRead) -> RusticResult<String> {
+pub fn read_password_from_reader(file: &mut impl BufRead) -> RusticResult<SecretString> {
     let mut password = String::new();
-    _ = file.read_line(&mut password).map_err(|err| {
-        RusticError::with_source(
+    if let Err(err) = file.read_line(&mut password) {
+        password.zeroize();
+        return Err(RusticError::with_source(
             ErrorKind::Password,
             "Reading password from reader failed. Is the file empty? Please check the file and the password.",
-            err
-        )
-        .attach_context("password", password.clone())
-    })?;
+            err,
+        ));
+    }
 
     // Remove the \n from the line if present
     if password.ends_with('\n') {
@@ -286,7 +475,121 @@ pub fn read_password_from_reader(file: &mut impl BufRead) -> RusticResult<String
         _ = password.pop();
     }
 
-    Ok(password)
+    // copy the password into the secret and wipe the original buffer, which may have excess capacity
+    let secret = SecretString::from(password.as_str());
+    password.zeroize();
+
+    Ok(secret)
+}
+
+/// The service name used for passwords in the system keyring
+pub const KEYRING_SERVICE: &str = "rustic";
+
+/// Read a password from the system keyring
+///
+/// # Arguments
+///
+/// * `account` - The account the password is stored for (using the service [`KEYRING_SERVICE`])
+///
+/// # Errors
+///
+/// * If no password is stored for the account
+/// * If accessing the keyring failed
+/// * If the `keyring` feature is not enabled
+pub fn read_password_from_keyring(account: &str) -> RusticResult<SecretString> {
+    #[cfg(feature = "keyring")]
+    {
+        let password = keyring_entry(account)?
+            .get_password()
+            .map_err(|err| match err {
+                keyring::Error::NoEntry => RusticError::new(
+                    ErrorKind::Password,
+                    "No password for account `{account}` found in the system keyring. Please store the password for service `{service}` and account `{account}` in the keyring first, e.g. using `rustic key add` with keyring support or the tools of your desktop environment.",
+                )
+                .attach_context("account", account)
+                .attach_context("service", KEYRING_SERVICE),
+                err => RusticError::with_source(
+                    ErrorKind::Password,
+                    "Reading the password for account `{account}` from the system keyring failed.",
+                    err,
+                )
+                .attach_context("account", account),
+            })?;
+        Ok(SecretString::from(password))
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        Err(keyring_unsupported(account))
+    }
+}
+
+/// Store a password in the system keyring
+///
+/// The password can then be used by setting [`RepositoryOptions::password_keyring`] to `account`.
+///
+/// # Arguments
+///
+/// * `account` - The account to store the password for (using the service [`KEYRING_SERVICE`])
+/// * `password` - The password to store
+///
+/// # Errors
+///
+/// * If accessing the keyring failed
+/// * If the `keyring` feature is not enabled
+pub fn store_password_in_keyring(account: &str, password: &SecretString) -> RusticResult<()> {
+    #[cfg(feature = "keyring")]
+    {
+        keyring_entry(account)?
+            .set_password(password.expose_secret())
+            .map_err(|err| {
+                RusticError::with_source(
+                    ErrorKind::Password,
+                    "Storing the password for account `{account}` in the system keyring failed.",
+                    err,
+                )
+                .attach_context("account", account)
+            })
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        _ = password;
+        Err(keyring_unsupported(account))
+    }
+}
+
+/// Get the entry for the given account in the system keyring
+///
+/// # Arguments
+///
+/// * `account` - The account of the entry
+///
+/// # Errors
+///
+/// * If the keyring could not be accessed
+#[cfg(feature = "keyring")]
+fn keyring_entry(account: &str) -> RusticResult<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, account).map_err(|err| {
+        RusticError::with_source(
+            ErrorKind::Password,
+            "Accessing the system keyring for account `{account}` failed.",
+            err,
+        )
+        .attach_context("account", account)
+    })
+}
+
+/// The error returned if the `keyring` feature is not enabled
+///
+/// # Arguments
+///
+/// * `account` - The requested account
+#[cfg(not(feature = "keyring"))]
+fn keyring_unsupported(account: &str) -> Box<RusticError> {
+    RusticError::new(
+        ErrorKind::Unsupported,
+        "Using the system keyring for account `{account}` is not supported. Please enable the `keyring` feature of rustic_core.",
+    )
+    .attach_context("account", account)
 }
 
 #[derive(Debug, Clone)]
@@ -311,11 +614,14 @@ pub struct Repository<P, S> {
     pub(crate) be_hot: Option<Arc<dyn WriteBackend>>,
 
     /// The options used for this repository
-    opts: RepositoryOptions,
+    pub(crate) opts: RepositoryOptions,
 
     /// The progress bar to use
     pub(crate) pb: P,
 
+    /// The token to cancel long-running operations
+    pub(crate) cancel: CancellationToken,
+
     /// The status
     status: S,
 }
@@ -379,6 +685,18 @@ impl<P> Repository<P, ()> {
             be = WarmUpAccessBackend::new_warm_up(be);
         }
 
+        let mirrors = backends.mirrors();
+        if !mirrors.is_empty() {
+            for mirror in mirrors {
+                info!("mirroring all modifications to {}", mirror.location());
+            }
+            be = Arc::new(MirrorBackend::new(
+                be,
+                mirrors.to_vec(),
+                backends.mirror_failure(),
+            ));
+        }
+
         let mut name = be.location();
         if let Some(be_hot) = &be_hot {
             be = Arc::new(HotColdBackend::new(be, be_hot.clone()));
@@ -392,12 +710,94 @@ impl<P> Repository<P, ()> {
             be_hot,
             opts: opts.clone(),
             pb,
+            cancel: CancellationToken::default(),
             status: (),
         })
     }
 }
 
 impl<P, S> Repository<P, S> {
+    /// List the keys of the repository.
+    ///
+    /// # Errors
+    ///
+    /// * If the key files could not be listed or read.
+    ///
+    /// # Returns
+    ///
+    /// The id, hostname, username and creation time of all keys, sorted by id.
+    pub fn list_keys(&self) -> RusticResult<Vec<KeyInfo>> {
+        commands::key::list_keys(self)
+    }
+
+    /// List the keys of the repository which have been created before the given age.
+    ///
+    /// Keys without creation time are not returned, as their age is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `age` - The minimum age of the keys to return
+    ///
+    /// # Errors
+    ///
+    /// * If the key files could not be listed or read.
+    ///
+    /// # Returns
+    ///
+    /// The information about the keys older than `age`, sorted by id.
+    pub fn keys_older_than(&self, age: Duration) -> RusticResult<Vec<KeyInfo>> {
+        commands::key::keys_older_than(self, age)
+    }
+
+    /// Check whether the given password opens a key of the repository.
+    ///
+    /// This doesn't change the state of the repository, so it can be used to verify passwords when rotating keys.
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` - The password to check
+    ///
+    /// # Errors
+    ///
+    /// * If the key files could not be listed or read.
+    ///
+    /// # Returns
+    ///
+    /// The id of the key which is opened by the password or `None` if the password doesn't fit to any key.
+    pub fn verify_password(&self, pass: &str) -> RusticResult<Option<KeyId>> {
+        commands::key::verify_password(self, pass)
+    }
+
+    /// Check that the repository backend is reachable and works as expected.
+    ///
+    /// A small test file is written, read back and removed again. For read-only repositories,
+    /// only listing is tested.
+    ///
+    /// # Errors
+    ///
+    /// * If one of the backend operations failed or the read data differs from the written data.
+    ///
+    /// # Returns
+    ///
+    /// The time needed for the single backend operations.
+    pub fn check_backend(&self) -> RusticResult<SelfTestResult> {
+        self.be.self_test()
+    }
+
+    /// Use the given [`CancellationToken`] to cancel long-running operations.
+    ///
+    /// Operations like `backup`, `restore`, `prune` and `check` poll the token and return an
+    /// error of kind [`ErrorKind::Cancelled`] once it has been cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token to use
+    #[must_use]
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancel = token;
+        self
+    }
+
     /// Evaluates the password given by the repository options
     ///
     /// # Errors
@@ -411,7 +811,7 @@ impl<P, S> Repository<P, S> {
     /// # Returns
     ///
     /// The password or `None` if no password is given
-    pub fn password(&self) -> RusticResult<Option<String>> {
+    pub fn password(&self) -> RusticResult<Option<SecretString>> {
         self.opts.evaluate_password()
     }
 
@@ -486,7 +886,7 @@ impl<P, S> Repository<P, S> {
             )
         })?;
 
-        self.open_with_password(&password)
+        self.open_with_password(password)
     }
 
     /// Open the repository with a given password.
@@ -505,7 +905,76 @@ impl<P, S> Repository<P, S> {
     /// * If no suitable key is found
     /// * If listing the repository config file failed
     /// * If there is more than one repository config file
-    pub fn open_with_password(self, password: &str) -> RusticResult<Repository<P, OpenStatus>> {
+    pub fn open_with_password(
+        self,
+        password: impl Into<SecretString>,
+    ) -> RusticResult<Repository<P, OpenStatus>> {
+        let (key, key_id, config) = self.key_and_config(&password.into())?;
+        self.open_raw(key, key_id, config)
+    }
+
+    /// Open the repository with a password requested by the given prompt.
+    ///
+    /// This allows front-ends to ask for the password interactively, e.g. using a terminal or a dialog.
+    /// If a password is given by the repository options, it is used and `prompt` is not called.
+    /// Otherwise, `prompt` is called until a fitting password is entered. If the password was
+    /// incorrect for 3 times, the error of kind [`ErrorKind::IncorrectPassword`] is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `prompt` - The prompt to get the password from
+    ///
+    /// # Errors
+    ///
+    /// * If reading the password given by the repository options failed
+    /// * If the prompt returned an error
+    /// * If the password is incorrect for 3 times
+    /// * If no repository config file is found
+    /// * If the keys of the hot and cold backend don't match
+    /// * If listing the repository config file failed
+    /// * If there is more than one repository config file
+    ///
+    /// # Returns
+    ///
+    /// The open repository
+    pub fn open_with_prompt(
+        self,
+        mut prompt: impl FnMut() -> RusticResult<SecretString>,
+    ) -> RusticResult<Repository<P, OpenStatus>> {
+        if let Some(password) = self.password()? {
+            return self.open_with_password(password);
+        }
+
+        let mut prompts = 0;
+        loop {
+            prompts += 1;
+            match self.key_and_config(&prompt()?) {
+                Ok((key, key_id, config)) => return self.open_raw(key, key_id, config),
+                Err(err)
+                    if err.is_incorrect_password() && prompts < constants::MAX_PASSWORD_PROMPTS =>
+                {
+                    warn!("repository {}: password is incorrect.", self.name);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Get the key fitting to the password and read the config file
+    ///
+    /// # Arguments
+    ///
+    /// * `password` - The password to use
+    ///
+    /// # Errors
+    ///
+    /// * If no repository config file is found
+    /// * If the keys of the hot and cold backend don't match
+    /// * If the password is incorrect
+    /// * If no suitable key is found
+    /// * If listing the repository config file failed
+    /// * If there is more than one repository config file
+    fn key_and_config(&self, password: &SecretString) -> RusticResult<(Key, KeyId, ConfigFile)> {
         let config_id = self.config_id()?.ok_or_else(|| {
             RusticError::new(
                 ErrorKind::Configuration,
@@ -528,27 +997,24 @@ impl<P, S> Repository<P, S> {
             }
         }
 
-        let key = find_key_in_backend(&self.be, &password, None)?;
+        let (key_id, key) = find_key_in_backend(&self.be, &password.expose_secret(), None)?;
 
         info!("repository {}: password is correct.", self.name);
 
         let dbe = DecryptBackend::new(self.be.clone(), key);
         let config: ConfigFile = dbe.get_file(&config_id)?;
-        self.open_raw(key, config)
+        Ok((key, key_id, config))
     }
 
-    /// Initialize a new repository with given options using the password defined in `RepositoryOptions`
-    ///
-    /// This returns an open repository which can be directly used.
-    ///
-    /// # Type Parameters
-    ///
-    /// * `P` - The type of the progress bar
+    /// Open the repository in read-only mode.
     ///
-    /// # Arguments
+    /// This gets the decryption key and reads the config file. All modifications of the backend are refused.
     ///
-    /// * `key_opts` - The options to use for the key
-    /// * `config_opts` - The options to use for the config
+    /// The returned repository can be indexed using e.g. [`Repository::to_indexed`] and then be used to
+    /// read snapshots, trees and file contents, e.g. with [`Repository::ls`], [`Repository::dump`],
+    /// [`Repository::read_file_at`], [`Repository::check`] or [`Repository::restore`].
+    /// Methods which modify the repository, like [`Repository::backup`], [`Repository::delete_snapshots`]
+    /// or [`Repository::prune`], are not available.
     ///
     /// # Errors
     ///
@@ -558,39 +1024,174 @@ impl<P, S> Repository<P, S> {
     /// * If parsing the password command failed
     /// * If reading the password from the command failed
     /// * If splitting the password command failed
-    pub fn init(
-        self,
-        key_opts: &KeyOptions,
-        config_opts: &ConfigOptions,
-    ) -> RusticResult<Repository<P, OpenStatus>> {
+    /// * If no repository config file is found
+    /// * If the keys of the hot and cold backend don't match
+    /// * If the password is incorrect
+    /// * If no suitable key is found
+    /// * If listing the repository config file failed
+    /// * If there is more than one repository config file
+    ///
+    /// # Returns
+    ///
+    /// The open read-only repository
+    pub fn open_read_only(self) -> RusticResult<Repository<P, ReadOnlyStatus>> {
         let password = self.password()?.ok_or_else(|| {
             RusticError::new(
                 ErrorKind::Password,
-                "No password given, or Password was empty. Please specify a valid password for `{name}`.",
+                "No password given, or Password was empty. Please specify a valid password.",
             )
-            .attach_context("name", self.name.clone())
         })?;
 
-        self.init_with_password(&password, key_opts, config_opts)
+        self.open_read_only_with_password(password)
     }
 
-    /// Initialize a new repository with given password and options.
-    ///
-    /// This returns an open repository which can be directly used.
+    /// Open the repository in read-only mode with a given password.
     ///
-    /// # Type Parameters
-    ///
-    /// * `P` - The type of the progress bar
+    /// See [`Repository::open_read_only`] for which operations are available.
     ///
     /// # Arguments
     ///
-    /// * `pass` - The password to use
-    /// * `key_opts` - The options to use for the key
-    /// * `config_opts` - The options to use for the config
+    /// * `password` - The password to use
     ///
     /// # Errors
     ///
-    /// * If a config file already exists
+    /// * If no repository config file is found
+    /// * If the keys of the hot and cold backend don't match
+    /// * If the password is incorrect
+    /// * If no suitable key is found
+    /// * If listing the repository config file failed
+    /// * If there is more than one repository config file
+    pub fn open_read_only_with_password(
+        mut self,
+        password: impl Into<SecretString>,
+    ) -> RusticResult<Repository<P, ReadOnlyStatus>> {
+        self.be = ReadOnlyBackend::new_read_only(self.be);
+        self.be_hot = self.be_hot.map(ReadOnlyBackend::new_read_only);
+        let repo = self.open_with_password(password)?;
+
+        Ok(Repository {
+            name: repo.name,
+            be: repo.be,
+            be_hot: repo.be_hot,
+            opts: repo.opts,
+            pb: repo.pb,
+            cancel: repo.cancel,
+            status: ReadOnlyStatus { open: repo.status },
+        })
+    }
+
+    /// Initialize a new repository with given options using the password defined in `RepositoryOptions`
+    ///
+    /// This returns an open repository which can be directly used.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `P` - The type of the progress bar
+    ///
+    /// # Arguments
+    ///
+    /// * `key_opts` - The options to use for the key
+    /// * `config_opts` - The options to use for the config
+    ///
+    /// # Errors
+    ///
+    /// * If no password is given
+    /// * If reading the password failed
+    /// * If opening the password file failed
+    /// * If parsing the password command failed
+    /// * If reading the password from the command failed
+    /// * If splitting the password command failed
+    pub fn init(
+        self,
+        key_opts: &KeyOptions,
+        config_opts: &ConfigOptions,
+    ) -> RusticResult<Repository<P, OpenStatus>> {
+        let password = self.password()?.ok_or_else(|| {
+            RusticError::new(
+                ErrorKind::Password,
+                "No password given, or Password was empty. Please specify a valid password for `{name}`.",
+            )
+            .attach_context("name", self.name.clone())
+        })?;
+
+        self.init_with_password(password.expose_secret(), key_opts, config_opts)
+    }
+
+    /// Open the repository or initialize it if it doesn't exist, using the password defined in `RepositoryOptions`
+    ///
+    /// If a config file exists in the repository or in the hot repository, the repository is opened
+    /// like with [`Repository::open`], including the consistency checks of hot and cold repository.
+    /// An existing repository is never re-initialized; if the password doesn't fit, an error of kind
+    /// [`ErrorKind::IncorrectPassword`] is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_opts` - The options to use for the key, if the repository is initialized
+    /// * `config_opts` - The options to use for the config, if the repository is initialized
+    ///
+    /// # Errors
+    ///
+    /// * If no password is given
+    /// * If reading the password failed
+    /// * If listing the repository config file failed
+    /// * If there is more than one repository config file
+    /// * If the repository exists, but the password is incorrect
+    /// * If the repository exists, but the keys of the hot and cold backend don't match
+    /// * If the repository could not be initialized
+    ///
+    /// # Returns
+    ///
+    /// The open repository
+    pub fn open_or_init(
+        self,
+        key_opts: &KeyOptions,
+        config_opts: &ConfigOptions,
+    ) -> RusticResult<Repository<P, OpenStatus>> {
+        let password = self.password()?.ok_or_else(|| {
+            RusticError::new(
+                ErrorKind::Password,
+                "No password given, or Password was empty. Please specify a valid password for `{name}`.",
+            )
+            .attach_context("name", self.name.clone())
+        })?;
+
+        let config_exists = self.config_id_with_backend(&self.be)?.is_some();
+        let hot_config_exists = match self.be_hot {
+            None => false,
+            Some(ref be) => self.config_id_with_backend(be)?.is_some(),
+        };
+        if !config_exists && !hot_config_exists {
+            return self.init_with_password(password.expose_secret(), key_opts, config_opts);
+        }
+
+        self.open_with_password(password).map_err(|err| {
+            if err.is_incorrect_password() {
+                err.prepend_guidance_line(
+                    "The repository already exists and is not initialized again.",
+                )
+            } else {
+                err
+            }
+        })
+    }
+
+    /// Initialize a new repository with given password and options.
+    ///
+    /// This returns an open repository which can be directly used.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `P` - The type of the progress bar
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` - The password to use
+    /// * `key_opts` - The options to use for the key
+    /// * `config_opts` - The options to use for the config
+    ///
+    /// # Errors
+    ///
+    /// * If a config file already exists
     /// * If listing the repository config file failed
     /// * If there is more than one repository config file
     pub fn init_with_password(
@@ -612,9 +1213,9 @@ impl<P, S> Repository<P, S> {
             .attach_context("name", self.name));
         }
 
-        let (key, config) = commands::init::init(&self, pass, key_opts, config_opts)?;
+        let (key, key_id, config) = commands::init::init(&self, pass, key_opts, config_opts)?;
 
-        self.open_raw(key, config)
+        self.open_raw(key, key_id, config)
     }
 
     /// Initialize a new repository with given password and a ready [`ConfigFile`].
@@ -640,9 +1241,9 @@ impl<P, S> Repository<P, S> {
         key_opts: &KeyOptions,
         config: ConfigFile,
     ) -> RusticResult<Repository<P, OpenStatus>> {
-        let key = commands::init::init_with_config(&self, password, key_opts, &config)?;
+        let (key, key_id) = commands::init::init_with_config(&self, password, key_opts, &config)?;
         info!("repository {} successfully created.", config.id);
-        self.open_raw(key, config)
+        self.open_raw(key, key_id, config)
     }
 
     /// Open the repository with given [`Key`] and [`ConfigFile`].
@@ -654,13 +1255,19 @@ impl<P, S> Repository<P, S> {
     /// # Arguments
     ///
     /// * `key` - The key to use
+    /// * `key_id` - The id of the key file the key was read from
     /// * `config` - The config file to use
     ///
     /// # Errors
     ///
     /// * If the config file has `is_hot` set to `true` but the repository is not hot
     /// * If the config file has `is_hot` set to `false` but the repository is hot
-    fn open_raw(mut self, key: Key, config: ConfigFile) -> RusticResult<Repository<P, OpenStatus>> {
+    fn open_raw(
+        mut self,
+        key: Key,
+        key_id: KeyId,
+        config: ConfigFile,
+    ) -> RusticResult<Repository<P, OpenStatus>> {
         match (config.is_hot == Some(true), self.be_hot.is_some()) {
             (true, false) => return Err(
                 RusticError::new(
@@ -677,6 +1284,7 @@ impl<P, S> Repository<P, S> {
             _ => {}
         }
 
+        let uncached_be = self.be.clone();
         let cache = (!self.opts.no_cache)
             .then(|| Cache::new(config.id, self.opts.cache_dir.clone()).ok())
             .flatten();
@@ -690,9 +1298,16 @@ impl<P, S> Repository<P, S> {
 
         let mut dbe = DecryptBackend::new(self.be.clone(), key);
         dbe.set_zstd(config.zstd()?);
+        dbe.set_zstd_dictionary(config.zstd_dictionary()?.map(Bytes::copy_from_slice));
         dbe.set_extra_verify(config.extra_verify());
+        dbe.set_write_verification((!self.opts.no_verify_writes).then_some(uncached_be));
 
-        let open = OpenStatus { cache, dbe, config };
+        let open = OpenStatus {
+            cache,
+            dbe,
+            config,
+            key_id,
+        };
 
         Ok(Repository {
             name: self.name,
@@ -700,6 +1315,7 @@ impl<P, S> Repository<P, S> {
             be_hot: self.be_hot,
             opts: self.opts,
             pb: self.pb,
+            cancel: self.cancel,
             status: open,
         })
     }
@@ -716,6 +1332,32 @@ impl<P, S> Repository<P, S> {
     pub fn list<T: RepoId>(&self) -> RusticResult<impl Iterator<Item = T>> {
         Ok(self.be.list(T::TYPE)?.into_iter().map(Into::into))
     }
+
+    /// Count the files of the given [`FileType`] which are present in the repository
+    ///
+    /// The files are only listed, their contents are not read.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the files to count
+    ///
+    /// # Errors
+    ///
+    /// * If the files could not be listed.
+    pub fn count_files(&self, tpe: FileType) -> RusticResult<usize> {
+        Ok(self.be.list(tpe)?.len())
+    }
+
+    /// Count the snapshots in the repository
+    ///
+    /// In contrast to [`Repository::get_all_snapshots`], the snapshot files are only listed and not read.
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshot files could not be listed.
+    pub fn count_snapshots(&self) -> RusticResult<usize> {
+        self.count_files(FileType::Snapshot)
+    }
 }
 
 impl<P: ProgressBars, S> Repository<P, S> {
@@ -756,12 +1398,29 @@ impl<P: ProgressBars, S> Repository<P, S> {
     ///
     /// * If the command could not be parsed.
     /// * If the thread pool could not be created.
-    pub(crate) fn warm_up_wait(
-        &self,
-        packs: impl ExactSizeIterator<Item = PackId> + Clone,
-    ) -> RusticResult<()> {
+    pub(crate) fn warm_up_wait(&self, packs: impl Iterator<Item = PackId>) -> RusticResult<()> {
         warm_up_wait(self, packs)
     }
+
+    /// Warm up all pack files needed by the given [`RestorePlan`] and wait the configured waiting time.
+    ///
+    /// Packs the backend reports to be already warm are skipped. A typical restore from cold storage is:
+    /// 1. Create the plan using [`Repository::prepare_restore`].
+    /// 2. Call this method. It runs [`RepositoryOptions::warm_up_command`] for each needed pack and then waits
+    ///    [`RepositoryOptions::warm_up_wait`] or runs [`RepositoryOptions::warm_up_wait_command`] for each pack.
+    /// 3. Call [`Repository::restore`] with [`RestoreOptions::no_warm_up`] set to not warm up and wait again.
+    ///
+    /// # Arguments
+    ///
+    /// * `plan` - The restore plan to warm up the packs for
+    ///
+    /// # Errors
+    ///
+    /// * If the command could not be parsed.
+    /// * If the thread pool could not be created.
+    pub fn warm_up_for_restore(&self, plan: &RestorePlan) -> RusticResult<()> {
+        warm_up_wait(self, plan.to_packs().into_iter())
+    }
 }
 
 /// A repository which is open, i.e. the password has been checked and the decryption key is available.
@@ -774,6 +1433,9 @@ pub trait Open {
 
     /// Get the [`ConfigFile`]
     fn config(&self) -> &ConfigFile;
+
+    /// Get the id of the key file used to open the repository
+    fn key_id(&self) -> &KeyId;
 }
 
 impl<P, S: Open> Open for Repository<P, S> {
@@ -791,6 +1453,11 @@ impl<P, S: Open> Open for Repository<P, S> {
     fn config(&self) -> &ConfigFile {
         self.status.config()
     }
+
+    /// Get the id of the key file used to open the repository
+    fn key_id(&self) -> &KeyId {
+        self.status.key_id()
+    }
 }
 
 /// Open Status: This repository is open, i.e. the password has been checked and the decryption key is available.
@@ -802,6 +1469,8 @@ pub struct OpenStatus {
     dbe: DecryptBackend<Key>,
     /// The [`ConfigFile`]
     config: ConfigFile,
+    /// The id of the key file used to open the repository
+    key_id: KeyId,
 }
 
 impl Open for OpenStatus {
@@ -819,8 +1488,51 @@ impl Open for OpenStatus {
     fn config(&self) -> &ConfigFile {
         &self.config
     }
+
+    /// Get the id of the key file used to open the repository
+    fn key_id(&self) -> &KeyId {
+        &self.key_id
+    }
+}
+
+/// Read-only Status: This repository is open, but was opened in read-only mode.
+///
+/// All modifications of the backend are refused and methods which modify the repository are not available.
+#[derive(Debug)]
+pub struct ReadOnlyStatus {
+    /// The open status
+    open: OpenStatus,
+}
+
+impl Open for ReadOnlyStatus {
+    /// Get the cache
+    fn cache(&self) -> Option<&Cache> {
+        self.open.cache()
+    }
+
+    /// Get the [`DecryptBackend`]
+    fn dbe(&self) -> &DecryptBackend<Key> {
+        self.open.dbe()
+    }
+
+    /// Get the [`ConfigFile`]
+    fn config(&self) -> &ConfigFile {
+        self.open.config()
+    }
+
+    /// Get the id of the key file used to open the repository
+    fn key_id(&self) -> &KeyId {
+        self.open.key_id()
+    }
 }
 
+/// A repository which is open and may be modified, i.e. was not opened in read-only mode.
+pub trait Writable: Open {}
+
+impl Writable for OpenStatus {}
+
+impl<P, S: Writable> Writable for Repository<P, S> {}
+
 impl<P, S: Open> Repository<P, S> {
     /// Get the content of the decrypted repository file given by id and [`FileType`]
     ///
@@ -838,38 +1550,69 @@ impl<P, S: Open> Repository<P, S> {
         commands::cat::cat_file(self, tpe, id)
     }
 
-    /// Add a new key to the repository
+    /// Get the repository version
     ///
-    /// # Arguments
+    /// The repository version determines which features are available:
     ///
-    /// * `pass` - The password to use for the new key
-    /// * `opts` - The options to use for the new key
+    /// * version 1: no compression
+    /// * version 2: compression
+    /// * version 3: compression with a compression dictionary
+    #[must_use]
+    pub fn repo_version(&self) -> u32 {
+        self.config().version
+    }
+
+    /// Check whether the repository should be migrated to a newer repository version
     ///
-    /// # Errors
+    /// Only versions which can be used without further configuration are considered, i.e.
+    /// version 2. Version 3 is only useful with a compression dictionary and must be set
+    /// explicitly using [`Repository::apply_config`].
     ///
-    /// * If the key could not be serialized.
-    pub fn add_key(&self, pass: &str, opts: &KeyOptions) -> RusticResult<KeyId> {
-        add_current_key_to_repo(self, opts, pass)
+    /// Use [`Repository::migrate_to_latest`] to migrate the repository.
+    ///
+    /// # Returns
+    ///
+    /// The version to migrate to, if the repository uses an older version; `None` if
+    /// no migration is needed
+    #[must_use]
+    pub fn needs_migration(&self) -> Option<u32> {
+        commands::config::needs_migration(self)
     }
 
-    /// Update the repository config by applying the given [`ConfigOptions`]
+    /// Get a range of the content of the decrypted repository file given by id and [`FileType`]
+    ///
+    /// # Note
+    ///
+    /// This is a convenience method and doesn't save bandwidth: The whole file is read,
+    /// decrypted and decompressed and the range is taken from the result.
     ///
     /// # Arguments
     ///
-    /// * `opts` - The options to apply
+    /// * `tpe` - The type of the file to get
+    /// * `id` - The id of the file to get
+    /// * `offset` - The offset within the decrypted content to start reading
+    /// * `length` - The length to read
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the given range.
+    /// If offset is behind the end of the file, an empty `Bytes` is returned.
+    /// If length is too large, the result up to the end of the file is returned.
     ///
     /// # Errors
     ///
-    /// * If the version is not supported
-    /// * If the version is lower than the current version
-    /// * If compression is set for a v1 repo
-    /// * If the compression level is not supported
-    /// * If the size is too large
-    /// * If the min pack size tolerance percent is wrong
-    /// * If the max pack size tolerance percent is wrong
-    /// * If the file could not be serialized to json.
-    pub fn apply_config(&self, opts: &ConfigOptions) -> RusticResult<bool> {
-        commands::config::apply_config(self, opts)
+    /// * If the string is not a valid hexadecimal string
+    /// * If no id could be found.
+    /// * If the id is not unique.
+    pub fn cat_file_partial(
+        &self,
+        tpe: FileType,
+        id: &str,
+        offset: usize,
+        length: usize,
+    ) -> RusticResult<Bytes> {
+        let data = commands::cat::cat_file(self, tpe, id)?;
+        Ok(commands::cat::slice_range(&data, offset, length))
     }
 
     /// Get the repository configuration
@@ -877,54 +1620,322 @@ impl<P, S: Open> Repository<P, S> {
         self.status.config()
     }
 
-    // TODO: add documentation!
-    pub(crate) fn dbe(&self) -> &DecryptBackend<Key> {
-        self.status.dbe()
+    /// Get a content defined [`Chunker`] using the chunker parameters of this repository.
+    ///
+    /// Data chunked by this chunker has the same chunk boundaries as data chunked during a backup into
+    /// this repository, so its chunks deduplicate against the data saved in the repository.
+    ///
+    /// # Errors
+    ///
+    /// * If the chunker polynomial or the chunk sizes saved in the config are invalid.
+    pub fn chunker(&self) -> RusticResult<Chunker> {
+        Chunker::from_config(self.config())
     }
-}
 
-impl<P: ProgressBars, S: Open> Repository<P, S> {
-    /// Get grouped snapshots.
+    /// Get the id of the key file which was used to open the repository
+    pub fn key_id(&self) -> &KeyId {
+        self.status.key_id()
+    }
+
+    /// Compute which keys would be removed by [`Repository::delete_keys`].
+    ///
+    /// The same safety checks as in [`Repository::delete_keys`] are applied, but nothing is removed.
     ///
     /// # Arguments
     ///
-    /// * `ids` - The ids of the snapshots to group. If empty, all snapshots are grouped.
-    /// * `group_by` - The criterion to group by
-    /// * `filter` - The filter to use
+    /// * `ids` - The ids of the keys to remove
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If a key is not found in the repository
+    /// * If the key used to open the repository is contained in `ids`
+    /// * If all keys of the repository would be removed
     ///
     /// # Returns
     ///
-    /// If `ids` are given, this will try to resolve the ids (or `latest` with respect to the given filter) and return a single group
-    /// If `ids` is empty, return and group all snapshots respecting the filter.
-    pub fn get_snapshot_group(
-        &self,
-        ids: &[String],
-        group_by: SnapshotGroupCriterion,
-        filter: impl FnMut(&SnapshotFile) -> bool,
-    ) -> RusticResult<Vec<(SnapshotGroup, Vec<SnapshotFile>)>> {
-        commands::snapshots::get_snapshot_group(self, ids, group_by, filter)
+    /// The information about the keys which would be removed, sorted by id.
+    pub fn delete_keys_dry_run(&self, ids: &[KeyId]) -> RusticResult<Vec<KeyInfo>> {
+        commands::key::keys_to_delete(self, ids)
     }
 
-    /// Get a single snapshot
+    // TODO: add documentation!
+    pub(crate) fn dbe(&self) -> &DecryptBackend<Key> {
+        self.status.dbe()
+    }
+
+    /// List all locks of the repository.
+    ///
+    /// # Errors
+    ///
+    /// * If the lock files could not be listed.
+    ///
+    /// # Returns
+    ///
+    /// The ids and contents of all locks, sorted by id. Use [`LockFile::is_stale`] to check for stale locks.
+    pub fn list_locks(&self) -> RusticResult<Vec<(LockId, LockFile)>> {
+        list_locks(self)
+    }
+
+    /// Compress a sample with several compression levels to help choosing a compression level.
+    ///
+    /// The compression dictionary of the repository is used, if set. Use
+    /// [`CompressionSuggestion::recommended`] to get a recommended level for a [`CompressionTarget`](crate::CompressionTarget).
     ///
     /// # Arguments
     ///
-    /// * `id` - The id of the snapshot to get
-    /// * `filter` - The filter to use
+    /// * `sample` - Some data representative for the data to back up
     ///
     /// # Errors
     ///
-    /// * If the string is not a valid hexadecimal string
-    /// * If no id could be found.
-    /// * If the id is not unique.
+    /// * If the sample is empty.
+    /// * If the compression dictionary of the repository is invalid.
+    /// * If the sample could not be compressed.
     ///
     /// # Returns
     ///
-    /// If `id` is (part of) an `Id`, return this snapshot.
+    /// The compressed sizes, ratios and timings of the tested compression levels
+    pub fn suggest_compression_level(&self, sample: &[u8]) -> RusticResult<CompressionSuggestion> {
+        commands::config::suggest_compression_level(self, sample)
+    }
+
+    /// Compute the changes to the repository config applying the given [`ConfigOptions`] would make.
+    ///
+    /// The config is validated like in [`Repository::apply_config`], but not saved.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to apply
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If the version is not supported
+    /// * If the version is lower than the current version
+    /// * If compression is set for a v1 repo
+    /// * If the compression level is not supported
+    /// * If the size is too large
+    /// * If the min pack size tolerance percent is wrong
+    /// * If the max pack size tolerance percent is wrong
+    /// * If the file could not be serialized to json.
+    ///
+    /// # Returns
+    ///
+    /// The changed fields of the config
+    pub fn apply_config_dry_run(&self, opts: &ConfigOptions) -> RusticResult<Vec<ConfigChange>> {
+        commands::config::apply_config_dry_run(self, opts)
+    }
+}
+
+impl<P, S: Writable> Repository<P, S> {
+    /// Add a new key to the repository
+    ///
+    /// # Arguments
+    ///
+    /// * `pass` - The password to use for the new key
+    /// * `opts` - The options to use for the new key
+    ///
+    /// # Errors
+    ///
+    /// * If the key could not be serialized.
+    pub fn add_key(&self, pass: &str, opts: &KeyOptions) -> RusticResult<KeyId> {
+        add_current_key_to_repo(self, opts, pass)
+    }
+
+    /// Remove the given keys from the repository
+    ///
+    /// Use [`Repository::delete_keys_dry_run`] to check which keys would be removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The ids of the keys to remove
+    ///
+    /// # Errors
+    ///
+    /// * If a key is not found in the repository
+    /// * If the key used to open the repository is contained in `ids`
+    /// * If all keys of the repository would be removed
+    /// * If removing a key file failed
+    ///
+    /// # Returns
+    ///
+    /// The information about the removed keys, sorted by id.
+    pub fn delete_keys(&self, ids: &[KeyId]) -> RusticResult<Vec<KeyInfo>> {
+        commands::key::delete_keys(self, ids)
+    }
+
+    /// Update the repository config by applying the given [`ConfigOptions`]
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to apply
+    ///
+    /// # Errors
+    ///
+    /// * If the version is not supported
+    /// * If the version is lower than the current version
+    /// * If compression is set for a v1 repo
+    /// * If the compression level is not supported
+    /// * If the size is too large
+    /// * If the min pack size tolerance percent is wrong
+    /// * If the max pack size tolerance percent is wrong
+    /// * If the compression dictionary could not be trained from the repository data
+    /// * If the file could not be serialized to json.
+    pub fn apply_config(&self, opts: &ConfigOptions) -> RusticResult<bool> {
+        commands::config::apply_config(self, opts)
+    }
+
+    /// Migrate the repository to the version reported by [`Repository::needs_migration`]
+    ///
+    /// This only performs the changes which are safe on config level, i.e. increases the
+    /// repository version up to version 2. It is validated like [`Repository::apply_config`].
+    /// In particular, downgrades are never done. Existing pack files are not changed:
+    ///
+    /// * Migrating from version 1 enables compression for newly written data. Existing data stays
+    ///   uncompressed; use [`Repository::prune`] with
+    ///   [`PruneOptions::repack_uncompressed`] to compress it, which requires a repack.
+    /// * Version 3 is never chosen by this method, as it needs a compression dictionary. Use
+    ///   [`Repository::apply_config`] with [`ConfigOptions::train_compression_dictionary`]
+    ///   to migrate to it. Already compressed data is not recompressed with the dictionary,
+    ///   this also would require a repack.
+    ///
+    /// # Arguments
+    ///
+    /// * `dry_run` - Only compute the changes, don't save the config
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If the repository uses an unsupported or newer version
+    /// * If the file could not be serialized to json.
+    ///
+    /// # Returns
+    ///
+    /// The changed fields of the config; empty if the repository already uses the latest version
+    pub fn migrate_to_latest(&self, dry_run: bool) -> RusticResult<Vec<ConfigChange>> {
+        commands::config::migrate_to_latest(self, dry_run)
+    }
+
+    /// Lock the repository exclusively.
+    ///
+    /// This is done automatically by destructive operations like `delete_snapshots`, `prune`,
+    /// `forget_and_prune` and `repair`. Operations which only remove single snapshots, like `forget`,
+    /// don't lock the repository, so they can be used while holding a lock.
+    /// Locks are written in the restic lock format to the `locks` directory, so they are respected
+    /// by restic, too. The lock is refreshed while held and removed when the returned guard is dropped.
+    ///
+    /// Each lock is tracked by its own lock file, so locks held by the current process conflict
+    /// with each other like locks of other processes.
+    ///
+    /// # Errors
+    ///
+    /// * If the lock file could not be written.
+    /// * If the repository is locked by another lock.
+    pub fn lock_exclusive(&self) -> RusticResult<RepositoryLock> {
+        lock_repository(self, true)
+    }
+
+    /// Lock the repository non-exclusively.
+    ///
+    /// Shared locks only conflict with exclusive locks. Use this to protect read operations like
+    /// `check` or `restore` from concurrently running destructive operations.
+    ///
+    /// # Errors
+    ///
+    /// * If the lock file could not be written.
+    /// * If the repository is locked exclusively by another lock.
+    pub fn lock_shared(&self) -> RusticResult<RepositoryLock> {
+        lock_repository(self, false)
+    }
+
+    /// Remove locks from the repository.
+    ///
+    /// # Warning
+    ///
+    /// * Removing locks which are not stale may allow conflicting operations which can corrupt the repository!
+    ///
+    /// # Arguments
+    ///
+    /// * `only_stale` - Whether to only remove stale locks, see [`LockFile::is_stale`]
+    ///
+    /// # Errors
+    ///
+    /// * If the lock files could not be listed or removed.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the removed locks
+    pub fn break_locks(&self, only_stale: bool) -> RusticResult<Vec<LockId>> {
+        break_locks(self, only_stale)
+    }
+}
+
+impl<P: ProgressBars, S: Open> Repository<P, S> {
+    /// Get grouped snapshots.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The ids of the snapshots to group. If empty, all snapshots are grouped.
+    /// * `group_by` - The criterion to group by
+    /// * `filter` - The filter to use
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// If `ids` are given, this will try to resolve the ids (or `latest` with respect to the given filter) and return a single group
+    /// If `ids` is empty, return and group all snapshots respecting the filter.
+    pub fn get_snapshot_group(
+        &self,
+        ids: &[String],
+        group_by: SnapshotGroupCriterion,
+        filter: impl FnMut(&SnapshotFile) -> bool,
+    ) -> RusticResult<Vec<(SnapshotGroup, Vec<SnapshotFile>)>> {
+        commands::snapshots::get_snapshot_group(self, ids, group_by, filter)
+    }
+
+    /// Get a summary of grouped snapshots.
+    ///
+    /// In contrast to [`Repository::get_snapshot_group`], the snapshots are not retained but only
+    /// aggregated per group. This is useful for repositories with many snapshots.
+    ///
+    /// # Arguments
+    ///
+    /// * `group_by` - The criterion to group by
+    /// * `filter` - The filter to use
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshots could not be listed or read.
+    ///
+    /// # Returns
+    ///
+    /// The groups together with their [`GroupSummary`], sorted by group.
+    pub fn snapshot_group_summary(
+        &self,
+        group_by: SnapshotGroupCriterion,
+        filter: impl FnMut(&SnapshotFile) -> bool,
+    ) -> RusticResult<Vec<(SnapshotGroup, GroupSummary)>> {
+        commands::snapshots::snapshot_group_summary(self, group_by, filter)
+    }
+
+    /// Get a single snapshot
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the snapshot to get
+    /// * `filter` - The filter to use
+    ///
+    /// # Errors
+    ///
+    /// * If the string is not a valid hexadecimal string
+    /// * If no id could be found.
+    /// * If the id is not unique.
+    ///
+    /// # Returns
+    ///
+    /// If `id` is (part of) an `Id`, return this snapshot.
     /// If `id` is "latest", return the latest snapshot respecting the giving filter.
     pub fn get_snapshot_from_str(
         &self,
@@ -981,6 +1992,19 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         result
     }
 
+    /// Check whether a snapshot with the given id exists in the repository
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the snapshot to check
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshot files could not be listed
+    pub fn snapshot_exists(&self, id: &SnapshotId) -> RusticResult<bool> {
+        Ok(self.list::<SnapshotId>()?.any(|snap_id| snap_id == *id))
+    }
+
     /// Get all snapshots from the repository
     ///
     /// # Errors
@@ -990,6 +2014,32 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         self.get_matching_snapshots(|_| true)
     }
 
+    /// Get all snapshots from the repository sorted by `sort`
+    ///
+    /// # Arguments
+    ///
+    /// * `sort` - The order to sort the snapshots in
+    /// * `range` - If given, only return the sorted snapshots within this range; parts out of bounds are ignored
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshot files could not be listed
+    /// * If a snapshot file could not be read or deserialized
+    pub fn get_snapshots_sorted(
+        &self,
+        sort: SnapshotSort,
+        range: Option<Range<usize>>,
+    ) -> RusticResult<Vec<SnapshotFile>> {
+        let mut snapshots = self.update_matching_snapshots(Vec::new(), |_| true)?;
+        snapshots.sort_unstable_by(|sn1, sn2| sort.compare(sn1, sn2));
+        if let Some(range) = range {
+            let end = range.end.min(snapshots.len());
+            snapshots.truncate(end);
+            _ = snapshots.drain(..range.start.min(end));
+        }
+        Ok(snapshots)
+    }
+
     /// Update existing snapshots to all from the repository
     ///
     /// # Arguments
@@ -1075,6 +2125,33 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         commands::forget::get_forget_snapshots(self, keep, group_by, filter)
     }
 
+    /// Compute what [`Repository::forget_and_prune`] would do without modifying the repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - The keep options to use
+    /// * `group_by` - The criterion to group by
+    /// * `filter` - The filter to use
+    /// * `prune_opts` - The options for the pruning
+    ///
+    /// # Errors
+    ///
+    /// * If keep options are not valid
+    /// * If the prune plan could not be computed
+    ///
+    /// # Returns
+    ///
+    /// The groups of snapshots to forget, the ids of the snapshots to forget and the statistics of the prune plan
+    pub fn forget_and_prune_dry_run(
+        &self,
+        keep: &KeepOptions,
+        group_by: SnapshotGroupCriterion,
+        filter: impl FnMut(&SnapshotFile) -> bool,
+        prune_opts: &PruneOptions,
+    ) -> RusticResult<ForgetPruneResult> {
+        commands::forget::forget_and_prune_dry_run(self, keep, group_by, filter, prune_opts)
+    }
+
     /// Get snapshots which are not already present and should be present.
     ///
     /// # Arguments
@@ -1097,132 +2174,242 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         commands::copy::relevant_snapshots(snaps, self, filter)
     }
 
-    // TODO: Maybe only offer a method to remove &[Snapshotfile] and check if they must be kept.
-    // See e.g. the merge command of the CLI
-    /// Remove the given snapshots from the repository
+    /// Check the repository and all snapshot trees for errors or inconsistencies
+    ///
+    /// The issues found are returned and, unless `opts.quiet` is set, logged.
+    /// Use [`CheckResults::into_result`] to turn found errors into an `Err`.
+    ///
+    /// If `opts.since` is set, only snapshots newer than the given snapshot are checked, see [`CheckOptions::since`].
     ///
     /// # Arguments
     ///
-    /// * `ids` - The ids of the snapshots to remove
+    /// * `opts` - The options to use
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If the snapshots or the index could not be read.
+    /// * If the snapshot given in `opts.since` does not exist.
+    /// * If a tree could not be loaded.
+    /// * If the check has been cancelled.
     ///
-    /// # Panics
+    /// # Returns
     ///
-    /// * If the files could not be deleted.
-    pub fn delete_snapshots(&self, ids: &[SnapshotId]) -> RusticResult<()> {
-        if self.config().append_only == Some(true) {
-            return Err(
-                RusticError::new(
-                    ErrorKind::Repository,
-                    "Repository is in append-only mode and snapshots cannot be deleted from it. Aborting.",
-                )
-            );
-        }
-        let p = self.pb.progress_counter("removing snapshots...");
-        self.dbe().delete_list(true, ids.iter(), p)?;
-        Ok(())
+    /// The issues found
+    pub fn check(&self, opts: CheckOptions) -> RusticResult<CheckResults> {
+        let trees = snapshots_since(self.get_all_snapshots()?, opts.since)?
+            .into_iter()
+            .map(|snap| snap.tree)
+            .collect();
+
+        check_repository(self, opts, trees)
     }
 
-    /// Save the given snapshots to the repository.
+    /// Check the repository and given trees for errors or inconsistencies
+    ///
+    /// If `opts.since` is set, only packs written since the given snapshot are read.
     ///
     /// # Arguments
     ///
-    /// * `snaps` - The snapshots to save
+    /// * `opts` - The options to use
+    /// * `trees` - The trees to check
     ///
     /// # Errors
     ///
-    /// * If the file could not be serialized to json.
-    pub fn save_snapshots(&self, mut snaps: Vec<SnapshotFile>) -> RusticResult<()> {
-        for snap in &mut snaps {
-            snap.id = SnapshotId::default();
-        }
-        let p = self.pb.progress_counter("saving snapshots...");
-        self.dbe().save_list(snaps.iter(), p)?;
-        Ok(())
-    }
-
-    /// Check the repository and all snapshot trees for errors or inconsistencies
+    /// * If the index could not be read.
+    /// * If a tree could not be loaded.
+    /// * If the check has been cancelled.
+    ///
+    /// # Returns
+    ///
+    /// The issues found
+    pub fn check_with_trees(
+        &self,
+        opts: CheckOptions,
+        trees: Vec<TreeId>,
+    ) -> RusticResult<CheckResults> {
+        check_repository(self, opts, trees)
+    }
+
+    /// Check a single snapshot for errors or inconsistencies
+    ///
+    /// Only the tree of the given snapshot and the packs referenced by it are checked.
+    /// The headers of these packs are always verified; their contents are read if `opts.read_data` is set,
+    /// restricted to `opts.read_data_subset`.
     ///
     /// # Arguments
     ///
+    /// * `snap` - The snapshot to check
     /// * `opts` - The options to use
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If the index could not be read.
+    /// * If a tree of the snapshot could not be loaded.
     ///
-    /// # Panics
+    /// # Returns
     ///
-    // TODO: Document panics
-    pub fn check(&self, opts: CheckOptions) -> RusticResult<()> {
-        let trees = self
-            .get_all_snapshots()?
-            .into_iter()
-            .map(|snap| snap.tree)
-            .collect();
+    /// The issues found
+    pub fn check_snapshot(
+        &self,
+        snap: &SnapshotFile,
+        opts: CheckOptions,
+    ) -> RusticResult<CheckResults> {
+        check_snapshot(self, opts, snap)
+    }
 
-        check_repository(self, opts, trees)?;
+    /// Verify a single pack file
+    ///
+    /// The pack is read from the backend and its size, hash, header length and header are compared
+    /// with the index. If these are fine, the hashes of all contained blobs are verified.
+    /// In contrast to [`Repository::check`], the problems found are returned instead of logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - The id of the pack to verify
+    ///
+    /// # Errors
+    ///
+    /// * If the index could not be read.
+    /// * If the pack is not referenced by the index.
+    /// * If the pack could not be read from the backend.
+    /// * If the pack header or a blob could not be decrypted.
+    ///
+    /// # Returns
+    ///
+    /// The problems found within the pack
+    pub fn verify_pack(&self, pack: PackId) -> RusticResult<PackVerification> {
+        verify_pack(self, pack)
+    }
 
-        Ok(())
+    /// Find index files which reference packs that no longer exist in the backend
+    ///
+    /// This can e.g. happen after an interrupted prune. In contrast to [`Repository::check`], which only
+    /// reports the missing packs, the affected index files are returned, so they can be repaired.
+    /// The repository is not modified.
+    ///
+    /// # Errors
+    ///
+    /// * If the packs could not be listed.
+    /// * If an index file could not be read.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the affected index files together with the missing packs they reference
+    pub fn find_stale_index_entries(&self) -> RusticResult<Vec<(IndexId, Vec<PackId>)>> {
+        find_stale_index_entries(self)
     }
 
-    /// Check the repository and given trees for errors or inconsistencies
+    /// Read the header of a pack file directly from the pack, bypassing the index
+    ///
+    /// This can be used to diagnose mismatches between index and pack files or to rebuild an index.
     ///
     /// # Arguments
     ///
-    /// * `opts` - The options to use
+    /// * `pack` - The id of the pack to read the header from
     ///
     /// # Errors
     ///
-    // TODO: Document errors
-    /// # Panics
+    /// * If the pack files could not be listed.
+    /// * If the pack does not exist.
+    /// * If the pack header could not be read or decrypted.
+    /// * If the pack header does not match the pack size.
     ///
-    // TODO: Document panics
-    pub fn check_with_trees(&self, opts: CheckOptions, trees: Vec<TreeId>) -> RusticResult<()> {
-        check_repository(self, opts, trees)
+    /// # Returns
+    ///
+    /// The blobs contained in the pack as given by the pack header
+    pub fn read_pack_header(&self, pack: PackId) -> RusticResult<Vec<IndexBlob>> {
+        let size = self
+            .dbe()
+            .list_with_size(FileType::Pack)?
+            .into_iter()
+            .find_map(|(id, size)| (PackId::from(id) == pack).then_some(size))
+            .ok_or_else(|| {
+                RusticError::new(
+                    ErrorKind::InvalidInput,
+                    "Pack `{pack_id}` does not exist in the repository.",
+                )
+                .attach_context("pack_id", pack.to_string())
+            })?;
+
+        self.warm_up_wait(std::iter::once(pack))?;
+        Ok(PackHeader::from_file(self.dbe(), pack, None, size)?.into_blobs())
     }
 
-    /// Get the plan about what should be pruned and/or repacked.
+    /// Read all blobs contained in a pack file
+    ///
+    /// The pack is read from the backend in a single request and its header is parsed from the
+    /// pack itself, so the index is not needed. All blobs are decrypted and decompressed.
+    ///
+    /// # Note
+    ///
+    /// The whole pack as well as all decompressed blobs are held in memory. For large packs
+    /// (e.g. data packs of several hundred MiB) consider [`Repository::cat_pack_blobs_with`] which
+    /// only keeps the raw pack and the blob currently processed in memory.
     ///
     /// # Arguments
     ///
-    /// * `opts` - The options to use
+    /// * `pack` - The id of the pack to read
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If the pack could not be read from the backend.
+    /// * If the pack header could not be read or decrypted.
+    /// * If a blob could not be decrypted or decompressed.
     ///
     /// # Returns
     ///
-    /// The plan about what should be pruned and/or repacked.
-    pub fn prune_plan(&self, opts: &PruneOptions) -> RusticResult<PrunePlan> {
-        PrunePlan::from_prune_options(self, opts)
+    /// The id, type and content of all blobs in the order they are stored in the pack
+    pub fn cat_pack_blobs(&self, pack: PackId) -> RusticResult<Vec<(BlobId, BlobType, Bytes)>> {
+        let mut blobs = Vec::new();
+        commands::cat::cat_pack_blobs_with(self, pack, |id, tpe, data| {
+            blobs.push((id, tpe, data));
+            Ok(())
+        })?;
+        Ok(blobs)
     }
 
-    /// Perform the pruning on the repository.
+    /// Read all blobs contained in a pack file and pass them to a callback
+    ///
+    /// This is the streaming variant of [`Repository::cat_pack_blobs`]: The pack is read once,
+    /// but each blob is handed to `callback` right after it has been decrypted and can be dropped
+    /// afterwards.
     ///
     /// # Arguments
     ///
-    /// * `opts` - The options for the pruning
-    /// * `prune_plan` - The plan about what should be pruned and/or repacked
+    /// * `pack` - The id of the pack to read
+    /// * `callback` - Called with id, type and content of each blob in the order they are stored in the pack
     ///
     /// # Errors
     ///
-    /// * If the repository is in append-only mode
-    /// * If a pack has no decision
+    /// * If the pack could not be read from the backend.
+    /// * If the pack header could not be read or decrypted.
+    /// * If a blob could not be decrypted or decompressed.
+    /// * If the callback returns an error. In this case, no further blobs are processed.
+    pub fn cat_pack_blobs_with(
+        &self,
+        pack: PackId,
+        callback: impl FnMut(BlobId, BlobType, Bytes) -> RusticResult<()>,
+    ) -> RusticResult<()> {
+        commands::cat::cat_pack_blobs_with(self, pack, callback)
+    }
+
+    /// Get the plan about what should be pruned and/or repacked.
     ///
-    /// # Returns
+    /// The repository is not locked, the lock is only taken by [`Repository::prune`] when executing the plan.
     ///
-    /// * `Ok(())` - If the pruning was successful
+    /// # Arguments
     ///
-    /// # Panics
+    /// * `opts` - The options to use
     ///
-    // TODO: Document panics
-    pub fn prune(&self, opts: &PruneOptions, prune_plan: PrunePlan) -> RusticResult<()> {
-        prune_repository(self, opts, prune_plan)
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// The plan about what should be pruned and/or repacked.
+    pub fn prune_plan(&self, opts: &PruneOptions) -> RusticResult<PrunePlan> {
+        PrunePlan::from_prune_options(self, opts)
     }
 
     /// Turn the repository into the `IndexedFull` state by reading and storing the index
@@ -1235,7 +2422,7 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
     ///
     /// This saves the full index in memory which can be quite memory-consuming!
     pub fn to_indexed(self) -> RusticResult<Repository<P, IndexedStatus<FullIndex, S>>> {
-        let index = GlobalIndex::new(self.dbe(), &self.pb.progress_counter(""))?;
+        let index = GlobalIndex::new(self.dbe(), &self.pb.progress_counter("reading index..."))?;
         Ok(self.into_indexed_with_index(index))
     }
 
@@ -1257,6 +2444,49 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         Ok(self.into_indexed_with_index(index))
     }
 
+    /// Turn the repository into the `IndexedFull` state by importing an index previously
+    /// exported by [`Repository::export_index`]
+    ///
+    /// This doesn't read any index file from the backend. Use [`Repository::refresh_index`]
+    /// afterwards if the repository might have been changed since the export.
+    ///
+    /// # Arguments
+    ///
+    /// * `r` - The reader to read the exported index from
+    ///
+    /// # Errors
+    ///
+    /// * If the exported index could not be read
+    /// * If the exported index could not be decrypted or deserialized
+    /// * If the exported index belongs to another repository
+    ///
+    /// # Note
+    ///
+    /// This saves the full index in memory which can be quite memory-consuming!
+    pub fn import_index(
+        self,
+        r: &mut impl Read,
+    ) -> RusticResult<Repository<P, IndexedStatus<FullIndex, S>>> {
+        let mut data = Vec::new();
+        _ = r.read_to_end(&mut data).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to read the exported index.",
+                err,
+            )
+        })?;
+        let data = self.dbe().decrypt_file(&data)?;
+        let exported = serde_json::from_slice(&data).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InvalidInput,
+                "Failed to deserialize the exported index.",
+                err,
+            )
+        })?;
+        let index = GlobalIndex::import(exported, self.config().id)?;
+        Ok(self.into_indexed_with_index(index))
+    }
+
     // helper function to deduplicate code
     fn into_indexed_with_index(
         self,
@@ -1265,14 +2495,11 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         let status = IndexedStatus {
             open: self.status,
             index,
-            index_data: FullIndex {
-                // TODO: Make cache size (32MB currently) customizable!
-                cache: quick_cache::sync::Cache::with_weighter(
-                    constants::ESTIMATED_ITEM_CAPACITY,
-                    constants::WEIGHT_CAPACITY,
-                    BytesWeighter {},
-                ),
-            },
+            index_data: FullIndex::new(
+                self.opts
+                    .blob_cache_size
+                    .unwrap_or(constants::WEIGHT_CAPACITY),
+            ),
         };
         Repository {
             name: self.name,
@@ -1280,6 +2507,7 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
             be_hot: self.be_hot,
             opts: self.opts,
             pb: self.pb,
+            cancel: self.cancel,
             status,
         }
     }
@@ -1299,7 +2527,10 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
     /// This saves only the `Id`s for data blobs. Therefore, not all operations are possible on the repository.
     /// However, operations which add data are fully functional.
     pub fn to_indexed_ids(self) -> RusticResult<Repository<P, IndexedStatus<IdIndex, S>>> {
-        let index = GlobalIndex::only_full_trees(self.dbe(), &self.pb.progress_counter(""))?;
+        let index = GlobalIndex::only_full_trees(
+            self.dbe(),
+            &self.pb.progress_counter("reading index..."),
+        )?;
         Ok(self.into_indexed_ids_with_index(index))
     }
 
@@ -1342,6 +2573,7 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
             be_hot: self.be_hot,
             opts: self.opts,
             pb: self.pb,
+            cancel: self.cancel,
             status,
         }
     }
@@ -1360,6 +2592,27 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
         commands::repoinfo::collect_index_infos(self)
     }
 
+    /// Get the distribution of pack sizes from the index. This method reads all index files,
+    /// but doesn't read any pack.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to use, e.g. the bucket boundaries
+    ///
+    /// # Errors
+    ///
+    /// * If the index could not be read.
+    ///
+    /// # Returns
+    ///
+    /// The number and total size of packs within each bucket, separately for tree and data packs.
+    pub fn pack_size_histogram(
+        &self,
+        opts: &PackSizeHistogramOptions,
+    ) -> RusticResult<PackSizeHistogram> {
+        commands::repoinfo::collect_pack_size_histogram(self, opts)
+    }
+
     /// Read all files of a given [`RepoFile`]
     ///
     /// # Errors
@@ -1381,6 +2634,172 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
             .stream_all::<F>(&self.pb.progress_hidden())?
             .into_iter())
     }
+}
+
+impl<P: ProgressBars, S: Writable> Repository<P, S> {
+    // TODO: Maybe only offer a method to remove &[Snapshotfile] and check if they must be kept.
+    // See e.g. the merge command of the CLI
+    /// Remove the given snapshots from the repository
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The ids of the snapshots to remove
+    ///
+    /// The repository is locked exclusively while removing the snapshots.
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If the repository is locked by another lock
+    /// * If the files could not be deleted
+    pub fn delete_snapshots(&self, ids: &[SnapshotId]) -> RusticResult<()> {
+        let _lock = self.lock_exclusive()?;
+        self.delete_snapshots_unlocked(ids)
+    }
+
+    /// Remove the given snapshots from the repository without locking it
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The ids of the snapshots to remove
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If the files could not be deleted
+    pub(crate) fn delete_snapshots_unlocked(&self, ids: &[SnapshotId]) -> RusticResult<()> {
+        if self.config().append_only == Some(true) {
+            return Err(
+                RusticError::new(
+                    ErrorKind::Repository,
+                    "Repository is in append-only mode and snapshots cannot be deleted from it. Aborting.",
+                )
+            );
+        }
+        let p = self.pb.progress_counter("removing snapshots...");
+        self.dbe().delete_list(true, ids.iter(), p)?;
+        Ok(())
+    }
+
+    /// Forget snapshots depending on the given [`KeepOptions`]
+    ///
+    /// This computes the snapshots to forget like [`Repository::get_forget_snapshots`] and removes them.
+    /// If the repository is in append-only mode, nothing is removed and an error listing the snapshots
+    /// which would have been forgotten is returned.
+    ///
+    /// As a safety check, nothing is removed if all snapshots of a group would be forgotten, unless
+    /// [`KeepOptions::keep_none`] is set. The error then lists the offending groups,
+    /// see also [`ForgetGroups::groups_keeping_nothing`].
+    ///
+    /// The repository is not locked, use [`Repository::lock_exclusive`] to protect the operation.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - The keep options to use
+    /// * `group_by` - The criterion to group by
+    /// * `filter` - The filter to use
+    ///
+    /// # Errors
+    ///
+    /// * If keep options are not valid
+    /// * If a group would keep no snapshot and `keep_none` is not set
+    /// * If the repository is in append-only mode and there are snapshots to forget
+    /// * If the snapshots could not be removed
+    ///
+    /// # Returns
+    ///
+    /// The ids of the removed snapshots
+    pub fn forget(
+        &self,
+        keep: &KeepOptions,
+        group_by: SnapshotGroupCriterion,
+        filter: impl FnMut(&SnapshotFile) -> bool,
+    ) -> RusticResult<Vec<SnapshotId>> {
+        commands::forget::forget(self, keep, group_by, filter)
+    }
+
+    /// Forget snapshots depending on the given [`KeepOptions`] and prune the repository afterwards.
+    ///
+    /// The snapshots to forget and the prune plan are computed before anything is modified, so invalid
+    /// options don't leave the repository with forgotten snapshots but unpruned data. The repository is
+    /// locked exclusively during the whole operation.
+    ///
+    /// Use [`Repository::forget_and_prune_dry_run`] to only compute the plans.
+    ///
+    /// Like [`Repository::forget`], nothing is modified if all snapshots of a group would be forgotten,
+    /// unless [`KeepOptions::keep_none`] is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `keep` - The keep options to use
+    /// * `group_by` - The criterion to group by
+    /// * `filter` - The filter to use
+    /// * `prune_opts` - The options for the pruning
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If keep options are not valid
+    /// * If a group would keep no snapshot and `keep_none` is not set
+    /// * If the prune plan could not be computed
+    /// * If the snapshots could not be removed
+    /// * If pruning failed
+    ///
+    /// # Returns
+    ///
+    /// The groups of snapshots, the ids of the removed snapshots and the statistics of the prune plan
+    pub fn forget_and_prune(
+        &self,
+        keep: &KeepOptions,
+        group_by: SnapshotGroupCriterion,
+        filter: impl FnMut(&SnapshotFile) -> bool,
+        prune_opts: &PruneOptions,
+    ) -> RusticResult<ForgetPruneResult> {
+        commands::forget::forget_and_prune(self, keep, group_by, filter, prune_opts)
+    }
+
+    /// Save the given snapshots to the repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `snaps` - The snapshots to save
+    ///
+    /// # Errors
+    ///
+    /// * If the file could not be serialized to json.
+    pub fn save_snapshots(&self, mut snaps: Vec<SnapshotFile>) -> RusticResult<()> {
+        for snap in &mut snaps {
+            snap.id = SnapshotId::default();
+        }
+        let p = self.pb.progress_counter("saving snapshots...");
+        self.dbe().save_list(snaps.iter(), p)?;
+        Ok(())
+    }
+
+    /// Perform the pruning on the repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options for the pruning
+    /// * `prune_plan` - The plan about what should be pruned and/or repacked
+    ///
+    /// # Errors
+    ///
+    /// * If the repository is in append-only mode
+    /// * If the repository is locked by another lock
+    /// * If a pack has no decision
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If the pruning was successful
+    ///
+    /// # Panics
+    ///
+    // TODO: Document panics
+    pub fn prune(&self, opts: &PruneOptions, prune_plan: PrunePlan) -> RusticResult<()> {
+        let _lock = self.lock_exclusive()?;
+        prune_repository(self, opts, prune_plan)
+    }
 
     /// Repair the index
     ///
@@ -1396,6 +2815,7 @@ impl<P: ProgressBars, S: Open> Repository<P, S> {
     ///
     // TODO: Document errors
     pub fn repair_index(&self, opts: &RepairIndexOptions, dry_run: bool) -> RusticResult<()> {
+        let _lock = (!dry_run).then(|| self.lock_exclusive()).transpose()?;
         repair_index(self, *opts, dry_run)
     }
 }
@@ -1502,11 +2922,44 @@ pub struct IdIndex;
 /// As we usually use this to access data blobs from the repository, we also have defined a blob cache for
 /// repositories with full index.
 pub struct FullIndex {
-    cache: quick_cache::sync::Cache<BlobId, Bytes, BytesWeighter>,
+    /// The blob cache; `None` if caching is disabled
+    cache: Option<quick_cache::sync::Cache<BlobId, Bytes, BytesWeighter>>,
 }
 
-impl<T, S: Open> IndexedTree for IndexedStatus<T, S> {
-    type I = GlobalIndex;
+impl FullIndex {
+    /// Create a new [`FullIndex`] with a blob cache of the given size
+    ///
+    /// # Arguments
+    ///
+    /// * `weight_capacity` - The maximum size of the blob cache in bytes; `0` disables the cache
+    fn new(weight_capacity: u64) -> Self {
+        let cache = (weight_capacity > 0).then(|| {
+            quick_cache::sync::Cache::with_weighter(
+                constants::ESTIMATED_ITEM_CAPACITY,
+                weight_capacity,
+                BytesWeighter {},
+            )
+        });
+        Self { cache }
+    }
+
+    /// Get a blob from the cache or insert it with the given function
+    ///
+    /// If caching is disabled, the function is always called.
+    fn get_blob_or_insert_with(
+        &self,
+        id: &BlobId,
+        with: impl FnOnce() -> RusticResult<Bytes>,
+    ) -> RusticResult<Bytes> {
+        match &self.cache {
+            Some(cache) => cache.get_or_insert_with(id, with),
+            None => with(),
+        }
+    }
+}
+
+impl<T, S: Open> IndexedTree for IndexedStatus<T, S> {
+    type I = GlobalIndex;
 
     fn index(&self) -> &Self::I {
         &self.index
@@ -1547,7 +3000,7 @@ impl<S: Open> IndexedFull for IndexedStatus<FullIndex, S> {
         id: &BlobId,
         with: impl FnOnce() -> RusticResult<Bytes>,
     ) -> RusticResult<Bytes> {
-        self.index_data.cache.get_or_insert_with(id, with)
+        self.index_data.get_blob_or_insert_with(id, with)
     }
 }
 
@@ -1567,6 +3020,8 @@ impl<P, S: IndexedFull> IndexedFull for Repository<P, S> {
     }
 }
 
+impl<T, S: Writable> Writable for IndexedStatus<T, S> {}
+
 impl<T, S: Open> Open for IndexedStatus<T, S> {
     fn cache(&self) -> Option<&Cache> {
         self.open.cache()
@@ -1579,6 +3034,93 @@ impl<T, S: Open> Open for IndexedStatus<T, S> {
     fn config(&self) -> &ConfigFile {
         self.open.config()
     }
+
+    fn key_id(&self) -> &KeyId {
+        self.open.key_id()
+    }
+}
+
+impl<P, T, S: Open> Repository<P, IndexedStatus<T, S>> {
+    /// drop the `Repository` index leaving a `Repository` in the status it had before indexing
+    ///
+    /// In contrast to [`Repository::drop_index`], this keeps the concrete status type,
+    /// so a writable repository stays [`Writable`].
+    pub fn to_open(self) -> Repository<P, S> {
+        Repository {
+            name: self.name,
+            be: self.be,
+            be_hot: self.be_hot,
+            opts: self.opts,
+            pb: self.pb,
+            cancel: self.cancel,
+            status: self.status.open,
+        }
+    }
+}
+
+impl<P: ProgressBars, T, S: Open> Repository<P, IndexedStatus<T, S>> {
+    /// Refresh the index by reading index files which have been added since the index was read
+    ///
+    /// Only new index files are read. If index files have been removed in the meantime, e.g. by `prune`,
+    /// the whole index is read again. If the index is shared with a clone of this repository, it is
+    /// copied before new entries are added.
+    ///
+    /// # Errors
+    ///
+    /// * If the index files could not be listed.
+    /// * If an index file could not be read.
+    pub fn refresh_index(&mut self) -> RusticResult<()> {
+        let dbe = self.dbe().clone();
+        let p = self.pb.progress_counter("refreshing index...");
+        self.status.index.update(&dbe, &p)
+    }
+}
+
+impl<P, S: Open> Repository<P, IndexedStatus<FullIndex, S>> {
+    /// drop the data pack information from the `Repository` index leaving an `IndexedTree` `Repository`
+    pub fn drop_data_from_index(self) -> Repository<P, IndexedStatus<TreeIndex, S>> {
+        Repository {
+            name: self.name,
+            be: self.be,
+            be_hot: self.be_hot,
+            opts: self.opts,
+            pb: self.pb,
+            cancel: self.cancel,
+            status: IndexedStatus {
+                index: self.status.index.drop_data(),
+                index_data: TreeIndex,
+                open: self.status.open,
+            },
+        }
+    }
+
+    /// Export the in-memory index such that it can be imported by [`Repository::import_index`]
+    ///
+    /// The index is encrypted with the repository key and compressed if the repository
+    /// supports compression.
+    ///
+    /// # Arguments
+    ///
+    /// * `w` - The writer to write the exported index to
+    ///
+    /// # Errors
+    ///
+    /// * If the index could not be serialized or encrypted
+    /// * If the exported index could not be written
+    pub fn export_index(&self, w: &mut impl Write) -> RusticResult<()> {
+        let exported = self.status.index.export(self.config().id)?;
+        let data = serde_json::to_vec(&exported).map_err(|err| {
+            RusticError::with_source(ErrorKind::Internal, "Failed to serialize the index.", err)
+        })?;
+        let data = self.dbe().encrypt_file(&data)?;
+        w.write_all(&data).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Failed to write the exported index.",
+                err,
+            )
+        })
+    }
 }
 
 impl<P, S: IndexedFull> Repository<P, S> {
@@ -1606,6 +3148,55 @@ impl<P, S: IndexedFull> Repository<P, S> {
         Ok(ie)
     }
 
+    /// Locate the given blob, i.e. get the pack containing it and its position within the pack
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the blob
+    /// * `id` - The id of the blob
+    ///
+    /// # Returns
+    ///
+    /// The [`IndexEntry`] of the blob or `None` if the blob is not contained in the index
+    pub fn locate_blob(&self, tpe: BlobType, id: &BlobId) -> RusticResult<Option<IndexEntry>> {
+        Ok(self.index().get_id(tpe, id))
+    }
+
+    /// Locate the given blobs, see [`Repository::locate_blob`]
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the blobs
+    /// * `ids` - The ids of the blobs
+    ///
+    /// # Returns
+    ///
+    /// The ids together with the [`IndexEntry`]s of the blobs in the given order
+    pub fn locate_blobs<'a>(
+        &self,
+        tpe: BlobType,
+        ids: impl IntoIterator<Item = &'a BlobId>,
+    ) -> RusticResult<Vec<(BlobId, Option<IndexEntry>)>> {
+        Ok(ids
+            .into_iter()
+            .map(|id| (*id, self.index().get_id(tpe, id)))
+            .collect())
+    }
+
+    /// List the contents of the given pack as stored in the index
+    ///
+    /// # Arguments
+    ///
+    /// * `pack` - The id of the pack
+    ///
+    /// # Returns
+    ///
+    /// The ids and [`IndexEntry`]s of all blobs in the pack, sorted by offset.
+    /// If the pack is not contained in the index, this is empty.
+    pub fn pack_contents(&self, pack: PackId) -> RusticResult<Vec<(BlobId, IndexEntry)>> {
+        Ok(self.index().pack_entries(&pack))
+    }
+
     /// Open a file in the repository for reading
     ///
     /// # Arguments
@@ -1720,6 +3311,34 @@ impl<P, S: IndexedTree> Repository<P, S> {
         Tree::find_matching_nodes(self.dbe(), self.index(), ids, matches)
     }
 
+    /// Get all [`Node`]s/[`Path`]s from given root trees which match one of the given glob patterns
+    ///
+    /// This is a convenience wrapper around [`Repository::find_matching_nodes`]. The patterns use the
+    /// gitignore syntax like the globs of `ls`, e.g. `*.pem` matches all `.pem` files at any depth and
+    /// patterns starting with `!` exclude matching paths.
+    ///
+    /// All given trees are searched, e.g. the trees of all snapshots to find all matching files ever
+    /// backed up. Results of subtrees are cached, so identical subtrees are only searched once.
+    ///
+    /// # Arguments
+    ///
+    /// * `ids` - The tree ids to search in
+    /// * `patterns` - The glob patterns to match
+    /// * `case_insensitive` - Whether the patterns are matched case-insensitively
+    ///
+    /// # Errors
+    ///
+    /// * If a glob pattern is invalid
+    /// * If loading trees from the backend fails
+    pub fn find_nodes_matching_glob(
+        &self,
+        ids: impl IntoIterator<Item = TreeId>,
+        patterns: &[String],
+        case_insensitive: bool,
+    ) -> RusticResult<FindMatches> {
+        Tree::find_nodes_matching_glob(self.dbe(), self.index(), ids, patterns, case_insensitive)
+    }
+
     /// drop the `Repository` index leaving an `Open` `Repository`
     pub fn drop_index(self) -> Repository<P, impl Open> {
         Repository {
@@ -1728,12 +3347,89 @@ impl<P, S: IndexedTree> Repository<P, S> {
             be_hot: self.be_hot,
             opts: self.opts,
             pb: self.pb,
+            cancel: self.cancel,
             status: self.status.into_open(),
         }
     }
 }
 
 impl<P: ProgressBars, S: IndexedTree> Repository<P, S> {
+    /// Find trees in the index which are not referenced by any snapshot, e.g. after an aborted backup.
+    ///
+    /// Only the roots of the orphaned trees are returned, i.e. trees which are no subtree of another orphaned tree.
+    /// Use [`Repository::create_snapshot_from_tree`] to save a snapshot of a recovered tree.
+    ///
+    /// # Warning
+    ///
+    /// * The trees are recovered heuristically. A recovered tree may be incomplete, e.g. if the backup was aborted
+    ///   before all trees were written or before the index was saved. Inspect recovered trees before relying on them!
+    ///
+    /// # Errors
+    ///
+    /// * If the index or snapshot files could not be read.
+    /// * If a tree could not be read.
+    ///
+    /// # Returns
+    ///
+    /// The ids of the orphaned root trees, sorted by id.
+    pub fn recover_orphaned_trees(&self) -> RusticResult<Vec<TreeId>> {
+        recover_orphaned_trees(self)
+    }
+
+    /// Compute the summary of a snapshot from its tree without saving it.
+    ///
+    /// This is the dry-run variant of [`Repository::backfill_summary`]. Only the totals of processed
+    /// files, dirs and their sizes can be computed from the tree; all other counts are left at zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to compute the summary for
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read or is missing in the index.
+    pub fn compute_snapshot_summary(&self, snap: &SnapshotFile) -> RusticResult<SnapshotSummary> {
+        commands::snapshots::compute_snapshot_summary(self, snap)
+    }
+
+    /// Compute the manifest hash of a snapshot.
+    ///
+    /// The manifest hash is the hash of the sorted lists of all tree and data blob ids referenced by
+    /// the snapshot. It only depends on the contents of the snapshot tree, i.e. it doesn't depend on
+    /// the pack layout and is identical for identical trees. See [`MANIFEST_HASH_KEY`] for how it
+    /// can be saved within the snapshot.
+    ///
+    /// [`MANIFEST_HASH_KEY`]: crate::MANIFEST_HASH_KEY
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to compute the manifest hash for
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read.
+    pub fn snapshot_manifest_hash(&self, snap: &SnapshotFile) -> RusticResult<Id> {
+        commands::manifest::manifest_hash(self, snap)
+    }
+
+    /// Verify the manifest hash saved in the `extra` field of a snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to verify
+    ///
+    /// # Errors
+    ///
+    /// * If the saved manifest hash is invalid.
+    /// * If a tree could not be read.
+    ///
+    /// # Returns
+    ///
+    /// `None` if the snapshot contains no manifest hash, else whether it matches the snapshot tree
+    pub fn verify_snapshot_manifest_hash(&self, snap: &SnapshotFile) -> RusticResult<Option<bool>> {
+        commands::manifest::verify_manifest_hash(self, snap)
+    }
+
     /// Get a [`Node`] from a "SNAP\[:PATH\]" syntax
     ///
     /// This parses for a snapshot (using the filter when "latest" is used) and then traverses into the path to get the node.
@@ -1780,6 +3476,96 @@ impl<P: ProgressBars, S: IndexedTree> Repository<P, S> {
     ) -> RusticResult<Node> {
         Tree::node_from_path(self.dbe(), self.index(), snap.tree, Path::new(path))
     }
+
+    /// Walk the tree of the given snapshot and call the visitor for each node
+    ///
+    /// In contrast to [`Repository::ls`], the visitor can prune the walk by returning
+    /// [`WalkAction::SkipSubtree`](crate::WalkAction::SkipSubtree) for a directory, in which case
+    /// the subtree is not loaded at all, or stop the walk early by returning
+    /// [`WalkAction::Stop`](crate::WalkAction::Stop).
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to walk
+    /// * `visitor` - The visitor to call for each node
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be loaded.
+    /// * If the visitor returned an error.
+    pub fn walk_snapshot(
+        &self,
+        snap: &SnapshotFile,
+        visitor: &mut impl TreeVisitor,
+    ) -> RusticResult<()> {
+        _ = Tree::walk(
+            self.dbe(),
+            self.index(),
+            snap.tree,
+            &mut PathBuf::new(),
+            visitor,
+        )?;
+        Ok(())
+    }
+
+    /// Compare two snapshots
+    ///
+    /// # Arguments
+    ///
+    /// * `snap1` - The first (old) snapshot
+    /// * `snap2` - The second (new) snapshot
+    /// * `opts` - The diff options
+    ///
+    /// # Returns
+    ///
+    /// The entries which were added, removed or modified in `snap2` compared to `snap1`,
+    /// together with the corresponding statistics.
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be loaded from the backend.
+    pub fn diff_snapshots(
+        &self,
+        snap1: &SnapshotFile,
+        snap2: &SnapshotFile,
+        opts: DiffOptions,
+    ) -> RusticResult<SnapshotDiff> {
+        diff_snapshots(self, snap1, snap2, opts)
+    }
+
+    /// Stream the differences between two snapshots
+    ///
+    /// In contrast to [`Repository::diff_snapshots`], the differences are not collected, but
+    /// returned one by one. Both snapshot trees are walked in lockstep, so the memory usage doesn't
+    /// depend on the number of differences. Unchanged entries are not returned.
+    ///
+    /// # Note
+    ///
+    /// The repository must be in an indexed state, as the trees of both snapshots are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap1` - The first (old) snapshot
+    /// * `snap2` - The second (new) snapshot
+    /// * `opts` - The diff options
+    ///
+    /// # Returns
+    ///
+    /// An iterator over the entries which were added, removed or modified in `snap2` compared to `snap1`,
+    /// ordered by path.
+    ///
+    /// # Errors
+    ///
+    /// * If the root tree of a snapshot could not be loaded from the backend.
+    pub fn diff_snapshots_streaming(
+        &self,
+        snap1: &SnapshotFile,
+        snap2: &SnapshotFile,
+        opts: DiffOptions,
+    ) -> RusticResult<impl Iterator<Item = RusticResult<DiffEntry>> + '_> {
+        diff_snapshots_streaming(self, snap1, snap2, opts)
+    }
+
     /// Reads a raw tree from a "SNAP\[:PATH\]" syntax
     ///
     /// This parses a snapshot (using the filter when "latest" is used) and then traverses into the path to get the tree.
@@ -1827,108 +3613,361 @@ impl<P: ProgressBars, S: IndexedTree> Repository<P, S> {
         NodeStreamer::new_with_glob(self.dbe().clone(), self.index(), node, ls_opts)
     }
 
-    /// Restore a given [`RestorePlan`] to a local destination
+    /// Restore a given [`RestorePlan`] to a destination, e.g. a [`LocalDestination`]
+    ///
+    /// [`LocalDestination`]: crate::LocalDestination
+    ///
+    /// # Arguments
+    ///
+    /// * `restore_infos` - The restore plan to use
+    /// * `opts` - The options to use
+    /// * `node_streamer` - The node streamer to use
+    /// * `dest` - The destination to use
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    pub fn restore(
+        &self,
+        restore_infos: RestorePlan,
+        opts: &RestoreOptions,
+        node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+        dest: &impl RestoreDestination,
+    ) -> RusticResult<()> {
+        restore_repository(restore_infos, self, opts, node_streamer, dest)
+    }
+}
+
+impl<P: ProgressBars, S: IndexedTree + Writable> Repository<P, S> {
+    /// Create a new snapshot of the given tree, e.g. a tree found by [`Repository::recover_orphaned_trees`].
+    ///
+    /// # Warning
+    ///
+    /// * The tree is not checked for completeness. The snapshot has no paths and no summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `tree_id` - The root tree of the snapshot
+    /// * `opts` - The options to create the snapshot from
+    /// * `dry_run` - If true, the snapshot is only returned, but not saved
+    ///
+    /// # Errors
+    ///
+    /// * If the tree is not contained in the index.
+    /// * If the snapshot could not be created from the options.
+    /// * If the snapshot could not be saved.
+    ///
+    /// # Returns
+    ///
+    /// The created snapshot
+    pub fn create_snapshot_from_tree(
+        &self,
+        tree_id: TreeId,
+        opts: &SnapshotOptions,
+        dry_run: bool,
+    ) -> RusticResult<SnapshotFile> {
+        create_snapshot_from_tree(self, tree_id, opts, dry_run)
+    }
+
+    /// Merge the given trees.
+    ///
+    /// This method creates needed tree blobs within the repository.
+    /// Merge conflicts (identical filenames which do not match) will be resolved using the ordering given by `cmp`.
+    ///
+    /// # Arguments
+    ///
+    /// * `trees` - The trees to merge
+    /// * `cmp` - The comparison function to use for merge conflicts
+    /// * `summary` - The summary to use
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// This method returns the blob [`Id`] of the merged tree.
+    pub fn merge_trees(
+        &self,
+        trees: &[TreeId],
+        cmp: &impl Fn(&Node, &Node) -> Ordering,
+        summary: &mut SnapshotSummary,
+    ) -> RusticResult<TreeId> {
+        commands::merge::merge_trees(self, trees, cmp, summary, None)
+    }
+
+    /// Merge the given trees and report the resolved merge conflicts.
+    ///
+    /// This works like [`Repository::merge_trees`], but additionally adds all resolved merge conflicts to `conflicts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `trees` - The trees to merge
+    /// * `cmp` - The comparison function to use for merge conflicts
+    /// * `summary` - The summary to use
+    /// * `conflicts` - All resolved merge conflicts are added to this list. The indices refer to `trees`.
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// This method returns the blob [`Id`] of the merged tree.
+    pub fn merge_trees_with_conflicts(
+        &self,
+        trees: &[TreeId],
+        cmp: &impl Fn(&Node, &Node) -> Ordering,
+        summary: &mut SnapshotSummary,
+        conflicts: &mut Vec<MergeConflict>,
+    ) -> RusticResult<TreeId> {
+        commands::merge::merge_trees(self, trees, cmp, summary, Some(conflicts))
+    }
+
+    /// Merge the given snapshots.
+    ///
+    /// This method will create needed tree blobs within the repository.
+    /// Merge conflicts (identical filenames which do not match) will be resolved using the ordering given by `cmp`.
+    ///
+    /// # Arguments
+    ///
+    /// * `snaps` - The snapshots to merge
+    /// * `cmp` - The comparison function to use for merge conflicts
+    /// * `snap` - The snapshot to save
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// This method returns the modified and already saved [`SnapshotFile`].
+    pub fn merge_snapshots(
+        &self,
+        snaps: &[SnapshotFile],
+        cmp: &impl Fn(&Node, &Node) -> Ordering,
+        snap: SnapshotFile,
+    ) -> RusticResult<SnapshotFile> {
+        commands::merge::merge_snapshots(self, snaps, cmp, snap, None)
+    }
+
+    /// Merge the given snapshots and report the resolved merge conflicts.
+    ///
+    /// This works like [`Repository::merge_snapshots`], but additionally adds all resolved merge conflicts to `conflicts`.
+    ///
+    /// # Arguments
+    ///
+    /// * `snaps` - The snapshots to merge
+    /// * `cmp` - The comparison function to use for merge conflicts
+    /// * `snap` - The snapshot to save
+    /// * `conflicts` - All resolved merge conflicts are added to this list. The indices refer to `snaps`.
+    ///
+    /// # Errors
+    ///
+    // TODO: Document errors
+    ///
+    /// # Returns
+    ///
+    /// This method returns the modified and already saved [`SnapshotFile`].
+    pub fn merge_snapshots_with_conflicts(
+        &self,
+        snaps: &[SnapshotFile],
+        cmp: &impl Fn(&Node, &Node) -> Ordering,
+        snap: SnapshotFile,
+        conflicts: &mut Vec<MergeConflict>,
+    ) -> RusticResult<SnapshotFile> {
+        commands::merge::merge_snapshots(self, snaps, cmp, snap, Some(conflicts))
+    }
+
+    /// Relocate paths within a snapshot and save the result as a new snapshot.
+    ///
+    /// Each mapping moves the subtree or file at the source path to the destination path. Missing parent
+    /// directories of destinations are created. Only the modified trees are newly written, all other
+    /// tree blobs and all data blobs are reused. Snapshot paths located within a source are relocated, too.
+    ///
+    /// The repository is locked non-exclusively while rewriting, so the reused blobs can't be pruned
+    /// concurrently. The original snapshot is kept, so this also works for append-only repositories.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to rewrite
+    /// * `mappings` - The paths to relocate as `(source, destination)`
+    /// * `new_opts` - The options to create the new snapshot from
+    ///
+    /// # Errors
+    ///
+    /// * If no mappings are given or a path is the root or contains `.` or `..`.
+    /// * If sources or destinations overlap with each other or a destination lies within a source.
+    /// * If a source doesn't exist in the snapshot.
+    /// * If a destination already exists or its parent is not a directory.
+    /// * If the repository is locked exclusively by another lock.
+    /// * If the snapshot could not be created from the options or could not be saved.
+    ///
+    /// # Returns
+    ///
+    /// The newly saved [`SnapshotFile`]. The original snapshot is not modified.
+    pub fn rewrite_snapshot_paths(
+        &self,
+        snap: &SnapshotFile,
+        mappings: &[(PathBuf, PathBuf)],
+        new_opts: &SnapshotOptions,
+    ) -> RusticResult<SnapshotFile> {
+        let _lock = self.lock_shared()?;
+        commands::rewrite::rewrite_snapshot_paths(self, snap, mappings, new_opts)
+    }
+
+    /// Compute the summary of a snapshot without summary and save it within the snapshot.
+    ///
+    /// This is useful for snapshots created by older versions which didn't save a summary.
+    /// The snapshot is replaced by a new snapshot which keeps the original id in `original`.
+    /// Snapshots which already contain a summary are returned unchanged.
+    /// Use [`Repository::compute_snapshot_summary`] to only compute the summary.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to backfill the summary for
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read or is missing in the index.
+    /// * If the new snapshot could not be saved or the old snapshot could not be removed.
+    ///
+    /// # Returns
+    ///
+    /// The [`SnapshotFile`] containing the summary
+    pub fn backfill_summary(&self, snap: &SnapshotFile) -> RusticResult<SnapshotFile> {
+        commands::snapshots::backfill_summary(self, snap)
+    }
+
+    /// Compute the manifest hash of a snapshot and save it within the `extra` field of the snapshot.
+    ///
+    /// The snapshot is replaced by a new snapshot which keeps the original id in `original`.
+    /// Snapshots which already contain a manifest hash are returned unchanged.
+    /// Use [`Repository::snapshot_manifest_hash`] to only compute the manifest hash.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot to save the manifest hash for
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read.
+    /// * If the new snapshot could not be saved or the old snapshot could not be removed.
+    ///
+    /// # Returns
+    ///
+    /// The [`SnapshotFile`] containing the manifest hash
+    pub fn save_snapshot_manifest_hash(&self, snap: &SnapshotFile) -> RusticResult<SnapshotFile> {
+        commands::manifest::save_manifest_hash(self, snap)
+    }
+}
+
+impl<P, S: IndexedIds> Repository<P, S> {
+    /// Estimate how much of the local file `path` is already contained in the repository.
+    ///
+    /// The file is chunked and hashed like during a backup, but nothing is written to the repository.
     ///
     /// # Arguments
     ///
-    /// * `restore_infos` - The restore plan to use
-    /// * `opts` - The options to use
-    /// * `node_streamer` - The node streamer to use
-    /// * `dest` - The destination to use
+    /// * `path` - The path of the file to check
     ///
     /// # Errors
     ///
-    // TODO: Document errors
-    pub fn restore(
-        &self,
-        restore_infos: RestorePlan,
-        opts: &RestoreOptions,
-        node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
-        dest: &LocalDestination,
-    ) -> RusticResult<()> {
-        restore_repository(restore_infos, self, *opts, node_streamer, dest)
+    /// * If the file could not be opened or read.
+    ///
+    /// # Returns
+    ///
+    /// The number and size of the chunks which already exist in the repository and which are new
+    pub fn would_deduplicate(&self, path: &Path) -> RusticResult<DedupEstimate> {
+        commands::dedup::would_deduplicate(self, path)
     }
+}
 
-    /// Merge the given trees.
+impl<P: ProgressBars, S: IndexedIds + Writable> Repository<P, S> {
+    /// Run a backup of `source` using the given options.
     ///
-    /// This method creates needed tree blobs within the repository.
-    /// Merge conflicts (identical filenames which do not match) will be resolved using the ordering given by `cmp`.
+    /// You have to give a preflled [`SnapshotFile`] which is modified and saved.
     ///
     /// # Arguments
     ///
-    /// * `trees` - The trees to merge
-    /// * `cmp` - The comparison function to use for merge conflicts
-    /// * `summary` - The summary to use
+    /// * `opts` - The options to use
+    /// * `source` - The source to backup
+    /// * `snap` - The snapshot to modify and save
     ///
     /// # Errors
     ///
     // TODO: Document errors
     ///
     /// # Returns
-    ///
-    /// This method returns the blob [`Id`] of the merged tree.
-    pub fn merge_trees(
+    ///  
+    /// The saved snapshot.
+    pub fn backup(
         &self,
-        trees: &[TreeId],
-        cmp: &impl Fn(&Node, &Node) -> Ordering,
-        summary: &mut SnapshotSummary,
-    ) -> RusticResult<TreeId> {
-        commands::merge::merge_trees(self, trees, cmp, summary)
+        opts: &BackupOptions,
+        source: &PathList,
+        snap: SnapshotFile,
+    ) -> RusticResult<SnapshotFile> {
+        commands::backup::backup(self, opts, source, snap).map(BackupOutcome::into_snapshot)
     }
 
-    /// Merge the given snapshots.
+    /// Run a backup of `source` using the given options and report whether a new snapshot has been saved.
     ///
-    /// This method will create needed tree blobs within the repository.
-    /// Merge conflicts (identical filenames which do not match) will be resolved using the ordering given by `cmp`.
+    /// If [`ParentOptions::skip_if_unchanged`](crate::ParentOptions::skip_if_unchanged) is set and the resulting tree is identical to the tree
+    /// of the parent snapshot, no new snapshot is saved and [`BackupOutcome::Unchanged`] is returned.
     ///
     /// # Arguments
     ///
-    /// * `snaps` - The snapshots to merge
-    /// * `cmp` - The comparison function to use for merge conflicts
-    /// * `snap` - The snapshot to save
+    /// * `opts` - The options to use
+    /// * `source` - The source to backup
+    /// * `snap` - The snapshot to modify and save
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If the backup fails, see [`Repository::backup`]
     ///
     /// # Returns
     ///
-    /// This method returns the modified and already saved [`SnapshotFile`].
-    pub fn merge_snapshots(
+    /// The [`BackupOutcome`] of the backup run
+    pub fn backup_with_outcome(
         &self,
-        snaps: &[SnapshotFile],
-        cmp: &impl Fn(&Node, &Node) -> Ordering,
+        opts: &BackupOptions,
+        source: &PathList,
         snap: SnapshotFile,
-    ) -> RusticResult<SnapshotFile> {
-        commands::merge::merge_snapshots(self, snaps, cmp, snap)
+    ) -> RusticResult<BackupOutcome> {
+        commands::backup::backup(self, opts, source, snap)
     }
-}
 
-impl<P: ProgressBars, S: IndexedIds> Repository<P, S> {
-    /// Run a backup of `source` using the given options.
+    /// Run a backup of the data read from `reader`, saved as a single file named `filename`.
     ///
-    /// You have to give a preflled [`SnapshotFile`] which is modified and saved.
+    /// This is like a backup from stdin, but for an arbitrary reader, e.g. a database dump piped
+    /// into the process. The size of the data need not be known in advance. The options
+    /// [`BackupOptions::stdin_filename`] and [`BackupOptions::stdin_command`] are ignored.
     ///
     /// # Arguments
     ///
+    /// * `reader` - The reader to backup
+    /// * `filename` - The name of the file in the snapshot
     /// * `opts` - The options to use
-    /// * `source` - The source to backup
     /// * `snap` - The snapshot to modify and save
     ///
     /// # Errors
     ///
-    // TODO: Document errors
+    /// * If the filename (or [`BackupOptions::as_path`], if given) doesn't name a file, e.g. is empty or `/`.
+    /// * If reading from the reader fails.
+    /// * If the backup fails, see [`Repository::backup`]
     ///
     /// # Returns
-    ///  
+    ///
     /// The saved snapshot.
-    pub fn backup(
+    pub fn backup_reader(
         &self,
+        reader: impl Read + Send + 'static,
+        filename: &str,
         opts: &BackupOptions,
-        source: &PathList,
         snap: SnapshotFile,
     ) -> RusticResult<SnapshotFile> {
-        commands::backup::backup(self, opts, source, snap)
+        commands::backup::backup_reader(self, reader, filename, opts, snap)
+            .map(BackupOutcome::into_snapshot)
     }
 }
 
@@ -1950,18 +3989,6 @@ impl<P, S: IndexedFull> Repository<P, S> {
     pub fn get_blob_cached(&self, id: &BlobId, tpe: BlobType) -> RusticResult<Bytes> {
         self.get_blob_or_insert_with(id, || self.index().blob_from_backend(self.dbe(), tpe, id))
     }
-
-    /// drop the data pack information from the `Repository` index leaving an `IndexedTree` `Repository`
-    pub fn drop_data_from_index(self) -> Repository<P, impl IndexedTree> {
-        Repository {
-            name: self.name,
-            be: self.be,
-            be_hot: self.be_hot,
-            opts: self.opts,
-            pb: self.pb,
-            status: self.status.into_indexed_tree(),
-        }
-    }
 }
 
 impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
@@ -1983,6 +4010,103 @@ impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
         commands::cat::cat_blob(self, tpe, id)
     }
 
+    /// Read a range of a raw blob
+    ///
+    /// # Note
+    ///
+    /// This is a convenience method and doesn't save bandwidth: The whole blob is read,
+    /// decrypted and decompressed and the range is taken from the result.
+    ///
+    /// # Arguments
+    ///
+    /// * `tpe` - The type of the blob
+    /// * `id` - The id of the blob
+    /// * `offset` - The offset within the blob to start reading
+    /// * `length` - The length to read
+    ///
+    /// # Errors
+    ///
+    /// * If the string is not a valid hexadecimal string
+    ///
+    /// # Returns
+    ///
+    /// The bytes of the given range of the blob.
+    /// If offset is behind the end of the blob, an empty `Bytes` is returned.
+    /// If length is too large, the result up to the end of the blob is returned.
+    pub fn cat_blob_partial(
+        &self,
+        tpe: BlobType,
+        id: &str,
+        offset: usize,
+        length: usize,
+    ) -> RusticResult<Bytes> {
+        let data = commands::cat::cat_blob(self, tpe, id)?;
+        Ok(commands::cat::slice_range(&data, offset, length))
+    }
+
+    /// Collect aggregated statistics about the repository
+    ///
+    /// This combines the information from [`Repository::infos_index`] and [`Repository::infos_files`]
+    /// with the snapshot count, the restore sizes and the deduplication ratio.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode to compute the total size and file count
+    ///
+    /// # Errors
+    ///
+    /// * If the index or a tree could not be read.
+    /// * If a blob referenced by a snapshot is missing in the index.
+    pub fn stats(&self, mode: StatsMode) -> RusticResult<RepoStats> {
+        collect_stats(self, mode)
+    }
+
+    /// Compute the size of a tree when restoring it without building a [`RestorePlan`]
+    ///
+    /// # Arguments
+    ///
+    /// * `tree` - The tree to compute the size for
+    /// * `unique` - Whether to also compute the size of the unique contents
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read.
+    /// * If a blob referenced by the tree is missing in the index.
+    ///
+    /// # Returns
+    ///
+    /// The total size, the number of files and, if requested, the size of the unique contents
+    pub fn tree_restore_size(&self, tree: TreeId, unique: bool) -> RusticResult<TreeRestoreSize> {
+        Ok(trees_restore_size(self, &[tree], unique)?[0])
+    }
+
+    /// Compute the sizes of snapshots when restoring them
+    ///
+    /// Identical subtrees of the snapshots are only read once, so this is much faster than calling
+    /// [`Repository::tree_restore_size`] for each snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `snaps` - The snapshots to compute the sizes for
+    /// * `unique` - Whether to also compute the size of the unique contents of each snapshot
+    ///
+    /// # Errors
+    ///
+    /// * If a tree could not be read.
+    /// * If a blob referenced by a tree is missing in the index.
+    ///
+    /// # Returns
+    ///
+    /// The sizes in the order of the given snapshots
+    pub fn snapshots_restore_size(
+        &self,
+        snaps: &[SnapshotFile],
+        unique: bool,
+    ) -> RusticResult<Vec<TreeRestoreSize>> {
+        let trees: Vec<_> = snaps.iter().map(|snap| snap.tree).collect();
+        trees_restore_size(self, &trees, unique)
+    }
+
     /// Dump a [`Node`] using the given writer.
     ///
     /// # Arguments
@@ -1996,11 +4120,35 @@ impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
     ///  
     /// # Note
     ///
-    /// Currently, only regular file nodes are supported.
+    /// Currently, only regular file nodes are supported. Use [`Repository::dump_tree`] to dump directories.
     pub fn dump(&self, node: &Node, w: &mut impl Write) -> RusticResult<()> {
         commands::dump::dump(self, node, w)
     }
 
+    /// Dump a [`Node`] and everything below it as archive using the given writer.
+    ///
+    /// Directories, regular files and symlinks are added including their mode, mtime and ownership,
+    /// other special files are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `node` - The node to dump
+    /// * `format` - The archive format to use
+    /// * `w` - The writer to use
+    ///
+    /// # Errors
+    ///
+    /// * If a tree or blob could not be read.
+    /// * If the archive could not be written.
+    pub fn dump_tree(
+        &self,
+        node: &Node,
+        format: DumpFormat,
+        w: &mut impl Write,
+    ) -> RusticResult<()> {
+        commands::dump::dump_tree(self, node, format, w)
+    }
+
     /// Prepare the restore.
     ///
     /// If `dry_run` is set to false, it will also:
@@ -2026,10 +4174,145 @@ impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
         &self,
         opts: &RestoreOptions,
         node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
-        dest: &LocalDestination,
+        dest: &impl RestoreDestination,
+        dry_run: bool,
+    ) -> RusticResult<RestorePlan> {
+        collect_and_prepare(
+            self,
+            opts,
+            &RestoreExtraOptions::default(),
+            node_streamer,
+            dest,
+            dry_run,
+        )
+    }
+
+    /// Prepare the restore using additional options like prefixes, filter rules or callbacks.
+    ///
+    /// This works like [`Repository::prepare_restore`]. The additional options are kept in the returned
+    /// plan and are also used by [`Repository::restore`].
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to use
+    /// * `extra` - The additional options to use
+    /// * `node_streamer` - The node streamer to use
+    /// * `dest` - The destination to use
+    /// * `dry_run` - If true, only print what would be done
+    ///
+    /// # Errors
+    ///
+    /// * If a directory could not be created.
+    /// * If the restore information could not be collected.
+    /// * If the resume state could not be read or belongs to a different snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The restore plan.
+    pub fn prepare_restore_with_extra(
+        &self,
+        opts: &RestoreOptions,
+        extra: &RestoreExtraOptions,
+        node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+        dest: &impl RestoreDestination,
         dry_run: bool,
     ) -> RusticResult<RestorePlan> {
-        collect_and_prepare(self, *opts, node_streamer, dest, dry_run)
+        collect_and_prepare(self, opts, extra, node_streamer, dest, dry_run)
+    }
+
+    /// Preview the restore without any changes to the destination.
+    ///
+    /// In contrast to [`Repository::prepare_restore`], this never creates dirs or removes entries
+    /// of the destination, regardless of [`RestoreOptions::delete`]. Existing files are only read to
+    /// determine which contents need to be restored. This can be used to show the amount of data
+    /// to transfer before actually restoring.
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to use
+    /// * `node_streamer` - The node streamer to use
+    /// * `dest` - The destination to use
+    ///
+    /// # Errors
+    ///
+    /// * If the restore information could not be collected.
+    ///
+    /// # Returns
+    ///
+    /// The statistics, sizes and needed packs of the restore.
+    pub fn restore_preview(
+        &self,
+        opts: &RestoreOptions,
+        node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+        dest: &impl RestoreDestination,
+    ) -> RusticResult<RestorePreview> {
+        restore_preview(
+            self,
+            opts,
+            &RestoreExtraOptions::default(),
+            node_streamer,
+            dest,
+        )
+    }
+
+    /// Preview the restore using additional options like prefixes or filter rules.
+    ///
+    /// This works like [`Repository::restore_preview`].
+    ///
+    /// # Arguments
+    ///
+    /// * `opts` - The options to use
+    /// * `extra` - The additional options to use
+    /// * `node_streamer` - The node streamer to use
+    /// * `dest` - The destination to use
+    ///
+    /// # Errors
+    ///
+    /// * If the restore information could not be collected.
+    ///
+    /// # Returns
+    ///
+    /// The statistics, sizes and needed packs of the restore.
+    pub fn restore_preview_with_extra(
+        &self,
+        opts: &RestoreOptions,
+        extra: &RestoreExtraOptions,
+        node_streamer: impl Iterator<Item = RusticResult<(PathBuf, Node)>>,
+        dest: &impl RestoreDestination,
+    ) -> RusticResult<RestorePreview> {
+        restore_preview(self, opts, extra, node_streamer, dest)
+    }
+
+    /// Verify the contents of the restored files of a snapshot.
+    ///
+    /// This is an explicit audit after a restore: All files contained in the snapshot are read from
+    /// the destination and their contents are compared blob-by-blob with the snapshot. In contrast
+    /// to [`RestoreOptions::verify_existing`], this doesn't trust the size and modification time of
+    /// existing files and is independent of the restore itself.
+    ///
+    /// # Arguments
+    ///
+    /// * `snap` - The snapshot which was restored
+    /// * `dest` - The destination the snapshot was restored to
+    ///
+    /// # Errors
+    ///
+    /// * If the snapshot tree could not be read.
+    /// * If a blob of a file is not contained in the index.
+    ///
+    /// # Returns
+    ///
+    /// The paths (relative to the destination) of all files which are missing or differ, sorted by path.
+    ///
+    /// # Notes
+    ///
+    /// Only the contents of regular files are verified, i.e. metadata and other node types are not checked.
+    pub fn verify_restored(
+        &self,
+        snap: &SnapshotFile,
+        dest: &impl RestoreDestination,
+    ) -> RusticResult<Vec<PathBuf>> {
+        verify_restored(self, snap, dest)
     }
 
     /// Copy the given `snapshots` to `repo_dest`.
@@ -2054,14 +4337,16 @@ impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
     /// copy will be created in the destination repository.
     ///
     /// To omit already existing snapshots, use `relevant_copy_snapshots` and filter out the non-relevant ones.
-    pub fn copy<'a, Q: ProgressBars, R: IndexedIds>(
+    pub fn copy<'a, Q: ProgressBars, R: IndexedIds + Writable>(
         &self,
         repo_dest: &Repository<Q, R>,
         snapshots: impl IntoIterator<Item = &'a SnapshotFile>,
     ) -> RusticResult<()> {
         commands::copy::copy(self, repo_dest, snapshots)
     }
+}
 
+impl<P: ProgressBars, S: IndexedFull + Writable> Repository<P, S> {
     /// Repair snapshots.
     ///
     /// This traverses all trees of all snapshots and repairs defect trees.
@@ -2085,6 +4370,81 @@ impl<P: ProgressBars, S: IndexedFull> Repository<P, S> {
         snapshots: Vec<SnapshotFile>,
         dry_run: bool,
     ) -> RusticResult<()> {
+        let _lock = (!dry_run).then(|| self.lock_exclusive()).transpose()?;
         repair_snapshots(self, opts, snapshots, dry_run)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Id;
+
+    use rstest::rstest;
+
+    fn fill_cache(index: &FullIndex, count: u8) -> RusticResult<usize> {
+        let mut calls = 0;
+        for i in 0..count {
+            let id = BlobId::from(Id::new([i; 32]));
+            _ = index.get_blob_or_insert_with(&id, || {
+                calls += 1;
+                Ok(Bytes::from(vec![i; 100]))
+            })?;
+        }
+        Ok(calls)
+    }
+
+    #[rstest]
+    #[case(5_000)]
+    #[case(50_000)]
+    fn blob_cache_honors_weight_capacity(#[case] capacity: u64) -> RusticResult<()> {
+        let index = FullIndex::new(capacity);
+        assert_eq!(fill_cache(&index, 200)?, 200);
+
+        let cache = index.cache.as_ref().unwrap();
+        assert!(cache.weight() <= capacity);
+        Ok(())
+    }
+
+    #[test]
+    fn blob_cache_can_be_disabled() -> RusticResult<()> {
+        let index = FullIndex::new(0);
+        assert!(index.cache.is_none());
+        assert_eq!(fill_cache(&index, 10)?, 10);
+        // blobs are fetched again as nothing is cached
+        assert_eq!(fill_cache(&index, 10)?, 10);
+
+        let index = FullIndex::new(constants::WEIGHT_CAPACITY);
+        assert_eq!(fill_cache(&index, 10)?, 10);
+        assert_eq!(fill_cache(&index, 10)?, 0);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn password_is_read_from_fd() -> RusticResult<()> {
+        use std::os::unix::{io::AsRawFd, net::UnixStream};
+
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        sender.write_all(b"secret\nignored").unwrap();
+        drop(sender);
+
+        let opts = RepositoryOptions::default().password_fd(receiver.as_raw_fd());
+        let password = opts.evaluate_password()?.unwrap();
+        assert_eq!(password.expose_secret(), "secret");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn password_fd_must_be_readable() {
+        use std::os::unix::io::AsRawFd;
+
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let write_only = File::options().write(true).open(file.path()).unwrap();
+        assert!(read_password_from_fd(write_only.as_raw_fd()).is_err());
+
+        // not an open file descriptor
+        assert!(read_password_from_fd(-1).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use std::thread::{self, JoinHandle};
+
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use log::{debug, warn};
+
+use crate::{
+    backend::{
+        decrypt::{DecryptBackend, DecryptReadBackend, DecryptWriteBackend},
+        FileType, ReadBackend, WriteBackend,
+    },
+    crypto::aespoly1305::Key,
+    error::{ErrorKind, RusticError, RusticResult},
+    repofile::lockfile::{LockFile, LockId},
+    repository::{Open, Repository},
+};
+
+pub(super) mod constants {
+    use std::time::Duration;
+
+    /// The interval in which held locks are refreshed.
+    pub(super) const REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+}
+
+/// A lock on the repository, see [`Repository::lock_exclusive`] and [`Repository::lock_shared`].
+///
+/// The lock is refreshed in the background while it is held and removed from the repository when it is dropped.
+#[derive(Debug)]
+pub struct RepositoryLock {
+    /// Whether the lock is exclusive
+    exclusive: bool,
+    /// Dropping this sender signals the refresh thread to remove the lock
+    stop: Option<Sender<()>>,
+    /// The refresh thread
+    handle: Option<JoinHandle<()>>,
+}
+
+impl RepositoryLock {
+    /// Returns whether the lock is exclusive.
+    #[must_use]
+    pub const fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+}
+
+impl Drop for RepositoryLock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                warn!("lock refresh thread panicked, the lock may not have been removed");
+            }
+        }
+    }
+}
+
+/// Lock the repository.
+///
+/// A new lock file is written and afterwards all other locks are checked for conflicts.
+/// Stale locks are ignored. Locks are tracked per lock file, so other locks held by the current
+/// process conflict as well.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to lock
+/// * `exclusive` - Whether to take an exclusive lock
+///
+/// # Errors
+///
+/// * If the lock file could not be written or the lock files could not be listed.
+/// * If the repository is locked by a conflicting lock.
+pub(crate) fn lock_repository<P, S: Open>(
+    repo: &Repository<P, S>,
+    exclusive: bool,
+) -> RusticResult<RepositoryLock> {
+    let dbe = repo.dbe().clone();
+    let id = LockId::from(dbe.save_file(&LockFile::new(exclusive))?);
+    debug!("created lock {id}");
+
+    let conflict = list_locks(repo).map(|locks| {
+        locks.into_iter().find(|(other_id, lock)| {
+            *other_id != id && lock.conflicts_with(exclusive) && !lock.is_stale()
+        })
+    });
+    let conflict = match conflict {
+        Ok(conflict) => conflict,
+        Err(err) => {
+            remove_lock(&dbe, &id);
+            return Err(err);
+        }
+    };
+    if let Some((other_id, lock)) = conflict {
+        remove_lock(&dbe, &id);
+        return Err(RusticError::new(
+            ErrorKind::Repository,
+            "Repository is already locked by `{username}@{hostname}` (pid `{pid}`, exclusive: `{exclusive}`) since `{time}`. If the lock is stale, it can be removed by breaking locks.",
+        )
+        .attach_context("exclusive", lock.exclusive.to_string())
+        .attach_context("username", lock.username)
+        .attach_context("hostname", lock.hostname)
+        .attach_context("pid", lock.pid.to_string())
+        .attach_context("time", lock.time.to_rfc3339())
+        .attach_context("id", other_id.to_string()));
+    }
+
+    let (stop, rx) = bounded::<()>(0);
+    let handle = thread::spawn(move || {
+        let mut id = id;
+        loop {
+            match rx.recv_timeout(constants::REFRESH_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => match dbe.save_file(&LockFile::new(exclusive)) {
+                    Ok(new_id) => {
+                        remove_lock(&dbe, &id);
+                        id = LockId::from(new_id);
+                        debug!("refreshed lock, new id {id}");
+                    }
+                    Err(err) => warn!("failed to refresh lock {id}: {}", err.display_log()),
+                },
+                _ => break,
+            }
+        }
+        remove_lock(&dbe, &id);
+    });
+
+    Ok(RepositoryLock {
+        exclusive,
+        stop: Some(stop),
+        handle: Some(handle),
+    })
+}
+
+/// List all locks of the repository.
+///
+/// Locks which are removed while listing are skipped.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to list the locks from
+///
+/// # Errors
+///
+/// * If the lock files could not be listed.
+///
+/// # Returns
+///
+/// The ids and contents of all locks, sorted by id.
+pub(crate) fn list_locks<P, S: Open>(
+    repo: &Repository<P, S>,
+) -> RusticResult<Vec<(LockId, LockFile)>> {
+    let mut ids = repo.dbe().list(FileType::Lock)?;
+    ids.sort_unstable();
+    Ok(ids
+        .into_iter()
+        .filter_map(|id| match repo.dbe().get_file::<LockFile>(&id) {
+            Ok(lock) => Some((LockId::from(id), lock)),
+            Err(err) => {
+                warn!("ignoring unreadable lock {id}: {}", err.display_log());
+                None
+            }
+        })
+        .collect())
+}
+
+/// Remove locks from the repository.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to remove the locks from
+/// * `only_stale` - Whether to only remove stale locks
+///
+/// # Errors
+///
+/// * If the lock files could not be listed or removed.
+///
+/// # Returns
+///
+/// The ids of the removed locks.
+pub(crate) fn break_locks<P, S: Open>(
+    repo: &Repository<P, S>,
+    only_stale: bool,
+) -> RusticResult<Vec<LockId>> {
+    let mut removed = Vec::new();
+    for (id, lock) in list_locks(repo)? {
+        if only_stale && !lock.is_stale() {
+            continue;
+        }
+        repo.dbe().remove(FileType::Lock, &id, false)?;
+        debug!("removed lock {id}");
+        removed.push(id);
+    }
+    Ok(removed)
+}
+
+/// Remove the given lock, only warning if this fails.
+fn remove_lock(dbe: &DecryptBackend<Key>, id: &LockId) {
+    if let Err(err) = dbe.remove(FileType::Lock, id, false) {
+        warn!("failed to remove lock {id}: {}", err.display_log());
+    }
+}
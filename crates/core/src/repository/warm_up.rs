@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::process::Command;
 use std::thread::sleep;
 
@@ -20,6 +21,8 @@ pub(super) mod constants {
 
 /// Warm up the repository and wait.
 ///
+/// Packs which the backend reports to be already warm are skipped. If all packs are warm, no waiting is done.
+///
 /// # Arguments
 ///
 /// * `repo` - The repository to warm up.
@@ -31,12 +34,21 @@ pub(super) mod constants {
 /// * If the thread pool could not be created.
 pub(crate) fn warm_up_wait<P: ProgressBars, S>(
     repo: &Repository<P, S>,
-    packs: impl ExactSizeIterator<Item = PackId> + Clone,
+    packs: impl Iterator<Item = PackId>,
 ) -> RusticResult<()> {
-    warm_up(repo, packs.clone())?;
+    let packs = cold_packs(repo, packs);
+    if packs.is_empty() {
+        return Ok(());
+    }
+    warm_up_packs(repo, packs.iter().copied())?;
 
     if let Some(warm_up_wait_cmd) = &repo.opts.warm_up_wait_command {
-        warm_up_command(packs, warm_up_wait_cmd, &repo.pb, &WarmUpType::WaitPack)?;
+        warm_up_command(
+            packs.into_iter(),
+            warm_up_wait_cmd,
+            &repo.pb,
+            &WarmUpType::WaitPack,
+        )?;
     } else if let Some(wait) = repo.opts.warm_up_wait {
         let p = repo.pb.progress_spinner(format!("waiting {wait}..."));
         sleep(*wait);
@@ -47,6 +59,8 @@ pub(crate) fn warm_up_wait<P: ProgressBars, S>(
 
 /// Warm up the repository.
 ///
+/// Packs which the backend reports to be already warm are skipped.
+///
 /// # Arguments
 ///
 /// * `repo` - The repository to warm up.
@@ -57,6 +71,24 @@ pub(crate) fn warm_up_wait<P: ProgressBars, S>(
 /// * If the command could not be parsed.
 /// * If the thread pool could not be created.
 pub(crate) fn warm_up<P: ProgressBars, S>(
+    repo: &Repository<P, S>,
+    packs: impl Iterator<Item = PackId>,
+) -> RusticResult<()> {
+    warm_up_packs(repo, cold_packs(repo, packs).into_iter())
+}
+
+/// Warm up the given packs using the warm-up command or the backend.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to warm up.
+/// * `packs` - The packs to warm up.
+///
+/// # Errors
+///
+/// * If the command could not be parsed.
+/// * If the thread pool could not be created.
+fn warm_up_packs<P: ProgressBars, S>(
     repo: &Repository<P, S>,
     packs: impl ExactSizeIterator<Item = PackId>,
 ) -> RusticResult<()> {
@@ -68,6 +100,29 @@ pub(crate) fn warm_up<P: ProgressBars, S>(
     Ok(())
 }
 
+/// Remove duplicates and packs which the backend reports to be already warm.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to warm up.
+/// * `packs` - The packs to warm up.
+fn cold_packs<P, S>(repo: &Repository<P, S>, packs: impl Iterator<Item = PackId>) -> Vec<PackId> {
+    packs
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|pack| match repo.be.is_warm(FileType::Pack, pack) {
+            Ok(warm) => !warm,
+            Err(err) => {
+                warn!(
+                    "could not determine if pack {pack:?} is warm. {}",
+                    err.display_log()
+                );
+                true
+            }
+        })
+        .collect()
+}
+
 #[derive(Debug)]
 enum WarmUpType {
     WarmUp,
@@ -172,3 +227,68 @@ fn warm_up_repo<P: ProgressBars, S>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc};
+
+    use super::*;
+    use crate::{
+        backend::MockBackend, progress::NoProgressBars, Id, RepositoryBackends, RepositoryOptions,
+    };
+
+    fn repo(
+        be: MockBackend,
+        opts: &RepositoryOptions,
+    ) -> RusticResult<Repository<NoProgressBars, ()>> {
+        Repository::new(opts, &RepositoryBackends::new(Arc::new(be), None))
+    }
+
+    #[test]
+    fn warm_up_skips_warm_packs() -> RusticResult<()> {
+        let warm = PackId::from(Id::new([1; 32]));
+        let cold = PackId::from(Id::new([2; 32]));
+        let unknown = PackId::from(Id::new([3; 32]));
+
+        let mut be = MockBackend::new();
+        _ = be.expect_location().return_const("mock".to_string());
+        _ = be.expect_needs_warm_up().return_const(true);
+        _ = be.expect_is_warm().returning(move |_, id| {
+            if *id == *warm {
+                Ok(true)
+            } else if *id == *cold {
+                Ok(false)
+            } else {
+                Err(RusticError::new(
+                    ErrorKind::Backend,
+                    "Storage class not available.",
+                ))
+            }
+        });
+        // packs with unknown state are warmed up, duplicates only once
+        _ = be
+            .expect_warm_up()
+            .withf(move |tpe, id| *tpe == FileType::Pack && (*id == *cold || *id == *unknown))
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        let repo = repo(be, &RepositoryOptions::default())?;
+        warm_up(&repo, [warm, cold, cold, unknown].into_iter())
+    }
+
+    #[test]
+    fn warm_up_wait_does_nothing_if_all_packs_are_warm() -> RusticResult<()> {
+        let mut be = MockBackend::new();
+        _ = be.expect_location().return_const("mock".to_string());
+        _ = be.expect_needs_warm_up().return_const(true);
+        _ = be.expect_is_warm().returning(|_, _| Ok(true));
+        _ = be.expect_warm_up().never();
+
+        // the wait command would fail if it was called
+        let opts = RepositoryOptions::default().warm_up_wait_command(
+            CommandInput::from_str("rustic-non-existing-command %id").unwrap(),
+        );
+        let repo = repo(be, &opts)?;
+        warm_up_wait(&repo, [PackId::from(Id::new([1; 32]))].into_iter())
+    }
+}
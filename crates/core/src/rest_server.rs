@@ -0,0 +1,661 @@
+//! A minimal server speaking the restic REST protocol
+//!
+//! [`RestServer`] serves the files of a repository's backend over HTTP using the
+//! [restic REST protocol](https://restic.readthedocs.io/en/stable/100_references.html#rest-backend),
+//! such that other rustic or restic clients can access the repository using a `rest:` backend.
+//!
+//! The served files are the raw (encrypted) repository files, i.e. clients still need the repository password.
+//! By default, the repository is served read-only, see [`RestServerOptions::allow_writes`].
+//!
+//! # Note
+//!
+//! The server neither supports authentication nor TLS. Only bind it to trusted networks, e.g. to `localhost`,
+//! or put it behind a reverse proxy which handles these.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use crossbeam_channel::{bounded, Receiver};
+use derive_setters::Setters;
+use log::{debug, trace, warn};
+use serde_derive::Serialize;
+
+use crate::{
+    backend::{FileType, WriteBackend, ALL_FILE_TYPES},
+    error::{ErrorKind, RusticError, RusticResult},
+    id::Id,
+    repository::Repository,
+};
+
+/// Media type of the list responses of version 1 of the REST protocol
+const MEDIA_TYPE_V1: &str = "application/vnd.x.restic.rest.v1";
+/// Media type of the list responses of version 2 of the REST protocol
+const MEDIA_TYPE_V2: &str = "application/vnd.x.restic.rest.v2";
+
+pub(super) mod constants {
+    use std::time::Duration;
+
+    /// The default maximum size of a request body, this exceeds the size of pack files of most repositories
+    pub(super) const MAX_BODY_SIZE: usize = 1 << 30;
+    /// The default timeout for reading from or writing to a connection
+    pub(super) const TIMEOUT: Duration = Duration::from_secs(60);
+    /// The default maximum number of connections handled concurrently
+    pub(super) const MAX_CONNECTIONS: usize = 16;
+    /// The maximum length of the request line and of each header line
+    pub(super) const MAX_HEADER_LINE: u64 = 8 * 1024;
+    /// The maximum number of headers of a request
+    pub(super) const MAX_HEADERS: usize = 100;
+}
+
+/// Options for the [`RestServer`]
+#[derive(Debug, Clone, Copy, Setters)]
+#[setters(into)]
+#[non_exhaustive]
+pub struct RestServerOptions {
+    /// Allow requests which modify the repository. By default, the repository is served read-only.
+    ///
+    /// # Warning
+    ///
+    /// As there is no authentication, everyone who can reach the server can then modify or delete
+    /// repository files!
+    pub allow_writes: bool,
+
+    /// The maximum size of a request body in bytes. Larger requests are rejected.
+    pub max_body_size: usize,
+
+    /// The timeout for reading a request from or writing a response to a connection
+    pub timeout: Duration,
+
+    /// The maximum number of connections handled concurrently. Further connections are accepted
+    /// once a handled connection is closed.
+    pub max_connections: usize,
+}
+
+impl Default for RestServerOptions {
+    fn default() -> Self {
+        Self {
+            allow_writes: false,
+            max_body_size: constants::MAX_BODY_SIZE,
+            timeout: constants::TIMEOUT,
+            max_connections: constants::MAX_CONNECTIONS,
+        }
+    }
+}
+
+/// A server which serves a repository using the restic REST protocol.
+///
+/// The server handles each connection in a separate thread, up to [`RestServerOptions::max_connections`]
+/// connections at the same time.
+#[derive(Debug)]
+pub struct RestServer {
+    /// The backend to serve
+    be: Arc<dyn WriteBackend>,
+    /// The listener accepting the connections
+    listener: TcpListener,
+    /// The options to use
+    opts: RestServerOptions,
+}
+
+impl RestServer {
+    /// Create a new [`RestServer`] serving the backend of the given repository.
+    ///
+    /// # Arguments
+    ///
+    /// * `repo` - The repository to serve. It doesn't need to be opened as only the raw files are served.
+    /// * `addr` - The address to listen on. Use port `0` to let the OS choose a free port.
+    /// * `opts` - The options to use
+    ///
+    /// # Errors
+    ///
+    /// * If the server could not bind to the given address.
+    pub fn new<P, S>(
+        repo: &Repository<P, S>,
+        addr: impl ToSocketAddrs,
+        opts: RestServerOptions,
+    ) -> RusticResult<Self> {
+        let listener = TcpListener::bind(addr).map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Binding the REST server to the given address failed.",
+                err,
+            )
+        })?;
+
+        Ok(Self {
+            be: repo.be.clone(),
+            listener,
+            opts,
+        })
+    }
+
+    /// Get the address the server is listening on.
+    ///
+    /// # Errors
+    ///
+    /// * If the address could not be determined.
+    pub fn local_addr(&self) -> RusticResult<SocketAddr> {
+        self.listener.local_addr().map_err(|err| {
+            RusticError::with_source(
+                ErrorKind::InputOutput,
+                "Getting the address of the REST server failed.",
+                err,
+            )
+        })
+    }
+
+    /// Serve requests. This blocks the calling thread and never returns.
+    pub fn serve(&self) -> ! {
+        // each handled connection holds a slot; sending blocks if all slots are taken
+        let (slots, free_slots) = bounded::<()>(self.opts.max_connections.max(1));
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    debug!("accepted REST connection from {peer}");
+                    _ = slots.send(());
+                    let be = self.be.clone();
+                    let opts = self.opts;
+                    let free_slots = free_slots.clone();
+                    _ = thread::spawn(move || {
+                        // free the slot even if handling the connection panics
+                        let _slot = ConnectionSlot(free_slots);
+                        if let Err(err) = handle_connection(be.as_ref(), opts, stream) {
+                            debug!("REST connection from {peer} closed: {err}");
+                        }
+                    });
+                }
+                Err(err) => warn!("error accepting REST connection: {err}"),
+            }
+        }
+    }
+}
+
+/// A slot of a handled connection which is freed when dropped
+struct ConnectionSlot(Receiver<()>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        _ = self.0.recv();
+    }
+}
+
+/// Serve the backend of a repository using the restic REST protocol.
+///
+/// This is a shortcut for [`RestServer::new`] followed by [`RestServer::serve`]
+/// and blocks the calling thread.
+///
+/// # Arguments
+///
+/// * `repo` - The repository to serve
+/// * `addr` - The address to listen on
+/// * `opts` - The options to use
+///
+/// # Errors
+///
+/// * If the server could not bind to the given address.
+pub fn serve_repository_rest<P, S>(
+    repo: &Repository<P, S>,
+    addr: impl ToSocketAddrs,
+    opts: RestServerOptions,
+) -> RusticResult<()> {
+    RestServer::new(repo, addr, opts)?.serve()
+}
+
+/// A parsed HTTP request
+#[derive(Debug)]
+struct Request {
+    /// The request method
+    method: String,
+    /// The path of the request target
+    path: String,
+    /// The query of the request target, if any
+    query: Option<String>,
+    /// The headers; names are lowercase
+    headers: Vec<(String, String)>,
+    /// The body; empty until it is read by [`Request::read_body`]
+    body: Bytes,
+}
+
+impl Request {
+    /// Read the request line and headers from the given reader.
+    ///
+    /// The body is not read, see [`Request::read_body`].
+    /// Returns `None` if the connection was closed before a request was started.
+    fn read_from(reader: &mut impl BufRead) -> std::io::Result<Option<Self>> {
+        let mut line = String::new();
+        if read_line_limited(reader, &mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            return Err(invalid_data("invalid request line"));
+        };
+        let method = method.to_string();
+        let (path, query) = match target.split_once('?') {
+            Some((path, query)) => (path.to_string(), Some(query.to_string())),
+            None => (target.to_string(), None),
+        };
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            if read_line_limited(reader, &mut line)? == 0 {
+                return Err(invalid_data("connection closed within headers"));
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() >= constants::MAX_HEADERS {
+                return Err(invalid_data("too many headers"));
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(invalid_data("invalid header"));
+            };
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+
+        let request = Self {
+            method,
+            path,
+            query,
+            headers,
+            body: Bytes::new(),
+        };
+
+        if request
+            .header("transfer-encoding")
+            .is_some_and(|enc| !enc.eq_ignore_ascii_case("identity"))
+        {
+            return Err(invalid_data("transfer encodings are not supported"));
+        }
+
+        Ok(Some(request))
+    }
+
+    /// The length of the body as given by the `Content-Length` header
+    fn content_length(&self) -> std::io::Result<usize> {
+        self.header("content-length").map_or(Ok(0), |length| {
+            length
+                .parse()
+                .map_err(|_| invalid_data("invalid content length"))
+        })
+    }
+
+    /// Read the body of the given length from the given reader.
+    ///
+    /// The body is read in increments, so memory is only used for data which has actually been received.
+    fn read_body(&mut self, reader: &mut impl BufRead, length: usize) -> std::io::Result<()> {
+        let mut body = Vec::new();
+        _ = reader.take(length as u64).read_to_end(&mut body)?;
+        if body.len() != length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "connection closed within body",
+            ));
+        }
+        self.body = body.into();
+        Ok(())
+    }
+
+    /// Whether the request would modify the repository
+    fn is_modifying(&self) -> bool {
+        !matches!(self.method.as_str(), "GET" | "HEAD")
+    }
+
+    /// Get the value of a header
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the client asked to close the connection after this request
+    fn wants_close(&self) -> bool {
+        self.header("connection")
+            .is_some_and(|c| c.eq_ignore_ascii_case("close"))
+    }
+}
+
+/// Read a line of at most [`constants::MAX_HEADER_LINE`] bytes.
+///
+/// Returns the number of bytes read, `0` means the connection has been closed.
+fn read_line_limited(reader: &mut impl BufRead, line: &mut String) -> std::io::Result<usize> {
+    let read = reader.take(constants::MAX_HEADER_LINE).read_line(line)?;
+    if read as u64 == constants::MAX_HEADER_LINE && !line.ends_with('\n') {
+        return Err(invalid_data("line too long"));
+    }
+    Ok(read)
+}
+
+/// Construct an [`std::io::Error`] for an invalid request
+fn invalid_data(msg: &'static str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// A HTTP response
+#[derive(Debug)]
+struct Response {
+    /// The status code
+    status: u16,
+    /// Additional headers
+    headers: Vec<(&'static str, String)>,
+    /// The length which is sent as `Content-Length`; this differs from the body length for `HEAD` requests
+    content_length: usize,
+    /// The body
+    body: Bytes,
+}
+
+impl Response {
+    /// Create a response without body
+    const fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            content_length: 0,
+            body: Bytes::new(),
+        }
+    }
+
+    /// Create a response with the given body
+    const fn body(status: u16, body: Bytes) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            content_length: body.len(),
+            body,
+        }
+    }
+
+    /// Add a header to the response
+    fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+
+    /// Write the response
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let reason = match self.status {
+            200 => "OK",
+            206 => "Partial Content",
+            400 => "Bad Request",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            413 => "Content Too Large",
+            416 => "Range Not Satisfiable",
+            _ => "Internal Server Error",
+        };
+        write!(writer, "HTTP/1.1 {} {reason}\r\n", self.status)?;
+        for (name, value) in &self.headers {
+            write!(writer, "{name}: {value}\r\n")?;
+        }
+        write!(writer, "Content-Length: {}\r\n\r\n", self.content_length)?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Handle all requests of a connection.
+///
+/// Modifying requests are rejected unless writes are allowed and request bodies are limited in
+/// size. Both is checked before the body is read.
+///
+/// # Arguments
+///
+/// * `be` - The backend to serve
+/// * `opts` - The options of the server
+/// * `stream` - The connection
+fn handle_connection(
+    be: &dyn WriteBackend,
+    opts: RestServerOptions,
+    stream: TcpStream,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(opts.timeout))?;
+    stream.set_write_timeout(Some(opts.timeout))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    while let Some(mut request) = Request::read_from(&mut reader)? {
+        trace!("REST request: {} {}", request.method, request.path);
+        let length = request.content_length()?;
+        let rejected = if request.is_modifying() && !opts.allow_writes {
+            Some(Response::status(403))
+        } else if length > opts.max_body_size {
+            Some(Response::status(413))
+        } else {
+            None
+        };
+        if let Some(response) = rejected {
+            // the body was not read, so the connection can't be used for further requests
+            response
+                .header("Connection", "close")
+                .write_to(&mut writer)?;
+            break;
+        }
+
+        request.read_body(&mut reader, length)?;
+        let response = handle_request(be, &request);
+        response.write_to(&mut writer)?;
+        if request.wants_close() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Handle a single request.
+///
+/// # Arguments
+///
+/// * `be` - The backend to serve
+/// * `request` - The request to handle
+fn handle_request(be: &dyn WriteBackend, request: &Request) -> Response {
+    let method = request.method.as_str();
+    let path = request.path.trim_start_matches('/');
+
+    if path.is_empty() {
+        // creating a repository is done by `POST /?create=true`
+        return match (method, request.query.as_deref()) {
+            ("POST", Some("create=true")) => result_response(be.create()),
+            _ => Response::status(405),
+        };
+    }
+
+    if path == "config" {
+        return handle_file(be, request, FileType::Config, &Id::default());
+    }
+
+    let (dir, name) = path.split_once('/').unwrap_or((path, ""));
+    let Some(tpe) = ALL_FILE_TYPES.into_iter().find(|tpe| tpe.dirname() == dir) else {
+        return Response::status(404);
+    };
+
+    if name.is_empty() {
+        return match method {
+            "GET" => handle_list(be, request, tpe),
+            _ => Response::status(405),
+        };
+    }
+
+    match Id::from_hex(name) {
+        Ok(id) => handle_file(be, request, tpe, &id),
+        Err(_) => Response::status(404),
+    }
+}
+
+/// Handle listing the files of a given type.
+///
+/// The response format depends on the protocol version requested by the client.
+fn handle_list(be: &dyn WriteBackend, request: &Request, tpe: FileType) -> Response {
+    #[derive(Serialize)]
+    struct ListEntry {
+        name: String,
+        size: u32,
+    }
+
+    let list = match be.list_with_size(tpe) {
+        Ok(list) => list,
+        Err(err) => {
+            warn!("error listing {tpe:?} files: {err}");
+            return Response::status(500);
+        }
+    };
+
+    let v2 = request
+        .header("accept")
+        .is_some_and(|accept| accept.contains(MEDIA_TYPE_V2));
+    let (media_type, json) = if v2 {
+        let entries: Vec<_> = list
+            .into_iter()
+            .map(|(id, size)| ListEntry {
+                name: id.to_hex().to_string(),
+                size,
+            })
+            .collect();
+        (MEDIA_TYPE_V2, serde_json::to_vec(&entries))
+    } else {
+        let names: Vec<_> = list
+            .into_iter()
+            .map(|(id, _)| id.to_hex().to_string())
+            .collect();
+        (MEDIA_TYPE_V1, serde_json::to_vec(&names))
+    };
+
+    match json {
+        Ok(json) => Response::body(200, json.into()).header("Content-Type", media_type),
+        Err(err) => {
+            warn!("error serializing {tpe:?} list: {err}");
+            Response::status(500)
+        }
+    }
+}
+
+/// Handle a request for a single file.
+fn handle_file(be: &dyn WriteBackend, request: &Request, tpe: FileType, id: &Id) -> Response {
+    match request.method.as_str() {
+        "HEAD" => match be.file_size(tpe, id) {
+            Ok(Some(size)) => {
+                let mut response = Response::status(200);
+                response.content_length = size as usize;
+                response
+            }
+            Ok(None) => Response::status(404),
+            Err(err) => {
+                warn!("error getting size of {tpe:?} file {id}: {err}");
+                Response::status(500)
+            }
+        },
+        "GET" => match request.header("range") {
+            Some(range) => handle_range(be, tpe, id, range),
+            None => match be.read_full(tpe, id) {
+                Ok(data) => Response::body(200, data),
+                Err(err) => read_error_response(be, tpe, id, &err),
+            },
+        },
+        "POST" => result_response(be.write_bytes(tpe, id, false, request.body.clone())),
+        "DELETE" if tpe != FileType::Config => result_response(be.remove(tpe, id, false)),
+        _ => Response::status(405),
+    }
+}
+
+/// Check whether the given file exists.
+fn file_exists(be: &dyn WriteBackend, tpe: FileType, id: &Id) -> RusticResult<bool> {
+    Ok(be.file_size(tpe, id)?.is_some())
+}
+
+/// The response for a failed read: `404` if the file doesn't exist, `500` otherwise
+fn read_error_response(
+    be: &dyn WriteBackend,
+    tpe: FileType,
+    id: &Id,
+    err: &RusticError,
+) -> Response {
+    match file_exists(be, tpe, id) {
+        Ok(false) => Response::status(404),
+        _ => {
+            warn!("error reading {tpe:?} file {id}: {err}");
+            Response::status(500)
+        }
+    }
+}
+
+/// Handle a `GET` request with a `Range` header.
+///
+/// Only single byte ranges are supported; for other ranges the whole file is returned.
+fn handle_range(be: &dyn WriteBackend, tpe: FileType, id: &Id, range: &str) -> Response {
+    let range = range
+        .strip_prefix("bytes=")
+        .filter(|range| !range.contains(','))
+        .and_then(|range| range.split_once('-'));
+
+    let data = match range {
+        // fully specified range: only read the requested part
+        Some((start, end)) if !start.is_empty() && !end.is_empty() => {
+            let (Ok(start), Ok(end)) = (start.parse::<u64>(), end.parse::<u64>()) else {
+                return Response::status(416);
+            };
+            if end < start {
+                return Response::status(416);
+            }
+            // the backend reads at most `u32::MAX` bytes at offsets below `u32::MAX`
+            let (Ok(offset), Some(Ok(length))) = (
+                u32::try_from(start),
+                (end - start).checked_add(1).map(u32::try_from),
+            ) else {
+                return Response::status(416);
+            };
+            return match be.read_partial(tpe, id, false, offset, length) {
+                Ok(data) if !data.is_empty() => {
+                    let end = start as usize + data.len() - 1;
+                    Response::body(206, data)
+                        .header("Content-Range", format!("bytes {start}-{end}/*"))
+                }
+                Ok(_) => Response::status(416),
+                Err(err) => read_error_response(be, tpe, id, &err),
+            };
+        }
+        _ => match be.read_full(tpe, id) {
+            Ok(data) => data,
+            Err(err) => return read_error_response(be, tpe, id, &err),
+        },
+    };
+
+    let len = data.len();
+    let (start, end) = match range {
+        // open range, e.g. `bytes=100-`
+        Some((start, "")) if !start.is_empty() => match start.parse::<usize>() {
+            Ok(start) => (start, len.saturating_sub(1)),
+            Err(_) => return Response::status(416),
+        },
+        // suffix range, e.g. `bytes=-100`
+        Some(("", suffix)) => match suffix.parse::<usize>() {
+            Ok(suffix) => (len.saturating_sub(suffix), len.saturating_sub(1)),
+            Err(_) => return Response::status(416),
+        },
+        _ => return Response::body(200, data),
+    };
+    if start >= len {
+        return Response::status(416).header("Content-Range", format!("bytes */{len}"));
+    }
+
+    Response::body(206, data.slice(start..=end))
+        .header("Content-Range", format!("bytes {start}-{end}/{len}"))
+}
+
+/// Convert the result of a modifying backend operation into a response
+fn result_response(result: RusticResult<()>) -> Response {
+    match result {
+        Ok(()) => Response::status(200),
+        Err(err) => {
+            warn!("error handling REST request: {err}");
+            Response::status(500)
+        }
+    }
+}
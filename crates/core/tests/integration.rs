@@ -25,10 +25,25 @@
 
 mod integration {
     mod backup;
+    mod check;
+    mod config;
+    mod copy;
+    mod dedup;
+    mod diff;
+    mod dump;
     mod find;
+    mod forget;
+    mod index;
+    mod key;
+    mod lock;
     mod ls;
+    mod merge;
     mod prune;
+    mod repair;
+    #[cfg(feature = "rest-server")]
+    mod rest_server;
     mod restore;
+    mod stats;
     mod vfs;
     use super::*;
 }
@@ -50,8 +65,9 @@ use tempfile::{tempdir, TempDir};
 // use simplelog::{Config, SimpleLogger};
 
 use rustic_core::{
-    CommandInput, ConfigOptions, FullIndex, IndexedFull, IndexedStatus, KeyOptions, NoProgressBars,
-    OpenStatus, PathList, Repository, RepositoryBackends, RepositoryOptions,
+    repofile::SnapshotFile, CommandInput, ConfigOptions, FullIndex, IndexedFull, IndexedStatus,
+    KeyOptions, NoProgressBars, OpenStatus, PathList, Repository, RepositoryBackends,
+    RepositoryOptions,
 };
 use rustic_testing::backend::in_memory_backend::InMemoryBackend;
 
@@ -210,3 +226,41 @@ fn test_wrapping_in_new_type() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn repo_open_read_only() -> Result<()> {
+    let be = InMemoryBackend::new();
+    let be = RepositoryBackends::new(Arc::new(be), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &be)?;
+    let repo = repo.init(&KeyOptions::default(), &ConfigOptions::default())?;
+    repo.save_snapshots(vec![SnapshotFile::default()])?;
+
+    let repo = Repository::new(&options, &be)?.open_read_only()?;
+    assert_eq!(repo.get_all_snapshots()?.len(), 1);
+
+    // reading still works after indexing
+    let repo = repo.to_indexed()?;
+    assert_eq!(repo.get_all_snapshots()?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn repo_debug_redacts_password() -> Result<()> {
+    let be = InMemoryBackend::new();
+    let be = RepositoryBackends::new(Arc::new(be), None);
+    let options = RepositoryOptions::default().password("very-secret-password");
+    let repo = Repository::new(&options, &be)?;
+    assert!(!format!("{repo:?}").contains("very-secret-password"));
+
+    let repo = repo.init(&KeyOptions::default(), &ConfigOptions::default())?;
+    assert!(!format!("{repo:?}").contains("very-secret-password"));
+    Ok(())
+}
+
+#[cfg(not(feature = "keyring"))]
+#[test]
+fn repo_password_keyring_needs_feature() {
+    let options = RepositoryOptions::default().password_keyring("test");
+    assert!(options.evaluate_password().is_err());
+}
@@ -1,18 +1,26 @@
 use std::{
+    collections::BTreeSet,
+    fs::{self, File},
     path::{Path, PathBuf},
     str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use anyhow::Result;
 use insta::Settings;
 use pretty_assertions::assert_eq;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
 use rstest::rstest;
 
 use rustic_core::{
-    repofile::{PackId, SnapshotFile},
-    BackupOptions, CommandInput, ParentOptions, PathList, SnapshotGroupCriterion, SnapshotOptions,
-    StringList,
+    repofile::{BlobType, PackId, SnapshotFile},
+    BackupEvent, BackupOptions, BackupOutcome, CancellationToken, CheckOptions, CommandInput,
+    ConfigOptions, ErrorKind, FileStatus, FileType, KeyOptions, MirrorFailureMode, ParentOptions,
+    PathList, PruneOptions, ReadBackend, Repository, RepositoryBackends, RepositoryOptions,
+    RusticResult, SnapshotGroupCriterion, SnapshotOptions, StringList, MANIFEST_HASH_KEY,
 };
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
 
 use super::{
     assert_with_win, insta_node_redaction, insta_snapshotfile_redaction, set_up_repo,
@@ -133,6 +141,28 @@ fn test_backup_with_tar_gz_passes(
         assert_with_win("backup-tar-groups", &groups);
     });
 
+    // get snapshot group summary; groups are sorted by tags as only tags are used for grouping
+    let summary = repo.snapshot_group_summary(group_by, |_| true)?;
+    assert_eq!(summary.len(), groups.len());
+    for ((group, snaps), (summary_group, summary)) in groups.iter().zip(&summary) {
+        assert_eq!(group, summary_group);
+        assert_eq!(summary.count, snaps.len());
+        assert_eq!(
+            summary.oldest,
+            snaps.iter().map(|sn| sn.time).min().unwrap()
+        );
+        assert_eq!(
+            summary.newest,
+            snaps.iter().map(|sn| sn.time).max().unwrap()
+        );
+        let size: u64 = snaps
+            .iter()
+            .filter_map(|sn| sn.summary.as_ref())
+            .map(|summary| summary.total_bytes_processed)
+            .sum();
+        assert_eq!(summary.total_size, size);
+    }
+
     // filter snapshots by tag
     let filter = |snap: &SnapshotFile| snap.tags.contains("a");
     let snaps = repo.get_matching_snapshots(filter)?;
@@ -243,3 +273,612 @@ fn test_backup_stdin_command(
     assert_eq!(content, b"test\n");
     Ok(())
 }
+
+#[rstest]
+fn test_backup_reader(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    // Fixtures
+    let repo = set_up_repo?.to_indexed_ids()?;
+
+    // data spanning several chunks; the reader doesn't know its size
+    let mut data = vec![0; 5_000_000];
+    StdRng::seed_from_u64(42).fill_bytes(&mut data);
+    let reader = std::io::Cursor::new(data.clone());
+
+    let snapshot = repo.backup_reader(
+        reader,
+        "dump.sql",
+        &BackupOptions::default(),
+        SnapshotFile::default(),
+    )?;
+    assert_eq!(snapshot.paths, StringList::from_str("dump.sql")?);
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_new, 1);
+    assert_eq!(summary.total_files_processed, 1);
+    assert_eq!(summary.total_bytes_processed, 5_000_000);
+    assert!(summary.data_blobs > 1);
+
+    // re-read index
+    let repo = repo.to_indexed()?;
+
+    // check content
+    let node = repo.node_from_snapshot_path("latest:dump.sql", |_| true)?;
+    let mut content = Vec::new();
+    repo.dump(&node, &mut content)?;
+    assert_eq!(content, data);
+    Ok(())
+}
+
+#[rstest]
+#[case("")]
+#[case("/")]
+#[case("..")]
+fn test_backup_reader_without_filename_fails(
+    set_up_repo: Result<RepoOpen>,
+    #[case] filename: &str,
+) -> Result<()> {
+    // Fixtures
+    let repo = set_up_repo?.to_indexed_ids()?;
+
+    let reader = std::io::Cursor::new(b"test".to_vec());
+    let err = repo
+        .backup_reader(
+            reader,
+            filename,
+            &BackupOptions::default(),
+            SnapshotFile::default(),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    assert!(repo.get_all_snapshots()?.is_empty());
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_with_parent_filter_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+
+    let snap = |label: &str| SnapshotFile {
+        label: label.to_string(),
+        ..Default::default()
+    };
+
+    let first_snapshot = repo.backup(&opts, paths, snap("first"))?;
+
+    // a different label doesn't match the default group
+    let repo = repo.to_indexed_ids()?;
+    let second_snapshot = repo.backup(&opts, paths, snap("second"))?;
+    assert_eq!(second_snapshot.parent, None);
+
+    // the filter overrides the default group
+    let repo = repo.to_indexed_ids()?;
+    let mut filter_opts = opts.clone();
+    filter_opts.parent_opts = ParentOptions::default().parent_filter(|snap| snap.label == "first");
+    let third_snapshot = repo.backup(&filter_opts, paths, snap("third"))?;
+    assert_eq!(third_snapshot.parent, Some(first_snapshot.id));
+
+    // force still disables the parent
+    let repo = repo.to_indexed_ids()?;
+    filter_opts.parent_opts.force = true;
+    let fourth_snapshot = repo.backup(&filter_opts, paths, snap("fourth"))?;
+    assert_eq!(fourth_snapshot.parent, None);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_skip_if_unchanged(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let mut opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    opts.parent_opts.skip_if_unchanged = true;
+
+    let outcome = repo.backup_with_outcome(&opts, paths, SnapshotFile::default())?;
+    assert!(outcome.is_created());
+    let first_snapshot = outcome.into_snapshot();
+    assert!(repo.snapshot_exists(&first_snapshot.id)?);
+
+    // nothing changed, so no new snapshot is written
+    let repo = repo.to_indexed_ids()?;
+    let outcome = repo.backup_with_outcome(&opts, paths, SnapshotFile::default())?;
+    assert!(matches!(
+        outcome,
+        BackupOutcome::Unchanged { parent, .. } if parent == first_snapshot.id
+    ));
+    assert_eq!(outcome.snapshot().tree, first_snapshot.tree);
+    assert!(!repo.snapshot_exists(&outcome.snapshot().id)?);
+    assert_eq!(repo.get_all_snapshots()?.len(), 1);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_snapshot_manifest_hash(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let second_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+
+    // identical trees give identical hashes
+    let hash = repo.snapshot_manifest_hash(&snapshot)?;
+    assert_eq!(repo.snapshot_manifest_hash(&second_snapshot)?, hash);
+
+    // different contents give different hashes
+    let paths = PathList::from_iter(Some(source.0.path().join("0/0/9/3")));
+    let other_snapshot = repo.backup(&opts, &paths, SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+    assert_ne!(repo.snapshot_manifest_hash(&other_snapshot)?, hash);
+
+    // save the hash in the snapshot and verify it
+    assert_eq!(repo.verify_snapshot_manifest_hash(&snapshot)?, None);
+    let new_snapshot = repo.save_snapshot_manifest_hash(&snapshot)?;
+    assert_eq!(new_snapshot.original, Some(snapshot.id));
+    assert_eq!(
+        new_snapshot.extra.get(MANIFEST_HASH_KEY),
+        Some(&hash.to_hex().as_str().into())
+    );
+    assert!(!repo.snapshot_exists(&snapshot.id)?);
+    assert_eq!(
+        repo.verify_snapshot_manifest_hash(&new_snapshot)?,
+        Some(true)
+    );
+
+    // the hash doesn't depend on the pack layout
+    let repo = repo.to_open();
+    let prune_opts = PruneOptions::default()
+        .repack_all(true)
+        .instant_delete(true)
+        .keep_delete(Duration::ZERO);
+    let plan = repo.prune_plan(&prune_opts)?;
+    repo.prune(&prune_opts, plan)?;
+    let repo = repo.to_indexed_ids()?;
+    assert_eq!(
+        repo.verify_snapshot_manifest_hash(&new_snapshot)?,
+        Some(true)
+    );
+
+    // a wrong hash is detected
+    let mut wrong_snapshot = new_snapshot;
+    _ = wrong_snapshot.extra.insert(
+        MANIFEST_HASH_KEY.to_string(),
+        repo.snapshot_manifest_hash(&other_snapshot)?
+            .to_hex()
+            .as_str()
+            .into(),
+    );
+    assert_eq!(
+        repo.verify_snapshot_manifest_hash(&wrong_snapshot)?,
+        Some(false)
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backfill_summary_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let expected = snapshot.summary.clone().unwrap();
+
+    // replace the snapshot by a copy without summary, like snapshots from old versions
+    let mut old_snapshot = snapshot.clone();
+    old_snapshot.summary = None;
+    repo.save_snapshots(vec![old_snapshot])?;
+    repo.delete_snapshots(&[snapshot.id])?;
+    let old_snapshot = repo.get_all_snapshots()?.pop().unwrap();
+    assert!(old_snapshot.summary.is_none());
+
+    // dry-run doesn't modify the repository
+    let repo = repo.to_indexed_ids()?;
+    let summary = repo.compute_snapshot_summary(&old_snapshot)?;
+    assert!(repo.snapshot_exists(&old_snapshot.id)?);
+
+    let new_snapshot = repo.backfill_summary(&old_snapshot)?;
+    let new_summary = new_snapshot.summary.as_ref().unwrap();
+    assert_eq!(
+        new_summary.total_files_processed,
+        summary.total_files_processed
+    );
+    assert_eq!(new_snapshot.original, Some(old_snapshot.id));
+    assert_eq!(new_snapshot.tree, old_snapshot.tree);
+    assert!(!repo.snapshot_exists(&old_snapshot.id)?);
+    assert_eq!(repo.get_all_snapshots()?.len(), 1);
+
+    assert_eq!(
+        summary.total_files_processed,
+        expected.total_files_processed
+    );
+    assert_eq!(
+        summary.total_bytes_processed,
+        expected.total_bytes_processed
+    );
+    assert_eq!(summary.total_dirs_processed, expected.total_dirs_processed);
+    assert_eq!(
+        summary.total_dirsize_processed,
+        expected.total_dirsize_processed
+    );
+
+    // snapshots with summary are left unchanged
+    let unchanged = repo.backfill_summary(&new_snapshot)?;
+    assert_eq!(unchanged.id, new_snapshot.id);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_to_mirror_passes(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let mirror = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None)
+        .with_mirror(mirror.clone())
+        .on_mirror_failure(MirrorFailureMode::Fatal);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // the mirror contains exactly the same files
+    for tpe in [
+        FileType::Config,
+        FileType::Key,
+        FileType::Snapshot,
+        FileType::Index,
+        FileType::Pack,
+    ] {
+        let mut files = be.list_with_size(tpe)?;
+        let mut mirrored = mirror.list_with_size(tpe)?;
+        files.sort_unstable();
+        mirrored.sort_unstable();
+        assert_eq!(files, mirrored);
+    }
+
+    // the mirror can be used as repository
+    let repo = Repository::new(&options, &RepositoryBackends::new(mirror, None))?.open()?;
+    assert_eq!(repo.get_all_snapshots()?, vec![snapshot]);
+    assert!(repo.check(CheckOptions::default())?.is_ok());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_with_event_sink_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let sink = events.clone();
+    let opts = BackupOptions::default()
+        .as_path(PathBuf::from_str("test")?)
+        .on_event(move |event| sink.lock().unwrap().push(event));
+
+    let first_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let first_events = std::mem::take(&mut *events.lock().unwrap());
+
+    assert!(matches!(
+        first_events.first(),
+        Some(BackupEvent::ScanStarted { .. })
+    ));
+    let Some(BackupEvent::Finished { summary }) = first_events.last() else {
+        panic!("last event should be Finished");
+    };
+    let summary_first = first_snapshot.summary.unwrap();
+    assert_eq!(summary.files_new, summary_first.files_new);
+
+    let processed: Vec<_> = first_events
+        .iter()
+        .filter_map(|event| match event {
+            BackupEvent::FileProcessed { path, status, .. } => Some((path, *status)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(processed.len() as u64, summary_first.total_files_processed);
+    assert!(processed
+        .iter()
+        .all(|(path, status)| path.starts_with("test") && *status == FileStatus::New));
+    assert!(processed
+        .iter()
+        .any(|(path, _)| path == &Path::new("test/0/0/9/0")));
+
+    // events can be serialized
+    for event in &first_events {
+        _ = serde_json::to_string(event)?;
+    }
+
+    // second backup only contains unchanged files
+    let repo = repo.to_indexed_ids()?;
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+    assert!(events.lock().unwrap().iter().all(|event| !matches!(
+        event,
+        BackupEvent::FileProcessed { status, .. } if *status != FileStatus::Unchanged
+    )));
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_with_force_hash_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let file = source.0.path().join("force-hash.txt");
+    fs::write(&file, "content")?;
+    let mtime = fs::metadata(&file)?.modified()?;
+    let paths = &source.path_list();
+
+    let parent_opts = ParentOptions::default()
+        .ignore_ctime(true)
+        .ignore_inode(true);
+    let opts = BackupOptions::default()
+        .as_path(PathBuf::from_str("test")?)
+        .parent_opts(parent_opts.clone());
+    let hash_opts = opts.clone().parent_opts(parent_opts.force_hash(true));
+
+    let _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // change the content, but keep size and mtime
+    fs::write(&file, "CONTENT")?;
+    File::options()
+        .write(true)
+        .open(&file)?
+        .set_modified(mtime)?;
+
+    // metadata says the file is unchanged
+    let repo = repo.to_indexed_ids()?;
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_changed, 0);
+
+    // comparing the content detects the change
+    let repo = repo.to_indexed_ids()?;
+    let snapshot = repo.backup(&hash_opts, paths, SnapshotFile::default())?;
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_changed, 1);
+    assert_eq!(summary.files_unmodified, summary.total_files_processed - 1);
+
+    // unchanged content is still classified as unmodified
+    let repo = repo.to_indexed_ids()?;
+    let snapshot = repo.backup(&hash_opts, paths, SnapshotFile::default())?;
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_changed, 0);
+    assert_eq!(summary.files_unmodified, summary.total_files_processed);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_backup_with_ignore_inode_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let file = source.0.path().join("ignore-inode.txt");
+    fs::write(&file, "content")?;
+    let mtime = fs::metadata(&file)?.modified()?;
+    let paths = &source.path_list();
+
+    let mut opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // always compare to the first snapshot
+    opts.parent_opts.parent = Some(first_snapshot.id.to_string());
+    let mut ignore_opts = opts.clone();
+    ignore_opts.parent_opts.ignore_ctime = true;
+    ignore_opts.parent_opts.ignore_inode = true;
+
+    // replace the file by a copy with identical content and mtime, but a different inode
+    let copy = source.0.path().join("ignore-inode.tmp");
+    _ = fs::copy(&file, &copy)?;
+    File::options()
+        .write(true)
+        .open(&copy)?
+        .set_modified(mtime)?;
+    fs::rename(&copy, &file)?;
+
+    // ignoring inode and ctime, the file is unchanged
+    let repo = repo.to_indexed_ids()?;
+    let snapshot = repo.backup(&ignore_opts, paths, SnapshotFile::default())?;
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_changed, 0);
+    assert_eq!(summary.files_new, 0);
+    assert_eq!(summary.files_unmodified, summary.total_files_processed);
+
+    // by default, the changed inode marks the file as changed
+    let repo = repo.to_indexed_ids()?;
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let summary = snapshot.summary.unwrap();
+    assert_eq!(summary.files_changed, 1);
+    assert_eq!(summary.files_unmodified, summary.total_files_processed - 1);
+    // but no new data is added
+    assert_eq!(summary.data_blobs, 0);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_with_fixed_chunk_size_passes(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let paths = &source.path_list();
+
+    // 16 times the same block of 64 KiB
+    let mut block = vec![0u8; 64 * 1024];
+    StdRng::seed_from_u64(42).fill_bytes(&mut block);
+    fs::write(source.0.path().join("cas.bin"), block.repeat(16))?;
+
+    let content = |opts: &BackupOptions| -> Result<_> {
+        let repo = set_up_repo()?.to_indexed_ids()?;
+        let snapshot = repo.backup(opts, paths, SnapshotFile::default())?;
+        let node = repo.node_from_path(snapshot.tree, Path::new("test/cas.bin"))?;
+        Ok(node.content.unwrap())
+    };
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let cdc_content = content(&opts)?;
+    let fixed_content = content(&opts.clone().fixed_chunk_size(64 * 1024))?;
+
+    // content defined chunking uses a minimum chunk size of 512 KiB
+    assert!(cdc_content.len() <= 2);
+    assert_eq!(fixed_content.len(), 16);
+    // all fixed chunks are identical, i.e. they are deduplicated to a single blob
+    assert_eq!(fixed_content.iter().collect::<BTreeSet<_>>().len(), 1);
+
+    // the chunk size is checked
+    let repo = set_up_repo()?.to_indexed_ids()?;
+    assert!(repo
+        .backup(
+            &opts.clone().fixed_chunk_size(1024),
+            paths,
+            SnapshotFile::default()
+        )
+        .is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_skipping_compression_passes(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let paths = &source.path_list();
+
+    let mut random = vec![0u8; 1024 * 1024];
+    StdRng::seed_from_u64(42).fill_bytes(&mut random);
+    fs::write(source.0.path().join("random.bin"), &random)?;
+    fs::write(source.0.path().join("uniform.bin"), vec![1u8; 1024 * 1024])?;
+    fs::write(source.0.path().join("uniform.jpg"), vec![2u8; 1024 * 1024])?;
+
+    // returns for each file whether all its blobs are stored compressed
+    let compressed = |opts: &BackupOptions| -> Result<Vec<bool>> {
+        let repo = set_up_repo()?.to_indexed_ids()?;
+        let snapshot = repo.backup(opts, paths, SnapshotFile::default())?;
+        let repo = repo.to_indexed()?;
+        ["random.bin", "uniform.bin", "uniform.jpg"]
+            .into_iter()
+            .map(|file| -> Result<_> {
+                let node = repo.node_from_path(snapshot.tree, &Path::new("test").join(file))?;
+                let mut compressed = true;
+                for id in node.content.unwrap() {
+                    compressed &= repo.get_index_entry(&id)?.uncompressed_length.is_some();
+                }
+                Ok(compressed)
+            })
+            .collect()
+    };
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    assert_eq!(compressed(&opts)?, [true, true, true]);
+
+    let opts = opts
+        .compression_probe(true)
+        .no_compression_extensions(vec!["JPG".to_string()]);
+    assert_eq!(compressed(&opts)?, [false, true, false]);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_chunker_matches_backup_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let mut data = vec![0u8; 4 * 1024 * 1024];
+    StdRng::seed_from_u64(42).fill_bytes(&mut data);
+    let file = source.0.path().join("chunker.bin");
+    fs::write(&file, &data)?;
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let repo = repo.to_indexed()?;
+    let content = repo
+        .node_from_path(snapshot.tree, Path::new("test/chunker.bin"))?
+        .content
+        .unwrap();
+
+    let chunker = repo.chunker()?;
+    assert_eq!(chunker.poly(), repo.config().poly()?);
+    let chunks = chunker
+        .chunks(File::open(&file)?)
+        .collect::<RusticResult<Vec<_>>>()?;
+
+    // the chunks are identical to the blobs saved by the backup
+    assert_eq!(chunks.len(), content.len());
+    let mut expected_offset = 0;
+    for ((offset, chunk), id) in chunks.iter().zip(&content) {
+        assert_eq!(*offset, expected_offset);
+        assert_eq!(*chunk, repo.get_blob_cached(&(*id).into(), BlobType::Data)?);
+        expected_offset += chunk.len() as u64;
+    }
+
+    // the chunks can be reassembled to the original data
+    let reassembled: Vec<u8> = chunks.into_iter().flat_map(|(_, chunk)| chunk).collect();
+    assert_eq!(reassembled, data);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_backup_cancelled_fails(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let token = CancellationToken::new();
+    let (source, repo) = (
+        tar_gz_testdata?,
+        set_up_repo?
+            .with_cancellation(token.clone())
+            .to_indexed_ids()?,
+    );
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+
+    token.cancel();
+    assert!(repo.backup(&opts, paths, SnapshotFile::default()).is_err());
+    // no snapshot has been written
+    assert!(repo.get_all_snapshots()?.is_empty());
+    // other operations are cancelled, too
+    assert!(repo.check(CheckOptions::default()).is_err());
+
+    Ok(())
+}
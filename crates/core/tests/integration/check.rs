@@ -0,0 +1,343 @@
+use std::{fs, path::PathBuf, str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use rstest::rstest;
+use tempfile::tempdir;
+
+use bytes::Bytes;
+use rustic_core::{
+    repofile::{BlobType, IndexFile, LockId, PackId, SnapshotFile},
+    BackupOptions, CheckIssueKind, CheckOptions, ConfigOptions, FileType, KeyOptions, PackProblem,
+    PathList, ReadBackend, Repository, RepositoryBackends, RepositoryOptions, TreeId, WriteBackend,
+};
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_check_snapshot_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    assert!(repo
+        .check_snapshot(&snapshot, CheckOptions::default())?
+        .is_ok());
+    assert!(repo
+        .check_snapshot(&snapshot, CheckOptions::default().read_data(true))?
+        .is_ok());
+
+    // a snapshot with an unknown tree can't be checked
+    let mut unknown = snapshot;
+    unknown.tree = TreeId::default();
+    assert!(repo
+        .check_snapshot(&unknown, CheckOptions::default())
+        .is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_check_reports_issues(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let check_opts = CheckOptions::default().read_data(true).quiet(true);
+    let results = repo.check(check_opts)?;
+    assert!(results.issues.is_empty(), "{results:?}");
+    assert!(results.is_ok());
+    assert!(results.into_result().is_ok());
+
+    // remove a data pack which is referenced by the index
+    let mut data_packs = Vec::new();
+    for index in repo.stream_files::<IndexFile>()? {
+        data_packs.extend(
+            index?
+                .1
+                .packs
+                .into_iter()
+                .filter(|pack| pack.blob_type() == BlobType::Data)
+                .map(|pack| pack.id),
+        );
+    }
+    let pack = data_packs[0];
+    be.remove(FileType::Pack, &pack, false)?;
+
+    let results = repo.check(check_opts)?;
+    assert!(!results.is_ok());
+    assert!(results.errors().any(|issue| issue.kind
+        == CheckIssueKind::PackMissing {
+            id: pack,
+            hot: false
+        }));
+    assert_eq!(results.warnings().count(), 0);
+    assert!(results.into_result().is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_check_since_snapshot(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let second = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let check_opts = CheckOptions::default().read_data(true).quiet(true);
+    for since in [first.id, second.id] {
+        let results = repo.check(check_opts.since(since))?;
+        assert!(results.is_ok(), "{results:?}");
+    }
+
+    // an unknown marker snapshot is an error
+    let unknown = SnapshotFile::default().id;
+    assert!(repo.check(check_opts.since(unknown)).is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_check_since_skips_older_packs(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let old_packs = be.list(FileType::Pack)?;
+
+    // the newer snapshot contains completely different data
+    let new_source = tempdir()?;
+    fs::write(new_source.path().join("file"), "new content")?;
+    let newer = repo.backup(
+        &BackupOptions::default(),
+        &PathList::from_iter(Some(new_source.path().to_path_buf())),
+        SnapshotFile::default(),
+    )?;
+
+    // corrupt a data pack which is only referenced by the older snapshot
+    let mut data_packs = Vec::new();
+    for index in repo.stream_files::<IndexFile>()? {
+        data_packs.extend(
+            index?
+                .1
+                .packs
+                .into_iter()
+                .filter(|pack| pack.blob_type() == BlobType::Data)
+                .map(|pack| pack.id),
+        );
+    }
+    let pack = *data_packs
+        .iter()
+        .find(|pack| old_packs.contains(pack))
+        .unwrap();
+    let mut data = be.read_full(FileType::Pack, &pack)?.to_vec();
+    let middle = data.len() / 2;
+    data[middle] ^= 0xff;
+    be.remove(FileType::Pack, &pack, false)?;
+    be.write_bytes(FileType::Pack, &pack, false, data.into())?;
+
+    let check_opts = CheckOptions::default().read_data(true).quiet(true);
+    let results = repo.check(check_opts)?;
+    assert!(!results.is_ok(), "{results:?}");
+
+    let results = repo.check(check_opts.since(newer.id))?;
+    assert!(results.is_ok(), "{results:?}");
+
+    Ok(())
+}
+
+#[rstest]
+fn test_find_stale_index_entries(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    assert!(repo.find_stale_index_entries()?.is_empty());
+
+    // remove a data pack which is referenced by the index
+    let (index_id, pack) = repo
+        .stream_files::<IndexFile>()?
+        .find_map(|index| {
+            let (id, index) = index.ok()?;
+            index
+                .packs
+                .into_iter()
+                .find(|pack| pack.blob_type() == BlobType::Data)
+                .map(|pack| (id, pack.id))
+        })
+        .unwrap();
+    be.remove(FileType::Pack, &pack, false)?;
+
+    assert_eq!(
+        repo.find_stale_index_entries()?,
+        vec![(index_id, vec![pack])]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_check_backend_passes() -> Result<()> {
+    // Fixtures
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+
+    let result = repo.check_backend()?;
+    assert!(result.write.is_some());
+    assert!(result.read.is_some());
+    assert!(result.remove.is_some());
+
+    // the test file must have been removed again and never appears as lock file
+    assert!(be.list(FileType::SelfTest)?.is_empty());
+    assert_eq!(repo.list::<LockId>()?.count(), 0);
+    Ok(())
+}
+
+#[rstest]
+fn test_verify_pack_finds_corrupted_pack(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let packs: Vec<PackId> = repo.list()?.collect();
+    assert!(!packs.is_empty());
+    for pack in &packs {
+        let verification = repo.verify_pack(*pack)?;
+        assert_eq!(verification.id, *pack);
+        assert!(verification.is_ok(), "{verification:?}");
+    }
+
+    // corrupt the first pack
+    let id = *packs[0];
+    let mut data = be.read_full(FileType::Pack, &id)?.to_vec();
+    data[0] ^= 0xff;
+    be.write_bytes(FileType::Pack, &id, false, Bytes::from(data))?;
+
+    let verification = repo.verify_pack(packs[0])?;
+    assert!(!verification.is_ok());
+    assert!(matches!(
+        verification.problems[..],
+        [PackProblem::HashMismatch { .. }]
+    ));
+
+    // unknown packs can't be verified
+    assert!(repo.verify_pack(PackId::default()).is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_read_pack_header_matches_index(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut packs = 0;
+    for index in repo.stream_files::<IndexFile>()? {
+        for pack in index?.1.packs {
+            let mut blobs = pack.blobs;
+            blobs.sort_unstable_by_key(|blob| blob.offset);
+            assert_eq!(repo.read_pack_header(pack.id)?, blobs);
+            packs += 1;
+        }
+    }
+    assert_eq!(packs, repo.list::<PackId>()?.count());
+
+    // unknown packs have no header
+    assert!(repo.read_pack_header(PackId::default()).is_err());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_cat_pack_blobs_matches_index(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // re-read index
+    let repo = repo.to_indexed()?;
+
+    for pack in repo.list::<PackId>()? {
+        let blobs = repo.cat_pack_blobs(pack)?;
+        let header = repo.read_pack_header(pack)?;
+        assert_eq!(blobs.len(), header.len());
+        for ((id, tpe, data), blob) in blobs.iter().zip(header) {
+            assert_eq!((*id, *tpe), (blob.id, blob.tpe));
+            assert_eq!(data, &repo.cat_blob(*tpe, &id.to_string())?);
+        }
+
+        // the streaming variant yields the same blobs
+        let mut streamed = Vec::new();
+        repo.cat_pack_blobs_with(pack, |id, tpe, data| {
+            streamed.push((id, tpe, data));
+            Ok(())
+        })?;
+        assert_eq!(streamed, blobs);
+    }
+
+    assert!(repo.cat_pack_blobs(PackId::default()).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+use tempfile::tempdir;
+
+use rustic_core::{
+    repofile::SnapshotFile, BackupOptions, CheckOptions, ConfigOptions, KeyOptions, Open, PathList,
+    Repository, RepositoryBackends, RepositoryOptions,
+};
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
+
+#[test]
+fn test_v2_needs_no_migration() -> Result<()> {
+    let backends = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+    assert_eq!(repo.repo_version(), 2);
+    assert_eq!(repo.needs_migration(), None);
+    assert!(repo.migrate_to_latest(false)?.is_empty());
+
+    // version 3 needs a compression dictionary
+    assert!(repo
+        .apply_config(&ConfigOptions::default().set_version(3u32))
+        .is_err());
+    let repo = Repository::new(&options, &backends)?.open()?;
+    assert_eq!(repo.repo_version(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate_to_latest() -> Result<()> {
+    // create a v1 repository
+    let v2_backends = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let options = RepositoryOptions::default().password("test");
+    let mut config = Repository::new(&options, &v2_backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .config()
+        .clone();
+    config.version = 1;
+
+    let backends = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let repo = Repository::new(&options, &backends)?.init_with_config(
+        "test",
+        &KeyOptions::default(),
+        config,
+    )?;
+    assert_eq!(repo.repo_version(), 1);
+    assert_eq!(repo.needs_migration(), Some(2));
+
+    // dry-run doesn't change the config
+    let changes = repo.migrate_to_latest(true)?;
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].field, "version");
+    assert_eq!(changes[0].old, 1);
+    assert_eq!(changes[0].new, 2);
+    let repo = Repository::new(&options, &backends)?.open()?;
+    assert_eq!(repo.repo_version(), 1);
+
+    assert_eq!(repo.migrate_to_latest(false)?, changes);
+
+    // re-open repository to read the new config
+    let repo = Repository::new(&options, &backends)?.open()?;
+    assert_eq!(repo.repo_version(), 2);
+    assert_eq!(repo.needs_migration(), None);
+    assert!(repo.migrate_to_latest(false)?.is_empty());
+
+    // downgrades are still refused
+    assert!(repo
+        .apply_config(&ConfigOptions::default().set_version(1u32))
+        .is_err());
+
+    Ok(())
+}
+
+/// Write many small similar files, which benefit from a compression dictionary
+fn write_small_files(dir: &Path, prefix: &str) -> Result<()> {
+    for i in 0..2000 {
+        fs::write(
+            dir.join(format!("{prefix}{i}.json")),
+            format!(
+                r#"{{"id":{i},"name":"{prefix} sample {i}","tags":["backup","rustic","sample"],"size":{}}}"#,
+                i * 17
+            ),
+        )?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_compression_dictionary_reads_old_and_new_data() -> Result<()> {
+    let backends = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let options = RepositoryOptions::default().password("test");
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+    let dictionary_opts = ConfigOptions::default()
+        .set_version(3u32)
+        .train_compression_dictionary(true);
+
+    // the dictionary is trained from the repository data, so an empty repository can't train it
+    assert!(repo.apply_config(&dictionary_opts).is_err());
+
+    // data compressed without dictionary
+    let old = tempdir()?;
+    write_small_files(old.path(), "old")?;
+    let repo = repo.to_indexed_ids()?;
+    let old_snap = repo.backup(
+        &BackupOptions::default().as_path(PathBuf::from("old")),
+        &PathList::from_iter(Some(old.path().to_path_buf())),
+        SnapshotFile::default(),
+    )?;
+
+    assert!(repo.apply_config(&dictionary_opts)?);
+    let repo = Repository::new(&options, &backends)?.open()?;
+    assert_eq!(repo.repo_version(), 3);
+    assert!(repo.config().compression_dictionary.is_some());
+
+    // data compressed with dictionary
+    let new = tempdir()?;
+    write_small_files(new.path(), "new")?;
+    let repo = repo.to_indexed_ids()?;
+    let new_snap = repo.backup(
+        &BackupOptions::default().as_path(PathBuf::from("new")),
+        &PathList::from_iter(Some(new.path().to_path_buf())),
+        SnapshotFile::default(),
+    )?;
+
+    // both can be read
+    let repo = repo.to_indexed()?;
+    assert!(repo.check(CheckOptions::default().read_data(true))?.is_ok());
+    for (snap, path, dir) in [
+        (&old_snap, "old/old7.json", old.path().join("old7.json")),
+        (&new_snap, "new/new7.json", new.path().join("new7.json")),
+    ] {
+        let node = repo.node_from_snapshot_path(&format!("{}:{path}", snap.id), |_| true)?;
+        let mut content = Vec::new();
+        repo.dump(&node, &mut content)?;
+        assert_eq!(content, fs::read(dir)?);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,147 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::{BlobType, SnapshotFile},
+    BackupOptions, ConfigOptions, FileType, KeyOptions, ReadBackend, Repository,
+    RepositoryBackends, RepositoryOptions, WriteBackend,
+};
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+fn repo_options() -> RepositoryOptions {
+    RepositoryOptions::default().password("test").no_cache(true)
+}
+
+/// Creates a destination repository which shares key and config with the source
+fn same_key_destination(be: &InMemoryBackend) -> Result<(Arc<InMemoryBackend>, RepoOpen)> {
+    let be_dest = Arc::new(InMemoryBackend::new());
+    for tpe in [FileType::Config, FileType::Key] {
+        for id in be.list(tpe)? {
+            be_dest.write_bytes(tpe, &id, false, be.read_full(tpe, &id)?)?;
+        }
+    }
+    let repo_dest = Repository::new(
+        &repo_options(),
+        &RepositoryBackends::new(be_dest.clone(), None),
+    )?
+    .open()?;
+    Ok((be_dest, repo_dest))
+}
+
+#[rstest]
+fn test_copy_with_same_key_keeps_blobs_passes(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let repo = Repository::new(&repo_options(), &RepositoryBackends::new(be.clone(), None))?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snap = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let repo = repo.to_indexed()?;
+
+    let (be_dest, repo_dest) = same_key_destination(&be)?;
+    let repo_dest = repo_dest.to_indexed_ids()?;
+
+    repo.copy(&repo_dest, [&snap])?;
+    let repo_dest = repo_dest.to_indexed()?;
+
+    let data_id = repo
+        .node_from_path(snap.tree, Path::new("test/0/0/9/0"))?
+        .content
+        .unwrap()[0];
+    let entries = [
+        (
+            repo.get_index_entry(&snap.tree)?,
+            repo_dest.get_index_entry(&snap.tree)?,
+        ),
+        (
+            repo.get_index_entry(&data_id)?,
+            repo_dest.get_index_entry(&data_id)?,
+        ),
+    ];
+    for (entry, entry_dest) in entries {
+        assert_eq!(
+            be.read_partial(
+                FileType::Pack,
+                &entry.pack,
+                false,
+                entry.offset,
+                entry.length
+            )?,
+            be_dest.read_partial(
+                FileType::Pack,
+                &entry_dest.pack,
+                false,
+                entry_dest.offset,
+                entry_dest.length
+            )?
+        );
+    }
+    Ok(())
+}
+
+#[rstest]
+fn test_copy_with_same_key_and_corrupted_blob_fails(
+    tar_gz_testdata: Result<TestSource>,
+) -> Result<()> {
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InMemoryBackend::new());
+    let repo = Repository::new(&repo_options(), &RepositoryBackends::new(be.clone(), None))?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snap = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let repo = repo.to_indexed()?;
+
+    // flip a byte within a data blob which is only read by the raw copy
+    let data_id = repo
+        .node_from_path(snap.tree, Path::new("test/0/0/9/0"))?
+        .content
+        .unwrap()[0];
+    let entry = repo.get_index_entry(&data_id)?;
+    let mut pack = be.read_full(FileType::Pack, &entry.pack)?.to_vec();
+    pack[entry.offset as usize + entry.length as usize / 2] ^= 0xff;
+    be.remove(FileType::Pack, &entry.pack, false)?;
+    be.write_bytes(FileType::Pack, &entry.pack, false, pack.into())?;
+
+    let (_, repo_dest) = same_key_destination(&be)?;
+    let repo_dest = repo_dest.to_indexed_ids()?;
+    assert!(repo.copy(&repo_dest, [&snap]).is_err());
+    Ok(())
+}
+
+#[rstest]
+fn test_copy_with_different_key_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snap = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let repo = repo.to_indexed()?;
+
+    let repo_dest = set_up_repo()?.to_indexed_ids()?;
+    repo.copy(&repo_dest, [&snap])?;
+    let repo_dest = repo_dest.to_indexed()?;
+
+    let data_id = repo
+        .node_from_path(snap.tree, Path::new("test/0/0/9/0"))?
+        .content
+        .unwrap()[0];
+    for (tpe, id) in [
+        (BlobType::Tree, snap.tree.to_string()),
+        (BlobType::Data, data_id.to_string()),
+    ] {
+        assert_eq!(repo.cat_blob(tpe, &id)?, repo_dest.cat_blob(tpe, &id)?);
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::fs;
+
+use anyhow::Result;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use rstest::rstest;
+
+use rustic_core::{repofile::SnapshotFile, BackupOptions};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+fn random_data(seed: u64, len: usize) -> Vec<u8> {
+    let mut data = vec![0; len];
+    StdRng::seed_from_u64(seed).fill_bytes(&mut data);
+    data
+}
+
+#[rstest]
+fn test_would_deduplicate(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let backed_up = random_data(1, 5_000_000);
+    let new = random_data(2, 5_000_000);
+    fs::write(source.0.path().join("backed_up"), &backed_up)?;
+
+    _ = repo.backup(
+        &BackupOptions::default(),
+        &source.path_list(),
+        SnapshotFile::default(),
+    )?;
+    let repo = repo.to_indexed_ids()?;
+
+    let estimate = repo.would_deduplicate(&source.0.path().join("backed_up"))?;
+    assert_eq!(estimate.size, backed_up.len() as u64);
+    assert!(estimate.chunks > 1);
+    assert_eq!(estimate.existing_chunks, estimate.chunks);
+    assert_eq!(estimate.existing_size, estimate.size);
+    assert_eq!(estimate.new_size, 0);
+
+    let path = source.0.path().join("new");
+    fs::write(&path, [new.as_slice(), backed_up.as_slice()].concat())?;
+    let estimate = repo.would_deduplicate(&path)?;
+    assert_eq!(estimate.size, (new.len() + backed_up.len()) as u64);
+    assert_eq!(
+        estimate.chunks,
+        estimate.existing_chunks + estimate.new_chunks
+    );
+    assert!(estimate.new_size >= new.len() as u64);
+    assert!(estimate.existing_size > 0);
+
+    Ok(())
+}
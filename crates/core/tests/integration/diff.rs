@@ -0,0 +1,95 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+use rustic_core::{repofile::SnapshotFile, BackupOptions, DiffKind, DiffOptions};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_diff_snapshots_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("other")?);
+    let second_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // identical snapshots don't differ
+    let diff = repo.diff_snapshots(
+        &first_snapshot,
+        &first_snapshot,
+        DiffOptions::default().content(true),
+    )?;
+    assert!(diff.added.is_empty());
+    assert!(diff.removed.is_empty());
+    assert!(diff.modified.is_empty());
+    assert!(diff.stats.unchanged > 0);
+
+    // moving all contents to another path removes and adds every entry
+    let diff = repo.diff_snapshots(&first_snapshot, &second_snapshot, DiffOptions::default())?;
+    assert_eq!(diff.stats.unchanged, 0);
+    assert_eq!(diff.stats.modified, 0);
+    assert_eq!(diff.stats.added, diff.stats.removed);
+    assert!(diff
+        .removed
+        .iter()
+        .all(|(path, _)| path.starts_with("test")));
+    assert!(diff.added.iter().all(|(path, _)| path.starts_with("other")));
+
+    Ok(())
+}
+
+#[rstest]
+fn test_diff_snapshots_streaming_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("other")?);
+    let second_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // identical snapshots don't differ
+    assert_eq!(
+        repo.diff_snapshots_streaming(&first_snapshot, &first_snapshot, DiffOptions::default())?
+            .count(),
+        0
+    );
+
+    // the streamed entries match the collected differences
+    let diff = repo.diff_snapshots(&first_snapshot, &second_snapshot, DiffOptions::default())?;
+    let entries = repo
+        .diff_snapshots_streaming(&first_snapshot, &second_snapshot, DiffOptions::default())?
+        .collect::<Result<Vec<_>, _>>()?;
+    let paths = |kind| {
+        entries
+            .iter()
+            .filter(|entry| entry.kind == kind)
+            .map(|entry| entry.path.clone())
+            .collect::<Vec<_>>()
+    };
+    let removed: Vec<_> = diff.removed.into_iter().map(|(path, _)| path).collect();
+    let added: Vec<_> = diff.added.into_iter().map(|(path, _)| path).collect();
+    assert_eq!(paths(DiffKind::Removed), removed);
+    assert_eq!(paths(DiffKind::Added), added);
+    assert!(paths(DiffKind::Modified).is_empty());
+    assert!(entries
+        .iter()
+        .all(|entry| entry.old.is_some() != entry.new.is_some()));
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{Cursor, Read},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use anyhow::Result;
+use rstest::rstest;
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+
+use rustic_core::{
+    repofile::{Metadata, Node, NodeType, SnapshotFile},
+    BackupOptions, DumpFormat, LsOptions, RusticResult,
+};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_dump_tree_as_tar(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let mut archive = Vec::new();
+    repo.dump_tree(&node, DumpFormat::Tar, &mut archive)?;
+
+    let nodes: Vec<_> = repo
+        .ls(&node, &LsOptions::default())?
+        .filter(|item| {
+            item.as_ref().map_or(true, |(_, node)| {
+                node.is_dir() || node.is_file() || node.is_symlink()
+            })
+        })
+        .collect::<RusticResult<_>>()?;
+
+    let mut archive = Archive::new(Cursor::new(archive));
+    let mut count = 0;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let (_, node) = nodes
+            .iter()
+            .find(|(p, _)| p == &path)
+            .expect("archive entry should be in the snapshot");
+        match entry.header().entry_type() {
+            EntryType::Directory => assert!(node.is_dir()),
+            EntryType::Symlink => assert!(node.is_symlink()),
+            EntryType::Regular => {
+                assert!(node.is_file());
+                let mut content = Vec::new();
+                _ = entry.read_to_end(&mut content)?;
+                let expected = fs::read(source.0.path().join(path.strip_prefix("test")?))?;
+                assert_eq!(content, expected, "content of {path:?} differs");
+            }
+            tpe => panic!("unexpected entry type {tpe:?}"),
+        }
+        count += 1;
+    }
+    assert_eq!(count, nodes.len());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_dump_tree_as_zip(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let mut archive = Vec::new();
+    repo.dump_tree(&node, DumpFormat::Zip, &mut archive)?;
+
+    let nodes: Vec<_> = repo
+        .ls(&node, &LsOptions::default())?
+        .filter(|item| {
+            item.as_ref().map_or(true, |(_, node)| {
+                node.is_dir() || node.is_file() || node.is_symlink()
+            })
+        })
+        .collect::<RusticResult<_>>()?;
+
+    let mut archive = ZipArchive::new(Cursor::new(archive))?;
+    assert_eq!(archive.len(), nodes.len());
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = PathBuf::from(file.name().trim_end_matches('/'));
+        let (_, node) = nodes
+            .iter()
+            .find(|(p, _)| p == &path)
+            .expect("archive entry should be in the snapshot");
+        let mut content = Vec::new();
+        _ = file.read_to_end(&mut content)?;
+        if file.is_dir() {
+            assert!(node.is_dir());
+        } else if file
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170_000 == 0o120_000)
+        {
+            assert!(node.is_symlink());
+            assert_eq!(
+                content,
+                node.node_type.to_link().to_string_lossy().as_bytes()
+            );
+        } else {
+            assert!(node.is_file());
+            let expected = fs::read(source.0.path().join(path.strip_prefix("test")?))?;
+            assert_eq!(content, expected, "content of {path:?} differs");
+        }
+    }
+
+    Ok(())
+}
@@ -59,3 +59,42 @@ fn test_find(tar_gz_testdata: Result<TestSource>, set_up_repo: Result<RepoOpen>)
     assert_with_win("find-matching-wildcard-existing", (paths, matches));
     Ok(())
 }
+
+#[rstest]
+fn test_find_glob(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+    let trees = vec![snapshot.tree, snapshot.tree];
+
+    // the same matches as with a custom matching function
+    let glob = Glob::new("testfile*")?.compile_matcher();
+    let match_func = |path: &Path, _: &Node| path.file_name().is_some_and(|f| glob.is_match(f));
+    let expected = repo.find_matching_nodes(trees.clone(), &match_func)?;
+    let found = repo.find_nodes_matching_glob(trees.clone(), &["testfile*".to_string()], false)?;
+    assert!(!found.paths.is_empty());
+    assert_eq!(found.paths, expected.paths);
+    assert_eq!(found.matches, expected.matches);
+    assert_eq!(found.matches[0], found.matches[1]);
+
+    // matching is case-sensitive unless requested
+    let patterns = ["TESTFILE*".to_string()];
+    let found = repo.find_nodes_matching_glob(trees.clone(), &patterns, false)?;
+    assert!(found.paths.is_empty());
+    let found = repo.find_nodes_matching_glob(trees.clone(), &patterns, true)?;
+    assert_eq!(found.paths, expected.paths);
+
+    // invalid patterns are rejected
+    assert!(repo
+        .find_nodes_matching_glob(trees, &["a[".to_string()], false)
+        .is_err());
+
+    Ok(())
+}
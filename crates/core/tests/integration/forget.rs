@@ -0,0 +1,208 @@
+use std::{path::PathBuf, str::FromStr, sync::Arc};
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::SnapshotFile, BackupOptions, CheckOptions, ConfigOptions, KeepOptions, KeyOptions,
+    LimitOption, PathList, PruneOptions, Repository, RepositoryBackends, RepositoryOptions,
+    SnapshotGroupCriterion, StringList,
+};
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_forget_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let second_snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let keep = KeepOptions::default().keep_last(1);
+    let forgotten = repo.forget(&keep, SnapshotGroupCriterion::default(), |_| true)?;
+    assert_eq!(forgotten, vec![first_snapshot.id]);
+
+    let snapshots = repo.get_all_snapshots()?;
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].id, second_snapshot.id);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_forget_keeping_nothing_fails(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // no snapshot has this tag, so all snapshots would be forgotten
+    let keep = KeepOptions::default().keep_tags(vec![StringList::from_str("unused")?]);
+    let groups = repo.get_forget_snapshots(&keep, SnapshotGroupCriterion::default(), |_| true)?;
+    let nothing = groups.groups_keeping_nothing();
+    assert_eq!(nothing.len(), 1);
+    assert_eq!(nothing[0].snapshots.len(), 2);
+
+    assert!(repo
+        .forget(&keep, SnapshotGroupCriterion::default(), |_| true)
+        .is_err());
+    assert!(repo
+        .forget_and_prune(
+            &keep,
+            SnapshotGroupCriterion::default(),
+            |_| true,
+            &PruneOptions::default()
+        )
+        .is_err());
+    assert_eq!(repo.get_all_snapshots()?.len(), 2);
+    assert_eq!(repo.count_snapshots()?, 2);
+
+    // with `keep_none`, forgetting all snapshots is allowed
+    let keep = keep.keep_none(true);
+    let forgotten = repo.forget(&keep, SnapshotGroupCriterion::default(), |_| true)?;
+    assert_eq!(forgotten.len(), 2);
+    assert!(repo.get_all_snapshots()?.is_empty());
+    assert_eq!(repo.count_snapshots()?, 0);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_forget_append_only_fails(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let options = RepositoryOptions::default().password("test");
+    let config_opts = ConfigOptions::default().set_append_only(true);
+    let repo = Repository::new(&options, &be)?
+        .init(&KeyOptions::default(), &config_opts)?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // the plan can still be computed
+    let keep = KeepOptions::default().keep_last(1);
+    let groups = repo.get_forget_snapshots(&keep, SnapshotGroupCriterion::default(), |_| true)?;
+    assert_eq!(groups.into_forget_ids().len(), 1);
+
+    // but nothing is removed
+    assert!(repo
+        .forget(&keep, SnapshotGroupCriterion::default(), |_| true)
+        .is_err());
+    assert_eq!(repo.get_all_snapshots()?.len(), 2);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_forget_and_prune_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+
+    // use the same path in the snapshots to get a single group
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let paths = PathList::from_iter(Some(source.0.path().join("0/0/9")));
+    let first_snapshot = repo.backup(&opts, &paths, SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+    let paths = PathList::from_iter(Some(source.0.path().join("0/0/9/2")));
+    let second_snapshot = repo.backup(&opts, &paths, SnapshotFile::default())?;
+    let repo = repo.to_open();
+
+    let keep = KeepOptions::default().keep_last(1);
+    let prune_opts = PruneOptions::default()
+        .instant_delete(true)
+        .max_repack(LimitOption::Unlimited)
+        .max_unused(LimitOption::Percentage(0));
+
+    // dry-run reports both plans, but doesn't modify the repository
+    let result = repo.forget_and_prune_dry_run(
+        &keep,
+        SnapshotGroupCriterion::default(),
+        |_| true,
+        &prune_opts,
+    )?;
+    assert_eq!(result.forget_ids, vec![first_snapshot.id]);
+    assert!(result.prune_stats.blobs_sum().unused > 0);
+    assert_eq!(repo.get_all_snapshots()?.len(), 2);
+
+    let result = repo.forget_and_prune(
+        &keep,
+        SnapshotGroupCriterion::default(),
+        |_| true,
+        &prune_opts,
+    )?;
+    assert_eq!(result.forget_ids, vec![first_snapshot.id]);
+
+    let snapshots = repo.get_all_snapshots()?;
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].id, second_snapshot.id);
+
+    // the data of the forgotten snapshot has been removed
+    _ = repo
+        .check(CheckOptions::default().read_data(true))?
+        .into_result()?;
+    let plan = repo.prune_plan(&prune_opts)?;
+    assert_eq!(plan.stats.blobs_sum().unused, 0);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_forget_and_prune_append_only_fails(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let options = RepositoryOptions::default().password("test");
+    let config_opts = ConfigOptions::default().set_append_only(true);
+    let repo = Repository::new(&options, &be)?
+        .init(&KeyOptions::default(), &config_opts)?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // the plans can still be computed
+    let keep = KeepOptions::default().keep_last(1);
+    let result = repo.forget_and_prune_dry_run(
+        &keep,
+        SnapshotGroupCriterion::default(),
+        |_| true,
+        &PruneOptions::default(),
+    )?;
+    assert_eq!(result.forget_ids.len(), 1);
+
+    // but nothing is removed
+    assert!(repo
+        .forget_and_prune(
+            &keep,
+            SnapshotGroupCriterion::default(),
+            |_| true,
+            &PruneOptions::default()
+        )
+        .is_err());
+    assert_eq!(repo.get_all_snapshots()?.len(), 2);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::{IndexId, SnapshotFile},
+    BackupOptions, ConfigOptions, FileType, KeyOptions, PathList, Repository, RepositoryBackends,
+    RepositoryOptions,
+};
+use rustic_testing::backend::{
+    in_memory_backend::InMemoryBackend, instrumented_backend::InstrumentedBackend,
+};
+
+use super::{tar_gz_testdata, TestSource};
+
+#[rstest]
+fn test_refresh_index_only_reads_new_index_files(
+    tar_gz_testdata: Result<TestSource>,
+) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InstrumentedBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test").no_cache(true);
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+
+    let opts = BackupOptions::default();
+    let backup = |path: &str| PathList::from_iter(Some(source.0.path().join(path)));
+    _ = repo.backup(&opts, &backup("0/0/9/2"), SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+    _ = repo.backup(&opts, &backup("0/0/9/3"), SnapshotFile::default())?;
+
+    // written index files are read back for verification
+    _ = be.take_reads(FileType::Index);
+    let mut repo = repo.to_indexed()?;
+    assert_eq!(
+        be.take_reads(FileType::Index),
+        repo.list::<IndexId>()?.count()
+    );
+
+    // nothing changed
+    repo.refresh_index()?;
+    assert_eq!(be.take_reads(FileType::Index), 0);
+
+    // a backup adds one index file which is the only one read
+    let snapshot = repo.backup(&opts, &backup("0/0/9"), SnapshotFile::default())?;
+    _ = be.take_reads(FileType::Index);
+    repo.refresh_index()?;
+    assert_eq!(be.take_reads(FileType::Index), 1);
+    _ = repo.get_index_entry(&snapshot.tree)?;
+
+    Ok(())
+}
+
+#[rstest]
+fn test_export_import_index(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InstrumentedBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test").no_cache(true);
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+
+    let paths = PathList::from_iter(Some(source.0.path().to_path_buf()));
+    let snapshot = repo.backup(&BackupOptions::default(), &paths, SnapshotFile::default())?;
+
+    let repo = repo.to_indexed()?;
+    let mut exported = Vec::new();
+    repo.export_index(&mut exported)?;
+    _ = be.take_reads(FileType::Index);
+
+    // importing the index doesn't read any index file
+    let repo = Repository::new(&options, &backends)?
+        .open()?
+        .import_index(&mut exported.as_slice())?;
+    assert_eq!(be.take_reads(FileType::Index), 0);
+    let entry = repo.get_index_entry(&snapshot.tree)?;
+    assert_eq!(entry, repo.to_indexed()?.get_index_entry(&snapshot.tree)?);
+
+    // the exported index can't be decrypted by a repository using another key;
+    // a mismatching repository id is checked in the unit tests of `GlobalIndex`
+    let other_backends = RepositoryBackends::new(Arc::new(InMemoryBackend::new()), None);
+    let other = Repository::new(&options, &other_backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+    assert!(other.import_index(&mut exported.as_slice()).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use bytes::Bytes;
+use chrono::Duration;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::SnapshotFile, ConfigOptions, ErrorKind, FileType, Id, KeyOptions, Repository,
+    RepositoryBackends, RepositoryOptions, WriteBackend,
+};
+use rustic_testing::backend::in_memory_backend::InMemoryBackend;
+use secrecy::SecretString;
+
+use super::{set_up_repo, RepoOpen};
+
+#[rstest]
+fn test_list_keys_and_verify_password(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    let repo = set_up_repo?;
+
+    let keys = repo.list_keys()?;
+    assert_eq!(keys.len(), 1);
+    let initial = keys[0].id;
+
+    let key_opts = KeyOptions::default()
+        .hostname("host".to_string())
+        .with_created(true);
+    let added = repo.add_key("other", &key_opts)?;
+
+    let keys = repo.list_keys()?;
+    assert_eq!(keys.len(), 2);
+    let info = keys.iter().find(|key| key.id == added).unwrap();
+    assert_eq!(info.hostname.as_deref(), Some("host"));
+    assert!(info.created.is_some());
+
+    assert_eq!(repo.verify_password("test")?, Some(initial));
+    assert_eq!(repo.verify_password("other")?, Some(added));
+    assert_eq!(repo.verify_password("wrong")?, None);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_keys_older_than_and_delete_keys(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    let repo = set_up_repo?;
+    let current = *repo.key_id();
+
+    let key_opts = KeyOptions::default().with_created(true);
+    let added = repo.add_key("other", &key_opts)?;
+
+    // the initial key has no creation time
+    let old_keys = repo.keys_older_than(Duration::zero())?;
+    assert_eq!(old_keys.len(), 1);
+    assert_eq!(old_keys[0].id, added);
+    assert!(repo.keys_older_than(Duration::days(365))?.is_empty());
+
+    // the key used to open the repository can't be removed
+    assert!(repo.delete_keys_dry_run(&[current]).is_err());
+    assert!(repo.delete_keys(&[current, added]).is_err());
+
+    let to_delete = repo.delete_keys_dry_run(&[added])?;
+    assert_eq!(to_delete.len(), 1);
+    assert_eq!(to_delete[0].id, added);
+    assert_eq!(repo.list_keys()?.len(), 2);
+
+    let deleted = repo.delete_keys(&[added])?;
+    assert_eq!(deleted, to_delete);
+    let keys = repo.list_keys()?;
+    assert_eq!(keys.len(), 1);
+    assert_eq!(keys[0].id, current);
+
+    // the key is not found anymore
+    assert!(repo.delete_keys(&[added]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_open_with_wrong_password_is_distinguished() -> Result<()> {
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    _ = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+
+    let options = RepositoryOptions::default().password("wrong");
+    let err = Repository::new(&options, &backends)?.open().unwrap_err();
+    assert!(err.is_incorrect_password());
+    assert_eq!(err.kind(), ErrorKind::IncorrectPassword);
+
+    // with an unreadable key file, a wrong password can't be detected for sure
+    be.write_bytes(
+        FileType::Key,
+        &Id::random(),
+        false,
+        Bytes::from_static(b"no key"),
+    )?;
+    let err = Repository::new(&options, &backends)?.open().unwrap_err();
+    assert!(!err.is_incorrect_password());
+    assert_eq!(err.kind(), ErrorKind::Key);
+
+    // the correct password still opens the repository
+    let options = RepositoryOptions::default().password("test");
+    _ = Repository::new(&options, &backends)?.open()?;
+
+    Ok(())
+}
+
+#[test]
+fn test_open_with_prompt_retries_incorrect_password() -> Result<()> {
+    let be = Arc::new(InMemoryBackend::new());
+    let backends = RepositoryBackends::new(be, None);
+    let options = RepositoryOptions::default().password("test");
+    _ = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?;
+
+    let mut passwords = vec!["test", "wrong"];
+    let repo = Repository::new(&RepositoryOptions::default(), &backends)?
+        .open_with_prompt(|| Ok(SecretString::from(passwords.pop().unwrap())))?;
+    assert!(passwords.is_empty());
+    assert_eq!(repo.verify_password("test")?, Some(*repo.key_id()));
+
+    let mut prompts = 0;
+    let err = Repository::new(&RepositoryOptions::default(), &backends)?
+        .open_with_prompt(|| {
+            prompts += 1;
+            Ok(SecretString::from("wrong"))
+        })
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IncorrectPassword);
+    assert_eq!(prompts, 3);
+
+    // a password given by the options is used without prompting
+    let repo = Repository::new(&options, &backends)?
+        .open_with_prompt(|| panic!("prompt should not be called"))?;
+    assert_eq!(repo.verify_password("test")?, Some(*repo.key_id()));
+
+    Ok(())
+}
+
+#[rstest]
+#[case(false)]
+#[case(true)]
+fn test_open_or_init(#[case] hot: bool) -> Result<()> {
+    let be = Arc::new(InMemoryBackend::new());
+    let be_hot = hot.then(|| Arc::new(InMemoryBackend::new()) as Arc<dyn WriteBackend>);
+    let backends = RepositoryBackends::new(be, be_hot);
+    let options = RepositoryOptions::default().password("test");
+    let key_opts = KeyOptions::default();
+    let config_opts = ConfigOptions::default();
+
+    // the repository is initialized
+    let repo = Repository::new(&options, &backends)?.open_or_init(&key_opts, &config_opts)?;
+    let repo_id = repo.config().id;
+    repo.save_snapshots(vec![SnapshotFile::default()])?;
+
+    // the existing repository is opened
+    let repo = Repository::new(&options, &backends)?.open_or_init(&key_opts, &config_opts)?;
+    assert_eq!(repo.config().id, repo_id);
+    assert_eq!(repo.get_all_snapshots()?.len(), 1);
+
+    // an existing repository is not touched if the password is incorrect
+    let wrong = RepositoryOptions::default().password("wrong");
+    let err = Repository::new(&wrong, &backends)?
+        .open_or_init(&key_opts, &config_opts)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::IncorrectPassword);
+    let repo = Repository::new(&options, &backends)?.open()?;
+    assert_eq!(repo.config().id, repo_id);
+    assert_eq!(repo.list_keys()?.len(), 1);
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::Result;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::SnapshotFile, BackupOptions, KeepOptions, PruneOptions, SnapshotGroupCriterion,
+};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_lock_and_break_locks(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    let repo = set_up_repo?;
+    assert!(repo.list_locks()?.is_empty());
+
+    let lock = repo.lock_exclusive()?;
+    assert!(lock.is_exclusive());
+    let locks = repo.list_locks()?;
+    assert_eq!(locks.len(), 1);
+    let (_, lock_file) = &locks[0];
+    assert!(lock_file.exclusive);
+    assert!(lock_file.is_own());
+    assert!(!lock_file.is_stale());
+
+    // locks of the own process conflict, too
+    assert!(repo.lock_exclusive().is_err());
+    assert!(repo.lock_shared().is_err());
+    // the lock files of the failed attempts are removed again
+    assert_eq!(repo.list_locks()?.len(), 1);
+
+    // dropping removes the lock
+    drop(lock);
+    assert!(repo.list_locks()?.is_empty());
+
+    // shared locks don't conflict with each other, but with exclusive ones
+    let shared = repo.lock_shared()?;
+    assert!(!shared.is_exclusive());
+    let other_shared = repo.lock_shared()?;
+    assert_eq!(repo.list_locks()?.len(), 2);
+    assert!(repo.lock_exclusive().is_err());
+    drop(other_shared);
+    assert_eq!(repo.list_locks()?.len(), 1);
+
+    // the lock is not stale, so only breaking all locks removes it
+    assert!(repo.break_locks(true)?.is_empty());
+    assert_eq!(repo.break_locks(false)?.len(), 1);
+    assert!(repo.list_locks()?.is_empty());
+    drop(shared);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_destructive_operations_release_locks(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    repo.delete_snapshots(&[snapshot.id])?;
+    assert!(repo.get_all_snapshots()?.is_empty());
+    assert!(repo.list_locks()?.is_empty());
+
+    // computing the prune plan doesn't lock, only executing it
+    let repo = repo.to_open();
+    let prune_opts = PruneOptions::default();
+    let plan = repo.prune_plan(&prune_opts)?;
+    assert!(repo.list_locks()?.is_empty());
+    let lock = repo.lock_shared()?;
+    assert!(repo
+        .prune(&prune_opts, repo.prune_plan(&prune_opts)?)
+        .is_err());
+    drop(lock);
+    repo.prune(&prune_opts, plan)?;
+    assert!(repo.list_locks()?.is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_snapshot_operations_work_while_holding_a_lock(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let lock = repo.lock_exclusive()?;
+    // deleting snapshots on its own takes a lock, which conflicts
+    assert!(repo.delete_snapshots(&[snapshot.id]).is_err());
+
+    let mut without_summary = snapshot.clone();
+    without_summary.summary = None;
+    repo.save_snapshots(vec![without_summary])?;
+    let without_summary = repo
+        .get_all_snapshots()?
+        .into_iter()
+        .find(|snap| snap.summary.is_none())
+        .unwrap();
+    let backfilled = repo.backfill_summary(&without_summary)?;
+    assert!(backfilled.summary.is_some());
+
+    let keep = KeepOptions::default().keep_none(true);
+    let forgotten = repo.forget(&keep, SnapshotGroupCriterion::default(), |_| true)?;
+    assert_eq!(forgotten.len(), 2);
+    assert!(repo.get_all_snapshots()?.is_empty());
+    drop(lock);
+    assert!(repo.list_locks()?.is_empty());
+
+    Ok(())
+}
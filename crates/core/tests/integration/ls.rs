@@ -1,14 +1,21 @@
 use std::{collections::BTreeMap, ffi::OsStr};
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
 
 use anyhow::Result;
+use bytesize::ByteSize;
 use insta::Settings;
 use rstest::rstest;
 
 use rustic_core::{
-    repofile::{Metadata, Node, SnapshotFile},
-    BackupOptions, LsOptions, RusticResult,
+    repofile::{Metadata, Node, NodeType, SnapshotFile},
+    BackupOptions, ConfigOptions, FileType, FilterRule, KeyOptions, LsOptions, Repository,
+    RepositoryBackends, RepositoryOptions, RusticResult, TreeVisitor, WalkAction,
 };
+use rustic_testing::backend::instrumented_backend::InstrumentedBackend;
 
 use super::{
     assert_with_win, insta_node_redaction, set_up_repo, tar_gz_testdata, RepoOpen, TestSource,
@@ -50,3 +57,166 @@ fn test_ls(
 
     Ok(())
 }
+
+#[rstest]
+fn test_ls_with_size_and_type_filter(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed_ids()?;
+
+    let all: Vec<_> = repo
+        .ls(&node, &LsOptions::default())?
+        .collect::<RusticResult<_>>()?;
+
+    let min_size = 1024;
+    let ls_opts = LsOptions::default()
+        .min_size(ByteSize(min_size))
+        .node_types(vec![NodeType::File]);
+    let entries: Vec<_> = repo.ls(&node, &ls_opts)?.collect::<RusticResult<_>>()?;
+
+    // nested files are found, so directories have been descended into
+    let expected: Vec<_> = all
+        .into_iter()
+        .filter(|(_, node)| node.is_file() && node.meta.size >= min_size)
+        .collect();
+    assert!(!expected.is_empty());
+    assert_eq!(entries, expected);
+
+    Ok(())
+}
+
+#[rstest]
+#[case(&["-*", "+test/0/tests/**"], |path| path.starts_with("test/0/tests") && path != Path::new("test/0/tests"))]
+#[case(&["+test/0/tests/**", "-*"], |_| false)]
+#[case(&["-testfile*"], |path| !name_starts_with(path, "testfile"))]
+#[case(&["-testfile*", "+ testfile"], |path| path.file_name() == Some(OsStr::new("testfile")) || !name_starts_with(path, "testfile"))]
+#[case(&["+testfile", "-testfile*"], |path| !name_starts_with(path, "testfile"))]
+fn test_ls_with_filter_rules(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+    #[case] rules: &[&str],
+    #[case] expected: fn(&Path) -> bool,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed_ids()?;
+
+    let all: Vec<_> = repo
+        .ls(&node, &LsOptions::default())?
+        .collect::<RusticResult<_>>()?;
+
+    let rules = rules
+        .iter()
+        .map(|rule| FilterRule::from_str(rule))
+        .collect::<RusticResult<Vec<_>>>()?;
+    let ls_opts = LsOptions::default().filter_rules(rules);
+    let entries: Vec<_> = repo.ls(&node, &ls_opts)?.collect::<RusticResult<_>>()?;
+
+    let expected: Vec<_> = all.into_iter().filter(|(path, _)| expected(path)).collect();
+    assert_eq!(entries, expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_filter_rules_fail() {
+    assert!(FilterRule::from_str("testfile").is_err());
+    assert!(FilterRule::from_str("-test[").is_err());
+    assert_eq!(
+        FilterRule::from_str("-*.tmp").unwrap(),
+        FilterRule::exclude("*.tmp")
+    );
+}
+
+fn name_starts_with(path: &Path, prefix: &str) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with(prefix))
+}
+
+/// A visitor recording all visited paths and returning the given action for directories
+struct RecordingVisitor {
+    dir_action: WalkAction,
+    paths: Vec<PathBuf>,
+}
+
+impl TreeVisitor for RecordingVisitor {
+    fn visit_dir(&mut self, path: &Path, _node: &Node) -> RusticResult<WalkAction> {
+        self.paths.push(path.to_path_buf());
+        Ok(self.dir_action)
+    }
+
+    fn visit_file(&mut self, path: &Path, _node: &Node) -> RusticResult<WalkAction> {
+        self.paths.push(path.to_path_buf());
+        Ok(WalkAction::Continue)
+    }
+}
+
+#[rstest]
+fn test_walk_snapshot(tar_gz_testdata: Result<TestSource>) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    let be = Arc::new(InstrumentedBackend::new());
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test").no_cache(true);
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &ConfigOptions::default())?
+        .to_indexed_ids()?;
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let entries: Vec<_> = repo
+        .ls(&node, &LsOptions::default())?
+        .collect::<RusticResult<_>>()?;
+    let dirs = entries.iter().filter(|(_, node)| node.is_dir()).count();
+    let entries: Vec<_> = entries.into_iter().map(|(path, _)| path).collect();
+
+    // walking everything visits the same nodes as ls and loads each tree once
+    let mut visitor = RecordingVisitor {
+        dir_action: WalkAction::Continue,
+        paths: Vec::new(),
+    };
+    _ = be.take_reads(FileType::Pack);
+    repo.walk_snapshot(&snapshot, &mut visitor)?;
+    assert_eq!(visitor.paths, entries);
+    assert_eq!(be.take_reads(FileType::Pack), dirs + 1);
+
+    // skipping subtrees doesn't load them
+    let mut visitor = RecordingVisitor {
+        dir_action: WalkAction::SkipSubtree,
+        paths: Vec::new(),
+    };
+    repo.walk_snapshot(&snapshot, &mut visitor)?;
+    assert_eq!(visitor.paths, [PathBuf::from("test")]);
+    assert_eq!(be.take_reads(FileType::Pack), 1);
+
+    // stopping ends the walk at the first directory
+    let mut visitor = RecordingVisitor {
+        dir_action: WalkAction::Stop,
+        paths: Vec::new(),
+    };
+    repo.walk_snapshot(&snapshot, &mut visitor)?;
+    assert_eq!(visitor.paths, [PathBuf::from("test")]);
+
+    Ok(())
+}
@@ -0,0 +1,129 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+use rustic_core::{
+    last_modified_node,
+    repofile::{SnapshotFile, SnapshotSummary},
+    BackupOptions, CheckOptions, PathList, SnapshotOptions,
+};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_merge_reports_conflicts_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first_snapshot = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+
+    // backup a single file as `test/0` which is a dir in the first snapshot
+    let file = PathList::from_iter(Some(source.0.path().join("0/0/9/0")));
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test/0")?);
+    let second_snapshot = repo.backup(&opts, &file, SnapshotFile::default())?;
+
+    // merging identical snapshots doesn't give conflicts
+    let mut conflicts = Vec::new();
+    let snaps = [first_snapshot.clone(), first_snapshot.clone()];
+    let merged = repo.merge_snapshots_with_conflicts(
+        &snaps,
+        &last_modified_node,
+        SnapshotFile::default(),
+        &mut conflicts,
+    )?;
+    assert!(conflicts.is_empty());
+    assert_eq!(merged.tree, first_snapshot.tree);
+
+    // the conflicting file is reported
+    let trees = [first_snapshot.tree, second_snapshot.tree];
+    let mut summary = SnapshotSummary::default();
+    let tree =
+        repo.merge_trees_with_conflicts(&trees, &last_modified_node, &mut summary, &mut conflicts)?;
+    assert_eq!(conflicts.len(), 1);
+    let conflict = &conflicts[0];
+    assert_eq!(conflict.path, PathBuf::from("test/0"));
+    let mut indices = conflict.losers.clone();
+    indices.push(conflict.winner);
+    indices.sort_unstable();
+    assert_eq!(indices, vec![0, 1]);
+
+    // reporting conflicts doesn't change the result
+    let mut summary = SnapshotSummary::default();
+    assert_eq!(
+        repo.merge_trees(&trees, &last_modified_node, &mut summary)?,
+        tree
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn test_rewrite_snapshot_paths_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, &source.path_list(), SnapshotFile::default())?;
+    let repo = repo.to_indexed_ids()?;
+
+    let mappings = [
+        (PathBuf::from("test/0/0/9"), PathBuf::from("moved/nine")),
+        (PathBuf::from("test/0/tests"), PathBuf::from("test/tests")),
+    ];
+    let rewritten =
+        repo.rewrite_snapshot_paths(&snapshot, &mappings, &SnapshotOptions::default())?;
+    assert_eq!(rewritten.original, Some(snapshot.id));
+    assert_eq!(rewritten.paths, snapshot.paths);
+
+    // the moved subtrees are reused
+    for (src, dst) in &mappings {
+        let old = repo.node_from_path(snapshot.tree, src)?;
+        let new = repo.node_from_path(rewritten.tree, dst)?;
+        assert_eq!(new.subtree, old.subtree);
+        assert_eq!(new.meta, old.meta);
+        assert!(repo.node_from_path(rewritten.tree, src).is_err());
+    }
+    // untouched parts of the tree stay available
+    _ = repo.node_from_path(rewritten.tree, Path::new("test/0/0"))?;
+    _ = repo.check(CheckOptions::default())?.into_result()?;
+
+    // colliding destinations are rejected
+    let colliding = [
+        (PathBuf::from("test/0/0/9"), PathBuf::from("moved")),
+        (PathBuf::from("test/0/tests"), PathBuf::from("moved/tests")),
+    ];
+    assert!(repo
+        .rewrite_snapshot_paths(&snapshot, &colliding, &SnapshotOptions::default())
+        .is_err());
+    // existing destinations are rejected
+    let existing = [(PathBuf::from("test/0/0/9"), PathBuf::from("test/0/tests"))];
+    assert!(repo
+        .rewrite_snapshot_paths(&snapshot, &existing, &SnapshotOptions::default())
+        .is_err());
+    // missing sources are rejected
+    let missing = [(PathBuf::from("test/missing"), PathBuf::from("moved"))];
+    assert!(repo
+        .rewrite_snapshot_paths(&snapshot, &missing, &SnapshotOptions::default())
+        .is_err());
+    // rewriting conflicts with an exclusive lock
+    let lock = repo.lock_exclusive()?;
+    assert!(repo
+        .rewrite_snapshot_paths(&snapshot, &mappings, &SnapshotOptions::default())
+        .is_err());
+    drop(lock);
+    assert!(repo.list_locks()?.is_empty());
+
+    Ok(())
+}
@@ -4,7 +4,8 @@ use anyhow::Result;
 use rstest::rstest;
 
 use rustic_core::{
-    repofile::SnapshotFile, BackupOptions, CheckOptions, LimitOption, PathList, PruneOptions,
+    repofile::{PackId, SnapshotFile},
+    BackupOptions, CheckOptions, LimitOption, PackDecisionReason, PackToDo, PathList, PruneOptions,
 };
 
 use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
@@ -43,7 +44,7 @@ fn test_prune(
     let _ = repo.backup(&opts, &paths, SnapshotFile::default())?;
 
     // drop index
-    let repo = repo.drop_index();
+    let repo = repo.to_open();
     repo.delete_snapshots(&[snapshot1.id])?;
 
     // get prune plan
@@ -58,14 +59,71 @@ fn test_prune(
 
     // run check
     let check_opts = CheckOptions::default().read_data(true);
-    repo.check(check_opts)?;
+    _ = repo.check(check_opts)?.into_result()?;
 
     if !instant_delete {
         // re-run if we only marked pack files. As keep-delete = 0, they should be removed here
         let plan = repo.prune_plan(&prune_opts)?;
         repo.prune(&prune_opts, plan)?;
-        repo.check(check_opts)?;
+        _ = repo.check(check_opts)?.into_result()?;
     }
 
     Ok(())
 }
+
+#[rstest]
+fn test_prune_repack_small_only(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+
+    // create some small packs by doing several backups
+    let opts = BackupOptions::default();
+    for path in ["0/0/9/2", "0/0/9/3", "0/0/9"] {
+        let paths = PathList::from_iter(Some(source.0.path().join(path)));
+        let _ = repo.backup(&opts, &paths, SnapshotFile::default())?;
+    }
+    let repo = repo.to_open();
+    let packs_before = repo.list::<PackId>()?.count();
+
+    let prune_opts = PruneOptions::default()
+        .repack_small_only(true)
+        .instant_delete(true);
+    let plan = repo.prune_plan(&prune_opts)?;
+    // all packs of the test repository are too small and are consolidated
+    assert_eq!(plan.stats.packs.repack, u64::try_from(packs_before)?);
+    assert_eq!(plan.stats.packs.keep, 0);
+    assert_eq!(plan.repack_packs().len(), packs_before);
+
+    // the decisions list each pack with a reason and can be serialized
+    let decisions = plan.pack_decisions();
+    assert_eq!(decisions.len(), packs_before);
+    for decision in decisions {
+        assert_eq!(decision.todo, PackToDo::Repack);
+        assert_eq!(decision.reason, PackDecisionReason::SizeMismatch);
+        assert!(plan.repack_packs().contains(&decision.id));
+    }
+    let json = serde_json::to_value(decisions)?;
+    assert_eq!(json[0]["reason"], "SizeMismatch");
+    assert_eq!(json[0]["todo"], "Repack");
+    repo.prune(&prune_opts, plan)?;
+
+    let packs_after = repo.list::<PackId>()?.count();
+    assert!(packs_after < packs_before);
+
+    let check_opts = CheckOptions::default().read_data(true);
+    _ = repo.check(check_opts)?.into_result()?;
+
+    // the consolidated packs are not repacked again
+    let plan = repo.prune_plan(&prune_opts)?;
+    assert_eq!(plan.stats.packs.repack, 0);
+    assert_eq!(plan.pack_decisions().len(), packs_after);
+    assert!(plan
+        .pack_decisions()
+        .iter()
+        .all(|decision| decision.todo == PackToDo::Keep));
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::Result;
+use rstest::rstest;
+
+use rustic_core::{repofile::SnapshotFile, BackupOptions, SnapshotOptions};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_recover_orphaned_trees(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    // all trees are referenced by the snapshot
+    let repo = repo.to_indexed_ids()?;
+    assert!(repo.recover_orphaned_trees()?.is_empty());
+
+    // simulate an aborted backup by removing the snapshot
+    repo.delete_snapshots(&[snapshot.id])?;
+    assert_eq!(repo.recover_orphaned_trees()?, vec![snapshot.tree]);
+
+    let snap_opts = SnapshotOptions::default().host("recovered".to_string());
+    let recovered = repo.create_snapshot_from_tree(snapshot.tree, &snap_opts, true)?;
+    assert_eq!(recovered.tree, snapshot.tree);
+    assert!(repo.get_all_snapshots()?.is_empty());
+
+    let recovered = repo.create_snapshot_from_tree(snapshot.tree, &snap_opts, false)?;
+    assert_eq!(recovered.hostname, "recovered");
+    let ids: Vec<_> = repo
+        .get_all_snapshots()?
+        .iter()
+        .map(|snap| snap.id)
+        .collect();
+    assert_eq!(ids, vec![recovered.id]);
+    assert!(repo.recover_orphaned_trees()?.is_empty());
+
+    Ok(())
+}
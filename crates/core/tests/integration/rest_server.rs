@@ -0,0 +1,151 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    thread,
+};
+
+use anyhow::Result;
+use bytes::Bytes;
+use rstest::rstest;
+
+use rustic_backend::RestBackend;
+use rustic_core::{
+    repofile::SnapshotFile,
+    rest_server::{RestServer, RestServerOptions},
+    BackupOptions, CheckOptions, FileType, Id, ReadBackend, Repository, RepositoryBackends,
+    RepositoryOptions, WriteBackend,
+};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_rest_server_serves_repository(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let server = RestServer::new(&repo, "127.0.0.1:0", RestServerOptions::default())?;
+    let url = format!("http://{}/", server.local_addr()?);
+    _ = thread::spawn(move || {
+        server.serve();
+    });
+
+    // access the repository using the REST backend
+    let be = Arc::new(RestBackend::new(
+        url,
+        [("retry".to_string(), "false".to_string())],
+    )?);
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let remote = Repository::new(&RepositoryOptions::default().password("test"), &backends)?
+        .open()?
+        .to_indexed()?;
+
+    let snapshots = remote.get_all_snapshots()?;
+    assert_eq!(snapshots.len(), 1);
+    assert_eq!(snapshots[0].id, snapshot.id);
+    assert!(remote
+        .check_snapshot(&snapshots[0], CheckOptions::default().read_data(true))?
+        .is_ok());
+
+    // modifying requests are rejected
+    assert!(be
+        .write_bytes(FileType::Lock, &Id::random(), false, Bytes::from("lock"))
+        .is_err());
+    assert!(be.remove(FileType::Snapshot, &snapshot.id, false).is_err());
+    assert_eq!(remote.get_all_snapshots()?.len(), 1);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_rest_server_allows_writes_up_to_body_size(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    let repo = set_up_repo?;
+    let opts = RestServerOptions::default()
+        .allow_writes(true)
+        .max_body_size(1024usize);
+    let server = RestServer::new(&repo, "127.0.0.1:0", opts)?;
+    let url = format!("http://{}/", server.local_addr()?);
+    _ = thread::spawn(move || {
+        server.serve();
+    });
+
+    let be = RestBackend::new(url, [("retry".to_string(), "false".to_string())])?;
+    let id = Id::random();
+    be.write_bytes(FileType::Lock, &id, false, Bytes::from("lock"))?;
+    assert_eq!(be.read_full(FileType::Lock, &id)?, Bytes::from("lock"));
+
+    // too large bodies are rejected
+    let large = Id::random();
+    assert!(be
+        .write_bytes(FileType::Lock, &large, false, Bytes::from(vec![0; 2048]))
+        .is_err());
+    assert_eq!(be.list(FileType::Lock)?, vec![id]);
+
+    // reading a missing file fails
+    assert!(be.read_full(FileType::Lock, &large).is_err());
+
+    be.remove(FileType::Lock, &id, false)?;
+    assert!(be.list(FileType::Lock)?.is_empty());
+
+    Ok(())
+}
+
+/// Send a raw request to the server and return the status line of the response
+fn raw_request(addr: std::net::SocketAddr, request: &[u8]) -> Result<String> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.write_all(request)?;
+    let mut response = Vec::new();
+    _ = stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    Ok(response.lines().next().unwrap_or_default().to_string())
+}
+
+#[rstest]
+fn test_rest_server_rejects_invalid_requests(set_up_repo: Result<RepoOpen>) -> Result<()> {
+    let repo = set_up_repo?;
+    let opts = RestServerOptions::default().allow_writes(true);
+    let server = RestServer::new(&repo, "127.0.0.1:0", opts)?;
+    let addr = server.local_addr()?;
+    _ = thread::spawn(move || {
+        server.serve();
+    });
+
+    let be = RestBackend::new(
+        format!("http://{addr}/"),
+        [("retry".to_string(), "false".to_string())],
+    )?;
+    let id = Id::random();
+    be.write_bytes(FileType::Lock, &id, false, Bytes::from("lock"))?;
+
+    // ranges which don't fit into the backend's read_partial are not satisfiable
+    let request = format!(
+        "GET /locks/{id} HTTP/1.1\r\nRange: bytes=0-4294967295\r\nConnection: close\r\n\r\n"
+    );
+    assert!(raw_request(addr, request.as_bytes())?.contains(" 416 "));
+    let request = format!(
+        "GET /locks/{id} HTTP/1.1\r\nRange: bytes=0-18446744073709551615\r\nConnection: close\r\n\r\n"
+    );
+    assert!(raw_request(addr, request.as_bytes())?.contains(" 416 "));
+
+    // overlong header lines are rejected without reading them completely
+    let request = format!(
+        "GET /locks/{id} HTTP/1.1\r\nX-Long: {}\r\n\r\n",
+        "a".repeat(100_000)
+    );
+    // the server closes the connection, so the client may also see a reset connection
+    assert!(raw_request(addr, request.as_bytes()).map_or(true, |status| status.is_empty()));
+
+    // the server still serves valid requests
+    assert_eq!(be.read_full(FileType::Lock, &id)?, Bytes::from("lock"));
+
+    Ok(())
+}
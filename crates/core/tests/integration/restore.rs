@@ -1 +1,641 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    ffi::OsStr,
+    fs,
+    hash::{Hash, Hasher},
+    io::Empty,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use anyhow::Result;
+use bytes::Bytes;
+use bytesize::ByteSize;
+use rstest::rstest;
+
+use rustic_core::{
+    repofile::{Metadata, Node, NodeType, SnapshotFile},
+    BackupOptions, CaseConflictAction, ConfigOptions, ErrorKind, FileType, KeyOptions, LsOptions,
+    MetadataOptions, Repository, RepositoryBackends, RepositoryOptions, RestoreDestination,
+    RestoreExtraOptions, RestoreOptions, RusticError, RusticResult,
+};
+use rustic_testing::backend::instrumented_backend::InstrumentedBackend;
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+/// A destination which keeps the restored files in memory
+#[derive(Debug, Default)]
+struct MemoryDestination {
+    files: Mutex<BTreeMap<PathBuf, Vec<u8>>>,
+    case_insensitive: bool,
+}
+
+impl RestoreDestination for MemoryDestination {
+    type File = Empty;
+
+    fn is_case_sensitive(&self, _dry_run: bool) -> RusticResult<bool> {
+        Ok(!self.case_insensitive)
+    }
+
+    fn remove_dir(&self, _path: &Path) -> RusticResult<()> {
+        Ok(())
+    }
+
+    fn remove_file(&self, _path: &Path) -> RusticResult<()> {
+        Ok(())
+    }
+
+    fn create_dir(&self, _item: &Path) -> RusticResult<()> {
+        Ok(())
+    }
+
+    fn set_length(&self, item: &Path, size: u64) -> RusticResult<()> {
+        self.files
+            .lock()
+            .unwrap()
+            .entry(item.to_path_buf())
+            .or_default()
+            .resize(usize::try_from(size).unwrap(), 0);
+        Ok(())
+    }
+
+    fn write_at(&self, item: &Path, offset: u64, data: &[u8]) -> RusticResult<()> {
+        let mut files = self.files.lock().unwrap();
+        let file = files.entry(item.to_path_buf()).or_default();
+        let start = usize::try_from(offset).unwrap();
+        let end = start + data.len();
+        if file.len() < end {
+            file.resize(end, 0);
+        }
+        file[start..end].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn read_at(&self, _item: &Path, _offset: u64, _length: u64) -> RusticResult<Bytes> {
+        Err(RusticError::new(
+            ErrorKind::Unsupported,
+            "Reading is not supported.",
+        ))
+    }
+
+    fn set_metadata(&self, _item: &Path, _node: &Node, _opts: MetadataOptions) -> RusticResult<()> {
+        Ok(())
+    }
+}
+
+#[rstest]
+fn test_restore_to_custom_destination(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let dest = MemoryDestination::default();
+    let opts = RestoreOptions::default();
+    let restore_infos = repo.prepare_restore(&opts, ls.clone(), &dest, false)?;
+    repo.restore(restore_infos, &opts, ls.clone(), &dest)?;
+
+    let files = dest.files.into_inner().unwrap();
+    let mut restored = 0;
+    for item in ls {
+        let (path, node) = item?;
+        if !node.is_file() {
+            continue;
+        }
+        let source_path = source.0.path().join(path.strip_prefix("test")?);
+        let expected = fs::read(source_path)?;
+        let actual = files.get(&path).map_or(&[][..], Vec::as_slice);
+        assert_eq!(actual, expected, "content of {path:?} differs");
+        restored += 1;
+    }
+    assert!(restored > 0);
+    assert_eq!(files.len(), restored);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_restore_with_prefixes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("/home/test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let restore = |extra: &RestoreExtraOptions| -> RusticResult<BTreeMap<PathBuf, Vec<u8>>> {
+        let ls = repo.ls(&node, &LsOptions::default())?;
+        let dest = MemoryDestination::default();
+        let opts = RestoreOptions::default();
+        let restore_infos =
+            repo.prepare_restore_with_extra(&opts, extra, ls.clone(), &dest, false)?;
+        repo.restore(restore_infos, &opts, ls, &dest)?;
+        Ok(dest.files.into_inner().unwrap())
+    };
+    let all = restore(&RestoreExtraOptions::default())?;
+    assert!(!all.is_empty());
+    let with_prefix = |prefix: &str| -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+        all.iter()
+            .map(|(path, content)| -> Result<_> {
+                Ok((
+                    Path::new(prefix).join(path.strip_prefix("home/test")?),
+                    content.clone(),
+                ))
+            })
+            .collect()
+    };
+
+    // strip
+    let files = restore(&RestoreExtraOptions::default().strip_prefix(PathBuf::from("/home")))?;
+    assert_eq!(files, with_prefix("test")?);
+
+    // add
+    let files = restore(&RestoreExtraOptions::default().add_prefix(PathBuf::from("restored")))?;
+    assert_eq!(files, with_prefix("restored/home/test")?);
+
+    // strip and add
+    let extra = RestoreExtraOptions::default()
+        .strip_prefix(PathBuf::from("/home/test"))
+        .add_prefix(PathBuf::from("restored"));
+    let files = restore(&extra)?;
+    assert_eq!(files, with_prefix("restored")?);
+
+    // entries not within the prefix are skipped
+    let files = restore(&RestoreExtraOptions::default().strip_prefix(PathBuf::from("/other")))?;
+    assert!(files.is_empty());
+
+    Ok(())
+}
+
+#[rstest]
+fn test_restore_with_path_mapper(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    // hide the names by hashing each path component
+    let mapper = |path: &Path| -> PathBuf {
+        path.components()
+            .map(|component| {
+                let mut hasher = DefaultHasher::new();
+                component.hash(&mut hasher);
+                format!("{:016x}", hasher.finish())
+            })
+            .collect()
+    };
+
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let dest = MemoryDestination::default();
+    let opts = RestoreOptions::default();
+    let extra = RestoreExtraOptions::default().path_mapper(mapper);
+    let restore_infos = repo.prepare_restore_with_extra(&opts, &extra, ls.clone(), &dest, false)?;
+    repo.restore(restore_infos, &opts, ls.clone(), &dest)?;
+
+    let files = dest.files.into_inner().unwrap();
+    let mut restored = 0;
+    for item in ls {
+        let (path, node) = item?;
+        if !node.is_file() {
+            continue;
+        }
+        let source_path = source.0.path().join(path.strip_prefix("test")?);
+        let expected = fs::read(source_path)?;
+        let actual = files.get(&mapper(&path)).map_or(&[][..], Vec::as_slice);
+        assert_eq!(actual, expected, "content of {path:?} differs");
+        restored += 1;
+    }
+    assert!(restored > 0);
+    assert_eq!(files.len(), restored);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_restore_case_conflicts(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let dir = source.0.path().join("case");
+    fs::create_dir(&dir)?;
+    fs::write(dir.join("Foo"), "upper")?;
+    fs::write(dir.join("foo"), "lower")?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let restore =
+        |action: Option<CaseConflictAction>| -> RusticResult<(u64, BTreeMap<PathBuf, Vec<u8>>)> {
+            let ls = repo.ls(&node, &LsOptions::default())?;
+            let dest = MemoryDestination {
+                case_insensitive: true,
+                ..Default::default()
+            };
+            let opts = RestoreOptions::default().on_case_conflict(action);
+            let restore_infos = repo.prepare_restore(&opts, ls.clone(), &dest, false)?;
+            let conflicts = restore_infos.stats.case_conflicts;
+            repo.restore(restore_infos, &opts, ls, &dest)?;
+            Ok((conflicts, dest.files.into_inner().unwrap()))
+        };
+
+    let (conflicts, files) = restore(Some(CaseConflictAction::Rename))?;
+    assert_eq!(conflicts, 1);
+    assert_eq!(files[Path::new("test/case/Foo")], b"upper");
+    assert_eq!(files[Path::new("test/case/foo~1")], b"lower");
+    assert!(!files.contains_key(Path::new("test/case/foo")));
+
+    let (conflicts, files) = restore(Some(CaseConflictAction::Skip))?;
+    assert_eq!(conflicts, 1);
+    assert_eq!(files[Path::new("test/case/Foo")], b"upper");
+    assert!(!files.contains_key(Path::new("test/case/foo")));
+
+    assert!(restore(Some(CaseConflictAction::Error)).is_err());
+
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[rstest]
+fn test_restore_extended_attributes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    use rustic_core::LocalDestination;
+
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let file = source.0.path().join("xattr.txt");
+    fs::write(&file, "xattr")?;
+    if xattr::set(&file, "user.rustic", b"value").is_err() {
+        // the filesystem doesn't support extended attributes
+        return Ok(());
+    }
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let xattr_node = repo.node_from_path(snapshot.tree, Path::new("test/xattr.txt"))?;
+    assert!(xattr_node.meta.extended_attributes.iter().any(
+        |attr| attr.name == "user.rustic" && attr.value.as_deref() == Some(b"value".as_slice())
+    ));
+
+    let restore_dir = tempfile::tempdir()?;
+    let dest = LocalDestination::new(&format!("{}/", restore_dir.path().display()), true, false)?;
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let opts = RestoreOptions::default();
+    let restore_infos = repo.prepare_restore(&opts, ls.clone(), &dest, false)?;
+    repo.restore(restore_infos, &opts, ls, &dest)?;
+
+    let restored = restore_dir.path().join("test/xattr.txt");
+    assert_eq!(fs::read(&restored)?, b"xattr");
+    assert_eq!(
+        xattr::get(&restored, "user.rustic")?.as_deref(),
+        Some(b"value".as_slice())
+    );
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_restore_dereferenced_symlinks(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    use std::os::unix::fs::symlink;
+
+    use rustic_core::LocalDestination;
+
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let dir = source.0.path().join("links");
+    fs::create_dir(&dir)?;
+    fs::write(dir.join("target.txt"), "target")?;
+    symlink("target.txt", dir.join("in-tree"))?;
+    symlink("../../outside.txt", dir.join("out-of-tree"))?;
+    symlink("missing.txt", dir.join("dangling"))?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let restore = |opts: &RestoreOptions| -> Result<tempfile::TempDir> {
+        let restore_dir = tempfile::tempdir()?;
+        let dest =
+            LocalDestination::new(&format!("{}/", restore_dir.path().display()), true, false)?;
+        let ls = repo.ls(&node, &LsOptions::default())?;
+        let restore_infos = repo.prepare_restore(opts, ls.clone(), &dest, false)?;
+        repo.restore(restore_infos, opts, ls, &dest)?;
+        Ok(restore_dir)
+    };
+
+    // symlinks within the tree are materialized, others are recreated
+    let restore_dir = restore(&RestoreOptions::default().dereference_symlinks(true))?;
+    let links = restore_dir.path().join("test/links");
+    let in_tree = links.join("in-tree");
+    assert!(fs::symlink_metadata(&in_tree)?.is_file());
+    assert_eq!(fs::read(&in_tree)?, b"target");
+    assert!(fs::symlink_metadata(links.join("out-of-tree"))?.is_symlink());
+    assert!(fs::symlink_metadata(links.join("dangling"))?.is_symlink());
+
+    // symlinks which can't be dereferenced can be skipped
+    let restore_dir = restore(
+        &RestoreOptions::default()
+            .dereference_symlinks(true)
+            .skip_external_symlinks(true),
+    )?;
+    let links = restore_dir.path().join("test/links");
+    assert!(fs::symlink_metadata(links.join("in-tree"))?.is_file());
+    assert!(fs::symlink_metadata(links.join("out-of-tree")).is_err());
+    assert!(fs::symlink_metadata(links.join("dangling")).is_err());
+
+    // without dereferencing, all symlinks are recreated
+    let restore_dir = restore(&RestoreOptions::default())?;
+    assert!(fs::symlink_metadata(restore_dir.path().join("test/links/in-tree"))?.is_symlink());
+
+    Ok(())
+}
+
+#[cfg(unix)]
+#[rstest]
+fn test_restore_preview_does_not_touch_destination(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    use rustic_core::LocalDestination;
+
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    use std::{fs::File, os::unix::fs::PermissionsExt, time::SystemTime};
+
+    let restore_dir = tempfile::tempdir()?;
+    let additional = restore_dir.path().join("additional");
+    fs::write(&additional, "additional")?;
+    let dest = LocalDestination::new(&format!("{}/", restore_dir.path().display()), true, false)?;
+
+    // make the destination read-only and set an old modification time which changes on any write to the dir,
+    // even if the permissions are not enforced, e.g. when running as root
+    let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    File::open(restore_dir.path())?.set_modified(mtime)?;
+    fs::set_permissions(restore_dir.path(), fs::Permissions::from_mode(0o555))?;
+
+    let opts = RestoreOptions::default().delete(true);
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let preview = repo.restore_preview(&opts, ls.clone(), &dest);
+    fs::set_permissions(restore_dir.path(), fs::Permissions::from_mode(0o755))?;
+    let preview = preview?;
+
+    // the destination is unchanged
+    assert_eq!(fs::metadata(restore_dir.path())?.modified()?, mtime);
+    assert!(additional.exists());
+    assert_eq!(fs::read_dir(restore_dir.path())?.count(), 1);
+
+    // the preview matches the plan of the real restore
+    let plan = repo.prepare_restore(&opts, ls, &dest, true)?;
+    assert!(preview.restore_size > 0);
+    assert_eq!(preview.restore_size, plan.restore_size);
+    assert_eq!(preview.matched_size, plan.matched_size);
+    assert_eq!(preview.stats.files.restore, plan.stats.files.restore);
+    assert_eq!(preview.stats.files.additional, 1);
+    assert_eq!(preview.packs, plan.to_packs());
+    assert!(!preview.packs.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_local_destination_probes_case_sensitivity() -> Result<()> {
+    use rustic_core::LocalDestination;
+
+    let dir = tempfile::tempdir()?;
+    // a destination which doesn't exist yet is probed in its nearest existing parent dir
+    let dest = LocalDestination::new(&format!("{}/new/sub/", dir.path().display()), false, false)?;
+
+    // an empty dir can't be probed without writing to it
+    assert!(dest.is_case_sensitive(true).is_err());
+    let case_sensitive = dest.is_case_sensitive(false)?;
+    // the probe file has been removed
+    assert_eq!(fs::read_dir(dir.path())?.count(), 0);
+
+    // existing entries are used instead of writing a probe file
+    fs::write(dir.path().join("file"), "file")?;
+    assert_eq!(dest.is_case_sensitive(true)?, case_sensitive);
+    assert_eq!(fs::read_dir(dir.path())?.count(), 1);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_verify_restored_detects_changed_files(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    use rustic_core::LocalDestination;
+
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let restore_dir = tempfile::tempdir()?;
+    let dest = LocalDestination::new(&format!("{}/", restore_dir.path().display()), true, false)?;
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let opts = RestoreOptions::default();
+    let restore_infos = repo.prepare_restore(&opts, ls.clone(), &dest, false)?;
+    repo.restore(restore_infos, &opts, ls.clone(), &dest)?;
+
+    // a fresh restore is verified completely
+    assert!(repo.verify_restored(&snapshot, &dest)?.is_empty());
+
+    let files: Vec<_> = ls
+        .filter_map(Result::ok)
+        .filter(|(_, node)| node.is_file() && node.meta.size > 0)
+        .map(|(path, _)| path)
+        .take(2)
+        .collect();
+    assert_eq!(files.len(), 2);
+
+    // modify the contents of one file in-place, keeping its size
+    let changed = restore_dir.path().join(&files[0]);
+    let mut content = fs::read(&changed)?;
+    content[0] = content[0].wrapping_add(1);
+    fs::write(&changed, content)?;
+    // remove another file
+    fs::remove_file(restore_dir.path().join(&files[1]))?;
+
+    let mut expected = files;
+    expected.sort();
+    assert_eq!(repo.verify_restored(&snapshot, &dest)?, expected);
+
+    Ok(())
+}
+
+#[rstest]
+fn test_restore_metadata_only(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    use rustic_core::LocalDestination;
+
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let dir = source.0.path().join("meta");
+    fs::create_dir(&dir)?;
+    fs::write(dir.join("file"), "content")?;
+    fs::set_permissions(dir.join("file"), fs::Permissions::from_mode(0o640))?;
+    fs::write(dir.join("removed"), "removed")?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let restore_dir = tempfile::tempdir()?;
+    let dest = LocalDestination::new(&format!("{}/", restore_dir.path().display()), true, false)?;
+    let restore = |opts: &RestoreOptions| -> Result<()> {
+        let ls = repo.ls(&node, &LsOptions::default())?;
+        let restore_infos = repo.prepare_restore(opts, ls.clone(), &dest, false)?;
+        repo.restore(restore_infos, opts, ls, &dest)?;
+        Ok(())
+    };
+    restore(&RestoreOptions::default())?;
+
+    // botch the restored files
+    let file = restore_dir.path().join("test/meta/file");
+    let removed = restore_dir.path().join("test/meta/removed");
+    fs::write(&file, "changed")?;
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o777))?;
+    fs::remove_file(&removed)?;
+
+    let opts = RestoreOptions::default().metadata_only(true);
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let restore_infos = repo.prepare_restore(&opts, ls, &dest, false)?;
+    assert_eq!(restore_infos.restore_size, 0);
+    assert_eq!(restore_infos.stats.files.restore, 0);
+    restore(&opts)?;
+
+    // the permissions are restored, but the contents are untouched and missing files are not created
+    assert_eq!(fs::metadata(&file)?.permissions().mode() & 0o777, 0o640);
+    assert_eq!(fs::read(&file)?, b"changed");
+    assert!(!removed.exists());
+
+    Ok(())
+}
+
+#[rstest]
+#[case(1)]
+#[case(2)]
+fn test_restore_uses_read_threads(
+    tar_gz_testdata: Result<TestSource>,
+    #[case] threads: usize,
+) -> Result<()> {
+    // Fixtures
+    let source = tar_gz_testdata?;
+    // give other reader threads the chance to run concurrently
+    let be = Arc::new(InstrumentedBackend::with_read_delay(Duration::from_millis(
+        5,
+    )));
+    let backends = RepositoryBackends::new(be.clone(), None);
+    let options = RepositoryOptions::default().password("test");
+    // use tiny data packs to get many pack reads
+    let config_opts = ConfigOptions::default()
+        .set_datapack_size(ByteSize::b(1))
+        .set_datapack_growfactor(0_u32);
+    let repo = Repository::new(&options, &backends)?
+        .init(&KeyOptions::default(), &config_opts)?
+        .to_indexed_ids()?;
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let mut node = Node::new_node(OsStr::new(""), NodeType::Dir, Metadata::default());
+    node.subtree = Some(snapshot.tree);
+    let repo = repo.to_indexed()?;
+
+    let ls = repo.ls(&node, &LsOptions::default())?;
+    let dest = MemoryDestination::default();
+    let opts = RestoreOptions::default().read_threads(threads);
+    let restore_infos = repo.prepare_restore(&opts, ls.clone(), &dest, false)?;
+    _ = be.take_max_concurrent_reads();
+    _ = be.take_reads(FileType::Pack);
+    repo.restore(restore_infos, &opts, ls, &dest)?;
+
+    assert!(be.take_reads(FileType::Pack) > threads);
+    let max = be.take_max_concurrent_reads();
+    assert!((1..=threads).contains(&max), "{max} concurrent reads");
+
+    // the contents have been restored completely
+    let files = dest.files.into_inner().unwrap();
+    assert!(!files.is_empty());
+    for (path, content) in files {
+        let expected = fs::read(source.0.path().join(path.strip_prefix("test")?))?;
+        assert_eq!(content, expected, "content of {path:?} differs");
+    }
+
+    Ok(())
+}
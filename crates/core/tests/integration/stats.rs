@@ -0,0 +1,85 @@
+use std::{path::PathBuf, str::FromStr};
+
+use anyhow::Result;
+use pretty_assertions::assert_eq;
+use rstest::rstest;
+
+use rustic_core::{repofile::SnapshotFile, BackupOptions, StatsMode};
+
+use super::{set_up_repo, tar_gz_testdata, RepoOpen, TestSource};
+
+#[rstest]
+fn test_stats_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    // back up the same data twice to different paths
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let snapshot = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("other")?);
+    _ = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let repo = repo.to_indexed()?;
+    let summary = snapshot.summary.unwrap();
+
+    let stats = repo.stats(StatsMode::RestoreSize)?;
+    assert_eq!(stats.snapshots_count, 2);
+    assert_eq!(stats.restore_size, 2 * summary.total_bytes_processed);
+    assert_eq!(stats.total_size, stats.restore_size);
+    assert_eq!(stats.total_file_count, 2 * summary.total_files_processed);
+    // both snapshots are in different groups
+    assert_eq!(stats.latest_restore_size, stats.restore_size);
+    assert!(stats.dedup_ratio.unwrap() > 1.0);
+
+    // identical files are only counted once
+    let stats = repo.stats(StatsMode::FilesByContents)?;
+    assert!(stats.total_size <= summary.total_bytes_processed);
+    assert!(stats.total_file_count <= summary.total_files_processed);
+
+    // all blobs are referenced
+    let stats = repo.stats(StatsMode::RawData)?;
+    assert_eq!(stats.total_size, stats.stored_size);
+    assert_eq!(
+        stats.total_blob_count,
+        Some(stats.index.blobs.iter().map(|blob| blob.count).sum())
+    );
+
+    Ok(())
+}
+
+#[rstest]
+fn test_tree_restore_size_passes(
+    tar_gz_testdata: Result<TestSource>,
+    set_up_repo: Result<RepoOpen>,
+) -> Result<()> {
+    // Fixtures
+    let (source, repo) = (tar_gz_testdata?, set_up_repo?.to_indexed_ids()?);
+    let paths = &source.path_list();
+
+    let opts = BackupOptions::default().as_path(PathBuf::from_str("test")?);
+    let first = repo.backup(&opts, paths, SnapshotFile::default())?;
+    let second = repo.backup(&opts, paths, SnapshotFile::default())?;
+
+    let repo = repo.to_indexed()?;
+    let summary = first.summary.clone().unwrap();
+
+    let size = repo.tree_restore_size(first.tree, false)?;
+    assert_eq!(size.total, summary.total_bytes_processed);
+    assert_eq!(size.files, summary.total_files_processed);
+    assert_eq!(size.unique, None);
+
+    // identical contents are only counted once
+    let size = repo.tree_restore_size(first.tree, true)?;
+    let unique = size.unique.unwrap();
+    assert!(unique > 0);
+    assert!(unique <= size.total);
+
+    let sizes = repo.snapshots_restore_size(&[first, second], true)?;
+    assert_eq!(sizes, vec![size, size]);
+
+    Ok(())
+}
@@ -44,6 +44,12 @@ pub mod in_memory_backend {
                 .collect())
         }
 
+        fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+            Ok(self.0.read().unwrap()[tpe]
+                .get(id)
+                .map(|byte| u32::try_from(byte.len()).expect("byte length is too large")))
+        }
+
         fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
             Ok(self.0.read().unwrap()[tpe][id].clone())
         }
@@ -93,3 +99,129 @@ pub mod in_memory_backend {
         }
     }
 }
+
+/// In-memory backend recording its reads to be used for testing
+pub mod instrumented_backend {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use enum_map::EnumMap;
+
+    use rustic_core::{FileType, Id, ReadBackend, RusticResult, WriteBackend};
+
+    use super::in_memory_backend::InMemoryBackend;
+
+    #[derive(Debug, Default)]
+    /// In-memory backend which counts the reads of each file type
+    /// and records the maximum number of concurrent non-cacheable reads, i.e. reads of data blobs
+    pub struct InstrumentedBackend {
+        /// The backend to use
+        be: InMemoryBackend,
+        /// The number of full and partial reads of each file type
+        reads: EnumMap<FileType, AtomicUsize>,
+        /// The delay of each non-cacheable read which gives other threads the chance to read concurrently
+        read_delay: Option<Duration>,
+        /// The number of currently running non-cacheable reads
+        current_reads: AtomicUsize,
+        /// The maximum number of concurrent non-cacheable reads
+        max_concurrent_reads: AtomicUsize,
+    }
+
+    impl InstrumentedBackend {
+        /// Create a new (empty) `InstrumentedBackend`
+        #[must_use]
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Create a new (empty) `InstrumentedBackend` which delays each non-cacheable read
+        ///
+        /// # Arguments
+        ///
+        /// * `delay` - The delay of each read
+        #[must_use]
+        pub fn with_read_delay(delay: Duration) -> Self {
+            Self {
+                read_delay: Some(delay),
+                ..Self::default()
+            }
+        }
+
+        /// Returns the number of reads of the given file type and resets the counter
+        pub fn take_reads(&self, tpe: FileType) -> usize {
+            self.reads[tpe].swap(0, Ordering::SeqCst)
+        }
+
+        /// Returns the maximum number of concurrent non-cacheable reads and resets it
+        pub fn take_max_concurrent_reads(&self) -> usize {
+            self.max_concurrent_reads.swap(0, Ordering::SeqCst)
+        }
+    }
+
+    impl ReadBackend for InstrumentedBackend {
+        fn location(&self) -> String {
+            self.be.location()
+        }
+
+        fn list_with_size(&self, tpe: FileType) -> RusticResult<Vec<(Id, u32)>> {
+            self.be.list_with_size(tpe)
+        }
+
+        fn file_size(&self, tpe: FileType, id: &Id) -> RusticResult<Option<u32>> {
+            self.be.file_size(tpe, id)
+        }
+
+        fn read_full(&self, tpe: FileType, id: &Id) -> RusticResult<Bytes> {
+            _ = self.reads[tpe].fetch_add(1, Ordering::SeqCst);
+            self.be.read_full(tpe, id)
+        }
+
+        fn read_partial(
+            &self,
+            tpe: FileType,
+            id: &Id,
+            cacheable: bool,
+            offset: u32,
+            length: u32,
+        ) -> RusticResult<Bytes> {
+            _ = self.reads[tpe].fetch_add(1, Ordering::SeqCst);
+            if cacheable {
+                return self.be.read_partial(tpe, id, cacheable, offset, length);
+            }
+            let current = self.current_reads.fetch_add(1, Ordering::SeqCst) + 1;
+            _ = self
+                .max_concurrent_reads
+                .fetch_max(current, Ordering::SeqCst);
+            if let Some(delay) = self.read_delay {
+                thread::sleep(delay);
+            }
+            let result = self.be.read_partial(tpe, id, cacheable, offset, length);
+            _ = self.current_reads.fetch_sub(1, Ordering::SeqCst);
+            result
+        }
+    }
+
+    impl WriteBackend for InstrumentedBackend {
+        fn create(&self) -> RusticResult<()> {
+            self.be.create()
+        }
+
+        fn write_bytes(
+            &self,
+            tpe: FileType,
+            id: &Id,
+            cacheable: bool,
+            buf: Bytes,
+        ) -> RusticResult<()> {
+            self.be.write_bytes(tpe, id, cacheable, buf)
+        }
+
+        fn remove(&self, tpe: FileType, id: &Id, cacheable: bool) -> RusticResult<()> {
+            self.be.remove(tpe, id, cacheable)
+        }
+    }
+}
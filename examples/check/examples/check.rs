@@ -19,6 +19,6 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Check repository with standard options but omitting cache checks
     let opts = CheckOptions::default().trust_cache(true);
-    repo.check(opts)?;
+    _ = repo.check(opts)?.into_result()?;
     Ok(())
 }